@@ -0,0 +1,13 @@
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // The gRPC service is generated from its .proto; the vendored protoc
+    // spares builders a system install.
+    println!("cargo:rerun-if-changed=proto/translator.proto");
+    #[cfg(feature = "grpc")]
+    {
+        std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path()?);
+        tonic_build::configure()
+            .build_client(false)
+            .compile_protos(&["proto/translator.proto"], &["proto"])?;
+    }
+    Ok(())
+}
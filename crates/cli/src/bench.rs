@@ -0,0 +1,124 @@
+//! `bench` subcommand: runs the backends configured for a session against a
+//! local file and prints per-stage latency percentiles.
+
+use clap::Args;
+use std::path::PathBuf;
+#[cfg(feature = "whisper-rs")]
+use twitch_translator_core::asr::WhisperAsrBackend;
+#[cfg(feature = "whisper-rs")]
+use twitch_translator_core::bench::Bench;
+use twitch_translator_core::bench::BenchReport;
+use twitch_translator_core::config::AppConfig;
+#[cfg(feature = "whisper-rs")]
+use twitch_translator_core::config::HttpService;
+#[cfg(feature = "whisper-rs")]
+use twitch_translator_core::decode::FfmpegAudioDecoder;
+#[cfg(feature = "whisper-rs")]
+use twitch_translator_core::translate::DeepLTranslator;
+#[cfg(feature = "whisper-rs")]
+use twitch_translator_core::tts::{ElevenLabsTtsClient, PiperTtsClient, Respellings, TtsClient};
+
+#[derive(Args, Debug)]
+pub struct BenchArgs {
+    /// Audio or video file to benchmark (anything ffmpeg can decode)
+    #[arg(long)]
+    input: PathBuf,
+
+    /// Whisper model: a catalog name such as `small` (looked up in ./models) or a path;
+    /// the same as --whisper-model
+    #[arg(long, conflicts_with = "whisper_model")]
+    pub(crate) model: Option<String>,
+
+    #[arg(long, default_value_t = 5)]
+    iterations: usize,
+
+    /// Skip translation even if a DeepL key is configured
+    #[arg(long)]
+    no_translate: bool,
+
+    /// Skip speech synthesis
+    #[arg(long)]
+    no_tts: bool,
+
+    /// Print the report as JSON
+    #[arg(long)]
+    json: bool,
+}
+
+pub async fn run(args: BenchArgs, cfg: AppConfig) -> anyhow::Result<()> {
+    tracing::info!(
+        input = %args.input.display(),
+        model = %cfg.asr.model_path,
+        iterations = args.iterations,
+        "starting benchmark"
+    );
+
+    let report = run_backends(&args, &cfg).await?;
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        println!("model: {}", cfg.asr.model_path);
+        println!("{report}");
+    }
+    Ok(())
+}
+
+#[cfg(feature = "whisper-rs")]
+async fn run_backends(args: &BenchArgs, cfg: &AppConfig) -> anyhow::Result<BenchReport> {
+    let asr = WhisperAsrBackend::from_config(&cfg.asr)?;
+    let translate = match (&cfg.api_keys.deepl, args.no_translate) {
+        (Some(key), false) => Some(
+            DeepLTranslator::new(key.expose().to_string())
+                .with_transport(crate::transport(cfg, HttpService::DeepL)?),
+        ),
+        (None, false) => {
+            tracing::warn!("no DeepL key; skipping translation");
+            None
+        }
+        (_, true) => None,
+    };
+
+    if args.no_tts {
+        return bench_with(args, cfg, asr, translate, None::<PiperTtsClient>).await;
+    }
+    match &cfg.api_keys.elevenlabs {
+        Some(key) => {
+            let tts = ElevenLabsTtsClient::new(key.expose().to_string())
+                .with_transport(crate::transport(cfg, HttpService::ElevenLabs)?)
+                .with_pronunciation_dictionaries(&cfg.pronunciation.elevenlabs_dictionaries);
+            bench_with(args, cfg, asr, translate, Some(tts)).await
+        }
+        None => {
+            cfg.piper.validate()?;
+            let tts = PiperTtsClient::from_config(&cfg.piper)
+                .with_respellings(Respellings::new(&cfg.pronunciation.respellings));
+            bench_with(args, cfg, asr, translate, Some(tts)).await
+        }
+    }
+}
+
+#[cfg(feature = "whisper-rs")]
+async fn bench_with<Ts: TtsClient>(
+    args: &BenchArgs,
+    cfg: &AppConfig,
+    asr: WhisperAsrBackend,
+    translate: Option<DeepLTranslator>,
+    tts: Option<Ts>,
+) -> anyhow::Result<BenchReport> {
+    let bench = Bench {
+        decode: FfmpegAudioDecoder::default(),
+        asr,
+        translate,
+        tts,
+        target_lang: cfg.target_lang.clone(),
+        iterations: args.iterations,
+    };
+    Ok(bench.run_file(&args.input).await?)
+}
+
+#[cfg(not(feature = "whisper-rs"))]
+async fn run_backends(_args: &BenchArgs, _cfg: &AppConfig) -> anyhow::Result<BenchReport> {
+    Err(anyhow::anyhow!(
+        "Whisper ASR feature is not enabled. Please install libclang and rebuild with --features whisper-rs"
+    ))
+}
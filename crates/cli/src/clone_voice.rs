@@ -0,0 +1,117 @@
+//! `clone-voice` subcommand: clones the streamer's voice with ElevenLabs from
+//! a recording, or from the microphone, and saves it in the channel's profile
+//! so sessions on the channel dub with it.
+
+use anyhow::Context;
+use clap::Args;
+use std::path::PathBuf;
+use std::time::Duration;
+use twitch_translator_core::config::{AppConfig, HttpService, ENV_ELEVENLABS_API_KEY};
+use twitch_translator_core::ingest::MicIngestor;
+use twitch_translator_core::profile::ChannelProfile;
+use twitch_translator_core::tts::ElevenLabsTtsClient;
+
+#[derive(Args, Debug)]
+pub struct CloneVoiceArgs {
+    /// Channel whose profile the voice is saved in
+    channel: String,
+
+    /// Recording of the streamer speaking, a minute or more of clear speech
+    /// (WAV, MP3 and most other audio files)
+    #[arg(long, value_name = "FILE", conflicts_with = "record")]
+    sample: Option<PathBuf>,
+
+    /// Record this many seconds from --record-device instead of using a
+    /// --sample
+    #[arg(long, value_name = "SECS", required_unless_present = "sample")]
+    record: Option<u64>,
+
+    /// Microphone to record, as ffmpeg names it; the system's default if
+    /// unset
+    #[arg(long, value_name = "DEVICE", default_value = "default")]
+    record_device: String,
+
+    /// Name of the new voice in ElevenLabs; "<channel> (dub)" if unset
+    #[arg(long)]
+    name: Option<String>,
+
+    /// Confirm that the streamer agreed to have their voice cloned and used
+    /// for dubbing
+    #[arg(long)]
+    consent: bool,
+}
+
+pub async fn run(args: CloneVoiceArgs, cfg: AppConfig, profile_dir: PathBuf) -> anyhow::Result<()> {
+    if !args.consent {
+        anyhow::bail!(
+            "cloning a voice needs the speaker's permission; once they agreed, pass --consent"
+        );
+    }
+    let Some(key) = &cfg.api_keys.elevenlabs else {
+        anyhow::bail!("clone-voice needs --elevenlabs-api-key or {ENV_ELEVENLABS_API_KEY}");
+    };
+
+    let (file_name, sample) = match (&args.sample, args.record) {
+        (Some(path), _) => {
+            let sample = tokio::fs::read(path)
+                .await
+                .with_context(|| format!("failed to read {}", path.display()))?;
+            let file_name = path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_else(|| "sample".to_owned());
+            (file_name, sample)
+        }
+        (None, Some(secs)) => (
+            "sample.wav".to_owned(),
+            record(&args.record_device, secs).await?,
+        ),
+        (None, None) => anyhow::bail!("give a --sample or --record"),
+    };
+
+    let name = args
+        .name
+        .unwrap_or_else(|| format!("{} (dub)", args.channel));
+    let client = ElevenLabsTtsClient::new(key.expose().to_string())
+        .with_transport(crate::transport(&cfg, HttpService::ElevenLabs)?);
+    let description = format!("Voice of {} for twitch-translator dubbing", args.channel);
+    let voice_id = client
+        .add_voice(&name, &description, &file_name, &sample)
+        .await
+        .context("ElevenLabs did not clone the voice")?;
+
+    let mut profile = ChannelProfile::load(&profile_dir, &args.channel)
+        .with_context(|| format!("failed to read the profile of {}", args.channel))?;
+    profile.voice_id = Some(voice_id.clone());
+    profile.voice_name = Some(name.clone());
+    let path = profile
+        .save(&profile_dir, &args.channel)
+        .with_context(|| format!("failed to save the profile of {}", args.channel))?;
+    println!(
+        "Cloned \"{name}\" as voice {voice_id}, saved in {}",
+        path.display()
+    );
+    println!(
+        "Sessions on --channel {} now dub with it unless --voice or --lang-voice says otherwise",
+        args.channel
+    );
+    Ok(())
+}
+
+/// Records `secs` seconds of `device` and returns them as a WAV file.
+async fn record(device: &str, secs: u64) -> anyhow::Result<Vec<u8>> {
+    let path = std::env::temp_dir().join(format!(
+        "twitch-translator-voice-{}.wav",
+        std::process::id()
+    ));
+    eprintln!("Recording {secs} s from {device}; speak naturally until it stops...");
+    let result = MicIngestor::new(device)?
+        .record(Duration::from_secs(secs), &path)
+        .await;
+    let sample = match result {
+        Ok(()) => tokio::fs::read(&path).await.map_err(anyhow::Error::from),
+        Err(e) => Err(e.into()),
+    };
+    let _ = tokio::fs::remove_file(&path).await;
+    sample.context("failed to record the voice sample")
+}
@@ -0,0 +1,243 @@
+//! Informational subcommands: `devices`, `voices`, `models` and `query`.
+
+use anyhow::Context;
+use clap::Args;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+#[cfg(feature = "sqlite")]
+use twitch_translator_core::archive::SessionArchive;
+use twitch_translator_core::asr::{download_model, installed_models, WHISPER_MODELS};
+#[cfg(feature = "sqlite")]
+use twitch_translator_core::config::{resolve_api_key, ENV_LLM_API_KEY};
+use twitch_translator_core::config::{
+    AppConfig, Env, HttpService, DEFAULT_EMBEDDING_MODEL, DEFAULT_LLM_URL,
+};
+#[cfg(feature = "sqlite")]
+use twitch_translator_core::embeddings::{EmbeddingClient, EMBEDDING_BATCH};
+use twitch_translator_core::playback::list_output_devices;
+use twitch_translator_core::tts::{list_piper_voices, ElevenLabsTtsClient};
+
+pub fn devices() -> anyhow::Result<()> {
+    let devices = list_output_devices()?;
+    if devices.is_empty() {
+        println!("no audio output devices found");
+    }
+    for device in devices {
+        let marker = if device.is_default { "*" } else { " " };
+        println!("{marker} {}", device.name);
+    }
+    Ok(())
+}
+
+pub async fn voices(cfg: &AppConfig) -> anyhow::Result<()> {
+    println!("ElevenLabs:");
+    match &cfg.api_keys.elevenlabs {
+        Some(key) => {
+            let client = ElevenLabsTtsClient::new(key.expose().to_string())
+                .with_transport(crate::transport(cfg, HttpService::ElevenLabs)?);
+            match client.list_voices().await {
+                Ok(voices) => {
+                    for voice in voices {
+                        let category = voice.category.unwrap_or_default();
+                        println!("  {:<24} {:<32} {category}", voice.voice_id, voice.name);
+                    }
+                }
+                Err(e) => println!("  failed to list voices: {e}"),
+            }
+        }
+        None => println!("  (no API key configured)"),
+    }
+
+    let model_dir = Path::new(&cfg.piper.model_path)
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    println!("Piper ({}):", model_dir.display());
+    match list_piper_voices(model_dir) {
+        Ok(voices) if voices.is_empty() => println!("  (no .onnx models found)"),
+        Ok(voices) => {
+            for voice in voices {
+                let language = voice.language.unwrap_or_default();
+                if voice.speakers > 1 {
+                    let speakers = voice.speakers;
+                    println!("  {:<40} {language:<8} {speakers} speakers", voice.name);
+                } else {
+                    println!("  {:<40} {language}", voice.name);
+                }
+            }
+        }
+        Err(e) => println!("  failed to read {}: {e}", model_dir.display()),
+    }
+    Ok(())
+}
+
+pub fn list_models(dir: &Path) -> anyhow::Result<()> {
+    let installed = installed_models(dir);
+    for model in WHISPER_MODELS {
+        let marker = if installed.iter().any(|(m, _)| m == model) {
+            "installed"
+        } else {
+            ""
+        };
+        let line = format!("{:<16} {:>6} MB  {marker}", model.name, model.size_mb);
+        println!("{}", line.trim_end());
+    }
+    Ok(())
+}
+
+pub async fn download(name: &str, dir: &Path) -> anyhow::Result<()> {
+    let mut last_percent = None;
+    let path = download_model(name, dir, |done, total| {
+        if let Some(total) = total.filter(|t| *t > 0) {
+            let percent = done * 100 / total;
+            if last_percent != Some(percent) {
+                last_percent = Some(percent);
+                print!("\rdownloading {name}: {percent}%");
+                let _ = std::io::stdout().flush();
+            }
+        }
+    })
+    .await
+    .with_context(|| format!("failed to download model '{name}'"))?;
+    println!("\nsaved to {}", path.display());
+    Ok(())
+}
+
+#[derive(Args, Debug)]
+#[cfg_attr(not(feature = "sqlite"), allow(dead_code))]
+pub struct QueryArgs {
+    /// Words that the transcript or translation must all contain, or with
+    /// --semantic what was talked about
+    #[arg(required = true)]
+    words: Vec<String>,
+
+    /// A --session-db directory, or one session's file in it
+    #[arg(long, value_name = "PATH")]
+    db: PathBuf,
+
+    /// Most utterances to show
+    #[arg(long, default_value_t = 20)]
+    limit: usize,
+
+    /// Find utterances by meaning rather than exact words, with vectors from
+    /// --embeddings-model; those of utterances not searched this way before
+    /// are computed first and saved alongside them
+    #[arg(long)]
+    semantic: bool,
+
+    /// OpenAI-compatible API root the embeddings come from (e.g. a local
+    /// Ollama at http://localhost:11434/v1)
+    #[arg(long, default_value = DEFAULT_LLM_URL)]
+    embeddings_url: String,
+
+    /// Embedding model; a multilingual one also finds what was said in
+    /// another language than the query's
+    #[arg(long, default_value = DEFAULT_EMBEDDING_MODEL)]
+    embeddings_model: String,
+
+    /// Key for --embeddings-url; LLM_API_KEY if not given
+    #[arg(long)]
+    embeddings_api_key: Option<String>,
+}
+
+/// Prints the utterances saved with `--session-db` that contain every word
+/// of the query, or with `--semantic` are closest to it in meaning.
+#[cfg(feature = "sqlite")]
+pub async fn query(args: QueryArgs, cfg: &AppConfig, env: &impl Env) -> anyhow::Result<()> {
+    let query = args.words.join(" ");
+    let files = if args.db.is_dir() {
+        let entries = std::fs::read_dir(&args.db)
+            .with_context(|| format!("failed to read {}", args.db.display()))?;
+        let mut files = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().is_some_and(|e| e == "sqlite") {
+                files.push(path);
+            }
+        }
+        files
+    } else {
+        vec![args.db.clone()]
+    };
+    let mut sessions = Vec::with_capacity(files.len());
+    for file in &files {
+        let session = SessionArchive::open(file)
+            .with_context(|| format!("failed to open {}", file.display()))?;
+        sessions.push(session);
+    }
+
+    let found = if args.semantic {
+        let mut embedder = EmbeddingClient::new(&args.embeddings_url, &args.embeddings_model)
+            .with_transport(crate::transport(cfg, HttpService::Llm)?);
+        if let Some(key) = resolve_api_key(args.embeddings_api_key, ENV_LLM_API_KEY, env)? {
+            embedder = embedder.with_api_key(key.expose().to_owned());
+        }
+        let mut found = Vec::new();
+        let target = embedder
+            .embed(std::slice::from_ref(&query))
+            .await
+            .context("failed to embed the query")?
+            .remove(0);
+        for session in &mut sessions {
+            embed_session(session, &embedder).await?;
+            found.extend(session.nearest(embedder.model(), &target, args.limit)?);
+        }
+        found.sort_by(|a, b| b.1.total_cmp(&a.1));
+        found.truncate(args.limit);
+        found.into_iter().map(|(u, _)| u).collect()
+    } else {
+        let mut found = Vec::new();
+        for session in &sessions {
+            found.extend(session.search(&query, args.limit)?);
+        }
+        found.sort_by_key(|u| u.at_ms);
+        // The latest sessions are the likeliest to be asked about.
+        let skip = found.len().saturating_sub(args.limit);
+        found.split_off(skip)
+    };
+
+    if found.is_empty() {
+        println!("nothing found in {} session(s)", files.len());
+    }
+    for utterance in &found {
+        let offset = utterance.offset.as_secs();
+        println!(
+            "[{} {} +{}:{:02}:{:02}] {}",
+            utterance.channel,
+            utterance.local_time,
+            offset / 3600,
+            offset / 60 % 60,
+            offset % 60,
+            utterance.source
+        );
+        if let (Some(text), Some(lang)) = (&utterance.translation, &utterance.target_lang) {
+            println!("    {lang}: {text}");
+        }
+    }
+    Ok(())
+}
+
+/// Computes and saves the vectors of the transcripts in `session` that have
+/// none from `embedder`'s model yet.
+#[cfg(feature = "sqlite")]
+async fn embed_session(
+    session: &mut SessionArchive,
+    embedder: &EmbeddingClient,
+) -> anyhow::Result<()> {
+    let pending = session.unembedded(embedder.model())?;
+    for batch in pending.chunks(EMBEDDING_BATCH) {
+        let texts: Vec<String> = batch.iter().map(|(_, text)| text.clone()).collect();
+        let vectors = embedder
+            .embed(&texts)
+            .await
+            .context("failed to embed the transcripts")?;
+        let vectors: Vec<(i64, Vec<f32>)> = batch.iter().map(|(id, _)| *id).zip(vectors).collect();
+        session.store_embeddings(embedder.model(), &vectors)?;
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "sqlite"))]
+pub async fn query(_args: QueryArgs, _cfg: &AppConfig, _env: &impl Env) -> anyhow::Result<()> {
+    anyhow::bail!("query needs a build with --features sqlite")
+}
@@ -0,0 +1,199 @@
+//! Headless daemon (`twitch-translator daemon`).
+//!
+//! Keeps no session running until told to over the control socket. One session
+//! runs at a time; `start` replaces it. Status and runtime controls outlive
+//! individual sessions, so a language or volume set while idle applies to the
+//! next `start`.
+
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use twitch_translator_core::config::{AppConfig, InputSource, TargetLang};
+use twitch_translator_core::control::{
+    ControlCommand, ControlHandle, ControlHandler, ControlReply, ControlServer,
+};
+use twitch_translator_core::status::PipelineStatus;
+
+struct Session {
+    input: InputSource,
+    task: JoinHandle<()>,
+}
+
+struct Daemon {
+    base: AppConfig,
+    skip_preflight: bool,
+    status: PipelineStatus,
+    control: ControlHandle,
+    session: Mutex<Option<Session>>,
+}
+
+impl Daemon {
+    fn new(base: AppConfig, skip_preflight: bool, status: PipelineStatus) -> Self {
+        let control = crate::new_control(&base);
+        crate::enable_clips(&base, &control);
+        Self {
+            base,
+            skip_preflight,
+            status,
+            control,
+            session: Mutex::new(None),
+        }
+    }
+
+    fn lock_session(&self) -> std::sync::MutexGuard<'_, Option<Session>> {
+        match self.session.lock() {
+            Ok(g) => g,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    async fn start(&self, channel: Option<String>, url: Option<String>) -> ControlReply {
+        let input = match (channel, url) {
+            (Some(c), None) if !c.trim().is_empty() => InputSource::Channel(c),
+            (None, Some(u)) if !u.trim().is_empty() => InputSource::Url(u),
+            _ => return ControlReply::error("exactly one of channel or url must be provided"),
+        };
+
+        let mut cfg = self.base.clone();
+        cfg.input = input.clone();
+        cfg.target_lang = self.control.target_lang();
+        if !self.skip_preflight {
+            if let Err(e) = crate::preflight(&cfg).await {
+                return ControlReply::error(e.to_string());
+            }
+        }
+
+        let status = self.status.clone();
+        let control = self.control.clone();
+        let task = tokio::spawn(async move {
+            if let Err(e) = crate::run_session(cfg, status.clone(), control).await {
+                tracing::error!(error = %e, "session ended with error");
+            }
+            status.set_stream_online(false);
+        });
+
+        if let Some(previous) = self.lock_session().replace(Session { input, task }) {
+            previous.task.abort();
+        }
+        tracing::info!("session started");
+        ControlReply::ok()
+    }
+
+    fn stop(&self) -> ControlReply {
+        match self.lock_session().take() {
+            Some(session) => {
+                session.task.abort();
+                self.status.set_stream_online(false);
+                tracing::info!("session stopped");
+                ControlReply::ok()
+            }
+            None => ControlReply::error("no session is running"),
+        }
+    }
+
+    fn status_reply(&self) -> ControlReply {
+        let (running, input) = match self.lock_session().as_ref() {
+            Some(s) => (!s.task.is_finished(), Some(s.input.clone())),
+            None => (false, None),
+        };
+        ControlReply::with_data(serde_json::json!({
+            "running": running,
+            "input": input,
+            "target_lang": self.control.target_lang().as_str(),
+            "volume": self.control.volume(),
+            "muted": self.control.is_muted(),
+            "paused": self.control.is_paused(),
+            "style": self.control.translation_style(),
+            "pipeline": self.status.snapshot(),
+        }))
+    }
+}
+
+impl ControlHandler for Daemon {
+    fn handle(&self, command: ControlCommand) -> BoxFuture<'_, ControlReply> {
+        async move {
+            match command {
+                ControlCommand::Start { channel, url } => self.start(channel, url).await,
+                ControlCommand::Stop => self.stop(),
+                ControlCommand::SetLanguage { lang } => match TargetLang::new(lang) {
+                    Ok(lang) => {
+                        self.control.set_target_lang(lang);
+                        ControlReply::ok()
+                    }
+                    Err(e) => ControlReply::error(e.to_string()),
+                },
+                ControlCommand::SetVolume { volume } => {
+                    if volume.is_nan() || volume < 0.0 {
+                        return ControlReply::error("volume must be a non-negative number");
+                    }
+                    self.control.set_volume(volume);
+                    ControlReply::ok()
+                }
+                ControlCommand::SetStyle { style } => {
+                    self.control.set_translation_style(style);
+                    ControlReply::ok()
+                }
+                ControlCommand::Mute => {
+                    self.control.set_muted(true);
+                    ControlReply::ok()
+                }
+                ControlCommand::Unmute => {
+                    self.control.set_muted(false);
+                    ControlReply::ok()
+                }
+                ControlCommand::Pause => {
+                    self.control.set_paused(true);
+                    ControlReply::ok()
+                }
+                ControlCommand::Resume => {
+                    self.control.set_paused(false);
+                    ControlReply::ok()
+                }
+                ControlCommand::Clip => match self.control.clips().save().await {
+                    Ok(saved) => ControlReply::with_data(serde_json::json!({
+                        "audio": saved.audio,
+                        "transcript": saved.transcript,
+                    })),
+                    Err(e) => ControlReply::error(e.to_string()),
+                },
+                ControlCommand::Status => self.status_reply(),
+            }
+        }
+        .boxed()
+    }
+}
+
+/// Serves the control socket at `endpoint` until it fails.
+pub async fn run(
+    base: AppConfig,
+    endpoint: &str,
+    grpc_addr: Option<SocketAddr>,
+    skip_preflight: bool,
+    global_hotkeys: bool,
+    now_playing: bool,
+) -> anyhow::Result<()> {
+    let status = PipelineStatus::new();
+    status.set_caption_history(Duration::from_secs(base.output.caption_history_secs));
+    crate::watch_words(&base, &status);
+    crate::spawn_summaries(&base, &status)?;
+
+    let daemon = Arc::new(Daemon::new(base, skip_preflight, status.clone()));
+    if let Some(addr) = daemon.base.status_addr {
+        crate::spawn_status_server(addr, status.clone(), daemon.control.clone()).await?;
+    }
+    let _live_config = crate::reload::watch(&daemon.base, vec![daemon.control.clone()])?;
+    if global_hotkeys {
+        crate::hotkeys::spawn(daemon.control.clone())?;
+    }
+    if now_playing {
+        crate::now_playing::spawn(&status, daemon.control.clone())?;
+    }
+    if let Some(addr) = grpc_addr {
+        crate::grpc::spawn(addr, daemon.clone(), status).await?;
+    }
+    ControlServer::new(daemon).serve(endpoint).await?;
+    Ok(())
+}
@@ -0,0 +1,102 @@
+//! `doctor` subcommand: the startup preflight plus checks that only matter
+//! when diagnosing a machine (GPU presence, Twitch reachability).
+
+use tokio::process::Command;
+use twitch_translator_core::config::{AppConfig, AsrDevice, HttpService};
+use twitch_translator_core::ingest::{TwitchHlsIngestor, TwitchIngestOptions};
+use twitch_translator_core::preflight::{
+    run_preflight, CheckResult, CheckStatus, PreflightOptions,
+};
+
+pub async fn run(cfg: &AppConfig) -> anyhow::Result<()> {
+    let options = PreflightOptions {
+        load_whisper_model: true,
+    };
+    let (mut report, gpu, twitch) = tokio::join!(
+        run_preflight(cfg, options),
+        check_gpu(cfg),
+        check_twitch(cfg)
+    );
+    report.push(gpu);
+    report.push(twitch);
+    println!("{report}");
+
+    let failures = report.with_status(CheckStatus::Fail).count();
+    if failures > 0 {
+        anyhow::bail!("{failures} check(s) failed");
+    }
+    Ok(())
+}
+
+/// Runs `program args` and returns the first line of its stdout on success.
+async fn first_line_of(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().await.ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .next()
+        .map(|l| l.trim().to_owned())
+}
+
+async fn check_gpu(cfg: &AppConfig) -> CheckResult {
+    const NAME: &str = "gpu";
+    if !cfg!(feature = "whisper-rs") {
+        return CheckResult::warn(NAME, "built without Whisper ASR; GPU not used");
+    }
+    if cfg.asr.device == AsrDevice::Cpu {
+        return CheckResult::ok(NAME, "--asr-device cpu; GPU not used");
+    }
+    if AsrDevice::compiled_gpus().is_empty() {
+        return CheckResult::warn(
+            NAME,
+            "built without a GPU backend (vulkan, cuda or metal); Whisper will run on the CPU",
+        );
+    }
+    if let Some(gpu) = first_line_of("nvidia-smi", &["-L"]).await {
+        return CheckResult::ok(NAME, gpu);
+    }
+    if let Some(summary) = vulkan_device().await {
+        return CheckResult::ok(NAME, summary);
+    }
+    CheckResult::warn(
+        NAME,
+        "no Vulkan/NVIDIA GPU detected; Whisper will run on the CPU",
+    )
+}
+
+async fn vulkan_device() -> Option<String> {
+    let output = Command::new("vulkaninfo")
+        .arg("--summary")
+        .output()
+        .await
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .find_map(|l| l.trim().strip_prefix("deviceName").map(str::to_owned))
+        .map(|name| format!("Vulkan: {}", name.trim_start_matches([' ', '='])))
+}
+
+async fn check_twitch(cfg: &AppConfig) -> CheckResult {
+    const NAME: &str = "twitch";
+    let ingestor = match TwitchHlsIngestor::new(
+        cfg.twitch.clone(),
+        cfg.input.clone(),
+        TwitchIngestOptions::from_config(&cfg.twitch),
+    ) {
+        Ok(i) => i,
+        Err(e) => return CheckResult::fail(NAME, e.to_string()),
+    };
+    let ingestor = match crate::transport(cfg, HttpService::Twitch) {
+        Ok(t) => ingestor.with_transport(t),
+        Err(e) => return CheckResult::fail(NAME, e.to_string()),
+    };
+    match ingestor.check_reachable().await {
+        Ok(()) => CheckResult::ok(NAME, "gql.twitch.tv reachable"),
+        Err(e) => CheckResult::fail(NAME, format!("unreachable: {e}")),
+    }
+}
@@ -0,0 +1,199 @@
+//! `eval` subcommand: scores the session's ASR and translators against a
+//! golden set of reference transcripts and translations.
+
+use anyhow::Context;
+use clap::{Args, ValueEnum};
+use std::path::PathBuf;
+#[cfg(feature = "whisper-rs")]
+use twitch_translator_core::asr::{AsrBackend, RemoteAsrBackend, WhisperAsrBackend};
+#[cfg(feature = "whisper-rs")]
+use twitch_translator_core::config::AsrProvider;
+use twitch_translator_core::config::{AppConfig, HttpService, LlmConfig};
+#[cfg(feature = "whisper-rs")]
+use twitch_translator_core::decode::FfmpegAudioDecoder;
+use twitch_translator_core::eval::{
+    load_golden, load_pairs, score_translator, AsrScore, EvalReport, GoldenCase,
+};
+use twitch_translator_core::summary::ChatSummarizer;
+use twitch_translator_core::translate::{DeepLTranslator, LibreTranslator};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum TranslatorName {
+    Deepl,
+    /// The --llm-url chat model
+    Llm,
+    /// A LibreTranslate server at --libre-url
+    Libre,
+}
+
+#[derive(Args, Debug)]
+pub struct EvalArgs {
+    /// JSONL file with one {"audio", "transcript", "translation"} object per
+    /// line; audio (relative to the file) and translation are optional
+    #[arg(
+        long,
+        value_name = "FILE",
+        required_unless_present = "transcripts",
+        conflicts_with = "transcripts"
+    )]
+    golden: Option<PathBuf>,
+
+    /// Reference transcripts, one per line, instead of --golden
+    #[arg(long, value_name = "FILE", requires = "translations")]
+    transcripts: Option<PathBuf>,
+
+    /// Reference translations into --target-lang, line by line with --transcripts
+    #[arg(long, value_name = "FILE", requires = "transcripts")]
+    translations: Option<PathBuf>,
+
+    /// Translator to score; repeat to compare several. Defaults to every one
+    /// configured: DeepL with a key, LibreTranslate with --libre-url
+    #[arg(long = "translator", value_enum)]
+    translators: Vec<TranslatorName>,
+
+    /// LibreTranslate server, e.g. http://localhost:5000
+    #[arg(long, env = "LIBRETRANSLATE_URL", value_name = "URL")]
+    libre_url: Option<String>,
+
+    #[arg(long, env = "LIBRETRANSLATE_API_KEY", hide_env_values = true)]
+    libre_api_key: Option<String>,
+
+    /// Skip ASR even if the golden set has audio
+    #[arg(long)]
+    no_asr: bool,
+
+    /// Print the report as JSON
+    #[arg(long)]
+    json: bool,
+}
+
+pub async fn run(args: EvalArgs, cfg: AppConfig, llm: LlmConfig) -> anyhow::Result<()> {
+    let cases = match (&args.golden, &args.transcripts, &args.translations) {
+        (Some(golden), _, _) => load_golden(golden).await?,
+        (None, Some(transcripts), Some(translations)) => {
+            load_pairs(transcripts, translations).await?
+        }
+        _ => anyhow::bail!("pass --golden, or --transcripts with --translations"),
+    };
+    tracing::info!(cases = cases.len(), "starting evaluation");
+
+    let mut report = EvalReport::default();
+    if !args.no_asr && cases.iter().any(|c| c.audio.is_some()) {
+        report.asr.push(score_asr(&cfg, &cases).await?);
+    }
+    if cases.iter().any(|c| c.translation.is_some()) {
+        let translators = if args.translators.is_empty() {
+            configured(&args, &cfg)
+        } else {
+            args.translators.clone()
+        };
+        if translators.is_empty() {
+            tracing::warn!(
+                "no translator configured; set DEEPL_API_KEY or --libre-url, or pass --translator"
+            );
+        }
+        for name in translators {
+            let score = match name {
+                TranslatorName::Deepl => {
+                    let key = cfg
+                        .api_keys
+                        .deepl
+                        .as_ref()
+                        .context("--translator deepl needs a DeepL API key")?;
+                    let mut translator = DeepLTranslator::new(key.expose().to_string())
+                        .with_transport(crate::transport(&cfg, HttpService::DeepL)?);
+                    if let Some(tokens) = cfg.text.protected_tokens() {
+                        translator = translator.with_protected_tokens(tokens);
+                    }
+                    score_translator("deepl", &translator, &cases, &cfg.target_lang).await
+                }
+                TranslatorName::Llm => {
+                    let translator = ChatSummarizer::from_config(&llm)
+                        .with_transport(crate::transport(&cfg, HttpService::Llm)?);
+                    let backend = format!("llm {}", llm.model);
+                    score_translator(&backend, &translator, &cases, &cfg.target_lang).await
+                }
+                TranslatorName::Libre => {
+                    let url = args
+                        .libre_url
+                        .as_ref()
+                        .context("--translator libre needs --libre-url")?;
+                    // LibreTranslate shares DeepL's proxy and timeout settings.
+                    let mut translator = LibreTranslator::new(url)
+                        .with_transport(crate::transport(&cfg, HttpService::DeepL)?);
+                    if let Some(key) = &args.libre_api_key {
+                        translator = translator.with_api_key(key.clone());
+                    }
+                    score_translator("libre", &translator, &cases, &cfg.target_lang).await
+                }
+            };
+            report.translation.push(score);
+        }
+    }
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        println!(
+            "{} cases, target language {}",
+            cases.len(),
+            cfg.target_lang.as_str()
+        );
+        print!("{report}");
+    }
+    Ok(())
+}
+
+/// The translators `cfg` and `args` have credentials or a server for.
+fn configured(args: &EvalArgs, cfg: &AppConfig) -> Vec<TranslatorName> {
+    let mut names = Vec::new();
+    if cfg.api_keys.deepl.is_some() {
+        names.push(TranslatorName::Deepl);
+    }
+    if args.libre_url.is_some() {
+        names.push(TranslatorName::Libre);
+    }
+    names
+}
+
+#[cfg(feature = "whisper-rs")]
+async fn score_asr(cfg: &AppConfig, cases: &[GoldenCase]) -> anyhow::Result<AsrScore> {
+    if cfg.asr.provider != AsrProvider::Whisper {
+        anyhow::bail!(
+            "--asr {} transcribes in real time; eval scores Whisper locally or on --asr-endpoint",
+            cfg.asr.provider
+        );
+    }
+    match &cfg.asr.endpoint {
+        Some(endpoint) => {
+            let transport = crate::transport(cfg, HttpService::Asr)?;
+            let asr = RemoteAsrBackend::from_config(&cfg.asr, transport)?;
+            with_asr(&format!("remote {endpoint}"), asr, cases).await
+        }
+        None => {
+            let asr = WhisperAsrBackend::from_config(&cfg.asr)?;
+            let model = std::path::Path::new(&cfg.asr.model_path)
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            with_asr(&format!("whisper {model}"), asr, cases).await
+        }
+    }
+}
+
+#[cfg(feature = "whisper-rs")]
+async fn with_asr<A: AsrBackend>(
+    backend: &str,
+    asr: A,
+    cases: &[GoldenCase],
+) -> anyhow::Result<AsrScore> {
+    let decode = FfmpegAudioDecoder::default();
+    Ok(twitch_translator_core::eval::score_asr(backend, &decode, &asr, cases).await?)
+}
+
+#[cfg(not(feature = "whisper-rs"))]
+async fn score_asr(_cfg: &AppConfig, _cases: &[GoldenCase]) -> anyhow::Result<AsrScore> {
+    Err(anyhow::anyhow!(
+        "Whisper ASR feature is not enabled. Please install libclang and rebuild with --features whisper-rs, or pass --no-asr"
+    ))
+}
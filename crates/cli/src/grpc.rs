@@ -0,0 +1,203 @@
+//! `daemon --grpc-addr`: the daemon's commands, plus live events and audio, as
+//! a gRPC service, so applications in other languages can run the translator
+//! as a sidecar. The service is defined in `proto/translator.proto`.
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+use twitch_translator_core::control::ControlHandler;
+use twitch_translator_core::status::PipelineStatus;
+
+#[cfg(feature = "grpc")]
+mod service {
+    use futures::stream::{self, BoxStream, StreamExt};
+    use std::sync::Arc;
+    use tokio::sync::broadcast::{self, error::RecvError};
+    use tonic::{Request, Response, Status};
+    use twitch_translator_core::control::{ControlCommand, ControlHandler, ControlReply};
+    use twitch_translator_core::status::{PipelineEvent, PipelineStatus};
+
+    pub mod proto {
+        tonic::include_proto!("twitch_translator.v1");
+    }
+
+    use proto::start_session_request::Input;
+    use proto::translator_server::Translator;
+    pub use proto::translator_server::TranslatorServer;
+    use proto::{
+        event, AudioClip, CommandReply, Event, GetStatusRequest, SetLanguageRequest,
+        SetPausedRequest, StartSessionRequest, StatusReply, StopSessionRequest, StreamAudioRequest,
+        StreamEventsRequest,
+    };
+
+    pub struct TranslatorService {
+        pub handler: Arc<dyn ControlHandler>,
+        pub status: PipelineStatus,
+    }
+
+    impl TranslatorService {
+        async fn run(&self, command: ControlCommand) -> Result<ControlReply, Status> {
+            let reply = self.handler.handle(command).await;
+            if reply.ok {
+                Ok(reply)
+            } else {
+                Err(Status::failed_precondition(reply.error.unwrap_or_default()))
+            }
+        }
+
+        async fn command(&self, command: ControlCommand) -> Result<Response<CommandReply>, Status> {
+            self.run(command).await?;
+            Ok(Response::new(CommandReply {}))
+        }
+    }
+
+    /// Yields what `rx` receives, skipping anything a slow client missed.
+    fn receive<T: Clone + Send + 'static>(rx: broadcast::Receiver<T>) -> BoxStream<'static, T> {
+        stream::unfold(rx, |mut rx| async move {
+            loop {
+                match rx.recv().await {
+                    Ok(item) => return Some((item, rx)),
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        })
+        .boxed()
+    }
+
+    fn to_event(event: PipelineEvent) -> Event {
+        let kind = match event {
+            PipelineEvent::Transcript { text, .. } => {
+                event::Kind::Transcript(proto::Transcript { text })
+            }
+            PipelineEvent::Translation {
+                source_text,
+                text,
+                target_lang,
+            } => event::Kind::Translation(proto::Translation {
+                source_text,
+                text,
+                target_lang,
+            }),
+            other => event::Kind::OtherJson(serde_json::to_string(&other).unwrap_or_default()),
+        };
+        Event { kind: Some(kind) }
+    }
+
+    #[tonic::async_trait]
+    impl Translator for TranslatorService {
+        type StreamEventsStream = BoxStream<'static, Result<Event, Status>>;
+        type StreamAudioStream = BoxStream<'static, Result<AudioClip, Status>>;
+
+        async fn start_session(
+            &self,
+            request: Request<StartSessionRequest>,
+        ) -> Result<Response<CommandReply>, Status> {
+            let (channel, url) = match request.into_inner().input {
+                Some(Input::Channel(channel)) => (Some(channel), None),
+                Some(Input::Url(url)) => (None, Some(url)),
+                None => return Err(Status::invalid_argument("a channel or url is required")),
+            };
+            self.command(ControlCommand::Start { channel, url }).await
+        }
+
+        async fn stop_session(
+            &self,
+            _request: Request<StopSessionRequest>,
+        ) -> Result<Response<CommandReply>, Status> {
+            self.command(ControlCommand::Stop).await
+        }
+
+        async fn set_language(
+            &self,
+            request: Request<SetLanguageRequest>,
+        ) -> Result<Response<CommandReply>, Status> {
+            let lang = request.into_inner().lang;
+            self.command(ControlCommand::SetLanguage { lang }).await
+        }
+
+        async fn set_paused(
+            &self,
+            request: Request<SetPausedRequest>,
+        ) -> Result<Response<CommandReply>, Status> {
+            let command = if request.into_inner().paused {
+                ControlCommand::Pause
+            } else {
+                ControlCommand::Resume
+            };
+            self.command(command).await
+        }
+
+        async fn get_status(
+            &self,
+            _request: Request<GetStatusRequest>,
+        ) -> Result<Response<StatusReply>, Status> {
+            let reply = self.run(ControlCommand::Status).await?;
+            let json = reply.data.map(|d| d.to_string()).unwrap_or_default();
+            Ok(Response::new(StatusReply { json }))
+        }
+
+        // tonic fixes the stream items as `Result<_, Status>`; it can't be boxed.
+        #[allow(clippy::result_large_err)]
+        async fn stream_events(
+            &self,
+            _request: Request<StreamEventsRequest>,
+        ) -> Result<Response<Self::StreamEventsStream>, Status> {
+            let events = receive(self.status.subscribe()).map(|e| Ok(to_event(e)));
+            Ok(Response::new(events.boxed()))
+        }
+
+        // tonic fixes the stream items as `Result<_, Status>`; it can't be boxed.
+        #[allow(clippy::result_large_err)]
+        async fn stream_audio(
+            &self,
+            _request: Request<StreamAudioRequest>,
+        ) -> Result<Response<Self::StreamAudioStream>, Status> {
+            let clips = receive(self.status.subscribe_audio()).map(|audio| {
+                Ok(AudioClip {
+                    sample_rate_hz: audio.sample_rate_hz,
+                    channels: u32::from(audio.channels),
+                    pcm_s16le: audio.pcm_i16.iter().flat_map(|s| s.to_le_bytes()).collect(),
+                })
+            });
+            Ok(Response::new(clips.boxed()))
+        }
+    }
+}
+
+/// Serves the gRPC API on `addr` in the background for the rest of the process.
+#[cfg(feature = "grpc")]
+pub async fn spawn(
+    addr: SocketAddr,
+    handler: Arc<dyn ControlHandler>,
+    status: PipelineStatus,
+) -> anyhow::Result<()> {
+    use anyhow::Context;
+    use tonic::transport::server::TcpIncoming;
+
+    let listener = tokio::net::TcpListener::bind(addr)
+        .await
+        .with_context(|| format!("failed to bind gRPC API on {addr}"))?;
+    let incoming = TcpIncoming::from_listener(listener, true, None)
+        .map_err(|e| anyhow::anyhow!("failed to bind gRPC API on {addr}: {e}"))?;
+    let service = service::TranslatorServer::new(service::TranslatorService { handler, status });
+    tracing::info!(%addr, "gRPC API listening");
+    tokio::spawn(async move {
+        let result = tonic::transport::Server::builder()
+            .add_service(service)
+            .serve_with_incoming(incoming)
+            .await;
+        if let Err(e) = result {
+            tracing::error!(error = %e, "gRPC API stopped");
+        }
+    });
+    Ok(())
+}
+
+#[cfg(not(feature = "grpc"))]
+pub async fn spawn(
+    _addr: SocketAddr,
+    _handler: Arc<dyn ControlHandler>,
+    _status: PipelineStatus,
+) -> anyhow::Result<()> {
+    anyhow::bail!("--grpc-addr needs a build with --features grpc")
+}
@@ -0,0 +1,54 @@
+//! `--global-hotkeys`: mute or pause the dub from any window, e.g. while the
+//! stream player has focus.
+//!
+//! Ctrl+Alt+M toggles mute and Ctrl+Alt+P toggles pause. Keys are read
+//! system-wide, which needs X11 on Linux and the accessibility permission on
+//! macOS.
+
+use twitch_translator_core::control::ControlHandle;
+
+/// Listens for the hotkeys on a background thread for the rest of the process.
+#[cfg(feature = "hotkeys")]
+pub fn spawn(control: ControlHandle) -> anyhow::Result<()> {
+    use rdev::{EventType, Key};
+
+    std::thread::Builder::new()
+        .name("global-hotkeys".to_owned())
+        .spawn(move || {
+            let (mut ctrl, mut alt) = (false, false);
+            // Held keys repeat their press events; act on the first one only.
+            let (mut m_down, mut p_down) = (false, false);
+            let result = rdev::listen(move |event| match event.event_type {
+                EventType::KeyPress(Key::ControlLeft | Key::ControlRight) => ctrl = true,
+                EventType::KeyRelease(Key::ControlLeft | Key::ControlRight) => ctrl = false,
+                EventType::KeyPress(Key::Alt | Key::AltGr) => alt = true,
+                EventType::KeyRelease(Key::Alt | Key::AltGr) => alt = false,
+                EventType::KeyPress(Key::KeyM) => {
+                    if ctrl && alt && !m_down {
+                        let muted = control.toggle_muted();
+                        tracing::info!(muted, "mute toggled by hotkey");
+                    }
+                    m_down = true;
+                }
+                EventType::KeyRelease(Key::KeyM) => m_down = false,
+                EventType::KeyPress(Key::KeyP) => {
+                    if ctrl && alt && !p_down {
+                        let paused = control.toggle_paused();
+                        tracing::info!(paused, "pause toggled by hotkey");
+                    }
+                    p_down = true;
+                }
+                EventType::KeyRelease(Key::KeyP) => p_down = false,
+                _ => {}
+            });
+            if let Err(e) = result {
+                tracing::error!(error = ?e, "global hotkeys unavailable");
+            }
+        })?;
+    Ok(())
+}
+
+#[cfg(not(feature = "hotkeys"))]
+pub fn spawn(_control: ControlHandle) -> anyhow::Result<()> {
+    anyhow::bail!("--global-hotkeys needs a build with --features hotkeys")
+}
@@ -0,0 +1,189 @@
+//! `--log-file` and `--log-format`: where log lines go and what they look like.
+//!
+//! The log file is rotated once it grows past `--log-max-size-mb` or has been
+//! written to for longer than `--log-rotate`, whichever comes first. Rotated
+//! files are renamed `FILE.1`, `FILE.2`, ... with the newest first, and the
+//! oldest beyond `--log-keep` are removed.
+
+use anyhow::Context;
+use clap::{Args, ValueEnum};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::{Layer, Registry};
+
+#[derive(Args, Debug)]
+pub struct LogArgs {
+    /// Also write log lines to this file (the only destination with --tui)
+    #[arg(long, global = true, value_name = "PATH")]
+    pub log_file: Option<PathBuf>,
+
+    /// Format of log lines, on the terminal and in --log-file
+    #[arg(long, global = true, value_enum, default_value_t = LogFormat::Pretty)]
+    pub log_format: LogFormat,
+
+    /// Start a new log file once the current one reaches this size
+    #[arg(long, global = true, value_name = "MB", default_value_t = 100)]
+    pub log_max_size_mb: u64,
+
+    /// Start a new log file after this long, regardless of its size
+    #[arg(long, global = true, value_enum, default_value_t = LogRotation::Never)]
+    pub log_rotate: LogRotation,
+
+    /// Rotated log files to keep next to the current one
+    #[arg(long, global = true, value_name = "N", default_value_t = 5)]
+    pub log_keep: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    /// Human-readable lines
+    Pretty,
+    /// One JSON object per line, for log collectors
+    Json,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum LogRotation {
+    Never,
+    Hourly,
+    Daily,
+}
+
+impl LogRotation {
+    fn period(self) -> Option<Duration> {
+        match self {
+            Self::Never => None,
+            Self::Hourly => Some(Duration::from_secs(60 * 60)),
+            Self::Daily => Some(Duration::from_secs(24 * 60 * 60)),
+        }
+    }
+}
+
+/// Formatting layer writing to `writer`; `ansi` colours only make sense on a
+/// terminal.
+pub fn fmt_layer<W>(
+    format: LogFormat,
+    writer: W,
+    ansi: bool,
+) -> Box<dyn Layer<Registry> + Send + Sync>
+where
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    let layer = tracing_subscriber::fmt::layer()
+        .with_writer(writer)
+        .with_ansi(ansi);
+    match format {
+        LogFormat::Pretty => layer.boxed(),
+        LogFormat::Json => layer.json().boxed(),
+    }
+}
+
+/// Layer writing to the file set with `--log-file`, if any.
+pub fn file_layer(
+    args: &LogArgs,
+) -> anyhow::Result<Option<Box<dyn Layer<Registry> + Send + Sync>>> {
+    let Some(path) = &args.log_file else {
+        return Ok(None);
+    };
+    let file = RotatingFile::open(
+        path,
+        args.log_max_size_mb.saturating_mul(1024 * 1024),
+        args.log_rotate.period(),
+        args.log_keep,
+    )
+    .with_context(|| format!("failed to open log file {}", path.display()))?;
+    Ok(Some(fmt_layer(args.log_format, Mutex::new(file), false)))
+}
+
+/// Append-only log file that moves itself aside when it gets too big or too
+/// old.
+pub struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    written: u64,
+    opened: Instant,
+    max_bytes: u64,
+    period: Option<Duration>,
+    keep: usize,
+}
+
+impl RotatingFile {
+    pub fn open(
+        path: &Path,
+        max_bytes: u64,
+        period: Option<Duration>,
+        keep: usize,
+    ) -> io::Result<Self> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        // Appending to a file left by an earlier run counts toward its size.
+        let written = file.metadata()?.len();
+        Ok(Self {
+            path: path.to_owned(),
+            file,
+            written,
+            opened: Instant::now(),
+            max_bytes,
+            period,
+            keep,
+        })
+    }
+
+    fn rotated(&self, n: usize) -> PathBuf {
+        let mut name = self.path.clone().into_os_string();
+        name.push(format!(".{n}"));
+        PathBuf::from(name)
+    }
+
+    fn due(&self, incoming: usize) -> bool {
+        if self.written == 0 {
+            return false;
+        }
+        self.written.saturating_add(incoming as u64) > self.max_bytes
+            || self.period.is_some_and(|p| self.opened.elapsed() >= p)
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        if self.keep == 0 {
+            fs::remove_file(&self.path)?;
+        } else {
+            // Missing files are fine: fewer rotations have happened than we keep.
+            let _ = fs::remove_file(self.rotated(self.keep));
+            for n in (1..self.keep).rev() {
+                let _ = fs::rename(self.rotated(n), self.rotated(n + 1));
+            }
+            fs::rename(&self.path, self.rotated(1))?;
+        }
+        self.file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        self.written = 0;
+        self.opened = Instant::now();
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    // The fmt layer writes each event with a single call, so a rotation never
+    // splits a line across two files.
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.due(buf.len()) {
+            self.rotate()?;
+        }
+        let n = self.file.write(buf)?;
+        self.written += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
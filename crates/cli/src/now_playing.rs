@@ -0,0 +1,94 @@
+//! `--now-playing`: publish the utterance being dubbed as desktop media
+//! metadata, so OS overlays, widgets and stream decks can show it.
+//!
+//! The translation is the title, the original transcript the artist and the
+//! stream title the album. The session shows up as a media player (MPRIS on
+//! Linux, the Now Playing center on macOS) whose play/pause buttons pause the
+//! dub.
+
+use twitch_translator_core::control::ControlHandle;
+use twitch_translator_core::status::PipelineStatus;
+
+/// Publishes metadata from a background thread for the rest of the process.
+#[cfg(feature = "now-playing")]
+pub fn spawn(status: &PipelineStatus, control: ControlHandle) -> anyhow::Result<()> {
+    use souvlaki::{
+        MediaControlEvent, MediaControls, MediaMetadata, MediaPlayback, PlatformConfig,
+    };
+    use tokio::sync::broadcast::error::RecvError;
+    use twitch_translator_core::status::PipelineEvent;
+
+    if cfg!(target_os = "windows") {
+        // SMTC attaches to a window, which a console session doesn't have.
+        anyhow::bail!("--now-playing is not supported on Windows yet");
+    }
+
+    let mut events = status.subscribe();
+    std::thread::Builder::new()
+        .name("now-playing".to_owned())
+        .spawn(move || {
+            let config = PlatformConfig {
+                dbus_name: "twitch_translator",
+                display_name: "Twitch Translator",
+                hwnd: None,
+            };
+            let mut controls = match MediaControls::new(config) {
+                Ok(controls) => controls,
+                Err(e) => {
+                    tracing::error!(error = ?e, "now-playing metadata unavailable");
+                    return;
+                }
+            };
+            let buttons = control.clone();
+            let attached = controls.attach(move |event| match event {
+                MediaControlEvent::Play => buttons.set_paused(false),
+                MediaControlEvent::Pause | MediaControlEvent::Stop => buttons.set_paused(true),
+                MediaControlEvent::Toggle => {
+                    let paused = buttons.toggle_paused();
+                    tracing::info!(paused, "pause toggled by media controls");
+                }
+                _ => {}
+            });
+            if let Err(e) = attached {
+                tracing::warn!(error = ?e, "media control buttons unavailable");
+            }
+
+            let mut stream_title = None::<String>;
+            loop {
+                let (source_text, text) = match events.blocking_recv() {
+                    Ok(PipelineEvent::Translation {
+                        source_text, text, ..
+                    }) => (source_text, text),
+                    Ok(PipelineEvent::StreamMetadata(meta)) => {
+                        stream_title = Some(meta.title);
+                        continue;
+                    }
+                    Ok(_) | Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => return,
+                };
+                let metadata = MediaMetadata {
+                    title: Some(&text),
+                    artist: Some(&source_text),
+                    album: stream_title.as_deref(),
+                    ..Default::default()
+                };
+                let playback = if control.is_paused() {
+                    MediaPlayback::Paused { progress: None }
+                } else {
+                    MediaPlayback::Playing { progress: None }
+                };
+                let result = controls
+                    .set_metadata(metadata)
+                    .and_then(|()| controls.set_playback(playback));
+                if let Err(e) = result {
+                    tracing::warn!(error = ?e, "failed to publish now-playing metadata");
+                }
+            }
+        })?;
+    Ok(())
+}
+
+#[cfg(not(feature = "now-playing"))]
+pub fn spawn(_status: &PipelineStatus, _control: ControlHandle) -> anyhow::Result<()> {
+    anyhow::bail!("--now-playing needs a build with --features now-playing")
+}
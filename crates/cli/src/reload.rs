@@ -0,0 +1,90 @@
+//! `--live-config`: apply the settings file at startup and again whenever it
+//! changes, without restarting the session.
+
+use anyhow::Context;
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use twitch_translator_core::config::AppConfig;
+use twitch_translator_core::control::{ControlHandle, LiveConfig};
+
+/// Editors save in several steps (truncate, write, rename); wait for them to
+/// settle before reading the file.
+const SETTLE: Duration = Duration::from_millis(250);
+
+/// Applies `cfg.live_config` to `controls` and keeps doing so on every change
+/// until the returned watcher is dropped. Startup fails on an invalid file;
+/// a later invalid edit is logged and the previous settings stay in effect.
+pub fn watch(
+    cfg: &AppConfig,
+    controls: Vec<ControlHandle>,
+) -> anyhow::Result<Option<RecommendedWatcher>> {
+    let Some(path) = cfg.live_config.clone() else {
+        return Ok(None);
+    };
+    let mut current = load(&path)?;
+    for control in &controls {
+        current
+            .apply(&LiveConfig::default(), control)
+            .with_context(|| format!("invalid --live-config {}", path.display()))?;
+    }
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let name = path.file_name().map(ToOwned::to_owned);
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        if let Ok(event) = event {
+            if event.paths.iter().any(|p| p.file_name() == name.as_deref()) {
+                let _ = tx.send(());
+            }
+        }
+    })?;
+    // Watch the directory: saving through a temporary file replaces the file
+    // itself, and a watch on the old one would go quiet.
+    watcher
+        .watch(&watch_dir(&path), RecursiveMode::NonRecursive)
+        .with_context(|| format!("failed to watch --live-config {}", path.display()))?;
+
+    tokio::spawn(async move {
+        while rx.recv().await.is_some() {
+            tokio::time::sleep(SETTLE).await;
+            while rx.try_recv().is_ok() {}
+
+            let next = match load(&path) {
+                Ok(next) if next == current => continue,
+                Ok(next) => next,
+                Err(e) => {
+                    tracing::warn!(error = %format!("{e:#}"), "ignoring --live-config change");
+                    continue;
+                }
+            };
+            // Every handle gets the same fields, so all of them fail or none.
+            match controls
+                .iter()
+                .try_fold(Vec::new(), |_, control| next.apply(&current, control))
+            {
+                Ok(changed) => {
+                    if !changed.is_empty() {
+                        tracing::info!(changed = ?changed, "applied --live-config");
+                    }
+                    current = next;
+                }
+                Err(e) => tracing::warn!(error = %e, "ignoring --live-config change"),
+            }
+        }
+    });
+    Ok(Some(watcher))
+}
+
+fn load(path: &Path) -> anyhow::Result<LiveConfig> {
+    let json = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read --live-config {}", path.display()))?;
+    serde_json::from_str(&json).with_context(|| format!("invalid --live-config {}", path.display()))
+}
+
+fn watch_dir(path: &Path) -> PathBuf {
+    match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_owned(),
+        _ => PathBuf::from("."),
+    }
+}
@@ -0,0 +1,189 @@
+//! `--output` routing: which audio device, network stream, OBS output,
+//! recording and subtitle or caption file each channel uses.
+
+use std::path::PathBuf;
+use std::str::FromStr;
+use twitch_translator_core::config::{NetworkAudio, OutputConfig, RecordFormat};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum OutputTarget {
+    Device(String),
+    Network(NetworkAudio),
+    Obs(String),
+    Record(PathBuf),
+    Srt(PathBuf),
+    Caption(PathBuf),
+}
+
+/// One `--output [CHANNEL=]device:NAME`, `--output [CHANNEL=]net:URL`,
+/// `--output [CHANNEL=]obs:NAME`, `--output [CHANNEL=]rec:PATH`,
+/// `--output [CHANNEL=]srt:PATH` or `--output [CHANNEL=]txt:PATH` value.
+/// Routes without a channel apply to every channel.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OutputRoute {
+    pub channel: Option<String>,
+    pub target: OutputTarget,
+}
+
+impl FromStr for OutputRoute {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // A `=` before the first `:` separates the channel prefix; later ones
+        // belong to the device name or path.
+        let (channel, target) = match (s.find('='), s.find(':')) {
+            (Some(eq), Some(colon)) if eq < colon => (Some(&s[..eq]), &s[eq + 1..]),
+            _ => (None, s),
+        };
+        let target = match target.split_once(':') {
+            Some(("device", name)) if !name.is_empty() => OutputTarget::Device(name.to_owned()),
+            Some(("net", url)) => OutputTarget::Network(url.parse()?),
+            Some(("obs", name)) if !name.is_empty() => OutputTarget::Obs(name.to_owned()),
+            Some(("rec", path)) if !path.is_empty() => {
+                let path = PathBuf::from(path);
+                if RecordFormat::from_path(&path).is_none() {
+                    return Err(format!(
+                        "invalid output '{s}': rec: files end in .wav, .mp3, .opus, .ogg or .aac"
+                    ));
+                }
+                OutputTarget::Record(path)
+            }
+            Some(("srt", path)) if !path.is_empty() => OutputTarget::Srt(PathBuf::from(path)),
+            Some(("txt", path)) if !path.is_empty() => OutputTarget::Caption(PathBuf::from(path)),
+            _ => {
+                return Err(format!(
+                    "invalid output '{s}': expected [CHANNEL=]device:NAME, [CHANNEL=]net:URL, \
+                     [CHANNEL=]obs:NAME, [CHANNEL=]rec:PATH, [CHANNEL=]srt:PATH or \
+                     [CHANNEL=]txt:PATH"
+                ))
+            }
+        };
+        Ok(Self {
+            channel: channel.map(|c| c.to_lowercase()),
+            target,
+        })
+    }
+}
+
+impl OutputTarget {
+    /// The kind of an output only one channel can write to; audio devices
+    /// mix the channels played on them.
+    fn exclusive_kind(&self) -> Option<&'static str> {
+        match self {
+            Self::Device(_) => None,
+            Self::Network(_) => Some("net"),
+            Self::Obs(_) => Some("obs"),
+            Self::Record(_) => Some("rec"),
+            Self::Srt(_) => Some("srt"),
+            Self::Caption(_) => Some("txt"),
+        }
+    }
+}
+
+/// Rejects routes for channels that are not being translated, and files,
+/// streams or OBS outputs that several channels would write over each other.
+pub fn validate(routes: &[OutputRoute], channels: &[String]) -> anyhow::Result<()> {
+    for (i, route) in routes.iter().enumerate() {
+        if let Some(c) = &route.channel {
+            if !channels.iter().any(|ch| ch.eq_ignore_ascii_case(c)) {
+                anyhow::bail!(
+                    "--output names channel '{c}', which is not one of the --channel values"
+                );
+            }
+        }
+        let Some(kind) = route.target.exclusive_kind() else {
+            continue;
+        };
+        let Some(channel) = &route.channel else {
+            if channels.len() > 1 {
+                anyhow::bail!("with several channels, {kind} outputs need a CHANNEL= prefix");
+            }
+            continue;
+        };
+        let shared = routes[..i].iter().find_map(|r| match &r.channel {
+            Some(other) if other != channel && r.target == route.target => Some(other),
+            _ => None,
+        });
+        if let Some(other) = shared {
+            anyhow::bail!(
+                "channels '{other}' and '{channel}' would write to the same {kind} output"
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Applies the routes for `channel` (plus unprefixed ones) to `output`;
+/// channel-specific routes win over unprefixed ones.
+pub fn apply(routes: &[OutputRoute], channel: Option<&str>, output: &mut OutputConfig) {
+    let general = routes.iter().filter(|r| r.channel.is_none());
+    let specific = routes.iter().filter(
+        |r| matches!((&r.channel, channel), (Some(c), Some(ch)) if c.eq_ignore_ascii_case(ch)),
+    );
+    for route in general.chain(specific) {
+        match &route.target {
+            OutputTarget::Device(name) => output.device = Some(name.clone()),
+            OutputTarget::Network(target) => output.network = Some(target.clone()),
+            OutputTarget::Obs(name) => output.obs_audio = Some(name.clone()),
+            OutputTarget::Record(path) => output.recording = Some(path.clone()),
+            OutputTarget::Srt(path) => output.subtitles = Some(path.clone()),
+            OutputTarget::Caption(path) => output.caption_file = Some(path.clone()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn routes(values: &[&str]) -> Vec<OutputRoute> {
+        values.iter().map(|v| v.parse().unwrap()).collect()
+    }
+
+    fn channels(names: &[&str]) -> Vec<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    #[test]
+    fn parses_channel_prefixes() {
+        let route: OutputRoute = "Alpha=device:Speakers (USB=2)".parse().unwrap();
+        assert_eq!(route.channel.as_deref(), Some("alpha"));
+        assert_eq!(
+            route.target,
+            OutputTarget::Device("Speakers (USB=2)".to_owned())
+        );
+        assert!("rec:dub.flac".parse::<OutputRoute>().is_err());
+        assert!("alpha=stdout:x".parse::<OutputRoute>().is_err());
+    }
+
+    #[test]
+    fn validates_every_route() {
+        let two = channels(&["alpha", "beta"]);
+        assert!(validate(&routes(&["device:Speakers", "alpha=srt:a.srt"]), &two).is_ok());
+        assert!(validate(&routes(&["gamma=device:Speakers"]), &two).is_err());
+        assert!(validate(&routes(&["alpha=srt:a.srt", "txt:all.txt"]), &two).is_err());
+        assert!(validate(&routes(&["txt:all.txt"]), &channels(&["alpha"])).is_ok());
+        assert!(validate(&routes(&["alpha=srt:a.srt", "beta=srt:a.srt"]), &two).is_err());
+        assert!(validate(&routes(&["alpha=srt:a.srt", "alpha=srt:a.srt"]), &two).is_ok());
+    }
+
+    #[test]
+    fn channel_routes_win_over_shared_ones() {
+        let routes = routes(&["beta=device:Headset", "device:Speakers", "beta=srt:b.srt"]);
+
+        let mut alpha = OutputConfig::default();
+        apply(&routes, Some("alpha"), &mut alpha);
+        assert_eq!(alpha.device.as_deref(), Some("Speakers"));
+        assert_eq!(alpha.subtitles, None);
+
+        let mut beta = OutputConfig::default();
+        apply(&routes, Some("Beta"), &mut beta);
+        assert_eq!(beta.device.as_deref(), Some("Headset"));
+        assert_eq!(beta.subtitles, Some(PathBuf::from("b.srt")));
+
+        // A --url or --replay session takes only the unprefixed routes.
+        let mut single = OutputConfig::default();
+        apply(&routes, None, &mut single);
+        assert_eq!(single.device.as_deref(), Some("Speakers"));
+    }
+}
@@ -0,0 +1,204 @@
+//! `--service`: run unattended under systemd or a Windows service wrapper.
+//!
+//! The session is started again whenever the stream ends or the pipeline
+//! fails, after a backoff, and the process exits cleanly on SIGTERM/SIGINT
+//! (console close or shutdown events on Windows). Under a systemd unit with
+//! `Type=notify`, readiness, a one-line status and watchdog pings are sent
+//! over `$NOTIFY_SOCKET`; without one those notifications do nothing.
+
+use std::time::{Duration, Instant};
+use twitch_translator_core::config::AppConfig;
+use twitch_translator_core::status::PipelineStatus;
+
+const INITIAL_BACKOFF: Duration = Duration::from_secs(5);
+const MAX_BACKOFF: Duration = Duration::from_secs(5 * 60);
+/// A session that ran this long was healthy; the next restart starts over at
+/// the initial backoff.
+const HEALTHY_AFTER: Duration = Duration::from_secs(10 * 60);
+
+/// Runs sessions for `cfg` back to back until the service is told to stop.
+pub async fn run(cfg: AppConfig, global_hotkeys: bool, now_playing: bool) -> anyhow::Result<()> {
+    let status = PipelineStatus::new();
+    status.set_caption_history(Duration::from_secs(cfg.output.caption_history_secs));
+    let control = crate::new_control(&cfg);
+    crate::enable_clips(&cfg, &control);
+    let _live_config = crate::reload::watch(&cfg, vec![control.clone()])?;
+    if global_hotkeys {
+        crate::hotkeys::spawn(control.clone())?;
+    }
+    if now_playing {
+        crate::now_playing::spawn(&status, control.clone())?;
+    }
+    if let Some(addr) = cfg.status_addr {
+        crate::spawn_status_server(addr, status.clone(), control.clone()).await?;
+    }
+    crate::spawn_subtitles(&cfg, &status).await?;
+    crate::spawn_session_db(&cfg, &status)?;
+    crate::spawn_alerts(&cfg, &status)?;
+    crate::spawn_summaries(&cfg, &status)?;
+
+    let notifier = Notifier::from_env();
+    if let Some(every) = notifier.watchdog_interval() {
+        let notifier = notifier.clone();
+        tokio::spawn(async move {
+            let mut ticks = tokio::time::interval(every);
+            loop {
+                ticks.tick().await;
+                notifier.send("WATCHDOG=1");
+            }
+        });
+    }
+    notifier.send("READY=1");
+
+    let label = crate::input_label(&cfg.input);
+    let shutdown = shutdown_signal();
+    tokio::pin!(shutdown);
+    let mut backoff = INITIAL_BACKOFF;
+    loop {
+        notifier.status(&format!("translating {label}"));
+        let started = Instant::now();
+        let result = tokio::select! {
+            result = crate::run_session(cfg.clone(), status.clone(), control.clone()) => result,
+            signal = &mut shutdown => return stop(&notifier, signal?, &cfg, &status),
+        };
+        status.set_stream_online(false);
+
+        if started.elapsed() >= HEALTHY_AFTER {
+            backoff = INITIAL_BACKOFF;
+        }
+        let reason = match &result {
+            Ok(()) => "stream ended".to_owned(),
+            Err(e) => format!("{e:#}"),
+        };
+        match result {
+            Ok(()) => tracing::info!(input = %label, "stream ended, restarting the session"),
+            Err(_) => tracing::error!(
+                input = %label,
+                error = %reason,
+                retry_in_secs = backoff.as_secs(),
+                "session failed, restarting"
+            ),
+        }
+        notifier.status(&format!("restarting in {}s: {reason}", backoff.as_secs()));
+        tokio::select! {
+            _ = tokio::time::sleep(backoff) => {}
+            signal = &mut shutdown => return stop(&notifier, signal?, &cfg, &status),
+        }
+        backoff = backoff.saturating_mul(2).min(MAX_BACKOFF);
+    }
+}
+
+/// Prints the report, which covers every session since the service started.
+fn stop(
+    notifier: &Notifier,
+    signal: &str,
+    cfg: &AppConfig,
+    status: &PipelineStatus,
+) -> anyhow::Result<()> {
+    tracing::info!(signal, "shutting down");
+    notifier.send("STOPPING=1");
+    let label = crate::input_label(&cfg.input);
+    crate::print_reports(vec![(label, status.report())], cfg.output.report.as_deref());
+    Ok(())
+}
+
+/// Resolves with the name of the first stop request the service manager sends.
+#[cfg(unix)]
+async fn shutdown_signal() -> std::io::Result<&'static str> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut terminate = signal(SignalKind::terminate())?;
+    let mut interrupt = signal(SignalKind::interrupt())?;
+    Ok(tokio::select! {
+        _ = terminate.recv() => "SIGTERM",
+        _ = interrupt.recv() => "SIGINT",
+    })
+}
+
+/// Resolves with the name of the first stop request the service wrapper sends.
+#[cfg(windows)]
+async fn shutdown_signal() -> std::io::Result<&'static str> {
+    use tokio::signal::windows::{ctrl_break, ctrl_c, ctrl_close, ctrl_shutdown};
+
+    let mut c = ctrl_c()?;
+    let mut brk = ctrl_break()?;
+    let mut close = ctrl_close()?;
+    let mut shutdown = ctrl_shutdown()?;
+    Ok(tokio::select! {
+        _ = c.recv() => "CTRL_C",
+        _ = brk.recv() => "CTRL_BREAK",
+        _ = close.recv() => "CTRL_CLOSE",
+        _ = shutdown.recv() => "CTRL_SHUTDOWN",
+    })
+}
+
+/// `sd_notify` messages to systemd, if it started us with `$NOTIFY_SOCKET`.
+#[derive(Clone, Default)]
+struct Notifier {
+    #[cfg(unix)]
+    socket: Option<std::ffi::OsString>,
+    watchdog: Option<Duration>,
+}
+
+impl Notifier {
+    fn from_env() -> Self {
+        // The watchdog applies to us only when WATCHDOG_PID is unset or ours.
+        let ours = std::env::var("WATCHDOG_PID")
+            .map_or(true, |pid| pid.trim() == std::process::id().to_string());
+        let watchdog = std::env::var("WATCHDOG_USEC")
+            .ok()
+            .and_then(|usec| usec.trim().parse().ok())
+            .filter(|&usec: &u64| ours && usec > 0)
+            .map(Duration::from_micros);
+        Self {
+            #[cfg(unix)]
+            socket: std::env::var_os("NOTIFY_SOCKET").filter(|s| !s.is_empty()),
+            watchdog,
+        }
+    }
+
+    /// How often to ping the watchdog: twice per timeout, as systemd advises.
+    fn watchdog_interval(&self) -> Option<Duration> {
+        self.watchdog.map(|timeout| timeout / 2)
+    }
+
+    fn status(&self, status: &str) {
+        // STATUS is a single line.
+        self.send(&format!("STATUS={}", status.replace('\n', " ")));
+    }
+
+    #[cfg(unix)]
+    fn send(&self, message: &str) {
+        let Some(socket) = &self.socket else {
+            return;
+        };
+        if let Err(e) = notify(socket, message) {
+            tracing::debug!(error = %e, "failed to notify systemd");
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn send(&self, _message: &str) {}
+}
+
+#[cfg(unix)]
+fn notify(socket: &std::ffi::OsStr, message: &str) -> std::io::Result<()> {
+    use std::os::unix::net::UnixDatagram;
+
+    let datagram = UnixDatagram::unbound()?;
+    // A leading '@' names a socket in the abstract namespace.
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::linux::net::SocketAddrExt;
+        use std::os::unix::ffi::OsStrExt;
+        use std::os::unix::net::SocketAddr;
+
+        if let Some(name) = socket.as_bytes().strip_prefix(b"@") {
+            let addr = SocketAddr::from_abstract_name(name)?;
+            datagram.send_to_addr(message.as_bytes(), &addr)?;
+            return Ok(());
+        }
+    }
+    datagram.send_to(message.as_bytes(), socket)?;
+    Ok(())
+}
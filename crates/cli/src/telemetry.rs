@@ -0,0 +1,67 @@
+//! `--otlp-endpoint`: export pipeline spans to an OpenTelemetry collector
+//! (Jaeger, Grafana Tempo, ...) over OTLP/HTTP. Each segment is one trace,
+//! with a child span per stage it passed through.
+
+#[cfg(feature = "otel")]
+use tracing_subscriber::Registry;
+
+/// Keeps the exporter running; dropping it flushes spans not yet sent.
+#[cfg_attr(not(feature = "otel"), allow(dead_code))]
+pub struct Telemetry {
+    #[cfg(feature = "otel")]
+    provider: opentelemetry_sdk::trace::SdkTracerProvider,
+}
+
+/// Layer sending spans to `endpoint`, e.g. `http://localhost:4318`.
+#[cfg(feature = "otel")]
+pub fn otlp_layer(
+    endpoint: &str,
+) -> anyhow::Result<(impl tracing_subscriber::Layer<Registry>, Telemetry)> {
+    use anyhow::Context;
+    use opentelemetry::trace::TracerProvider as _;
+    use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+    use opentelemetry_sdk::trace::SdkTracerProvider;
+    use opentelemetry_sdk::Resource;
+
+    // Collectors take traces under /v1/traces; accept the bare base URL too.
+    let endpoint = endpoint.trim_end_matches('/');
+    let endpoint = if endpoint.ends_with("/v1/traces") {
+        endpoint.to_owned()
+    } else {
+        format!("{endpoint}/v1/traces")
+    };
+    let exporter = SpanExporter::builder()
+        .with_http()
+        .with_endpoint(&endpoint)
+        .build()
+        .with_context(|| format!("invalid --otlp-endpoint {endpoint}"))?;
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(
+            Resource::builder()
+                .with_service_name(env!("CARGO_PKG_NAME"))
+                .build(),
+        )
+        .build();
+    let tracer = provider.tracer(env!("CARGO_PKG_NAME"));
+    Ok((
+        tracing_opentelemetry::layer().with_tracer(tracer),
+        Telemetry { provider },
+    ))
+}
+
+#[cfg(not(feature = "otel"))]
+pub fn otlp_layer(
+    _endpoint: &str,
+) -> anyhow::Result<(tracing_subscriber::layer::Identity, Telemetry)> {
+    anyhow::bail!("--otlp-endpoint needs a build with --features otel")
+}
+
+impl Drop for Telemetry {
+    fn drop(&mut self) {
+        #[cfg(feature = "otel")]
+        if let Err(e) = self.provider.shutdown() {
+            eprintln!("failed to flush trace spans: {e}");
+        }
+    }
+}
@@ -0,0 +1,437 @@
+//! Interactive terminal frontend (`--tui`).
+//!
+//! Renders a rolling view of transcripts and translations next to per-stage
+//! queue depths and latencies, and maps hotkeys onto the pipeline's
+//! [`ControlHandle`]. Tracing output is suppressed while the TUI owns the screen.
+
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Paragraph, Row, Table};
+use ratatui::{DefaultTerminal, Frame};
+use std::collections::VecDeque;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc};
+use twitch_translator_core::clip::ClipError;
+use twitch_translator_core::config::TargetLang;
+use twitch_translator_core::control::ControlHandle;
+use twitch_translator_core::status::{PipelineEvent, PipelineStatus, StatusSnapshot};
+
+const MAX_ENTRIES: usize = 500;
+const TICK: Duration = Duration::from_millis(250);
+const KEY_POLL: Duration = Duration::from_millis(100);
+
+enum Entry {
+    Transcript(String),
+    Translation { text: String, target_lang: String },
+    Gloss(String),
+    Notice(String),
+    Alert(String),
+}
+
+struct App {
+    title: String,
+    status: PipelineStatus,
+    control: ControlHandle,
+    entries: VecDeque<Entry>,
+    snapshot: StatusSnapshot,
+    lang_input: Option<String>,
+    /// Results of background work (clip saves) to show as notices.
+    notices: mpsc::UnboundedSender<String>,
+    quit: bool,
+}
+
+impl App {
+    fn new(
+        title: String,
+        status: PipelineStatus,
+        control: ControlHandle,
+        notices: mpsc::UnboundedSender<String>,
+    ) -> Self {
+        let snapshot = status.snapshot();
+        Self {
+            title,
+            status,
+            control,
+            entries: VecDeque::new(),
+            snapshot,
+            lang_input: None,
+            notices,
+            quit: false,
+        }
+    }
+
+    fn push(&mut self, entry: Entry) {
+        if self.entries.len() == MAX_ENTRIES {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+
+    fn on_event(&mut self, event: PipelineEvent) {
+        match event {
+            PipelineEvent::Transcript { text, .. } => self.push(Entry::Transcript(text)),
+            PipelineEvent::Translation {
+                text, target_lang, ..
+            } => self.push(Entry::Translation { text, target_lang }),
+            PipelineEvent::StreamMetadata(metadata) => self.push(Entry::Notice(format!(
+                "{}: {}",
+                metadata.game, metadata.title
+            ))),
+            PipelineEvent::Gloss { glosses, .. } => {
+                let line: Vec<String> = glosses
+                    .iter()
+                    .map(|g| format!("{} = {}", g.source, g.gloss))
+                    .collect();
+                self.push(Entry::Gloss(line.join(" · ")))
+            }
+            PipelineEvent::Summary { text, window_secs } => self.push(Entry::Notice(format!(
+                "last {} min: {text}",
+                window_secs / 60
+            ))),
+            PipelineEvent::AsrOverloaded { rtf } => self.push(Entry::Notice(format!(
+                "ASR is slower than real time (RTF {rtf:.2}); delay is growing"
+            ))),
+            PipelineEvent::Skipped {
+                stage,
+                sequence,
+                age_ms,
+            } => self.push(Entry::Notice(format!(
+                "{stage:?} skipped chunk {sequence}, {:.1}s old",
+                age_ms as f64 / 1000.0
+            ))),
+            PipelineEvent::DeadlineExceeded { stage, deadline_ms } => self.push(Entry::Notice(
+                format!("{stage:?} took longer than {deadline_ms} ms"),
+            )),
+            PipelineEvent::AsrModelSwitched { to, .. } => {
+                self.push(Entry::Notice(format!("switched Whisper model to {to}")))
+            }
+            PipelineEvent::StageRestarted { stage, reason, .. } => self.push(Entry::Notice(
+                format!("{stage:?} stage restarted: {reason}"),
+            )),
+            PipelineEvent::TtsVariant { backend, .. } => {
+                self.push(Entry::Notice(format!("voiced by {backend}")))
+            }
+            PipelineEvent::VariantSwitched { to_bandwidth, .. } => {
+                self.push(Entry::Notice(format!(
+                    "Network too slow; switched to a {} kbps stream",
+                    to_bandwidth / 1000
+                )))
+            }
+            PipelineEvent::NonSpeech { sound } => self.push(Entry::Notice(sound.label())),
+            PipelineEvent::KeywordMatched {
+                keyword,
+                translated,
+                ..
+            } => self.push(Entry::Alert(if translated {
+                format!("\"{keyword}\" in the translation")
+            } else {
+                format!("\"{keyword}\" was said")
+            })),
+        }
+    }
+
+    fn on_key(&mut self, key: KeyEvent) {
+        if key.kind != KeyEventKind::Press {
+            return;
+        }
+
+        if let Some(input) = self.lang_input.as_mut() {
+            match key.code {
+                KeyCode::Enter => {
+                    let value = input.trim().to_owned();
+                    self.lang_input = None;
+                    match TargetLang::new(value) {
+                        Ok(lang) => {
+                            self.push(Entry::Notice(format!(
+                                "target language -> {}",
+                                lang.as_str()
+                            )));
+                            self.control.set_target_lang(lang);
+                        }
+                        Err(e) => self.push(Entry::Notice(e.to_string())),
+                    }
+                }
+                KeyCode::Esc => self.lang_input = None,
+                KeyCode::Backspace => {
+                    input.pop();
+                }
+                KeyCode::Char(c) => input.push(c),
+                _ => {}
+            }
+            return;
+        }
+
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => self.quit = true,
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => self.quit = true,
+            KeyCode::Char('m') => {
+                let muted = self.control.toggle_muted();
+                self.push(Entry::Notice(
+                    if muted { "tts muted" } else { "tts unmuted" }.into(),
+                ));
+            }
+            KeyCode::Char('p') => {
+                let paused = self.control.toggle_paused();
+                self.push(Entry::Notice(
+                    if paused {
+                        "playback paused"
+                    } else {
+                        "playback resumed"
+                    }
+                    .into(),
+                ));
+            }
+            KeyCode::Char('l') => self.lang_input = Some(String::new()),
+            KeyCode::Char('c') => self.save_clip(),
+            _ => {}
+        }
+    }
+
+    fn save_clip(&self) {
+        let clips = self.control.clips().clone();
+        let notices = self.notices.clone();
+        tokio::spawn(async move {
+            let notice = match clips.save().await {
+                Ok(saved) => format!("clip saved to {}", saved.audio.display()),
+                Err(ClipError::Disabled) => "clip capture is off; start with --clip-dir".into(),
+                Err(e) => format!("clip failed: {e}"),
+            };
+            let _ = notices.send(notice);
+        });
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let [header, body, footer] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Min(3),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        let [feed, stages] =
+            Layout::horizontal([Constraint::Min(30), Constraint::Length(46)]).areas(body);
+
+        frame.render_widget(Paragraph::new(self.header_line()), header);
+        self.draw_feed(frame, feed);
+        frame.render_widget(self.stage_table(), stages);
+        frame.render_widget(Paragraph::new(self.footer_line()), footer);
+    }
+
+    fn header_line(&self) -> Line<'_> {
+        let mut spans = vec![Span::styled(
+            format!(" {} ", self.title),
+            Style::default().add_modifier(Modifier::BOLD),
+        )];
+        spans.push(if self.snapshot.stream_online {
+            Span::styled(" LIVE ", Style::default().fg(Color::Black).bg(Color::Green))
+        } else {
+            Span::styled(
+                " OFFLINE ",
+                Style::default().fg(Color::Black).bg(Color::DarkGray),
+            )
+        });
+        if let Some(stream) = &self.snapshot.stream {
+            spans.push(Span::raw(format!(
+                " {} | {} viewers ",
+                stream.game, stream.viewer_count
+            )));
+        }
+        spans.push(Span::raw(format!(
+            " -> {} ",
+            self.control.target_lang().as_str()
+        )));
+        if self.control.is_muted() {
+            spans.push(Span::styled(
+                " MUTED ",
+                Style::default().fg(Color::Black).bg(Color::Yellow),
+            ));
+        }
+        if self.control.is_paused() {
+            spans.push(Span::styled(
+                " PAUSED ",
+                Style::default().fg(Color::Black).bg(Color::Yellow),
+            ));
+        }
+        if self.snapshot.tts_fallback_active {
+            spans.push(Span::styled(
+                " TTS FALLBACK ",
+                Style::default().fg(Color::Black).bg(Color::Red),
+            ));
+        }
+        if let Some(cost) = &self.snapshot.cost {
+            match cost.budget_usd {
+                Some(budget) if cost.spent_usd >= budget => spans.push(Span::styled(
+                    format!(" ${:.2}/${budget:.2} ", cost.spent_usd),
+                    Style::default().fg(Color::Black).bg(Color::Red),
+                )),
+                Some(budget) => {
+                    spans.push(Span::raw(format!(" ${:.2}/${budget:.2} ", cost.spent_usd)))
+                }
+                None => spans.push(Span::raw(format!(" ${:.2} ", cost.spent_usd))),
+            }
+        }
+        Line::from(spans)
+    }
+
+    fn footer_line(&self) -> Line<'_> {
+        match &self.lang_input {
+            Some(input) => Line::from(format!(
+                " target language: {input}_   (Enter apply, Esc cancel)"
+            )),
+            None => Line::from(" q quit   m mute   p pause   l language   c clip")
+                .style(Style::default().fg(Color::DarkGray)),
+        }
+    }
+
+    fn draw_feed(&self, frame: &mut Frame, area: Rect) {
+        let block = Block::default().borders(Borders::ALL).title(" transcript ");
+        let visible = usize::from(block.inner(area).height);
+        let lines: Vec<Line> = self
+            .entries
+            .iter()
+            .rev()
+            .take(visible)
+            .rev()
+            .map(|entry| match entry {
+                Entry::Transcript(text) => Line::from(text.as_str()),
+                Entry::Translation { text, target_lang } => Line::from(vec![
+                    Span::styled(
+                        format!("  [{target_lang}] "),
+                        Style::default().fg(Color::DarkGray),
+                    ),
+                    Span::styled(text.as_str(), Style::default().fg(Color::Cyan)),
+                ]),
+                Entry::Gloss(text) => {
+                    Line::styled(format!("    {text}"), Style::default().fg(Color::DarkGray))
+                }
+                Entry::Notice(text) => Line::styled(
+                    format!("* {text}"),
+                    Style::default()
+                        .fg(Color::Yellow)
+                        .add_modifier(Modifier::ITALIC),
+                ),
+                Entry::Alert(text) => Line::styled(
+                    format!("! {text}"),
+                    Style::default()
+                        .fg(Color::Black)
+                        .bg(Color::Yellow)
+                        .add_modifier(Modifier::BOLD),
+                ),
+            })
+            .collect();
+        frame.render_widget(Paragraph::new(lines).block(block), area);
+    }
+
+    fn stage_table(&self) -> Table<'_> {
+        let rows = self.snapshot.stages.iter().map(|s| {
+            Row::new(vec![
+                s.stage.as_str().to_owned(),
+                s.queue_depth.to_string(),
+                format!("{:.0}", s.last_latency_ms),
+                s.processed.to_string(),
+                s.errors.to_string(),
+            ])
+        });
+        Table::new(
+            rows,
+            [
+                Constraint::Length(10),
+                Constraint::Length(6),
+                Constraint::Length(8),
+                Constraint::Length(8),
+                Constraint::Length(7),
+            ],
+        )
+        .header(
+            Row::new(vec!["stage", "queue", "ms", "done", "errors"])
+                .style(Style::default().add_modifier(Modifier::BOLD)),
+        )
+        .block(Block::default().borders(Borders::ALL).title(" pipeline "))
+    }
+}
+
+/// Runs `pipeline` in the background while the TUI owns the terminal.
+///
+/// Returns when the user quits (the pipeline is aborted) or when the pipeline
+/// finishes on its own, in which case its result is returned.
+pub async fn run<F>(
+    title: String,
+    status: PipelineStatus,
+    control: ControlHandle,
+    pipeline: F,
+) -> anyhow::Result<()>
+where
+    F: Future<Output = anyhow::Result<()>> + Send + 'static,
+{
+    let mut pipeline = tokio::spawn(pipeline);
+    let mut events = status.subscribe();
+    let (key_tx, mut key_rx) = mpsc::unbounded_channel();
+    let stop_keys = Arc::new(AtomicBool::new(false));
+    spawn_key_reader(key_tx, Arc::clone(&stop_keys));
+
+    let mut terminal = ratatui::init();
+    let (notice_tx, mut notice_rx) = mpsc::unbounded_channel();
+    let mut app = App::new(title, status, control, notice_tx);
+    let mut tick = tokio::time::interval(TICK);
+
+    let result = loop {
+        if let Err(e) = draw(&mut terminal, &app) {
+            break Err(e);
+        }
+        if app.quit {
+            pipeline.abort();
+            break Ok(());
+        }
+
+        tokio::select! {
+            _ = tick.tick() => app.snapshot = app.status.snapshot(),
+            Some(key) = key_rx.recv() => app.on_key(key),
+            Some(notice) = notice_rx.recv() => app.push(Entry::Notice(notice)),
+            event = events.recv() => match event {
+                Ok(event) => app.on_event(event),
+                Err(broadcast::error::RecvError::Lagged(n)) => {
+                    app.push(Entry::Notice(format!("{n} events skipped")));
+                }
+                Err(broadcast::error::RecvError::Closed) => {}
+            },
+            joined = &mut pipeline => {
+                break match joined {
+                    Ok(result) => result,
+                    Err(e) => Err(anyhow::anyhow!("pipeline task failed: {e}")),
+                };
+            }
+        }
+    };
+
+    stop_keys.store(true, Ordering::Relaxed);
+    ratatui::restore();
+    result
+}
+
+fn draw(terminal: &mut DefaultTerminal, app: &App) -> anyhow::Result<()> {
+    terminal.draw(|frame| app.draw(frame))?;
+    Ok(())
+}
+
+/// Reads terminal key events on a dedicated thread; crossterm's reader is blocking.
+fn spawn_key_reader(tx: mpsc::UnboundedSender<KeyEvent>, stop: Arc<AtomicBool>) {
+    std::thread::spawn(move || {
+        while !stop.load(Ordering::Relaxed) {
+            match event::poll(KEY_POLL) {
+                Ok(true) => {
+                    if let Ok(Event::Key(key)) = event::read() {
+                        if tx.send(key).is_err() {
+                            break;
+                        }
+                    }
+                }
+                Ok(false) => {}
+                Err(_) => break,
+            }
+        }
+    });
+}
@@ -0,0 +1,204 @@
+//! `transcribe-vod` subcommand: runs a whole VOD through the session's
+//! backends as fast as they go and writes subtitles, and optionally a dubbed
+//! track, to a directory.
+
+use clap::Args;
+use std::path::PathBuf;
+#[cfg(feature = "whisper-rs")]
+use twitch_translator_core::asr::{AsrBackend, RemoteAsrBackend, WhisperAsrBackend};
+#[cfg(feature = "whisper-rs")]
+use twitch_translator_core::config::HttpService;
+use twitch_translator_core::config::{AppConfig, AsrProvider, LlmConfig};
+#[cfg(feature = "whisper-rs")]
+use twitch_translator_core::decode::FfmpegAudioDecoder;
+#[cfg(feature = "whisper-rs")]
+use twitch_translator_core::ingest::{TwitchHlsIngestor, TwitchIngestOptions};
+#[cfg(feature = "whisper-rs")]
+use twitch_translator_core::summary::ChatSummarizer;
+#[cfg(feature = "whisper-rs")]
+use twitch_translator_core::translate::DeepLTranslator;
+#[cfg(feature = "whisper-rs")]
+use twitch_translator_core::tts::{
+    ElevenLabsTtsClient, FallbackTtsClient, PiperTtsClient, Respellings, TtsClient,
+};
+#[cfg(feature = "whisper-rs")]
+use twitch_translator_core::vod::VodJob;
+use twitch_translator_core::vod::{DubAlign, VodReport};
+
+#[derive(Args, Debug)]
+pub struct VodArgs {
+    /// VOD to process: a Twitch URL such as https://www.twitch.tv/videos/ID,
+    /// or the URL of a finished HLS playlist
+    #[arg(long)]
+    pub url: String,
+
+    /// Directory for source.srt, <lang>.srt and dub-<lang>.wav
+    #[arg(long, value_name = "DIR")]
+    out: PathBuf,
+
+    /// Also speak the translations into a dubbed audio track as long as the VOD
+    #[arg(long)]
+    dub: bool,
+
+    /// Start each dubbed line where speech begins in the original (speech),
+    /// where its segment begins (segment), or right after the line before,
+    /// leaving out the silence between lines (packed)
+    #[arg(long, value_name = "speech|segment|packed", default_value = "speech")]
+    dub_align: DubAlign,
+
+    /// Also write chapters.txt: YouTube-style chapter timestamps with titles
+    /// in --target-lang, found by the --llm-url model
+    #[arg(long)]
+    chapters: bool,
+
+    /// Print the report as JSON
+    #[arg(long)]
+    json: bool,
+}
+
+pub async fn run(args: VodArgs, cfg: AppConfig, llm: LlmConfig) -> anyhow::Result<()> {
+    if cfg.asr.provider != AsrProvider::Whisper {
+        anyhow::bail!(
+            "--asr {} transcribes in real time; transcribe-vod runs Whisper locally or on --asr-endpoint",
+            cfg.asr.provider
+        );
+    }
+    if args.dub && cfg.api_keys.deepl.is_none() {
+        anyhow::bail!("--dub needs a DeepL API key to translate");
+    }
+    tracing::info!(url = %args.url, out = %args.out.display(), "processing VOD");
+
+    let report = run_backends(&args, &cfg, &llm).await?;
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+    let secs = report.audio_duration.as_secs();
+    println!(
+        "{} segments, {}:{:02}:{:02} of audio in {:.0} s{}",
+        report.segments,
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60,
+        report.elapsed.as_secs_f64(),
+        report
+            .real_time_factor()
+            .map(|rtf| format!(" ({rtf:.2}x real time)"))
+            .unwrap_or_default()
+    );
+    println!(
+        "{} lines, {} chapters, {} skipped after errors",
+        report.cues, report.chapters, report.skipped
+    );
+    for file in &report.files {
+        println!("wrote {}", file.display());
+    }
+    Ok(())
+}
+
+#[cfg(feature = "whisper-rs")]
+async fn run_backends(
+    args: &VodArgs,
+    cfg: &AppConfig,
+    llm: &LlmConfig,
+) -> anyhow::Result<VodReport> {
+    match &cfg.asr.endpoint {
+        Some(_) => {
+            let transport = crate::transport(cfg, HttpService::Asr)?;
+            with_asr(
+                args,
+                cfg,
+                llm,
+                RemoteAsrBackend::from_config(&cfg.asr, transport)?,
+            )
+            .await
+        }
+        None => with_asr(args, cfg, llm, WhisperAsrBackend::from_config(&cfg.asr)?).await,
+    }
+}
+
+#[cfg(feature = "whisper-rs")]
+async fn with_asr<A: AsrBackend>(
+    args: &VodArgs,
+    cfg: &AppConfig,
+    llm: &LlmConfig,
+    asr: A,
+) -> anyhow::Result<VodReport> {
+    let translate = match &cfg.api_keys.deepl {
+        Some(key) => {
+            let mut translator = DeepLTranslator::new(key.expose().to_string())
+                .with_transport(crate::transport(cfg, HttpService::DeepL)?);
+            if let Some(tokens) = cfg.text.protected_tokens() {
+                translator = translator.with_protected_tokens(tokens);
+            }
+            Some(translator)
+        }
+        None => {
+            tracing::warn!("DEEPL_API_KEY not set; writing source subtitles only");
+            None
+        }
+    };
+    let chapters = if args.chapters {
+        let transport = crate::transport(cfg, HttpService::Llm)?;
+        Some(ChatSummarizer::from_config(llm).with_transport(transport))
+    } else {
+        None
+    };
+
+    if !args.dub {
+        return process(args, cfg, asr, translate, chapters, None::<PiperTtsClient>).await;
+    }
+    cfg.piper.validate()?;
+    let local = PiperTtsClient::from_config(&cfg.piper)
+        .with_respellings(Respellings::new(&cfg.pronunciation.respellings));
+    match &cfg.api_keys.elevenlabs {
+        Some(key) => {
+            let primary = ElevenLabsTtsClient::new(key.expose().to_string())
+                .with_transport(crate::transport(cfg, HttpService::ElevenLabs)?)
+                .with_pronunciation_dictionaries(&cfg.pronunciation.elevenlabs_dictionaries);
+            let tts = FallbackTtsClient::new(primary, local);
+            process(args, cfg, asr, translate, chapters, Some(tts)).await
+        }
+        None => process(args, cfg, asr, translate, chapters, Some(local)).await,
+    }
+}
+
+#[cfg(feature = "whisper-rs")]
+async fn process<A: AsrBackend, Ts: TtsClient>(
+    args: &VodArgs,
+    cfg: &AppConfig,
+    asr: A,
+    translate: Option<DeepLTranslator>,
+    chapters: Option<ChatSummarizer>,
+    tts: Option<Ts>,
+) -> anyhow::Result<VodReport> {
+    let ingest = TwitchHlsIngestor::new(
+        cfg.twitch.clone(),
+        cfg.input.clone(),
+        TwitchIngestOptions::from_config(&cfg.twitch),
+    )?
+    .with_transport(crate::transport(cfg, HttpService::Twitch)?);
+    let job = VodJob {
+        ingest,
+        decode: FfmpegAudioDecoder::default(),
+        asr,
+        translate,
+        tts,
+        voice: crate::new_control(cfg).voice(),
+        dub_align: args.dub_align,
+        target_lang: cfg.target_lang.clone(),
+        chapters,
+    };
+    Ok(job.run(&args.out).await?)
+}
+
+#[cfg(not(feature = "whisper-rs"))]
+async fn run_backends(
+    _args: &VodArgs,
+    _cfg: &AppConfig,
+    _llm: &LlmConfig,
+) -> anyhow::Result<VodReport> {
+    Err(anyhow::anyhow!(
+        "Whisper ASR feature is not enabled. Please install libclang and rebuild with --features whisper-rs"
+    ))
+}
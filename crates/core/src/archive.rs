@@ -0,0 +1,450 @@
+//! Searchable record of what was said in each session
+//!
+//! A [`TranscriptArchive`] writes every utterance of a session into an SQLite
+//! file of its own: when it was said, the transcript and its translation, how
+//! sure the recognizer was and how long both took. The text is indexed for
+//! full-text search: [`SessionArchive::search`] finds the utterances in such a
+//! file that mention some words, to look up when something came up on a past
+//! stream. With vectors from an embedding model stored alongside,
+//! [`SessionArchive::nearest`] finds them by meaning instead.
+
+use crate::embeddings::cosine_similarity;
+use crate::status::PipelineEvent;
+use rusqlite::{params, Connection, OpenFlags};
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast::{self, error::RecvError};
+
+/// Transcripts still waiting for their translation; older ones are taken to
+/// have failed.
+const PENDING_TRANSCRIPTS: usize = 32;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS session (
+        channel TEXT NOT NULL,
+        started_at_ms INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS utterances (
+        id INTEGER PRIMARY KEY,
+        at_ms INTEGER NOT NULL,
+        source TEXT NOT NULL,
+        translation TEXT,
+        target_lang TEXT,
+        confidence REAL,
+        latency_ms INTEGER NOT NULL
+    );
+    CREATE VIRTUAL TABLE IF NOT EXISTS utterances_fts USING fts5(
+        source, translation, content = 'utterances', content_rowid = 'id'
+    );
+    CREATE TRIGGER IF NOT EXISTS utterances_inserted AFTER INSERT ON utterances BEGIN
+        INSERT INTO utterances_fts (rowid, source, translation)
+        VALUES (new.id, new.source, new.translation);
+    END;
+    CREATE TRIGGER IF NOT EXISTS utterances_updated AFTER UPDATE ON utterances BEGIN
+        INSERT INTO utterances_fts (utterances_fts, rowid, source, translation)
+        VALUES ('delete', old.id, old.source, old.translation);
+        INSERT INTO utterances_fts (rowid, source, translation)
+        VALUES (new.id, new.source, new.translation);
+    END;
+";
+
+#[derive(thiserror::Error, Debug)]
+pub enum ArchiveError {
+    #[error("session database: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// One utterance found in a [`SessionArchive`]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Utterance {
+    pub channel: String,
+    /// When it was said, in milliseconds since the Unix epoch.
+    pub at_ms: u64,
+    /// The same in local time, as `YYYY-MM-DD HH:MM:SS`.
+    pub local_time: String,
+    /// How far into the session it was said.
+    pub offset: Duration,
+    pub source: String,
+    pub translation: Option<String>,
+    pub target_lang: Option<String>,
+    pub confidence: Option<f32>,
+    /// From the recognizer starting on the audio to the translation, or to
+    /// the transcript if it was not translated.
+    pub latency_ms: u64,
+}
+
+/// A transcript written before its translation
+struct Pending {
+    id: i64,
+    source: String,
+    at: Instant,
+    latency_ms: u64,
+}
+
+/// The session database being written. Writes block on the file, so
+/// [`TranscriptArchive::run`] belongs on a blocking thread.
+pub struct TranscriptArchive {
+    conn: Connection,
+    path: PathBuf,
+    pending: VecDeque<Pending>,
+}
+
+impl TranscriptArchive {
+    /// Starts a database for a new session of `channel` in `dir`, named
+    /// after the channel and when the session started, creating the
+    /// directory if needed.
+    pub fn create(dir: &Path, channel: &str) -> Result<Self, ArchiveError> {
+        std::fs::create_dir_all(dir)?;
+        let started_at_ms = now_ms();
+        let path = dir.join(format!(
+            "{}-{}.sqlite",
+            file_stem(channel),
+            started_at_ms / 1000
+        ));
+        let conn = Connection::open(&path)?;
+        // Lets the session be searched while it is being written.
+        conn.pragma_update_and_check(None, "journal_mode", "WAL", |_| Ok(()))?;
+        Self::init(conn, path, channel, started_at_ms)
+    }
+
+    fn init(
+        conn: Connection,
+        path: PathBuf,
+        channel: &str,
+        started_at_ms: u64,
+    ) -> Result<Self, ArchiveError> {
+        conn.execute_batch(SCHEMA)?;
+        conn.execute(
+            "INSERT INTO session (channel, started_at_ms) VALUES (?1, ?2)",
+            params![channel, started_at_ms as i64],
+        )?;
+        Ok(Self {
+            conn,
+            path,
+            pending: VecDeque::new(),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Writes a transcript, to be completed by its translation.
+    pub fn record_transcript(
+        &mut self,
+        text: &str,
+        confidence: Option<f32>,
+        latency_ms: u64,
+    ) -> Result<(), ArchiveError> {
+        self.conn.execute(
+            "INSERT INTO utterances (at_ms, source, confidence, latency_ms)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![now_ms() as i64, text, confidence, latency_ms as i64],
+        )?;
+        if self.pending.len() == PENDING_TRANSCRIPTS {
+            self.pending.pop_front();
+        }
+        self.pending.push_back(Pending {
+            id: self.conn.last_insert_rowid(),
+            source: text.to_owned(),
+            at: Instant::now(),
+            latency_ms,
+        });
+        Ok(())
+    }
+
+    /// Adds `text` to the transcript of `source_text` waiting longest for its
+    /// translation; transcripts before it were not translated. A translation
+    /// whose transcript was not written is ignored.
+    pub fn record_translation(
+        &mut self,
+        source_text: &str,
+        text: &str,
+        target_lang: &str,
+    ) -> Result<(), ArchiveError> {
+        let Some(index) = self.pending.iter().position(|p| p.source == source_text) else {
+            return Ok(());
+        };
+        self.pending.drain(..index);
+        let Some(pending) = self.pending.pop_front() else {
+            return Ok(());
+        };
+        let latency_ms = pending.latency_ms + pending.at.elapsed().as_millis() as u64;
+        self.conn.execute(
+            "UPDATE utterances SET translation = ?1, target_lang = ?2, latency_ms = ?3
+             WHERE id = ?4",
+            params![text, target_lang, latency_ms as i64, pending.id],
+        )?;
+        Ok(())
+    }
+
+    /// Writes the transcripts and translations published on `events` until
+    /// the session ends. Blocks the thread it runs on.
+    pub fn run(
+        mut self,
+        mut events: broadcast::Receiver<PipelineEvent>,
+    ) -> Result<(), ArchiveError> {
+        loop {
+            match events.blocking_recv() {
+                Ok(PipelineEvent::Transcript {
+                    text,
+                    confidence,
+                    latency_ms,
+                }) => self.record_transcript(&text, confidence, latency_ms)?,
+                Ok(PipelineEvent::Translation {
+                    source_text,
+                    text,
+                    target_lang,
+                }) => self.record_translation(&source_text, &text, &target_lang)?,
+                Ok(_) => {}
+                Err(RecvError::Lagged(n)) => {
+                    tracing::warn!(skipped = n, "session database fell behind; utterances lost");
+                }
+                Err(RecvError::Closed) => return Ok(()),
+            }
+        }
+    }
+}
+
+/// Vectors of the transcripts, by embedding model, added when a session is
+/// first searched by meaning.
+const EMBEDDINGS_SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS embeddings (
+        utterance_id INTEGER NOT NULL,
+        model TEXT NOT NULL,
+        vector BLOB NOT NULL,
+        PRIMARY KEY (utterance_id, model)
+    );
+";
+
+/// Columns [`SessionArchive::utterance`] reads, from `utterances u`.
+const UTTERANCE_COLUMNS: &str = "u.at_ms, u.source, u.translation, u.target_lang, u.confidence,
+    u.latency_ms, datetime(u.at_ms / 1000, 'unixepoch', 'localtime')";
+
+/// A session database opened for searching, which may still be written to
+pub struct SessionArchive {
+    conn: Connection,
+    channel: String,
+    started_at_ms: u64,
+}
+
+impl SessionArchive {
+    /// Opens the session database at `path`, which must exist.
+    pub fn open(path: &Path) -> Result<Self, ArchiveError> {
+        let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_WRITE)?;
+        conn.busy_timeout(Duration::from_secs(5))?;
+        conn.execute_batch(EMBEDDINGS_SCHEMA)?;
+        let (channel, started_at_ms): (String, i64) = conn.query_row(
+            "SELECT channel, started_at_ms FROM session LIMIT 1",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        Ok(Self {
+            conn,
+            channel,
+            started_at_ms: started_at_ms.max(0) as u64,
+        })
+    }
+
+    /// Utterances whose transcript or translation has all the words of
+    /// `query`, oldest first, at most `limit`.
+    pub fn search(&self, query: &str, limit: usize) -> Result<Vec<Utterance>, ArchiveError> {
+        let query = match_expression(query);
+        if query.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut statement = self.conn.prepare(&format!(
+            "SELECT {UTTERANCE_COLUMNS}
+             FROM utterances_fts JOIN utterances u ON u.id = utterances_fts.rowid
+             WHERE utterances_fts MATCH ?1
+             ORDER BY u.at_ms LIMIT ?2"
+        ))?;
+        let rows =
+            statement.query_map(params![query, limit as i64], |row| self.utterance(row, 0))?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    /// Transcripts that have no vector from `model` yet, with their ids.
+    pub fn unembedded(&self, model: &str) -> Result<Vec<(i64, String)>, ArchiveError> {
+        let mut statement = self.conn.prepare(
+            "SELECT u.id, u.source FROM utterances u
+             WHERE NOT EXISTS (
+                 SELECT 1 FROM embeddings e WHERE e.utterance_id = u.id AND e.model = ?1
+             )
+             ORDER BY u.id",
+        )?;
+        let rows = statement.query_map(params![model], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    /// Saves the `model` vectors of the transcripts with these ids.
+    pub fn store_embeddings(
+        &mut self,
+        model: &str,
+        vectors: &[(i64, Vec<f32>)],
+    ) -> Result<(), ArchiveError> {
+        let tx = self.conn.transaction()?;
+        {
+            let mut insert = tx.prepare(
+                "INSERT OR REPLACE INTO embeddings (utterance_id, model, vector)
+                 VALUES (?1, ?2, ?3)",
+            )?;
+            for (id, vector) in vectors {
+                let bytes: Vec<u8> = vector.iter().flat_map(|x| x.to_le_bytes()).collect();
+                insert.execute(params![id, model, bytes])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// The `limit` utterances whose `model` vectors are most like `query`,
+    /// most alike first, each with its cosine similarity.
+    pub fn nearest(
+        &self,
+        model: &str,
+        query: &[f32],
+        limit: usize,
+    ) -> Result<Vec<(Utterance, f32)>, ArchiveError> {
+        let mut statement = self.conn.prepare(&format!(
+            "SELECT e.vector, {UTTERANCE_COLUMNS}
+             FROM embeddings e JOIN utterances u ON u.id = e.utterance_id
+             WHERE e.model = ?1"
+        ))?;
+        let rows = statement.query_map(params![model], |row| {
+            let bytes: Vec<u8> = row.get(0)?;
+            let vector: Vec<f32> = bytes
+                .chunks_exact(4)
+                .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                .collect();
+            Ok((self.utterance(row, 1)?, cosine_similarity(query, &vector)))
+        })?;
+        let mut found = rows.collect::<Result<Vec<_>, _>>()?;
+        found.sort_by(|a, b| b.1.total_cmp(&a.1));
+        found.truncate(limit);
+        Ok(found)
+    }
+
+    /// The utterance in the [`UTTERANCE_COLUMNS`] of `row` from `first` on.
+    fn utterance(&self, row: &rusqlite::Row<'_>, first: usize) -> rusqlite::Result<Utterance> {
+        let at_ms = row.get::<_, i64>(first)?.max(0) as u64;
+        Ok(Utterance {
+            channel: self.channel.clone(),
+            at_ms,
+            local_time: row.get(first + 6)?,
+            offset: Duration::from_millis(at_ms.saturating_sub(self.started_at_ms)),
+            source: row.get(first + 1)?,
+            translation: row.get(first + 2)?,
+            target_lang: row.get(first + 3)?,
+            confidence: row.get::<_, Option<f64>>(first + 4)?.map(|c| c as f32),
+            latency_ms: row.get::<_, i64>(first + 5)?.max(0) as u64,
+        })
+    }
+}
+
+/// FTS5 query matching every word of `query`, each quoted so that
+/// punctuation in it is not taken as query syntax.
+fn match_expression(query: &str) -> String {
+    query
+        .split_whitespace()
+        .map(|word| format!("\"{}\"", word.replace('"', "\"\"")))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+/// File name for `channel`, keeping only characters safe on every platform.
+fn file_stem(channel: &str) -> String {
+    channel
+        .to_lowercase()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_when_something_was_said() {
+        let dir = std::env::temp_dir().join(format!("tt-archive-{}", std::process::id()));
+        let mut archive = TranscriptArchive::create(&dir, "Some Streamer").unwrap();
+        archive
+            .record_transcript("hello chat", Some(0.9), 400)
+            .unwrap();
+        archive
+            .record_transcript("let's read the patch notes?", None, 500)
+            .unwrap();
+        archive
+            .record_transcript("the patch is huge", None, 450)
+            .unwrap();
+        // The first transcript went untranslated.
+        archive
+            .record_translation(
+                "let's read the patch notes?",
+                "lisons les notes de patch ?",
+                "fr",
+            )
+            .unwrap();
+        let path = archive.path().to_owned();
+        assert!(path
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .starts_with("some_streamer-"));
+
+        let mut session = SessionArchive::open(&path).unwrap();
+        let found = session.search("patch notes?", 10).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].channel, "Some Streamer");
+        assert_eq!(found[0].source, "let's read the patch notes?");
+        assert_eq!(
+            found[0].translation.as_deref(),
+            Some("lisons les notes de patch ?")
+        );
+        assert_eq!(found[0].target_lang.as_deref(), Some("fr"));
+        assert!(found[0].latency_ms >= 500);
+        // Translations are searched too.
+        assert_eq!(session.search("lisons", 10).unwrap().len(), 1);
+        assert_eq!(session.search("patch", 10).unwrap().len(), 2);
+        assert_eq!(
+            session.search("hello", 10).unwrap()[0].confidence,
+            Some(0.9)
+        );
+        assert!(session.search("raid", 10).unwrap().is_empty());
+
+        // Searching by meaning uses the vectors of the transcripts.
+        let unembedded = session.unembedded("test-model").unwrap();
+        assert_eq!(unembedded.len(), 3);
+        let vectors: Vec<(i64, Vec<f32>)> = unembedded
+            .iter()
+            .map(|(id, text)| (*id, vec![text.len() as f32, 1.0]))
+            .collect();
+        session.store_embeddings("test-model", &vectors).unwrap();
+        assert!(session.unembedded("test-model").unwrap().is_empty());
+        assert_eq!(session.unembedded("other-model").unwrap().len(), 3);
+        let nearest = session.nearest("test-model", &[10.0, 1.0], 2).unwrap();
+        assert_eq!(nearest.len(), 2);
+        assert_eq!(nearest[0].0.source, "hello chat");
+        assert!(nearest[0].1 > nearest[1].1);
+        drop(archive);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,8 +1,17 @@
 //! Automatic Speech Recognition (ASR) module
 //!
 //! This module provides traits and implementations for converting audio to text.
-//! Currently supports Whisper-based ASR when the `whisper-rs` feature is enabled.
+//! Currently supports Whisper-based ASR when the `whisper-rs` feature is enabled,
+//! a whisper.cpp or OpenAI-compatible server over HTTP, and the AssemblyAI
+//! and Gladia realtime services with the `cloud-asr` feature.
 
+mod models;
+pub mod pool;
+#[cfg(feature = "cloud-asr")]
+mod realtime;
+mod remote;
+pub mod rtf;
+mod vad;
 #[cfg(feature = "whisper-rs")]
 mod whisper;
 
@@ -11,9 +20,18 @@ use futures::future::BoxFuture;
 use serde::{Deserialize, Serialize};
 use std::time::Duration;
 
+pub use models::{
+    download_model, installed_models, resolve_model_path, smaller_installed_model,
+    ModelDownloadError, WhisperModel, DEFAULT_MODELS_DIR, WHISPER_MODELS,
+};
+#[cfg(feature = "cloud-asr")]
+pub use realtime::RealtimeAsrBackend;
+pub use remote::RemoteAsrBackend;
 #[cfg(feature = "whisper-rs")]
 pub use whisper::WhisperAsrBackend;
 
+pub use crate::text::{lyrics, split_non_speech, MusicDetector, NonSpeech};
+
 /// A segment of transcribed text with metadata
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub struct TranscriptSegment {
@@ -23,6 +41,45 @@ pub struct TranscriptSegment {
     pub audio_duration: Duration,
     /// Confidence score for the transcription (if available)
     pub confidence: Option<f32>,
+    /// Word timings and speakers, from backends that report them
+    #[serde(default)]
+    pub words: Vec<TranscriptWord>,
+    /// How far into the chunk speech begins, from backends that time what
+    /// they recognize
+    #[serde(default)]
+    pub speech_start: Option<Duration>,
+}
+
+/// One recognized word with its timing
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct TranscriptWord {
+    pub text: String,
+    /// Offset of the word from the start of the backend's session
+    pub start: Duration,
+    pub end: Duration,
+    pub confidence: Option<f32>,
+    /// Speaker label from diarization, if the backend reports one
+    pub speaker: Option<String>,
+}
+
+impl TranscriptSegment {
+    /// The diarized speaker who said most of the words, if the backend labels
+    /// them.
+    pub fn speaker(&self) -> Option<&str> {
+        let mut counts: Vec<(&str, usize)> = Vec::new();
+        for speaker in self.words.iter().filter_map(|w| w.speaker.as_deref()) {
+            match counts.iter_mut().find(|(s, _)| *s == speaker) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((speaker, 1)),
+            }
+        }
+        // The first to speak wins a tie.
+        counts
+            .into_iter()
+            .rev()
+            .max_by_key(|&(_, count)| count)
+            .map(|(speaker, _)| speaker)
+    }
 }
 
 /// Errors that can occur during automatic speech recognition
@@ -56,6 +113,19 @@ pub enum AsrError {
     /// Failed to extract transcription from the model output
     #[error("transcription failed: {0}")]
     TranscriptionFailed(String),
+
+    /// A remote or cloud recognizer could not be reached, failed the
+    /// request or dropped the session
+    #[error("ASR service error: {0}")]
+    Service(String),
+
+    /// A cloud recognizer rejected the API key
+    #[error("ASR service rejected the API key: {0}")]
+    Unauthorized(String),
+
+    /// The remote recognizer's URL is unusable
+    #[error("invalid ASR endpoint: {0}")]
+    InvalidEndpoint(String),
 }
 
 /// Trait for automatic speech recognition backends
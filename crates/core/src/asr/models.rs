@@ -0,0 +1,221 @@
+//! Whisper model catalog and downloader
+//!
+//! Models are the ggml conversions published alongside whisper.cpp. They are
+//! stored as `ggml-<name>.bin`, which is the layout [`crate::config::AsrConfig`]
+//! expects.
+
+use reqwest::Client;
+use std::path::{Path, PathBuf};
+use tokio::io::AsyncWriteExt;
+
+/// Directory the `models` command downloads into by default.
+pub const DEFAULT_MODELS_DIR: &str = "models";
+
+const MODEL_BASE_URL: &str = "https://huggingface.co/ggerganov/whisper.cpp/resolve/main";
+
+/// A downloadable Whisper model
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WhisperModel {
+    pub name: &'static str,
+    /// Approximate download size in megabytes.
+    pub size_mb: u32,
+}
+
+impl WhisperModel {
+    pub fn file_name(&self) -> String {
+        format!("ggml-{}.bin", self.name)
+    }
+
+    pub fn url(&self) -> String {
+        format!("{MODEL_BASE_URL}/{}", self.file_name())
+    }
+
+    pub fn find(name: &str) -> Option<WhisperModel> {
+        WHISPER_MODELS.iter().copied().find(|m| m.name == name)
+    }
+}
+
+/// Models known to the `models` command, smallest first.
+pub const WHISPER_MODELS: &[WhisperModel] = &[
+    WhisperModel {
+        name: "tiny",
+        size_mb: 75,
+    },
+    WhisperModel {
+        name: "tiny.en",
+        size_mb: 75,
+    },
+    WhisperModel {
+        name: "base",
+        size_mb: 142,
+    },
+    WhisperModel {
+        name: "base.en",
+        size_mb: 142,
+    },
+    WhisperModel {
+        name: "small",
+        size_mb: 466,
+    },
+    WhisperModel {
+        name: "small.en",
+        size_mb: 466,
+    },
+    WhisperModel {
+        name: "medium",
+        size_mb: 1500,
+    },
+    WhisperModel {
+        name: "medium.en",
+        size_mb: 1500,
+    },
+    WhisperModel {
+        name: "large-v3-turbo",
+        size_mb: 1600,
+    },
+    WhisperModel {
+        name: "large-v3",
+        size_mb: 3100,
+    },
+];
+
+#[derive(thiserror::Error, Debug)]
+pub enum ModelDownloadError {
+    #[error("unknown model '{0}'")]
+    UnknownModel(String),
+
+    #[error("http error: {0}")]
+    Http(#[from] reqwest::Error),
+
+    #[error("http error {0} downloading {1}")]
+    HttpStatus(u16, String),
+
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// Returns the catalog models already present in `dir`.
+pub fn installed_models(dir: &Path) -> Vec<(WhisperModel, PathBuf)> {
+    WHISPER_MODELS
+        .iter()
+        .map(|m| (*m, dir.join(m.file_name())))
+        .filter(|(_, path)| path.is_file())
+        .collect()
+}
+
+/// The file to load for `model`: a catalog name such as `small` is its file
+/// in [`DEFAULT_MODELS_DIR`], where the `models` command downloads it;
+/// anything else is taken as a path.
+pub fn resolve_model_path(model: &str) -> String {
+    match WhisperModel::find(model.trim()) {
+        Some(m) => Path::new(DEFAULT_MODELS_DIR)
+            .join(m.file_name())
+            .to_string_lossy()
+            .into_owned(),
+        None => model.to_owned(),
+    }
+}
+
+/// The largest installed catalog model next to `model_path` that is smaller
+/// than it, preferring one with the same `.en` suffix; `None` when
+/// `model_path` is not a catalog model or nothing smaller is installed.
+pub fn smaller_installed_model(model_path: &Path) -> Option<(WhisperModel, PathBuf)> {
+    let file_name = model_path.file_name()?.to_str()?;
+    let current = WHISPER_MODELS.iter().find(|m| m.file_name() == file_name)?;
+    let english_only = |m: &WhisperModel| m.name.ends_with(".en");
+    installed_models(model_path.parent().unwrap_or(Path::new(".")))
+        .into_iter()
+        .filter(|(m, _)| m.size_mb < current.size_mb)
+        .max_by_key(|(m, _)| (english_only(m) == english_only(current), m.size_mb))
+}
+
+/// Downloads `name` into `dir`, reporting `(downloaded, total)` bytes as it goes.
+///
+/// The file is written under a `.part` name and renamed once complete, so an
+/// interrupted download never leaves a truncated model where the ASR expects one.
+pub async fn download_model(
+    name: &str,
+    dir: &Path,
+    mut progress: impl FnMut(u64, Option<u64>),
+) -> Result<PathBuf, ModelDownloadError> {
+    let model = WhisperModel::find(name)
+        .ok_or_else(|| ModelDownloadError::UnknownModel(name.to_owned()))?;
+    tokio::fs::create_dir_all(dir).await?;
+
+    let url = model.url();
+    let mut response = Client::new().get(&url).send().await?;
+    if !response.status().is_success() {
+        return Err(ModelDownloadError::HttpStatus(
+            response.status().as_u16(),
+            url,
+        ));
+    }
+
+    let total = response.content_length();
+    let target = dir.join(model.file_name());
+    let partial = dir.join(format!("{}.part", model.file_name()));
+    let mut file = tokio::fs::File::create(&partial).await?;
+    let mut downloaded = 0u64;
+
+    while let Some(chunk) = response.chunk().await? {
+        file.write_all(&chunk).await?;
+        downloaded += chunk.len() as u64;
+        progress(downloaded, total);
+    }
+    file.flush().await?;
+    drop(file);
+
+    tokio::fs::rename(&partial, &target).await?;
+    Ok(target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_model_is_in_catalog() {
+        let default = crate::config::AsrConfig::default().model_path;
+        let model = WhisperModel::find("base.en").unwrap();
+        assert!(default.ends_with(&model.file_name()));
+        assert!(model.url().ends_with("/ggml-base.en.bin"));
+    }
+
+    #[test]
+    fn model_names_resolve_to_the_models_dir() {
+        let small = Path::new(DEFAULT_MODELS_DIR).join("ggml-small.bin");
+        assert_eq!(resolve_model_path("small"), small.to_string_lossy());
+        assert_eq!(resolve_model_path("/opt/ggml-x.bin"), "/opt/ggml-x.bin");
+    }
+
+    #[test]
+    fn installed_models_only_lists_existing_files() {
+        let dir = std::env::temp_dir().join(format!("whisper-models-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("ggml-tiny.bin"), b"").unwrap();
+        std::fs::write(dir.join("ggml-tiny.bin.part"), b"").unwrap();
+
+        let installed = installed_models(&dir);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(installed.len(), 1);
+        assert_eq!(installed[0].0.name, "tiny");
+    }
+
+    #[test]
+    fn smaller_model_prefers_the_same_language_variant() {
+        let dir = std::env::temp_dir().join(format!("whisper-smaller-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for name in ["tiny", "base.en", "small.en", "medium.en"] {
+            std::fs::write(dir.join(format!("ggml-{name}.bin")), b"").unwrap();
+        }
+
+        let smaller = |name: &str| {
+            smaller_installed_model(&dir.join(format!("ggml-{name}.bin"))).map(|(m, _)| m.name)
+        };
+        let results = [smaller("medium.en"), smaller("small"), smaller("tiny")];
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(results, [Some("small.en"), Some("tiny"), None]);
+    }
+}
@@ -0,0 +1,192 @@
+//! Pool of per-inference states sharing one loaded model.
+//!
+//! A Whisper context holds the model weights; each concurrent inference needs
+//! its own `WhisperState` (KV cache and scratch buffers). [`StatePool`] creates
+//! states lazily up to a limit and hands them out one caller at a time, so
+//! several pipelines can transcribe in parallel without loading the model twice.
+
+use crate::asr::AsrError;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+type Factory<T> = Arc<dyn Fn() -> Result<T, AsrError> + Send + Sync>;
+
+pub struct StatePool<T> {
+    idle: Mutex<Vec<T>>,
+    permits: Arc<Semaphore>,
+    max_states: usize,
+    create: Factory<T>,
+}
+
+impl<T> StatePool<T> {
+    /// A pool of at most `max_states` (minimum 1) states built by `create`.
+    pub fn new(
+        max_states: usize,
+        create: impl Fn() -> Result<T, AsrError> + Send + Sync + 'static,
+    ) -> Self {
+        let max_states = max_states.max(1);
+        Self {
+            idle: Mutex::new(Vec::new()),
+            permits: Arc::new(Semaphore::new(max_states)),
+            max_states,
+            create: Arc::new(create),
+        }
+    }
+
+    /// Creates one state up front so a broken model fails at startup rather
+    /// than on the first segment.
+    pub fn warm(self) -> Result<Self, AsrError> {
+        let state = (self.create)()?;
+        lock(&self.idle).push(state);
+        Ok(self)
+    }
+
+    /// A pool with a different limit that takes over this pool's idle states.
+    pub fn resized(&self, max_states: usize) -> Self {
+        let max_states = max_states.max(1);
+        Self {
+            idle: Mutex::new(std::mem::take(&mut *lock(&self.idle))),
+            permits: Arc::new(Semaphore::new(max_states)),
+            max_states,
+            create: Arc::clone(&self.create),
+        }
+    }
+
+    pub fn max_states(&self) -> usize {
+        self.max_states
+    }
+
+    /// States created so far that are not currently checked out.
+    pub fn idle(&self) -> usize {
+        lock(&self.idle).len()
+    }
+
+    /// Waits for a free slot and returns an idle state, creating one if none
+    /// is left. The state goes back to the pool when the guard is dropped.
+    pub async fn acquire(self: &Arc<Self>) -> Result<PooledState<T>, AsrError> {
+        let permit = self
+            .permits
+            .clone()
+            .acquire_owned()
+            .await
+            .map_err(|_| AsrError::InferenceError("state pool closed".to_owned()))?;
+        let idle = lock(&self.idle).pop();
+        let state = match idle {
+            Some(state) => state,
+            None => (self.create)()?,
+        };
+        Ok(PooledState {
+            pool: Arc::clone(self),
+            state: Some(state),
+            _permit: permit,
+        })
+    }
+}
+
+/// A state checked out of a [`StatePool`].
+pub struct PooledState<T> {
+    pool: Arc<StatePool<T>>,
+    state: Option<T>,
+    _permit: OwnedSemaphorePermit,
+}
+
+impl<T> Deref for PooledState<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.state.as_ref().expect("state present until drop")
+    }
+}
+
+impl<T> DerefMut for PooledState<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.state.as_mut().expect("state present until drop")
+    }
+}
+
+impl<T> Drop for PooledState<T> {
+    fn drop(&mut self) {
+        // A state abandoned mid-inference by a panic may be inconsistent;
+        // drop it and let the next caller create a fresh one.
+        if std::thread::panicking() {
+            return;
+        }
+        if let Some(state) = self.state.take() {
+            lock(&self.pool.idle).push(state);
+        }
+    }
+}
+
+fn lock<T>(m: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    match m.lock() {
+        Ok(g) => g,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    fn counting_pool(max_states: usize) -> (Arc<StatePool<usize>>, Arc<AtomicUsize>) {
+        let created = Arc::new(AtomicUsize::new(0));
+        let counter = created.clone();
+        let pool = StatePool::new(max_states, move || {
+            Ok(counter.fetch_add(1, Ordering::SeqCst))
+        });
+        (Arc::new(pool), created)
+    }
+
+    #[tokio::test]
+    async fn reuses_returned_states() {
+        let (pool, created) = counting_pool(2);
+        let first = *pool.acquire().await.unwrap();
+        let second = *pool.acquire().await.unwrap();
+        assert_eq!(first, second);
+        assert_eq!(created.load(Ordering::SeqCst), 1);
+        assert_eq!(pool.idle(), 1);
+    }
+
+    #[tokio::test]
+    async fn creates_states_up_to_the_limit_then_waits() {
+        let (pool, created) = counting_pool(2);
+        let a = pool.acquire().await.unwrap();
+        let b = pool.acquire().await.unwrap();
+        assert_ne!(*a, *b);
+
+        let waiting = tokio::time::timeout(Duration::from_millis(50), pool.acquire()).await;
+        assert!(
+            waiting.is_err(),
+            "third caller should wait for a free state"
+        );
+
+        drop(a);
+        let c = pool.acquire().await.unwrap();
+        assert_eq!(created.load(Ordering::SeqCst), 2);
+        drop((b, c));
+        assert_eq!(pool.idle(), 2);
+    }
+
+    #[tokio::test]
+    async fn resizing_keeps_idle_states() {
+        let (pool, created) = counting_pool(1);
+        drop(pool.acquire().await.unwrap());
+        let pool = Arc::new(pool.resized(3));
+        assert_eq!(pool.max_states(), 3);
+        let _a = pool.acquire().await.unwrap();
+        let _b = pool.acquire().await.unwrap();
+        assert_eq!(created.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn failed_creation_releases_the_slot() {
+        let pool = Arc::new(StatePool::<()>::new(1, || {
+            Err(AsrError::InferenceError("no memory".to_owned()))
+        }));
+        assert!(pool.acquire().await.is_err());
+        assert!(pool.acquire().await.is_err());
+    }
+}
@@ -0,0 +1,145 @@
+//! AssemblyAI Universal-Streaming (v3)
+
+use super::{connect_error, mean_confidence, Service, Socket, Utterance};
+use crate::asr::{AsrError, TranscriptWord};
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use serde::Deserialize;
+use std::time::Duration;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::http::HeaderValue;
+
+const URL: &str = "wss://streaming.assemblyai.com/v3/ws";
+
+pub(super) struct AssemblyAi {
+    api_key: String,
+    diarize: bool,
+}
+
+impl AssemblyAi {
+    pub(super) fn new(api_key: String, diarize: bool) -> Self {
+        Self { api_key, diarize }
+    }
+}
+
+#[derive(Deserialize)]
+struct Message {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    transcript: String,
+    #[serde(default)]
+    end_of_turn: bool,
+    #[serde(default)]
+    turn_is_formatted: bool,
+    #[serde(default)]
+    words: Vec<Word>,
+    #[serde(default)]
+    speaker_label: Option<String>,
+    #[serde(default)]
+    error: Option<String>,
+}
+
+/// Times are in milliseconds from the start of the session.
+#[derive(Deserialize)]
+struct Word {
+    text: String,
+    start: u64,
+    end: u64,
+    #[serde(default)]
+    confidence: Option<f32>,
+    #[serde(default)]
+    speaker: Option<String>,
+}
+
+impl Service for AssemblyAi {
+    fn name(&self) -> &'static str {
+        "assemblyai"
+    }
+
+    fn connect(&self, sample_rate: u32) -> BoxFuture<'_, Result<Socket, AsrError>> {
+        async move {
+            let mut url =
+                format!("{URL}?sample_rate={sample_rate}&encoding=pcm_s16le&format_turns=true");
+            if self.diarize {
+                url.push_str("&speaker_labels=true");
+            }
+            let mut request = url
+                .into_client_request()
+                .map_err(|e| AsrError::Service(format!("assemblyai: {e}")))?;
+            let key = HeaderValue::from_str(&self.api_key).map_err(|_| {
+                AsrError::Unauthorized("the AssemblyAI key is not a valid header".to_owned())
+            })?;
+            request.headers_mut().insert("Authorization", key);
+            let (socket, _) = tokio_tungstenite::connect_async(request)
+                .await
+                .map_err(|e| connect_error(self.name(), e))?;
+            Ok(socket)
+        }
+        .boxed()
+    }
+
+    fn parse(&self, message: &str) -> Result<Vec<Utterance>, AsrError> {
+        let message: Message = serde_json::from_str(message).map_err(|e| {
+            AsrError::Service(format!("assemblyai sent an unexpected message: {e}"))
+        })?;
+        if let Some(error) = message.error {
+            return Err(AsrError::Service(format!("assemblyai: {error}")));
+        }
+        // Each turn is sent again as it grows; only the last, formatted one
+        // is final.
+        if message.kind != "Turn" || !message.end_of_turn || !message.turn_is_formatted {
+            return Ok(Vec::new());
+        }
+        let turn_speaker = message.speaker_label;
+        let words: Vec<TranscriptWord> = message
+            .words
+            .into_iter()
+            .map(|w| TranscriptWord {
+                text: w.text,
+                start: Duration::from_millis(w.start),
+                end: Duration::from_millis(w.end),
+                confidence: w.confidence,
+                speaker: w.speaker.or_else(|| turn_speaker.clone()),
+            })
+            .collect();
+        Ok(vec![Utterance {
+            text: message.transcript,
+            confidence: mean_confidence(&words),
+            words,
+        }])
+    }
+
+    fn stop_message(&self) -> &'static str {
+        r#"{"type":"Terminate"}"#
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_formatted_turn_ends_are_final() {
+        let service = AssemblyAi::new("key".to_owned(), true);
+        let partial = r#"{"type":"Turn","transcript":"hello chat","end_of_turn":false,
+            "turn_is_formatted":false,"words":[]}"#;
+        assert!(service.parse(partial).unwrap().is_empty());
+        assert!(service
+            .parse(r#"{"type":"Begin","id":"x"}"#)
+            .unwrap()
+            .is_empty());
+
+        let last = r#"{"type":"Turn","transcript":"Hello chat.","end_of_turn":true,
+            "turn_is_formatted":true,"speaker_label":"A","words":[
+            {"text":"Hello","start":120,"end":480,"confidence":0.9,"word_is_final":true},
+            {"text":"chat.","start":500,"end":900,"confidence":0.7,"word_is_final":true}]}"#;
+        let utterances = service.parse(last).unwrap();
+        assert_eq!(utterances.len(), 1);
+        let utterance = &utterances[0];
+        assert_eq!(utterance.text, "Hello chat.");
+        assert_eq!(utterance.words[1].start, Duration::from_millis(500));
+        assert_eq!(utterance.words[0].speaker.as_deref(), Some("A"));
+        assert!((utterance.confidence.unwrap() - 0.8).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,222 @@
+//! Gladia live transcription (v2)
+//!
+//! A session is set up over HTTP first; the answer holds the WebSocket URL
+//! audio is then streamed to.
+
+use super::{connect_error, mean_confidence, Service, Socket, Utterance};
+use crate::asr::{AsrError, TranscriptWord};
+use crate::util::{HttpRequest, SharedTransport};
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use serde::Deserialize;
+use std::time::Duration;
+
+const SESSION_URL: &str = "https://api.gladia.io/v2/live";
+
+pub(super) struct Gladia {
+    api_key: String,
+    language: String,
+    transport: SharedTransport,
+}
+
+impl Gladia {
+    pub(super) fn new(api_key: String, language: String, transport: SharedTransport) -> Self {
+        Self {
+            api_key,
+            language,
+            transport,
+        }
+    }
+
+    fn session_request(&self, sample_rate: u32) -> Result<HttpRequest, AsrError> {
+        let auto = self.language == "auto";
+        let languages: Vec<&str> = if auto {
+            Vec::new()
+        } else {
+            vec![self.language.as_str()]
+        };
+        let body = serde_json::json!({
+            "encoding": "wav/pcm",
+            "bit_depth": 16,
+            "sample_rate": sample_rate,
+            "channels": 1,
+            "language_config": {
+                "languages": languages,
+                "code_switching": auto,
+            },
+            "messages_config": {
+                "receive_partial_transcripts": false,
+                "receive_final_transcripts": true,
+            },
+        });
+        HttpRequest::post(SESSION_URL)
+            .with_header("x-gladia-key", &self.api_key)
+            .with_json(&body)
+            .map_err(|e| AsrError::Service(format!("gladia: {e}")))
+    }
+}
+
+#[derive(Deserialize)]
+struct Session {
+    url: String,
+}
+
+#[derive(Deserialize)]
+struct Message {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    data: Option<Data>,
+}
+
+#[derive(Deserialize)]
+struct Data {
+    #[serde(default)]
+    is_final: bool,
+    utterance: Option<GladiaUtterance>,
+}
+
+/// Times are in seconds from the start of the session.
+#[derive(Deserialize)]
+struct GladiaUtterance {
+    text: String,
+    #[serde(default)]
+    confidence: Option<f32>,
+    #[serde(default)]
+    speaker: Option<serde_json::Value>,
+    #[serde(default)]
+    words: Vec<Word>,
+}
+
+#[derive(Deserialize)]
+struct Word {
+    word: String,
+    start: f64,
+    end: f64,
+    #[serde(default)]
+    confidence: Option<f32>,
+}
+
+impl Service for Gladia {
+    fn name(&self) -> &'static str {
+        "gladia"
+    }
+
+    fn connect(&self, sample_rate: u32) -> BoxFuture<'_, Result<Socket, AsrError>> {
+        async move {
+            let response = self
+                .transport
+                .send(self.session_request(sample_rate)?)
+                .await
+                .map_err(|e| AsrError::Service(format!("gladia: {e}")))?;
+            if matches!(response.status, 401 | 403) {
+                return Err(AsrError::Unauthorized(format!(
+                    "gladia answered {}",
+                    response.status
+                )));
+            }
+            if !response.is_success() {
+                return Err(AsrError::Service(format!(
+                    "gladia session setup failed with {}: {}",
+                    response.status,
+                    response.text()
+                )));
+            }
+            let session: Session = response
+                .json()
+                .map_err(|e| AsrError::Service(format!("gladia: {e}")))?;
+            let (socket, _) = tokio_tungstenite::connect_async(session.url.as_str())
+                .await
+                .map_err(|e| connect_error(self.name(), e))?;
+            Ok(socket)
+        }
+        .boxed()
+    }
+
+    fn parse(&self, message: &str) -> Result<Vec<Utterance>, AsrError> {
+        let message: Message = serde_json::from_str(message)
+            .map_err(|e| AsrError::Service(format!("gladia sent an unexpected message: {e}")))?;
+        let Some(Data {
+            is_final: true,
+            utterance: Some(utterance),
+        }) = message.data.filter(|_| message.kind == "transcript")
+        else {
+            return Ok(Vec::new());
+        };
+        let speaker = utterance.speaker.and_then(|s| match s {
+            serde_json::Value::String(s) => Some(s),
+            serde_json::Value::Number(n) => Some(n.to_string()),
+            _ => None,
+        });
+        let words: Vec<TranscriptWord> = utterance
+            .words
+            .into_iter()
+            .map(|w| TranscriptWord {
+                text: w.word.trim().to_owned(),
+                start: Duration::from_secs_f64(w.start.max(0.0)),
+                end: Duration::from_secs_f64(w.end.max(0.0)),
+                confidence: w.confidence,
+                speaker: speaker.clone(),
+            })
+            .collect();
+        Ok(vec![Utterance {
+            text: utterance.text,
+            confidence: utterance.confidence.or_else(|| mean_confidence(&words)),
+            words,
+        }])
+    }
+
+    fn stop_message(&self) -> &'static str {
+        r#"{"type":"stop_recording"}"#
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::MockTransport;
+    use std::sync::Arc;
+
+    fn gladia(language: &str) -> Gladia {
+        Gladia::new(
+            "key".to_owned(),
+            language.to_owned(),
+            Arc::new(MockTransport::new()),
+        )
+    }
+
+    #[test]
+    fn session_asks_for_the_configured_language() {
+        let request = gladia("de").session_request(16_000).unwrap();
+        assert_eq!(request.header("x-gladia-key"), Some("key"));
+        let body: serde_json::Value = serde_json::from_slice(&request.body.unwrap()).unwrap();
+        assert_eq!(body["sample_rate"], 16_000);
+        assert_eq!(body["language_config"]["languages"][0], "de");
+        assert_eq!(body["language_config"]["code_switching"], false);
+    }
+
+    #[test]
+    fn final_utterances_carry_words_and_speaker() {
+        let service = gladia("en");
+        let partial = r#"{"type":"transcript","data":{"is_final":false,
+            "utterance":{"text":"hello","start":0.1,"end":0.4,"words":[]}}}"#;
+        assert!(service.parse(partial).unwrap().is_empty());
+        assert!(service
+            .parse(r#"{"type":"speech_start","data":{"time":1.0}}"#)
+            .unwrap()
+            .is_empty());
+
+        let last = r#"{"type":"transcript","data":{"id":"u1","is_final":true,
+            "utterance":{"text":" Hello chat.","start":0.1,"end":0.9,"confidence":0.85,
+            "channel":0,"speaker":1,"words":[
+            {"word":" Hello","start":0.1,"end":0.4,"confidence":0.9},
+            {"word":" chat.","start":0.5,"end":0.9,"confidence":0.8}]}}}"#;
+        let utterances = service.parse(last).unwrap();
+        assert_eq!(utterances.len(), 1);
+        let utterance = &utterances[0];
+        assert_eq!(utterance.confidence, Some(0.85));
+        assert_eq!(utterance.words[0].text, "Hello");
+        assert_eq!(utterance.words[1].start, Duration::from_millis(500));
+        assert_eq!(utterance.words[1].speaker.as_deref(), Some("1"));
+    }
+}
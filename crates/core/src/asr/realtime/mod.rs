@@ -0,0 +1,334 @@
+//! Realtime cloud ASR over WebSocket
+//!
+//! [`RealtimeAsrBackend`] keeps one streaming session open with AssemblyAI or
+//! Gladia and sends it each chunk as 16-bit PCM. The service decides where an
+//! utterance ends, so a chunk's transcript holds the utterances finished
+//! since the previous chunk: speech can come out a chunk late, but is never
+//! cut at chunk boundaries. Word timings and speaker labels are passed on in
+//! [`TranscriptSegment::words`]. A session that drops is reopened with the
+//! next chunk.
+
+mod assemblyai;
+mod gladia;
+
+use crate::asr::{AsrBackend, AsrError, TranscriptSegment, TranscriptWord};
+use crate::config::{ApiKeys, AsrConfig, AsrProvider};
+use crate::decode::PcmChunk;
+use crate::util::SharedTransport;
+use futures::future::BoxFuture;
+use futures::{FutureExt, SinkExt, StreamExt};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio::sync::{mpsc, Mutex};
+use tokio_tungstenite::tungstenite::{self, Message};
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// How long to wait after sending a chunk for the utterances it finished.
+const SETTLE: Duration = Duration::from_millis(300);
+/// How long a closing session may take to flush its last utterances.
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// One finished utterance.
+#[derive(Debug, PartialEq)]
+struct Utterance {
+    text: String,
+    confidence: Option<f32>,
+    words: Vec<TranscriptWord>,
+}
+
+/// What tells the services apart.
+trait Service: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Opens a session for mono 16-bit PCM at `sample_rate`.
+    fn connect(&self, sample_rate: u32) -> BoxFuture<'_, Result<Socket, AsrError>>;
+
+    /// The finished utterances in a message from the service.
+    fn parse(&self, message: &str) -> Result<Vec<Utterance>, AsrError>;
+
+    /// The message that ends a session cleanly.
+    fn stop_message(&self) -> &'static str;
+}
+
+/// Transcribes through a realtime cloud service. Clones share the session,
+/// so the pipeline should run one ASR worker.
+#[derive(Clone)]
+pub struct RealtimeAsrBackend {
+    service: Arc<dyn Service>,
+    session: Arc<Mutex<Option<Session>>>,
+}
+
+struct Session {
+    sample_rate: u32,
+    audio: mpsc::Sender<Vec<u8>>,
+    utterances: mpsc::UnboundedReceiver<Result<Utterance, AsrError>>,
+}
+
+impl RealtimeAsrBackend {
+    /// AssemblyAI Universal-Streaming; with `diarize`, words carry speaker
+    /// labels.
+    pub fn assemblyai(api_key: impl Into<String>, diarize: bool) -> Self {
+        Self::new(assemblyai::AssemblyAi::new(api_key.into(), diarize))
+    }
+
+    /// Gladia live transcription of `language`, or any language for `auto`.
+    /// The session is set up over `transport`.
+    pub fn gladia(
+        api_key: impl Into<String>,
+        language: impl Into<String>,
+        transport: SharedTransport,
+    ) -> Self {
+        Self::new(gladia::Gladia::new(
+            api_key.into(),
+            language.into(),
+            transport,
+        ))
+    }
+
+    /// The service `config.provider` names, with its key from `keys`.
+    pub fn from_config(
+        config: &AsrConfig,
+        keys: &ApiKeys,
+        transport: SharedTransport,
+    ) -> Result<Self, AsrError> {
+        let missing = |name: &str| AsrError::Unauthorized(format!("no {name} API key"));
+        match config.provider {
+            AsrProvider::AssemblyAi => {
+                let key = keys
+                    .assemblyai
+                    .as_ref()
+                    .ok_or_else(|| missing("AssemblyAI"))?;
+                Ok(Self::assemblyai(key.expose(), config.diarize))
+            }
+            AsrProvider::Gladia => {
+                let key = keys.gladia.as_ref().ok_or_else(|| missing("Gladia"))?;
+                Ok(Self::gladia(key.expose(), &config.language, transport))
+            }
+            AsrProvider::Whisper => Err(AsrError::Service(
+                "Whisper runs locally, not as a realtime service".to_owned(),
+            )),
+        }
+    }
+
+    fn new(service: impl Service + 'static) -> Self {
+        Self {
+            service: Arc::new(service),
+            session: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    async fn open(&self, sample_rate: u32) -> Result<Session, AsrError> {
+        let socket = self.service.connect(sample_rate).await?;
+        tracing::info!(service = self.service.name(), "ASR session opened");
+        let (audio_tx, audio_rx) = mpsc::channel(8);
+        let (utterance_tx, utterance_rx) = mpsc::unbounded_channel();
+        tokio::spawn(run_session(
+            self.service.clone(),
+            socket,
+            audio_rx,
+            utterance_tx,
+        ));
+        Ok(Session {
+            sample_rate,
+            audio: audio_tx,
+            utterances: utterance_rx,
+        })
+    }
+}
+
+impl AsrBackend for RealtimeAsrBackend {
+    fn transcribe(&self, audio: PcmChunk) -> BoxFuture<'_, Result<TranscriptSegment, AsrError>> {
+        async move {
+            if audio.samples.is_empty() {
+                return Err(AsrError::EmptyAudio);
+            }
+            let sample_rate = audio.format.sample_rate;
+            if audio.format.channels != 1 {
+                return Err(AsrError::UnsupportedFormat {
+                    expected_sample_rate: sample_rate,
+                    expected_channels: 1,
+                    actual_sample_rate: sample_rate,
+                    actual_channels: audio.format.channels,
+                });
+            }
+            let audio_duration =
+                Duration::from_secs_f64(audio.samples.len() as f64 / f64::from(sample_rate.max(1)));
+
+            let mut slot = self.session.lock().await;
+            let session = match slot.take() {
+                Some(s) if s.sample_rate == sample_rate && !s.audio.is_closed() => slot.insert(s),
+                _ => slot.insert(self.open(sample_rate).await?),
+            };
+            if session.audio.send(pcm16(&audio.samples)).await.is_err() {
+                *slot = None;
+                return Err(AsrError::Service(format!(
+                    "{} session closed",
+                    self.service.name()
+                )));
+            }
+
+            tokio::time::sleep(SETTLE).await;
+            let mut utterances = Vec::new();
+            while let Ok(next) = session.utterances.try_recv() {
+                match next {
+                    Ok(utterance) => utterances.push(utterance),
+                    Err(e) => {
+                        *slot = None;
+                        return Err(e);
+                    }
+                }
+            }
+            Ok(segment(utterances, audio_duration))
+        }
+        .boxed()
+    }
+}
+
+/// Forwards audio to the service and utterances back until the backend
+/// drops the session, then ends it cleanly.
+async fn run_session(
+    service: Arc<dyn Service>,
+    socket: Socket,
+    mut audio: mpsc::Receiver<Vec<u8>>,
+    utterances: mpsc::UnboundedSender<Result<Utterance, AsrError>>,
+) {
+    let name = service.name();
+    let (mut sink, mut stream) = socket.split();
+    loop {
+        tokio::select! {
+            pcm = audio.recv() => {
+                let Some(pcm) = pcm else {
+                    break;
+                };
+                if let Err(e) = sink.send(Message::binary(pcm)).await {
+                    let _ = utterances.send(Err(AsrError::Service(format!("{name}: {e}"))));
+                    return;
+                }
+            }
+            message = stream.next() => match message {
+                Some(Ok(Message::Text(text))) => match service.parse(text.as_str()) {
+                    Ok(found) => {
+                        for utterance in found {
+                            let _ = utterances.send(Ok(utterance));
+                        }
+                    }
+                    Err(e) => {
+                        let _ = utterances.send(Err(e));
+                        return;
+                    }
+                },
+                Some(Ok(Message::Close(frame))) => {
+                    let reason = frame.map(|f| f.reason.to_string()).unwrap_or_default();
+                    let _ = utterances.send(Err(AsrError::Service(format!(
+                        "{name} closed the session: {reason}"
+                    ))));
+                    return;
+                }
+                Some(Ok(_)) => {}
+                Some(Err(e)) => {
+                    let _ = utterances.send(Err(AsrError::Service(format!("{name}: {e}"))));
+                    return;
+                }
+                None => {
+                    let _ = utterances.send(Err(AsrError::Service(format!(
+                        "{name} closed the session"
+                    ))));
+                    return;
+                }
+            },
+        }
+    }
+
+    // Nobody reads further utterances; just let the service wind down.
+    let stop = Message::text(service.stop_message().to_owned());
+    if sink.send(stop).await.is_ok() {
+        let drain = async { while let Some(Ok(_)) = stream.next().await {} };
+        let _ = tokio::time::timeout(CLOSE_TIMEOUT, drain).await;
+    }
+    tracing::debug!(service = name, "ASR session closed");
+}
+
+/// Maps a failed WebSocket handshake, telling rejected keys apart.
+fn connect_error(name: &str, e: tungstenite::Error) -> AsrError {
+    match &e {
+        tungstenite::Error::Http(response) if matches!(response.status().as_u16(), 401 | 403) => {
+            AsrError::Unauthorized(format!("{name} answered {}", response.status()))
+        }
+        _ => AsrError::Service(format!("{name}: {e}")),
+    }
+}
+
+/// Little-endian signed 16-bit samples.
+fn pcm16(samples: &[f32]) -> Vec<u8> {
+    samples
+        .iter()
+        .flat_map(|s| ((s.clamp(-1.0, 1.0) * f32::from(i16::MAX)) as i16).to_le_bytes())
+        .collect()
+}
+
+/// One transcript for the utterances finished during a chunk.
+fn segment(utterances: Vec<Utterance>, audio_duration: Duration) -> TranscriptSegment {
+    let confidences: Vec<f32> = utterances.iter().filter_map(|u| u.confidence).collect();
+    let confidence = (!confidences.is_empty())
+        .then(|| confidences.iter().sum::<f32>() / confidences.len() as f32);
+    let text = utterances
+        .iter()
+        .map(|u| u.text.trim())
+        .filter(|t| !t.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ");
+    TranscriptSegment {
+        text,
+        audio_duration,
+        confidence,
+        words: utterances.into_iter().flat_map(|u| u.words).collect(),
+        speech_start: None,
+    }
+}
+
+/// Mean of the word confidences, as the services report none per utterance.
+fn mean_confidence(words: &[TranscriptWord]) -> Option<f32> {
+    let confidences: Vec<f32> = words.iter().filter_map(|w| w.confidence).collect();
+    (!confidences.is_empty()).then(|| confidences.iter().sum::<f32>() / confidences.len() as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn samples_become_clamped_pcm16() {
+        assert_eq!(pcm16(&[0.0, 1.0, -2.0]), [0, 0, 0xff, 0x7f, 0x01, 0x80]);
+    }
+
+    #[test]
+    fn utterances_of_a_chunk_are_joined() {
+        let word = |text: &str, confidence| TranscriptWord {
+            text: text.to_owned(),
+            start: Duration::ZERO,
+            end: Duration::ZERO,
+            confidence: Some(confidence),
+            speaker: None,
+        };
+        let utterances = vec![
+            Utterance {
+                text: "Hello.".to_owned(),
+                confidence: Some(0.5),
+                words: vec![word("Hello.", 0.5)],
+            },
+            Utterance {
+                text: " Welcome back. ".to_owned(),
+                confidence: Some(1.0),
+                words: vec![word("Welcome", 1.0), word("back.", 1.0)],
+            },
+        ];
+        let segment = segment(utterances, Duration::from_secs(2));
+        assert_eq!(segment.text, "Hello. Welcome back.");
+        assert_eq!(segment.confidence, Some(0.75));
+        assert_eq!(segment.words.len(), 3);
+        assert_eq!(mean_confidence(&segment.words), Some(2.5 / 3.0));
+    }
+}
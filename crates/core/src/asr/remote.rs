@@ -0,0 +1,304 @@
+//! Remote Whisper over HTTP
+//!
+//! [`RemoteAsrBackend`] uploads each chunk as a WAV file to a whisper.cpp
+//! server (`/inference`) or an OpenAI-compatible one such as
+//! faster-whisper-server (`/v1/audio/transcriptions`), so a GPU machine on the
+//! LAN can run inference for a laptop that only ingests and plays back.
+
+use crate::asr::vad::has_speech;
+use crate::asr::{AsrBackend, AsrError, TranscriptSegment};
+use crate::clip::encode_wav;
+use crate::config::AsrConfig;
+use crate::decode::PcmChunk;
+use crate::util::{HttpRequest, SharedTransport};
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use serde::Deserialize;
+use std::time::Duration;
+use url::Url;
+
+const BOUNDARY: &str = "twitch-translator-asr-chunk";
+
+/// Which server API the endpoint speaks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Api {
+    WhisperCpp,
+    OpenAi,
+}
+
+#[derive(Clone)]
+pub struct RemoteAsrBackend {
+    url: String,
+    api: Api,
+    language: String,
+    prompt: Option<String>,
+    /// Chunks without speech are not posted.
+    vad: bool,
+    transport: SharedTransport,
+}
+
+#[derive(Deserialize)]
+struct Response {
+    #[serde(default)]
+    text: String,
+    #[serde(default)]
+    error: Option<String>,
+}
+
+impl RemoteAsrBackend {
+    /// Transcribes on `endpoint`: a full `/inference` or
+    /// `/audio/transcriptions` URL, an OpenAI-style base URL ending in `/v1`,
+    /// or the address of a whisper.cpp server.
+    pub fn new(endpoint: &str, transport: SharedTransport) -> Result<Self, AsrError> {
+        let (url, api) = resolve_endpoint(endpoint)?;
+        Ok(Self {
+            url,
+            api,
+            language: "en".to_owned(),
+            prompt: None,
+            vad: false,
+            transport,
+        })
+    }
+
+    /// Uses `config.endpoint` with the language, prompt and VAD setting of
+    /// `config`.
+    pub fn from_config(config: &AsrConfig, transport: SharedTransport) -> Result<Self, AsrError> {
+        let endpoint = config
+            .endpoint
+            .as_deref()
+            .ok_or_else(|| AsrError::InvalidEndpoint("no endpoint configured".to_owned()))?;
+        let backend = Self::new(endpoint, transport)?
+            .with_language(&config.language)
+            .with_vad(config.vad);
+        Ok(match &config.initial_prompt {
+            Some(prompt) => backend.with_initial_prompt(prompt),
+            None => backend,
+        })
+    }
+
+    /// Transcribes `language`, a code such as `en` or `pt`, or lets the
+    /// server detect it for `auto`.
+    pub fn with_language(mut self, language: &str) -> Self {
+        let language = language.trim().to_ascii_lowercase();
+        if !language.is_empty() {
+            self.language = language;
+        }
+        self
+    }
+
+    /// Primes every transcription with `prompt`; the server trims it to what
+    /// its model takes.
+    pub fn with_initial_prompt(mut self, prompt: &str) -> Self {
+        self.prompt = Some(prompt.to_owned());
+        self
+    }
+
+    /// Answers chunks without speech with an empty transcript rather than
+    /// posting them.
+    pub fn with_vad(mut self, vad: bool) -> Self {
+        self.vad = vad;
+        self
+    }
+
+    fn request(&self, audio: &PcmChunk) -> HttpRequest {
+        let mut fields = vec![("response_format", "json")];
+        // OpenAI-style servers reject `auto`; leaving it out detects.
+        if self.api == Api::WhisperCpp || self.language != "auto" {
+            fields.push(("language", self.language.as_str()));
+        }
+        if let Some(prompt) = &self.prompt {
+            fields.push(("prompt", prompt.as_str()));
+        }
+        let wav = encode_wav(audio.format, &audio.samples);
+        HttpRequest::post(&self.url).with_body(
+            format!("multipart/form-data; boundary={BOUNDARY}"),
+            multipart(&fields, &wav),
+        )
+    }
+}
+
+impl AsrBackend for RemoteAsrBackend {
+    fn transcribe(&self, audio: PcmChunk) -> BoxFuture<'_, Result<TranscriptSegment, AsrError>> {
+        async move {
+            if audio.samples.is_empty() {
+                return Err(AsrError::EmptyAudio);
+            }
+            let channels = audio.format.channels.max(1);
+            let frames = audio.samples.len() / usize::from(channels);
+            let audio_duration =
+                Duration::from_secs_f64(frames as f64 / f64::from(audio.format.sample_rate.max(1)));
+            if self.vad && !has_speech(&audio.samples, audio.format.sample_rate, channels) {
+                return Ok(TranscriptSegment {
+                    text: String::new(),
+                    audio_duration,
+                    confidence: None,
+                    words: Vec::new(),
+                    speech_start: None,
+                });
+            }
+
+            let response = self
+                .transport
+                .send(self.request(&audio))
+                .await
+                .map_err(|e| AsrError::Service(format!("{}: {e}", self.url)))?;
+            if matches!(response.status, 401 | 403) {
+                return Err(AsrError::Unauthorized(format!(
+                    "{} answered {}",
+                    self.url, response.status
+                )));
+            }
+            if !response.is_success() {
+                return Err(AsrError::Service(format!(
+                    "{} answered {}: {}",
+                    self.url,
+                    response.status,
+                    response.text()
+                )));
+            }
+            let body: Response = response
+                .json()
+                .map_err(|e| AsrError::TranscriptionFailed(e.to_string()))?;
+            if let Some(error) = body.error {
+                return Err(AsrError::TranscriptionFailed(error));
+            }
+            Ok(TranscriptSegment {
+                text: body.text.trim().to_owned(),
+                audio_duration,
+                confidence: None,
+                words: Vec::new(),
+                speech_start: None,
+            })
+        }
+        .boxed()
+    }
+}
+
+/// The URL to post chunks to and the API it speaks.
+fn resolve_endpoint(endpoint: &str) -> Result<(String, Api), AsrError> {
+    let url = Url::parse(endpoint.trim())
+        .map_err(|e| AsrError::InvalidEndpoint(format!("{endpoint}: {e}")))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(AsrError::InvalidEndpoint(format!(
+            "{endpoint}: expected an http:// or https:// URL"
+        )));
+    }
+    let base = url.as_str().trim_end_matches('/');
+    let path = url.path().trim_end_matches('/');
+    Ok(if path.ends_with("/audio/transcriptions") {
+        (base.to_owned(), Api::OpenAi)
+    } else if path.ends_with("/v1") {
+        (format!("{base}/audio/transcriptions"), Api::OpenAi)
+    } else if path.ends_with("/inference") {
+        (base.to_owned(), Api::WhisperCpp)
+    } else {
+        (format!("{base}/inference"), Api::WhisperCpp)
+    })
+}
+
+/// A `multipart/form-data` body of text `fields` and the chunk as `file`.
+fn multipart(fields: &[(&str, &str)], wav: &[u8]) -> Vec<u8> {
+    let mut body = Vec::with_capacity(wav.len() + 512);
+    for (name, value) in fields {
+        body.extend_from_slice(
+            format!(
+                "--{BOUNDARY}\r\nContent-Disposition: form-data; name=\"{name}\"\r\n\r\n{value}\r\n"
+            )
+            .as_bytes(),
+        );
+    }
+    body.extend_from_slice(
+        format!(
+            "--{BOUNDARY}\r\nContent-Disposition: form-data; name=\"file\"; \
+             filename=\"chunk.wav\"\r\nContent-Type: audio/wav\r\n\r\n"
+        )
+        .as_bytes(),
+    );
+    body.extend_from_slice(wav);
+    body.extend_from_slice(format!("\r\n--{BOUNDARY}--\r\n").as_bytes());
+    body
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode::PcmFormat;
+    use crate::util::{HttpMethod, MockTransport};
+    use std::sync::Arc;
+    use std::time::SystemTime;
+
+    fn chunk() -> PcmChunk {
+        PcmChunk {
+            sequence: 0,
+            started_at: SystemTime::now(),
+            fetched_at: SystemTime::now(),
+            format: PcmFormat::whisper_f32_mono_16khz(),
+            samples: vec![0.0; 8_000],
+            duration_estimate: Duration::from_millis(500),
+            discontinuity: false,
+        }
+    }
+
+    #[test]
+    fn endpoints_resolve_to_their_api() {
+        let resolve = |e| resolve_endpoint(e).unwrap();
+        assert_eq!(
+            resolve("http://gpu-box:8080"),
+            ("http://gpu-box:8080/inference".to_owned(), Api::WhisperCpp)
+        );
+        assert_eq!(
+            resolve("http://gpu-box:8000/v1/"),
+            (
+                "http://gpu-box:8000/v1/audio/transcriptions".to_owned(),
+                Api::OpenAi
+            )
+        );
+        assert_eq!(resolve("http://gpu-box:8080/inference").1, Api::WhisperCpp);
+        assert!(matches!(
+            resolve_endpoint("gpu-box:8080"),
+            Err(AsrError::InvalidEndpoint(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn chunks_are_posted_as_a_wav_form() {
+        let transport = Arc::new(MockTransport::new().on(
+            HttpMethod::Post,
+            "/inference",
+            200,
+            r#"{"text":" Hello chat.\n"}"#,
+        ));
+        let asr = RemoteAsrBackend::new("http://gpu-box:8080", transport.clone())
+            .unwrap()
+            .with_language("PT")
+            .with_initial_prompt("Elden Ring");
+
+        let segment = asr.transcribe(chunk()).await.unwrap();
+        assert_eq!(segment.text, "Hello chat.");
+        assert_eq!(segment.audio_duration, Duration::from_millis(500));
+
+        let request = &transport.requests()[0];
+        assert_eq!(
+            request.header("content-type"),
+            Some("multipart/form-data; boundary=twitch-translator-asr-chunk")
+        );
+        let body = String::from_utf8_lossy(request.body.as_ref().unwrap());
+        assert!(body.contains("name=\"language\"\r\n\r\npt\r\n"));
+        assert!(body.contains("name=\"prompt\"\r\n\r\nElden Ring\r\n"));
+        assert!(body.contains("filename=\"chunk.wav\"\r\nContent-Type: audio/wav\r\n\r\nRIFF"));
+    }
+
+    #[tokio::test]
+    async fn silent_chunks_are_not_posted_with_vad() {
+        let transport = Arc::new(MockTransport::new());
+        let asr = RemoteAsrBackend::new("http://gpu-box:8080", transport.clone())
+            .unwrap()
+            .with_vad(true);
+
+        let segment = asr.transcribe(chunk()).await.unwrap();
+        assert_eq!(segment.text, "");
+        assert_eq!(segment.audio_duration, Duration::from_millis(500));
+        assert!(transport.requests().is_empty());
+    }
+}
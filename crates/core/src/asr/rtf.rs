@@ -0,0 +1,85 @@
+//! Real-time factor tracking
+//!
+//! The real-time factor (RTF) is inference time divided by audio duration.
+//! Above 1.0 the ASR cannot keep up and the delay grows without bound.
+//! [`RtfMonitor`] averages it over a window of chunks and reports a sustained
+//! overload once per full window.
+
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// Chunks averaged over before an overload is reported.
+pub const RTF_WINDOW: usize = 8;
+
+#[derive(Clone, Debug)]
+pub struct RtfMonitor {
+    samples: VecDeque<(Duration, Duration)>,
+    window: usize,
+    threshold: f64,
+}
+
+impl RtfMonitor {
+    /// Reports overload when the RTF over `window` chunks exceeds `threshold`,
+    /// normally the number of chunks transcribed in parallel.
+    pub fn new(window: usize, threshold: f64) -> Self {
+        let window = window.max(1);
+        Self {
+            samples: VecDeque::with_capacity(window),
+            window,
+            threshold,
+        }
+    }
+
+    /// Records one transcribed chunk. Returns the windowed RTF when the
+    /// window is full and above the threshold, then starts a new window so a
+    /// persisting overload is reported at most once per window.
+    pub fn record(&mut self, processing: Duration, audio: Duration) -> Option<f64> {
+        if audio.is_zero() {
+            return None;
+        }
+        if self.samples.len() == self.window {
+            self.samples.pop_front();
+        }
+        self.samples.push_back((processing, audio));
+        let rtf = self.rtf()?;
+        if self.samples.len() == self.window && rtf > self.threshold {
+            self.samples.clear();
+            return Some(rtf);
+        }
+        None
+    }
+
+    /// RTF over the chunks currently in the window.
+    pub fn rtf(&self) -> Option<f64> {
+        let (processing, audio) = self
+            .samples
+            .iter()
+            .fold((Duration::ZERO, Duration::ZERO), |(p, a), (dp, da)| {
+                (p + *dp, a + *da)
+            });
+        (!audio.is_zero()).then(|| processing.as_secs_f64() / audio.as_secs_f64())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_sustained_overload_once_per_window() {
+        let mut monitor = RtfMonitor::new(3, 1.0);
+        let secs = Duration::from_secs;
+
+        assert_eq!(monitor.record(secs(3), secs(2)), None);
+        assert_eq!(monitor.record(secs(3), secs(2)), None);
+        assert_eq!(monitor.record(secs(3), secs(2)), Some(1.5));
+        assert_eq!(monitor.rtf(), None);
+
+        // One fast chunk amid slow ones keeps the average under 1.0.
+        assert_eq!(monitor.record(secs(1), secs(4)), None);
+        assert_eq!(monitor.record(secs(3), secs(2)), None);
+        assert_eq!(monitor.record(secs(2), secs(2)), None);
+        assert_eq!(monitor.rtf(), Some(0.75));
+        assert_eq!(monitor.record(secs(1), Duration::ZERO), None);
+    }
+}
@@ -0,0 +1,51 @@
+//! Telling speech from silence
+//!
+//! Whisper makes up words for chunks without speech ("Thank you for
+//! watching."), and posting such a chunk to a server costs a round trip for
+//! nothing. [`has_speech`] is a plain energy detector run before either: a
+//! chunk holds speech if enough of its short frames are louder than a quiet
+//! room.
+
+/// Length of the frames looked at.
+const FRAME_MS: u32 = 30;
+/// Frames quieter than this (RMS of samples in -1..1, about -40 dBFS) are
+/// background.
+const SPEECH_RMS: f32 = 0.01;
+/// Loud frames a chunk needs, about a quarter of a second.
+const MIN_SPEECH_FRAMES: usize = 8;
+
+/// Whether interleaved `samples` (in -1..1) sound like they hold speech.
+pub(crate) fn has_speech(samples: &[f32], sample_rate_hz: u32, channels: u16) -> bool {
+    let frame = (sample_rate_hz * FRAME_MS / 1000) as usize * usize::from(channels.max(1));
+    if frame == 0 {
+        return true;
+    }
+    samples
+        .chunks(frame)
+        .filter(|frame| {
+            let energy: f32 = frame.iter().map(|s| s * s).sum();
+            (energy / frame.len() as f32).sqrt() >= SPEECH_RMS
+        })
+        .nth(MIN_SPEECH_FRAMES - 1)
+        .is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    #[test]
+    fn tells_speech_from_silence() {
+        let tone: Vec<f32> = (0..16_000)
+            .map(|i| 0.2 * (2.0 * PI * 180.0 * i as f32 / 16_000.0).sin())
+            .collect();
+        assert!(has_speech(&tone, 16_000, 1));
+        // Hiss and a click are not speech.
+        let mut quiet: Vec<f32> = (0..16_000)
+            .map(|i| if i % 2 == 0 { 0.003 } else { -0.003 })
+            .collect();
+        quiet[8000..8100].fill(0.5);
+        assert!(!has_speech(&quiet, 16_000, 1));
+    }
+}
@@ -1,40 +1,297 @@
-use crate::asr::{AsrBackend, AsrError, TranscriptSegment};
+use crate::asr::pool::StatePool;
+use crate::asr::vad::has_speech;
+use crate::asr::{smaller_installed_model, AsrBackend, AsrError, TranscriptSegment};
+use crate::config::{AsrConfig, AsrDevice, DEFAULT_ASR_THREADS};
 use crate::decode::PcmChunk;
+use crate::status::{PipelineEvent, PipelineStatus};
 use futures::future::BoxFuture;
 use futures::FutureExt;
-use std::sync::Arc;
+use std::os::raw::c_int;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
 use std::time::Duration;
-use tokio::sync::Mutex;
+use tokio::sync::broadcast;
 use whisper_rs::{FullParams, SamplingStrategy, WhisperContext, WhisperContextParameters, WhisperState};
 
+/// Whisper ASR over one loaded model.
+///
+/// Clones share the model and a pool of inference states, so pipelines and
+/// workers holding clones transcribe concurrently up to
+/// [`with_max_states`](Self::with_max_states) at a time (one by default).
+/// [`swap_model`](Self::swap_model) replaces the model for every clone.
+/// The spoken language and how chunks are decoded are set per clone.
 #[derive(Clone)]
 pub struct WhisperAsrBackend {
-    _ctx: Arc<WhisperContext>,
-    state: Arc<Mutex<WhisperState>>,
+    model: Arc<RwLock<Arc<LoadedModel>>>,
+    /// Whisper language code, or `auto` to detect it per chunk.
+    language: String,
+    /// CPU threads per transcription.
+    threads: usize,
+    /// Beam search width; 1 decodes greedily.
+    beam_size: usize,
+    /// Chunks without speech are not transcribed.
+    vad: bool,
 }
 
+/// A loaded model and everything derived from it. Transcriptions in flight
+/// keep the model they started on alive through a swap.
+#[derive(Clone)]
+struct LoadedModel {
+    path: String,
+    ctx: Arc<WhisperContext>,
+    states: Arc<StatePool<WhisperState>>,
+    device: AsrDevice,
+    prompt: Option<String>,
+    prompt_tokens: Arc<[c_int]>,
+}
+
+/// Whisper conditions on at most half its 448-token text context.
+const MAX_PROMPT_TOKENS: usize = 224;
+
 impl WhisperAsrBackend {
+    /// Loads the model on the best available device, see [`AsrDevice::Auto`].
     pub fn new(model_path: &str) -> Result<Self, AsrError> {
+        Self::with_device(model_path, AsrDevice::Auto)
+    }
+
+    /// Loads the model on `device`. A GPU backend this build was compiled
+    /// without, or one that fails to initialize (no driver, no device, out of
+    /// memory), falls back to the CPU with a warning.
+    pub fn with_device(model_path: &str, device: AsrDevice) -> Result<Self, AsrError> {
+        Ok(Self::from_model(LoadedModel::load_with_fallback(
+            model_path, device,
+        )?))
+    }
+
+    /// Loads `config.model_path` and applies the rest of `config`.
+    pub fn from_config(config: &AsrConfig) -> Result<Self, AsrError> {
+        let backend = Self::with_device(&config.model_path, config.device)?
+            .with_language(&config.language)
+            .with_decoding(config.threads, config.beam_size)
+            .with_vad(config.vad);
+        match &config.initial_prompt {
+            Some(prompt) => backend.with_initial_prompt(prompt),
+            None => Ok(backend),
+        }
+    }
+
+    fn from_model(model: LoadedModel) -> Self {
+        Self {
+            model: Arc::new(RwLock::new(Arc::new(model))),
+            language: "en".to_owned(),
+            threads: DEFAULT_ASR_THREADS,
+            beam_size: 1,
+            vad: false,
+        }
+    }
+
+    /// This clone's settings on `model`, no longer shared with other clones.
+    fn with_model(self, model: LoadedModel) -> Self {
+        Self {
+            model: Arc::new(RwLock::new(Arc::new(model))),
+            ..self
+        }
+    }
+
+    fn current(&self) -> Arc<LoadedModel> {
+        match self.model.read() {
+            Ok(model) => Arc::clone(&model),
+            Err(poisoned) => Arc::clone(&poisoned.into_inner()),
+        }
+    }
+
+    /// Primes every transcription with `prompt`, e.g. names and jargon of the
+    /// stream or a sample of the wanted punctuation and casing. Only the last
+    /// [`MAX_PROMPT_TOKENS`] tokens are kept, as Whisper itself would.
+    pub fn with_initial_prompt(self, prompt: &str) -> Result<Self, AsrError> {
+        let mut model = (*self.current()).clone();
+        model.set_prompt(Some(prompt.to_owned()))?;
+        Ok(self.with_model(model))
+    }
+
+    /// Transcribes `language`, a code such as `en` or `pt`, or detects the
+    /// language of each chunk for `auto`. Only this clone and clones made
+    /// from it are affected; the model stays shared.
+    pub fn with_language(mut self, language: &str) -> Self {
+        let language = language.trim().to_ascii_lowercase();
+        if !language.is_empty() {
+            self.language = language;
+        }
+        self
+    }
+
+    /// Decodes on `threads` CPU threads, keeping `beam_size` candidates in a
+    /// beam search, or greedily for 1. Only this clone and clones made from
+    /// it are affected.
+    pub fn with_decoding(mut self, threads: usize, beam_size: usize) -> Self {
+        self.threads = threads.max(1);
+        self.beam_size = beam_size.max(1);
+        self
+    }
+
+    /// Answers chunks without speech with an empty transcript rather than
+    /// running the model on them.
+    pub fn with_vad(mut self, vad: bool) -> Self {
+        self.vad = vad;
+        self
+    }
+
+    /// Allows up to `max_states` transcriptions to run at once, each with its
+    /// own state (a few hundred MB for larger models) on the shared model.
+    pub fn with_max_states(self, max_states: usize) -> Self {
+        let mut model = (*self.current()).clone();
+        model.states = Arc::new(model.states.resized(max_states));
+        self.with_model(model)
+    }
+
+    pub fn max_states(&self) -> usize {
+        self.current().states.max_states()
+    }
+
+    /// Where inference actually runs, after any fallback.
+    pub fn device(&self) -> AsrDevice {
+        self.current().device
+    }
+
+    pub fn model_path(&self) -> String {
+        self.current().path.clone()
+    }
+
+    /// Loads `model_path` with this backend's device, state limit and prompt,
+    /// then switches every clone over to it. Blocks while the model loads;
+    /// on failure the current model stays in use.
+    pub fn swap_model(&self, model_path: &str) -> Result<(), AsrError> {
+        let current = self.current();
+        let mut model = LoadedModel::load_with_fallback(model_path, current.device)?;
+        model.states = Arc::new(model.states.resized(current.states.max_states()));
+        model.set_prompt(current.prompt.clone())?;
+        match self.model.write() {
+            Ok(mut slot) => *slot = Arc::new(model),
+            Err(poisoned) => *poisoned.into_inner() = Arc::new(model),
+        }
+        Ok(())
+    }
+
+    /// Swaps to the next smaller installed model each time `status` reports
+    /// an [`PipelineEvent::AsrOverloaded`], publishing
+    /// [`PipelineEvent::AsrModelSwitched`]; runs until the event stream closes.
+    pub async fn downgrade_on_overload(self, status: PipelineStatus) {
+        let mut events = status.subscribe();
+        loop {
+            match events.recv().await {
+                Ok(PipelineEvent::AsrOverloaded { rtf }) => {
+                    let from = self.model_path();
+                    let Some((smaller, path)) = smaller_installed_model(Path::new(&from)) else {
+                        tracing::warn!(rtf, model = %from, "ASR overloaded and no smaller model is installed");
+                        continue;
+                    };
+                    let to = path.to_string_lossy().into_owned();
+                    tracing::warn!(rtf, from = %from, to = %to, "ASR overloaded; switching to {}", smaller.name);
+                    let backend = self.clone();
+                    let target = to.clone();
+                    match tokio::task::spawn_blocking(move || backend.swap_model(&target)).await {
+                        Ok(Ok(())) => {
+                            // Overload reports queued against the old model
+                            // say nothing about the new one.
+                            events = events.resubscribe();
+                            status.publish(PipelineEvent::AsrModelSwitched { from, to });
+                        }
+                        Ok(Err(e)) => tracing::error!(error = %e, model = %to, "model switch failed"),
+                        Err(e) => tracing::error!(error = %e, model = %to, "model switch panicked"),
+                    }
+                }
+                Ok(_) => {}
+                Err(broadcast::error::RecvError::Lagged(_)) => {}
+                Err(broadcast::error::RecvError::Closed) => return,
+            }
+        }
+    }
+}
+
+impl LoadedModel {
+    fn load_with_fallback(model_path: &str, device: AsrDevice) -> Result<Self, AsrError> {
         if !std::path::Path::new(model_path).exists() {
             return Err(AsrError::ModelNotFound(model_path.to_string()));
         }
 
+        let compiled = AsrDevice::compiled_gpus();
+        let gpu = match device {
+            AsrDevice::Cpu => None,
+            AsrDevice::Auto => compiled.first().copied(),
+            requested if compiled.contains(&requested) => Some(requested),
+            requested => {
+                tracing::warn!(
+                    device = %requested,
+                    "this build has no {requested} support; Whisper will run on the CPU"
+                );
+                None
+            }
+        };
+
+        if let Some(gpu) = gpu {
+            match Self::load(model_path, gpu) {
+                Ok(model) => {
+                    tracing::info!(device = %gpu, "Whisper model loaded with {gpu} GPU acceleration.");
+                    return Ok(model);
+                }
+                Err(e) => tracing::warn!(
+                    device = %gpu,
+                    error = %e,
+                    "{gpu} initialization failed; falling back to the CPU"
+                ),
+            }
+        }
+        let model = Self::load(model_path, AsrDevice::Cpu)?;
+        tracing::info!(device = "cpu", "Whisper model loaded on the CPU.");
+        Ok(model)
+    }
+
+    fn load(model_path: &str, device: AsrDevice) -> Result<Self, AsrError> {
         let mut ctx_params = WhisperContextParameters::default();
-        ctx_params.use_gpu(true);
+        ctx_params.use_gpu(device != AsrDevice::Cpu);
 
         let ctx = WhisperContext::new_with_params(model_path, ctx_params)
             .map_err(|e| AsrError::ModelLoadError(format!("Load failed: {e:?}")))?;
+        let ctx = Arc::new(ctx);
 
-        let state = ctx
-            .create_state()
-            .map_err(|e| AsrError::InferenceError(format!("State init failed: {e:?}")))?;
+        // Warming the pool creates the first state, which is where GPU
+        // buffers are allocated, so a broken GPU fails here.
+        let state_ctx = ctx.clone();
+        let states = StatePool::new(1, move || {
+            state_ctx.create_state()
+                .map_err(|e| AsrError::InferenceError(format!("State init failed: {e:?}")))
+        })
+        .warm()?;
 
-        tracing::info!("Whisper model loaded with Vulkan GPU acceleration.");
         Ok(Self {
-            _ctx: Arc::new(ctx),
-            state: Arc::new(Mutex::new(state)),
+            path: model_path.to_owned(),
+            ctx,
+            states: Arc::new(states),
+            device,
+            prompt: None,
+            prompt_tokens: Vec::new().into(),
         })
     }
+
+    /// Tokenizes `prompt` with this model's vocabulary.
+    fn set_prompt(&mut self, prompt: Option<String>) -> Result<(), AsrError> {
+        let prompt = prompt
+            .map(|p| p.trim().to_owned())
+            .filter(|p| !p.is_empty());
+        self.prompt_tokens = match &prompt {
+            Some(prompt) => {
+                let tokens = self
+                    .ctx
+                    .tokenize(prompt, prompt.len() + 1)
+                    .map_err(|e| AsrError::InferenceError(format!("Prompt tokenization failed: {e:?}")))?;
+                let start = tokens.len().saturating_sub(MAX_PROMPT_TOKENS);
+                tokens[start..].into()
+            }
+            None => Vec::new().into(),
+        };
+        self.prompt = prompt;
+        Ok(())
+    }
 }
 
 impl AsrBackend for WhisperAsrBackend {
@@ -43,35 +300,76 @@ impl AsrBackend for WhisperAsrBackend {
             if audio.samples.is_empty() {
                 return Err(AsrError::EmptyAudio);
             }
+            let duration = Duration::from_secs_f32(audio.samples.len() as f32 / 16000.0);
+            let format = audio.format;
+            if self.vad && !has_speech(&audio.samples, format.sample_rate, format.channels) {
+                return Ok(TranscriptSegment {
+                    text: String::new(),
+                    audio_duration: duration,
+                    confidence: None,
+                    words: Vec::new(),
+                    speech_start: None,
+                });
+            }
 
-            let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
-            params.set_n_threads(4);
-            params.set_language(Some("en"));
+            let model = self.current();
+            let mut state = model.states.acquire().await?;
+            let samples = audio.samples;
+            let prompt_tokens = model.prompt_tokens.clone();
+            let language = self.language.clone();
+            let threads = self.threads as c_int;
+            let strategy = if self.beam_size > 1 {
+                SamplingStrategy::BeamSearch {
+                    beam_size: self.beam_size as c_int,
+                    // -1 keeps whisper.cpp's default patience.
+                    patience: -1.0,
+                }
+            } else {
+                SamplingStrategy::Greedy { best_of: 1 }
+            };
 
-            let mut state = self.state.lock().await;
+            // Inference blocks for the whole segment; keep it off the async
+            // workers so other pipelines' states can run in parallel.
+            let (text, speech_start) = tokio::task::spawn_blocking(move || {
+                let mut params = FullParams::new(strategy);
+                params.set_n_threads(threads);
+                params.set_language(Some(&language));
+                if !prompt_tokens.is_empty() {
+                    params.set_tokens(&prompt_tokens);
+                }
 
-            state
-                .full(params, &audio.samples)
-                .map_err(|e| AsrError::InferenceError(format!("Inference failed: {e:?}")))?;
+                state
+                    .full(params, &samples)
+                    .map_err(|e| AsrError::InferenceError(format!("Inference failed: {e:?}")))?;
 
-            let num_segments = state.full_n_segments();
-            let mut text = String::new();
+                let num_segments = state.full_n_segments();
+                let mut text = String::new();
+                let mut speech_start = None;
 
-            for i in 0..num_segments {
-                if let Some(segment) = state.get_segment(i) {
-                    if let Ok(segment_text) = segment.to_str() {
-                        text.push_str(segment_text);
-                        text.push(' ');
+                for i in 0..num_segments {
+                    if let Some(segment) = state.get_segment(i) {
+                        if let Ok(segment_text) = segment.to_str() {
+                            text.push_str(segment_text);
+                            text.push(' ');
+                            if speech_start.is_none() && !segment_text.trim().is_empty() {
+                                // Timestamps are in centiseconds.
+                                let t0 = segment.start_timestamp().max(0) as u64;
+                                speech_start = Some(Duration::from_millis(t0 * 10));
+                            }
+                        }
                     }
                 }
-            }
-
-            let duration = Duration::from_secs_f32(audio.samples.len() as f32 / 16000.0);
+                Ok::<_, AsrError>((text, speech_start))
+            })
+            .await
+            .map_err(|e| AsrError::InferenceError(format!("Inference task failed: {e}")))??;
 
             Ok(TranscriptSegment {
                 text: text.trim().to_string(),
                 audio_duration: duration,
                 confidence: None,
+                words: Vec::new(),
+                speech_start,
             })
         }
         .boxed()
@@ -0,0 +1,377 @@
+//! Backend benchmarking
+//!
+//! [`Bench`] pushes a local audio file through decode → ASR → translate → TTS
+//! repeatedly, outside the streaming pipeline, and reports latency percentiles
+//! per stage. Comparing the ASR real-time factor across Whisper model sizes is
+//! the main use: anything near or above 1.0 cannot keep up with a live stream.
+
+use crate::asr::AsrBackend;
+use crate::config::TargetLang;
+use crate::decode::AudioDecoder;
+use crate::ingest::IngestItem;
+use crate::status::Stage;
+use crate::translate::Translator;
+use crate::tts::{TtsClient, TtsRequest};
+use bytes::Bytes;
+use serde::Serialize;
+use std::fmt;
+use std::time::{Duration, Instant, SystemTime};
+use url::Url;
+
+#[derive(thiserror::Error, Debug)]
+pub enum BenchError {
+    #[error("failed to read bench input: {0}")]
+    Input(#[from] std::io::Error),
+
+    #[error("iterations must be at least 1")]
+    NoIterations,
+}
+
+pub struct Bench<D, A, Tr, Ts> {
+    pub decode: D,
+    pub asr: A,
+    /// Skipped when `None`, e.g. without a DeepL key.
+    pub translate: Option<Tr>,
+    /// Skipped when `None`.
+    pub tts: Option<Ts>,
+    pub target_lang: TargetLang,
+    pub iterations: usize,
+}
+
+impl<D, A, Tr, Ts> Bench<D, A, Tr, Ts>
+where
+    D: AudioDecoder,
+    A: AsrBackend,
+    Tr: Translator,
+    Ts: TtsClient,
+{
+    /// Benchmarks the file at `path`, read once up front so disk speed is not measured.
+    pub async fn run_file(&self, path: &std::path::Path) -> Result<BenchReport, BenchError> {
+        let bytes = Bytes::from(tokio::fs::read(path).await?);
+        let url = Url::from_file_path(std::path::absolute(path)?)
+            .unwrap_or_else(|_| Url::parse("file:///bench-input").expect("static url"));
+        self.run(bytes, url).await
+    }
+
+    pub async fn run(&self, input: Bytes, url: Url) -> Result<BenchReport, BenchError> {
+        if self.iterations == 0 {
+            return Err(BenchError::NoIterations);
+        }
+
+        let mut samples = StageSamples::default();
+        let mut audio_duration = Duration::ZERO;
+        let mut transcript = None;
+
+        for sequence in 0..self.iterations as u64 {
+            let item = IngestItem {
+                sequence,
+                fetched_at: SystemTime::now(),
+                url: url.clone(),
+                approx_duration: Duration::ZERO,
+                bytes: input.clone(),
+                discontinuity: false,
+                gap_before: Duration::ZERO,
+            };
+
+            let started = Instant::now();
+            let pcm = match self.decode.decode_segment(item).await {
+                Ok(pcm) => pcm,
+                Err(e) => {
+                    samples.error(Stage::Decode, e.to_string());
+                    continue;
+                }
+            };
+            samples.record(Stage::Decode, started.elapsed());
+            audio_duration = pcm.duration_estimate;
+
+            let started = Instant::now();
+            let text = match self.asr.transcribe(pcm).await {
+                Ok(segment) => segment.text,
+                Err(e) => {
+                    samples.error(Stage::Asr, e.to_string());
+                    continue;
+                }
+            };
+            samples.record(Stage::Asr, started.elapsed());
+            if text.trim().is_empty() {
+                continue;
+            }
+            transcript.get_or_insert_with(|| text.clone());
+
+            let text = match &self.translate {
+                Some(translate) => {
+                    let started = Instant::now();
+                    match translate.translate(text, self.target_lang.clone()).await {
+                        Ok(t) => {
+                            samples.record(Stage::Translate, started.elapsed());
+                            t.text
+                        }
+                        Err(e) => {
+                            samples.error(Stage::Translate, e.to_string());
+                            continue;
+                        }
+                    }
+                }
+                None => text,
+            };
+
+            if let Some(tts) = &self.tts {
+                let started = Instant::now();
+                let request = TtsRequest {
+                    text,
+                    voice: None,
+                    prosody: None,
+                    speaker: None,
+                };
+                match tts.synthesize(request).await {
+                    Ok(_) => samples.record(Stage::Tts, started.elapsed()),
+                    Err(e) => samples.error(Stage::Tts, e.to_string()),
+                }
+            }
+        }
+
+        Ok(samples.into_report(self.iterations, audio_duration, transcript))
+    }
+}
+
+#[derive(Default)]
+struct StageSamples {
+    latencies: Vec<(Stage, Duration)>,
+    errors: Vec<(Stage, String)>,
+}
+
+impl StageSamples {
+    fn record(&mut self, stage: Stage, elapsed: Duration) {
+        self.latencies.push((stage, elapsed));
+    }
+
+    fn error(&mut self, stage: Stage, message: String) {
+        tracing::warn!(stage = stage.as_str(), error = %message, "bench iteration failed");
+        self.errors.push((stage, message));
+    }
+
+    fn into_report(
+        self,
+        iterations: usize,
+        audio_duration: Duration,
+        transcript: Option<String>,
+    ) -> BenchReport {
+        let stages = Stage::ALL
+            .iter()
+            .filter_map(|stage| {
+                let latencies: Vec<Duration> = self
+                    .latencies
+                    .iter()
+                    .filter(|(s, _)| s == stage)
+                    .map(|(_, d)| *d)
+                    .collect();
+                let errors = self.errors.iter().filter(|(s, _)| s == stage).count();
+                if latencies.is_empty() && errors == 0 {
+                    return None;
+                }
+                Some(StageStats::from_latencies(
+                    *stage,
+                    latencies,
+                    errors,
+                    audio_duration,
+                ))
+            })
+            .collect();
+
+        BenchReport {
+            iterations,
+            audio_duration_ms: audio_duration.as_secs_f64() * 1000.0,
+            transcript,
+            stages,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq)]
+pub struct StageStats {
+    pub stage: Stage,
+    pub runs: usize,
+    pub errors: usize,
+    pub mean_ms: f64,
+    pub p50_ms: f64,
+    pub p90_ms: f64,
+    pub p99_ms: f64,
+    /// Median processing time divided by the input's audio duration.
+    pub real_time_factor: Option<f64>,
+}
+
+impl StageStats {
+    fn from_latencies(
+        stage: Stage,
+        mut latencies: Vec<Duration>,
+        errors: usize,
+        audio_duration: Duration,
+    ) -> Self {
+        latencies.sort();
+        let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+        let mean_ms = if latencies.is_empty() {
+            0.0
+        } else {
+            latencies.iter().map(|d| ms(*d)).sum::<f64>() / latencies.len() as f64
+        };
+        let p50 = percentile(&latencies, 50.0);
+        let real_time_factor = match stage {
+            Stage::Decode | Stage::Asr if !audio_duration.is_zero() && !latencies.is_empty() => {
+                Some(p50.as_secs_f64() / audio_duration.as_secs_f64())
+            }
+            _ => None,
+        };
+
+        Self {
+            stage,
+            runs: latencies.len(),
+            errors,
+            mean_ms,
+            p50_ms: ms(p50),
+            p90_ms: ms(percentile(&latencies, 90.0)),
+            p99_ms: ms(percentile(&latencies, 99.0)),
+            real_time_factor,
+        }
+    }
+}
+
+/// Nearest-rank percentile of an ascending slice; zero when empty.
+fn percentile(sorted: &[Duration], pct: f64) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    let rank = ((pct / 100.0) * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq)]
+pub struct BenchReport {
+    pub iterations: usize,
+    pub audio_duration_ms: f64,
+    /// First non-empty transcript, to sanity-check the ASR output.
+    pub transcript: Option<String>,
+    pub stages: Vec<StageStats>,
+}
+
+impl fmt::Display for BenchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} iterations over {:.0} ms of audio",
+            self.iterations, self.audio_duration_ms
+        )?;
+        writeln!(
+            f,
+            "{:<10} {:>5} {:>6} {:>9} {:>9} {:>9} {:>9} {:>6}",
+            "stage", "runs", "errors", "mean ms", "p50 ms", "p90 ms", "p99 ms", "rtf"
+        )?;
+        for s in &self.stages {
+            let rtf = s
+                .real_time_factor
+                .map(|r| format!("{r:.2}"))
+                .unwrap_or_else(|| "-".to_owned());
+            writeln!(
+                f,
+                "{:<10} {:>5} {:>6} {:>9.1} {:>9.1} {:>9.1} {:>9.1} {:>6}",
+                s.stage.as_str(),
+                s.runs,
+                s.errors,
+                s.mean_ms,
+                s.p50_ms,
+                s.p90_ms,
+                s.p99_ms,
+                rtf
+            )?;
+        }
+        if let Some(t) = &self.transcript {
+            write!(f, "transcript: {t}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asr::{AsrError, TranscriptSegment};
+    use crate::decode::{DecodeError, PcmChunk, PcmFormat};
+    use crate::translate::DummyTranslator;
+    use crate::tts::BasicTtsClient;
+    use futures::future::BoxFuture;
+    use futures::FutureExt;
+
+    struct SilenceDecoder;
+
+    impl AudioDecoder for SilenceDecoder {
+        fn decode_segment(&self, item: IngestItem) -> BoxFuture<'_, Result<PcmChunk, DecodeError>> {
+            async move {
+                Ok(PcmChunk {
+                    sequence: item.sequence,
+                    started_at: item.fetched_at,
+                    fetched_at: item.fetched_at,
+                    format: PcmFormat::whisper_f32_mono_16khz(),
+                    samples: vec![0.0; 16_000],
+                    duration_estimate: Duration::from_secs(1),
+                    discontinuity: item.discontinuity,
+                })
+            }
+            .boxed()
+        }
+    }
+
+    struct FixedAsr;
+
+    impl AsrBackend for FixedAsr {
+        fn transcribe(
+            &self,
+            audio: PcmChunk,
+        ) -> BoxFuture<'_, Result<TranscriptSegment, AsrError>> {
+            async move {
+                Ok(TranscriptSegment {
+                    text: "hello world".to_owned(),
+                    audio_duration: audio.duration_estimate,
+                    confidence: None,
+                    words: Vec::new(),
+                    speech_start: None,
+                })
+            }
+            .boxed()
+        }
+    }
+
+    #[test]
+    fn percentile_uses_nearest_rank() {
+        let data: Vec<Duration> = (1..=10).map(Duration::from_millis).collect();
+        assert_eq!(percentile(&data, 50.0), Duration::from_millis(5));
+        assert_eq!(percentile(&data, 90.0), Duration::from_millis(9));
+        assert_eq!(percentile(&data, 99.0), Duration::from_millis(10));
+        assert_eq!(percentile(&[], 50.0), Duration::ZERO);
+    }
+
+    #[tokio::test]
+    async fn reports_each_enabled_stage() {
+        let bench = Bench {
+            decode: SilenceDecoder,
+            asr: FixedAsr,
+            translate: Some(DummyTranslator::new()),
+            tts: None::<BasicTtsClient>,
+            target_lang: TargetLang::default(),
+            iterations: 3,
+        };
+        let report = bench
+            .run(
+                Bytes::from_static(b"x"),
+                Url::parse("file:///x.wav").unwrap(),
+            )
+            .await
+            .unwrap();
+
+        let stages: Vec<Stage> = report.stages.iter().map(|s| s.stage).collect();
+        assert_eq!(stages, vec![Stage::Decode, Stage::Asr, Stage::Translate]);
+        assert!(report.stages.iter().all(|s| s.runs == 3 && s.errors == 0));
+        assert_eq!(report.audio_duration_ms, 1000.0);
+        assert!(report.stages[1].real_time_factor.is_some());
+        assert!(report.stages[2].real_time_factor.is_none());
+        assert_eq!(report.transcript.as_deref(), Some("hello world"));
+    }
+}
@@ -0,0 +1,253 @@
+//! CEA-608 caption bytes for CC1 in two-row roll-up mode
+
+use std::collections::VecDeque;
+
+/// Columns on a 608 caption row.
+const ROW_COLUMNS: usize = 32;
+/// Pairs queued beyond this, about ten seconds at 30 fps, are dropped in
+/// favour of the newest caption.
+const MAX_QUEUED_PAIRS: usize = 300;
+
+/// Roll-up captions, two rows.
+const RU2: [u8; 2] = [0x14, 0x25];
+/// Carriage return: rolls the rows up and starts a new bottom row.
+const CR: [u8; 2] = [0x14, 0x2d];
+/// Preamble address code: row 15, column 0, white.
+const PAC_ROW_15: [u8; 2] = [0x14, 0x60];
+
+/// Two-byte special characters, sent as `0x11, 0x30 + index`.
+const SPECIAL: [char; 16] = [
+    '®', '°', '½', '¿', '™', '¢', '£', '♪', 'à', '\u{a0}', 'è', 'â', 'ê', 'î', 'ô', 'û',
+];
+
+/// Extended characters, sent as `0x12` or `0x13`, `0x20 + index`, after a
+/// basic fallback character the decoder replaces.
+const EXTENDED: [(u8, [char; 32], &str); 2] = [
+    (
+        0x12,
+        [
+            'Á', 'É', 'Ó', 'Ú', 'Ü', 'ü', '‘', '¡', '*', '’', '—', '©', '℠', '•', '“', '”', 'À',
+            'Â', 'Ç', 'È', 'Ê', 'Ë', 'ë', 'Î', 'Ï', 'ï', 'Ô', 'Ù', 'ù', 'Û', '«', '»',
+        ],
+        "AEOUUu'!-'-cs.\"\"AACEEEeIIiOUuU\"\"",
+    ),
+    (
+        0x13,
+        [
+            'Ã', 'ã', 'Í', 'Ì', 'ì', 'Ò', 'ò', 'Õ', 'õ', '{', '}', '\\', '^', '_', '|', '~', 'Ä',
+            'ä', 'Ö', 'ö', 'ß', '¥', '¤', '│', 'Å', 'å', 'Ø', 'ø', '┌', '┐', '└', '┘',
+        ],
+        "AaIIiOoOo()/ -!-AaOosYc!AaOo++++",
+    ),
+];
+
+/// Turns caption text into the byte pairs a decoder receives one per video
+/// frame, with odd parity applied.
+#[derive(Debug, Default)]
+pub struct Cea608Encoder {
+    pairs: VecDeque<[u8; 2]>,
+}
+
+impl Cea608Encoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queues `text` as new rows rolling up from the bottom of the screen.
+    /// Characters 608 cannot show are sent as `?`.
+    pub fn push_caption(&mut self, text: &str) {
+        if self.pairs.len() > MAX_QUEUED_PAIRS {
+            tracing::debug!(dropped = self.pairs.len(), "closed captions fell behind");
+            self.pairs.clear();
+        }
+        for row in rows(text) {
+            // Every row restates the mode, so viewers who tune in mid-stream
+            // see captions from the next row on.
+            for code in [RU2, CR, PAC_ROW_15] {
+                self.push_code(code);
+            }
+            let mut pending = None;
+            for c in row.chars() {
+                match encode_char(c) {
+                    Encoded::Basic(b) => match pending.take() {
+                        Some(first) => self.pairs.push_back(with_parity([first, b])),
+                        None => pending = Some(b),
+                    },
+                    Encoded::Code(fallback, code) => {
+                        if let Some(first) = pending.take() {
+                            self.pairs.push_back(with_parity([first, 0]));
+                        }
+                        if let Some(fallback) = fallback {
+                            self.pairs.push_back(with_parity([fallback, 0]));
+                        }
+                        self.push_code(code);
+                    }
+                }
+            }
+            if let Some(first) = pending {
+                self.pairs.push_back(with_parity([first, 0]));
+            }
+        }
+    }
+
+    /// The pair for the next video frame, if any caption is pending.
+    pub fn next_pair(&mut self) -> Option<[u8; 2]> {
+        self.pairs.pop_front()
+    }
+
+    /// Two-byte codes go out twice; decoders ignore an immediate repeat, so
+    /// one corrupted copy does not lose the command.
+    fn push_code(&mut self, code: [u8; 2]) {
+        let code = with_parity(code);
+        self.pairs.push_back(code);
+        self.pairs.push_back(code);
+    }
+}
+
+enum Encoded {
+    Basic(u8),
+    /// A two-byte code, after a basic fallback for extended characters.
+    Code(Option<u8>, [u8; 2]),
+}
+
+fn encode_char(c: char) -> Encoded {
+    let basic = match c {
+        'á' => Some(0x2a),
+        'é' => Some(0x5c),
+        'í' => Some(0x5e),
+        'ó' => Some(0x5f),
+        'ú' => Some(0x60),
+        'ç' => Some(0x7b),
+        '÷' => Some(0x7c),
+        'Ñ' => Some(0x7d),
+        'ñ' => Some(0x7e),
+        // The basic set reuses these codes for the accented letters above.
+        '*' | '\\' | '^' | '_' | '`' | '{' | '|' | '}' | '~' => None,
+        ' '..='\u{7e}' => Some(c as u8),
+        _ => None,
+    };
+    if let Some(b) = basic {
+        return Encoded::Basic(b);
+    }
+    if let Some(i) = SPECIAL.iter().position(|&s| s == c) {
+        return Encoded::Code(None, [0x11, 0x30 + i as u8]);
+    }
+    for (first, chars, fallbacks) in &EXTENDED {
+        if let Some(i) = chars.iter().position(|&e| e == c) {
+            let fallback = fallbacks.as_bytes()[i];
+            return Encoded::Code(Some(fallback), [*first, 0x20 + i as u8]);
+        }
+    }
+    Encoded::Basic(b'?')
+}
+
+/// Splits `text` into rows of at most [`ROW_COLUMNS`] characters, breaking
+/// between words where possible.
+fn rows(text: &str) -> Vec<String> {
+    let mut rows = Vec::new();
+    let mut row = String::new();
+    for word in text.split_whitespace() {
+        let mut word = word;
+        loop {
+            let row_len = row.chars().count();
+            let word_len = word.chars().count();
+            let needed = if row.is_empty() {
+                word_len
+            } else {
+                row_len + 1 + word_len
+            };
+            if needed <= ROW_COLUMNS {
+                if !row.is_empty() {
+                    row.push(' ');
+                }
+                row.push_str(word);
+                break;
+            }
+            if !row.is_empty() {
+                rows.push(std::mem::take(&mut row));
+                continue;
+            }
+            // A word longer than a row is cut.
+            let cut = word
+                .char_indices()
+                .nth(ROW_COLUMNS)
+                .map_or(word.len(), |(i, _)| i);
+            rows.push(word[..cut].to_owned());
+            word = &word[cut..];
+            if word.is_empty() {
+                break;
+            }
+        }
+    }
+    if !row.is_empty() {
+        rows.push(row);
+    }
+    rows
+}
+
+/// Sets bit 7 of each byte so it has an odd number of ones.
+fn with_parity(pair: [u8; 2]) -> [u8; 2] {
+    pair.map(|b| {
+        let b = b & 0x7f;
+        if b.count_ones() % 2 == 0 {
+            b | 0x80
+        } else {
+            b
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn drain(encoder: &mut Cea608Encoder) -> Vec<[u8; 2]> {
+        std::iter::from_fn(|| encoder.next_pair()).collect()
+    }
+
+    #[test]
+    fn parity_is_odd() {
+        assert_eq!(with_parity([0x14, 0x25]), [0x94, 0x25]);
+        assert_eq!(with_parity([0x00, 0x41]), [0x80, 0xc1]);
+    }
+
+    #[test]
+    fn caption_is_rolled_up_with_doubled_codes() {
+        let mut encoder = Cea608Encoder::new();
+        encoder.push_caption("Olá");
+        let pairs = drain(&mut encoder);
+        let codes: Vec<[u8; 2]> = [RU2, RU2, CR, CR, PAC_ROW_15, PAC_ROW_15]
+            .into_iter()
+            .map(with_parity)
+            .collect();
+        assert_eq!(pairs[..6], codes[..]);
+        assert_eq!(
+            pairs[6..],
+            [with_parity([b'O', b'l']), with_parity([0x2a, 0])]
+        );
+    }
+
+    #[test]
+    fn extended_characters_follow_a_fallback() {
+        let mut encoder = Cea608Encoder::new();
+        encoder.push_caption("Ã");
+        let pairs = drain(&mut encoder);
+        assert_eq!(
+            pairs[6..],
+            [
+                with_parity([b'A', 0]),
+                with_parity([0x13, 0x20]),
+                with_parity([0x13, 0x20])
+            ]
+        );
+    }
+
+    #[test]
+    fn long_text_is_split_into_rows() {
+        let text = "the quick brown fox jumps over the lazy dog again and again";
+        let split = rows(text);
+        assert!(split.iter().all(|r| r.chars().count() <= ROW_COLUMNS));
+        assert_eq!(split.join(" "), text);
+        assert_eq!(rows(&"x".repeat(40)), ["x".repeat(32), "x".repeat(8)]);
+    }
+}
@@ -0,0 +1,165 @@
+//! FLV and H.264 plumbing for the caption relay
+//!
+//! Caption pairs travel inside the video as SEI NAL units in the ATSC A/53
+//! format, the same way hardware caption encoders and OBS embed them, so the
+//! stream needs no re-encoding: each H.264 frame tag just gains one NAL.
+
+pub const TAG_HEADER_BYTES: usize = 11;
+pub const TAG_VIDEO: u8 = 9;
+
+const CODEC_AVC: u8 = 7;
+/// Set in the first byte of enhanced RTMP video tags (HEVC, AV1, ...),
+/// which are passed through uncaptioned.
+const EX_HEADER: u8 = 0x80;
+const AVC_SEQUENCE_HEADER: u8 = 0;
+const AVC_NALU: u8 = 1;
+/// Frame type and codec, packet type and composition time.
+const AVC_TAG_PREFIX: usize = 5;
+const NAL_SEI: u8 = 6;
+const NAL_AUD: u8 = 9;
+/// `user_data_registered_itu_t_t35`
+const SEI_USER_DATA: u8 = 4;
+
+/// What an FLV video tag holds, as far as captioning is concerned.
+#[derive(Debug, PartialEq, Eq)]
+pub enum VideoTag {
+    /// The decoder configuration, giving the size of each NAL length prefix.
+    AvcConfig {
+        length_size: usize,
+    },
+    /// One H.264 frame as length-prefixed NAL units.
+    AvcFrame,
+    Other,
+}
+
+pub fn classify(data: &[u8]) -> VideoTag {
+    if data.len() < AVC_TAG_PREFIX || data[0] & EX_HEADER != 0 || data[0] & 0x0f != CODEC_AVC {
+        return VideoTag::Other;
+    }
+    match data[1] {
+        // AVCDecoderConfigurationRecord: lengthSizeMinusOne is in byte 4.
+        AVC_SEQUENCE_HEADER => match data.get(AVC_TAG_PREFIX + 4) {
+            Some(b) => VideoTag::AvcConfig {
+                length_size: usize::from(b & 0x03) + 1,
+            },
+            None => VideoTag::Other,
+        },
+        AVC_NALU => VideoTag::AvcFrame,
+        _ => VideoTag::Other,
+    }
+}
+
+/// Adds `nal` to the frame tag `data`, after the access unit delimiter if
+/// there is one, since an SEI must not come before it.
+pub fn insert_nal(data: &mut Vec<u8>, length_size: usize, nal: &[u8]) {
+    let mut at = AVC_TAG_PREFIX;
+    if let Some(len) = data.get(at..at + length_size) {
+        let len = len.iter().fold(0usize, |n, &b| n << 8 | usize::from(b));
+        let first = at + length_size;
+        if data.get(first).is_some_and(|h| h & 0x1f == NAL_AUD) && first + len <= data.len() {
+            at = first + len;
+        }
+    }
+    let mut prefixed = nal.len().to_be_bytes()[8 - length_size..].to_vec();
+    prefixed.extend_from_slice(nal);
+    data.splice(at..at, prefixed);
+}
+
+/// An SEI NAL unit carrying `pair` as CEA-608 field 1 data.
+pub fn caption_sei(pair: [u8; 2]) -> Vec<u8> {
+    let payload = [
+        0xb5, // ITU-T T.35 country code: United States
+        0x00, 0x31, // provider: ATSC
+        b'G', b'A', b'9', b'4', // user identifier
+        0x03, // cc_data
+        0x41, // process_cc_data_flag, cc_count = 1
+        0xff, // em_data
+        0xfc, // marker bits, cc_valid, cc_type = NTSC field 1
+        pair[0], pair[1], 0xff, // marker bits
+    ];
+    let mut rbsp = vec![SEI_USER_DATA, payload.len() as u8];
+    rbsp.extend_from_slice(&payload);
+    rbsp.push(0x80); // rbsp_trailing_bits
+    let mut nal = vec![NAL_SEI];
+    nal.extend(with_emulation_prevention(&rbsp));
+    nal
+}
+
+/// Escapes byte sequences that would read as a start code.
+fn with_emulation_prevention(rbsp: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(rbsp.len() + 4);
+    let mut zeros = 0;
+    for &b in rbsp {
+        if zeros >= 2 && b <= 3 {
+            out.push(3);
+            zeros = 0;
+        }
+        out.push(b);
+        zeros = if b == 0 { zeros + 1 } else { 0 };
+    }
+    out
+}
+
+/// The 24-bit big-endian field at `at`.
+pub fn read_u24(bytes: &[u8], at: usize) -> usize {
+    bytes[at..at + 3]
+        .iter()
+        .fold(0, |n, &b| n << 8 | usize::from(b))
+}
+
+pub fn write_u24(bytes: &mut [u8], at: usize, value: usize) {
+    bytes[at..at + 3].copy_from_slice(&(value as u32).to_be_bytes()[1..]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A keyframe tag holding an AUD and one slice.
+    fn frame() -> Vec<u8> {
+        let mut data = vec![0x17, AVC_NALU, 0, 0, 0];
+        data.extend([0, 0, 0, 2, NAL_AUD, 0xf0]);
+        data.extend([0, 0, 0, 3, 0x65, 0xaa, 0xbb]);
+        data
+    }
+
+    #[test]
+    fn classifies_avc_tags() {
+        let config = [0x17, AVC_SEQUENCE_HEADER, 0, 0, 0, 1, 0x64, 0, 0x1f, 0xff];
+        assert_eq!(classify(&config), VideoTag::AvcConfig { length_size: 4 });
+        assert_eq!(classify(&frame()), VideoTag::AvcFrame);
+        // HEVC over enhanced RTMP
+        assert_eq!(classify(&[0x91, 0, 0, 0, 0]), VideoTag::Other);
+    }
+
+    #[test]
+    fn sei_goes_after_the_access_unit_delimiter() {
+        let mut data = frame();
+        insert_nal(&mut data, 4, &[NAL_SEI, 0x01]);
+        assert_eq!(
+            data[5..],
+            [0, 0, 0, 2, NAL_AUD, 0xf0, 0, 0, 0, 2, NAL_SEI, 0x01, 0, 0, 0, 3, 0x65, 0xaa, 0xbb]
+        );
+
+        let mut data = vec![0x27, AVC_NALU, 0, 0, 0, 0, 1, 0x41];
+        insert_nal(&mut data, 2, &[NAL_SEI]);
+        assert_eq!(data[5..], [0, 1, NAL_SEI, 0, 1, 0x41]);
+    }
+
+    #[test]
+    fn sei_carries_the_pair_as_a53_cc_data() {
+        let sei = caption_sei([0x94, 0x25]);
+        assert_eq!(sei[..3], [NAL_SEI, SEI_USER_DATA, 14]);
+        assert_eq!(&sei[6..10], b"GA94");
+        assert_eq!(sei[13..16], [0xfc, 0x94, 0x25]);
+        assert_eq!(sei.last(), Some(&0x80));
+    }
+
+    #[test]
+    fn start_codes_are_escaped() {
+        assert_eq!(
+            with_emulation_prevention(&[0, 0, 1, 0, 0, 0, 5]),
+            [0, 0, 3, 1, 0, 0, 3, 0, 5]
+        );
+    }
+}
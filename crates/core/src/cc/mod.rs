@@ -0,0 +1,205 @@
+//! Closed captions embedded in a relayed broadcast
+//!
+//! With a caption relay, a stream pushed to
+//! [`PushIngestor`](crate::ingest::PushIngestor) is also forwarded to an RTMP
+//! server such as Twitch, with each translation embedded in the video as
+//! CEA-608 captions (CC1) that viewers turn on with the player's CC button.
+//! The ingest ffmpeg remuxes the push to FLV over a local socket; video tags
+//! gain a caption SEI here and are piped into a second ffmpeg that publishes
+//! the stream unchanged otherwise. Only H.264 video is captioned, and captions
+//! are attached in decode order, which decoders accept for 608 data.
+//! Translations in scripts 608 has no characters for can be romanized first.
+
+mod cea608;
+mod flv;
+
+pub use cea608::Cea608Encoder;
+
+use crate::ingest::push::ffmpeg_binary;
+use crate::ingest::IngestError;
+use crate::status::PipelineEvent;
+use flv::VideoTag;
+use std::io::ErrorKind;
+use std::process::Stdio;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+use tokio::sync::broadcast;
+
+/// Accepts the ingest ffmpeg's FLV on `listener` and publishes it to
+/// `target` with the translations from `events` as captions, until the
+/// stream ends. If publishing fails the stream is still drained, so the
+/// translation itself keeps running. With `romanize`, captions in non-Latin
+/// scripts are spelled with Latin letters.
+pub async fn relay(
+    listener: TcpListener,
+    target: String,
+    events: broadcast::Receiver<PipelineEvent>,
+    romanize: bool,
+) -> Result<(), IngestError> {
+    let (input, _) = listener.accept().await?;
+    let mut child = tokio::process::Command::new(ffmpeg_binary())
+        .args(["-hide_banner", "-nostdin", "-loglevel", "warning"])
+        .args(["-f", "flv", "-i", "pipe:0", "-map", "0", "-c", "copy"])
+        .args(["-f", "flv"])
+        .arg(&target)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| IngestError::Ffmpeg(format!("failed to start ffmpeg: {e}")))?;
+    let output = child
+        .stdin
+        .take()
+        .ok_or_else(|| IngestError::Ffmpeg("ffmpeg stdin unavailable".to_owned()))?;
+    if let Some(stderr) = child.stderr.take() {
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(stderr).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                tracing::warn!(target: "ffmpeg", "caption relay: {}", line);
+            }
+        });
+    }
+
+    tracing::info!("Relaying the pushed stream with closed captions");
+    pump(BufReader::new(input), output, events, romanize).await?;
+    let status = child.wait().await?;
+    if !status.success() {
+        return Err(IngestError::Ffmpeg(format!(
+            "caption relay exited with {status}"
+        )));
+    }
+    Ok(())
+}
+
+/// Copies FLV from `input` to `output`, adding the next caption pair to each
+/// H.264 frame.
+async fn pump<R, W>(
+    mut input: R,
+    output: W,
+    mut events: broadcast::Receiver<PipelineEvent>,
+    romanize: bool,
+) -> Result<(), IngestError>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut output = Some(output);
+    let mut encoder = Cea608Encoder::new();
+    let mut length_size = None;
+
+    // File header, then the zero size of the tag before the first.
+    let mut header = [0u8; 9];
+    input.read_exact(&mut header).await?;
+    let header_size = u32::from_be_bytes([header[5], header[6], header[7], header[8]]) as usize;
+    let mut rest = vec![0u8; header_size.saturating_sub(header.len()) + 4];
+    input.read_exact(&mut rest).await?;
+    forward(&mut output, &[&header, &rest]).await;
+
+    let mut tag = [0u8; flv::TAG_HEADER_BYTES];
+    let mut previous_size = [0u8; 4];
+    loop {
+        match input.read_exact(&mut tag).await {
+            Ok(_) => {}
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e.into()),
+        }
+        let mut data = vec![0u8; flv::read_u24(&tag, 1)];
+        input.read_exact(&mut data).await?;
+        input.read_exact(&mut previous_size).await?;
+
+        loop {
+            match events.try_recv() {
+                Ok(PipelineEvent::Translation { text, .. }) => {
+                    let roman = if romanize {
+                        crate::text::romanize(&text)
+                    } else {
+                        None
+                    };
+                    encoder.push_caption(roman.as_deref().unwrap_or(&text));
+                }
+                Ok(PipelineEvent::NonSpeech { sound }) => encoder.push_caption(&sound.label()),
+                Ok(_) | Err(broadcast::error::TryRecvError::Lagged(_)) => {}
+                Err(_) => break,
+            }
+        }
+        if tag[0] & 0x1f == flv::TAG_VIDEO {
+            match flv::classify(&data) {
+                VideoTag::AvcConfig { length_size: n } => length_size = Some(n),
+                VideoTag::AvcFrame => {
+                    if let Some(n) = length_size {
+                        if let Some(pair) = encoder.next_pair() {
+                            flv::insert_nal(&mut data, n, &flv::caption_sei(pair));
+                            flv::write_u24(&mut tag, 1, data.len());
+                        }
+                    }
+                }
+                VideoTag::Other => {}
+            }
+        }
+        let size = ((tag.len() + data.len()) as u32).to_be_bytes();
+        forward(&mut output, &[&tag, &data, &size]).await;
+    }
+    if let Some(mut output) = output {
+        let _ = output.shutdown().await;
+    }
+    Ok(())
+}
+
+/// Writes `parts` to `output`, dropping it if the publishing ffmpeg is gone.
+async fn forward<W: AsyncWrite + Unpin>(output: &mut Option<W>, parts: &[&[u8]]) {
+    let Some(out) = output else {
+        return;
+    };
+    for part in parts {
+        if let Err(e) = out.write_all(part).await {
+            tracing::warn!(error = %e, "caption relay stopped; translation continues");
+            *output = None;
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tag(kind: u8, data: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![kind, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        flv::write_u24(&mut bytes, 1, data.len());
+        bytes.extend_from_slice(data);
+        bytes.extend(((11 + data.len()) as u32).to_be_bytes());
+        bytes
+    }
+
+    #[tokio::test]
+    async fn frames_gain_a_caption_sei_and_sizes_follow() {
+        let mut input = b"FLV\x01\x05\x00\x00\x00\x09\x00\x00\x00\x00".to_vec();
+        input.extend(tag(9, &[0x17, 0, 0, 0, 0, 1, 0x64, 0, 0x1f, 0xff, 0xe1]));
+        input.extend(tag(8, &[0xaf, 1, 0x21]));
+        let frame = [0x17, 1, 0, 0, 0, 0, 0, 0, 2, 0x65, 0x88];
+        input.extend(tag(9, &frame));
+
+        let (events_tx, events) = broadcast::channel(4);
+        events_tx
+            .send(PipelineEvent::Translation {
+                source_text: "hallo".to_owned(),
+                text: "hi".to_owned(),
+                target_lang: "EN".to_owned(),
+            })
+            .unwrap();
+        let mut output = Vec::new();
+        pump(&input[..], &mut output, events, false).await.unwrap();
+
+        // Header and the first two tags pass through untouched.
+        let untouched = input.len() - frame.len() - 15;
+        assert_eq!(output[..untouched], input[..untouched]);
+        // The first pair is the roll-up command.
+        let sei = flv::caption_sei([0x94, 0x25]);
+        let grown = frame.len() + 4 + sei.len();
+        let last = &output[untouched..];
+        assert_eq!(flv::read_u24(last, 1), grown);
+        assert_eq!(last[11 + 9..11 + 9 + sei.len()], sei[..]);
+        assert_eq!(last[11 + grown..], ((11 + grown) as u32).to_be_bytes());
+    }
+}
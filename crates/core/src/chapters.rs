@@ -0,0 +1,257 @@
+//! VOD chapters
+//!
+//! A [`Chapterizer`] splits a finished transcript into chapters where the
+//! topic changes and titles each one, in the viewer's language when given.
+//! [`ChatSummarizer`] does it with the same chat API as the stream summaries,
+//! reading the transcript condensed to about a line a minute.
+//! [`youtube_timestamps`] writes chapters as the timestamp list YouTube turns
+//! into chapter markers when pasted into a video description.
+
+use crate::config::TargetLang;
+use crate::summary::{ChatSummarizer, SummaryError};
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use std::time::Duration;
+
+/// Shortest chapter YouTube accepts.
+const MIN_CHAPTER: Duration = Duration::from_secs(10);
+/// Transcript lines are merged into blocks of about this length for the model.
+const BLOCK: Duration = Duration::from_secs(60);
+/// Characters of each block the model sees, so a long VOD fits its context.
+const BLOCK_CHARS: usize = 400;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Chapter {
+    /// Offset into the recording.
+    pub start: Duration,
+    pub title: String,
+}
+
+pub trait Chapterizer: Send + Sync {
+    /// Splits `transcript`, lines with their offset into the recording, into
+    /// chapters titled in `lang`, or in the transcript's language for `None`.
+    fn chapters(
+        &self,
+        transcript: Vec<(Duration, String)>,
+        lang: Option<TargetLang>,
+    ) -> BoxFuture<'_, Result<Vec<Chapter>, SummaryError>>;
+}
+
+impl Chapterizer for ChatSummarizer {
+    fn chapters(
+        &self,
+        transcript: Vec<(Duration, String)>,
+        lang: Option<TargetLang>,
+    ) -> BoxFuture<'_, Result<Vec<Chapter>, SummaryError>> {
+        async move {
+            let language = match &lang {
+                Some(lang) => format!("in the language with code {}", lang.as_str()),
+                None => "in the language of the transcript".to_owned(),
+            };
+            let prompt = format!(
+                "You split a stream recording into chapters. The user message is its transcript, \
+                 one line per minute starting with the H:MM:SS it was said at. Start a new \
+                 chapter where the topic or activity changes, usually every 5 to 20 minutes, \
+                 the first at 0:00:00. Reply with one chapter per line as H:MM:SS followed by a \
+                 title of at most six words {language}, without preamble."
+            );
+            let reply = self.complete(prompt, condense(&transcript)).await?;
+            let chapters = parse_chapters(&reply);
+            if chapters.is_empty() {
+                return Err(SummaryError::InvalidResponse(format!(
+                    "no chapters in reply: {reply}"
+                )));
+            }
+            Ok(chapters)
+        }
+        .boxed()
+    }
+}
+
+/// `chapters` as YouTube's description timestamps, `M:SS Title` per line
+/// (`H:MM:SS` past an hour). The first chapter is moved to 0:00 and one that
+/// starts within YouTube's minimum chapter length of the last is dropped.
+pub fn youtube_timestamps(chapters: &[Chapter]) -> String {
+    let mut sorted: Vec<&Chapter> = chapters.iter().collect();
+    sorted.sort_by_key(|c| c.start);
+    let hours = sorted
+        .last()
+        .is_some_and(|c| c.start >= Duration::from_secs(3600));
+    let mut out = String::new();
+    let mut last: Option<Duration> = None;
+    for chapter in sorted {
+        let start = match last {
+            None => Duration::ZERO,
+            Some(last) if chapter.start < last + MIN_CHAPTER => continue,
+            Some(_) => chapter.start,
+        };
+        last = Some(start);
+        out.push_str(&format_timestamp(start, hours));
+        out.push(' ');
+        out.push_str(&chapter.title);
+        out.push('\n');
+    }
+    out
+}
+
+/// The transcript as `[H:MM:SS] text` blocks of about [`BLOCK`].
+fn condense(transcript: &[(Duration, String)]) -> String {
+    let mut out = String::new();
+    let mut block: Option<(Duration, String)> = None;
+    for (at, text) in transcript {
+        if let Some((start, _)) = &block {
+            if *at >= *start + BLOCK {
+                push_block(&mut out, block.take());
+            }
+        }
+        let (_, lines) = block.get_or_insert_with(|| (*at, String::new()));
+        if lines.chars().count() < BLOCK_CHARS {
+            if !lines.is_empty() {
+                lines.push(' ');
+            }
+            lines.push_str(text.trim());
+        }
+    }
+    push_block(&mut out, block);
+    out
+}
+
+fn push_block(out: &mut String, block: Option<(Duration, String)>) {
+    if let Some((start, text)) = block {
+        let text: String = text.chars().take(BLOCK_CHARS).collect();
+        out.push_str(&format!("[{}] {text}\n", format_timestamp(start, true)));
+    }
+}
+
+/// Chapters from lines such as `0:12:34 Boss fight` or `- [12:34] - Boss
+/// fight`; lines without a leading timestamp are ignored.
+fn parse_chapters(reply: &str) -> Vec<Chapter> {
+    reply
+        .lines()
+        .filter_map(|line| {
+            let line = line.trim_start_matches(|c: char| !c.is_ascii_digit());
+            let end = line
+                .find(|c: char| !c.is_ascii_digit() && c != ':')
+                .unwrap_or(line.len());
+            let start = parse_timestamp(line[..end].trim_end_matches(':'))?;
+            let title = line[end..]
+                .trim_start_matches(|c: char| c.is_whitespace() || "-–—:|])".contains(c))
+                .trim()
+                .trim_matches('"');
+            (!title.is_empty()).then(|| Chapter {
+                start,
+                title: title.to_owned(),
+            })
+        })
+        .collect()
+}
+
+/// `M:SS` or `H:MM:SS`.
+fn parse_timestamp(s: &str) -> Option<Duration> {
+    let parts: Vec<u64> = s
+        .split(':')
+        .map(|p| p.parse().ok())
+        .collect::<Option<_>>()?;
+    let secs = match parts[..] {
+        [m, s] if s < 60 => m * 60 + s,
+        [h, m, s] if m < 60 && s < 60 => h * 3600 + m * 60 + s,
+        _ => return None,
+    };
+    Some(Duration::from_secs(secs))
+}
+
+fn format_timestamp(at: Duration, hours: bool) -> String {
+    let secs = at.as_secs();
+    if hours {
+        format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+    } else {
+        format!("{}:{:02}", secs / 60, secs % 60)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::{HttpMethod, MockTransport};
+
+    fn chapter(secs: u64, title: &str) -> Chapter {
+        Chapter {
+            start: Duration::from_secs(secs),
+            title: title.to_owned(),
+        }
+    }
+
+    #[test]
+    fn replies_are_parsed_leniently() {
+        let reply = "Here you go:\n0:00:00 Intro\n- [0:12:34] - \"Boss fight\"\n1:02:03: Q&A\n\
+                     12:99 Not a time\n0:30:00";
+        assert_eq!(
+            parse_chapters(reply),
+            [
+                chapter(0, "Intro"),
+                chapter(754, "Boss fight"),
+                chapter(3723, "Q&A")
+            ]
+        );
+    }
+
+    #[test]
+    fn youtube_timestamps_start_at_zero_and_skip_short_chapters() {
+        let chapters = [
+            chapter(600, "Boss fight"),
+            chapter(5, "Intro"),
+            chapter(605, "Too soon"),
+        ];
+        assert_eq!(
+            youtube_timestamps(&chapters),
+            "0:00 Intro\n10:00 Boss fight\n"
+        );
+        assert_eq!(
+            youtube_timestamps(&[chapter(0, "Intro"), chapter(3723, "Q&A")]),
+            "0:00:00 Intro\n1:02:03 Q&A\n"
+        );
+    }
+
+    #[test]
+    fn transcripts_are_condensed_into_minute_blocks() {
+        let transcript = [
+            (Duration::from_secs(1), "hello chat".to_owned()),
+            (Duration::from_secs(30), "welcome back".to_owned()),
+            (Duration::from_secs(95), "let's go".to_owned()),
+        ];
+        assert_eq!(
+            condense(&transcript),
+            "[0:00:01] hello chat welcome back\n[0:01:35] let's go\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn chat_summarizer_titles_chapters_in_the_target_language() {
+        let mock = MockTransport::new().on(
+            HttpMethod::Post,
+            "/chat/completions",
+            200,
+            r#"{"choices":[{"message":{"role":"assistant","content":"0:00:00 Abertura\n0:15:00 Chefe final"}}]}"#,
+        );
+        let llm = ChatSummarizer::new("http://llm/v1", "m").with_transport(mock.clone().shared());
+
+        let chapters = llm
+            .chapters(
+                vec![(Duration::ZERO, "hello chat".to_owned())],
+                Some(TargetLang::new("PT-BR").unwrap()),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            chapters,
+            [chapter(0, "Abertura"), chapter(900, "Chefe final")]
+        );
+        let body: serde_json::Value =
+            serde_json::from_slice(mock.requests()[0].body.as_ref().unwrap()).unwrap();
+        assert!(body["messages"][0]["content"]
+            .as_str()
+            .unwrap()
+            .contains("code PT-BR"));
+        assert_eq!(body["messages"][1]["content"], "[0:00:00] hello chat\n");
+    }
+}
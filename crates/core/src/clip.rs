@@ -0,0 +1,291 @@
+//! On-demand clip capture
+//!
+//! [`ClipBuffer`] keeps the last few seconds of decoded stream audio together
+//! with the transcripts and translations made from it. Saving a clip writes
+//! that window to disk as a WAV file plus a text file, for the "what did they
+//! just say?" moments. Until [`ClipBuffer::enable`] is called, it records
+//! nothing.
+
+use crate::decode::{PcmChunk, PcmFormat};
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[derive(thiserror::Error, Debug)]
+pub enum ClipError {
+    #[error("clip capture is disabled")]
+    Disabled,
+    #[error("no audio has been buffered yet")]
+    Empty,
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// Files written by [`ClipBuffer::save`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SavedClip {
+    pub audio: PathBuf,
+    pub transcript: PathBuf,
+}
+
+#[derive(Clone, Debug)]
+struct ClipText {
+    at: SystemTime,
+    source: String,
+    translation: Option<String>,
+}
+
+#[derive(Default)]
+struct ClipState {
+    dir: Option<PathBuf>,
+    window: Duration,
+    audio: VecDeque<PcmChunk>,
+    texts: VecDeque<ClipText>,
+}
+
+impl ClipState {
+    fn buffered(&self) -> Duration {
+        self.audio.iter().map(|c| c.duration_estimate).sum()
+    }
+
+    fn prune(&mut self, now: SystemTime) {
+        while self.audio.len() > 1
+            && self.buffered() - self.audio[0].duration_estimate >= self.window
+        {
+            self.audio.pop_front();
+        }
+        let oldest = now.checked_sub(self.window).unwrap_or(UNIX_EPOCH);
+        while self.texts.front().is_some_and(|t| t.at < oldest) {
+            self.texts.pop_front();
+        }
+    }
+}
+
+/// Shared ring buffer of recent audio and text. Clones share the same buffer.
+#[derive(Clone, Default)]
+pub struct ClipBuffer {
+    state: Arc<Mutex<ClipState>>,
+}
+
+impl ClipBuffer {
+    /// Starts keeping the last `window` of audio and text, saved into `dir`.
+    pub fn enable(&self, dir: PathBuf, window: Duration) {
+        let mut state = self.lock();
+        state.dir = Some(dir);
+        state.window = window;
+        state.prune(SystemTime::now());
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.lock().window.is_zero()
+    }
+
+    /// Appends decoded audio. Chunks in a different format from the ones
+    /// already held replace them, so a saved clip has a single format.
+    pub fn push_audio(&self, chunk: &PcmChunk) {
+        let mut state = self.lock();
+        if state.window.is_zero() {
+            return;
+        }
+        if state.audio.back().is_some_and(|c| c.format != chunk.format) {
+            state.audio.clear();
+        }
+        state.audio.push_back(chunk.clone());
+        state.prune(SystemTime::now());
+    }
+
+    pub fn push_transcript(&self, text: &str) {
+        let mut state = self.lock();
+        if state.window.is_zero() {
+            return;
+        }
+        state.texts.push_back(ClipText {
+            at: SystemTime::now(),
+            source: text.to_owned(),
+            translation: None,
+        });
+        state.prune(SystemTime::now());
+    }
+
+    /// Attaches `translation` to the most recent matching transcript, or
+    /// records it on its own if that has already left the window.
+    pub fn push_translation(&self, source: &str, translation: &str) {
+        let mut state = self.lock();
+        if state.window.is_zero() {
+            return;
+        }
+        let pending = state
+            .texts
+            .iter_mut()
+            .rev()
+            .find(|t| t.translation.is_none() && t.source == source);
+        match pending {
+            Some(text) => text.translation = Some(translation.to_owned()),
+            None => state.texts.push_back(ClipText {
+                at: SystemTime::now(),
+                source: source.to_owned(),
+                translation: Some(translation.to_owned()),
+            }),
+        }
+    }
+
+    /// Writes the buffered window into the configured directory as
+    /// `clip-<unix time>.wav` and `clip-<unix time>.txt`.
+    pub async fn save(&self) -> Result<SavedClip, ClipError> {
+        let dir = self.lock().dir.clone().ok_or(ClipError::Disabled)?;
+        self.save_to(&dir).await
+    }
+
+    /// Like [`Self::save`], into `dir`.
+    pub async fn save_to(&self, dir: &Path) -> Result<SavedClip, ClipError> {
+        let (wav, text) = {
+            let state = self.lock();
+            if state.window.is_zero() {
+                return Err(ClipError::Disabled);
+            }
+            let Some(format) = state.audio.front().map(|c| c.format) else {
+                return Err(ClipError::Empty);
+            };
+            let samples: Vec<f32> = state
+                .audio
+                .iter()
+                .flat_map(|c| c.samples.iter().copied())
+                .collect();
+            (encode_wav(format, &samples), transcript_text(&state.texts))
+        };
+
+        tokio::fs::create_dir_all(dir).await?;
+        let stamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let saved = SavedClip {
+            audio: dir.join(format!("clip-{stamp}.wav")),
+            transcript: dir.join(format!("clip-{stamp}.txt")),
+        };
+        tokio::fs::write(&saved.audio, wav).await?;
+        tokio::fs::write(&saved.transcript, text).await?;
+        Ok(saved)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, ClipState> {
+        match self.state.lock() {
+            Ok(g) => g,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+}
+
+/// One block per utterance: the original line, then its translation.
+fn transcript_text(texts: &VecDeque<ClipText>) -> String {
+    let Some(first) = texts.front() else {
+        return String::new();
+    };
+    let mut out = String::new();
+    for text in texts {
+        let offset = text.at.duration_since(first.at).unwrap_or_default();
+        let _ = writeln!(out, "[+{:.1}s] {}", offset.as_secs_f64(), text.source);
+        if let Some(translation) = &text.translation {
+            let _ = writeln!(out, "        {translation}");
+        }
+    }
+    out
+}
+
+/// 16-bit PCM WAV of interleaved float `samples`.
+pub(crate) fn encode_wav(format: PcmFormat, samples: &[f32]) -> Vec<u8> {
+    let channels = format.channels.max(1);
+    let data_len = (samples.len() * 2) as u32;
+    let byte_rate = format.sample_rate * u32::from(channels) * 2;
+
+    let mut wav = Vec::with_capacity(44 + data_len as usize);
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&channels.to_le_bytes());
+    wav.extend_from_slice(&format.sample_rate.to_le_bytes());
+    wav.extend_from_slice(&byte_rate.to_le_bytes());
+    wav.extend_from_slice(&(channels * 2).to_le_bytes());
+    wav.extend_from_slice(&16u16.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_len.to_le_bytes());
+    for sample in samples {
+        let value = (sample.clamp(-1.0, 1.0) * f32::from(i16::MAX)) as i16;
+        wav.extend_from_slice(&value.to_le_bytes());
+    }
+    wav
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(sequence: u64, secs: u64) -> PcmChunk {
+        let format = PcmFormat::whisper_f32_mono_16khz();
+        PcmChunk {
+            sequence,
+            started_at: SystemTime::now(),
+            fetched_at: SystemTime::now(),
+            format,
+            samples: vec![0.5; (format.sample_rate as u64 * secs) as usize],
+            duration_estimate: Duration::from_secs(secs),
+            discontinuity: false,
+        }
+    }
+
+    fn enabled(window: Duration) -> ClipBuffer {
+        let clips = ClipBuffer::default();
+        clips.enable(std::env::temp_dir(), window);
+        clips
+    }
+
+    #[test]
+    fn keeps_only_the_window() {
+        let clips = enabled(Duration::from_secs(5));
+        for sequence in 0..5 {
+            clips.push_audio(&chunk(sequence, 2));
+        }
+        let state = clips.lock();
+        let kept: Vec<u64> = state.audio.iter().map(|c| c.sequence).collect();
+        assert_eq!(kept, [2, 3, 4]);
+    }
+
+    #[test]
+    fn zero_window_records_nothing() {
+        let clips = ClipBuffer::default();
+        clips.push_audio(&chunk(0, 2));
+        clips.push_transcript("hello");
+        assert!(!clips.is_enabled());
+        assert!(clips.lock().audio.is_empty());
+        assert!(clips.lock().texts.is_empty());
+    }
+
+    #[tokio::test]
+    async fn saves_wav_and_transcript() {
+        assert!(matches!(
+            ClipBuffer::default().save().await,
+            Err(ClipError::Disabled)
+        ));
+        let clips = enabled(Duration::from_secs(30));
+        assert!(matches!(clips.save().await, Err(ClipError::Empty)));
+        clips.push_audio(&chunk(0, 1));
+        clips.push_transcript("what did he say");
+        clips.push_translation("what did he say", "o que ele disse");
+
+        let dir = std::env::temp_dir().join(format!("clip-test-{}", rand::random::<u32>()));
+        let saved = clips.save_to(&dir).await.unwrap();
+        let wav = std::fs::read(&saved.audio).unwrap();
+        let text = std::fs::read_to_string(&saved.transcript).unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert_eq!(&wav[..4], b"RIFF");
+        assert_eq!(wav.len(), 44 + 16_000 * 2);
+        assert_eq!(u32::from_le_bytes(wav[24..28].try_into().unwrap()), 16_000);
+        assert!(text.contains("what did he say\n"));
+        assert!(text.contains("o que ele disse"));
+    }
+}
@@ -0,0 +1,168 @@
+//! Settings that can change while a session runs
+//!
+//! A [`LiveConfig`] is read from the `--live-config` file at startup and again
+//! whenever the file changes. Every setting here is consulted per utterance,
+//! so a change, even a switch of TTS backend, takes effect at the next
+//! utterance without reconnecting to the stream or cutting one off.
+
+use super::{ControlHandle, MAX_VOLUME};
+use crate::config::{ConfigError, TargetLang, TextConfig, TranslationStyle};
+use crate::text::{TextChains, TextError};
+use crate::tts::VoiceId;
+use serde::{Deserialize, Serialize};
+
+/// Contents of a `--live-config` file. Fields left out keep their current
+/// value, except `voice`, `tts` and `text`, which go back to the session's
+/// startup settings.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct LiveConfig {
+    pub target_lang: Option<String>,
+    /// Playback gain, `1.0` leaving TTS audio untouched.
+    pub volume: Option<f32>,
+    pub muted: Option<bool>,
+    /// ElevenLabs voice ID to speak with.
+    pub voice: Option<String>,
+    pub tts: Option<TtsBackend>,
+    /// Replaces the rules from `--text-rules`, e.g. with a word filter.
+    pub text: Option<TextConfig>,
+    /// How an LLM translator phrases translations.
+    pub style: Option<TranslationStyle>,
+}
+
+/// Which voice speaks when both a cloud and a local one are configured
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TtsBackend {
+    /// ElevenLabs, falling back to Piper when its quota runs out.
+    Cloud,
+    /// Piper only.
+    Local,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum LiveConfigError {
+    #[error(transparent)]
+    Config(#[from] ConfigError),
+    #[error(transparent)]
+    Text(#[from] TextError),
+    #[error("volume must be between 0 and {MAX_VOLUME}, got {0}")]
+    InvalidVolume(f32),
+}
+
+impl LiveConfig {
+    /// Applies the fields that differ from `previous` to `control` and
+    /// returns their names. Nothing is applied if any field is invalid.
+    pub fn apply(
+        &self,
+        previous: &LiveConfig,
+        control: &ControlHandle,
+    ) -> Result<Vec<&'static str>, LiveConfigError> {
+        let target_lang = self
+            .target_lang
+            .as_deref()
+            .map(TargetLang::new)
+            .transpose()?;
+        if let Some(volume) = self.volume {
+            if !(0.0..=MAX_VOLUME).contains(&volume) {
+                return Err(LiveConfigError::InvalidVolume(volume));
+            }
+        }
+        let text_changed = self.text != previous.text;
+        let text = match &self.text {
+            Some(text) if text_changed => Some(TextChains::from_config(text)?),
+            _ => None,
+        };
+
+        let mut changed = Vec::new();
+        if self.target_lang != previous.target_lang {
+            if let Some(lang) = target_lang {
+                control.set_target_lang(lang);
+                changed.push("target_lang");
+            }
+        }
+        if self.volume != previous.volume {
+            if let Some(volume) = self.volume {
+                control.set_volume(volume);
+                changed.push("volume");
+            }
+        }
+        if self.muted != previous.muted {
+            if let Some(muted) = self.muted {
+                control.set_muted(muted);
+                changed.push("muted");
+            }
+        }
+        if self.voice != previous.voice {
+            control.set_voice(self.voice.clone().map(VoiceId));
+            changed.push("voice");
+        }
+        if self.tts != previous.tts {
+            control.set_prefer_local_tts(self.tts == Some(TtsBackend::Local));
+            changed.push("tts");
+        }
+        if text_changed {
+            control.set_text_chains(text);
+            changed.push("text");
+        }
+        if self.style != previous.style {
+            if let Some(style) = &self.style {
+                control.set_translation_style(style.clone());
+                changed.push("style");
+            }
+        }
+        Ok(changed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text::TextRule;
+
+    #[test]
+    fn applies_only_changed_fields() {
+        let control = ControlHandle::new(TargetLang::default());
+        let first: LiveConfig =
+            serde_json::from_str(r#"{"target_lang": "de", "volume": 0.5}"#).unwrap();
+        assert_eq!(
+            first.apply(&LiveConfig::default(), &control).unwrap(),
+            ["target_lang", "volume"]
+        );
+
+        // A hotkey change survives an edit that leaves the volume alone.
+        control.set_volume(1.5);
+        let second = LiveConfig {
+            tts: Some(TtsBackend::Local),
+            text: Some(TextConfig {
+                post_translate: vec![TextRule::StripEmoji],
+                ..Default::default()
+            }),
+            ..first.clone()
+        };
+        assert_eq!(second.apply(&first, &control).unwrap(), ["tts", "text"]);
+        assert_eq!(control.volume(), 1.5);
+        assert_eq!(control.target_lang().as_str(), "de");
+        assert!(control.prefers_local_tts());
+        assert!(control.text_chains().is_some());
+
+        assert_eq!(first.apply(&second, &control).unwrap(), ["tts", "text"]);
+        assert!(!control.prefers_local_tts());
+        assert!(control.text_chains().is_none());
+    }
+
+    #[test]
+    fn invalid_file_applies_nothing() {
+        let control = ControlHandle::new(TargetLang::default());
+        let live = LiveConfig {
+            target_lang: Some("de".to_owned()),
+            volume: Some(-1.0),
+            ..Default::default()
+        };
+        assert!(matches!(
+            live.apply(&LiveConfig::default(), &control),
+            Err(LiveConfigError::InvalidVolume(_))
+        ));
+        assert_eq!(control.target_lang(), TargetLang::default());
+    }
+}
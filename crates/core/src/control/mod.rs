@@ -0,0 +1,416 @@
+//! Runtime controls shared between the pipeline and interactive frontends
+//!
+//! A [`ControlHandle`] is handed to the pipeline and to whatever drives it at
+//! runtime (TUI hotkeys, control sockets). Stages consult it per item, so changes
+//! take effect at the next utterance without restarting the stream connection.
+//!
+//! [`LiveConfig`] applies a settings file to a handle while a session runs.
+//!
+//! [`ControlCommand`]/[`ControlReply`] define the line-delimited JSON protocol
+//! spoken over the daemon's control socket (see [`ControlServer`]).
+
+mod live;
+mod socket;
+
+use crate::clip::ClipBuffer;
+use crate::config::{TargetLang, TranslationStyle};
+use crate::text::TextChains;
+use crate::tts::VoiceId;
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use tokio::sync::watch;
+
+pub use live::{LiveConfig, LiveConfigError, TtsBackend};
+pub use socket::{default_control_endpoint, ControlServer, ControlServerError};
+
+/// Upper bound accepted for playback gain; anything louder clips badly.
+pub const MAX_VOLUME: f32 = 2.0;
+
+struct ControlInner {
+    clips: ClipBuffer,
+    muted: AtomicBool,
+    volume_bits: AtomicU32,
+    paused: watch::Sender<bool>,
+    target_lang: RwLock<TargetLang>,
+    language_epoch: AtomicU64,
+    voice: RwLock<Option<VoiceId>>,
+    language_voices: RwLock<HashMap<String, VoiceId>>,
+    text_chains: RwLock<Option<Arc<TextChains>>>,
+    local_tts: AtomicBool,
+    translation_style: RwLock<TranslationStyle>,
+}
+
+/// Shared handle to runtime controls. Clones share the same state.
+#[derive(Clone)]
+pub struct ControlHandle {
+    inner: Arc<ControlInner>,
+}
+
+impl ControlHandle {
+    pub fn new(target_lang: TargetLang) -> Self {
+        let (paused, _) = watch::channel(false);
+        Self {
+            inner: Arc::new(ControlInner {
+                clips: ClipBuffer::default(),
+                muted: AtomicBool::new(false),
+                volume_bits: AtomicU32::new(1.0f32.to_bits()),
+                paused,
+                target_lang: RwLock::new(target_lang),
+                language_epoch: AtomicU64::new(0),
+                voice: RwLock::new(None),
+                language_voices: RwLock::new(HashMap::new()),
+                text_chains: RwLock::new(None),
+                local_tts: AtomicBool::new(false),
+                translation_style: RwLock::new(TranslationStyle::default()),
+            }),
+        }
+    }
+
+    /// Recent audio and text, saved on request; disabled until enabled.
+    pub fn clips(&self) -> &ClipBuffer {
+        &self.inner.clips
+    }
+
+    /// While muted, translated text is not synthesized or played at all.
+    pub fn is_muted(&self) -> bool {
+        self.inner.muted.load(Ordering::Relaxed)
+    }
+
+    pub fn set_muted(&self, muted: bool) {
+        self.inner.muted.store(muted, Ordering::Relaxed);
+    }
+
+    /// Flips the mute state and returns the new value.
+    pub fn toggle_muted(&self) -> bool {
+        !self.inner.muted.fetch_xor(true, Ordering::Relaxed)
+    }
+
+    /// While paused, nothing is synthesized and speech reaching playback is
+    /// dropped, so transcription and subtitles keep up and playback resumes
+    /// with the next utterance.
+    pub fn is_paused(&self) -> bool {
+        *self.inner.paused.borrow()
+    }
+
+    pub fn set_paused(&self, paused: bool) {
+        self.inner.paused.send_replace(paused);
+    }
+
+    /// Flips the pause state and returns the new value.
+    pub fn toggle_paused(&self) -> bool {
+        let mut now_paused = false;
+        self.inner.paused.send_modify(|p| {
+            *p = !*p;
+            now_paused = *p;
+        });
+        now_paused
+    }
+
+    /// Resolves immediately when not paused, otherwise once playback is resumed.
+    pub async fn wait_while_paused(&self) {
+        let mut rx = self.inner.paused.subscribe();
+        // The sender lives in `self`, so the channel cannot close while we wait.
+        let _ = rx.wait_for(|paused| !*paused).await;
+    }
+
+    /// Linear playback gain, where `1.0` leaves TTS audio untouched.
+    pub fn volume(&self) -> f32 {
+        f32::from_bits(self.inner.volume_bits.load(Ordering::Relaxed))
+    }
+
+    /// Sets the playback gain, clamped to `0.0..=MAX_VOLUME`.
+    pub fn set_volume(&self, volume: f32) {
+        let v = if volume.is_nan() {
+            1.0
+        } else {
+            volume.clamp(0.0, MAX_VOLUME)
+        };
+        self.inner.volume_bits.store(v.to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn target_lang(&self) -> TargetLang {
+        read(&self.inner.target_lang)
+    }
+
+    /// Switches the target language. Translations already made in the old
+    /// one are dropped by the stages still holding them; see
+    /// [`Self::language_epoch`].
+    pub fn set_target_lang(&self, lang: TargetLang) {
+        let mut guard = match self.inner.target_lang.write() {
+            Ok(g) => g,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        if *guard != lang {
+            *guard = lang;
+            self.inner.language_epoch.fetch_add(1, Ordering::AcqRel);
+        }
+    }
+
+    /// Counts target language switches. Items tagged with an older epoch
+    /// were translated into a language that is no longer wanted.
+    pub fn language_epoch(&self) -> u64 {
+        self.inner.language_epoch.load(Ordering::Acquire)
+    }
+
+    /// Voice requested from TTS: the one set for the current target language
+    /// if any, else the one from [`Self::set_voice`]. `None` leaves the choice
+    /// to the backend.
+    pub fn voice(&self) -> Option<VoiceId> {
+        let lang = self.target_lang().as_str().to_lowercase();
+        match self.inner.language_voices.read() {
+            Ok(g) => g.get(&lang).cloned(),
+            Err(poisoned) => poisoned.into_inner().get(&lang).cloned(),
+        }
+        .or_else(|| read(&self.inner.voice))
+    }
+
+    /// Voice to use for each target language, keyed by language code in any
+    /// case.
+    pub fn with_language_voices<I>(self, voices: I) -> Self
+    where
+        I: IntoIterator<Item = (String, VoiceId)>,
+    {
+        let voices = voices
+            .into_iter()
+            .map(|(lang, voice)| (lang.to_lowercase(), voice))
+            .collect();
+        write(&self.inner.language_voices, voices);
+        self
+    }
+
+    pub fn set_voice(&self, voice: Option<VoiceId>) {
+        write(&self.inner.voice, voice);
+    }
+
+    /// Text chains replacing the session's configured ones, if any were set.
+    pub fn text_chains(&self) -> Option<Arc<TextChains>> {
+        read(&self.inner.text_chains)
+    }
+
+    pub fn set_text_chains(&self, chains: Option<TextChains>) {
+        write(&self.inner.text_chains, chains.map(Arc::new));
+    }
+
+    /// How an LLM translator phrases the next translation.
+    pub fn translation_style(&self) -> TranslationStyle {
+        read(&self.inner.translation_style)
+    }
+
+    pub fn set_translation_style(&self, style: TranslationStyle) {
+        write(&self.inner.translation_style, style);
+    }
+
+    /// Whether a TTS client with a local fallback should use it even while
+    /// the cloud voice works.
+    pub fn prefers_local_tts(&self) -> bool {
+        self.inner.local_tts.load(Ordering::Relaxed)
+    }
+
+    pub fn set_prefer_local_tts(&self, local: bool) {
+        self.inner.local_tts.store(local, Ordering::Relaxed);
+    }
+}
+
+fn read<T: Clone>(lock: &RwLock<T>) -> T {
+    match lock.read() {
+        Ok(g) => g.clone(),
+        Err(poisoned) => poisoned.into_inner().clone(),
+    }
+}
+
+fn write<T>(lock: &RwLock<T>, value: T) {
+    let mut guard = match lock.write() {
+        Ok(g) => g,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    *guard = value;
+}
+
+/// A command sent to a running daemon, one JSON object per line.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "cmd", rename_all = "snake_case")]
+pub enum ControlCommand {
+    /// Starts translating a channel or URL, replacing any running session.
+    Start {
+        #[serde(default)]
+        channel: Option<String>,
+        #[serde(default)]
+        url: Option<String>,
+    },
+    Stop,
+    SetLanguage {
+        lang: String,
+    },
+    SetVolume {
+        volume: f32,
+    },
+    /// Changes how an LLM translator phrases translations, from the next
+    /// utterance.
+    SetStyle {
+        style: TranslationStyle,
+    },
+    /// Stops synthesizing and playing speech; transcripts and translations
+    /// continue.
+    Mute,
+    Unmute,
+    /// Stops playing speech for a moment, to hear the original stream.
+    Pause,
+    Resume,
+    /// Saves the last few seconds of audio, transcripts and translations.
+    Clip,
+    Status,
+}
+
+/// Reply to a [`ControlCommand`], serialized as a single JSON line.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct ControlReply {
+    pub ok: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<serde_json::Value>,
+}
+
+impl ControlReply {
+    pub fn ok() -> Self {
+        Self {
+            ok: true,
+            error: None,
+            data: None,
+        }
+    }
+
+    pub fn with_data(data: serde_json::Value) -> Self {
+        Self {
+            data: Some(data),
+            ..Self::ok()
+        }
+    }
+
+    pub fn error<S: Into<String>>(message: S) -> Self {
+        Self {
+            ok: false,
+            error: Some(message.into()),
+            data: None,
+        }
+    }
+}
+
+/// Executes control commands on behalf of a [`ControlServer`].
+pub trait ControlHandler: Send + Sync {
+    fn handle(&self, command: ControlCommand) -> BoxFuture<'_, ControlReply>;
+}
+
+impl<H: ControlHandler + ?Sized> ControlHandler for Arc<H> {
+    fn handle(&self, command: ControlCommand) -> BoxFuture<'_, ControlReply> {
+        (**self).handle(command)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn toggles_return_new_state() {
+        let control = ControlHandle::new(TargetLang::default());
+        assert!(control.toggle_muted());
+        assert!(control.is_muted());
+        assert!(!control.toggle_muted());
+
+        assert!(control.toggle_paused());
+        assert!(control.is_paused());
+        assert!(!control.toggle_paused());
+    }
+
+    #[test]
+    fn language_switch_bumps_the_epoch_and_picks_its_voice() {
+        let control = ControlHandle::new(TargetLang::new("en").unwrap())
+            .with_language_voices([("DE".to_owned(), VoiceId("german".to_owned()))]);
+        control.set_voice(Some(VoiceId("default".to_owned())));
+        assert_eq!(control.voice(), Some(VoiceId("default".to_owned())));
+
+        control.set_target_lang(TargetLang::new("en").unwrap());
+        assert_eq!(control.language_epoch(), 0);
+        control.set_target_lang(TargetLang::new("de").unwrap());
+        assert_eq!(control.language_epoch(), 1);
+        assert_eq!(control.voice(), Some(VoiceId("german".to_owned())));
+    }
+
+    #[test]
+    fn target_lang_is_shared_between_clones() {
+        let control = ControlHandle::new(TargetLang::default());
+        let clone = control.clone();
+        clone.set_target_lang(TargetLang::new("de").unwrap());
+        assert_eq!(control.target_lang().as_str(), "de");
+    }
+
+    #[test]
+    fn volume_is_clamped() {
+        let control = ControlHandle::new(TargetLang::default());
+        assert_eq!(control.volume(), 1.0);
+        control.set_volume(0.5);
+        assert_eq!(control.volume(), 0.5);
+        control.set_volume(-1.0);
+        assert_eq!(control.volume(), 0.0);
+        control.set_volume(10.0);
+        assert_eq!(control.volume(), MAX_VOLUME);
+    }
+
+    #[test]
+    fn commands_parse_from_json_lines() {
+        let cmd: ControlCommand =
+            serde_json::from_str(r#"{"cmd":"start","channel":"somechannel"}"#).unwrap();
+        assert_eq!(
+            cmd,
+            ControlCommand::Start {
+                channel: Some("somechannel".to_owned()),
+                url: None
+            }
+        );
+        let cmd: ControlCommand = serde_json::from_str(r#"{"cmd":"stop"}"#).unwrap();
+        assert_eq!(cmd, ControlCommand::Stop);
+        let cmd: ControlCommand =
+            serde_json::from_str(r#"{"cmd":"set_volume","volume":0.8}"#).unwrap();
+        assert_eq!(cmd, ControlCommand::SetVolume { volume: 0.8 });
+        let cmd: ControlCommand = serde_json::from_str(r#"{"cmd":"resume"}"#).unwrap();
+        assert_eq!(cmd, ControlCommand::Resume);
+        let cmd: ControlCommand = serde_json::from_str(
+            r#"{"cmd":"set_style","style":{"formality":"less","persona":"a pirate"}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            cmd,
+            ControlCommand::SetStyle {
+                style: TranslationStyle {
+                    formality: crate::config::Formality::Less,
+                    persona: Some("a pirate".to_owned()),
+                    ..Default::default()
+                }
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn wait_while_paused_resolves_on_resume() {
+        let control = ControlHandle::new(TargetLang::default());
+        control.set_paused(true);
+
+        let waiter = {
+            let control = control.clone();
+            tokio::spawn(async move { control.wait_while_paused().await })
+        };
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!waiter.is_finished());
+
+        control.set_paused(false);
+        tokio::time::timeout(Duration::from_secs(1), waiter)
+            .await
+            .expect("resumed")
+            .unwrap();
+    }
+}
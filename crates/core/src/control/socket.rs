@@ -0,0 +1,211 @@
+use crate::control::{ControlCommand, ControlHandler, ControlReply};
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+
+/// Longest accepted command line; protects the daemon from unbounded reads.
+const MAX_LINE_BYTES: usize = 64 * 1024;
+
+#[derive(thiserror::Error, Debug)]
+pub enum ControlServerError {
+    #[error("failed to listen on control endpoint {endpoint}: {source}")]
+    Listen {
+        endpoint: String,
+        source: std::io::Error,
+    },
+
+    #[error("another daemon is already listening on {0}")]
+    AlreadyRunning(String),
+
+    #[error("control endpoint io error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// The platform's default control endpoint: a Unix socket in the temp dir, or a
+/// named pipe on Windows.
+pub fn default_control_endpoint() -> String {
+    #[cfg(windows)]
+    {
+        r"\\.\pipe\twitch-translator".to_owned()
+    }
+    #[cfg(not(windows))]
+    {
+        std::env::temp_dir()
+            .join("twitch-translator.sock")
+            .to_string_lossy()
+            .into_owned()
+    }
+}
+
+/// Serves the line-delimited JSON control protocol on a local socket.
+///
+/// Each connection may send any number of commands; every command line gets
+/// exactly one reply line. Connections are handled concurrently, so commands
+/// from different clients can interleave.
+pub struct ControlServer<H> {
+    handler: Arc<H>,
+}
+
+impl<H> ControlServer<H>
+where
+    H: ControlHandler + 'static,
+{
+    pub fn new(handler: H) -> Self {
+        Self {
+            handler: Arc::new(handler),
+        }
+    }
+
+    #[cfg(unix)]
+    pub async fn serve(self, endpoint: &str) -> Result<(), ControlServerError> {
+        use tokio::net::{UnixListener, UnixStream};
+
+        let path = std::path::Path::new(endpoint);
+        if path.exists() {
+            if UnixStream::connect(path).await.is_ok() {
+                return Err(ControlServerError::AlreadyRunning(endpoint.to_owned()));
+            }
+            // Left behind by a daemon that did not shut down cleanly.
+            std::fs::remove_file(path)?;
+        }
+
+        let listener = UnixListener::bind(path).map_err(|source| ControlServerError::Listen {
+            endpoint: endpoint.to_owned(),
+            source,
+        })?;
+        tracing::info!(endpoint, "control socket listening");
+
+        loop {
+            let (stream, _) = listener.accept().await?;
+            let handler = Arc::clone(&self.handler);
+            tokio::spawn(async move {
+                if let Err(e) = handle_connection(stream, handler).await {
+                    tracing::debug!(error = %e, "control connection failed");
+                }
+            });
+        }
+    }
+
+    #[cfg(windows)]
+    pub async fn serve(self, endpoint: &str) -> Result<(), ControlServerError> {
+        use tokio::net::windows::named_pipe::ServerOptions;
+
+        let listen_err = |source| ControlServerError::Listen {
+            endpoint: endpoint.to_owned(),
+            source,
+        };
+        let mut server = ServerOptions::new()
+            .first_pipe_instance(true)
+            .create(endpoint)
+            .map_err(|e| match e.kind() {
+                std::io::ErrorKind::PermissionDenied => {
+                    ControlServerError::AlreadyRunning(endpoint.to_owned())
+                }
+                _ => listen_err(e),
+            })?;
+        tracing::info!(endpoint, "control pipe listening");
+
+        loop {
+            server.connect().await?;
+            let connected = server;
+            // Create the next instance before serving so clients never see the pipe missing.
+            server = ServerOptions::new().create(endpoint).map_err(listen_err)?;
+            let handler = Arc::clone(&self.handler);
+            tokio::spawn(async move {
+                if let Err(e) = handle_connection(connected, handler).await {
+                    tracing::debug!(error = %e, "control connection failed");
+                }
+            });
+        }
+    }
+}
+
+async fn handle_connection<S, H>(stream: S, handler: Arc<H>) -> Result<(), std::io::Error>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    H: ControlHandler,
+{
+    let (read, mut write) = tokio::io::split(stream);
+    let mut lines = BufReader::new(read);
+    let mut line = String::new();
+
+    loop {
+        line.clear();
+        let n = (&mut lines)
+            .take(MAX_LINE_BYTES as u64)
+            .read_line(&mut line)
+            .await?;
+        if n == 0 {
+            return Ok(());
+        }
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            continue;
+        }
+
+        let reply = match serde_json::from_str::<ControlCommand>(trimmed) {
+            Ok(command) => {
+                tracing::debug!(?command, "control command received");
+                handler.handle(command).await
+            }
+            Err(e) => ControlReply::error(format!("invalid command: {e}")),
+        };
+
+        let mut out = serde_json::to_vec(&reply).unwrap_or_else(|e| {
+            format!(r#"{{"ok":false,"error":"failed to encode reply: {e}"}}"#).into_bytes()
+        });
+        out.push(b'\n');
+        write.write_all(&out).await?;
+        write.flush().await?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::future::BoxFuture;
+    use futures::FutureExt;
+
+    struct EchoHandler;
+
+    impl ControlHandler for EchoHandler {
+        fn handle(&self, command: ControlCommand) -> BoxFuture<'_, ControlReply> {
+            async move {
+                match command {
+                    ControlCommand::Stop => ControlReply::error("nothing running"),
+                    other => ControlReply::with_data(serde_json::to_value(other).unwrap()),
+                }
+            }
+            .boxed()
+        }
+    }
+
+    #[tokio::test]
+    async fn replies_once_per_command_line() {
+        let (client, server) = tokio::io::duplex(4096);
+        let task = tokio::spawn(handle_connection(server, Arc::new(EchoHandler)));
+
+        let (mut rd, mut wr) = tokio::io::split(client);
+        wr.write_all(b"{\"cmd\":\"status\"}\n\n{\"cmd\":\"stop\"}\nnot json\n")
+            .await
+            .unwrap();
+        wr.shutdown().await.unwrap();
+
+        let mut out = String::new();
+        rd.read_to_string(&mut out).await.unwrap();
+        task.await.unwrap().unwrap();
+
+        let replies: Vec<ControlReply> = out
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(replies.len(), 3);
+        assert!(replies[0].ok);
+        assert_eq!(replies[0].data.as_ref().unwrap()["cmd"], "status");
+        assert_eq!(replies[1].error.as_deref(), Some("nothing running"));
+        assert!(replies[2]
+            .error
+            .as_ref()
+            .unwrap()
+            .starts_with("invalid command"));
+    }
+}
@@ -0,0 +1,271 @@
+//! Session spend tracking
+//!
+//! A [`CostMeter`] counts what the cloud backends bill by: characters sent to
+//! DeepL and ElevenLabs, and prompt and completion tokens of the LLM. It
+//! prices them with a [`Pricing`] table for a running estimate shown on the
+//! status endpoint and in the TUI. Clients given a meter record each request
+//! that succeeded. Once a budget is set and spent, they refuse further
+//! requests with [`BudgetExceeded`], which the pipeline treats as fatal, so
+//! the session stops instead of running up the bill.
+//!
+//! Chat APIs that report `usage` are billed by it; otherwise tokens are
+//! estimated with a [`Tokenizer`], [`ApproxTokenizer`] unless one that
+//! matches the model is plugged in.
+
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// What a backend bills by
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Billable {
+    DeepLChars,
+    ElevenLabsChars,
+    LlmInputTokens,
+    LlmOutputTokens,
+}
+
+impl Billable {
+    const ALL: [Billable; 4] = [
+        Billable::DeepLChars,
+        Billable::ElevenLabsChars,
+        Billable::LlmInputTokens,
+        Billable::LlmOutputTokens,
+    ];
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Prices in US dollars. The defaults are list prices when this was written:
+/// DeepL API Pro, ElevenLabs overage on the Creator plan and the default
+/// `gpt-4o-mini` model. Set a price to 0 for usage a plan already includes.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct Pricing {
+    pub deepl_per_million_chars: f64,
+    pub elevenlabs_per_thousand_chars: f64,
+    pub llm_input_per_million_tokens: f64,
+    pub llm_output_per_million_tokens: f64,
+}
+
+impl Default for Pricing {
+    fn default() -> Self {
+        Self {
+            deepl_per_million_chars: 25.0,
+            elevenlabs_per_thousand_chars: 0.30,
+            llm_input_per_million_tokens: 0.15,
+            llm_output_per_million_tokens: 0.60,
+        }
+    }
+}
+
+impl Pricing {
+    /// Dollars per unit of `item`.
+    fn unit_price(&self, item: Billable) -> f64 {
+        match item {
+            Billable::DeepLChars => self.deepl_per_million_chars / 1_000_000.0,
+            Billable::ElevenLabsChars => self.elevenlabs_per_thousand_chars / 1_000.0,
+            Billable::LlmInputTokens => self.llm_input_per_million_tokens / 1_000_000.0,
+            Billable::LlmOutputTokens => self.llm_output_per_million_tokens / 1_000_000.0,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct CostConfig {
+    pub pricing: Pricing,
+    /// Spend in US dollars after which billed requests are refused and the
+    /// session stops; unlimited when `None`.
+    pub budget_usd: Option<f64>,
+}
+
+#[derive(thiserror::Error, Debug, Clone, PartialEq)]
+#[error("spend budget of ${budget_usd:.2} reached (${spent_usd:.2} estimated)")]
+pub struct BudgetExceeded {
+    pub budget_usd: f64,
+    pub spent_usd: f64,
+}
+
+/// Counts tokens the way an LLM would bill them.
+pub trait Tokenizer: Send + Sync {
+    fn count_tokens(&self, text: &str) -> u64;
+}
+
+/// About four characters per token for alphabetic scripts and one per
+/// character for Chinese, Japanese and Korean, close enough to the common
+/// BPE vocabularies for an estimate.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ApproxTokenizer;
+
+impl Tokenizer for ApproxTokenizer {
+    fn count_tokens(&self, text: &str) -> u64 {
+        let (wide, other) = text.chars().fold((0u64, 0u64), |(wide, other), c| {
+            if is_cjk(c) {
+                (wide + 1, other)
+            } else {
+                (wide, other + 1)
+            }
+        });
+        wide + other.div_ceil(4)
+    }
+}
+
+fn is_cjk(c: char) -> bool {
+    matches!(c,
+        '\u{3040}'..='\u{30ff}' // kana
+        | '\u{3400}'..='\u{4dbf}' // CJK extension A
+        | '\u{4e00}'..='\u{9fff}' // CJK unified ideographs
+        | '\u{ac00}'..='\u{d7af}' // Hangul syllables
+    )
+}
+
+/// Shared spend counter. Clones share the same counts.
+#[derive(Clone)]
+pub struct CostMeter {
+    config: Arc<CostConfig>,
+    units: Arc<[AtomicU64; 4]>,
+    tokenizer: Arc<dyn Tokenizer>,
+}
+
+impl Default for CostMeter {
+    fn default() -> Self {
+        Self::new(CostConfig::default())
+    }
+}
+
+impl CostMeter {
+    pub fn new(config: CostConfig) -> Self {
+        Self {
+            config: Arc::new(config),
+            units: Arc::default(),
+            tokenizer: Arc::new(ApproxTokenizer),
+        }
+    }
+
+    /// Estimates LLM tokens with `tokenizer` when the API reports no usage.
+    pub fn with_tokenizer(mut self, tokenizer: impl Tokenizer + 'static) -> Self {
+        self.tokenizer = Arc::new(tokenizer);
+        self
+    }
+
+    pub fn record(&self, item: Billable, units: u64) {
+        self.units[item.index()].fetch_add(units, Ordering::Relaxed);
+    }
+
+    /// Records the characters of `text`, as DeepL and ElevenLabs count them.
+    pub fn record_chars(&self, item: Billable, text: &str) {
+        self.record(item, text.chars().count() as u64);
+    }
+
+    /// Records an LLM exchange, from the API's reported `(prompt,
+    /// completion)` token counts when there are any, otherwise estimated from
+    /// the text sent and received.
+    pub fn record_llm(&self, prompt: &str, reply: &str, usage: Option<(u64, u64)>) {
+        let (input, output) = usage.unwrap_or_else(|| {
+            (
+                self.tokenizer.count_tokens(prompt),
+                self.tokenizer.count_tokens(reply),
+            )
+        });
+        self.record(Billable::LlmInputTokens, input);
+        self.record(Billable::LlmOutputTokens, output);
+    }
+
+    pub fn spent_usd(&self) -> f64 {
+        Billable::ALL
+            .iter()
+            .map(|&item| self.units(item) as f64 * self.config.pricing.unit_price(item))
+            .sum()
+    }
+
+    /// Fails once the budget, if any, is spent; clients call this before each
+    /// billed request, so the request that crosses the budget still goes out.
+    pub fn check_budget(&self) -> Result<(), BudgetExceeded> {
+        match self.config.budget_usd {
+            Some(budget_usd) => {
+                let spent_usd = self.spent_usd();
+                if spent_usd >= budget_usd {
+                    Err(BudgetExceeded {
+                        budget_usd,
+                        spent_usd,
+                    })
+                } else {
+                    Ok(())
+                }
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn units(&self, item: Billable) -> u64 {
+        self.units[item.index()].load(Ordering::Relaxed)
+    }
+
+    pub fn snapshot(&self) -> CostSnapshot {
+        CostSnapshot {
+            deepl_chars: self.units(Billable::DeepLChars),
+            elevenlabs_chars: self.units(Billable::ElevenLabsChars),
+            llm_input_tokens: self.units(Billable::LlmInputTokens),
+            llm_output_tokens: self.units(Billable::LlmOutputTokens),
+            spent_usd: self.spent_usd(),
+            budget_usd: self.config.budget_usd,
+        }
+    }
+}
+
+/// Usage and estimated spend so far
+#[derive(Clone, Debug, Serialize, PartialEq)]
+pub struct CostSnapshot {
+    pub deepl_chars: u64,
+    pub elevenlabs_chars: u64,
+    pub llm_input_tokens: u64,
+    pub llm_output_tokens: u64,
+    pub spent_usd: f64,
+    pub budget_usd: Option<f64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prices_usage_and_enforces_the_budget() {
+        let meter = CostMeter::new(CostConfig {
+            pricing: Pricing::default(),
+            budget_usd: Some(0.5),
+        });
+        meter.record(Billable::DeepLChars, 10_000);
+        meter.record(Billable::ElevenLabsChars, 1_000);
+        assert!((meter.spent_usd() - 0.55).abs() < 1e-9);
+        assert_eq!(
+            meter.check_budget(),
+            Err(BudgetExceeded {
+                budget_usd: 0.5,
+                spent_usd: meter.spent_usd()
+            })
+        );
+
+        let unlimited = CostMeter::default();
+        unlimited.record(Billable::DeepLChars, 1_000_000_000);
+        assert!(unlimited.check_budget().is_ok());
+    }
+
+    #[test]
+    fn llm_usage_is_preferred_over_the_estimate() {
+        let meter = CostMeter::default();
+        meter.record_llm("abcdefgh", "こんにちは", None);
+        meter.record_llm("ignored", "ignored", Some((100, 10)));
+        let snapshot = meter.snapshot();
+        assert_eq!(snapshot.llm_input_tokens, 102);
+        assert_eq!(snapshot.llm_output_tokens, 15);
+    }
+
+    #[test]
+    fn approx_tokenizer_counts_cjk_per_character() {
+        assert_eq!(ApproxTokenizer.count_tokens("hello"), 2);
+        assert_eq!(ApproxTokenizer.count_tokens("你好 gg"), 3);
+        assert_eq!(ApproxTokenizer.count_tokens(""), 0);
+    }
+}
@@ -39,6 +39,18 @@ pub struct PcmChunk {
     pub format: PcmFormat,
     pub samples: Vec<f32>,
     pub duration_estimate: Duration,
+    /// The audio does not continue from the previous chunk; stages that carry
+    /// context between chunks should reset it.
+    pub discontinuity: bool,
+}
+
+impl PcmChunk {
+    /// Wall-clock time since the segment this audio came from was fetched.
+    pub fn age(&self) -> Duration {
+        SystemTime::now()
+            .duration_since(self.fetched_at)
+            .unwrap_or_default()
+    }
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -55,6 +67,10 @@ pub enum DecodeError {
 
 pub type Result<T> = std::result::Result<T, DecodeError>;
 
+/// Longest run of silence inserted for lost segments; beyond this the gap is
+/// only flagged, so a long outage does not stall ASR on empty audio.
+pub const MAX_GAP_FILL: Duration = Duration::from_secs(10);
+
 #[allow(async_fn_in_trait)]
 pub trait AudioDecoder: Send + Sync {
     fn decode_segment(&self, item: IngestItem) -> BoxFuture<'_, Result<PcmChunk>>;
@@ -75,6 +91,12 @@ impl Decoder {
     }
 }
 
+impl AudioDecoder for Decoder {
+    fn decode_segment(&self, item: IngestItem) -> BoxFuture<'_, Result<PcmChunk>> {
+        self.inner.decode_segment(item)
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct FfmpegAudioDecoder {
     output_format: PcmFormat,
@@ -106,7 +128,7 @@ impl FfmpegAudioDecoder {
 
     #[cfg(feature = "ffmpeg-sidecar")]
     fn parse_f32le_mono(raw: &[u8]) -> Result<Vec<f32>> {
-        if raw.len() % 4 != 0 {
+        if !raw.len().is_multiple_of(4) {
             return Err(DecodeError::InvalidPcm(format!(
                 "f32le byte length must be multiple of 4, got {}",
                 raw.len()
@@ -259,7 +281,10 @@ impl AudioDecoder for FfmpegAudioDecoder {
         let this = self.clone();
         async move {
             this.ensure_ffmpeg_available()?;
-            let samples = this.decode_with_ffmpeg(item.bytes).await?;
+            // Each segment is decoded by a fresh ffmpeg process, so codec state
+            // never leaks across a discontinuity.
+            let decoded = this.decode_with_ffmpeg(item.bytes).await?;
+            let samples = prepend_gap_silence(this.output_format, item.gap_before, decoded);
             let duration_estimate =
                 Self::duration_from_samples(this.output_format.sample_rate, samples.len());
 
@@ -270,12 +295,27 @@ impl AudioDecoder for FfmpegAudioDecoder {
                 format: this.output_format,
                 samples,
                 duration_estimate,
+                discontinuity: item.discontinuity,
             })
         }
         .boxed()
     }
 }
 
+/// Prepends silence standing in for `gap` of lost audio (capped at
+/// [`MAX_GAP_FILL`]), so downstream timing matches the stream's and ASR does
+/// not hear the audio either side of the gap as one utterance.
+pub fn prepend_gap_silence(format: PcmFormat, gap: Duration, samples: Vec<f32>) -> Vec<f32> {
+    if gap.is_zero() {
+        return samples;
+    }
+    let fill = gap.min(MAX_GAP_FILL);
+    let frames = (fill.as_secs_f64() * f64::from(format.sample_rate)).round() as usize;
+    let mut out = vec![0.0; frames * usize::from(format.channels)];
+    out.extend(samples);
+    out
+}
+
 pub fn i16_to_f32_pcm(samples: &[i16]) -> Vec<f32> {
     if samples.is_empty() {
         return Vec::new();
@@ -309,6 +349,19 @@ mod tests {
         assert!(v[4] > 0.9999);
     }
 
+    #[test]
+    fn gap_silence_is_prepended_and_capped() {
+        let fmt = PcmFormat::whisper_f32_mono_16khz();
+        let out = prepend_gap_silence(fmt, Duration::from_millis(500), vec![1.0; 4]);
+        assert_eq!(out.len(), 8_004);
+        assert!(out[..8_000].iter().all(|&s| s == 0.0));
+        assert_eq!(out[8_000..], [1.0; 4]);
+
+        let capped = prepend_gap_silence(fmt, Duration::from_secs(60), Vec::new());
+        assert_eq!(capped.len(), 16_000 * MAX_GAP_FILL.as_secs() as usize);
+        assert_eq!(prepend_gap_silence(fmt, Duration::ZERO, vec![1.0]), [1.0]);
+    }
+
     #[test]
     fn duration_from_sample_count_mono_16k() {
         let d = duration_from_sample_count(16_000, 1, 16_000);
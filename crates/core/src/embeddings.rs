@@ -0,0 +1,173 @@
+//! Text embeddings for searching by meaning
+//!
+//! [`EmbeddingClient`] turns text into vectors with any OpenAI-compatible
+//! `/embeddings` endpoint, including local servers such as Ollama or
+//! llama.cpp, so nothing has to leave the machine. Texts that mean much the
+//! same get vectors pointing the same way, which [`cosine_similarity`]
+//! measures.
+
+use crate::util::{HttpError, HttpRequest, ReqwestTransport, SharedTransport};
+use serde::{Deserialize, Serialize};
+
+/// Texts sent in one request; servers cap the batch size.
+pub const EMBEDDING_BATCH: usize = 64;
+
+#[derive(thiserror::Error, Debug)]
+pub enum EmbeddingError {
+    #[error("network error: {0}")]
+    Network(#[from] HttpError),
+    #[error("API error: {0}")]
+    Api(String),
+    #[error("invalid response: {0}")]
+    InvalidResponse(String),
+}
+
+/// Embeddings from an OpenAI-compatible `/embeddings` endpoint
+#[derive(Clone)]
+pub struct EmbeddingClient {
+    transport: SharedTransport,
+    base_url: String,
+    model: String,
+    api_key: Option<String>,
+}
+
+impl EmbeddingClient {
+    /// `base_url` is the API root, e.g. `https://api.openai.com/v1` or
+    /// `http://localhost:11434/v1`.
+    pub fn new(base_url: impl Into<String>, model: impl Into<String>) -> Self {
+        Self {
+            transport: ReqwestTransport::default().shared(),
+            base_url: base_url.into().trim_end_matches('/').to_owned(),
+            model: model.into(),
+            api_key: None,
+        }
+    }
+
+    /// Sent as a bearer token; local servers usually need none.
+    pub fn with_api_key(mut self, api_key: String) -> Self {
+        self.api_key = Some(api_key);
+        self
+    }
+
+    /// Sends requests through `transport` instead of the network, e.g. a
+    /// [`crate::util::MockTransport`] in tests.
+    pub fn with_transport(mut self, transport: SharedTransport) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    /// The model vectors come from; vectors of different models cannot be
+    /// compared.
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    /// One vector per text, in order. Send at most [`EMBEDDING_BATCH`]
+    /// texts at a time.
+    pub async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, EmbeddingError> {
+        if texts.is_empty() {
+            return Ok(Vec::new());
+        }
+        let body = EmbeddingRequest {
+            model: &self.model,
+            input: texts,
+        };
+        let mut request =
+            HttpRequest::post(format!("{}/embeddings", self.base_url)).with_json(&body)?;
+        if let Some(key) = &self.api_key {
+            request = request.with_header("Authorization", format!("Bearer {key}"));
+        }
+
+        let response = self.transport.send(request).await?;
+        if !response.is_success() {
+            return Err(EmbeddingError::Api(format!(
+                "HTTP {}: {}",
+                response.status,
+                response.text()
+            )));
+        }
+        let mut parsed: EmbeddingResponse = response
+            .json()
+            .map_err(|e| EmbeddingError::InvalidResponse(e.to_string()))?;
+        if parsed.data.len() != texts.len() {
+            return Err(EmbeddingError::InvalidResponse(format!(
+                "{} embeddings for {} texts",
+                parsed.data.len(),
+                texts.len()
+            )));
+        }
+        parsed.data.sort_by_key(|d| d.index);
+        Ok(parsed.data.into_iter().map(|d| d.embedding).collect())
+    }
+}
+
+#[derive(Serialize)]
+struct EmbeddingRequest<'a> {
+    model: &'a str,
+    input: &'a [String],
+}
+
+#[derive(Deserialize)]
+struct EmbeddingResponse {
+    data: Vec<EmbeddingData>,
+}
+
+#[derive(Deserialize)]
+struct EmbeddingData {
+    embedding: Vec<f32>,
+    #[serde(default)]
+    index: usize,
+}
+
+/// How alike two vectors are, from -1 (opposite) to 1 (the same meaning);
+/// 0 for vectors of different lengths or no length.
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norms = norm(a) * norm(b);
+    if norms == 0.0 {
+        0.0
+    } else {
+        dot / norms
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::{HttpMethod, MockTransport};
+
+    #[tokio::test]
+    async fn embeds_texts_in_order() {
+        let mock = MockTransport::new().on(
+            HttpMethod::Post,
+            "localhost:11434/v1/embeddings",
+            200,
+            r#"{"data":[{"index":1,"embedding":[0.0,1.0]},{"index":0,"embedding":[1.0,0.0]}]}"#,
+        );
+        let client = EmbeddingClient::new("http://localhost:11434/v1/", "nomic-embed-text")
+            .with_transport(mock.clone().shared());
+
+        let vectors = client
+            .embed(&["patch notes".to_owned(), "boss fight".to_owned()])
+            .await
+            .unwrap();
+        assert_eq!(vectors, [vec![1.0, 0.0], vec![0.0, 1.0]]);
+        let body: serde_json::Value =
+            serde_json::from_slice(mock.requests()[0].body.as_ref().unwrap()).unwrap();
+        assert_eq!(body["model"], "nomic-embed-text");
+        assert_eq!(body["input"][1], "boss fight");
+    }
+
+    #[test]
+    fn measures_how_alike_vectors_are() {
+        assert_eq!(cosine_similarity(&[1.0, 0.0], &[2.0, 0.0]), 1.0);
+        assert_eq!(cosine_similarity(&[1.0, 0.0], &[0.0, 3.0]), 0.0);
+        assert_eq!(cosine_similarity(&[1.0, 0.0], &[-1.0, 0.0]), -1.0);
+        assert_eq!(cosine_similarity(&[1.0], &[1.0, 0.0]), 0.0);
+        assert_eq!(cosine_similarity(&[0.0, 0.0], &[1.0, 0.0]), 0.0);
+    }
+}
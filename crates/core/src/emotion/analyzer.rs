@@ -58,11 +58,7 @@ impl EmotionAnalyzer for BasicEmotionAnalyzer {
             let emotion = if features.energy_rms > 0.3 {
                 if let Some(pitch) = features.pitch_hz {
                     if pitch > 220.0 {
-                        if features.energy_rms > 0.6 {
-                            Emotion::Happy // Using Happy instead of Excited
-                        } else {
-                            Emotion::Happy
-                        }
+                        Emotion::Happy // Using Happy instead of Excited
                     } else if pitch < 100.0 {
                         if features.energy_rms > 0.5 {
                             Emotion::Angry
@@ -77,11 +73,7 @@ impl EmotionAnalyzer for BasicEmotionAnalyzer {
                         }
                     }
                 } else {
-                    if features.energy_rms > 0.6 {
-                        Emotion::Happy // Using Happy instead of Excited
-                    } else {
-                        Emotion::Happy
-                    }
+                    Emotion::Happy // Using Happy instead of Excited
                 }
             } else if features.energy_rms > 0.1 {
                 if let Some(pitch) = features.pitch_hz {
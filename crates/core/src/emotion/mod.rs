@@ -1,4 +1,5 @@
 mod analyzer;
+mod pitch;
 
 use serde::{Deserialize, Serialize};
 use std::time::Duration;
@@ -15,11 +16,13 @@ pub enum Emotion {
 }
 
 pub use analyzer::{BasicEmotionAnalyzer, EmotionAnalyzer, EmotionError};
+pub use pitch::{estimate_pitch, gender_of_pitch, typical_pitch, PitchTracker};
 
 #[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
 pub struct ProsodyFeatures {
     pub energy_rms: f32,
     pub pitch_hz: Option<f32>,
+    /// Words per second.
     pub speaking_rate: Option<f32>,
 }
 
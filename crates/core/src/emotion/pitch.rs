@@ -0,0 +1,192 @@
+//! Voice pitch
+//!
+//! How high someone speaks is the plainest cue to whether a voice sounds
+//! male or female. [`estimate_pitch`] finds the fundamental frequency of
+//! speech by autocorrelation, frame by frame, and a [`PitchTracker`] follows
+//! the speaker's over the last half minute or so of speech to tell which
+//! range it falls in.
+
+use crate::config::SpeakerGender;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+/// Lowest and highest voice pitch looked for.
+const MIN_PITCH_HZ: f32 = 65.0;
+const MAX_PITCH_HZ: f32 = 400.0;
+/// Frames quieter than this (RMS of samples in -1..1) are not looked at.
+const SILENCE_RMS: f32 = 0.01;
+/// How alike a frame must be to itself one period later to count as voiced.
+const VOICED: f32 = 0.6;
+/// Fewer voiced frames than this give no estimate.
+const MIN_FRAMES: usize = 3;
+/// Voiced frames the tracker keeps, about half a minute of speech.
+const TRACKED_FRAMES: usize = 1000;
+/// Voiced frames the tracker needs before it tells a range.
+const TRACKER_MIN_FRAMES: usize = 30;
+/// Male voices are mostly pitched below this, female voices above
+/// [`FEMALE_ABOVE_HZ`]; in between a voice could be either.
+const MALE_BELOW_HZ: f32 = 150.0;
+const FEMALE_ABOVE_HZ: f32 = 180.0;
+
+/// The pitch of each voiced frame of mono `samples` (in -1..1), in Hz.
+fn frame_pitches(samples: &[f32], sample_rate_hz: u32) -> Vec<f32> {
+    let rate = sample_rate_hz as f32;
+    let min_lag = (rate / MAX_PITCH_HZ).max(1.0) as usize;
+    let max_lag = (rate / MIN_PITCH_HZ) as usize;
+    if max_lag <= min_lag {
+        return Vec::new();
+    }
+    // Each frame is compared with itself up to `max_lag` later.
+    let window = max_lag;
+    samples
+        .chunks_exact(window + max_lag)
+        .filter_map(|frame| {
+            let energy = |at: usize| frame[at..at + window].iter().map(|s| s * s).sum::<f32>();
+            let own = energy(0);
+            if (own / window as f32).sqrt() < SILENCE_RMS {
+                return None;
+            }
+            let similarity: Vec<f32> = (min_lag..=max_lag)
+                .map(|lag| {
+                    let product: f32 = (0..window).map(|i| frame[i] * frame[i + lag]).sum();
+                    product / (own * energy(lag)).sqrt().max(f32::EPSILON)
+                })
+                .collect();
+            let best = similarity.iter().copied().fold(f32::MIN, f32::max);
+            if best < VOICED {
+                return None;
+            }
+            // The first peak nearly as strong as the best, so a voice is not
+            // taken for one an octave lower.
+            let peak = (0..similarity.len()).find(|&i| {
+                let s = similarity[i];
+                s >= 0.9 * best
+                    && (i == 0 || similarity[i - 1] <= s)
+                    && similarity.get(i + 1).is_none_or(|&next| next <= s)
+            })?;
+            Some(rate / (min_lag + peak) as f32)
+        })
+        .collect()
+}
+
+/// The median pitch of the voiced parts of mono `samples` (in -1..1), in
+/// Hz, if enough of them are voiced.
+pub fn estimate_pitch(samples: &[f32], sample_rate_hz: u32) -> Option<f32> {
+    let mut pitches = frame_pitches(samples, sample_rate_hz);
+    (pitches.len() >= MIN_FRAMES).then(|| median(&mut pitches))
+}
+
+/// The voice range `pitch_hz` falls in; `None` between the two.
+pub fn gender_of_pitch(pitch_hz: f32) -> Option<SpeakerGender> {
+    if pitch_hz < MALE_BELOW_HZ {
+        Some(SpeakerGender::Male)
+    } else if pitch_hz > FEMALE_ABOVE_HZ {
+        Some(SpeakerGender::Female)
+    } else {
+        None
+    }
+}
+
+/// A pitch in the middle of the range of `gender`, in Hz.
+pub fn typical_pitch(gender: SpeakerGender) -> f32 {
+    match gender {
+        SpeakerGender::Male => 120.0,
+        SpeakerGender::Female => 210.0,
+    }
+}
+
+fn median(values: &mut [f32]) -> f32 {
+    values.sort_by(f32::total_cmp);
+    values[values.len() / 2]
+}
+
+/// The speaker's pitch over recent speech. Clones share it, so one stage can
+/// listen while another asks.
+#[derive(Clone, Default)]
+pub struct PitchTracker {
+    pitches: Arc<Mutex<VecDeque<f32>>>,
+}
+
+impl PitchTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Takes in the voiced frames of interleaved `samples` (in -1..1).
+    pub fn observe(&self, samples: &[f32], sample_rate_hz: u32, channels: u16) {
+        let channels = usize::from(channels.max(1));
+        let pitches = if channels == 1 {
+            frame_pitches(samples, sample_rate_hz)
+        } else {
+            let mono: Vec<f32> = samples
+                .chunks_exact(channels)
+                .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+                .collect();
+            frame_pitches(&mono, sample_rate_hz)
+        };
+        let mut tracked = self.lock();
+        for pitch in pitches {
+            if tracked.len() == TRACKED_FRAMES {
+                tracked.pop_front();
+            }
+            tracked.push_back(pitch);
+        }
+    }
+
+    /// The median pitch of recent speech, once there has been enough of it.
+    pub fn pitch(&self) -> Option<f32> {
+        let mut pitches: Vec<f32> = self.lock().iter().copied().collect();
+        (pitches.len() >= TRACKER_MIN_FRAMES).then(|| median(&mut pitches))
+    }
+
+    /// Whether the speaker sounds male or female; `None` while unsure.
+    pub fn gender(&self) -> Option<SpeakerGender> {
+        self.pitch().and_then(gender_of_pitch)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, VecDeque<f32>> {
+        match self.pitches.lock() {
+            Ok(g) => g,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    /// A buzzy voice-like tone with a strong second harmonic.
+    fn voice(pitch_hz: f32, secs: f32) -> Vec<f32> {
+        let rate = 16_000.0;
+        (0..(rate * secs) as usize)
+            .map(|i| {
+                let t = i as f32 / rate;
+                0.3 * (2.0 * PI * pitch_hz * t).sin() + 0.25 * (4.0 * PI * pitch_hz * t).sin()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn estimates_the_pitch_of_a_voice() {
+        for pitch in [100.0, 220.0] {
+            let estimate = estimate_pitch(&voice(pitch, 0.5), 16_000).unwrap();
+            assert!(
+                (estimate - pitch).abs() < pitch * 0.03,
+                "{estimate} for {pitch}"
+            );
+        }
+        assert_eq!(estimate_pitch(&[0.0; 8000], 16_000), None);
+    }
+
+    #[test]
+    fn tracks_the_speakers_range() {
+        let tracker = PitchTracker::new();
+        assert_eq!(tracker.gender(), None);
+        tracker.observe(&voice(110.0, 2.0), 16_000, 1);
+        assert_eq!(tracker.gender(), Some(SpeakerGender::Male));
+        assert_eq!(gender_of_pitch(165.0), None);
+        assert_eq!(gender_of_pitch(210.0), Some(SpeakerGender::Female));
+    }
+}
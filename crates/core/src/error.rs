@@ -0,0 +1,216 @@
+//! Shared classification of stage errors
+//!
+//! Each stage keeps its own error enum, but all of them map onto an
+//! [`ErrorClass`] so the pipeline can react the same way everywhere: retry a
+//! transient failure, drop the one item that failed and carry on, or shut
+//! down when the stage cannot make progress at all.
+
+#[cfg(feature = "native")]
+use crate::asr::AsrError;
+#[cfg(feature = "native")]
+use crate::decode::DecodeError;
+#[cfg(feature = "native")]
+use crate::ingest::IngestError;
+#[cfg(feature = "native")]
+use crate::playback::PlaybackError;
+use crate::translate::TranslateError;
+use crate::tts::TtsError;
+use crate::util::{is_http_retryable, HttpError};
+use std::fmt;
+
+/// How the pipeline should react to an error
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorClass {
+    /// Transient, e.g. a timeout or a 5xx; the same request may succeed if
+    /// tried again shortly.
+    Retryable,
+    /// Only this item is lost; the stage keeps running, possibly with reduced
+    /// quality such as a fallback voice.
+    Degraded,
+    /// Every following item would fail the same way, e.g. a rejected API key
+    /// or a missing model; the pipeline shuts down.
+    Fatal,
+}
+
+impl fmt::Display for ErrorClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Retryable => "retryable",
+            Self::Degraded => "degraded",
+            Self::Fatal => "fatal",
+        })
+    }
+}
+
+/// Implemented by every stage error
+pub trait Classify {
+    fn class(&self) -> ErrorClass;
+
+    fn is_retryable(&self) -> bool {
+        self.class() == ErrorClass::Retryable
+    }
+
+    fn is_fatal(&self) -> bool {
+        self.class() == ErrorClass::Fatal
+    }
+}
+
+/// Class of a non-success HTTP status from an upstream API.
+fn http_status_class(status: u16) -> ErrorClass {
+    match status {
+        s if is_http_retryable(s) => ErrorClass::Retryable,
+        401 | 403 => ErrorClass::Fatal,
+        _ => ErrorClass::Degraded,
+    }
+}
+
+impl Classify for HttpError {
+    fn class(&self) -> ErrorClass {
+        match self {
+            #[cfg(feature = "native")]
+            Self::Transport(_) => ErrorClass::Retryable,
+            Self::Json(_) | Self::Unmatched(_) => ErrorClass::Degraded,
+            #[cfg(feature = "native")]
+            Self::InvalidProxy(..) => ErrorClass::Fatal,
+            Self::CaBundle(..) => ErrorClass::Fatal,
+        }
+    }
+}
+
+#[cfg(feature = "native")]
+impl Classify for IngestError {
+    fn class(&self) -> ErrorClass {
+        match self {
+            Self::Http(e) => e.class(),
+            Self::HttpStatus(status, _) => http_status_class(*status),
+            // The stream may come back, or the next playlist may parse.
+            Self::ChannelOffline(_) | Self::HlsParse => ErrorClass::Retryable,
+            Self::SegmentDeadline(_) => ErrorClass::Degraded,
+            Self::NotImplemented
+            | Self::InvalidUrl(_)
+            | Self::TwitchGqlMissingFields
+            | Self::ExpectedMasterPlaylist
+            | Self::ExpectedMediaPlaylist
+            | Self::NoUsableVariant
+            | Self::Io(_)
+            | Self::Replay(_)
+            | Self::UnsupportedListenUrl(_)
+            | Self::UnsupportedPeerUrl(_)
+            | Self::Ffmpeg(_)
+            | Self::Capture(_)
+            | Self::TaskFailed(_) => ErrorClass::Fatal,
+        }
+    }
+}
+
+#[cfg(feature = "native")]
+impl Classify for DecodeError {
+    fn class(&self) -> ErrorClass {
+        match self {
+            Self::FfmpegUnavailable(_) => ErrorClass::Fatal,
+            // A corrupt segment does not affect the next one.
+            Self::FfmpegFailed(_) | Self::InvalidPcm(_) => ErrorClass::Degraded,
+        }
+    }
+}
+
+#[cfg(feature = "native")]
+impl Classify for AsrError {
+    fn class(&self) -> ErrorClass {
+        match self {
+            Self::ModelNotFound(_)
+            | Self::ModelLoadError(_)
+            | Self::UnsupportedFormat { .. }
+            | Self::Unauthorized(_)
+            | Self::InvalidEndpoint(_) => ErrorClass::Fatal,
+            Self::InferenceError(_)
+            | Self::EmptyAudio
+            | Self::TranscriptionFailed(_)
+            | Self::Service(_) => ErrorClass::Degraded,
+        }
+    }
+}
+
+impl Classify for TranslateError {
+    fn class(&self) -> ErrorClass {
+        match self {
+            Self::NotImplemented | Self::BudgetExceeded(_) => ErrorClass::Fatal,
+            Self::Network(e) => e.class(),
+            Self::HttpStatus(status, _) => http_status_class(*status),
+            Self::InvalidResponse(_) | Self::Api(_) => ErrorClass::Degraded,
+        }
+    }
+}
+
+impl Classify for TtsError {
+    fn class(&self) -> ErrorClass {
+        match self {
+            Self::NotImplemented | Self::BudgetExceeded(_) | Self::Unauthorized(_) => {
+                ErrorClass::Fatal
+            }
+            Self::RateLimited(_) => ErrorClass::Retryable,
+            Self::Network(e) => e.class(),
+            Self::HttpStatus(status, _) => http_status_class(*status),
+            // A fallback voice takes over when one is configured.
+            Self::QuotaExhausted | Self::Decode(_) | Self::Backend { .. } => ErrorClass::Degraded,
+        }
+    }
+}
+
+#[cfg(feature = "native")]
+impl Classify for PlaybackError {
+    fn class(&self) -> ErrorClass {
+        match self {
+            Self::NotImplemented => ErrorClass::Fatal,
+            // The output device is opened again for the next clip.
+            Self::AudioOutputUnavailable { .. } => ErrorClass::Degraded,
+            // The encoder or writer task is gone; no later clip gets through either.
+            Self::NetworkOutput { .. } | Self::ObsOutput { .. } => ErrorClass::Fatal,
+            // Only starting a recording fails, and nothing would be recorded.
+            Self::Recording { .. } => ErrorClass::Fatal,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn http_statuses_are_classified_by_cause() {
+        assert_eq!(
+            TranslateError::HttpStatus(503, String::new()).class(),
+            ErrorClass::Retryable
+        );
+        assert_eq!(
+            TranslateError::HttpStatus(403, String::new()).class(),
+            ErrorClass::Fatal
+        );
+    }
+
+    #[test]
+    fn tts_errors_are_classified_by_cause() {
+        let quota = TtsError::from_status(401, r#"{"detail":{"status":"quota_exceeded"}}"#.into());
+        assert!(matches!(quota, TtsError::QuotaExhausted));
+        assert_eq!(quota.class(), ErrorClass::Degraded);
+        assert!(quota.disables_backend());
+        let unauthorized = TtsError::from_status(401, "invalid api key".into());
+        assert!(unauthorized.is_fatal() && unauthorized.disables_backend());
+        assert!(TtsError::from_status(429, "too many requests".into()).is_retryable());
+        assert!(TtsError::from_status(502, String::new()).is_retryable());
+        let backend = TtsError::backend("piper", "exited with 1");
+        assert_eq!(backend.to_string(), "piper: exited with 1");
+        assert!(!backend.disables_backend());
+    }
+
+    #[cfg(feature = "native")]
+    #[test]
+    fn native_stage_errors_are_classified() {
+        assert_eq!(
+            IngestError::HttpStatus(404, String::new()).class(),
+            ErrorClass::Degraded
+        );
+        assert!(IngestError::ChannelOffline("x".to_owned()).is_retryable());
+        assert!(AsrError::ModelNotFound("x".to_owned()).is_fatal());
+    }
+}
@@ -0,0 +1,586 @@
+//! Accuracy evaluation against a reference
+//!
+//! Where [`crate::bench`] measures how fast the backends are, this measures
+//! how well they do on the user's own content. A golden set pairs reference
+//! transcripts with reference translations and, optionally, the audio they
+//! were heard in. [`score_asr`] transcribes the audio and reports the word
+//! error rate; [`score_translator`] translates the reference transcripts, so
+//! ASR mistakes do not count against the translator, and reports BLEU and
+//! chrF. All three are corpus-level: counts are summed over the cases before
+//! the score is taken, as sacreBLEU does.
+
+use crate::asr::AsrBackend;
+use crate::config::TargetLang;
+use crate::decode::AudioDecoder;
+use crate::ingest::IngestItem;
+use crate::translate::Translator;
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use url::Url;
+
+/// Longest word n-gram BLEU counts.
+const BLEU_ORDER: usize = 4;
+/// Longest character n-gram chrF counts.
+const CHRF_ORDER: usize = 6;
+/// chrF weighs recall this many times as much as precision.
+const CHRF_BETA: f64 = 2.0;
+
+#[derive(thiserror::Error, Debug)]
+pub enum EvalError {
+    #[error("failed to read eval input: {0}")]
+    Input(#[from] std::io::Error),
+
+    #[error("line {line} of the golden file: {source}")]
+    Golden {
+        line: usize,
+        source: serde_json::Error,
+    },
+
+    #[error(
+        "{transcripts} transcripts but {translations} translations; the files must be line-aligned"
+    )]
+    Mismatch {
+        transcripts: usize,
+        translations: usize,
+    },
+}
+
+/// One line of a golden JSONL file.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct GoldenCase {
+    /// Audio to transcribe, relative to the golden file. Cases without it
+    /// are only used for translation.
+    #[serde(default)]
+    pub audio: Option<PathBuf>,
+    /// What was said, as the ASR should have heard it.
+    pub transcript: String,
+    /// A reference translation of `transcript` into the target language.
+    /// Cases without it are only used for ASR.
+    #[serde(default)]
+    pub translation: Option<String>,
+}
+
+/// Reads a golden JSONL file, one [`GoldenCase`] per line. Relative audio
+/// paths are resolved against the file's directory.
+pub async fn load_golden(path: &Path) -> Result<Vec<GoldenCase>, EvalError> {
+    let text = tokio::fs::read_to_string(path).await?;
+    let dir = path.parent().unwrap_or(Path::new(""));
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            let mut case: GoldenCase =
+                serde_json::from_str(line).map_err(|source| EvalError::Golden {
+                    line: i + 1,
+                    source,
+                })?;
+            case.audio = case.audio.map(|audio| dir.join(audio));
+            Ok(case)
+        })
+        .collect()
+}
+
+/// Pairs the lines of a transcript file with those of its translation.
+pub async fn load_pairs(
+    transcripts: &Path,
+    translations: &Path,
+) -> Result<Vec<GoldenCase>, EvalError> {
+    let sources = read_lines(transcripts).await?;
+    let targets = read_lines(translations).await?;
+    if sources.len() != targets.len() {
+        return Err(EvalError::Mismatch {
+            transcripts: sources.len(),
+            translations: targets.len(),
+        });
+    }
+    Ok(sources
+        .into_iter()
+        .zip(targets)
+        .map(|(transcript, translation)| GoldenCase {
+            audio: None,
+            transcript,
+            translation: Some(translation),
+        })
+        .collect())
+}
+
+async fn read_lines(path: &Path) -> Result<Vec<String>, EvalError> {
+    Ok(tokio::fs::read_to_string(path)
+        .await?
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(str::to_owned)
+        .collect())
+}
+
+/// Transcribes the audio of each case that has some and scores it against
+/// the reference transcript. Cases that fail to decode or transcribe are
+/// counted in `errors` and left out of the score.
+pub async fn score_asr<D: AudioDecoder, A: AsrBackend>(
+    backend: &str,
+    decode: &D,
+    asr: &A,
+    cases: &[GoldenCase],
+) -> Result<AsrScore, EvalError> {
+    let mut pairs = Vec::new();
+    let mut errors = 0;
+    for (sequence, case) in cases.iter().enumerate() {
+        let Some(audio) = &case.audio else { continue };
+        let item = IngestItem {
+            sequence: sequence as u64,
+            fetched_at: SystemTime::now(),
+            url: Url::from_file_path(std::path::absolute(audio)?)
+                .unwrap_or_else(|_| Url::parse("file:///eval-input").expect("static url")),
+            approx_duration: Duration::ZERO,
+            bytes: Bytes::from(tokio::fs::read(audio).await?),
+            discontinuity: true,
+            gap_before: Duration::ZERO,
+        };
+        let transcribed = match decode.decode_segment(item).await {
+            Ok(pcm) => asr.transcribe(pcm).await.map_err(|e| e.to_string()),
+            Err(e) => Err(e.to_string()),
+        };
+        match transcribed {
+            Ok(segment) => pairs.push((case.transcript.as_str(), segment.text)),
+            Err(error) => {
+                tracing::warn!(backend, audio = %audio.display(), %error, "eval case failed");
+                errors += 1;
+            }
+        }
+    }
+    Ok(AsrScore {
+        backend: backend.to_owned(),
+        cases: pairs.len(),
+        errors,
+        wer: word_error_rate(&pairs),
+    })
+}
+
+/// Translates the reference transcript of each case that has a reference
+/// translation and scores the result. Failed translations are counted in
+/// `errors` and left out of the score.
+pub async fn score_translator<T: Translator>(
+    backend: &str,
+    translator: &T,
+    cases: &[GoldenCase],
+    target: &TargetLang,
+) -> TranslationScore {
+    let mut pairs = Vec::new();
+    let mut errors = 0;
+    for case in cases {
+        let Some(reference) = &case.translation else {
+            continue;
+        };
+        match translator
+            .translate(case.transcript.clone(), target.clone())
+            .await
+        {
+            Ok(translation) => pairs.push((reference.as_str(), translation.text)),
+            Err(error) => {
+                tracing::warn!(backend, %error, "eval case failed");
+                errors += 1;
+            }
+        }
+    }
+    TranslationScore {
+        backend: backend.to_owned(),
+        cases: pairs.len(),
+        errors,
+        bleu: bleu(&pairs),
+        chrf: chrf(&pairs),
+    }
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq)]
+pub struct AsrScore {
+    pub backend: String,
+    /// Cases scored.
+    pub cases: usize,
+    pub errors: usize,
+    /// Word edits per reference word; `None` when no case was scored.
+    pub wer: Option<f64>,
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq)]
+pub struct TranslationScore {
+    pub backend: String,
+    /// Cases scored.
+    pub cases: usize,
+    pub errors: usize,
+    /// 0–100; `None` when no case was scored.
+    pub bleu: Option<f64>,
+    /// 0–100; `None` when no case was scored.
+    pub chrf: Option<f64>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, PartialEq)]
+pub struct EvalReport {
+    pub asr: Vec<AsrScore>,
+    pub translation: Vec<TranslationScore>,
+}
+
+impl fmt::Display for EvalReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let score = |s: Option<f64>| s.map(|s| format!("{s:.1}")).unwrap_or_else(|| "-".into());
+        if !self.asr.is_empty() {
+            writeln!(
+                f,
+                "{:<16} {:>5} {:>6} {:>7}",
+                "asr", "cases", "errors", "wer %"
+            )?;
+            for s in &self.asr {
+                writeln!(
+                    f,
+                    "{:<16} {:>5} {:>6} {:>7}",
+                    s.backend,
+                    s.cases,
+                    s.errors,
+                    score(s.wer.map(|wer| wer * 100.0))
+                )?;
+            }
+        }
+        if !self.translation.is_empty() {
+            writeln!(
+                f,
+                "{:<16} {:>5} {:>6} {:>7} {:>7}",
+                "translation", "cases", "errors", "bleu", "chrf"
+            )?;
+            for s in &self.translation {
+                writeln!(
+                    f,
+                    "{:<16} {:>5} {:>6} {:>7} {:>7}",
+                    s.backend,
+                    s.cases,
+                    s.errors,
+                    score(s.bleu),
+                    score(s.chrf)
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Corpus word error rate of `(reference, hypothesis)` pairs: substituted,
+/// inserted and deleted words over reference words, ignoring case and
+/// punctuation.
+pub fn word_error_rate<S: AsRef<str>>(pairs: &[(&str, S)]) -> Option<f64> {
+    if pairs.is_empty() {
+        return None;
+    }
+    let words = |text: &str| -> Vec<String> {
+        text.to_lowercase()
+            .split(|c: char| !c.is_alphanumeric() && c != '\'')
+            .filter(|w| !w.is_empty())
+            .map(str::to_owned)
+            .collect()
+    };
+    let (mut edits, mut total) = (0, 0);
+    for (reference, hypothesis) in pairs {
+        let reference = words(reference);
+        edits += edit_distance(&reference, &words(hypothesis.as_ref()));
+        total += reference.len();
+    }
+    Some(edits as f64 / total.max(1) as f64)
+}
+
+/// Corpus BLEU of `(reference, hypothesis)` pairs over word 1- to 4-grams,
+/// with punctuation split into its own tokens. Without smoothing, so a corpus
+/// with no matching 4-gram scores 0.
+pub fn bleu<S: AsRef<str>>(pairs: &[(&str, S)]) -> Option<f64> {
+    if pairs.is_empty() {
+        return None;
+    }
+    let mut matches = [0usize; BLEU_ORDER];
+    let mut totals = [0usize; BLEU_ORDER];
+    let (mut reference_len, mut hypothesis_len) = (0, 0);
+    for (reference, hypothesis) in pairs {
+        let reference = tokens(reference);
+        let hypothesis = tokens(hypothesis.as_ref());
+        reference_len += reference.len();
+        hypothesis_len += hypothesis.len();
+        for n in 1..=BLEU_ORDER {
+            let (matched, total) = ngram_matches(&reference, &hypothesis, n);
+            matches[n - 1] += matched;
+            totals[n - 1] += total;
+        }
+    }
+    if matches.contains(&0) {
+        return Some(0.0);
+    }
+    let log_precision = matches
+        .iter()
+        .zip(&totals)
+        .map(|(&m, &t)| (m as f64 / t as f64).ln())
+        .sum::<f64>()
+        / BLEU_ORDER as f64;
+    let brevity = if hypothesis_len < reference_len {
+        (1.0 - reference_len as f64 / hypothesis_len as f64).exp()
+    } else {
+        1.0
+    };
+    Some(100.0 * brevity * log_precision.exp())
+}
+
+/// Corpus chrF of `(reference, hypothesis)` pairs: the F-score of character
+/// 1- to 6-grams, whitespace removed, with recall weighted by β = 2. Unlike
+/// BLEU it needs no word boundaries, so it also suits Chinese or Japanese.
+pub fn chrf<S: AsRef<str>>(pairs: &[(&str, S)]) -> Option<f64> {
+    if pairs.is_empty() {
+        return None;
+    }
+    let mut matches = [0usize; CHRF_ORDER];
+    let mut hypothesis_totals = [0usize; CHRF_ORDER];
+    let mut reference_totals = [0usize; CHRF_ORDER];
+    let chars = |text: &str| -> Vec<char> { text.chars().filter(|c| !c.is_whitespace()).collect() };
+    for (reference, hypothesis) in pairs {
+        let reference = chars(reference);
+        let hypothesis = chars(hypothesis.as_ref());
+        for n in 1..=CHRF_ORDER {
+            let (matched, total) = ngram_matches(&reference, &hypothesis, n);
+            matches[n - 1] += matched;
+            hypothesis_totals[n - 1] += total;
+            reference_totals[n - 1] += reference.len().saturating_sub(n - 1);
+        }
+    }
+    // Averaged over the orders both sides have n-grams of, as sacrebleu
+    // does, so text shorter than six characters can still score 100.
+    let orders: Vec<usize> = (0..CHRF_ORDER)
+        .filter(|&i| hypothesis_totals[i] > 0 && reference_totals[i] > 0)
+        .collect();
+    if orders.is_empty() {
+        return Some(0.0);
+    }
+    let average = |totals: &[usize; CHRF_ORDER]| {
+        orders
+            .iter()
+            .map(|&i| matches[i] as f64 / totals[i] as f64)
+            .sum::<f64>()
+            / orders.len() as f64
+    };
+    let precision = average(&hypothesis_totals);
+    let recall = average(&reference_totals);
+    if precision + recall == 0.0 {
+        return Some(0.0);
+    }
+    let beta2 = CHRF_BETA * CHRF_BETA;
+    Some(100.0 * (1.0 + beta2) * precision * recall / (beta2 * precision + recall))
+}
+
+/// Words with punctuation split off: `"gg, wp!"` is `gg` `,` `wp` `!`.
+fn tokens(text: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    for word in text.split_whitespace() {
+        let mut current = String::new();
+        for c in word.chars() {
+            if c.is_alphanumeric() || c == '\'' {
+                current.push(c);
+            } else {
+                if !current.is_empty() {
+                    tokens.push(std::mem::take(&mut current));
+                }
+                tokens.push(c.to_string());
+            }
+        }
+        if !current.is_empty() {
+            tokens.push(current);
+        }
+    }
+    tokens
+}
+
+/// n-grams of `hypothesis` also in `reference`, each counted at most as
+/// often as it occurs there, and the number of n-grams in `hypothesis`.
+fn ngram_matches<T: Eq + Hash>(reference: &[T], hypothesis: &[T], n: usize) -> (usize, usize) {
+    if hypothesis.len() < n {
+        return (0, 0);
+    }
+    let reference = ngram_counts(reference, n);
+    let matched = ngram_counts(hypothesis, n)
+        .into_iter()
+        .map(|(gram, count)| count.min(reference.get(gram).copied().unwrap_or(0)))
+        .sum();
+    (matched, hypothesis.len() + 1 - n)
+}
+
+fn ngram_counts<T: Eq + Hash>(items: &[T], n: usize) -> HashMap<&[T], usize> {
+    let mut counts = HashMap::new();
+    for gram in items.windows(n) {
+        *counts.entry(gram).or_default() += 1;
+    }
+    counts
+}
+
+/// Levenshtein distance between two word sequences.
+fn edit_distance<T: PartialEq>(a: &[T], b: &[T]) -> usize {
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, x) in a.iter().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, y) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(x != y);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(row[j + 1] + 1);
+        }
+    }
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asr::{AsrError, TranscriptSegment};
+    use crate::decode::{DecodeError, PcmChunk, PcmFormat};
+    use crate::translate::DummyTranslator;
+    use futures::future::BoxFuture;
+    use futures::FutureExt;
+
+    fn assert_close(actual: Option<f64>, expected: f64) {
+        let actual = actual.unwrap();
+        assert!((actual - expected).abs() < 0.01, "{actual} != {expected}");
+    }
+
+    #[test]
+    fn word_error_rate_ignores_case_and_punctuation() {
+        assert_close(
+            word_error_rate(&[("Hello, world! How are you?", "hello word how you")]),
+            0.4,
+        );
+        assert_close(word_error_rate(&[("GG, wp", "gg wp"), ("", "")]), 0.0);
+        assert_eq!(word_error_rate::<&str>(&[]), None);
+    }
+
+    #[test]
+    fn bleu_is_corpus_level_with_a_brevity_penalty() {
+        let sentence = "the quick brown fox jumps over the lazy dog";
+        assert_close(bleu(&[(sentence, sentence)]), 100.0);
+        assert_close(bleu(&[(sentence, "a completely different line")]), 0.0);
+        assert_close(
+            bleu(&[(sentence, "the quick brown fox jumps over the dog")]),
+            76.73,
+        );
+        assert_close(
+            bleu(&[(sentence, "the quick brown fox jumps over the lazy cat")]),
+            86.33,
+        );
+    }
+
+    #[test]
+    fn chrf_scores_character_ngrams() {
+        assert_close(chrf(&[("gg wp", "gg wp")]), 100.0);
+        assert_close(chrf(&[("abc", "xyz")]), 0.0);
+        assert_close(chrf(&[("the lazy dog", "the lazy cat")]), 57.72);
+    }
+
+    #[test]
+    fn tokens_split_off_punctuation() {
+        assert_eq!(tokens("gg, wp! don't"), ["gg", ",", "wp", "!", "don't"]);
+    }
+
+    #[tokio::test]
+    async fn golden_audio_is_relative_to_the_file() {
+        let dir = std::env::temp_dir().join(format!("tt-eval-{}", std::process::id()));
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        let path = dir.join("golden.jsonl");
+        tokio::fs::write(
+            &path,
+            "{\"audio\":\"a.wav\",\"transcript\":\"hi\",\"translation\":\"oi\"}\n\n\
+             {\"transcript\":\"bye\"}\n",
+        )
+        .await
+        .unwrap();
+
+        let cases = load_golden(&path).await.unwrap();
+        assert_eq!(cases.len(), 2);
+        assert_eq!(cases[0].audio.as_deref(), Some(dir.join("a.wav").as_path()));
+        assert_eq!(cases[1].translation, None);
+
+        tokio::fs::write(&path, "{\"audio\":\"a.wav\"}\n")
+            .await
+            .unwrap();
+        assert!(matches!(
+            load_golden(&path).await,
+            Err(EvalError::Golden { line: 1, .. })
+        ));
+        tokio::fs::remove_dir_all(&dir).await.unwrap();
+    }
+
+    struct SilenceDecoder;
+
+    impl AudioDecoder for SilenceDecoder {
+        fn decode_segment(&self, item: IngestItem) -> BoxFuture<'_, Result<PcmChunk, DecodeError>> {
+            async move {
+                Ok(PcmChunk {
+                    sequence: item.sequence,
+                    started_at: item.fetched_at,
+                    fetched_at: item.fetched_at,
+                    format: PcmFormat::whisper_f32_mono_16khz(),
+                    samples: vec![0.0; 16_000],
+                    duration_estimate: Duration::from_secs(1),
+                    discontinuity: item.discontinuity,
+                })
+            }
+            .boxed()
+        }
+    }
+
+    struct FixedAsr;
+
+    impl AsrBackend for FixedAsr {
+        fn transcribe(
+            &self,
+            audio: PcmChunk,
+        ) -> BoxFuture<'_, Result<TranscriptSegment, AsrError>> {
+            async move {
+                Ok(TranscriptSegment {
+                    text: "hello world".to_owned(),
+                    audio_duration: audio.duration_estimate,
+                    confidence: None,
+                    words: Vec::new(),
+                    speech_start: None,
+                })
+            }
+            .boxed()
+        }
+    }
+
+    #[tokio::test]
+    async fn scores_only_the_cases_each_stage_can_use() {
+        let audio = std::env::temp_dir().join(format!("tt-eval-{}.wav", std::process::id()));
+        tokio::fs::write(&audio, b"x").await.unwrap();
+        let cases = [
+            GoldenCase {
+                audio: Some(audio.clone()),
+                transcript: "hello there world".to_owned(),
+                translation: None,
+            },
+            GoldenCase {
+                audio: None,
+                transcript: "gg wp".to_owned(),
+                translation: Some("gg wp".to_owned()),
+            },
+        ];
+
+        let asr = score_asr("fixed", &SilenceDecoder, &FixedAsr, &cases)
+            .await
+            .unwrap();
+        assert_eq!((asr.cases, asr.errors), (1, 0));
+        assert_close(asr.wer, 1.0 / 3.0);
+
+        let translation = score_translator(
+            "dummy",
+            &DummyTranslator::new(),
+            &cases,
+            &TargetLang::default(),
+        )
+        .await;
+        assert_eq!((translation.cases, translation.errors), (1, 0));
+        assert_close(translation.chrf, 100.0);
+        tokio::fs::remove_file(&audio).await.unwrap();
+    }
+}
@@ -0,0 +1,323 @@
+//! Word-level glosses for language learners
+//!
+//! A [`Glosser`] breaks a source utterance into words or phrases and glosses
+//! each one in the target language, either from a user dictionary
+//! ([`Dictionary`]) or by asking a [`Translator`] word by word
+//! ([`TranslatorGlosser`]). [`run`] glosses every translated utterance and
+//! publishes the result as a [`PipelineEvent::Gloss`] next to the translation.
+
+use crate::config::TargetLang;
+use crate::status::{PipelineEvent, PipelineStatus};
+use crate::translate::{TranslateError, Translator};
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+#[cfg(feature = "native")]
+use std::path::Path;
+use tokio::sync::broadcast;
+
+/// Most distinct words [`TranslatorGlosser`] looks up per utterance, to
+/// bound the extra translation requests.
+pub const MAX_TRANSLATED_WORDS: usize = 16;
+
+#[derive(thiserror::Error, Debug)]
+pub enum GlossError {
+    #[error("translation failed: {0}")]
+    Translate(#[from] TranslateError),
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// A word or phrase from the source text and its meaning.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Gloss {
+    pub source: String,
+    pub gloss: String,
+}
+
+pub trait Glosser: Send + Sync {
+    fn gloss(
+        &self,
+        text: String,
+        target: TargetLang,
+    ) -> BoxFuture<'_, Result<Vec<Gloss>, GlossError>>;
+}
+
+/// Glosses from a fixed word list, one `source<TAB>gloss` entry per line.
+/// Entries may be phrases; the longest phrase starting at a word wins. The
+/// target language is ignored, so a dictionary is built for one language pair.
+#[derive(Clone, Debug, Default)]
+pub struct Dictionary {
+    entries: HashMap<String, String>,
+    longest_phrase: usize,
+}
+
+impl Dictionary {
+    /// Reads a tab-separated dictionary; blank lines and lines starting with
+    /// `#` are skipped.
+    #[cfg(feature = "native")]
+    pub async fn load(path: &Path) -> Result<Self, GlossError> {
+        Ok(Self::from_tsv(&tokio::fs::read_to_string(path).await?))
+    }
+
+    pub fn from_tsv(tsv: &str) -> Self {
+        let mut dictionary = Self::default();
+        for line in tsv.lines() {
+            if line.trim_start().starts_with('#') {
+                continue;
+            }
+            let Some((source, gloss)) = line.split_once('\t') else {
+                continue;
+            };
+            let key = words(source).join(" ").to_lowercase();
+            if key.is_empty() || gloss.trim().is_empty() {
+                continue;
+            }
+            dictionary.longest_phrase = dictionary.longest_phrase.max(words(source).len());
+            dictionary.entries.insert(key, gloss.trim().to_owned());
+        }
+        dictionary
+    }
+
+    pub fn lookup(&self, text: &str) -> Vec<Gloss> {
+        let words = words(text);
+        let mut glosses = Vec::new();
+        let mut i = 0;
+        while i < words.len() {
+            let longest = self.longest_phrase.min(words.len() - i);
+            let found = (1..=longest).rev().find_map(|n| {
+                let phrase = words[i..i + n].join(" ");
+                let gloss = self.entries.get(&phrase.to_lowercase())?;
+                Some((n, phrase, gloss))
+            });
+            match found {
+                Some((n, source, gloss)) => {
+                    glosses.push(Gloss {
+                        source,
+                        gloss: gloss.clone(),
+                    });
+                    i += n;
+                }
+                None => i += 1,
+            }
+        }
+        glosses
+    }
+}
+
+impl Glosser for Dictionary {
+    fn gloss(
+        &self,
+        text: String,
+        _target: TargetLang,
+    ) -> BoxFuture<'_, Result<Vec<Gloss>, GlossError>> {
+        let glosses = self.lookup(&text);
+        async move { Ok(glosses) }.boxed()
+    }
+}
+
+/// Glosses each distinct word by translating it on its own. Words the
+/// translator leaves unchanged (names, cognates) are left out.
+#[derive(Clone)]
+pub struct TranslatorGlosser<T> {
+    translator: T,
+}
+
+impl<T: Translator> TranslatorGlosser<T> {
+    pub fn new(translator: T) -> Self {
+        Self { translator }
+    }
+}
+
+impl<T: Translator> Glosser for TranslatorGlosser<T> {
+    fn gloss(
+        &self,
+        text: String,
+        target: TargetLang,
+    ) -> BoxFuture<'_, Result<Vec<Gloss>, GlossError>> {
+        async move {
+            let mut seen = HashSet::new();
+            let distinct: Vec<String> = words(&text)
+                .into_iter()
+                .filter(|w| seen.insert(w.to_lowercase()))
+                .take(MAX_TRANSLATED_WORDS)
+                .map(str::to_owned)
+                .collect();
+            let results = futures::future::join_all(
+                distinct
+                    .iter()
+                    .map(|word| self.translator.translate(word.clone(), target.clone())),
+            )
+            .await;
+
+            let mut glosses = Vec::new();
+            let mut first_error = None;
+            for (source, result) in distinct.into_iter().zip(results) {
+                match result {
+                    Ok(t) if t.text.trim().to_lowercase() != source.to_lowercase() => {
+                        glosses.push(Gloss {
+                            source,
+                            gloss: t.text.trim().to_owned(),
+                        });
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        first_error.get_or_insert(e);
+                    }
+                }
+            }
+            match first_error {
+                Some(e) if glosses.is_empty() => Err(e.into()),
+                _ => Ok(glosses),
+            }
+        }
+        .boxed()
+    }
+}
+
+/// Glosses the source text of every translation in `events` and publishes
+/// the glosses on `status`, until the event stream closes.
+pub async fn run<G: Glosser>(
+    glosser: G,
+    status: PipelineStatus,
+    mut events: broadcast::Receiver<PipelineEvent>,
+) {
+    loop {
+        match events.recv().await {
+            Ok(PipelineEvent::Translation {
+                source_text,
+                target_lang,
+                ..
+            }) => {
+                let Ok(target) = TargetLang::new(target_lang.clone()) else {
+                    continue;
+                };
+                match glosser.gloss(source_text.clone(), target).await {
+                    Ok(glosses) if !glosses.is_empty() => status.publish(PipelineEvent::Gloss {
+                        source_text,
+                        target_lang,
+                        glosses,
+                    }),
+                    Ok(_) => {}
+                    Err(e) => tracing::warn!(error = %e, "gloss failed"),
+                }
+            }
+            Ok(_) => {}
+            Err(broadcast::error::RecvError::Lagged(n)) => {
+                tracing::warn!(skipped = n, "glosser fell behind; skipped events");
+            }
+            Err(broadcast::error::RecvError::Closed) => return,
+        }
+    }
+}
+
+/// Words of `text`, keeping inner apostrophes and hyphens ("don't", "e-mail").
+fn words(text: &str) -> Vec<&str> {
+    text.split(|c: char| !(c.is_alphanumeric() || matches!(c, '\'' | '’' | '-')))
+        .map(|w| w.trim_matches(['\'', '’', '-']))
+        .filter(|w| w.chars().any(char::is_alphabetic))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::translate::Translation;
+
+    #[test]
+    fn dictionary_prefers_the_longest_phrase() {
+        let dictionary = Dictionary::from_tsv(
+            "# en -> pt\nlet's go\tvamos lá\ngo\tir\nboss\tchefe\nnot-a-tab line\n",
+        );
+        assert_eq!(
+            dictionary.lookup("Let's go, BOSS! Go!"),
+            [
+                Gloss {
+                    source: "Let's go".into(),
+                    gloss: "vamos lá".into()
+                },
+                Gloss {
+                    source: "BOSS".into(),
+                    gloss: "chefe".into()
+                },
+                Gloss {
+                    source: "Go".into(),
+                    gloss: "ir".into()
+                },
+            ]
+        );
+    }
+
+    #[derive(Clone)]
+    struct Upper;
+
+    impl Translator for Upper {
+        fn translate(
+            &self,
+            text: String,
+            _target: TargetLang,
+        ) -> BoxFuture<'_, Result<Translation, TranslateError>> {
+            async move {
+                if text == "fail" {
+                    return Err(TranslateError::Api("nope".into()));
+                }
+                let text = if text == "Twitch" {
+                    text
+                } else {
+                    format!("{}!", text.to_uppercase())
+                };
+                Ok(Translation {
+                    text,
+                    detected_source_lang: None,
+                })
+            }
+            .boxed()
+        }
+    }
+
+    #[tokio::test]
+    async fn translator_glosses_distinct_changed_words() {
+        let glosser = TranslatorGlosser::new(Upper);
+        let target = TargetLang::new("pt-BR").unwrap();
+        let glosses = glosser
+            .gloss("hi Twitch, hi fail".to_owned(), target.clone())
+            .await
+            .unwrap();
+        let pairs: Vec<(&str, &str)> = glosses
+            .iter()
+            .map(|g| (g.source.as_str(), g.gloss.as_str()))
+            .collect();
+        assert_eq!(pairs, [("hi", "HI!")]);
+
+        let err = glosser.gloss("fail".to_owned(), target).await;
+        assert!(matches!(err, Err(GlossError::Translate(_))));
+    }
+
+    #[tokio::test]
+    async fn run_publishes_glosses_for_translations() {
+        let status = PipelineStatus::new();
+        let mut published = status.subscribe();
+        let task = tokio::spawn(run(
+            Dictionary::from_tsv("boss\tchefe\n"),
+            status.clone(),
+            status.subscribe(),
+        ));
+        status.publish(PipelineEvent::Translation {
+            source_text: "nice boss".into(),
+            text: "chefe legal".into(),
+            target_lang: "pt-BR".into(),
+        });
+
+        let _translation = published.recv().await.unwrap();
+        let gloss = published.recv().await.unwrap();
+        task.abort();
+        assert!(matches!(
+            gloss,
+            PipelineEvent::Gloss { source_text, glosses, .. }
+                if source_text == "nice boss" && glosses[0].gloss == "chefe"
+        ));
+        let captions = status.captions(None);
+        assert_eq!(captions[0].glosses[0].source, "boss");
+    }
+}
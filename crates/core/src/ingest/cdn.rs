@@ -0,0 +1,140 @@
+//! Failing over between CDN hosts
+//!
+//! Twitch serves a stream's segments from several edge hosts, and a media
+//! playlist names more than one of them across its segment and
+//! `#EXT-X-TWITCH-PREFETCH` lines. When one edge keeps failing while the
+//! others are fine, waiting for it costs the segment its playout time.
+//! [`CdnFailover`] remembers the hosts seen in the playlist and, once a host
+//! has failed [`FAILURES_BEFORE_FAILOVER`] times in a row, sends requests for
+//! its paths to another one.
+
+use std::collections::{HashMap, VecDeque};
+use url::Url;
+
+/// Failures in a row after which a host's segments are fetched elsewhere.
+pub(crate) const FAILURES_BEFORE_FAILOVER: u32 = 2;
+
+/// Hosts remembered, the oldest forgotten first.
+const MAX_HOSTS: usize = 8;
+
+/// Tag Twitch lists upcoming segments with, ahead of their `#EXTINF`.
+const TWITCH_PREFETCH_TAG: &str = "#EXT-X-TWITCH-PREFETCH:";
+
+/// Segment hosts seen in the playlist, and the failures in a row of each
+#[derive(Debug, Default)]
+pub(crate) struct CdnFailover {
+    hosts: VecDeque<String>,
+    failures: HashMap<String, u32>,
+}
+
+impl CdnFailover {
+    /// Remembers the hosts segments of `playlist`, fetched from `base`, are
+    /// served from.
+    pub(crate) fn learn(&mut self, playlist: &str, base: &Url) {
+        for line in playlist.lines().map(str::trim) {
+            let uri = match line.strip_prefix(TWITCH_PREFETCH_TAG) {
+                Some(uri) => uri,
+                None if !line.is_empty() && !line.starts_with('#') => line,
+                None => continue,
+            };
+            let Some(host) = base
+                .join(uri)
+                .ok()
+                .and_then(|u| u.host_str().map(str::to_owned))
+            else {
+                continue;
+            };
+            if !self.hosts.contains(&host) {
+                if self.hosts.len() == MAX_HOSTS {
+                    if let Some(oldest) = self.hosts.pop_front() {
+                        self.failures.remove(&oldest);
+                    }
+                }
+                self.hosts.push_back(host);
+            }
+        }
+    }
+
+    /// `url`, moved to the healthiest other host once its own keeps failing.
+    pub(crate) fn route(&self, url: &Url) -> Url {
+        let Some(host) = url.host_str() else {
+            return url.clone();
+        };
+        if self.failures_of(host) < FAILURES_BEFORE_FAILOVER {
+            return url.clone();
+        }
+        let alternate = self
+            .hosts
+            .iter()
+            .filter(|h| *h != host && self.failures_of(h) < FAILURES_BEFORE_FAILOVER)
+            .min_by_key(|h| self.failures_of(h));
+        let mut routed = url.clone();
+        match alternate {
+            Some(alternate) if routed.set_host(Some(alternate)).is_ok() => routed,
+            _ => url.clone(),
+        }
+    }
+
+    /// Counts a request to `host` that succeeded, or failed in a way another
+    /// host might not.
+    pub(crate) fn record(&mut self, host: &str, ok: bool) {
+        if ok {
+            self.failures.remove(host);
+            return;
+        }
+        let failures = self.failures.entry(host.to_owned()).or_default();
+        *failures += 1;
+        if *failures == FAILURES_BEFORE_FAILOVER {
+            tracing::warn!(
+                host,
+                "CDN host keeps failing; fetching its segments from another host"
+            );
+        }
+    }
+
+    fn failures_of(&self, host: &str) -> u32 {
+        self.failures.get(host).copied().unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fails_over_to_another_playlist_host_and_back() {
+        let base = Url::parse("https://weaver.example/v1/playlist/abc.m3u8").unwrap();
+        let mut cdn = CdnFailover::default();
+        cdn.learn(
+            "#EXTM3U\n#EXT-X-TARGETDURATION:2\n#EXTINF:2.0,\n\
+             https://edge-a.example/v1/segment/1.ts\n\
+             #EXT-X-TWITCH-PREFETCH:https://edge-b.example/v1/segment/2.ts\n",
+            &base,
+        );
+        let url = Url::parse("https://edge-a.example/v1/segment/1.ts").unwrap();
+
+        cdn.record("edge-a.example", false);
+        assert_eq!(cdn.route(&url), url);
+        cdn.record("edge-a.example", false);
+        assert_eq!(
+            cdn.route(&url).as_str(),
+            "https://edge-b.example/v1/segment/1.ts"
+        );
+
+        // With every host failing, the original one is kept.
+        cdn.record("edge-b.example", false);
+        cdn.record("edge-b.example", false);
+        assert_eq!(cdn.route(&url), url);
+
+        cdn.record("edge-a.example", true);
+        assert_eq!(cdn.route(&url), url);
+    }
+
+    #[test]
+    fn relative_segments_are_served_by_the_playlist_host() {
+        let base = Url::parse("https://cdn.example/live/index.m3u8").unwrap();
+        let mut cdn = CdnFailover::default();
+        cdn.learn("#EXTM3U\n#EXTINF:2.0,\ns1.ts\n#EXTINF:2.0,\ns2.ts\n", &base);
+        assert_eq!(cdn.hosts, ["cdn.example"]);
+    }
+}
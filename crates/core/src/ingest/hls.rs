@@ -0,0 +1,1179 @@
+//! Generic HLS ingest
+//!
+//! [`HlsIngestor`] follows any m3u8 stream: it resolves a master playlist to
+//! one variant, polls the media playlist and fetches each new segment. Where
+//! the playlist URL comes from is a [`PlaylistSource`]; a plain [`Url`] works
+//! for IPTV, news or conference streams, while the Twitch ingestor plugs in a
+//! source that requests a playback token first and renews it before it expires.
+
+use crate::config::{VariantQuality, DEFAULT_SEGMENT_PREFETCH};
+use crate::ingest::cdn::CdnFailover;
+use crate::ingest::{IngestError, IngestItem, IngestRecorder, Ingestor};
+use crate::session::SessionStore;
+use crate::status::{lock, PipelineEvent, PipelineStatus};
+use crate::util::{
+    is_http_retryable, retry_with_backoff, HttpError, HttpRequest, ReqwestTransport, RetryConfig,
+    SharedTransport,
+};
+use bytes::Bytes;
+use futures::future::BoxFuture;
+use futures::{FutureExt, StreamExt};
+use m3u8_rs::{Playlist, VariantStream};
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use tokio::sync::mpsc::Sender;
+use tokio::time::Instant;
+use url::Url;
+
+/// Lower bound between credential refreshes, so a URL that is already (nearly)
+/// expired does not cause a refresh on every playlist poll.
+const MIN_REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Lower bound on how long a segment may be retried, for playlists that
+/// advertise tiny or zero durations.
+const MIN_SEGMENT_DEADLINE: Duration = Duration::from_millis(500);
+
+/// A fetch taking this share of the segment's duration is not keeping up.
+const SLOW_FETCH_RATIO: f64 = 0.8;
+
+/// Recent segment fetches considered when deciding to switch variants.
+const THROUGHPUT_WINDOW: usize = 3;
+
+/// Slow or dropped fetches within the window that trigger a switch.
+const SLOW_FETCHES_TO_SWITCH: usize = 2;
+
+/// A playlist URL, and when the credential embedded in it stops working.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ResolvedPlaylist {
+    pub url: Url,
+    pub expires_at: Option<SystemTime>,
+}
+
+/// Where an [`HlsIngestor`] gets its (master or media) playlist URL.
+pub trait PlaylistSource: Send + Sync {
+    /// Resolves the URL to follow. Called again shortly before `expires_at`
+    /// and when the media playlist answers 401/403.
+    fn resolve(&self) -> BoxFuture<'_, Result<ResolvedPlaylist, IngestError>>;
+
+    /// Error to report when the playlist itself answers 404.
+    fn not_found(&self) -> IngestError {
+        IngestError::HttpStatus(404, "stream playlist not found".to_string())
+    }
+}
+
+/// A fixed URL with no expiring credential.
+impl PlaylistSource for Url {
+    fn resolve(&self) -> BoxFuture<'_, Result<ResolvedPlaylist, IngestError>> {
+        let resolved = ResolvedPlaylist {
+            url: self.clone(),
+            expires_at: None,
+        };
+        async move { Ok(resolved) }.boxed()
+    }
+}
+
+impl VariantQuality {
+    /// Picks a variant, falling back to the lowest bandwidth one when nothing
+    /// satisfies a height or bandwidth limit.
+    pub fn select<'a>(&self, variants: &'a [VariantStream]) -> Option<&'a VariantStream> {
+        let variants = || variants.iter().filter(|v| !v.is_i_frame);
+        let worst = || variants().min_by_key(|v| v.bandwidth);
+        match *self {
+            Self::AudioOnly => variants()
+                .find(|v| !has_video(v) && carries_audio(v))
+                .or_else(|| variants().find(|v| carries_audio(v)))
+                .or_else(|| variants().next()),
+            Self::Best => variants().max_by_key(|v| v.bandwidth),
+            Self::Worst => worst(),
+            Self::MaxHeight(max) => variants()
+                .filter(|v| v.resolution.is_some_and(|r| r.height <= max))
+                .max_by_key(|v| (v.resolution.map(|r| r.height), v.bandwidth))
+                .or_else(worst),
+            Self::MaxBandwidth(max) => variants()
+                .filter(|v| v.bandwidth <= max)
+                .max_by_key(|v| v.bandwidth)
+                .or_else(worst),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct HlsOptions {
+    /// Which variant of a master playlist to follow.
+    pub quality: VariantQuality,
+    /// How long before the source's credential expires to resolve it again.
+    pub refresh_margin_secs: u64,
+    /// Backoff for segment fetches that fail transiently. Retries also stop
+    /// once the segment's playout time has passed.
+    pub segment_retry: RetryConfig,
+    /// Fixed delay between media playlist polls; `None` waits the playlist's
+    /// target duration.
+    pub poll_interval: Option<Duration>,
+    /// Switch to a lower bandwidth variant when segment downloads cannot keep
+    /// up with playout.
+    pub adaptive: bool,
+    /// Most segments of a playlist fetched at once; 1 fetches one after the
+    /// other.
+    pub prefetch: usize,
+}
+
+impl Default for HlsOptions {
+    fn default() -> Self {
+        Self {
+            quality: VariantQuality::AudioOnly,
+            refresh_margin_secs: 300,
+            segment_retry: RetryConfig {
+                max_attempts: 4,
+                initial_delay: Duration::from_millis(250),
+                backoff_multiplier: 2.0,
+                max_delay: Duration::from_secs(2),
+            },
+            poll_interval: None,
+            adaptive: true,
+            prefetch: DEFAULT_SEGMENT_PREFETCH,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct HlsIngestor {
+    source: Arc<dyn PlaylistSource>,
+    options: HlsOptions,
+    transport: SharedTransport,
+    recorder: Option<Arc<IngestRecorder>>,
+    status: Option<PipelineStatus>,
+    session: Option<Arc<SessionStore>>,
+}
+
+impl HlsIngestor {
+    pub fn new(source: Arc<dyn PlaylistSource>) -> Self {
+        Self {
+            source,
+            options: HlsOptions::default(),
+            transport: ReqwestTransport::default().shared(),
+            recorder: None,
+            status: None,
+            session: None,
+        }
+    }
+
+    /// Follows the m3u8 playlist at `url`.
+    pub fn from_url(url: &str) -> Result<Self, IngestError> {
+        Ok(Self::new(Arc::new(Url::parse(url)?)))
+    }
+
+    pub fn with_options(mut self, options: HlsOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Sends requests through `transport` instead of the network, e.g. a
+    /// [`crate::util::MockTransport`] in tests.
+    pub fn with_transport(mut self, transport: SharedTransport) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    /// Writes every fetched playlist and segment to `recorder` as well.
+    pub fn with_recorder(mut self, recorder: Arc<IngestRecorder>) -> Self {
+        self.recorder = Some(recorder);
+        self
+    }
+
+    /// Reports recovered and dropped segments to `status`.
+    pub fn with_status(mut self, status: PipelineStatus) -> Self {
+        self.status = Some(status);
+        self
+    }
+
+    /// Starts after the last segment recorded in `session` and records each
+    /// segment handled from then on.
+    pub fn with_session(mut self, session: Arc<SessionStore>) -> Self {
+        self.session = Some(session);
+        self
+    }
+
+    async fn save_progress(&self, next_media_sequence: u64) {
+        if let Some(session) = &self.session {
+            if let Err(e) = session.set_next_media_sequence(next_media_sequence).await {
+                tracing::warn!(error = %e, "failed to save session state");
+            }
+        }
+    }
+
+    async fn fetch_playlist(&self, url: &Url) -> Result<String, IngestError> {
+        let response = self.transport.send(HttpRequest::get(url.as_str())).await?;
+        if !response.is_success() {
+            return Err(IngestError::HttpStatus(response.status, response.text()));
+        }
+
+        let playlist = response.text();
+        if let Some(recorder) = &self.recorder {
+            if let Err(e) = recorder.record_playlist(url, &playlist).await {
+                tracing::warn!(error = %e, "failed to record playlist");
+            }
+        }
+        Ok(playlist)
+    }
+
+    async fn fetch_media_segment(&self, url: &Url) -> Result<Bytes, IngestError> {
+        let response = self.transport.send(HttpRequest::get(url.as_str())).await?;
+        if !response.is_success() {
+            return Err(IngestError::HttpStatus(response.status, response.text()));
+        }
+        Ok(response.body)
+    }
+
+    /// Fetches a segment, retrying transient failures with backoff until
+    /// `deadline`, after which the segment would be too late to play anyway.
+    /// Attempts go to another of the playlist's hosts while `cdn` has the
+    /// segment's own host failing.
+    async fn fetch_segment_with_retry(
+        &self,
+        url: &Url,
+        deadline: Instant,
+        cdn: &Mutex<CdnFailover>,
+    ) -> Result<Bytes, IngestError> {
+        let mut attempts = 0u32;
+        let fetch = retry_with_backoff(
+            &self.options.segment_retry,
+            || {
+                attempts += 1;
+                let target = lock(cdn).route(url);
+                if target != *url {
+                    tracing::debug!("Fetching segment {} from {}", url, target);
+                }
+                async move {
+                    let result = self.fetch_media_segment(&target).await;
+                    if let Some(host) = target.host_str() {
+                        match &result {
+                            Ok(_) => lock(cdn).record(host, true),
+                            Err(e) if is_segment_error_transient(e) => {
+                                lock(cdn).record(host, false)
+                            }
+                            Err(_) => {}
+                        }
+                    }
+                    result
+                }
+            },
+            |e| Instant::now() < deadline && is_segment_error_transient(e),
+        );
+        let result = match tokio::time::timeout_at(deadline, fetch).await {
+            Ok(result) => result,
+            Err(_) => Err(IngestError::SegmentDeadline(url.to_string())),
+        };
+        if result.is_ok() && attempts > 1 {
+            tracing::info!(attempts, "Recovered segment {}", url);
+            if let Some(status) = &self.status {
+                status.record_segment_recovered();
+            }
+        }
+        result
+    }
+
+    /// Resolves the source and, for a master playlist, picks the variant to
+    /// follow. Called again whenever the source's credential needs renewing.
+    pub(crate) async fn resolve_media_playlist(
+        &self,
+    ) -> Result<MediaPlaylistLocation, IngestError> {
+        self.resolve_capped(None).await
+    }
+
+    /// Like [`Self::resolve_media_playlist`], only considering variants of at
+    /// most `bandwidth_cap` once adaptive switching has stepped down.
+    async fn resolve_capped(
+        &self,
+        bandwidth_cap: Option<u64>,
+    ) -> Result<MediaPlaylistLocation, IngestError> {
+        let resolved = self.source.resolve().await?;
+        tracing::info!("Using stream URL: {}", resolved.url);
+
+        let content = match self.fetch_playlist(&resolved.url).await {
+            Err(IngestError::HttpStatus(404, _)) => return Err(self.source.not_found()),
+            other => other?,
+        };
+        let (_, parsed) = m3u8_rs::parse_playlist(content.as_bytes()).map_err(|e| {
+            tracing::error!("HLS initial parse error: {:?}", e);
+            tracing::debug!("Initial playlist content: {}", content);
+            IngestError::HlsParse
+        })?;
+
+        let (url, bandwidth, lower, fetched) = match parsed {
+            Playlist::MasterPlaylist(master) => {
+                tracing::info!(
+                    "Received master playlist with {} variants",
+                    master.variants.len()
+                );
+                let allowed: Vec<VariantStream> = match bandwidth_cap {
+                    Some(cap) => master
+                        .variants
+                        .iter()
+                        .filter(|v| v.bandwidth <= cap)
+                        .cloned()
+                        .collect(),
+                    None => master.variants.clone(),
+                };
+                let variant = self
+                    .options
+                    .quality
+                    .select(&allowed)
+                    .or_else(|| VariantQuality::Worst.select(&master.variants))
+                    .ok_or_else(|| {
+                        tracing::error!("No variants found in master playlist");
+                        IngestError::HlsParse
+                    })?;
+                tracing::info!(
+                    "Selected variant for quality {}: {} (bandwidth: {}, codecs: {:?})",
+                    self.options.quality,
+                    variant.uri,
+                    variant.bandwidth,
+                    variant.codecs
+                );
+                let mut lower = Vec::new();
+                for v in master.variants.iter().filter(|v| {
+                    !v.is_i_frame && v.bandwidth < variant.bandwidth && carries_audio(v)
+                }) {
+                    lower.push(VariantChoice {
+                        url: resolved.url.join(&v.uri)?,
+                        bandwidth: v.bandwidth,
+                    });
+                }
+                lower.sort_by_key(|v| std::cmp::Reverse(v.bandwidth));
+                (
+                    resolved.url.join(&variant.uri)?,
+                    Some(variant.bandwidth),
+                    lower,
+                    None,
+                )
+            }
+            Playlist::MediaPlaylist(_) => (resolved.url, None, Vec::new(), Some(content)),
+        };
+
+        let margin = Duration::from_secs(self.options.refresh_margin_secs);
+        Ok(MediaPlaylistLocation {
+            url,
+            bandwidth,
+            bandwidth_cap,
+            lower,
+            fetched,
+            refresh_at: resolved
+                .expires_at
+                .map(|expires| Instant::now() + refresh_delay(expires, SystemTime::now(), margin)),
+        })
+    }
+
+    /// Resolves the source again. On failure the current location is kept and
+    /// the refresh retried a little later.
+    async fn refresh_location(&self, current: MediaPlaylistLocation) -> MediaPlaylistLocation {
+        match self.resolve_capped(current.bandwidth_cap).await {
+            Ok(location) => {
+                tracing::info!(
+                    "Refreshed playlist credential; now following {}",
+                    location.url
+                );
+                location
+            }
+            Err(e) => {
+                tracing::warn!(error = %e, "playlist credential refresh failed; retrying later");
+                MediaPlaylistLocation {
+                    refresh_at: Some(Instant::now() + MIN_REFRESH_INTERVAL),
+                    ..current
+                }
+            }
+        }
+    }
+
+    /// Polls the media playlist at `location` and sends every new segment to
+    /// `tx` until the receiver is dropped, a request fails or the playlist
+    /// ends (`#EXT-X-ENDLIST`, as on a VOD or a finished broadcast).
+    pub(crate) async fn process_playlist(
+        &self,
+        mut location: MediaPlaylistLocation,
+        tx: Sender<IngestItem>,
+    ) -> Result<(), IngestError> {
+        let mut sequence = 0u64;
+        let mut tracker = match &self.session {
+            Some(session) => SequenceTracker {
+                next: session.state().await.next_media_sequence,
+            },
+            None => SequenceTracker::default(),
+        };
+        // Audio lost to segments dropped since the last one sent.
+        let mut dropped_gap = Duration::ZERO;
+        let mut target_duration;
+        // Set after renewing the credential because of a 401/403, so a second
+        // denial fails instead of looping.
+        let mut refreshed_after_denial = false;
+        let mut throughput = ThroughputMonitor::default();
+        let cdn = Mutex::new(CdnFailover::default());
+        // Set after stepping down to another variant, whose segments may not
+        // continue seamlessly from the previous one's.
+        let mut switched = false;
+
+        'poll: loop {
+            if location.refresh_at.is_some_and(|at| Instant::now() >= at) {
+                tracing::info!("Playlist credential about to expire; refreshing");
+                location = self.refresh_location(location).await;
+            }
+
+            // The first poll reuses the playlist fetched while resolving.
+            let fetched = match location.fetched.take() {
+                Some(content) => Ok(content),
+                None => self.fetch_playlist(&location.url).await,
+            };
+            let playlist_content = match fetched {
+                Ok(content) => {
+                    refreshed_after_denial = false;
+                    content
+                }
+                Err(IngestError::HttpStatus(status @ (401 | 403), _))
+                    if location.refresh_at.is_some() && !refreshed_after_denial =>
+                {
+                    tracing::warn!(
+                        "Media playlist returned HTTP {}; renewing playlist credential",
+                        status
+                    );
+                    location = self.resolve_capped(location.bandwidth_cap).await?;
+                    refreshed_after_denial = true;
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+
+            let (_remaining, parsed) = m3u8_rs::parse_playlist(playlist_content.as_bytes())
+                .map_err(|e| {
+                    tracing::error!("HLS parse error: {:?}", e);
+                    tracing::debug!("Playlist content: {}", playlist_content);
+                    IngestError::HlsParse
+                })?;
+
+            let playlist = match parsed {
+                Playlist::MasterPlaylist(_) => {
+                    tracing::error!("Received master playlist when expecting media playlist");
+                    return Err(IngestError::ExpectedMediaPlaylist);
+                }
+                Playlist::MediaPlaylist(playlist) => playlist,
+            };
+            target_duration = Duration::from_secs(playlist.target_duration);
+            lock(&cdn).learn(&playlist_content, &location.url);
+
+            let mut restarted =
+                tracker.start_playlist(playlist.media_sequence, playlist.segments.len());
+            if restarted {
+                tracing::warn!("Media sequence went backwards; treating as a stream restart");
+            }
+
+            // Segments not sent on an earlier poll
+            let mut new_segments = Vec::new();
+            for (index, segment) in playlist.segments.iter().enumerate() {
+                let media_sequence = playlist.media_sequence + index as u64;
+                if tracker.missed_before(media_sequence).is_some() {
+                    let segment_url = location.url.join(&segment.uri)?;
+                    new_segments.push((media_sequence, segment.clone(), segment_url));
+                }
+            }
+            // Fetched up to `prefetch` at a time, so one slow request does
+            // not hold up the ones after it, and handled in playlist order.
+            let cdn = &cdn;
+            let mut fetches = futures::stream::iter(new_segments.into_iter().map(
+                |(media_sequence, segment, segment_url)| async move {
+                    let segment_duration = Duration::from_secs_f64(segment.duration as f64);
+                    tracing::debug!("Fetching segment: {}", segment_url);
+                    let started = Instant::now();
+                    let deadline = started + segment_duration.max(MIN_SEGMENT_DEADLINE);
+                    let fetched = self
+                        .fetch_segment_with_retry(&segment_url, deadline, cdn)
+                        .await;
+                    let elapsed = started.elapsed();
+                    (media_sequence, segment, segment_url, fetched, elapsed)
+                },
+            ))
+            .buffered(self.options.prefetch.max(1));
+
+            while let Some((media_sequence, segment, segment_url, fetched, elapsed)) =
+                fetches.next().await
+            {
+                let Some(missed) = tracker.missed_before(media_sequence) else {
+                    continue;
+                };
+                if missed > 0 {
+                    tracing::warn!(
+                        missed,
+                        "Segments expired from the playlist before they were fetched"
+                    );
+                }
+                let segment_duration = Duration::from_secs_f64(segment.duration as f64);
+                throughput.record(fetched.is_ok(), elapsed, segment_duration);
+                let stepped_down = self.options.adaptive
+                    && throughput.is_struggling()
+                    && self.step_down(&mut location);
+                if stepped_down {
+                    throughput = ThroughputMonitor::default();
+                    switched = true;
+                    if fetched.is_err() {
+                        // Fetch this segment again from the new variant.
+                        continue 'poll;
+                    }
+                }
+                let bytes = match fetched {
+                    Ok(bytes) => bytes,
+                    Err(e) => {
+                        tracing::warn!(error = %e, "Dropping segment {}", segment_url);
+                        if let Some(status) = &self.status {
+                            status.record_segment_dropped();
+                        }
+                        dropped_gap += segment_duration;
+                        tracker.sent(media_sequence);
+                        self.save_progress(media_sequence + 1).await;
+                        continue;
+                    }
+                };
+                tracing::debug!(
+                    "Fetched segment: {} bytes from {}",
+                    bytes.len(),
+                    segment_url
+                );
+
+                let ingest_item = IngestItem {
+                    sequence,
+                    fetched_at: SystemTime::now(),
+                    url: segment_url.clone(),
+                    approx_duration: segment_duration,
+                    bytes,
+                    discontinuity: segment.discontinuity
+                        || missed > 0
+                        || restarted
+                        || switched
+                        || !dropped_gap.is_zero(),
+                    gap_before: target_duration * missed as u32 + dropped_gap,
+                };
+                restarted = false;
+                switched = false;
+                dropped_gap = Duration::ZERO;
+
+                if let Some(recorder) = &self.recorder {
+                    if let Err(e) = recorder.record_segment(&ingest_item).await {
+                        tracing::warn!(error = %e, "failed to record segment");
+                    }
+                }
+
+                if tx.send(ingest_item).await.is_err() {
+                    // Receiver gone: the pipeline is shutting down.
+                    return Ok(());
+                }
+
+                sequence += 1;
+                tracker.sent(media_sequence);
+                self.save_progress(media_sequence + 1).await;
+                if stepped_down {
+                    // The remaining segments are listed in the new variant's playlist.
+                    continue 'poll;
+                }
+            }
+            if playlist.end_list {
+                tracing::info!("Playlist ended");
+                return Ok(());
+            }
+
+            // Wait for the target duration before checking for new segments
+            tokio::time::sleep(self.options.poll_interval.unwrap_or(target_duration)).await;
+        }
+    }
+}
+
+impl HlsIngestor {
+    /// Switches `location` to the next lower bandwidth variant, if any.
+    fn step_down(&self, location: &mut MediaPlaylistLocation) -> bool {
+        if location.lower.is_empty() {
+            return false;
+        }
+        let next = location.lower.remove(0);
+        tracing::warn!(
+            from = ?location.bandwidth,
+            to = next.bandwidth,
+            "Segment downloads are falling behind; switching to a lower bandwidth variant"
+        );
+        if let Some(status) = &self.status {
+            status.publish(PipelineEvent::VariantSwitched {
+                from_bandwidth: location.bandwidth,
+                to_bandwidth: next.bandwidth,
+            });
+        }
+        location.url = next.url;
+        location.bandwidth = Some(next.bandwidth);
+        location.bandwidth_cap = Some(next.bandwidth);
+        true
+    }
+}
+
+impl Ingestor for HlsIngestor {
+    fn start(
+        &self,
+        tx: Sender<IngestItem>,
+    ) -> Pin<Box<dyn Future<Output = Result<(), IngestError>> + Send + 'static>> {
+        let this = self.clone();
+        Box::pin(async move {
+            tracing::info!("Starting HLS ingestor (quality: {})", this.options.quality);
+            let location = this.resolve_media_playlist().await?;
+            this.process_playlist(location, tx).await
+        })
+    }
+}
+
+/// Media playlist being followed, and when to renew the credential it was resolved with.
+#[derive(Clone, Debug)]
+pub(crate) struct MediaPlaylistLocation {
+    url: Url,
+    /// Advertised bandwidth of the followed variant, if it came from a master playlist.
+    bandwidth: Option<u64>,
+    /// Upper bound on the variant picked when the source is resolved again.
+    bandwidth_cap: Option<u64>,
+    /// Variants to step down to, highest bandwidth first.
+    lower: Vec<VariantChoice>,
+    /// The media playlist itself, when resolving already fetched it.
+    fetched: Option<String>,
+    refresh_at: Option<Instant>,
+}
+
+#[derive(Clone, Debug)]
+struct VariantChoice {
+    url: Url,
+    bandwidth: u64,
+}
+
+/// Remembers whether recent segment fetches kept up with playout.
+#[derive(Debug, Default)]
+struct ThroughputMonitor {
+    slow: VecDeque<bool>,
+}
+
+impl ThroughputMonitor {
+    /// Records a fetch that took `elapsed` for `duration` of audio; a failed
+    /// fetch counts as slow.
+    fn record(&mut self, ok: bool, elapsed: Duration, duration: Duration) {
+        let slow = !ok || elapsed.as_secs_f64() >= duration.as_secs_f64() * SLOW_FETCH_RATIO;
+        if self.slow.len() == THROUGHPUT_WINDOW {
+            self.slow.pop_front();
+        }
+        self.slow.push_back(slow);
+    }
+
+    fn is_struggling(&self) -> bool {
+        self.slow.iter().filter(|slow| **slow).count() >= SLOW_FETCHES_TO_SWITCH
+    }
+}
+
+/// Timeouts, 5xx/429, and 404s from a CDN edge that has not caught up yet.
+fn is_segment_error_transient(error: &IngestError) -> bool {
+    match error {
+        IngestError::HttpStatus(status, _) => *status == 404 || is_http_retryable(*status),
+        IngestError::Http(HttpError::Transport(_)) => true,
+        _ => false,
+    }
+}
+
+/// Follows `EXT-X-MEDIA-SEQUENCE` numbers across playlist polls, so repeated
+/// segments are skipped and segments that expired unseen are noticed.
+#[derive(Debug, Default)]
+struct SequenceTracker {
+    next: Option<u64>,
+}
+
+impl SequenceTracker {
+    /// Starts a poll of a playlist whose first segment is `first`. Returns
+    /// true if the whole playlist lies before what was already sent, i.e. the
+    /// encoder restarted its numbering; tracking then starts over.
+    fn start_playlist(&mut self, first: u64, len: usize) -> bool {
+        match self.next {
+            Some(next) if first + (len as u64) < next => {
+                self.next = None;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// `None` if `media_sequence` was already sent, otherwise how many
+    /// segments were skipped since the last one sent.
+    fn missed_before(&self, media_sequence: u64) -> Option<u64> {
+        match self.next {
+            None => Some(0),
+            Some(next) if media_sequence < next => None,
+            Some(next) => Some(media_sequence - next),
+        }
+    }
+
+    fn sent(&mut self, media_sequence: u64) {
+        self.next = Some(media_sequence + 1);
+    }
+}
+
+fn carries_audio(variant: &VariantStream) -> bool {
+    variant.audio.is_some()
+        || variant
+            .codecs
+            .as_ref()
+            .is_some_and(|c| c.contains("mp4a") || c.contains("opus"))
+}
+
+/// Twitch marks its audio-only rendition with `VIDEO="audio_only"`; elsewhere
+/// a resolution or a video codec gives it away.
+fn has_video(variant: &VariantStream) -> bool {
+    if variant.video.as_deref() == Some("audio_only") {
+        return false;
+    }
+    variant.resolution.is_some()
+        || variant.codecs.as_ref().is_some_and(|c| {
+            ["avc1", "avc3", "hvc1", "hev1", "av01", "vp09"]
+                .iter()
+                .any(|codec| c.contains(codec))
+        })
+}
+
+fn refresh_delay(expires_at: SystemTime, now: SystemTime, margin: Duration) -> Duration {
+    expires_at
+        .duration_since(now)
+        .unwrap_or_default()
+        .saturating_sub(margin)
+        .max(MIN_REFRESH_INTERVAL)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::{HttpMethod, MockTransport};
+    use std::time::UNIX_EPOCH;
+
+    #[test]
+    fn refresh_delay_subtracts_margin_but_never_spins() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_000);
+        let margin = Duration::from_secs(300);
+        let in_an_hour = now + Duration::from_secs(3_600);
+        assert_eq!(
+            refresh_delay(in_an_hour, now, margin),
+            Duration::from_secs(3_300)
+        );
+        let expired = now - Duration::from_secs(10);
+        assert_eq!(refresh_delay(expired, now, margin), MIN_REFRESH_INTERVAL);
+    }
+
+    #[test]
+    fn tracker_skips_repeats_and_counts_expired_segments() {
+        let mut tracker = SequenceTracker::default();
+        assert!(!tracker.start_playlist(10, 3));
+        for seq in 10..13 {
+            assert_eq!(tracker.missed_before(seq), Some(0));
+            tracker.sent(seq);
+        }
+
+        // Next poll overlaps the previous one
+        assert!(!tracker.start_playlist(11, 3));
+        assert_eq!(tracker.missed_before(12), None);
+        assert_eq!(tracker.missed_before(13), Some(0));
+        tracker.sent(13);
+
+        // Polled too late: 14 and 15 already rolled off
+        assert!(!tracker.start_playlist(16, 3));
+        assert_eq!(tracker.missed_before(16), Some(2));
+        tracker.sent(16);
+
+        // Numbering restarted
+        assert!(tracker.start_playlist(0, 3));
+        assert_eq!(tracker.missed_before(0), Some(0));
+    }
+
+    #[tokio::test]
+    async fn flags_discontinuities_and_expired_segments() {
+        let mock = MockTransport::new()
+            // Once for resolving the URL, once for the first poll
+            .on(
+                HttpMethod::Get,
+                "/live.m3u8",
+                200,
+                "#EXTM3U\n#EXT-X-TARGETDURATION:1\n#EXT-X-MEDIA-SEQUENCE:1\n\
+                 #EXTINF:2.0,\ns1.ts\n#EXT-X-DISCONTINUITY\n#EXTINF:2.0,\ns2.ts\n",
+            )
+            .on(
+                HttpMethod::Get,
+                "/live.m3u8",
+                200,
+                "#EXTM3U\n#EXT-X-TARGETDURATION:1\n#EXT-X-MEDIA-SEQUENCE:1\n\
+                 #EXTINF:2.0,\ns1.ts\n#EXT-X-DISCONTINUITY\n#EXTINF:2.0,\ns2.ts\n",
+            )
+            .on(
+                HttpMethod::Get,
+                "/live.m3u8",
+                200,
+                "#EXTM3U\n#EXT-X-TARGETDURATION:1\n#EXT-X-MEDIA-SEQUENCE:5\n\
+                 #EXTINF:2.0,\ns5.ts\n",
+            )
+            .on(HttpMethod::Get, ".ts", 200, "ts");
+        let ingestor = HlsIngestor::from_url("https://x.example/live.m3u8")
+            .unwrap()
+            .with_transport(mock.shared());
+
+        let (tx, mut rx) = tokio::sync::mpsc::channel(4);
+        let task = tokio::spawn(async move { ingestor.start(tx).await });
+        let mut items = Vec::new();
+        // The second poll follows after the 1 s target duration.
+        for _ in 0..3 {
+            let item = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+                .await
+                .unwrap()
+                .unwrap();
+            items.push(item);
+        }
+        task.abort();
+
+        let flags: Vec<_> = items
+            .iter()
+            .map(|i| (i.url.path().to_owned(), i.discontinuity, i.gap_before))
+            .collect();
+        assert_eq!(
+            flags,
+            [
+                ("/s1.ts".to_owned(), false, Duration::ZERO),
+                ("/s2.ts".to_owned(), true, Duration::ZERO),
+                ("/s5.ts".to_owned(), true, Duration::from_secs(2)),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn resumes_after_the_last_saved_segment() {
+        let dir = std::env::temp_dir().join(format!("hls-session-{}", std::process::id()));
+        let input = crate::config::InputSource::Url("https://x.example/vod.m3u8".to_owned());
+        let session = Arc::new(SessionStore::open(&dir, &input).await.unwrap());
+        session.set_next_media_sequence(3).await.unwrap();
+        let mock = MockTransport::new()
+            .on(
+                HttpMethod::Get,
+                "/vod.m3u8",
+                200,
+                "#EXTM3U\n#EXT-X-TARGETDURATION:2\n#EXT-X-MEDIA-SEQUENCE:1\n\
+                 #EXTINF:2.0,\ns1.ts\n#EXTINF:2.0,\ns2.ts\n#EXTINF:2.0,\ns3.ts\n",
+            )
+            .on(HttpMethod::Get, ".ts", 200, "ts");
+        let ingestor = HlsIngestor::from_url("https://x.example/vod.m3u8")
+            .unwrap()
+            .with_transport(mock.shared())
+            .with_session(session.clone());
+
+        let (tx, mut rx) = tokio::sync::mpsc::channel(4);
+        let task = tokio::spawn(async move { ingestor.start(tx).await });
+        let item = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        // The state is saved once the segment is handed over.
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        task.abort();
+        let next = session.state().await.next_media_sequence;
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(item.url.path(), "/s3.ts");
+        assert!(!item.discontinuity);
+        assert_eq!(next, Some(4));
+    }
+
+    #[tokio::test]
+    async fn stops_at_the_end_of_a_finished_playlist() {
+        let mock = MockTransport::new()
+            .on(
+                HttpMethod::Get,
+                "/vod.m3u8",
+                200,
+                "#EXTM3U\n#EXT-X-TARGETDURATION:10\n#EXT-X-MEDIA-SEQUENCE:0\n\
+                 #EXTINF:10.0,\ns0.ts\n#EXTINF:10.0,\ns1.ts\n#EXT-X-ENDLIST\n",
+            )
+            .on(HttpMethod::Get, ".ts", 200, "ts");
+        let ingestor = HlsIngestor::from_url("https://x.example/vod.m3u8")
+            .unwrap()
+            .with_transport(mock.shared());
+
+        let (tx, mut rx) = tokio::sync::mpsc::channel(4);
+        // Ends without waiting out the 10 s target duration.
+        tokio::time::timeout(Duration::from_secs(5), ingestor.start(tx))
+            .await
+            .unwrap()
+            .unwrap();
+        let mut paths = Vec::new();
+        while let Some(item) = rx.recv().await {
+            paths.push(item.url.path().to_owned());
+        }
+        assert_eq!(paths, ["/s0.ts", "/s1.ts"]);
+    }
+
+    fn fast_retry_ingestor(mock: &MockTransport, status: &PipelineStatus) -> HlsIngestor {
+        HlsIngestor::from_url("https://x.example/live.m3u8")
+            .unwrap()
+            .with_options(HlsOptions {
+                segment_retry: RetryConfig::new(5, Duration::from_millis(10)),
+                ..Default::default()
+            })
+            .with_transport(mock.clone().shared())
+            .with_status(status.clone())
+    }
+
+    #[tokio::test]
+    async fn retries_transient_segment_failures() {
+        let mock = MockTransport::new()
+            .on(
+                HttpMethod::Get,
+                "/live.m3u8",
+                200,
+                "#EXTM3U\n#EXT-X-TARGETDURATION:2\n#EXTINF:2.0,\ns1.ts\n",
+            )
+            .on(HttpMethod::Get, "/s1.ts", 404, "not yet")
+            .on(HttpMethod::Get, "/s1.ts", 503, "busy")
+            .on(HttpMethod::Get, "/s1.ts", 200, "ts");
+        let status = PipelineStatus::new();
+        let ingestor = fast_retry_ingestor(&mock, &status);
+
+        let (tx, mut rx) = tokio::sync::mpsc::channel(4);
+        let task = tokio::spawn(async move { ingestor.start(tx).await });
+        let item = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        task.abort();
+
+        assert_eq!(&item.bytes[..], b"ts");
+        assert!(!item.discontinuity);
+        let snap = status.snapshot();
+        assert_eq!((snap.segments_recovered, snap.segments_dropped), (1, 0));
+    }
+
+    #[tokio::test]
+    async fn prefetches_segments_but_sends_them_in_order() {
+        let mock = MockTransport::new()
+            .on(
+                HttpMethod::Get,
+                "/live.m3u8",
+                200,
+                "#EXTM3U\n#EXT-X-TARGETDURATION:2\n#EXT-X-ENDLIST\n\
+                 #EXTINF:2.0,\ns1.ts\n#EXTINF:2.0,\ns2.ts\n#EXTINF:2.0,\ns3.ts\n",
+            )
+            .on(HttpMethod::Get, "/s1.ts", 503, "busy")
+            .on(HttpMethod::Get, "/s1.ts", 200, "ts")
+            .on(HttpMethod::Get, "/s2.ts", 200, "ts")
+            .on(HttpMethod::Get, "/s3.ts", 200, "ts");
+        let status = PipelineStatus::new();
+        let ingestor = fast_retry_ingestor(&mock, &status);
+
+        let (tx, mut rx) = tokio::sync::mpsc::channel(4);
+        ingestor.start(tx).await.unwrap();
+        let mut paths = Vec::new();
+        while let Some(item) = rx.recv().await {
+            paths.push(item.url.path().to_owned());
+        }
+        assert_eq!(paths, ["/s1.ts", "/s2.ts", "/s3.ts"]);
+        // s2 and s3 were fetched while s1 waited for its retry.
+        let requested: Vec<String> = mock.requests().into_iter().map(|r| r.url).collect();
+        let segments: Vec<&str> = requested
+            .iter()
+            .filter_map(|url| url.rsplit('/').next().filter(|s| s.ends_with(".ts")))
+            .collect();
+        assert_eq!(segments, ["s1.ts", "s2.ts", "s3.ts", "s1.ts"]);
+        // The playlist fetched while resolving serves the first poll too.
+        assert_eq!(requested.len(), 5);
+    }
+
+    #[tokio::test]
+    async fn drops_segment_that_misses_its_playout_time() {
+        let mock = MockTransport::new()
+            .on(
+                HttpMethod::Get,
+                "/live.m3u8",
+                200,
+                "#EXTM3U\n#EXT-X-TARGETDURATION:1\n\
+                 #EXTINF:0.5,\ns1.ts\n#EXTINF:0.5,\ns2.ts\n",
+            )
+            .on(HttpMethod::Get, "/s1.ts", 404, "gone")
+            .on(HttpMethod::Get, "/s2.ts", 200, "ts");
+        let status = PipelineStatus::new();
+        let ingestor = fast_retry_ingestor(&mock, &status).with_options(HlsOptions {
+            // More attempts than fit before the deadline
+            segment_retry: RetryConfig::new(100, Duration::from_millis(100)),
+            ..Default::default()
+        });
+
+        let (tx, mut rx) = tokio::sync::mpsc::channel(4);
+        let task = tokio::spawn(async move { ingestor.start(tx).await });
+        let item = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        task.abort();
+
+        assert_eq!(item.url.path(), "/s2.ts");
+        assert!(item.discontinuity);
+        assert_eq!(item.gap_before, Duration::from_millis(500));
+        let snap = status.snapshot();
+        assert_eq!((snap.segments_recovered, snap.segments_dropped), (0, 1));
+    }
+
+    fn twitch_master() -> Vec<VariantStream> {
+        let master = "#EXTM3U\n\
+            #EXT-X-STREAM-INF:BANDWIDTH=6000000,RESOLUTION=1920x1080,CODECS=\"avc1.64002A,mp4a.40.2\",VIDEO=\"chunked\"\nsource.m3u8\n\
+            #EXT-X-STREAM-INF:BANDWIDTH=1400000,RESOLUTION=852x480,CODECS=\"avc1.4D401F,mp4a.40.2\",VIDEO=\"480p30\"\n480p.m3u8\n\
+            #EXT-X-STREAM-INF:BANDWIDTH=630000,RESOLUTION=640x360,CODECS=\"avc1.4D401E,mp4a.40.2\",VIDEO=\"360p30\"\n360p.m3u8\n\
+            #EXT-X-STREAM-INF:BANDWIDTH=160000,CODECS=\"mp4a.40.2\",VIDEO=\"audio_only\"\naudio_only.m3u8\n";
+        match m3u8_rs::parse_playlist_res(master.as_bytes()).unwrap() {
+            Playlist::MasterPlaylist(master) => master.variants,
+            Playlist::MediaPlaylist(_) => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn selects_variant_by_quality() {
+        let variants = twitch_master();
+        let pick = |quality: &str| {
+            let quality: VariantQuality = quality.parse().unwrap();
+            quality.select(&variants).unwrap().uri.clone()
+        };
+        assert_eq!(pick("audio_only"), "audio_only.m3u8");
+        assert_eq!(pick("best"), "source.m3u8");
+        assert_eq!(pick("worst"), "audio_only.m3u8");
+        assert_eq!(pick("480p"), "480p.m3u8");
+        assert_eq!(pick("720p"), "480p.m3u8");
+        assert_eq!(pick("1000k"), "360p.m3u8");
+        // Nothing fits; fall back to the lowest bandwidth
+        assert_eq!(pick("240p"), "audio_only.m3u8");
+    }
+
+    #[test]
+    fn parses_and_prints_quality() {
+        for quality in ["audio_only", "best", "worst", "480p", "1500k"] {
+            assert_eq!(
+                quality.parse::<VariantQuality>().unwrap().to_string(),
+                quality
+            );
+        }
+        assert_eq!(
+            "3m".parse::<VariantQuality>(),
+            Ok(VariantQuality::MaxBandwidth(3_000_000))
+        );
+        assert!("high".parse::<VariantQuality>().is_err());
+        assert!("p".parse::<VariantQuality>().is_err());
+    }
+
+    #[test]
+    fn throughput_monitor_needs_repeated_slow_fetches() {
+        let segment = Duration::from_secs(2);
+        let mut monitor = ThroughputMonitor::default();
+        monitor.record(true, Duration::from_millis(1900), segment);
+        assert!(!monitor.is_struggling());
+        monitor.record(true, Duration::from_millis(200), segment);
+        monitor.record(false, Duration::from_millis(500), segment);
+        assert!(monitor.is_struggling());
+        // The first slow fetch leaves the window
+        monitor.record(true, Duration::from_millis(200), segment);
+        assert!(!monitor.is_struggling());
+    }
+
+    #[tokio::test]
+    async fn steps_down_when_segments_cannot_be_fetched_in_time() {
+        let media = "#EXTM3U\n#EXT-X-TARGETDURATION:1\n#EXT-X-MEDIA-SEQUENCE:0\n\
+                     #EXTINF:0.5,\ns0.ts\n#EXTINF:0.5,\ns1.ts\n";
+        let mock = MockTransport::new()
+            .on(
+                HttpMethod::Get,
+                "x.example/index.m3u8",
+                200,
+                "#EXTM3U\n\
+                 #EXT-X-STREAM-INF:BANDWIDTH=2000000,RESOLUTION=1280x720,CODECS=\"avc1.64001f,mp4a.40.2\"\nhi/index.m3u8\n\
+                 #EXT-X-STREAM-INF:BANDWIDTH=500000,RESOLUTION=640x360,CODECS=\"avc1.4D401E,mp4a.40.2\"\nlo/index.m3u8\n",
+            )
+            .on(HttpMethod::Get, "/hi/index.m3u8", 200, media)
+            .on(HttpMethod::Get, "/lo/index.m3u8", 200, media)
+            .on(HttpMethod::Get, "/hi/s", 503, "")
+            .on(HttpMethod::Get, "/lo/s", 200, "ts");
+        let status = PipelineStatus::new();
+        let mut events = status.subscribe();
+        let ingestor = HlsIngestor::from_url("https://x.example/index.m3u8")
+            .unwrap()
+            .with_options(HlsOptions {
+                quality: VariantQuality::Best,
+                segment_retry: RetryConfig::new(100, Duration::from_millis(50)),
+                ..Default::default()
+            })
+            .with_transport(mock.clone().shared())
+            .with_status(status.clone());
+
+        let (tx, mut rx) = tokio::sync::mpsc::channel(4);
+        let task = tokio::spawn(async move { ingestor.start(tx).await });
+        let item = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        task.abort();
+
+        // s0 was dropped on the high variant; s1 is fetched again from the low one
+        assert_eq!(item.url.as_str(), "https://x.example/lo/s1.ts");
+        assert!(item.discontinuity);
+        assert_eq!(item.gap_before, Duration::from_millis(500));
+        assert_eq!(
+            events.try_recv().unwrap(),
+            PipelineEvent::VariantSwitched {
+                from_bandwidth: Some(2_000_000),
+                to_bandwidth: 500_000,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn follows_plain_m3u8_url_through_master_playlist() {
+        let mock = MockTransport::new()
+            .on(
+                HttpMethod::Get,
+                "news.example/live/index.m3u8",
+                200,
+                "#EXTM3U\n\
+                 #EXT-X-STREAM-INF:BANDWIDTH=2000000,CODECS=\"avc1.64001f,mp4a.40.2\"\nhd/index.m3u8\n\
+                 #EXT-X-STREAM-INF:BANDWIDTH=64000,CODECS=\"mp4a.40.2\"\naudio/index.m3u8\n",
+            )
+            .on(
+                HttpMethod::Get,
+                "news.example/live/hd/index.m3u8",
+                200,
+                "#EXTM3U\n#EXT-X-TARGETDURATION:4\n#EXTINF:4.0,\nseg1.ts\n",
+            )
+            .on(HttpMethod::Get, "news.example/live/hd/seg1.ts", 200, "ts");
+        let ingestor = HlsIngestor::from_url("https://news.example/live/index.m3u8")
+            .unwrap()
+            .with_options(HlsOptions {
+                quality: VariantQuality::Best,
+                ..Default::default()
+            })
+            .with_transport(mock.clone().shared());
+
+        let (tx, mut rx) = tokio::sync::mpsc::channel(4);
+        let task = tokio::spawn(async move { ingestor.start(tx).await });
+        let item = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        task.abort();
+
+        assert_eq!(item.url.as_str(), "https://news.example/live/hd/seg1.ts");
+        assert_eq!(item.approx_duration, Duration::from_secs(4));
+    }
+
+    #[tokio::test]
+    async fn missing_playlist_reports_http_404() {
+        let mock = MockTransport::new().on(HttpMethod::Get, "/gone.m3u8", 404, "");
+        let (tx, _rx) = tokio::sync::mpsc::channel(1);
+        let err = HlsIngestor::from_url("https://x.example/gone.m3u8")
+            .unwrap()
+            .with_transport(mock.shared())
+            .start(tx)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, IngestError::HttpStatus(404, _)));
+    }
+}
@@ -0,0 +1,176 @@
+//! Segments waiting for the decoder
+//!
+//! Segments arrive in bursts: a playlist poll can list several at once, and a
+//! slow download delays the ones after it. The [`JitterBuffer`] follows each
+//! segment from the moment it is queued for the decoder until the decoder
+//! takes it, by its target playout time: when it was fetched plus the latency
+//! budget. A segment taken after that time can no longer be played within
+//! the budget, so with a deadline set it is dropped as late instead of
+//! decoded, and the segment after it is marked as following a gap. How much
+//! audio is waiting, and how many segments were late, goes to
+//! [`PipelineStatus`].
+
+use super::IngestItem;
+use crate::status::{PipelineEvent, PipelineStatus, Stage};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+/// Playout deadlines and occupancy of the segments queued for the decoder.
+/// Clones share the occupancy.
+#[derive(Clone, Default)]
+pub struct JitterBuffer {
+    max_delay: Option<Duration>,
+    queued_us: Arc<AtomicU64>,
+    status: Option<PipelineStatus>,
+}
+
+impl JitterBuffer {
+    /// Drops segments not taken within `max_delay` of being fetched; `None`
+    /// keeps every segment and only measures.
+    pub fn new(max_delay: Option<Duration>) -> Self {
+        Self {
+            max_delay,
+            ..Default::default()
+        }
+    }
+
+    /// Reports occupancy and late segments to `status`.
+    pub fn with_status(mut self, status: PipelineStatus) -> Self {
+        status.set_jitter_buffered(self.queued());
+        self.status = Some(status);
+        self
+    }
+
+    /// Audio queued and not yet taken.
+    pub fn queued(&self) -> Duration {
+        Duration::from_micros(self.queued_us.load(Ordering::Acquire))
+    }
+
+    /// When `item` has to be taken by to be played within the budget.
+    pub fn playout_deadline(&self, item: &IngestItem) -> Option<SystemTime> {
+        Some(item.fetched_at + self.max_delay?)
+    }
+
+    /// Counts `item` as queued.
+    pub(crate) fn push(&self, item: &IngestItem) {
+        let us = item.approx_duration.as_micros() as u64;
+        let queued = self.queued_us.fetch_add(us, Ordering::AcqRel) + us;
+        self.report(queued);
+    }
+
+    /// Stops counting `item` as queued, e.g. when nobody will take it.
+    pub(crate) fn discard(&self, item: &IngestItem) {
+        let us = item.approx_duration.as_micros() as u64;
+        let queued = self
+            .queued_us
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |q| {
+                Some(q.saturating_sub(us))
+            })
+            .unwrap_or_default()
+            .saturating_sub(us);
+        self.report(queued);
+    }
+
+    /// Takes `item` out of the queue at `now`, handing it back unless it
+    /// missed its playout deadline. The audio of late items is added to
+    /// `lost` and carried over to the next item that is on time.
+    pub(crate) fn take(
+        &self,
+        mut item: IngestItem,
+        lost: &mut Duration,
+        now: SystemTime,
+    ) -> Option<IngestItem> {
+        self.discard(&item);
+
+        if let Some(deadline) = self.playout_deadline(&item) {
+            if now > deadline {
+                let age_ms = now
+                    .duration_since(item.fetched_at)
+                    .unwrap_or_default()
+                    .as_millis() as u64;
+                tracing::debug!(sequence = item.sequence, age_ms, "dropping late segment");
+                if let Some(status) = &self.status {
+                    status.record_segment_late();
+                    status.record_skipped(Stage::Ingest);
+                    status.publish(PipelineEvent::Skipped {
+                        stage: Stage::Ingest,
+                        sequence: item.sequence,
+                        age_ms,
+                    });
+                }
+                *lost += item.gap_before + item.approx_duration;
+                return None;
+            }
+        }
+        if !lost.is_zero() {
+            item.discontinuity = true;
+            item.gap_before += std::mem::take(lost);
+        }
+        Some(item)
+    }
+
+    fn report(&self, queued_us: u64) {
+        if let Some(status) = &self.status {
+            status.set_jitter_buffered(Duration::from_micros(queued_us));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+    use url::Url;
+
+    fn segment(sequence: u64, fetched_at: SystemTime) -> IngestItem {
+        IngestItem {
+            sequence,
+            fetched_at,
+            url: Url::parse("https://cdn.example/seg.ts").unwrap(),
+            approx_duration: Duration::from_secs(2),
+            bytes: Bytes::new(),
+            discontinuity: false,
+            gap_before: Duration::ZERO,
+        }
+    }
+
+    #[test]
+    fn late_segments_are_dropped_and_leave_a_gap() {
+        let status = PipelineStatus::new();
+        let jitter =
+            JitterBuffer::new(Some(Duration::from_millis(1500))).with_status(status.clone());
+        let fetched = SystemTime::UNIX_EPOCH + Duration::from_secs(100);
+        let (late, on_time) = (
+            segment(0, fetched),
+            segment(1, fetched + Duration::from_secs(2)),
+        );
+        jitter.push(&late);
+        jitter.push(&on_time);
+        assert_eq!(status.snapshot().jitter_buffer_ms, 4000);
+
+        let now = fetched + Duration::from_secs(3);
+        let mut lost = Duration::ZERO;
+        assert!(jitter.take(late, &mut lost, now).is_none());
+        let taken = jitter.take(on_time, &mut lost, now).unwrap();
+        assert!(taken.discontinuity);
+        assert_eq!(taken.gap_before, Duration::from_secs(2));
+        assert!(lost.is_zero());
+
+        let snap = status.snapshot();
+        assert_eq!((snap.segments_late, snap.jitter_buffer_ms), (1, 0));
+        assert_eq!(snap.stage(Stage::Ingest).unwrap().skipped, 1);
+    }
+
+    #[test]
+    fn without_a_deadline_segments_are_only_measured() {
+        let jitter = JitterBuffer::new(None);
+        let item = segment(0, SystemTime::UNIX_EPOCH);
+        jitter.push(&item);
+        assert_eq!(jitter.queued(), Duration::from_secs(2));
+        let mut lost = Duration::ZERO;
+        let taken = jitter.take(item, &mut lost, SystemTime::now()).unwrap();
+        assert!(!taken.discontinuity);
+        assert_eq!(jitter.queued(), Duration::ZERO);
+    }
+}
@@ -0,0 +1,236 @@
+//! Microphone ingest
+//!
+//! [`MicIngestor`] captures an audio input device with ffmpeg (PulseAudio or
+//! PipeWire on Linux, DirectShow on Windows, AVFoundation on macOS) and cuts
+//! it into short MPEG-TS chunks, sent on like pushed stream chunks. It feeds
+//! the viewer's side of a conversation, see
+//! [`crate::config::ConversationConfig`].
+
+use crate::ingest::push::ffmpeg_binary;
+use crate::ingest::{IngestError, IngestItem, Ingestor};
+use std::future::Future;
+use std::path::Path;
+use std::pin::Pin;
+use std::process::Stdio;
+use std::time::{Duration, SystemTime};
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::sync::mpsc::Sender;
+use url::Url;
+
+/// Chunk length; speech is cut at fixed points, so shorter chunks split more
+/// words between them.
+pub const DEFAULT_MIC_CHUNK: Duration = Duration::from_secs(3);
+
+#[derive(Clone)]
+pub struct MicIngestor {
+    device: String,
+    chunk: Duration,
+    url: Url,
+}
+
+impl MicIngestor {
+    /// Captures `device`, or the system's default input for `default`.
+    /// DirectShow has no default device, so Windows needs a name.
+    pub fn new(device: &str) -> Result<Self, IngestError> {
+        let device = device.trim();
+        if cfg!(windows) && (device.is_empty() || device == "default") {
+            return Err(IngestError::Capture(
+                "name the microphone, as listed by `ffmpeg -list_devices true -f dshow -i dummy`"
+                    .to_owned(),
+            ));
+        }
+        let device = if device.is_empty() { "default" } else { device };
+        Ok(Self {
+            device: device.to_owned(),
+            chunk: DEFAULT_MIC_CHUNK,
+            url: Url::parse(&format!("mic:{device}"))?,
+        })
+    }
+
+    pub fn with_chunk_duration(mut self, chunk: Duration) -> Self {
+        self.chunk = chunk;
+        self
+    }
+
+    /// Records `length` of the device into a WAV file at `path`, e.g. a
+    /// sample of someone's voice.
+    pub async fn record(&self, length: Duration, path: &Path) -> Result<(), IngestError> {
+        tracing::info!(device = %self.device, secs = length.as_secs(), "Recording the microphone");
+        let output = tokio::process::Command::new(ffmpeg_binary())
+            .args(record_args(&self.device, length, path))
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .output()
+            .await
+            .map_err(|e| IngestError::Ffmpeg(format!("failed to start ffmpeg: {e}")))?;
+        if !output.status.success() {
+            return Err(IngestError::Capture(format!(
+                "recording {} failed: {}",
+                self.device,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(())
+    }
+
+    async fn run(&self, tx: Sender<IngestItem>) -> Result<(), IngestError> {
+        let dir = std::env::temp_dir().join(format!(
+            "twitch-translator-mic-{}-{:08x}",
+            std::process::id(),
+            rand::random::<u32>()
+        ));
+        tokio::fs::create_dir_all(&dir).await?;
+        let result = self.run_in(&dir, tx).await;
+        if let Err(e) = tokio::fs::remove_dir_all(&dir).await {
+            tracing::debug!(error = %e, dir = %dir.display(), "failed to remove microphone chunk directory");
+        }
+        result
+    }
+
+    async fn run_in(&self, dir: &Path, tx: Sender<IngestItem>) -> Result<(), IngestError> {
+        let mut child = tokio::process::Command::new(ffmpeg_binary())
+            .args(ffmpeg_args(&self.device, self.chunk, dir))
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| IngestError::Ffmpeg(format!("failed to start ffmpeg: {e}")))?;
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| IngestError::Ffmpeg("ffmpeg stdout unavailable".to_owned()))?;
+        if let Some(stderr) = child.stderr.take() {
+            tokio::spawn(async move {
+                let mut lines = BufReader::new(stderr).lines();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    tracing::warn!(target: "ffmpeg", "microphone: {}", line);
+                }
+            });
+        }
+
+        tracing::info!(device = %self.device, "Capturing the microphone");
+        let mut chunks = BufReader::new(stdout).lines();
+        let mut sequence = 0u64;
+        while let Some(line) = chunks.next_line().await? {
+            let path = dir.join(line.trim());
+            let bytes = tokio::fs::read(&path).await?;
+            let _ = tokio::fs::remove_file(&path).await;
+            let item = IngestItem {
+                sequence,
+                fetched_at: SystemTime::now(),
+                url: Url::from_file_path(&path).unwrap_or_else(|_| self.url.clone()),
+                approx_duration: self.chunk,
+                bytes: bytes.into(),
+                discontinuity: false,
+                gap_before: Duration::ZERO,
+            };
+            if tx.send(item).await.is_err() {
+                return Ok(());
+            }
+            sequence += 1;
+        }
+
+        let status = child.wait().await?;
+        if !status.success() {
+            return Err(IngestError::Capture(format!(
+                "capturing {} failed: ffmpeg exited with {status}",
+                self.device
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl Ingestor for MicIngestor {
+    fn start(
+        &self,
+        tx: Sender<IngestItem>,
+    ) -> Pin<Box<dyn Future<Output = Result<(), IngestError>> + Send + 'static>> {
+        let this = self.clone();
+        Box::pin(async move { this.run(tx).await })
+    }
+}
+
+/// The ffmpeg input format and name for `device` on this platform.
+fn capture_input(device: &str) -> [String; 2] {
+    if cfg!(windows) {
+        ["dshow".to_owned(), format!("audio={device}")]
+    } else if cfg!(target_os = "macos") {
+        ["avfoundation".to_owned(), format!(":{device}")]
+    } else {
+        ["pulse".to_owned(), device.to_owned()]
+    }
+}
+
+/// Arguments that make ffmpeg capture `device` as mono AAC in `chunk`-long
+/// MPEG-TS files in `dir`, listing each on stdout once it is complete.
+fn ffmpeg_args(device: &str, chunk: Duration, dir: &Path) -> Vec<String> {
+    let [format, input] = capture_input(device);
+    let mut args: Vec<String> = ["-hide_banner", "-nostdin", "-loglevel", "warning", "-f"]
+        .map(String::from)
+        .to_vec();
+    args.extend([format, "-i".to_owned(), input]);
+    args.extend(
+        [
+            "-ac",
+            "1",
+            "-c:a",
+            "aac",
+            "-f",
+            "segment",
+            "-segment_format",
+            "mpegts",
+            "-reset_timestamps",
+            "1",
+            "-segment_list",
+            "pipe:1",
+            "-segment_list_type",
+            "flat",
+            "-segment_time",
+        ]
+        .map(String::from),
+    );
+    args.push(format!("{:.3}", chunk.as_secs_f64()));
+    args.push(dir.join("chunk%06d.ts").display().to_string());
+    args
+}
+
+/// Arguments that make ffmpeg record `length` of `device` as mono 16-bit
+/// WAV into `path`.
+fn record_args(device: &str, length: Duration, path: &Path) -> Vec<String> {
+    let [format, input] = capture_input(device);
+    let mut args: Vec<String> = ["-hide_banner", "-nostdin", "-loglevel", "error", "-y", "-f"]
+        .map(String::from)
+        .to_vec();
+    args.extend([format, "-i".to_owned(), input, "-t".to_owned()]);
+    args.push(format!("{:.3}", length.as_secs_f64()));
+    args.extend(["-ac", "1", "-c:a", "pcm_s16le"].map(String::from));
+    args.push(path.display().to_string());
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn captures_through_pulse_in_chunks() {
+        let args = ffmpeg_args("default", DEFAULT_MIC_CHUNK, Path::new("/tmp/chunks"));
+        let input = args.iter().position(|a| a == "-i").unwrap();
+        assert_eq!(args[input - 2..input + 2], ["-f", "pulse", "-i", "default"]);
+        assert!(args.contains(&"3.000".to_owned()));
+        assert_eq!(args.last().unwrap(), "/tmp/chunks/chunk%06d.ts");
+    }
+
+    #[test]
+    fn records_a_fixed_length() {
+        let args = record_args("mic", Duration::from_secs(60), Path::new("/tmp/voice.wav"));
+        let length = args.iter().position(|a| a == "-t").unwrap();
+        assert_eq!(args[length + 1], "60.000");
+        assert_eq!(args.last().unwrap(), "/tmp/voice.wav");
+    }
+}
@@ -1,12 +1,33 @@
+use crate::memory::{BufferBudget, Buffered};
+use crate::status::Stage;
 use bytes::Bytes;
+use futures::Stream;
 use std::{
     future::Future,
     pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
     time::{Duration, SystemTime},
 };
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
 use url::Url;
 
+mod cdn;
+pub mod hls;
+pub mod jitter;
+pub mod mic;
+pub mod push;
+pub mod record;
+pub mod replay;
 pub mod twitch;
+pub use crate::config::VariantQuality;
+pub use hls::{HlsIngestor, HlsOptions, PlaylistSource, ResolvedPlaylist};
+pub use jitter::JitterBuffer;
+pub use mic::MicIngestor;
+pub use push::PushIngestor;
+pub use record::{IngestRecorder, RecordedEntry};
+pub use replay::ReplayIngestor;
 pub use twitch::{TwitchHlsIngestor, TwitchIngestOptions};
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -16,6 +37,11 @@ pub struct IngestItem {
     pub url: Url,
     pub approx_duration: Duration,
     pub bytes: Bytes,
+    /// Timestamps or codec parameters may not continue from the previous
+    /// item (`EXT-X-DISCONTINUITY`, or segments were lost in between).
+    pub discontinuity: bool,
+    /// Estimated audio missing between the previous item and this one.
+    pub gap_before: Duration,
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -27,7 +53,7 @@ pub enum IngestError {
     InvalidUrl(#[from] url::ParseError),
 
     #[error("http error: {0}")]
-    Http(#[from] reqwest::Error),
+    Http(#[from] crate::util::HttpError),
 
     #[error("twitch gql response missing required fields")]
     TwitchGqlMissingFields,
@@ -41,11 +67,38 @@ pub enum IngestError {
     #[error("expected HLS media playlist")]
     ExpectedMediaPlaylist,
 
+    #[error("channel '{0}' is offline")]
+    ChannelOffline(String),
+
     #[error("no usable variant found")]
     NoUsableVariant,
 
     #[error("http error {0}: {1}")]
     HttpStatus(u16, String),
+
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("replay error: {0}")]
+    Replay(String),
+
+    #[error("unsupported listen url '{0}': expected rtmp:// or srt://")]
+    UnsupportedListenUrl(String),
+
+    #[error("unsupported peer url '{0}': expected tcp://HOST:PORT")]
+    UnsupportedPeerUrl(String),
+
+    #[error("ffmpeg error: {0}")]
+    Ffmpeg(String),
+
+    #[error("audio capture error: {0}")]
+    Capture(String),
+
+    #[error("segment {0} could not be fetched before its playout time")]
+    SegmentDeadline(String),
+
+    #[error("ingest task failed: {0}")]
+    TaskFailed(String),
 }
 
 pub trait Ingestor: Send + Sync {
@@ -53,4 +106,211 @@ pub trait Ingestor: Send + Sync {
         &self,
         tx: tokio::sync::mpsc::Sender<IngestItem>,
     ) -> Pin<Box<dyn Future<Output = Result<(), IngestError>> + Send + 'static>>;
-}
\ No newline at end of file
+
+    /// Runs the ingestor in the background and yields its items in order,
+    /// buffering up to `capacity`. Must be called within a Tokio runtime.
+    fn stream(&self, capacity: usize) -> IngestStream {
+        let (tx, rx) = mpsc::channel(capacity);
+        IngestStream {
+            rx,
+            task: Some(tokio::spawn(self.start(tx))),
+            budget: None,
+            jitter: None,
+            lost: Duration::ZERO,
+        }
+    }
+
+    /// Like [`Self::stream`], but queued items count against `budget` and
+    /// are followed by `jitter` until they are taken. An item that does not
+    /// fit the budget, or is taken after its playout deadline, is dropped and
+    /// the next one is marked as following a gap.
+    fn stream_within(
+        &self,
+        capacity: usize,
+        budget: BufferBudget,
+        jitter: JitterBuffer,
+    ) -> IngestStream {
+        let (tx, rx) = mpsc::channel(capacity);
+        let (fetched_tx, mut fetched_rx) = mpsc::channel::<IngestItem>(1);
+        let ingest = self.start(fetched_tx);
+        let relay = {
+            let budget = budget.clone();
+            let jitter = jitter.clone();
+            async move {
+                let mut lost = Duration::ZERO;
+                while let Some(mut item) = fetched_rx.recv().await {
+                    if !budget.try_reserve(Stage::Ingest, item.buffered_bytes()) {
+                        lost += item.approx_duration;
+                        continue;
+                    }
+                    if !lost.is_zero() {
+                        item.discontinuity = true;
+                        item.gap_before += std::mem::take(&mut lost);
+                    }
+                    jitter.push(&item);
+                    if let Err(mpsc::error::SendError(item)) = tx.send(item).await {
+                        budget.release(item.buffered_bytes());
+                        jitter.discard(&item);
+                        break;
+                    }
+                }
+            }
+        };
+        // The relay ends once the ingestor returns and drops its sender;
+        // dropping the relay's receiver likewise stops the ingestor.
+        let task = tokio::spawn(async move { futures::join!(ingest, relay).0 });
+        IngestStream {
+            rx,
+            task: Some(task),
+            budget: Some(budget),
+            jitter: Some(jitter),
+            lost: Duration::ZERO,
+        }
+    }
+}
+
+/// Items from an [`Ingestor`], followed by its error if it fails. Dropping the
+/// stream stops the ingestor.
+pub struct IngestStream {
+    rx: mpsc::Receiver<IngestItem>,
+    task: Option<JoinHandle<Result<(), IngestError>>>,
+    budget: Option<BufferBudget>,
+    jitter: Option<JitterBuffer>,
+    /// Audio of late items not yet accounted for in the next item's gap.
+    lost: Duration,
+}
+
+impl IngestStream {
+    /// Items fetched but not yet taken from the stream.
+    pub fn queued(&self) -> usize {
+        self.rx.len()
+    }
+}
+
+impl Stream for IngestStream {
+    type Item = Result<IngestItem, IngestError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        loop {
+            match this.rx.poll_recv(cx) {
+                Poll::Ready(Some(item)) => {
+                    if let Some(budget) = &this.budget {
+                        budget.release(item.buffered_bytes());
+                    }
+                    let item = match &this.jitter {
+                        Some(jitter) => jitter.take(item, &mut this.lost, SystemTime::now()),
+                        None => Some(item),
+                    };
+                    if let Some(item) = item {
+                        return Poll::Ready(Some(Ok(item)));
+                    }
+                }
+                Poll::Pending => return Poll::Pending,
+                // The channel closes once the ingestor has returned; report how.
+                Poll::Ready(None) => break,
+            }
+        }
+        let Some(task) = self.task.as_mut() else {
+            return Poll::Ready(None);
+        };
+        let result = match Pin::new(task).poll(cx) {
+            Poll::Pending => return Poll::Pending,
+            Poll::Ready(Ok(result)) => result,
+            Poll::Ready(Err(e)) => Err(IngestError::TaskFailed(e.to_string())),
+        };
+        self.task = None;
+        Poll::Ready(result.err().map(Err))
+    }
+}
+
+impl Drop for IngestStream {
+    fn drop(&mut self) {
+        if let Some(task) = &self.task {
+            task.abort();
+        }
+    }
+}
+
+/// Type-erased [`Ingestor`] for picking the source at runtime.
+#[derive(Clone)]
+pub struct DynIngestor {
+    inner: Arc<dyn Ingestor>,
+}
+
+impl DynIngestor {
+    pub fn new(inner: Arc<dyn Ingestor>) -> Self {
+        Self { inner }
+    }
+}
+
+impl Ingestor for DynIngestor {
+    fn start(
+        &self,
+        tx: tokio::sync::mpsc::Sender<IngestItem>,
+    ) -> Pin<Box<dyn Future<Output = Result<(), IngestError>> + Send + 'static>> {
+        self.inner.start(tx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::StreamExt;
+
+    /// Sends `count` items, then fails if `fail` is set.
+    struct Scripted {
+        count: u64,
+        fail: bool,
+    }
+
+    impl Ingestor for Scripted {
+        fn start(
+            &self,
+            tx: mpsc::Sender<IngestItem>,
+        ) -> Pin<Box<dyn Future<Output = Result<(), IngestError>> + Send + 'static>> {
+            let (count, fail) = (self.count, self.fail);
+            Box::pin(async move {
+                for sequence in 0..count {
+                    let item = IngestItem {
+                        sequence,
+                        fetched_at: SystemTime::now(),
+                        url: Url::parse("https://cdn.example/seg.ts")?,
+                        approx_duration: Duration::from_secs(2),
+                        bytes: Bytes::new(),
+                        discontinuity: false,
+                        gap_before: Duration::ZERO,
+                    };
+                    if tx.send(item).await.is_err() {
+                        return Ok(());
+                    }
+                }
+                if fail {
+                    return Err(IngestError::ChannelOffline("x".to_owned()));
+                }
+                Ok(())
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn stream_yields_items_then_ends() {
+        let items: Vec<_> = Scripted { count: 3, fail: false }
+            .stream(1)
+            .map(|r| r.unwrap().sequence)
+            .collect()
+            .await;
+        assert_eq!(items, [0, 1, 2]);
+    }
+
+    #[tokio::test]
+    async fn stream_reports_ingestor_error_after_its_items() {
+        let mut stream = Scripted { count: 1, fail: true }.stream(4);
+        assert_eq!(stream.next().await.unwrap().unwrap().sequence, 0);
+        assert!(matches!(
+            stream.next().await,
+            Some(Err(IngestError::ChannelOffline(_)))
+        ));
+        assert!(stream.next().await.is_none());
+    }
+}
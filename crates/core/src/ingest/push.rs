@@ -0,0 +1,336 @@
+//! RTMP/SRT push ingest
+//!
+//! [`PushIngestor`] listens for a stream pushed straight from OBS (or any
+//! encoder) instead of pulling from Twitch, skipping the transcoding and HLS
+//! segmenting delay. ffmpeg acts as the server: it accepts one RTMP or SRT
+//! connection and cuts the audio into short MPEG-TS chunks, which are sent on
+//! exactly like HLS segments. With a caption relay it also forwards the push
+//! to an RTMP server with the translations as closed captions (see
+//! [`crate::cc`]).
+
+use crate::ingest::{IngestError, IngestItem, IngestRecorder, Ingestor};
+use crate::status::PipelineStatus;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::sync::mpsc::Sender;
+use url::Url;
+
+/// Chunk length; shorter chunks cut latency but give ASR less context.
+pub const DEFAULT_PUSH_CHUNK: Duration = Duration::from_secs(2);
+
+/// Lines of ffmpeg's stderr kept for the error message if it fails.
+const STDERR_TAIL_LINES: usize = 20;
+
+#[derive(Clone)]
+pub struct PushIngestor {
+    listen: Url,
+    chunk: Duration,
+    recorder: Option<Arc<IngestRecorder>>,
+    caption_relay: Option<(String, PipelineStatus)>,
+    romanize_captions: bool,
+}
+
+impl PushIngestor {
+    /// Listens on `listen`, e.g. `rtmp://0.0.0.0:1935/live/key` or
+    /// `srt://0.0.0.0:9000`.
+    pub fn new(listen: &str) -> Result<Self, IngestError> {
+        let url = Url::parse(listen)?;
+        if !matches!(url.scheme(), "rtmp" | "srt") {
+            return Err(IngestError::UnsupportedListenUrl(listen.to_string()));
+        }
+        Ok(Self {
+            listen: url,
+            chunk: DEFAULT_PUSH_CHUNK,
+            recorder: None,
+            caption_relay: None,
+            romanize_captions: false,
+        })
+    }
+
+    pub fn with_chunk_duration(mut self, chunk: Duration) -> Self {
+        self.chunk = chunk;
+        self
+    }
+
+    /// Writes every chunk to `recorder` as well.
+    pub fn with_recorder(mut self, recorder: Arc<IngestRecorder>) -> Self {
+        self.recorder = Some(recorder);
+        self
+    }
+
+    /// Forwards the push to the RTMP URL `target` as well, with the
+    /// translations published on `status` embedded as closed captions.
+    pub fn with_caption_relay(mut self, target: String, status: PipelineStatus) -> Self {
+        self.caption_relay = Some((target, status));
+        self
+    }
+
+    /// Romanizes relayed captions in non-Latin scripts, which CEA-608
+    /// cannot show.
+    pub fn with_romanized_captions(mut self) -> Self {
+        self.romanize_captions = true;
+        self
+    }
+
+    async fn run(&self, tx: Sender<IngestItem>) -> Result<(), IngestError> {
+        let dir = std::env::temp_dir().join(format!(
+            "twitch-translator-push-{}-{:08x}",
+            std::process::id(),
+            rand::random::<u32>()
+        ));
+        tokio::fs::create_dir_all(&dir).await?;
+        let result = self.run_in(&dir, tx).await;
+        if let Err(e) = tokio::fs::remove_dir_all(&dir).await {
+            tracing::debug!(error = %e, dir = %dir.display(), "failed to remove push chunk directory");
+        }
+        result
+    }
+
+    async fn run_in(&self, dir: &Path, tx: Sender<IngestItem>) -> Result<(), IngestError> {
+        let mut relay = None;
+        if let Some((target, status)) = &self.caption_relay {
+            let listener = tokio::net::TcpListener::bind(("127.0.0.1", 0)).await?;
+            let addr = listener.local_addr()?;
+            let task = tokio::spawn(crate::cc::relay(
+                listener,
+                target.clone(),
+                status.subscribe(),
+                self.romanize_captions,
+            ));
+            relay = Some((addr, AbortOnDrop(task)));
+        }
+        let mut child = tokio::process::Command::new(ffmpeg_binary())
+            .args(ffmpeg_args(
+                &self.listen,
+                self.chunk,
+                dir,
+                relay.as_ref().map(|(addr, _)| *addr),
+            ))
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| IngestError::Ffmpeg(format!("failed to start ffmpeg: {e}")))?;
+
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| IngestError::Ffmpeg("ffmpeg stdout unavailable".to_owned()))?;
+        let stderr = child
+            .stderr
+            .take()
+            .ok_or_else(|| IngestError::Ffmpeg("ffmpeg stderr unavailable".to_owned()))?;
+        let stderr_task = tokio::spawn(async move {
+            let mut lines = BufReader::new(stderr).lines();
+            let mut tail = std::collections::VecDeque::with_capacity(STDERR_TAIL_LINES);
+            while let Ok(Some(line)) = lines.next_line().await {
+                tracing::debug!(target: "ffmpeg", "{}", line);
+                if tail.len() == STDERR_TAIL_LINES {
+                    tail.pop_front();
+                }
+                tail.push_back(line);
+            }
+            Vec::from(tail).join("\n")
+        });
+
+        tracing::info!("Waiting for a stream to be pushed to {}", self.listen);
+        // The segment muxer lists each chunk on stdout once it is complete.
+        let mut chunks = BufReader::new(stdout).lines();
+        let mut sequence = 0u64;
+        while let Some(line) = chunks.next_line().await? {
+            let path = dir.join(line.trim());
+            let bytes = tokio::fs::read(&path).await?;
+            let _ = tokio::fs::remove_file(&path).await;
+            if sequence == 0 {
+                tracing::info!("Receiving pushed stream");
+            }
+
+            let item = IngestItem {
+                sequence,
+                fetched_at: SystemTime::now(),
+                url: Url::from_file_path(&path).unwrap_or_else(|_| self.listen.clone()),
+                approx_duration: self.chunk,
+                bytes: bytes.into(),
+                discontinuity: false,
+                gap_before: Duration::ZERO,
+            };
+            if let Some(recorder) = &self.recorder {
+                if let Err(e) = recorder.record_segment(&item).await {
+                    tracing::warn!(error = %e, "failed to record segment");
+                }
+            }
+            if tx.send(item).await.is_err() {
+                return Ok(());
+            }
+            sequence += 1;
+        }
+
+        let status = child.wait().await?;
+        let stderr = stderr_task.await.unwrap_or_default();
+        if !status.success() {
+            return Err(IngestError::Ffmpeg(format!("{status}: {stderr}")));
+        }
+        if let Some((_, AbortOnDrop(task))) = relay.as_mut() {
+            if let Ok(Err(e)) = task.await {
+                tracing::warn!(error = %e, "caption relay failed");
+            }
+        }
+        tracing::info!("Pushed stream ended");
+        Ok(())
+    }
+}
+
+impl Ingestor for PushIngestor {
+    fn start(
+        &self,
+        tx: Sender<IngestItem>,
+    ) -> Pin<Box<dyn Future<Output = Result<(), IngestError>> + Send + 'static>> {
+        let this = self.clone();
+        Box::pin(async move { this.run(tx).await })
+    }
+}
+
+/// Aborts the caption relay if the ingest stops early.
+struct AbortOnDrop(tokio::task::JoinHandle<Result<(), IngestError>>);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+#[cfg(feature = "ffmpeg-sidecar")]
+pub(crate) fn ffmpeg_binary() -> PathBuf {
+    ffmpeg_sidecar::paths::ffmpeg_path()
+}
+
+#[cfg(not(feature = "ffmpeg-sidecar"))]
+pub(crate) fn ffmpeg_binary() -> PathBuf {
+    PathBuf::from("ffmpeg")
+}
+
+/// Arguments that make ffmpeg accept one push on `listen` and write its first
+/// audio track to `dir` as `chunk`-long MPEG-TS files, plus the whole stream
+/// as FLV to `relay` if set.
+fn ffmpeg_args(
+    listen: &Url,
+    chunk: Duration,
+    dir: &Path,
+    relay: Option<SocketAddr>,
+) -> Vec<String> {
+    let mut args: Vec<String> = ["-hide_banner", "-nostdin", "-loglevel", "warning"]
+        .map(String::from)
+        .to_vec();
+
+    if listen.scheme() == "rtmp" {
+        args.extend(["-listen".to_owned(), "1".to_owned()]);
+    }
+    let mut input = listen.clone();
+    if input.scheme() == "srt" && !input.query_pairs().any(|(k, _)| k == "mode") {
+        input.query_pairs_mut().append_pair("mode", "listener");
+    }
+    args.extend(["-i".to_owned(), input.to_string()]);
+
+    args.extend(
+        [
+            "-map",
+            "0:a:0",
+            "-vn",
+            "-c:a",
+            "copy",
+            "-f",
+            "segment",
+            "-segment_format",
+            "mpegts",
+            "-reset_timestamps",
+            "1",
+            "-segment_list",
+            "pipe:1",
+            "-segment_list_type",
+            "flat",
+            "-segment_time",
+        ]
+        .map(String::from),
+    );
+    args.push(format!("{:.3}", chunk.as_secs_f64()));
+    args.push(dir.join("chunk%06d.ts").display().to_string());
+    if let Some(addr) = relay {
+        args.extend(["-map", "0", "-c", "copy", "-f", "flv"].map(String::from));
+        args.push(format!("tcp://{addr}"));
+    }
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_non_push_urls() {
+        assert!(matches!(
+            PushIngestor::new("https://example.com/live.m3u8"),
+            Err(IngestError::UnsupportedListenUrl(_))
+        ));
+        assert!(PushIngestor::new("rtmp://0.0.0.0:1935/live/key").is_ok());
+    }
+
+    #[test]
+    fn rtmp_listens_and_srt_defaults_to_listener_mode() {
+        let dir = Path::new("/tmp/chunks");
+        let rtmp = ffmpeg_args(
+            &Url::parse("rtmp://0.0.0.0:1935/live/key").unwrap(),
+            DEFAULT_PUSH_CHUNK,
+            dir,
+            None,
+        );
+        let listen = rtmp.iter().position(|a| a == "-listen").unwrap();
+        assert_eq!(rtmp[listen + 1], "1");
+        assert!(rtmp.contains(&"2.000".to_owned()));
+
+        let srt = ffmpeg_args(
+            &Url::parse("srt://0.0.0.0:9000").unwrap(),
+            DEFAULT_PUSH_CHUNK,
+            dir,
+            None,
+        );
+        assert!(!srt.contains(&"-listen".to_owned()));
+        assert!(srt.contains(&"srt://0.0.0.0:9000?mode=listener".to_owned()));
+
+        let caller = ffmpeg_args(
+            &Url::parse("srt://0.0.0.0:9000?mode=caller").unwrap(),
+            DEFAULT_PUSH_CHUNK,
+            dir,
+            None,
+        );
+        assert!(caller.contains(&"srt://0.0.0.0:9000?mode=caller".to_owned()));
+    }
+
+    #[test]
+    fn caption_relay_gets_the_whole_stream_as_flv() {
+        let args = ffmpeg_args(
+            &Url::parse("rtmp://0.0.0.0:1935/live/key").unwrap(),
+            DEFAULT_PUSH_CHUNK,
+            Path::new("/tmp/chunks"),
+            Some("127.0.0.1:40000".parse().unwrap()),
+        );
+        assert_eq!(
+            args[args.len() - 7..],
+            [
+                "-map",
+                "0",
+                "-c",
+                "copy",
+                "-f",
+                "flv",
+                "tcp://127.0.0.1:40000"
+            ]
+        );
+    }
+}
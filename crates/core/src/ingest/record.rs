@@ -0,0 +1,117 @@
+//! Recording of fetched playlists and segments for later replay
+//!
+//! A recording directory holds one file per fetched playlist or segment plus a
+//! `manifest.jsonl` listing them in fetch order with their offset from the
+//! start of the recording. [`crate::ingest::ReplayIngestor`] reads it back.
+
+use crate::ingest::{IngestError, IngestItem};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+use url::Url;
+
+pub const MANIFEST_FILE: &str = "manifest.jsonl";
+
+/// One line of `manifest.jsonl`
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RecordedEntry {
+    Playlist {
+        offset_ms: u64,
+        url: String,
+        file: String,
+    },
+    Segment {
+        offset_ms: u64,
+        sequence: u64,
+        url: String,
+        file: String,
+        duration_ms: u64,
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        discontinuity: bool,
+        #[serde(default, skip_serializing_if = "is_zero")]
+        gap_ms: u64,
+    },
+}
+
+struct RecorderState {
+    manifest: tokio::fs::File,
+    playlists: u64,
+    segments: u64,
+}
+
+/// Writes everything an ingestor fetches into a recording directory.
+pub struct IngestRecorder {
+    dir: PathBuf,
+    started: Instant,
+    state: Mutex<RecorderState>,
+}
+
+impl IngestRecorder {
+    /// Creates `dir` if needed and starts a fresh manifest in it.
+    pub async fn create(dir: impl AsRef<Path>) -> Result<Self, IngestError> {
+        let dir = dir.as_ref().to_path_buf();
+        tokio::fs::create_dir_all(&dir).await?;
+        let manifest = tokio::fs::File::create(dir.join(MANIFEST_FILE)).await?;
+        tracing::info!(dir = %dir.display(), "recording ingest");
+        Ok(Self {
+            dir,
+            started: Instant::now(),
+            state: Mutex::new(RecorderState {
+                manifest,
+                playlists: 0,
+                segments: 0,
+            }),
+        })
+    }
+
+    pub async fn record_playlist(&self, url: &Url, content: &str) -> Result<(), IngestError> {
+        let mut state = self.state.lock().await;
+        state.playlists += 1;
+        let file = format!("playlist-{:06}.m3u8", state.playlists);
+        tokio::fs::write(self.dir.join(&file), content).await?;
+        let entry = RecordedEntry::Playlist {
+            offset_ms: self.offset_ms(),
+            url: url.to_string(),
+            file,
+        };
+        append(&mut state.manifest, &entry).await
+    }
+
+    pub async fn record_segment(&self, item: &IngestItem) -> Result<(), IngestError> {
+        let mut state = self.state.lock().await;
+        state.segments += 1;
+        let file = format!("segment-{:06}.ts", state.segments);
+        tokio::fs::write(self.dir.join(&file), &item.bytes).await?;
+        let entry = RecordedEntry::Segment {
+            offset_ms: self.offset_ms(),
+            sequence: item.sequence,
+            url: item.url.to_string(),
+            file,
+            duration_ms: u64::try_from(item.approx_duration.as_millis()).unwrap_or(u64::MAX),
+            discontinuity: item.discontinuity,
+            gap_ms: u64::try_from(item.gap_before.as_millis()).unwrap_or(u64::MAX),
+        };
+        append(&mut state.manifest, &entry).await
+    }
+
+    fn offset_ms(&self) -> u64 {
+        u64::try_from(self.started.elapsed().as_millis()).unwrap_or(u64::MAX)
+    }
+}
+
+fn is_zero(ms: &u64) -> bool {
+    *ms == 0
+}
+
+async fn append(manifest: &mut tokio::fs::File, entry: &RecordedEntry) -> Result<(), IngestError> {
+    let mut line = serde_json::to_vec(entry)
+        .map_err(|e| IngestError::Replay(format!("failed to encode manifest entry: {e}")))?;
+    line.push(b'\n');
+    manifest.write_all(&line).await?;
+    // Flushed per entry so a recording cut short by a crash is still usable.
+    manifest.flush().await?;
+    Ok(())
+}
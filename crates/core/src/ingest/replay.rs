@@ -0,0 +1,162 @@
+//! Deterministic replay of a recorded ingest session
+
+use crate::ingest::record::{RecordedEntry, MANIFEST_FILE};
+use crate::ingest::{IngestError, IngestItem, Ingestor};
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::time::{Duration, Instant, SystemTime};
+use tokio::sync::mpsc::Sender;
+use url::Url;
+
+/// Replays the segments of a recording made with [`crate::ingest::IngestRecorder`].
+///
+/// Segments are emitted in recorded order with their original sequence numbers
+/// and URLs. In real-time mode (the default) each one is held back until its
+/// recorded offset; otherwise they are sent as fast as the pipeline accepts them,
+/// which is what tests want. The stream ends after the last segment.
+#[derive(Clone, Debug)]
+pub struct ReplayIngestor {
+    dir: PathBuf,
+    realtime: bool,
+}
+
+impl ReplayIngestor {
+    pub fn new(dir: impl AsRef<Path>) -> Self {
+        Self {
+            dir: dir.as_ref().to_path_buf(),
+            realtime: true,
+        }
+    }
+
+    pub fn with_realtime(mut self, realtime: bool) -> Self {
+        self.realtime = realtime;
+        self
+    }
+
+    /// Reads and parses the recording's manifest.
+    pub async fn entries(&self) -> Result<Vec<RecordedEntry>, IngestError> {
+        let manifest = tokio::fs::read_to_string(self.dir.join(MANIFEST_FILE)).await?;
+        manifest
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(n, line)| {
+                serde_json::from_str(line).map_err(|e| {
+                    IngestError::Replay(format!("{MANIFEST_FILE} line {}: {e}", n + 1))
+                })
+            })
+            .collect()
+    }
+
+    async fn replay(&self, tx: Sender<IngestItem>) -> Result<(), IngestError> {
+        let entries = self.entries().await?;
+        let started = Instant::now();
+        tracing::info!(dir = %self.dir.display(), entries = entries.len(), "replaying ingest");
+
+        for entry in entries {
+            let RecordedEntry::Segment {
+                offset_ms,
+                sequence,
+                url,
+                file,
+                duration_ms,
+                discontinuity,
+                gap_ms,
+            } = entry
+            else {
+                continue;
+            };
+
+            if self.realtime {
+                tokio::time::sleep_until((started + Duration::from_millis(offset_ms)).into()).await;
+            }
+
+            let bytes = tokio::fs::read(self.dir.join(&file)).await?;
+            let item = IngestItem {
+                sequence,
+                fetched_at: SystemTime::now(),
+                url: Url::parse(&url)?,
+                approx_duration: Duration::from_millis(duration_ms),
+                bytes: bytes.into(),
+                discontinuity,
+                gap_before: Duration::from_millis(gap_ms),
+            };
+            if tx.send(item).await.is_err() {
+                // Receiver gone: the pipeline is shutting down.
+                return Ok(());
+            }
+        }
+
+        tracing::info!("replay finished");
+        Ok(())
+    }
+}
+
+impl Ingestor for ReplayIngestor {
+    fn start(
+        &self,
+        tx: Sender<IngestItem>,
+    ) -> Pin<Box<dyn Future<Output = Result<(), IngestError>> + Send + 'static>> {
+        let this = self.clone();
+        Box::pin(async move { this.replay(tx).await })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ingest::IngestRecorder;
+    use bytes::Bytes;
+
+    fn item(sequence: u64, payload: &'static [u8]) -> IngestItem {
+        IngestItem {
+            sequence,
+            fetched_at: SystemTime::now(),
+            url: Url::parse(&format!("https://cdn.example/seg{sequence}.ts")).unwrap(),
+            approx_duration: Duration::from_secs(2),
+            bytes: Bytes::from_static(payload),
+            discontinuity: false,
+            gap_before: Duration::ZERO,
+        }
+    }
+
+    #[tokio::test]
+    async fn replays_recorded_segments_in_order() {
+        let dir = std::env::temp_dir().join(format!("ingest-replay-{}", std::process::id()));
+        let recorder = IngestRecorder::create(&dir).await.unwrap();
+        let playlist_url = Url::parse("https://cdn.example/index.m3u8").unwrap();
+        recorder
+            .record_playlist(&playlist_url, "#EXTM3U\n")
+            .await
+            .unwrap();
+        recorder.record_segment(&item(7, b"first")).await.unwrap();
+        recorder.record_segment(&item(8, b"second")).await.unwrap();
+
+        let replay = ReplayIngestor::new(&dir).with_realtime(false);
+        assert_eq!(replay.entries().await.unwrap().len(), 3);
+
+        let (tx, mut rx) = tokio::sync::mpsc::channel(8);
+        replay.start(tx).await.unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let first = rx.recv().await.unwrap();
+        let second = rx.recv().await.unwrap();
+        assert!(rx.recv().await.is_none());
+        assert_eq!(first.sequence, 7);
+        assert_eq!(first.bytes, Bytes::from_static(b"first"));
+        assert_eq!(first.approx_duration, Duration::from_secs(2));
+        assert_eq!(second.url.as_str(), "https://cdn.example/seg8.ts");
+    }
+
+    #[tokio::test]
+    async fn reports_corrupt_manifest_lines() {
+        let dir = std::env::temp_dir().join(format!("ingest-replay-bad-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join(MANIFEST_FILE), "{\"kind\":\"segment\"}\n").unwrap();
+
+        let err = ReplayIngestor::new(&dir).entries().await.unwrap_err();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(err.to_string().contains("line 1"));
+    }
+}
@@ -1,26 +1,98 @@
-use crate::ingest::{IngestError, IngestItem, Ingestor};
-use bytes::Bytes;
-use m3u8_rs::Playlist;
+//! Twitch live ingest
+//!
+//! Layers Twitch's stream lookup and playback-token handling on top of the
+//! generic [`HlsIngestor`]: a channel name is resolved through Helix and GQL to
+//! a signed usher URL, which is renewed before the token expires. VOD URLs
+//! (`https://www.twitch.tv/videos/ID`) are resolved the same way.
+
+use crate::config::{VariantQuality, DEFAULT_SEGMENT_PREFETCH};
+use crate::ingest::hls::{
+    HlsIngestor, HlsOptions, MediaPlaylistLocation, PlaylistSource, ResolvedPlaylist,
+};
+use crate::ingest::{IngestError, IngestItem, IngestRecorder, Ingestor};
+use crate::session::SessionStore;
+use crate::status::{PipelineStatus, StreamMetadata};
+use crate::util::{HttpRequest, ReqwestTransport, RetryConfig, SharedTransport};
+use futures::future::BoxFuture;
+use futures::FutureExt;
 use reqwest::Client;
 use std::future::Future;
 use std::pin::Pin;
-use std::time::{Duration, SystemTime};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tokio::sync::mpsc::Sender;
 use url::Url;
 
+/// Assumed playback token lifetime when the token does not state its expiry.
+const DEFAULT_TOKEN_LIFETIME: Duration = Duration::from_secs(60 * 60);
+
+const DEFAULT_METADATA_REFRESH: Duration = Duration::from_secs(60);
+
 #[derive(Clone, Debug)]
 pub struct TwitchIngestOptions {
-    pub audio_only: bool,
+    pub quality: VariantQuality,
+    /// Retries for a failed segment fetch, within the segment's playout time.
     pub max_retries: u32,
+    /// Delay before the first segment retry; doubles on each further attempt.
     pub retry_delay_ms: u64,
+    /// How long before the playback token expires to fetch a new one.
+    pub token_refresh_margin_secs: u64,
+    /// Keep polling at this interval while the channel is offline instead of failing.
+    pub wait_for_live: Option<Duration>,
+    /// Fixed media playlist poll interval; `None` follows the playlist's
+    /// target duration.
+    pub playlist_poll: Option<Duration>,
+    /// Step down to a lower bitrate variant when downloads fall behind.
+    pub adaptive: bool,
+    /// Most segments fetched at once.
+    pub prefetch: usize,
+    /// How often to refresh the channel title, category and viewer count
+    /// reported to the status handle; `None` only reads them at startup.
+    pub metadata_refresh: Option<Duration>,
 }
 
 impl Default for TwitchIngestOptions {
     fn default() -> Self {
         Self {
-            audio_only: true,
+            quality: VariantQuality::AudioOnly,
             max_retries: 3,
-            retry_delay_ms: 1000,
+            retry_delay_ms: 250,
+            token_refresh_margin_secs: 300,
+            wait_for_live: None,
+            playlist_poll: None,
+            adaptive: true,
+            prefetch: DEFAULT_SEGMENT_PREFETCH,
+            metadata_refresh: Some(DEFAULT_METADATA_REFRESH),
+        }
+    }
+}
+
+impl TwitchIngestOptions {
+    pub fn from_config(config: &crate::config::TwitchConfig) -> Self {
+        Self {
+            quality: config.hls_quality,
+            max_retries: config.segment_retries,
+            wait_for_live: config.wait_for_live_secs.map(Duration::from_secs),
+            playlist_poll: config.playlist_poll_ms.map(Duration::from_millis),
+            adaptive: config.adaptive_quality,
+            prefetch: config.segment_prefetch,
+            ..Self::default()
+        }
+    }
+
+    /// The settings that apply to any HLS stream, Twitch or not.
+    pub fn hls_options(&self) -> HlsOptions {
+        HlsOptions {
+            quality: self.quality,
+            refresh_margin_secs: self.token_refresh_margin_secs,
+            segment_retry: RetryConfig {
+                max_attempts: self.max_retries + 1,
+                initial_delay: Duration::from_millis(self.retry_delay_ms),
+                ..HlsOptions::default().segment_retry
+            },
+            poll_interval: self.playlist_poll,
+            adaptive: self.adaptive,
+            prefetch: self.prefetch,
         }
     }
 }
@@ -30,7 +102,10 @@ pub struct TwitchHlsIngestor {
     _twitch_config: crate::config::TwitchConfig,
     input: crate::config::InputSource,
     options: TwitchIngestOptions,
-    client: Client,
+    transport: SharedTransport,
+    recorder: Option<Arc<IngestRecorder>>,
+    status: Option<PipelineStatus>,
+    session: Option<Arc<SessionStore>>,
 }
 
 impl TwitchHlsIngestor {
@@ -48,62 +123,222 @@ impl TwitchHlsIngestor {
             _twitch_config: twitch_config,
             input,
             options,
-            client,
+            transport: ReqwestTransport::new(client).shared(),
+            recorder: None,
+            status: None,
+            session: None,
         })
     }
 
-    async fn get_stream_url(&self) -> Result<Url, IngestError> {
-        match &self.input {
-            crate::config::InputSource::Url(url) => {
-                Url::parse(url).map_err(IngestError::InvalidUrl)
+    /// Sends requests through `transport` instead of the network, e.g. a
+    /// [`crate::util::MockTransport`] in tests.
+    pub fn with_transport(mut self, transport: SharedTransport) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    /// Writes every fetched playlist and segment to `recorder` as well.
+    pub fn with_recorder(mut self, recorder: Arc<IngestRecorder>) -> Self {
+        self.recorder = Some(recorder);
+        self
+    }
+
+    /// Reports recovered and dropped segments to `status`.
+    pub fn with_status(mut self, status: PipelineStatus) -> Self {
+        self.status = Some(status);
+        self
+    }
+
+    /// Resumes from and saves progress to `session`, see [`HlsIngestor::with_session`].
+    pub fn with_session(mut self, session: Arc<SessionStore>) -> Self {
+        self.session = Some(session);
+        self
+    }
+
+    /// Sends a trivial query to Twitch GQL to confirm the API is reachable and
+    /// accepts our client id, without touching any channel.
+    pub async fn check_reachable(&self) -> Result<(), IngestError> {
+        let request = HttpRequest::post("https://gql.twitch.tv/gql")
+            .with_header("Client-ID", &self._twitch_config.client_id)
+            .with_json(&serde_json::json!({ "query": "query { __typename }" }))?;
+        let response = self.transport.send(request).await?;
+
+        if !response.is_success() {
+            return Err(IngestError::HttpStatus(response.status, response.text()));
+        }
+        Ok(())
+    }
+
+    /// The HLS ingestor for this input: channel names and Twitch VOD URLs go
+    /// through the Twitch token lookup, other URLs are followed as-is.
+    fn hls(&self) -> Result<HlsIngestor, IngestError> {
+        let source: Arc<dyn PlaylistSource> = match &self.input {
+            crate::config::InputSource::Channel(channel) => Arc::new(self.channel_source(channel)),
+            crate::config::InputSource::Url(url) => match twitch_vod_id(url) {
+                Some(id) => Arc::new(TwitchVodSource {
+                    twitch_config: self._twitch_config.clone(),
+                    id,
+                    transport: self.transport.clone(),
+                }),
+                None => Arc::new(Url::parse(url)?),
+            },
+            crate::config::InputSource::Replay(_) => {
+                return Err(IngestError::Replay(
+                    "replay input is served by ReplayIngestor, not the Twitch ingestor".to_string(),
+                ))
+            }
+            crate::config::InputSource::Listen(url) => {
+                return Err(IngestError::UnsupportedListenUrl(format!(
+                    "{url} (pushed streams are served by PushIngestor, not the Twitch ingestor)"
+                )))
             }
-            crate::config::InputSource::Channel(channel) => {
-                self.get_channel_stream_url(channel).await
+            crate::config::InputSource::Microphone(_) => {
+                return Err(IngestError::Capture(
+                    "microphones are served by MicIngestor, not the Twitch ingestor".to_string(),
+                ))
             }
+            crate::config::InputSource::Peer(url) => {
+                return Err(IngestError::UnsupportedPeerUrl(format!(
+                    "{url} (ingest hosts are served by PeerIngestor, not the Twitch ingestor)"
+                )))
+            }
+        };
+        let mut hls = HlsIngestor::new(source)
+            .with_options(self.options.hls_options())
+            .with_transport(self.transport.clone());
+        if let Some(recorder) = &self.recorder {
+            hls = hls.with_recorder(recorder.clone());
+        }
+        if let Some(status) = &self.status {
+            hls = hls.with_status(status.clone());
         }
+        if let Some(session) = &self.session {
+            hls = hls.with_session(session.clone());
+        }
+        Ok(hls)
     }
 
-    async fn get_channel_stream_url(&self, channel: &str) -> Result<Url, IngestError> {
-        // Twitch Helix API endpoint for getting stream information
-        let api_url = format!(
-            "https://api.twitch.tv/helix/streams?user_login={}",
-            channel
-        );
+    fn channel_source(&self, channel: &str) -> TwitchChannelSource {
+        TwitchChannelSource {
+            twitch_config: self._twitch_config.clone(),
+            channel: channel.to_owned(),
+            transport: self.transport.clone(),
+            status: self.status.clone(),
+        }
+    }
 
-        tracing::info!("Fetching stream info for channel: {}", channel);
-        
-        let mut request = self.client
-            .get(&api_url)
-            .header("Client-ID", &self._twitch_config.client_id);
+    /// Keeps the status handle's channel metadata current; never returns.
+    async fn refresh_metadata(&self) {
+        let (crate::config::InputSource::Channel(channel), Some(status), Some(interval)) =
+            (&self.input, &self.status, self.options.metadata_refresh)
+        else {
+            return std::future::pending().await;
+        };
+        let source = self.channel_source(channel);
+        loop {
+            tokio::time::sleep(interval).await;
+            match source.fetch_metadata().await {
+                Ok(Some(metadata)) => status.set_stream_metadata(metadata),
+                Ok(None) => {}
+                Err(e) => tracing::debug!(error = %e, "stream metadata refresh failed"),
+            }
+        }
+    }
 
-        // Add OAuth token if available
-        if let Some(token) = &self._twitch_config.oauth_token {
-            request = request.header("Authorization", format!("Bearer {}", token));
+    /// Resolves the stream, polling while the channel is offline if
+    /// `wait_for_live` is set.
+    async fn wait_until_live(&self, hls: &HlsIngestor) -> Result<MediaPlaylistLocation, IngestError> {
+        let mut announced = false;
+        loop {
+            match hls.resolve_media_playlist().await {
+                Err(IngestError::ChannelOffline(channel)) => {
+                    let Some(interval) = self.options.wait_for_live else {
+                        return Err(IngestError::ChannelOffline(channel));
+                    };
+                    if !announced {
+                        tracing::info!("Channel '{}' is offline; checking again every {:?}", channel, interval);
+                        announced = true;
+                    }
+                    tokio::time::sleep(interval).await;
+                }
+                Ok(location) => {
+                    if announced {
+                        tracing::info!("Channel is live; starting");
+                    }
+                    return Ok(location);
+                }
+                Err(e) => return Err(e),
+            }
         }
+    }
+}
 
-        let response = request
-            .send()
-            .await
-            .map_err(|e| {
-                tracing::error!("Twitch API request failed: {}", e);
-                IngestError::Http(e)
-            })?;
+impl Ingestor for TwitchHlsIngestor {
+    fn start(
+        &self,
+        tx: Sender<IngestItem>,
+    ) -> Pin<Box<dyn Future<Output = Result<(), IngestError>> + Send + 'static>> {
+        let this = self.clone();
+        Box::pin(async move {
+            tracing::info!(
+                "Starting Twitch HLS ingestor for {:?} (quality: {})",
+                this.input,
+                this.options.quality
+            );
 
-        if !response.status().is_success() {
-            let status = response.status();
-            let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
-            tracing::error!("Twitch API error {}: {}", status, error_text);
-            // Use our new HttpStatus error variant
-            return Err(IngestError::HttpStatus(status.as_u16(), error_text));
+            let hls = this.hls()?;
+            let location = this.wait_until_live(&hls).await?;
+            tokio::select! {
+                result = hls.process_playlist(location, tx) => result,
+                () = this.refresh_metadata() => unreachable!("metadata refresh never returns"),
+            }
+        })
+    }
+}
+
+/// Resolves a channel name to a signed usher playlist URL.
+struct TwitchChannelSource {
+    twitch_config: crate::config::TwitchConfig,
+    channel: String,
+    transport: SharedTransport,
+    /// Receives the title, category and viewer count seen while resolving.
+    status: Option<PipelineStatus>,
+}
+
+impl TwitchChannelSource {
+    /// Sends an authenticated GET for `path` under the Helix API and returns
+    /// its JSON body.
+    async fn helix_get(&self, path: &str) -> Result<serde_json::Value, IngestError> {
+        let mut request = HttpRequest::get(format!("https://api.twitch.tv/helix/{path}"))
+            .with_header("Client-ID", &self.twitch_config.client_id);
+        if let Some(token) = &self.twitch_config.oauth_token {
+            request = request.with_header("Authorization", format!("Bearer {}", token));
         }
+        let response = self.transport.send(request).await?;
+        if !response.is_success() {
+            return Err(IngestError::HttpStatus(response.status, response.text()));
+        }
+        Ok(response.json()?)
+    }
 
-        let stream_data: serde_json::Value = response
-            .json()
+    /// The channel's current title, category and viewer count from Helix, or
+    /// `None` if it is offline.
+    async fn fetch_metadata(&self) -> Result<Option<StreamMetadata>, IngestError> {
+        let body = self
+            .helix_get(&format!("streams?user_login={}", self.channel))
+            .await?;
+        Ok(body["data"].get(0).map(stream_metadata))
+    }
+
+    async fn get_channel_stream_url(&self) -> Result<ResolvedPlaylist, IngestError> {
+        let channel = self.channel.as_str();
+
+        tracing::info!("Fetching stream info for channel: {}", channel);
+
+        let stream_data = self
+            .helix_get(&format!("streams?user_login={}", channel))
             .await
-            .map_err(|e| {
-                tracing::error!("Failed to parse Twitch API response: {}", e);
-                IngestError::Http(e)
-            })?;
+            .inspect_err(|e| tracing::error!("Twitch API request failed: {}", e))?;
 
         tracing::debug!("Twitch API response: {:?}", stream_data);
 
@@ -117,7 +352,7 @@ impl TwitchHlsIngestor {
 
         if streams.is_empty() {
             tracing::warn!("Channel '{}' is not live or not found", channel);
-            return Err(IngestError::HttpStatus(404, format!("Channel '{}' is not live", channel)));
+            return Err(IngestError::ChannelOffline(channel.to_string()));
         }
 
         // For now, we'll use a placeholder approach since getting actual HLS URLs
@@ -129,6 +364,9 @@ impl TwitchHlsIngestor {
         
         // Extract the user ID from the response
         let user_data = &streams[0];
+        if let Some(status) = &self.status {
+            status.set_stream_metadata(stream_metadata(user_data));
+        }
         let _user_id = user_data["user_id"]
             .as_str()
             .ok_or_else(|| {
@@ -137,23 +375,30 @@ impl TwitchHlsIngestor {
             })?;
 
         // Get the stream access token via Twitch GQL API
-        let (token, sig) = self.get_stream_access_token(channel).await?;
+        let (token, sig) = self.get_stream_access_token().await?;
         
         // Construct the HLS URL with the actual token and signature
         let hls_url = format!(
             "https://usher.ttvnw.net/api/channel/hls/{}.m3u8?client_id={}&token={}&sig={}&allow_audio_only=true&allow_source=true&type=any&p={}", 
             channel, 
-            &self._twitch_config.client_id,
+            &self.twitch_config.client_id,
             urlencoding::encode(&token),
             urlencoding::encode(&sig),
             rand::random::<u32>()
         );
         
+        let expires_at = token_expiry(&token)
+            .unwrap_or_else(|| SystemTime::now() + DEFAULT_TOKEN_LIFETIME);
+
         tracing::info!("Constructed HLS URL for channel '{}'", channel);
-        Url::parse(&hls_url).map_err(IngestError::InvalidUrl)
+        Ok(ResolvedPlaylist {
+            url: Url::parse(&hls_url).map_err(IngestError::InvalidUrl)?,
+            expires_at: Some(expires_at),
+        })
     }
 
-    async fn get_stream_access_token(&self, channel: &str) -> Result<(String, String), IngestError> {
+    async fn get_stream_access_token(&self) -> Result<(String, String), IngestError> {
+        let channel = self.channel.as_str();
         // Twitch GQL API endpoint
         let gql_url = "https://gql.twitch.tv/gql";
         
@@ -169,36 +414,33 @@ impl TwitchHlsIngestor {
         // This is the client ID used by Twitch's web interface
         let gql_client_id = "kimne78kx3ncx6brgo4mv6wki5h1ko";
         
-        let mut request = self.client
-            .post(gql_url)
-            .header("Client-ID", gql_client_id)
-            .header("Content-Type", "application/json");
+        let mut request = HttpRequest::post(gql_url)
+            .with_header("Client-ID", gql_client_id)
+            .with_header("Content-Type", "application/json");
 
         // Add OAuth token if available (required for private/age-restricted streams)
         // Note: For public streams, no Authorization header is needed
-        if let Some(token) = &self._twitch_config.oauth_token {
-            request = request.header("Authorization", format!("Bearer {}", token));
+        if let Some(token) = &self.twitch_config.oauth_token {
+            request = request.with_header("Authorization", format!("Bearer {}", token));
         }
 
-        let response = request
-            .json(&query)
-            .send()
+        let response = self.transport
+            .send(request.with_json(&query)?)
             .await
             .map_err(|e| {
                 tracing::error!("Twitch GQL API request failed: {}", e);
                 IngestError::Http(e)
             })?;
 
-        if !response.status().is_success() {
-            let status = response.status();
-            let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
+        if !response.is_success() {
+            let status = response.status;
+            let error_text = response.text();
             tracing::error!("Twitch GQL API error {}: {}", status, error_text);
-            return Err(IngestError::HttpStatus(status.as_u16(), error_text));
+            return Err(IngestError::HttpStatus(status, error_text));
         }
 
         let gql_response: serde_json::Value = response
             .json()
-            .await
             .map_err(|e| {
                 tracing::error!("Failed to parse Twitch GQL response: {}", e);
                 IngestError::Http(e)
@@ -246,162 +488,362 @@ impl TwitchHlsIngestor {
         tracing::info!("Successfully obtained stream access token for channel '{}'", channel);
         Ok((token, sig))
     }
+}
 
-    async fn fetch_playlist(&self, url: &Url) -> Result<String, IngestError> {
-        let response = self.client
-            .get(url.as_str())
-            .send()
-            .await
-            .map_err(IngestError::Http)?;
+impl PlaylistSource for TwitchChannelSource {
+    fn resolve(&self) -> BoxFuture<'_, Result<ResolvedPlaylist, IngestError>> {
+        self.get_channel_stream_url().boxed()
+    }
+
+    /// Usher answers 404 for channels that are not live.
+    fn not_found(&self) -> IngestError {
+        IngestError::ChannelOffline(self.channel.clone())
+    }
+}
 
-        if !response.status().is_success() {
-            let status = response.status();
-            let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
-            return Err(IngestError::HttpStatus(status.as_u16(), error_text));
+/// Resolves a VOD id to a signed usher playlist URL.
+struct TwitchVodSource {
+    twitch_config: crate::config::TwitchConfig,
+    id: String,
+    transport: SharedTransport,
+}
+
+impl TwitchVodSource {
+    async fn get_vod_url(&self) -> Result<ResolvedPlaylist, IngestError> {
+        let query = serde_json::json!({
+            "query": "query PlaybackAccessToken($id: ID!) { videoPlaybackAccessToken(id: $id, params: {platform: \"web\", playerType: \"site\"}) { value signature } }",
+            "variables": { "id": self.id }
+        });
+        // The GQL endpoint only accepts the web client's id.
+        let mut request = HttpRequest::post("https://gql.twitch.tv/gql")
+            .with_header("Client-ID", "kimne78kx3ncx6brgo4mv6wki5h1ko")
+            .with_header("Content-Type", "application/json");
+        // Subscriber-only VODs need the viewer's token.
+        if let Some(token) = &self.twitch_config.oauth_token {
+            request = request.with_header("Authorization", format!("Bearer {}", token));
+        }
+        let response = self.transport.send(request.with_json(&query)?).await?;
+        if !response.is_success() {
+            return Err(IngestError::HttpStatus(response.status, response.text()));
+        }
+        let body: serde_json::Value = response.json()?;
+        let access = &body["data"]["videoPlaybackAccessToken"];
+        if access.is_null() {
+            return Err(self.not_found());
         }
+        let (Some(token), Some(sig)) = (access["value"].as_str(), access["signature"].as_str())
+        else {
+            tracing::error!("Twitch GQL response missing videoPlaybackAccessToken fields");
+            return Err(IngestError::TwitchGqlMissingFields);
+        };
 
-        response
-            .text()
-            .await
-            .map_err(IngestError::Http)
+        let hls_url = format!(
+            "https://usher.ttvnw.net/vod/{}.m3u8?client_id={}&token={}&sig={}&allow_audio_only=true&allow_source=true&p={}",
+            self.id,
+            &self.twitch_config.client_id,
+            urlencoding::encode(token),
+            urlencoding::encode(sig),
+            rand::random::<u32>()
+        );
+        tracing::info!("Constructed HLS URL for VOD {}", self.id);
+        Ok(ResolvedPlaylist {
+            url: Url::parse(&hls_url)?,
+            expires_at: Some(
+                token_expiry(token).unwrap_or_else(|| SystemTime::now() + DEFAULT_TOKEN_LIFETIME),
+            ),
+        })
     }
+}
 
-    async fn fetch_media_segment(&self, url: &Url) -> Result<Bytes, IngestError> {
-        let response = self.client
-            .get(url.as_str())
-            .send()
-            .await
-            .map_err(IngestError::Http)?;
+impl PlaylistSource for TwitchVodSource {
+    fn resolve(&self) -> BoxFuture<'_, Result<ResolvedPlaylist, IngestError>> {
+        self.get_vod_url().boxed()
+    }
+
+    fn not_found(&self) -> IngestError {
+        IngestError::HttpStatus(404, format!("VOD {} not found or not available", self.id))
+    }
+}
 
-        if !response.status().is_success() {
-            let status = response.status();
-            let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
-            return Err(IngestError::HttpStatus(status.as_u16(), error_text));
+/// The id of a Twitch VOD URL such as `https://www.twitch.tv/videos/123`.
+fn twitch_vod_id(url: &str) -> Option<String> {
+    let url = Url::parse(url).ok()?;
+    let host = url.host_str()?;
+    if host != "twitch.tv" && !host.ends_with(".twitch.tv") {
+        return None;
+    }
+    let mut path = url.path_segments()?;
+    match (path.next(), path.next()) {
+        (Some("videos"), Some(id)) if !id.is_empty() && id.bytes().all(|b| b.is_ascii_digit()) => {
+            Some(id.to_owned())
         }
+        _ => None,
+    }
+}
 
-        response
-            .bytes()
-            .await
-            .map_err(IngestError::Http)
+/// Picks the fields we report out of one Helix `streams` entry.
+fn stream_metadata(stream: &serde_json::Value) -> StreamMetadata {
+    StreamMetadata {
+        title: stream["title"].as_str().unwrap_or_default().to_owned(),
+        game: stream["game_name"].as_str().unwrap_or_default().to_owned(),
+        viewer_count: stream["viewer_count"].as_u64().unwrap_or_default(),
     }
+}
 
-    async fn process_playlist(&self, playlist_url: Url, tx: Sender<IngestItem>) -> Result<(), IngestError> {
-        let mut sequence = 0u64;
-        let mut last_segment_url: Option<Url> = None;
-        let mut target_duration;
-        let mut media_playlist_url = playlist_url.clone();
+/// Reads the `expires` unix timestamp out of a playback access token.
+fn token_expiry(token: &str) -> Option<SystemTime> {
+    let value: serde_json::Value = serde_json::from_str(token).ok()?;
+    let expires = value.get("expires")?.as_u64()?;
+    Some(UNIX_EPOCH + Duration::from_secs(expires))
+}
 
-        // If we get a master playlist, extract the media playlist URL
-        let initial_content = self.fetch_playlist(&media_playlist_url).await?;
-        let (_remaining, initial_parsed) = m3u8_rs::parse_playlist(&initial_content.as_bytes())
-            .map_err(|e| {
-                tracing::error!("HLS initial parse error: {:?}", e);
-                tracing::debug!("Initial playlist content: {}", initial_content);
-                IngestError::HlsParse
-            })?;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{InputSource, TwitchConfig};
+    use crate::status::PipelineEvent;
+    use crate::util::{HttpMethod, MockTransport};
+
+    #[test]
+    fn reads_expiry_from_playback_token() {
+        let token = r#"{"adblock":false,"expires":1700000000,"channel":"x"}"#;
+        assert_eq!(
+            token_expiry(token),
+            Some(UNIX_EPOCH + Duration::from_secs(1_700_000_000))
+        );
+        assert_eq!(token_expiry("not json"), None);
+    }
 
-        // Handle master playlist by selecting the appropriate variant
-        if let Playlist::MasterPlaylist(master) = initial_parsed {
-            tracing::info!("Received master playlist with {} variants", master.variants.len());
-            
-            // Select variant based on audio_only option
-            let selected_variant = if self.options.audio_only {
-                // Try to find audio-only variant first
-                master.variants.iter()
-                    .find(|v| v.audio.is_some() || v.codecs.as_ref().map(|c| c.contains("mp4a")).unwrap_or(false))
-                    .or_else(|| master.variants.first())
-            } else {
-                // Select first variant (usually highest quality)
-                master.variants.first()
-            };
-
-            let variant = selected_variant.ok_or_else(|| {
-                tracing::error!("No variants found in master playlist");
-                IngestError::HlsParse
-            })?;
+    #[test]
+    fn recognizes_vod_urls() {
+        assert_eq!(
+            twitch_vod_id("https://www.twitch.tv/videos/2131415161?t=1h"),
+            Some("2131415161".to_owned())
+        );
+        assert_eq!(
+            twitch_vod_id("https://twitch.tv/videos/12/"),
+            Some("12".to_owned())
+        );
+        assert_eq!(twitch_vod_id("https://www.twitch.tv/somechannel"), None);
+        assert_eq!(twitch_vod_id("https://cdn.example/videos/12"), None);
+    }
 
-            media_playlist_url = playlist_url.join(&variant.uri)
-                .map_err(IngestError::InvalidUrl)?;
-            
-            tracing::info!("Selected variant: {} (codecs: {:?})", variant.uri, variant.codecs);
-        }
+    #[tokio::test]
+    async fn vod_urls_play_the_signed_vod_playlist_to_the_end() {
+        let mock = MockTransport::new()
+            .on(
+                HttpMethod::Post,
+                "gql.twitch.tv/gql",
+                200,
+                r#"{"data":{"videoPlaybackAccessToken":{"value":"{}","signature":"sig"}}}"#,
+            )
+            .on(
+                HttpMethod::Get,
+                "usher.ttvnw.net/vod/12.m3u8",
+                200,
+                "#EXTM3U\n#EXT-X-TARGETDURATION:10\n#EXTINF:10.0,\nhttps://cdn.example/seg0.ts\n#EXT-X-ENDLIST\n",
+            )
+            .on(HttpMethod::Get, "cdn.example/seg0.ts", 200, "ts");
+        let ingestor = TwitchHlsIngestor::new(
+            TwitchConfig::default(),
+            InputSource::Url("https://www.twitch.tv/videos/12".to_owned()),
+            TwitchIngestOptions::default(),
+        )
+        .unwrap()
+        .with_transport(mock.clone().shared());
+
+        let (tx, mut rx) = tokio::sync::mpsc::channel(4);
+        tokio::time::timeout(Duration::from_secs(5), ingestor.start(tx))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(rx.recv().await.unwrap().url.path(), "/seg0.ts");
+        assert!(rx.recv().await.is_none());
+        let gql = &mock.requests()[0];
+        assert!(String::from_utf8_lossy(gql.body.as_ref().unwrap()).contains(r#""id":"12""#));
+    }
 
-        loop {
-            let playlist_content = self.fetch_playlist(&media_playlist_url).await?;
-            
-            // Parse the HLS playlist
-            let (_remaining, parsed) = m3u8_rs::parse_playlist(&playlist_content.as_bytes())
-                .map_err(|e| {
-                    tracing::error!("HLS parse error: {:?}", e);
-                    tracing::debug!("Playlist content: {}", playlist_content);
-                    IngestError::HlsParse
-                })?;
-
-            match parsed {
-                Playlist::MasterPlaylist(_) => {
-                    tracing::error!("Received master playlist when expecting media playlist");
-                    return Err(IngestError::ExpectedMediaPlaylist);
-                }
-                Playlist::MediaPlaylist(playlist) => {
-                    // Set target duration
-                    target_duration = Duration::from_secs(playlist.target_duration);
-
-                    for segment in &playlist.segments {
-                        let segment_url = playlist_url
-                            .join(&segment.uri)
-                            .map_err(IngestError::InvalidUrl)?;
-
-                        // Skip if we've already processed this segment
-                        if last_segment_url.as_ref() == Some(&segment_url) {
-                            continue;
-                        }
-
-                        // Fetch the media segment
-                        tracing::debug!("Fetching segment: {}", segment_url);
-                        let bytes = self.fetch_media_segment(&segment_url).await?;
-                        tracing::debug!("Fetched segment: {} bytes from {}", bytes.len(), segment_url);
-
-                        let ingest_item = IngestItem {
-                            sequence,
-                            fetched_at: SystemTime::now(),
-                            url: segment_url.clone(),
-                            approx_duration: Duration::from_secs_f64(segment.duration as f64),
-                            bytes,
-                        };
-
-                        if tx.send(ingest_item).await.is_err() {
-                            return Err(IngestError::NotImplemented);
-                        }
-
-                        sequence += 1;
-                        last_segment_url = Some(segment_url);
-                    }
-                }
-            }
+    fn live_channel_mock(mock: MockTransport) -> MockTransport {
+        mock.on(
+            HttpMethod::Post,
+            "gql.twitch.tv/gql",
+            200,
+            r#"{"data":{"streamPlaybackAccessToken":{"value":"{}","signature":"sig"}}}"#,
+        )
+        .on(
+            HttpMethod::Get,
+            "usher.ttvnw.net",
+            200,
+            "#EXTM3U\n#EXTINF:2.0,\nhttps://cdn.example/seg0.ts\n",
+        )
+        .on(HttpMethod::Get, "cdn.example/seg0.ts", 200, "ts")
+    }
 
-            // Wait for the target duration before checking for new segments
-            tokio::time::sleep(target_duration).await;
-        }
+    fn channel_ingestor(mock: &MockTransport, options: TwitchIngestOptions) -> TwitchHlsIngestor {
+        TwitchHlsIngestor::new(
+            TwitchConfig::default(),
+            InputSource::Channel("somechannel".to_owned()),
+            options,
+        )
+        .unwrap()
+        .with_transport(mock.clone().shared())
     }
-}
 
-impl Ingestor for TwitchHlsIngestor {
-    fn start(
-        &self,
-        tx: Sender<IngestItem>,
-    ) -> Pin<Box<dyn Future<Output = Result<(), IngestError>> + Send + 'static>> {
-        let this = self.clone();
-        Box::pin(async move {
-            tracing::info!(
-                "Starting Twitch HLS ingestor for {:?} (audio_only: {})",
-                this.input,
-                this.options.audio_only
-            );
+    #[test]
+    fn config_maps_to_hls_options() {
+        let config = TwitchConfig {
+            hls_quality: VariantQuality::MaxHeight(480),
+            segment_retries: 5,
+            segment_prefetch: 2,
+            playlist_poll_ms: Some(500),
+            ..TwitchConfig::default()
+        };
+        let hls = TwitchIngestOptions::from_config(&config).hls_options();
+        assert_eq!(hls.quality, VariantQuality::MaxHeight(480));
+        assert_eq!(hls.segment_retry.max_attempts, 6);
+        assert_eq!(hls.prefetch, 2);
+        assert_eq!(hls.poll_interval, Some(Duration::from_millis(500)));
+    }
 
-            let stream_url = this.get_stream_url().await?;
-            tracing::info!("Using stream URL: {}", stream_url);
+    #[tokio::test]
+    async fn offline_channel_fails_without_wait_for_live() {
+        let mock = MockTransport::new().on(HttpMethod::Get, "helix/streams", 200, r#"{"data":[]}"#);
+        let (tx, _rx) = tokio::sync::mpsc::channel(1);
+        let err = channel_ingestor(&mock, TwitchIngestOptions::default())
+            .start(tx)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, IngestError::ChannelOffline(c) if c == "somechannel"));
+    }
 
-            this.process_playlist(stream_url, tx).await
-        })
+    #[tokio::test]
+    async fn waits_for_offline_channel_to_go_live() {
+        let mock = live_channel_mock(
+            MockTransport::new()
+                .on(HttpMethod::Get, "helix/streams", 200, r#"{"data":[]}"#)
+                .on(HttpMethod::Get, "helix/streams", 200, r#"{"data":[]}"#)
+                .on(HttpMethod::Get, "helix/streams", 200, r#"{"data":[{"user_id":"1"}]}"#),
+        );
+        let options = TwitchIngestOptions {
+            wait_for_live: Some(Duration::from_millis(5)),
+            ..Default::default()
+        };
+        let ingestor = channel_ingestor(&mock, options);
+
+        let (tx, mut rx) = tokio::sync::mpsc::channel(4);
+        let task = tokio::spawn(async move { ingestor.start(tx).await });
+        let item = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        task.abort();
+
+        assert_eq!(item.url.as_str(), "https://cdn.example/seg0.ts");
+        let polls = mock
+            .requests()
+            .iter()
+            .filter(|r| r.url.contains("helix/streams"))
+            .count();
+        assert_eq!(polls, 3);
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn reports_and_refreshes_stream_metadata() {
+        let mock = live_channel_mock(
+            MockTransport::new()
+                .on(
+                    HttpMethod::Get,
+                    "helix/streams",
+                    200,
+                    r#"{"data":[{"user_id":"1","title":"any%","game_name":"Celeste","viewer_count":42}]}"#,
+                )
+                .on(
+                    HttpMethod::Get,
+                    "helix/streams",
+                    200,
+                    r#"{"data":[{"user_id":"1","title":"any%","game_name":"Just Chatting","viewer_count":40}]}"#,
+                ),
+        );
+        let status = PipelineStatus::new();
+        let mut events = status.subscribe();
+        let options = TwitchIngestOptions {
+            metadata_refresh: Some(Duration::from_millis(20)),
+            ..Default::default()
+        };
+        let ingestor = channel_ingestor(&mock, options).with_status(status.clone());
+
+        let (tx, _rx) = tokio::sync::mpsc::channel(4);
+        let task = tokio::spawn(async move { ingestor.start(tx).await });
+        let first = tokio::time::timeout(Duration::from_secs(5), events.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        let second = tokio::time::timeout(Duration::from_secs(5), events.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        task.abort();
+
+        assert_eq!(
+            first,
+            PipelineEvent::StreamMetadata(StreamMetadata {
+                title: "any%".to_owned(),
+                game: "Celeste".to_owned(),
+                viewer_count: 42,
+            })
+        );
+        assert!(matches!(second, PipelineEvent::StreamMetadata(m) if m.game == "Just Chatting"));
+        assert_eq!(status.stream_metadata().unwrap().viewer_count, 40);
+    }
+
+    #[tokio::test]
+    async fn renews_token_when_media_playlist_is_forbidden() {
+        let token = r#"{\"expires\":4102444800}"#;
+        let mock = MockTransport::new()
+            .on(HttpMethod::Get, "api.twitch.tv/helix/streams", 200, r#"{"data":[{"user_id":"1"}]}"#)
+            .on(
+                HttpMethod::Post,
+                "gql.twitch.tv/gql",
+                200,
+                format!(r#"{{"data":{{"streamPlaybackAccessToken":{{"value":"{token}","signature":"sig"}}}}}}"#),
+            )
+            .on(
+                HttpMethod::Get,
+                "usher.ttvnw.net",
+                200,
+                "#EXTM3U\n#EXT-X-STREAM-INF:BANDWIDTH=1,CODECS=\"mp4a.40.2\"\nhttps://cdn.example/audio.m3u8\n",
+            )
+            .on(HttpMethod::Get, "cdn.example/audio.m3u8", 403, "token expired")
+            .on(
+                HttpMethod::Get,
+                "cdn.example/audio.m3u8",
+                200,
+                "#EXTM3U\n#EXT-X-TARGETDURATION:2\n#EXTINF:2.0,\nseg0.ts\n",
+            )
+            .on(HttpMethod::Get, "cdn.example/seg0.ts", 200, "ts");
+
+        let ingestor = TwitchHlsIngestor::new(
+            TwitchConfig::default(),
+            InputSource::Channel("somechannel".to_owned()),
+            TwitchIngestOptions::default(),
+        )
+        .unwrap()
+        .with_transport(mock.clone().shared());
+
+        let (tx, mut rx) = tokio::sync::mpsc::channel(4);
+        let task = tokio::spawn(async move { ingestor.start(tx).await });
+        let item = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        task.abort();
+
+        assert_eq!(item.url.as_str(), "https://cdn.example/seg0.ts");
+        let gql_calls = mock
+            .requests()
+            .iter()
+            .filter(|r| r.url.contains("gql.twitch.tv"))
+            .count();
+        assert_eq!(gql_calls, 2);
+    }
+}
@@ -1,12 +1,60 @@
+//! Without the default `native` feature only the text path is built: config,
+//! translation, emotion analysis, glossing, text rules and subtitles. That
+//! part compiles to wasm32, so a browser frontend can reuse it.
+
 #![deny(warnings)]
 
+#[cfg(feature = "sqlite")]
+pub mod archive;
+#[cfg(feature = "native")]
 pub mod asr;
+#[cfg(feature = "native")]
+pub mod bench;
+#[cfg(feature = "native")]
+pub mod cc;
+#[cfg(feature = "native")]
+pub mod chapters;
+#[cfg(feature = "native")]
+pub mod clip;
 pub mod config;
+#[cfg(feature = "native")]
+pub mod control;
+pub mod cost;
+#[cfg(feature = "native")]
 pub mod decode;
+#[cfg(feature = "native")]
+pub mod embeddings;
 pub mod emotion;
+pub mod error;
+#[cfg(feature = "native")]
+pub mod eval;
+pub mod gloss;
+#[cfg(feature = "native")]
 pub mod ingest;
+#[cfg(feature = "native")]
+pub mod memory;
+#[cfg(feature = "native")]
+pub mod peer;
+#[cfg(feature = "native")]
 pub mod pipeline;
+#[cfg(feature = "native")]
 pub mod playback;
+#[cfg(feature = "native")]
+pub mod preflight;
+#[cfg(feature = "native")]
+pub mod profile;
+#[cfg(feature = "native")]
+pub mod session;
+pub mod status;
+pub mod subtitles;
+#[cfg(feature = "native")]
+pub mod summary;
+pub mod text;
 pub mod translate;
 pub mod tts;
 pub mod util;
+#[cfg(feature = "native")]
+pub mod vod;
+pub mod watch;
+#[cfg(feature = "native")]
+pub mod webhook;
@@ -0,0 +1,172 @@
+//! Byte accounting for audio and segment data queued between stages
+//!
+//! Every queue between stages is bounded in items, but a segment, a PCM chunk
+//! and a synthesized utterance differ in size by orders of magnitude. A
+//! [`BufferBudget`] counts the bytes actually waiting in those queues: a
+//! producer reserves an item's size before queueing it and the consumer
+//! releases it on receipt. Once a cap is set and reached, new items are
+//! dropped instead of queued, so a long stall sheds audio rather than growing
+//! memory without bound.
+
+use crate::decode::PcmChunk;
+use crate::ingest::IngestItem;
+use crate::status::{PipelineStatus, Stage};
+use crate::tts::TtsAudio;
+use std::mem::size_of;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// Payload size of an item that is queued between stages
+pub trait Buffered {
+    fn buffered_bytes(&self) -> usize;
+}
+
+impl Buffered for IngestItem {
+    fn buffered_bytes(&self) -> usize {
+        self.bytes.len()
+    }
+}
+
+impl Buffered for PcmChunk {
+    fn buffered_bytes(&self) -> usize {
+        self.samples.len() * size_of::<f32>()
+    }
+}
+
+impl Buffered for TtsAudio {
+    fn buffered_bytes(&self) -> usize {
+        self.pcm_i16.len() * size_of::<i16>()
+    }
+}
+
+#[derive(Default)]
+struct BudgetState {
+    used: AtomicUsize,
+    /// Set while items are being refused, so the cap is logged once per stall.
+    full: AtomicBool,
+}
+
+/// Shared count of queued bytes, optionally capped. Clones share the count.
+#[derive(Clone, Default)]
+pub struct BufferBudget {
+    state: Arc<BudgetState>,
+    cap: Option<usize>,
+    status: Option<PipelineStatus>,
+}
+
+impl BufferBudget {
+    /// A budget that refuses items once `cap` bytes are queued; `None` only
+    /// counts.
+    pub fn new(cap: Option<usize>) -> Self {
+        Self {
+            cap,
+            ..Default::default()
+        }
+    }
+
+    /// Reports usage and refused items to `status`.
+    pub fn with_status(mut self, status: PipelineStatus) -> Self {
+        status.set_buffer_cap(self.cap);
+        status.set_buffered_bytes(self.used());
+        self.status = Some(status);
+        self
+    }
+
+    pub fn cap(&self) -> Option<usize> {
+        self.cap
+    }
+
+    /// Bytes currently reserved.
+    pub fn used(&self) -> usize {
+        self.state.used.load(Ordering::Acquire)
+    }
+
+    /// Reserves `bytes` for an item about to be queued for `stage`. Returns
+    /// false, counting the item as skipped by `stage`, if that would exceed
+    /// the cap; the caller then drops the item.
+    pub fn try_reserve(&self, stage: Stage, bytes: usize) -> bool {
+        let reserved = self
+            .state
+            .used
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |used| {
+                let next = used.saturating_add(bytes);
+                match self.cap {
+                    Some(cap) if next > cap => None,
+                    _ => Some(next),
+                }
+            });
+        match reserved {
+            Ok(used) => {
+                if self.state.full.swap(false, Ordering::Relaxed) {
+                    tracing::info!("queued audio is back under the buffer cap");
+                }
+                self.report(used.saturating_add(bytes));
+                true
+            }
+            Err(used) => {
+                if !self.state.full.swap(true, Ordering::Relaxed) {
+                    tracing::warn!(
+                        stage = stage.as_str(),
+                        used,
+                        cap = self.cap.unwrap_or_default(),
+                        "buffer cap reached, dropping queued audio until the pipeline catches up"
+                    );
+                }
+                if let Some(status) = &self.status {
+                    status.record_skipped(stage);
+                }
+                false
+            }
+        }
+    }
+
+    /// Gives back `bytes` reserved with [`Self::try_reserve`] once the item
+    /// has left its queue.
+    pub fn release(&self, bytes: usize) {
+        let used = self
+            .state
+            .used
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |used| {
+                Some(used.saturating_sub(bytes))
+            })
+            .unwrap_or_default();
+        self.report(used.saturating_sub(bytes));
+    }
+
+    fn report(&self, used: usize) {
+        if let Some(status) = &self.status {
+            status.set_buffered_bytes(used);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn refuses_items_over_the_cap_until_bytes_are_released() {
+        let status = PipelineStatus::new();
+        let budget = BufferBudget::new(Some(100)).with_status(status.clone());
+
+        assert!(budget.try_reserve(Stage::Asr, 60));
+        assert!(!budget.try_reserve(Stage::Asr, 60));
+        assert_eq!(budget.used(), 60);
+        assert_eq!(status.snapshot().buffered_bytes, 60);
+        assert_eq!(status.snapshot().buffer_cap_bytes, Some(100));
+        assert_eq!(status.snapshot().stage(Stage::Asr).unwrap().skipped, 1);
+
+        budget.release(60);
+        assert!(budget.try_reserve(Stage::Asr, 60));
+        assert_eq!(status.snapshot().buffered_bytes, 60);
+    }
+
+    #[test]
+    fn uncapped_budget_only_counts() {
+        let budget = BufferBudget::new(None);
+        assert!(budget.try_reserve(Stage::Playback, usize::MAX / 2));
+        assert!(budget.try_reserve(Stage::Playback, usize::MAX / 2));
+        budget.release(usize::MAX / 2);
+        assert_eq!(budget.used(), usize::MAX / 2);
+    }
+}
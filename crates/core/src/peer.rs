@@ -0,0 +1,302 @@
+//! Splitting a session across machines
+//!
+//! An ingest host fetches and decodes the stream and hands every
+//! [`PcmChunk`] to [`forward`], which sends it over TCP to a worker. The
+//! worker receives them with [`PeerIngestor`] and [`PeerDecoder`] in place
+//! of a stream ingestor and ffmpeg, and runs ASR, translation, TTS and
+//! playback as usual. Each chunk travels as a big-endian `u32` length
+//! followed by the chunk as JSON.
+
+use crate::decode::{AudioDecoder, DecodeError, PcmChunk};
+use crate::error::{Classify, ErrorClass};
+use crate::ingest::{IngestError, IngestItem, Ingestor};
+use futures::future::BoxFuture;
+use futures::{FutureExt, StreamExt};
+use serde::Deserialize;
+use std::future::Future;
+use std::pin::Pin;
+use std::time::{Duration, SystemTime};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc::Sender;
+use url::Url;
+
+/// Largest frame accepted, far above any chunk the decoder produces.
+const MAX_FRAME_BYTES: usize = 64 << 20;
+/// How long the ingest host waits for a worker to accept a connection.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+/// Decoded chunks buffered on the ingest host while one is being sent.
+const FORWARD_CAPACITY: usize = 8;
+
+#[derive(thiserror::Error, Debug)]
+pub enum PeerError {
+    #[error(transparent)]
+    Ingest(#[from] IngestError),
+
+    #[error(transparent)]
+    Decode(#[from] DecodeError),
+
+    #[error("failed to encode a chunk: {0}")]
+    Encode(#[from] serde_json::Error),
+}
+
+/// Fetches items with `ingest`, decodes them with `decode` and sends the audio
+/// to the worker at `peer` (`tcp://HOST:PORT`) until the stream ends. Audio
+/// decoded while no worker is reachable is dropped, and the first chunk sent
+/// after that is marked as a discontinuity.
+pub async fn forward<I, D>(ingest: &I, decode: &D, peer: &str) -> Result<(), PeerError>
+where
+    I: Ingestor,
+    D: AudioDecoder,
+{
+    let addr = peer_addr(peer)?;
+    let mut items = ingest.stream(FORWARD_CAPACITY);
+    let mut connection: Option<TcpStream> = None;
+    let mut lost = false;
+    while let Some(item) = items.next().await {
+        let mut pcm = match decode.decode_segment(item?).await {
+            Ok(pcm) => pcm,
+            Err(e) if e.class() == ErrorClass::Fatal => return Err(e.into()),
+            Err(e) => {
+                tracing::warn!(error = %e, "failed to decode a segment");
+                continue;
+            }
+        };
+
+        if connection.is_none() {
+            match tokio::time::timeout(CONNECT_TIMEOUT, TcpStream::connect(&addr)).await {
+                Ok(Ok(stream)) => {
+                    tracing::info!(peer, "connected to the worker");
+                    let _ = stream.set_nodelay(true);
+                    connection = Some(stream);
+                }
+                Ok(Err(e)) => {
+                    tracing::warn!(peer, error = %e, "worker unreachable; dropping audio");
+                    lost = true;
+                    continue;
+                }
+                Err(_) => {
+                    tracing::warn!(peer, "worker did not answer; dropping audio");
+                    lost = true;
+                    continue;
+                }
+            }
+        }
+        pcm.discontinuity |= std::mem::take(&mut lost);
+        let frame = encode_frame(&pcm)?;
+        if let Some(stream) = connection.as_mut() {
+            if let Err(e) = stream.write_all(&frame).await {
+                tracing::warn!(peer, error = %e, "lost the worker; reconnecting");
+                connection = None;
+                lost = true;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Receives chunks from ingest hosts on a `tcp://` address, one host at a
+/// time. Each item carries an encoded chunk for [`PeerDecoder`]. When a host
+/// disconnects, the next one to connect carries on the stream.
+#[derive(Clone)]
+pub struct PeerIngestor {
+    addr: String,
+    url: Url,
+}
+
+impl PeerIngestor {
+    /// Listens on `listen`, e.g. `tcp://0.0.0.0:7800`.
+    pub fn new(listen: &str) -> Result<Self, IngestError> {
+        Ok(Self {
+            addr: peer_addr(listen)?,
+            url: Url::parse(listen)?,
+        })
+    }
+
+    async fn run(&self, tx: Sender<IngestItem>) -> Result<(), IngestError> {
+        let listener = TcpListener::bind(&self.addr).await?;
+        tracing::info!(addr = %self.addr, "Waiting for an ingest host");
+        let mut sequence = 0u64;
+        loop {
+            let (stream, from) = listener.accept().await?;
+            tracing::info!(%from, "Ingest host connected");
+            let mut reader = BufReader::new(stream);
+            // Audio is missing between one host's last chunk and the next's first.
+            let mut reconnected = sequence > 0;
+            loop {
+                let frame = match read_frame(&mut reader).await {
+                    Ok(Some(frame)) => frame,
+                    Ok(None) => {
+                        tracing::info!(%from, "Ingest host disconnected");
+                        break;
+                    }
+                    Err(e) => {
+                        tracing::warn!(%from, error = %e, "dropping ingest host");
+                        break;
+                    }
+                };
+                let header: FrameHeader = match serde_json::from_slice(&frame) {
+                    Ok(header) => header,
+                    Err(e) => {
+                        tracing::warn!(%from, error = %e, "dropping ingest host after a bad chunk");
+                        break;
+                    }
+                };
+                let item = IngestItem {
+                    sequence,
+                    fetched_at: SystemTime::now(),
+                    url: self.url.clone(),
+                    approx_duration: header.duration_estimate,
+                    bytes: frame.into(),
+                    discontinuity: header.discontinuity || std::mem::take(&mut reconnected),
+                    gap_before: Duration::ZERO,
+                };
+                if tx.send(item).await.is_err() {
+                    return Ok(());
+                }
+                sequence += 1;
+            }
+        }
+    }
+}
+
+impl Ingestor for PeerIngestor {
+    fn start(
+        &self,
+        tx: Sender<IngestItem>,
+    ) -> Pin<Box<dyn Future<Output = Result<(), IngestError>> + Send + 'static>> {
+        let this = self.clone();
+        Box::pin(async move { this.run(tx).await })
+    }
+}
+
+/// Turns the items of [`PeerIngestor`] back into the chunks the ingest host
+/// decoded.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PeerDecoder;
+
+impl AudioDecoder for PeerDecoder {
+    fn decode_segment(&self, item: IngestItem) -> BoxFuture<'_, Result<PcmChunk, DecodeError>> {
+        async move {
+            let mut pcm: PcmChunk = serde_json::from_slice(&item.bytes)
+                .map_err(|e| DecodeError::InvalidPcm(format!("bad chunk from ingest host: {e}")))?;
+            pcm.sequence = item.sequence;
+            pcm.discontinuity = item.discontinuity;
+            Ok(pcm)
+        }
+        .boxed()
+    }
+}
+
+/// The fields [`PeerIngestor`] needs without decoding the samples.
+#[derive(Deserialize)]
+struct FrameHeader {
+    duration_estimate: Duration,
+    discontinuity: bool,
+}
+
+/// `HOST:PORT` of a `tcp://HOST:PORT` URL.
+fn peer_addr(peer: &str) -> Result<String, IngestError> {
+    let url = Url::parse(peer)?;
+    match (url.scheme(), url.host_str(), url.port()) {
+        ("tcp", Some(host), Some(port)) => Ok(format!("{host}:{port}")),
+        _ => Err(IngestError::UnsupportedPeerUrl(peer.to_owned())),
+    }
+}
+
+fn encode_frame(pcm: &PcmChunk) -> Result<Vec<u8>, serde_json::Error> {
+    let json = serde_json::to_vec(pcm)?;
+    let mut frame = Vec::with_capacity(4 + json.len());
+    frame.extend_from_slice(&(json.len() as u32).to_be_bytes());
+    frame.extend_from_slice(&json);
+    Ok(frame)
+}
+
+/// The next frame's payload, or `None` when the connection closed between
+/// frames.
+async fn read_frame(reader: &mut (impl AsyncRead + Unpin)) -> std::io::Result<Option<Vec<u8>>> {
+    let mut len = [0u8; 4];
+    match reader.read_exact(&mut len).await {
+        Ok(_) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    let len = u32::from_be_bytes(len) as usize;
+    if len > MAX_FRAME_BYTES {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("frame of {len} bytes is too large"),
+        ));
+    }
+    let mut frame = vec![0u8; len];
+    reader.read_exact(&mut frame).await?;
+    Ok(Some(frame))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode::PcmFormat;
+
+    fn chunk(sequence: u64) -> PcmChunk {
+        PcmChunk {
+            sequence,
+            started_at: SystemTime::now(),
+            fetched_at: SystemTime::now(),
+            format: PcmFormat::whisper_f32_mono_16khz(),
+            samples: vec![0.25; 1_600],
+            duration_estimate: Duration::from_millis(100),
+            discontinuity: false,
+        }
+    }
+
+    #[test]
+    fn only_tcp_urls_with_a_port_are_peers() {
+        assert_eq!(peer_addr("tcp://gpu-box:7800").unwrap(), "gpu-box:7800");
+        assert!(matches!(
+            peer_addr("tcp://gpu-box"),
+            Err(IngestError::UnsupportedPeerUrl(_))
+        ));
+        assert!(peer_addr("http://gpu-box:7800").is_err());
+    }
+
+    #[tokio::test]
+    async fn chunks_arrive_in_order_and_reconnects_are_flagged() {
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let ingest = PeerIngestor::new(&format!("tcp://127.0.0.1:{port}")).unwrap();
+        let mut items = ingest.stream(8);
+
+        let send = |chunks: Vec<PcmChunk>| async move {
+            let mut stream = loop {
+                match TcpStream::connect(("127.0.0.1", port)).await {
+                    Ok(stream) => break stream,
+                    Err(_) => tokio::time::sleep(Duration::from_millis(10)).await,
+                }
+            };
+            for pcm in &chunks {
+                stream.write_all(&encode_frame(pcm).unwrap()).await.unwrap();
+            }
+        };
+        send(vec![chunk(40), chunk(41)]).await;
+        send(vec![chunk(42)]).await;
+
+        let mut received = Vec::new();
+        for _ in 0..3 {
+            let item = items.next().await.unwrap().unwrap();
+            received.push(PeerDecoder.decode_segment(item).await.unwrap());
+        }
+        assert_eq!(
+            received.iter().map(|p| p.sequence).collect::<Vec<_>>(),
+            [0, 1, 2]
+        );
+        assert_eq!(
+            received.iter().map(|p| p.discontinuity).collect::<Vec<_>>(),
+            [false, false, true]
+        );
+        assert_eq!(received[0].samples, chunk(0).samples);
+    }
+}
@@ -0,0 +1,126 @@
+//! Per-stage deadlines
+//!
+//! A slow translation or TTS answer delays every sentence queued behind it,
+//! so the dub drifts further behind the stream with each one. With
+//! [`super::PipelineConfig::stage_deadlines`], [`within`] gives up on a call
+//! that takes longer than its stage's share of the latency budget. A
+//! translation that ran out of time is answered from [`RecentTranslations`]
+//! when the sentence was translated shortly before, and otherwise shown
+//! untranslated without being voiced. TTS deadlines are kept per backend by
+//! [`crate::tts::FallbackTtsClient`], so a slow cloud voice hands the
+//! sentence to the local one instead of dropping it.
+
+use crate::config::TargetLang;
+use crate::status::{PipelineEvent, PipelineStatus, Stage};
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::time::Duration;
+
+/// Translations kept to answer with when the translator is too slow.
+const RECENT_TRANSLATIONS: usize = 256;
+
+/// Output of `future`, or `None` once it has taken longer than `deadline`,
+/// which is counted and published for `stage`. Without a deadline the
+/// future always runs to completion.
+pub(super) async fn within<F: Future>(
+    stage: Stage,
+    deadline: Option<Duration>,
+    status: &PipelineStatus,
+    future: F,
+) -> Option<F::Output> {
+    let Some(deadline) = deadline else {
+        return Some(future.await);
+    };
+    match tokio::time::timeout(deadline, future).await {
+        Ok(output) => Some(output),
+        Err(_) => {
+            let deadline_ms = deadline.as_millis() as u64;
+            tracing::warn!(
+                stage = stage.as_str(),
+                deadline_ms,
+                "stage missed its deadline"
+            );
+            status.record_skipped(stage);
+            status.publish(PipelineEvent::DeadlineExceeded { stage, deadline_ms });
+            None
+        }
+    }
+}
+
+/// The last sentences translated, by target language
+#[derive(Debug, Default)]
+pub(super) struct RecentTranslations {
+    translations: HashMap<(String, String), String>,
+    order: VecDeque<(String, String)>,
+}
+
+impl RecentTranslations {
+    pub(super) fn get(&self, target_lang: &TargetLang, source: &str) -> Option<&str> {
+        self.translations
+            .get(&(target_lang.as_str().to_owned(), source.to_owned()))
+            .map(String::as_str)
+    }
+
+    pub(super) fn insert(&mut self, target_lang: &TargetLang, source: &str, translation: &str) {
+        let key = (target_lang.as_str().to_owned(), source.to_owned());
+        if self
+            .translations
+            .insert(key.clone(), translation.to_owned())
+            .is_none()
+        {
+            self.order.push_back(key);
+        }
+        while self.order.len() > RECENT_TRANSLATIONS {
+            if let Some(oldest) = self.order.pop_front() {
+                self.translations.remove(&oldest);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn calls_past_the_deadline_are_given_up_and_reported() {
+        let status = PipelineStatus::new();
+        let mut events = status.subscribe();
+        let slow = tokio::time::sleep(Duration::from_millis(200));
+        let deadline = Some(Duration::from_millis(20));
+        assert!(within(Stage::Tts, deadline, &status, slow).await.is_none());
+        assert!(matches!(
+            events.recv().await.unwrap(),
+            PipelineEvent::DeadlineExceeded {
+                stage: Stage::Tts,
+                deadline_ms: 20
+            }
+        ));
+        assert_eq!(status.snapshot().stage(Stage::Tts).unwrap().skipped, 1);
+
+        assert_eq!(
+            within(Stage::Tts, deadline, &status, async { 1 }).await,
+            Some(1)
+        );
+        assert_eq!(
+            within(Stage::Tts, None, &status, async { 2 }).await,
+            Some(2)
+        );
+    }
+
+    #[test]
+    fn recent_translations_are_kept_by_language_and_bounded() {
+        let de = TargetLang::new("de").unwrap();
+        let fr = TargetLang::new("fr").unwrap();
+        let mut recent = RecentTranslations::default();
+        recent.insert(&de, "hello", "hallo");
+        assert_eq!(recent.get(&de, "hello"), Some("hallo"));
+        assert_eq!(recent.get(&fr, "hello"), None);
+
+        for i in 0..RECENT_TRANSLATIONS {
+            recent.insert(&fr, &i.to_string(), "x");
+        }
+        assert_eq!(recent.get(&de, "hello"), None);
+        assert_eq!(recent.get(&fr, "0"), Some("x"));
+    }
+}
@@ -0,0 +1,150 @@
+//! Drops sentences ASR has just transcribed already
+//!
+//! Chunks that overlap, or segments that play again after a playlist jump,
+//! make Whisper emit the same sentence twice. A sentence is a repeat when its
+//! normalized text, ignoring case, punctuation and spacing, matches one seen
+//! within the window; repeats are cut before translation so they are not
+//! spoken twice.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Sentences normalized to fewer characters than this are never dropped,
+/// since short replies such as "yes" or "no" are often really said twice.
+const MIN_SENTENCE_CHARS: usize = 8;
+
+#[derive(Debug)]
+pub(crate) struct Deduplicator {
+    window: Duration,
+    recent: VecDeque<(Instant, String)>,
+}
+
+impl Deduplicator {
+    pub(crate) fn new(window: Duration) -> Self {
+        Self {
+            window,
+            recent: VecDeque::new(),
+        }
+    }
+
+    /// `text` without the sentences seen within the window before `at`, or
+    /// `None` when nothing new is left.
+    pub(crate) fn filter(&mut self, text: &str, at: Instant) -> Option<String> {
+        while let Some((seen, _)) = self.recent.front() {
+            if at.saturating_duration_since(*seen) <= self.window {
+                break;
+            }
+            self.recent.pop_front();
+        }
+
+        let mut kept = Vec::new();
+        let mut dropped = 0;
+        for sentence in sentences(text) {
+            let key = normalize(sentence);
+            if key.chars().count() < MIN_SENTENCE_CHARS {
+                kept.push(sentence);
+                continue;
+            }
+            if self.recent.iter().any(|(_, seen)| *seen == key) {
+                dropped += 1;
+                continue;
+            }
+            self.recent.push_back((at, key));
+            kept.push(sentence);
+        }
+        if dropped > 0 {
+            tracing::debug!(dropped, "dropped repeated sentences from a transcript");
+        }
+        let kept = kept.join(" ");
+        (!normalize(&kept).is_empty()).then_some(kept)
+    }
+}
+
+/// Splits `text` after each `.`, `!`, `?` or `…` that ends a word.
+fn sentences(text: &str) -> impl Iterator<Item = &str> {
+    let mut rest = text.trim();
+    std::iter::from_fn(move || {
+        if rest.is_empty() {
+            return None;
+        }
+        let mut end = rest.len();
+        let mut chars = rest.char_indices().peekable();
+        while let Some((i, c)) = chars.next() {
+            if matches!(c, '.' | '!' | '?' | '…')
+                && chars.peek().is_none_or(|(_, next)| next.is_whitespace())
+            {
+                end = i + c.len_utf8();
+                break;
+            }
+        }
+        let sentence = &rest[..end];
+        rest = rest[end..].trim_start();
+        Some(sentence.trim())
+    })
+}
+
+/// Lowercase words with punctuation removed, separated by single spaces.
+fn normalize(text: &str) -> String {
+    text.split_whitespace()
+        .map(|word| {
+            word.chars()
+                .filter(|c| c.is_alphanumeric())
+                .flat_map(char::to_lowercase)
+                .collect::<String>()
+        })
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repeated_sentences_are_dropped() {
+        let mut dedup = Deduplicator::new(Duration::from_secs(10));
+        let start = Instant::now();
+        assert_eq!(
+            dedup.filter("Welcome back, everyone. Let's go!", start),
+            Some("Welcome back, everyone. Let's go!".to_owned())
+        );
+        assert_eq!(
+            dedup.filter(
+                "welcome back everyone...  Today we play ranked.",
+                start + Duration::from_secs(2)
+            ),
+            Some("Today we play ranked.".to_owned())
+        );
+        assert_eq!(
+            dedup.filter("Today we play RANKED", start + Duration::from_secs(3)),
+            None
+        );
+    }
+
+    #[test]
+    fn sentences_return_after_the_window() {
+        let mut dedup = Deduplicator::new(Duration::from_secs(5));
+        let start = Instant::now();
+        assert!(dedup.filter("That was a great play.", start).is_some());
+        assert!(dedup
+            .filter("That was a great play.", start + Duration::from_secs(6))
+            .is_some());
+    }
+
+    #[test]
+    fn short_replies_are_kept() {
+        let mut dedup = Deduplicator::new(Duration::from_secs(10));
+        let start = Instant::now();
+        assert_eq!(dedup.filter("No. No.", start), Some("No. No.".to_owned()));
+        assert_eq!(dedup.filter("No!", start), Some("No!".to_owned()));
+    }
+
+    #[test]
+    fn decimals_do_not_split_sentences() {
+        assert_eq!(
+            sentences("It costs 2.50 now. Cheap!").collect::<Vec<_>>(),
+            ["It costs 2.50 now.", "Cheap!"]
+        );
+    }
+}
@@ -1,16 +1,40 @@
 use crate::{
-    config::{ApiKeys, AppConfig, LatencyBudget},
+    asr::{
+        lyrics,
+        rtf::{RtfMonitor, RTF_WINDOW},
+        split_non_speech, AsrBackend, AsrError, MusicDetector, TranscriptSegment,
+    },
+    config::{ApiKeys, AppConfig, LatencyBudget, MusicPolicy, TargetLang, VoiceMatch},
+    control::ControlHandle,
+    decode::{AudioDecoder, DecodeError, PcmChunk},
+    emotion::PitchTracker,
+    error::{Classify, ErrorClass},
+    ingest::{IngestError, Ingestor, JitterBuffer},
+    memory::{BufferBudget, Buffered},
+    playback::{
+        pacing::{speaking_rate, spoken_length, Pacer},
+        stretch::pitch_into_range,
+        PlaybackError, PlaybackFinished, PlaybackSink,
+    },
+    status::{PipelineEvent, PipelineStatus, Stage},
+    text::{InverseNormalize, RestorePunctuation, TextChain, TextChains, TextError},
+    translate::{TranslateError, Translation, Translator},
+    tts::{TtsAudio, TtsClient, TtsError, TtsRequest, VoiceId},
 };
+use deadline::{within, RecentTranslations};
+use dedup::Deduplicator;
+use futures::future::BoxFuture;
+use futures::{Stream, StreamExt};
+use std::ops::DerefMut;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use supervisor::Supervisor;
+use tokio::sync::{broadcast, Mutex};
+use tracing::{info_span, Instrument, Span};
 
-#[cfg(feature = "whisper-rs")]
-use crate::{
-    asr::AsrBackend,
-    decode::AudioDecoder,
-    ingest::Ingestor,
-    playback::PlaybackSink,
-    translate::Translator,
-    tts::TtsClient,
-};
+mod deadline;
+mod dedup;
+mod supervisor;
 
 #[derive(thiserror::Error, Debug)]
 pub enum PipelineError {
@@ -18,6 +42,36 @@ pub enum PipelineError {
     NotImplemented,
     #[error("internal channel closed")]
     ChannelClosed,
+    #[error("ingest failed: {0}")]
+    Ingest(#[from] IngestError),
+    #[error("decode failed: {0}")]
+    Decode(#[from] DecodeError),
+    #[error("asr failed: {0}")]
+    Asr(#[from] AsrError),
+    #[error("translation failed: {0}")]
+    Translate(#[from] TranslateError),
+    #[error("tts failed: {0}")]
+    Tts(#[from] TtsError),
+    #[error("playback failed: {0}")]
+    Playback(#[from] PlaybackError),
+    #[error("{} stage failed: {reason}", .stage.as_str())]
+    StageFailed { stage: Stage, reason: String },
+}
+
+impl Classify for PipelineError {
+    fn class(&self) -> ErrorClass {
+        match self {
+            Self::NotImplemented | Self::ChannelClosed | Self::StageFailed { .. } => {
+                ErrorClass::Fatal
+            }
+            Self::Ingest(e) => e.class(),
+            Self::Decode(e) => e.class(),
+            Self::Asr(e) => e.class(),
+            Self::Translate(e) => e.class(),
+            Self::Tts(e) => e.class(),
+            Self::Playback(e) => e.class(),
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -25,30 +79,207 @@ pub struct PipelineConfig {
     pub latency: LatencyBudget,
     pub api_keys: ApiKeys,
     pub target_lang: crate::config::TargetLang,
+    /// Run over each transcript as it leaves ASR, before it is published.
+    pub post_asr: TextChain,
+    /// Run over each transcript before translation.
+    pub pre_translate: TextChain,
+    /// Run over each translation before it is published and synthesized.
+    pub post_translate: TextChain,
+    /// Run over the text handed to TTS only.
+    pub pre_tts: TextChain,
+    /// Most chunks transcribed concurrently while ASR is behind.
+    pub asr_workers: usize,
+    /// Skip segments and chunks older than the latency budget instead of
+    /// decoding and transcribing them.
+    pub skip_stale: bool,
+    /// Give up on a translation or synthesis that takes longer than its
+    /// share of the latency budget.
+    pub stage_deadlines: bool,
+    /// Drop sentences transcribed again within this long. Off when `None`.
+    pub dedup_window: Option<Duration>,
+    /// Most bytes of segments and audio queued between stages; items beyond
+    /// it are dropped. Unlimited when `None`.
+    pub max_buffer_bytes: Option<usize>,
+    /// Take sounds the recognizer annotates out of transcripts and publish
+    /// them as [`PipelineEvent::NonSpeech`].
+    pub non_speech_events: bool,
+    /// What happens to transcripts while sustained music plays.
+    pub music_policy: MusicPolicy,
+    /// Speed up or hold back speech so it follows the speaker's rate and
+    /// bursts after slow TTS answers are smoothed out.
+    pub pace_speech: bool,
+    /// Dub in a voice of the speaker's range, male or female; off when
+    /// `None`.
+    pub voice_match: Option<VoiceMatch>,
 }
 
 impl PipelineConfig {
-    pub fn from_app(app: &AppConfig) -> Self {
-        Self {
+    pub fn from_app(app: &AppConfig) -> Result<Self, TextError> {
+        let TextChains {
+            pre_translate,
+            post_translate,
+            pre_tts,
+        } = TextChains::from_config(&app.text)?;
+        Ok(Self {
             latency: app.latency,
             api_keys: app.api_keys.clone(),
             target_lang: app.target_lang.clone(),
-        }
+            post_asr: post_asr_chain(&app.asr),
+            pre_translate,
+            post_translate,
+            pre_tts,
+            asr_workers: app.asr.workers,
+            skip_stale: app.asr.skip_stale,
+            stage_deadlines: app.stage_deadlines,
+            dedup_window: (app.asr.dedup_window_secs > 0)
+                .then(|| Duration::from_secs(app.asr.dedup_window_secs)),
+            max_buffer_bytes: app
+                .max_buffer_mb
+                .map(|mb| usize::try_from(mb.saturating_mul(1024 * 1024)).unwrap_or(usize::MAX)),
+            non_speech_events: app.asr.non_speech_events,
+            music_policy: app.asr.music_policy,
+            pace_speech: app.output.pace_speech,
+            voice_match: app.output.voice_match.clone(),
+        })
+    }
+
+    /// The configured text chains, used while no others are set at runtime
+    /// through [`ControlHandle::set_text_chains`].
+    fn text_chains(&self) -> Arc<TextChains> {
+        Arc::new(TextChains {
+            pre_translate: self.pre_translate.clone(),
+            post_translate: self.post_translate.clone(),
+            pre_tts: self.pre_tts.clone(),
+        })
+    }
+}
+
+/// Casing and punctuation come first, so numbers are written into the
+/// restored sentence.
+fn post_asr_chain(asr: &crate::config::AsrConfig) -> TextChain {
+    let mut chain = TextChain::default();
+    if asr.restore_punctuation {
+        chain = chain.with(RestorePunctuation::new(&asr.language));
+    }
+    if asr.inverse_normalize {
+        chain = chain.with(InverseNormalize::new(&asr.language));
     }
+    chain
 }
 
-#[cfg(feature = "whisper-rs")]
-pub struct Pipeline<I, D, A, Tr, Ts, P> {
+/// The stages of a session. Ingest, decode and ASR always run; translation,
+/// TTS and playback only when set, so e.g. a captioning session is just
+/// `Pipeline::new(..)` and a dub adds all three.
+pub struct Pipeline<I, D, A, Tr = Skipped, Ts = Skipped, P = Skipped> {
     pub ingest: I,
     pub decode: D,
     pub asr: A,
-    pub translate: Tr,
-    pub tts: Ts,
-    pub playback: P,
+    /// Without a translator, transcripts are spoken as they are.
+    pub translate: Option<Tr>,
+    /// Without TTS, the pipeline ends at the translator.
+    pub tts: Option<Ts>,
+    /// Without playback, speech only goes to [`PipelineStatus::subscribe_audio`].
+    pub playback: Option<P>,
     pub config: PipelineConfig,
+    pub status: PipelineStatus,
+    pub control: ControlHandle,
+}
+
+/// The type of a stage a [`Pipeline`] leaves out. It has no values, so a
+/// skipped stage can never be called.
+#[derive(Clone, Copy, Debug)]
+pub enum Skipped {}
+
+impl Translator for Skipped {
+    fn translate(
+        &self,
+        _text: String,
+        _target: TargetLang,
+    ) -> BoxFuture<'_, Result<Translation, TranslateError>> {
+        match *self {}
+    }
+}
+
+impl TtsClient for Skipped {
+    fn synthesize(&self, _request: TtsRequest) -> BoxFuture<'_, Result<TtsAudio, TtsError>> {
+        match *self {}
+    }
+}
+
+impl PlaybackSink for Skipped {
+    fn play(&self, _audio: TtsAudio) -> BoxFuture<'_, Result<(), PlaybackError>> {
+        match *self {}
+    }
+}
+
+impl<I, D, A> Pipeline<I, D, A> {
+    /// A pipeline that only transcribes.
+    pub fn new(
+        ingest: I,
+        decode: D,
+        asr: A,
+        config: PipelineConfig,
+        status: PipelineStatus,
+        control: ControlHandle,
+    ) -> Self {
+        Self {
+            ingest,
+            decode,
+            asr,
+            translate: None,
+            tts: None,
+            playback: None,
+            config,
+            status,
+            control,
+        }
+    }
+}
+
+impl<I, D, A, Tr, Ts, P> Pipeline<I, D, A, Tr, Ts, P> {
+    pub fn with_translator<T>(self, translate: T) -> Pipeline<I, D, A, T, Ts, P> {
+        Pipeline {
+            ingest: self.ingest,
+            decode: self.decode,
+            asr: self.asr,
+            translate: Some(translate),
+            tts: self.tts,
+            playback: self.playback,
+            config: self.config,
+            status: self.status,
+            control: self.control,
+        }
+    }
+
+    pub fn with_tts<T>(self, tts: T) -> Pipeline<I, D, A, Tr, T, P> {
+        Pipeline {
+            ingest: self.ingest,
+            decode: self.decode,
+            asr: self.asr,
+            translate: self.translate,
+            tts: Some(tts),
+            playback: self.playback,
+            config: self.config,
+            status: self.status,
+            control: self.control,
+        }
+    }
+
+    pub fn with_playback<T>(self, playback: T) -> Pipeline<I, D, A, Tr, Ts, T> {
+        Pipeline {
+            ingest: self.ingest,
+            decode: self.decode,
+            asr: self.asr,
+            translate: self.translate,
+            tts: self.tts,
+            playback: Some(playback),
+            config: self.config,
+            status: self.status,
+            control: self.control,
+        }
+    }
 }
 
-#[cfg(feature = "whisper-rs")]
 impl<I, D, A, Tr, Ts, P> Pipeline<I, D, A, Tr, Ts, P>
 where
     I: Ingestor + Clone + 'static,
@@ -59,164 +290,552 @@ where
     P: PlaybackSink + Clone + 'static,
 {
     pub async fn run(&self) -> Result<(), PipelineError> {
-        // Create channels for communication between components
-        let (ingest_tx, mut ingest_rx) =
-            tokio::sync::mpsc::channel::<crate::ingest::IngestItem>(self.channel_capacity());
-        let (pcm_tx, mut pcm_rx) =
-            tokio::sync::mpsc::channel::<crate::decode::PcmChunk>(self.channel_capacity());
-        let (transcript_tx, mut transcript_rx) =
-            tokio::sync::mpsc::channel::<crate::asr::TranscriptSegment>(self.channel_capacity());
-        let (translation_tx, mut translation_rx) =
-            tokio::sync::mpsc::channel::<crate::translate::Translation>(self.channel_capacity());
-        let (tts_tx, mut tts_rx) =
-            tokio::sync::mpsc::channel::<crate::tts::TtsAudio>(self.channel_capacity());
-
-        // Start the ingestor
-        let ingest_task: tokio::task::JoinHandle<Result<(), PipelineError>> = {
-            let ingest = self.ingest.clone();
-            tokio::spawn(async move {
-                ingest.start(ingest_tx).await.map_err(|e| {
-                    tracing::error!(error = %e, "ingestor failed");
-                    PipelineError::ChannelClosed
-                })
-            })
-        };
+        // Create channels for communication between components. Receivers
+        // are shared with the supervisor so they outlive a stage that dies.
+        let (pcm_tx, pcm_rx) =
+            tokio::sync::mpsc::channel::<(crate::decode::PcmChunk, Span)>(self.channel_capacity());
+        // Transcripts carry whether their translation may be voiced, which
+        // sung lyrics may not be.
+        let (transcript_tx, transcript_rx) =
+            tokio::sync::mpsc::channel::<(crate::asr::TranscriptSegment, bool, Span)>(
+                self.channel_capacity(),
+            );
+        // Translations and speech also carry the language epoch they were
+        // translated in, so a language switch can drop what is still queued,
+        // and when speech is paced, the speaker's rate (words per second)
+        // and how long the speaker took to say the clip. Translations also
+        // carry the diarized speaker, for TTS that voices each differently.
+        let (translation_tx, translation_rx) = tokio::sync::mpsc::channel::<(
+            crate::translate::Translation,
+            Option<f32>,
+            Option<String>,
+            u64,
+            Span,
+        )>(self.channel_capacity());
+        let (tts_tx, tts_rx) =
+            tokio::sync::mpsc::channel::<(crate::tts::TtsAudio, Option<Duration>, u64, Span)>(
+                self.channel_capacity(),
+            );
+        let pcm_rx = Arc::new(Mutex::new(pcm_rx));
+        let transcript_rx = Arc::new(Mutex::new(transcript_rx));
+        let translation_rx = Arc::new(Mutex::new(translation_rx));
+        let tts_rx = Arc::new(Mutex::new(tts_rx));
+        let budget =
+            BufferBudget::new(self.config.max_buffer_bytes).with_status(self.status.clone());
+        let mut supervisor = Supervisor::new(self.status.clone());
+        // Follows the speaker's pitch when the dub's voice is matched to it.
+        let pitch = self
+            .config
+            .voice_match
+            .as_ref()
+            .map(|_| PitchTracker::new());
 
-        // Start the decoder
-        let decode_task = {
+        // Start the decoder, which drives the ingestor
+        {
+            // With --skip-stale, segments the decoder gets to later than the
+            // latency budget after they were fetched are dropped before decoding.
+            let jitter = JitterBuffer::new(
+                self.config
+                    .skip_stale
+                    .then(|| self.config.latency.duration()),
+            )
+            .with_status(self.status.clone());
+            let ingest = self
+                .ingest
+                .stream_within(self.channel_capacity(), budget.clone(), jitter);
+            let ingest = Arc::new(Mutex::new(ingest));
             let decode = self.decode.clone();
-            tokio::spawn(async move {
-                while let Some(packet) = ingest_rx.recv().await {
-                    match decode.decode_segment(packet).await {
-                        Ok(pcm) => {
-                            if pcm_tx.send(pcm).await.is_err() {
-                                tracing::error!("pcm channel closed");
-                                return Err(PipelineError::ChannelClosed);
+            let status = self.status.clone();
+            let clips = self.control.clips().clone();
+            let budget = budget.clone();
+            let pitch = pitch.clone();
+            supervisor.add(Stage::Decode, move || {
+                let ingest = ingest.clone();
+                let decode = decode.clone();
+                let status = status.clone();
+                let clips = clips.clone();
+                let budget = budget.clone();
+                let pitch = pitch.clone();
+                let pcm_tx = pcm_tx.clone();
+                tokio::spawn(async move {
+                    let mut ingest = ingest.lock().await;
+                    while let Some(next) = ingest.next().await {
+                        let packet = match next {
+                            Ok(packet) => packet,
+                            Err(e) => {
+                                tracing::error!(error = %e, class = %e.class(), "ingestor failed");
+                                status.record_error(Stage::Ingest);
+                                status.set_stream_online(false);
+                                return Err(e.into());
                             }
-                        }
-                        Err(e) => {
-                            tracing::warn!(error = %e, "decode failed");
+                        };
+                        status.set_stream_online(true);
+                        status.record_processed(Stage::Ingest);
+                        status.set_queue_depth(Stage::Decode, ingest.queued());
+                        // Parent of each stage's span for this segment, so a
+                        // trace follows the audio through to playback.
+                        let utterance = info_span!(
+                            "utterance",
+                            sequence = packet.sequence,
+                            ingest_wait_ms =
+                                packet.fetched_at.elapsed().unwrap_or_default().as_millis() as u64,
+                        );
+                        let started = Instant::now();
+                        match decode
+                            .decode_segment(packet)
+                            .instrument(info_span!(parent: &utterance, "decode"))
+                            .await
+                        {
+                            Ok(pcm) => {
+                                status.record_latency(Stage::Decode, started.elapsed());
+                                status.record_processed(Stage::Decode);
+                                clips.push_audio(&pcm);
+                                if let Some(pitch) = &pitch {
+                                    pitch.observe(
+                                        &pcm.samples,
+                                        pcm.format.sample_rate,
+                                        pcm.format.channels,
+                                    );
+                                }
+                                if !budget.try_reserve(Stage::Asr, pcm.buffered_bytes()) {
+                                    continue;
+                                }
+                                if pcm_tx.send((pcm, utterance)).await.is_err() {
+                                    tracing::error!("pcm channel closed");
+                                    return Err(PipelineError::ChannelClosed);
+                                }
+                            }
+                            Err(e) => handle_item_error(Stage::Decode, e, &status)?,
                         }
                     }
-                }
-                Ok(())
-            })
-        };
+                    status.set_stream_online(false);
+                    Ok(())
+                })
+            });
+        }
 
         // Start the ASR
-        let asr_task = {
+        {
             let asr = self.asr.clone();
-            tokio::spawn(async move {
-                while let Some(pcm) = pcm_rx.recv().await {
-                    match asr.transcribe(pcm).await {
-                        Ok(transcript) => {
-                            if transcript_tx.send(transcript).await.is_err() {
-                                tracing::error!("transcript channel closed");
-                                return Err(PipelineError::ChannelClosed);
+            let status = self.status.clone();
+            let control = self.control.clone();
+            let clips = self.control.clips().clone();
+            let post_asr = self.config.post_asr.clone();
+            let budget = budget.clone();
+            // Transcripts go on only to a stage that reads them.
+            let forward = self.translate.is_some() || self.tts.is_some();
+            let workers = self.config.asr_workers;
+            let max_age = self
+                .config
+                .skip_stale
+                .then(|| self.config.latency.duration());
+            let dedup_window = self.config.dedup_window;
+            let non_speech_events = self.config.non_speech_events;
+            let music_policy = self.config.music_policy;
+            supervisor.add(Stage::Asr, move || {
+                let pcm_rx = pcm_rx.clone();
+                let asr = asr.clone();
+                let status = status.clone();
+                let control = control.clone();
+                let clips = clips.clone();
+                let post_asr = post_asr.clone();
+                let budget = budget.clone();
+                let transcript_tx = transcript_tx.clone();
+                // With several chunks in flight, each may take that many times
+                // longer than real time before the stage falls behind.
+                let mut rtf = RtfMonitor::new(RTF_WINDOW, workers.max(1) as f64);
+                let mut dedup = dedup_window.map(Deduplicator::new);
+                // A song spans many chunks; it is captioned once, until
+                // speech or another sound follows.
+                let mut last_sound = None;
+                let mut music =
+                    (music_policy != MusicPolicy::Normal).then(MusicDetector::new);
+                tokio::spawn(async move {
+                    let pcm_rx = pcm_rx.lock_owned().await;
+                    let mut transcripts = std::pin::pin!(transcribe_in_order(
+                        asr,
+                        pcm_rx,
+                        workers,
+                        max_age,
+                        budget,
+                        status.clone()
+                    ));
+                    while let Some((result, elapsed, span)) = transcripts.next().await {
+                        match result {
+                            Ok(mut transcript) => {
+                                status.record_latency(Stage::Asr, elapsed);
+                                status.record_processed(Stage::Asr);
+                                status.record_audio_processed(transcript.audio_duration);
+                                if let Some(overload) =
+                                    rtf.record(elapsed, transcript.audio_duration)
+                                {
+                                    tracing::warn!(rtf = overload, "ASR is slower than real time");
+                                    status.set_asr_rtf(overload);
+                                    status.publish(PipelineEvent::AsrOverloaded { rtf: overload });
+                                }
+                                if let Some(current) = rtf.rtf() {
+                                    status.set_asr_rtf(current);
+                                }
+                                let was_playing = music.as_ref().is_some_and(|m| m.is_playing());
+                                let playing = music
+                                    .as_mut()
+                                    .is_some_and(|m| m.observe(&transcript.text));
+                                if playing != was_playing {
+                                    tracing::info!(playing, policy = %music_policy, "music changed");
+                                }
+                                let sung = playing.then(|| lyrics(&transcript.text));
+                                if non_speech_events {
+                                    let (speech, sound) = split_non_speech(&transcript.text);
+                                    transcript.text = speech;
+                                    if let Some(sound) = sound.filter(|s| Some(*s) != last_sound) {
+                                        status.publish(PipelineEvent::NonSpeech { sound });
+                                    }
+                                    if sound.is_some() || !transcript.text.is_empty() {
+                                        last_sound = sound;
+                                    }
+                                }
+                                let voice = match sung {
+                                    Some(sung) if music_policy == MusicPolicy::SubtitleOnly => {
+                                        transcript.text = sung;
+                                        false
+                                    }
+                                    Some(_) => {
+                                        transcript.text.clear();
+                                        false
+                                    }
+                                    None => true,
+                                };
+                                if !post_asr.is_empty() && !transcript.text.is_empty() {
+                                    transcript.text =
+                                        post_asr.apply(transcript.text, &control.target_lang());
+                                }
+                                if let Some(dedup) = dedup.as_mut() {
+                                    if !transcript.text.is_empty() {
+                                        transcript.text = dedup
+                                            .filter(&transcript.text, Instant::now())
+                                            .unwrap_or_default();
+                                    }
+                                }
+                                if !transcript.text.is_empty() {
+                                    status.set_last_transcript(transcript.text.clone());
+                                    clips.push_transcript(&transcript.text);
+                                    status.publish(PipelineEvent::Transcript {
+                                        text: transcript.text.clone(),
+                                        confidence: transcript.confidence,
+                                        latency_ms: elapsed.as_millis() as u64,
+                                    });
+                                }
+                                if forward && transcript_tx.send((transcript, voice, span)).await.is_err()
+                                {
+                                    tracing::error!("transcript channel closed");
+                                    return Err(PipelineError::ChannelClosed);
+                                }
                             }
-                        }
-                        Err(e) => {
-                            tracing::warn!(error = %e, "asr failed");
+                            Err(e) => handle_item_error(Stage::Asr, e, &status)?,
                         }
                     }
-                }
-                Ok(())
-            })
-        };
+                    Ok(())
+                })
+            });
+        }
 
-        // Start the translator
-        let translate_task = {
+        // Start the translator, which also hands transcripts straight to TTS
+        // when there is nothing to translate with
+        if self.translate.is_some() || self.tts.is_some() {
             let translate = self.translate.clone();
-            let target_lang = self.config.target_lang.clone();
-            let has_deepl_key = self.config.api_keys.deepl.is_some();
-            tokio::spawn(async move {
-                while let Some(transcript) = transcript_rx.recv().await {
-                    if has_deepl_key {
-                        // Use DeepL translator with the configured target language
-                        match translate
-                            .translate(transcript.text, target_lang.clone())
+            let control = self.control.clone();
+            let speak = self.tts.is_some();
+            let text_rules = self.config.text_chains();
+            let status = self.status.clone();
+            let pace = self.config.pace_speech;
+            let deadline = self
+                .config
+                .stage_deadlines
+                .then(|| self.config.latency.translate_deadline());
+            supervisor.add(Stage::Translate, move || {
+                let transcript_rx = transcript_rx.clone();
+                let translate = translate.clone();
+                let control = control.clone();
+                let text_rules = text_rules.clone();
+                let status = status.clone();
+                let translation_tx = translation_tx.clone();
+                tokio::spawn(async move {
+                    let mut transcript_rx = transcript_rx.lock().await;
+                    let mut recent = RecentTranslations::default();
+                    while let Some((transcript, voice, span)) = transcript_rx.recv().await {
+                        status.set_queue_depth(Stage::Translate, transcript_rx.len());
+                        // Translate into the currently selected target language.
+                        // The epoch is read first so a switch in between makes
+                        // the item look stale rather than current.
+                        let epoch = control.language_epoch();
+                        let target_lang = control.target_lang();
+                        let rules = control.text_chains().unwrap_or_else(|| text_rules.clone());
+                        let rate = pace.then(|| speaking_rate(&transcript)).flatten();
+                        let speaker = transcript.speaker().map(str::to_owned);
+                        let source_text = transcript.text;
+                        let text = rules.pre_translate.apply(source_text.clone(), &target_lang);
+                        if text.trim().is_empty() {
+                            continue;
+                        }
+                        if let Some(translate) = &translate {
+                            let started = Instant::now();
+                            let translating = translate
+                                .translate(text.clone(), target_lang.clone())
+                                .instrument(info_span!(parent: &span, "translate"));
+                            // Too slow, the sentence is answered with its
+                            // translation from earlier in the session, or
+                            // else shown untranslated and not voiced.
+                            let mut untranslated = false;
+                            let result = match within(
+                                Stage::Translate,
+                                deadline,
+                                &status,
+                                translating,
+                            )
                             .await
-                        {
-                            Ok(translation) => {
-                                if translation_tx.send(translation).await.is_err() {
-                                    tracing::error!("translation channel closed");
-                                    return Err(PipelineError::ChannelClosed);
+                            {
+                                Some(result) => result,
+                                None => Ok(Translation {
+                                    text: match recent.get(&target_lang, &text) {
+                                        Some(cached) => cached.to_owned(),
+                                        None => {
+                                            untranslated = true;
+                                            text.clone()
+                                        }
+                                    },
+                                    detected_source_lang: None,
+                                }),
+                            };
+                            match result {
+                                Ok(mut translation) => {
+                                    if deadline.is_some() && !untranslated {
+                                        recent.insert(&target_lang, &text, &translation.text);
+                                    }
+                                    translation.text =
+                                        rules.post_translate.apply(translation.text, &target_lang);
+                                    status.record_latency(Stage::Translate, started.elapsed());
+                                    status.record_processed(Stage::Translate);
+                                    status.set_last_translation(translation.text.clone());
+                                    control
+                                        .clips()
+                                        .push_translation(&source_text, &translation.text);
+                                    status.publish(PipelineEvent::Translation {
+                                        source_text,
+                                        text: translation.text.clone(),
+                                        target_lang: target_lang.as_str().to_owned(),
+                                    });
+                                    if !speak || !voice || untranslated {
+                                        continue;
+                                    }
+                                    if translation_tx
+                                        .send((translation, rate, speaker, epoch, span))
+                                        .await
+                                        .is_err()
+                                    {
+                                        tracing::error!("translation channel closed");
+                                        return Err(PipelineError::ChannelClosed);
+                                    }
                                 }
+                                Err(e) => handle_item_error(Stage::Translate, e, &status)?,
                             }
-                            Err(e) => {
-                                tracing::warn!(error = %e, "translation failed");
+                        } else if voice {
+                            // Without a translator, pass through the text
+                            let translation = crate::translate::Translation {
+                                text: rules.post_translate.apply(text, &target_lang),
+                                detected_source_lang: None,
+                            };
+                            if translation_tx
+                                .send((translation, rate, speaker, epoch, span))
+                                .await
+                                .is_err()
+                            {
+                                tracing::error!("translation channel closed");
+                                return Err(PipelineError::ChannelClosed);
                             }
                         }
-                    } else {
-                        // If no DeepL API key (dummy translator), pass through the text
-                        let translation = crate::translate::Translation {
-                            text: transcript.text,
-                            detected_source_lang: None,
+                    }
+                    Ok(())
+                })
+            });
+        }
+
+        // Start the TTS
+        if let Some(tts) = self.tts.clone() {
+            let has_playback = self.playback.is_some();
+            let text_rules = self.config.text_chains();
+            let status = self.status.clone();
+            let control = self.control.clone();
+            let budget = budget.clone();
+            let voice_match = self.config.voice_match.clone();
+            supervisor.add(Stage::Tts, move || {
+                let translation_rx = translation_rx.clone();
+                let tts = tts.clone();
+                let text_rules = text_rules.clone();
+                let status = status.clone();
+                let control = control.clone();
+                let budget = budget.clone();
+                let voice_match = voice_match.clone();
+                let pitch = pitch.clone();
+                let tts_tx = tts_tx.clone();
+                tokio::spawn(async move {
+                    let mut translation_rx = translation_rx.lock().await;
+                    while let Some((translation, rate, speaker, epoch, span)) =
+                        translation_rx.recv().await
+                    {
+                        status.set_queue_depth(Stage::Tts, translation_rx.len());
+                        if epoch != control.language_epoch() {
+                            tracing::debug!("target language changed, dropping translation");
+                            status.record_skipped(Stage::Tts);
+                            continue;
+                        }
+                        if control.is_muted() || control.is_paused() {
+                            tracing::debug!("tts muted or paused, skipping synthesis");
+                            continue;
+                        }
+                        let rules = control.text_chains().unwrap_or_else(|| text_rules.clone());
+                        let text = rules
+                            .pre_tts
+                            .apply(translation.text, &control.target_lang());
+                        if text.trim().is_empty() {
+                            continue;
+                        }
+                        let spoken = rate.and_then(|rate| spoken_length(&text, rate));
+                        // A voice of the speaker's range, else the session's
+                        // voice pitched into it after synthesis.
+                        let gender = pitch.as_ref().and_then(PitchTracker::gender);
+                        let matched = gender.and_then(|gender| {
+                            voice_match
+                                .as_ref()?
+                                .voice(gender)
+                                .map(|v| VoiceId(v.to_owned()))
+                        });
+                        let repitch = gender.filter(|_| matched.is_none());
+                        let request = crate::tts::TtsRequest {
+                            text,
+                            voice: matched.or_else(|| control.voice()),
+                            prosody: None, // TODO: Add prosody features
+                            speaker,
                         };
-                        if translation_tx.send(translation).await.is_err() {
-                            tracing::error!("translation channel closed");
-                            return Err(PipelineError::ChannelClosed);
+                        let started = Instant::now();
+                        // A TTS deadline is up to the client, which can
+                        // still hand a late sentence to a faster voice.
+                        let result = tts
+                            .synthesize(request)
+                            .instrument(info_span!(parent: &span, "tts"))
+                            .await;
+                        match result {
+                            Ok(audio) => {
+                                status.record_latency(Stage::Tts, started.elapsed());
+                                status.record_processed(Stage::Tts);
+                                let audio = match repitch {
+                                    Some(gender) => pitch_into_range(audio, gender),
+                                    None => audio,
+                                };
+                                if !has_playback {
+                                    status.publish_audio(&audio);
+                                    continue;
+                                }
+                                if !budget.try_reserve(Stage::Playback, audio.buffered_bytes()) {
+                                    continue;
+                                }
+                                if tts_tx.send((audio, spoken, epoch, span)).await.is_err() {
+                                    tracing::error!("tts channel closed");
+                                    return Err(PipelineError::ChannelClosed);
+                                }
+                            }
+                            Err(e) => handle_item_error(Stage::Tts, e, &status)?,
                         }
                     }
-                }
-                Ok(())
-            })
-        };
+                    Ok(())
+                })
+            });
+        }
 
-        // Start the TTS
-        let tts_task = {
-            let tts = self.tts.clone();
-            tokio::spawn(async move {
-                while let Some(translation) = translation_rx.recv().await {
-                    let request = crate::tts::TtsRequest {
-                        text: translation.text,
-                        voice: None,
-                        prosody: None, // TODO: Add prosody features
-                    };
-                    match tts.synthesize(request).await {
-                        Ok(audio) => {
-                            if tts_tx.send(audio).await.is_err() {
-                                tracing::error!("tts channel closed");
-                                return Err(PipelineError::ChannelClosed);
+        // Start the playback, if there is speech to play
+        if let Some(playback) = self.playback.clone().filter(|_| self.tts.is_some()) {
+            let status = self.status.clone();
+            let control = self.control.clone();
+            let pace = self.config.pace_speech;
+            supervisor.add(Stage::Playback, move || {
+                let tts_rx = tts_rx.clone();
+                let playback = playback.clone();
+                let status = status.clone();
+                let control = control.clone();
+                let budget = budget.clone();
+                tokio::spawn(async move {
+                    let mut tts_rx = tts_rx.lock().await;
+                    // Queueing sinks report playback latency as clips finish
+                    // rather than when `play` returns.
+                    let mut finished = playback.subscribe_finished();
+                    let queueing = finished.is_some();
+                    let mut queued_epoch = control.language_epoch();
+                    let mut pacer = pace.then(Pacer::default);
+                    loop {
+                        let (mut audio, spoken, epoch, span) = tokio::select! {
+                            item = tts_rx.recv() => match item {
+                                Some(item) => item,
+                                None => break,
+                            },
+                            Some(done) = next_finished(&mut finished) => {
+                                status.record_latency(
+                                    Stage::Playback,
+                                    done.finished_at.saturating_duration_since(done.queued_at),
+                                );
+                                continue;
+                            }
+                        };
+                        budget.release(audio.buffered_bytes());
+                        status.set_queue_depth(Stage::Playback, tts_rx.len());
+                        let current_epoch = control.language_epoch();
+                        if current_epoch != queued_epoch {
+                            // Speech already queued is in the old language.
+                            playback.clear();
+                            if let Some(pacer) = pacer.as_mut() {
+                                pacer.reset();
                             }
+                            queued_epoch = current_epoch;
                         }
-                        Err(e) => {
-                            tracing::warn!(error = %e, "tts failed");
+                        if epoch != current_epoch {
+                            tracing::debug!("target language changed, dropping speech");
+                            status.record_skipped(Stage::Playback);
+                            continue;
                         }
-                    }
-                }
-                Ok(())
-            })
-        };
-
-        // Start the playback
-        let playback_task: tokio::task::JoinHandle<Result<(), PipelineError>> = {
-            let playback = self.playback.clone();
-            tokio::spawn(async move {
-                while let Some(audio) = tts_rx.recv().await {
-                    match playback.play(audio).await {
-                        Ok(()) => {}
-                        Err(e) => {
-                            tracing::warn!(error = %e, "playback failed");
+                        // Drop rather than hold while paused, so the stages
+                        // upstream keep publishing transcripts and translations.
+                        if control.is_muted() || control.is_paused() {
+                            continue;
+                        }
+                        if let Some(pacer) = pacer.as_mut() {
+                            let paced = pacer.pace(audio, spoken, Instant::now());
+                            if !paced.wait.is_zero() {
+                                tokio::time::sleep(paced.wait).await;
+                            }
+                            audio = paced.audio;
+                        }
+                        status.publish_audio(&audio);
+                        let started = Instant::now();
+                        match playback
+                            .play_with_gain(audio, control.volume())
+                            .instrument(info_span!(parent: &span, "playback"))
+                            .await
+                        {
+                            Ok(()) => {
+                                if !queueing {
+                                    status.record_latency(Stage::Playback, started.elapsed());
+                                }
+                                status.record_processed(Stage::Playback);
+                            }
+                            Err(e) => handle_item_error(Stage::Playback, e, &status)?,
                         }
                     }
-                }
-                Ok(())
-            })
-        };
-
-        // Wait for all tasks to complete
-        let _ = tokio::try_join!(
-            ingest_task,
-            decode_task,
-            asr_task,
-            translate_task,
-            tts_task,
-            playback_task
-        )
-        .map_err(|_| PipelineError::ChannelClosed)?;
+                    Ok(())
+                })
+            });
+        }
 
-        Ok(())
+        // Wait for all stages to finish; downstream stages drain what was
+        // already decoded before an ingest failure is reported.
+        supervisor.run().await
     }
 
     pub fn channel_capacity(&self) -> usize {
@@ -224,3 +843,216 @@ where
         usize::try_from(cap).unwrap_or(8)
     }
 }
+
+/// Reacts to an item that failed in `stage` according to its [`ErrorClass`].
+/// A fatal error stops the stage, which closes its channels and winds down
+/// the rest of the pipeline. Anything else only drops the item: retryable
+/// errors get here once the client's own retries have run out.
+fn handle_item_error<E>(
+    stage: Stage,
+    error: E,
+    status: &PipelineStatus,
+) -> Result<(), PipelineError>
+where
+    E: Classify + std::fmt::Display + Into<PipelineError>,
+{
+    status.record_error(stage);
+    let class = error.class();
+    if class == ErrorClass::Fatal {
+        tracing::error!(stage = stage.as_str(), error = %error, "stage failed, shutting down");
+        return Err(error.into());
+    }
+    tracing::warn!(stage = stage.as_str(), %class, error = %error, "dropping item");
+    Ok(())
+}
+
+/// Next clip a queueing playback sink finished. Never resolves for a sink
+/// that does not report completions, and `None` once the sink is gone.
+async fn next_finished(
+    finished: &mut Option<broadcast::Receiver<PlaybackFinished>>,
+) -> Option<PlaybackFinished> {
+    let Some(rx) = finished else {
+        return std::future::pending().await;
+    };
+    loop {
+        match rx.recv().await {
+            Ok(done) => return Some(done),
+            // Missed completions only cost latency samples.
+            Err(broadcast::error::RecvError::Lagged(_)) => continue,
+            Err(broadcast::error::RecvError::Closed) => return None,
+        }
+    }
+}
+
+/// Transcribes chunks from `pcm_rx` with up to `workers` in flight, yielding
+/// results (and how long each took) in arrival order. Chunks only overlap
+/// when they queue up faster than ASR keeps pace, so a pipeline that is
+/// keeping up still transcribes one at a time. Chunks older than `max_age`
+/// when their turn comes are skipped and reported.
+fn transcribe_in_order<A: AsrBackend + Clone + 'static>(
+    asr: A,
+    pcm_rx: impl DerefMut<Target = tokio::sync::mpsc::Receiver<(PcmChunk, Span)>>,
+    workers: usize,
+    max_age: Option<Duration>,
+    budget: BufferBudget,
+    status: PipelineStatus,
+) -> impl Stream<Item = (Result<TranscriptSegment, AsrError>, Duration, Span)> {
+    futures::stream::unfold(pcm_rx, move |mut rx| {
+        let budget = budget.clone();
+        let status = status.clone();
+        async move {
+            loop {
+                let (pcm, span) = rx.recv().await?;
+                budget.release(pcm.buffered_bytes());
+                status.set_queue_depth(Stage::Asr, rx.len());
+                let age = pcm.age();
+                if max_age.is_none_or(|max| age <= max) {
+                    return Some(((pcm, span), rx));
+                }
+                let age_ms = age.as_millis() as u64;
+                tracing::debug!(sequence = pcm.sequence, age_ms, "skipping stale chunk");
+                status.record_skipped(Stage::Asr);
+                status.publish(PipelineEvent::Skipped {
+                    stage: Stage::Asr,
+                    sequence: pcm.sequence,
+                    age_ms,
+                });
+            }
+        }
+    })
+    .map(move |(pcm, span)| {
+        let asr = asr.clone();
+        async move {
+            let started = Instant::now();
+            let result = asr
+                .transcribe(pcm)
+                .instrument(info_span!(parent: &span, "asr"))
+                .await;
+            (result, started.elapsed(), span)
+        }
+    })
+    .buffered(workers.max(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode::PcmFormat;
+    use futures::future::BoxFuture;
+    use futures::FutureExt;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::SystemTime;
+
+    /// Takes longer for earlier chunks and records peak concurrency.
+    #[derive(Clone, Default)]
+    struct SlowAsr {
+        running: Arc<AtomicUsize>,
+        peak: Arc<AtomicUsize>,
+    }
+
+    impl AsrBackend for SlowAsr {
+        fn transcribe(
+            &self,
+            audio: PcmChunk,
+        ) -> BoxFuture<'_, Result<TranscriptSegment, AsrError>> {
+            async move {
+                let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+                self.peak.fetch_max(running, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(40 - 10 * audio.sequence)).await;
+                self.running.fetch_sub(1, Ordering::SeqCst);
+                Ok(TranscriptSegment {
+                    text: audio.sequence.to_string(),
+                    audio_duration: Duration::ZERO,
+                    confidence: None,
+                    words: Vec::new(),
+                    speech_start: None,
+                })
+            }
+            .boxed()
+        }
+    }
+
+    fn chunk(sequence: u64) -> PcmChunk {
+        PcmChunk {
+            sequence,
+            started_at: SystemTime::now(),
+            fetched_at: SystemTime::now(),
+            format: PcmFormat::whisper_f32_mono_16khz(),
+            samples: vec![0.0; 160],
+            duration_estimate: Duration::from_millis(10),
+            discontinuity: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn backlog_is_transcribed_in_parallel_and_kept_in_order() {
+        let asr = SlowAsr::default();
+        let (tx, mut rx) = tokio::sync::mpsc::channel(8);
+        for sequence in 0..4 {
+            tx.send((chunk(sequence), Span::none())).await.unwrap();
+        }
+        drop(tx);
+
+        let texts: Vec<String> = transcribe_in_order(
+            asr.clone(),
+            &mut rx,
+            3,
+            None,
+            BufferBudget::default(),
+            PipelineStatus::new(),
+        )
+        .map(|(result, ..)| result.unwrap().text)
+        .collect()
+        .await;
+        assert_eq!(texts, ["0", "1", "2", "3"]);
+        assert_eq!(asr.peak.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn chunks_older_than_the_budget_are_skipped() {
+        let status = PipelineStatus::new();
+        let mut events = status.subscribe();
+        let (tx, mut rx) = tokio::sync::mpsc::channel(8);
+        let mut stale = chunk(0);
+        stale.fetched_at = SystemTime::now() - Duration::from_secs(5);
+        tx.send((stale, Span::none())).await.unwrap();
+        tx.send((chunk(1), Span::none())).await.unwrap();
+        drop(tx);
+
+        let texts: Vec<String> = transcribe_in_order(
+            SlowAsr::default(),
+            &mut rx,
+            1,
+            Some(Duration::from_secs(2)),
+            BufferBudget::default(),
+            status.clone(),
+        )
+        .map(|(result, ..)| result.unwrap().text)
+        .collect()
+        .await;
+
+        assert_eq!(texts, ["1"]);
+        assert!(matches!(
+            events.recv().await.unwrap(),
+            PipelineEvent::Skipped { stage: Stage::Asr, sequence: 0, age_ms } if age_ms >= 5000
+        ));
+        assert_eq!(status.snapshot().stage(Stage::Asr).unwrap().skipped, 1);
+    }
+
+    #[test]
+    fn fatal_errors_stop_the_stage_and_others_drop_the_item() {
+        let status = PipelineStatus::new();
+        assert!(handle_item_error(Stage::Tts, TtsError::QuotaExhausted, &status).is_ok());
+        let fatal = handle_item_error(
+            Stage::Translate,
+            TranslateError::HttpStatus(403, "Forbidden".to_owned()),
+            &status,
+        );
+        assert!(matches!(
+            fatal,
+            Err(PipelineError::Translate(TranslateError::HttpStatus(403, _)))
+        ));
+        assert_eq!(status.snapshot().stage(Stage::Tts).unwrap().errors, 1);
+    }
+}
@@ -0,0 +1,249 @@
+//! Restarts pipeline stages that die
+//!
+//! Each stage is registered with a closure that spawns a fresh task for it.
+//! The stage's channel ends are shared with the closure rather than moved
+//! into the task, so a task that panics leaves them in place and a restarted
+//! task carries on with the next item. Restarts back off exponentially; a
+//! stage that keeps dying, or one that fails with a fatal error, is given up
+//! on and the error is returned once the remaining stages have drained.
+
+use super::PipelineError;
+use crate::error::Classify;
+use crate::status::{PipelineEvent, PipelineStatus, Stage};
+use futures::stream::FuturesUnordered;
+use futures::StreamExt;
+use std::any::Any;
+use std::time::{Duration, Instant};
+use tokio::task::{JoinError, JoinHandle};
+
+/// Restarts allowed before a stage is given up on.
+const MAX_RESTARTS: u32 = 5;
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_BACKOFF: Duration = Duration::from_secs(10);
+/// A stage that stayed up this long is considered healthy again and gets the
+/// full number of restarts back.
+const HEALTHY_AFTER: Duration = Duration::from_secs(60);
+
+pub(crate) type StageTask = JoinHandle<Result<(), PipelineError>>;
+type SpawnStage = Box<dyn Fn() -> StageTask + Send + Sync>;
+
+pub(crate) struct Supervisor {
+    status: PipelineStatus,
+    stages: Vec<(Stage, SpawnStage)>,
+    max_restarts: u32,
+    initial_backoff: Duration,
+}
+
+/// How one run of a stage task ended.
+struct StageExit {
+    stage: Stage,
+    spawn: SpawnStage,
+    restarts: u32,
+    uptime: Duration,
+    outcome: Result<Result<(), PipelineError>, JoinError>,
+}
+
+impl Supervisor {
+    pub(crate) fn new(status: PipelineStatus) -> Self {
+        Self {
+            status,
+            stages: Vec::new(),
+            max_restarts: MAX_RESTARTS,
+            initial_backoff: INITIAL_BACKOFF,
+        }
+    }
+
+    /// Registers `stage`; `spawn` is called to start it and again for every
+    /// restart.
+    pub(crate) fn add(
+        &mut self,
+        stage: Stage,
+        spawn: impl Fn() -> StageTask + Send + Sync + 'static,
+    ) {
+        self.stages.push((stage, Box::new(spawn)));
+    }
+
+    /// Runs every stage until all have finished or been given up on. The
+    /// spawn closure of a finished stage is dropped, closing the channels it
+    /// holds so the stages around it wind down too.
+    pub(crate) async fn run(self) -> Result<(), PipelineError> {
+        let mut running: FuturesUnordered<_> = self
+            .stages
+            .into_iter()
+            .map(|(stage, spawn)| run_stage(stage, spawn, 0, Duration::ZERO))
+            .collect();
+        let mut failure: Option<PipelineError> = None;
+
+        while let Some(exit) = running.next().await {
+            let StageExit {
+                stage,
+                spawn,
+                mut restarts,
+                uptime,
+                outcome,
+            } = exit;
+            let reason = match outcome {
+                Ok(Ok(())) => continue,
+                Ok(Err(e)) if !is_restartable(&e) => {
+                    tracing::error!(stage = stage.as_str(), error = %e, "stage stopped");
+                    keep_root_cause(&mut failure, e);
+                    continue;
+                }
+                Ok(Err(e)) => e.to_string(),
+                Err(e) if e.is_panic() => format!("panicked: {}", panic_message(e.into_panic())),
+                Err(e) => e.to_string(),
+            };
+
+            self.status.record_error(stage);
+            if uptime >= HEALTHY_AFTER {
+                restarts = 0;
+            }
+            if restarts >= self.max_restarts {
+                tracing::error!(
+                    stage = stage.as_str(),
+                    %reason,
+                    "stage keeps failing, giving up"
+                );
+                keep_root_cause(&mut failure, PipelineError::StageFailed { stage, reason });
+                continue;
+            }
+            restarts += 1;
+            let delay = self
+                .initial_backoff
+                .saturating_mul(1 << (restarts - 1).min(16))
+                .min(MAX_BACKOFF);
+            tracing::warn!(
+                stage = stage.as_str(),
+                %reason,
+                restarts,
+                delay_ms = delay.as_millis() as u64,
+                "stage died, restarting"
+            );
+            self.status.publish(PipelineEvent::StageRestarted {
+                stage,
+                restarts,
+                reason,
+            });
+            running.push(run_stage(stage, spawn, restarts, delay));
+        }
+
+        failure.map_or(Ok(()), Err)
+    }
+}
+
+async fn run_stage(stage: Stage, spawn: SpawnStage, restarts: u32, delay: Duration) -> StageExit {
+    tokio::time::sleep(delay).await;
+    let started = Instant::now();
+    let outcome = spawn().await;
+    StageExit {
+        stage,
+        spawn,
+        restarts,
+        uptime: started.elapsed(),
+        outcome,
+    }
+}
+
+/// Whether starting the stage again can help. Fatal errors would recur, and
+/// once an ingest error reaches the decode stage the ingestor has already
+/// returned, so there is nothing left to resume.
+fn is_restartable(error: &PipelineError) -> bool {
+    !error.is_fatal() && !matches!(error, PipelineError::Ingest(_))
+}
+
+/// Stages stopped only because a neighbour closed its channel report
+/// [`PipelineError::ChannelClosed`]; the neighbour's own error wins.
+fn keep_root_cause(failure: &mut Option<PipelineError>, error: PipelineError) {
+    if matches!(failure, None | Some(PipelineError::ChannelClosed)) {
+        *failure = Some(error);
+    }
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        (*message).to_owned()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asr::AsrError;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    fn fast(status: &PipelineStatus) -> Supervisor {
+        let mut supervisor = Supervisor::new(status.clone());
+        supervisor.initial_backoff = Duration::from_millis(1);
+        supervisor
+    }
+
+    #[tokio::test]
+    async fn panicking_stage_is_restarted() {
+        let status = PipelineStatus::new();
+        let mut events = status.subscribe();
+        let mut supervisor = fast(&status);
+        let runs = Arc::new(AtomicUsize::new(0));
+        let counter = runs.clone();
+        supervisor.add(Stage::Asr, move || {
+            let run = counter.fetch_add(1, Ordering::SeqCst);
+            tokio::spawn(async move {
+                if run == 0 {
+                    panic!("boom");
+                }
+                Ok(())
+            })
+        });
+
+        supervisor.run().await.unwrap();
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+        assert!(matches!(
+            events.recv().await.unwrap(),
+            PipelineEvent::StageRestarted { stage: Stage::Asr, restarts: 1, reason }
+                if reason.contains("boom")
+        ));
+        assert_eq!(status.snapshot().stage(Stage::Asr).unwrap().errors, 1);
+    }
+
+    #[tokio::test]
+    async fn stage_that_keeps_panicking_is_given_up_on() {
+        let status = PipelineStatus::new();
+        let mut supervisor = fast(&status);
+        supervisor.max_restarts = 2;
+        supervisor.add(Stage::Decode, || tokio::spawn(async { panic!("boom") }));
+
+        let err = supervisor.run().await.unwrap_err();
+        assert!(matches!(
+            err,
+            PipelineError::StageFailed {
+                stage: Stage::Decode,
+                ..
+            }
+        ));
+        assert_eq!(status.snapshot().stage(Stage::Decode).unwrap().errors, 3);
+    }
+
+    #[tokio::test]
+    async fn fatal_error_is_returned_over_closed_channels() {
+        let mut supervisor = fast(&PipelineStatus::new());
+        supervisor.add(Stage::Decode, || {
+            tokio::spawn(async { Err(PipelineError::ChannelClosed) })
+        });
+        supervisor.add(Stage::Asr, || {
+            tokio::spawn(async {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                Err(AsrError::ModelNotFound("x".to_owned()).into())
+            })
+        });
+
+        let err = supervisor.run().await.unwrap_err();
+        assert!(matches!(
+            err,
+            PipelineError::Asr(AsrError::ModelNotFound(_))
+        ));
+    }
+}
@@ -1,4 +1,9 @@
-use crate::playback::{PlaybackError, PlaybackSink};
+use crate::config::OutputHost;
+use crate::playback::limiter::SoftLimiter;
+use crate::playback::mixer::MixerProps;
+use crate::playback::queue::OnFinished;
+use crate::playback::smooth::smooth_clip;
+use crate::playback::{PlaybackError, PlaybackFinished, PlaybackSink};
 use crate::tts::TtsAudio;
 use futures::future::BoxFuture;
 use futures::FutureExt;
@@ -9,6 +14,13 @@ use rodio::{OutputStream, OutputStreamBuilder, Sink, StreamError};
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex, OnceLock};
 use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, Semaphore};
+
+/// Clips handed to rodio at once: one playing and one queued behind it, so
+/// consecutive clips play back to back. Further `play` calls wait for a slot,
+/// which leaves the backlog in the pipeline channel where the buffer budget
+/// counts it.
+const MAX_QUEUED_CLIPS: usize = 2;
 
 /// A minimal, poison-tolerant, lazy initializer for a single value.
 ///
@@ -27,13 +39,8 @@ impl<T> LazyInit<T> {
         }
     }
 
-    fn get_or_try_init_with<R, E>(
-        &self,
-        init: impl FnOnce() -> Result<T, E>,
-        f: impl FnOnce(&T) -> R,
-        invariant_err: impl FnOnce() -> E,
-    ) -> Result<R, E> {
-        let mut guard = match self.value.lock() {
+    fn lock(&self) -> std::sync::MutexGuard<'_, Option<T>> {
+        match self.value.lock() {
             Ok(g) => g,
             Err(poisoned) => {
                 tracing::warn!(
@@ -41,7 +48,16 @@ impl<T> LazyInit<T> {
                 );
                 poisoned.into_inner()
             }
-        };
+        }
+    }
+
+    fn get_or_try_init_with<R, E>(
+        &self,
+        init: impl FnOnce() -> Result<T, E>,
+        f: impl FnOnce(&T) -> R,
+        invariant_err: impl FnOnce() -> E,
+    ) -> Result<R, E> {
+        let mut guard = self.lock();
 
         // NOTE: `init` is used at most once (only when the cache is empty).
         if guard.is_none() {
@@ -53,6 +69,18 @@ impl<T> LazyInit<T> {
             None => Err(invariant_err()),
         }
     }
+
+    /// Runs `f` on the value if it has been initialized, without initializing it.
+    fn with_existing<R>(&self, f: impl FnOnce(&T) -> R) -> Option<R> {
+        self.lock().as_ref().map(f)
+    }
+}
+
+/// The output stream and the one sink every clip is queued on.
+struct Output {
+    // Declared first so it is dropped before the stream it plays through.
+    sink: Arc<Sink>,
+    _stream: OutputStream,
 }
 
 struct RateLimitedWarn {
@@ -92,12 +120,18 @@ impl RateLimitedWarn {
 #[derive(Clone)]
 pub struct AudioPlaybackSink {
     output_device_name: Option<String>,
+    output_host: OutputHost,
+    buffer_frames: Option<u32>,
+    mixer_props: MixerProps,
     disabled: Arc<AtomicBool>,
     disabled_details: Arc<OnceLock<String>>,
 
-    // Keep the OutputStream alive across play calls. Clones share a single stream.
-    output_stream: Arc<LazyInit<OutputStream>>,
+    // Keep the OutputStream and its Sink alive across play calls. Clones share
+    // a single stream and queue.
+    output: Arc<LazyInit<Output>>,
     output_stream_open_attempts: Arc<AtomicUsize>,
+    queue_slots: Arc<Semaphore>,
+    finished: broadcast::Sender<PlaybackFinished>,
 
     blank_audio_warn: Arc<RateLimitedWarn>,
 }
@@ -106,11 +140,16 @@ impl AudioPlaybackSink {
     pub fn new() -> Result<Self, PlaybackError> {
         Ok(Self {
             output_device_name: None,
+            output_host: OutputHost::Auto,
+            buffer_frames: None,
+            mixer_props: MixerProps::default(),
             disabled: Arc::new(AtomicBool::new(false)),
             disabled_details: Arc::new(OnceLock::new()),
 
-            output_stream: Arc::new(LazyInit::new()),
+            output: Arc::new(LazyInit::new()),
             output_stream_open_attempts: Arc::new(AtomicUsize::new(0)),
+            queue_slots: Arc::new(Semaphore::new(MAX_QUEUED_CLIPS)),
+            finished: broadcast::channel(64).0,
             blank_audio_warn: Arc::new(RateLimitedWarn::new(Duration::from_secs(5))),
         })
     }
@@ -120,6 +159,26 @@ impl AudioPlaybackSink {
         self
     }
 
+    /// Audio API the output device is opened through.
+    pub fn with_output_host(mut self, host: OutputHost) -> Self {
+        self.output_host = host;
+        self
+    }
+
+    /// Asks the device for a fixed buffer of `frames` per callback, trading
+    /// robustness against underruns for lower output latency. A device that
+    /// rejects the size is opened with its defaults.
+    pub fn with_buffer_frames(mut self, frames: u32) -> Self {
+        self.buffer_frames = Some(frames);
+        self
+    }
+
+    /// Names, tags and routes the stream for a Linux desktop mixer.
+    pub fn with_mixer_props(mut self, props: MixerProps) -> Self {
+        self.mixer_props = props;
+        self
+    }
+
     fn open_output_stream(&self) -> Result<OutputStream, PlaybackError> {
         let attempt = self
             .output_stream_open_attempts
@@ -130,9 +189,11 @@ impl AudioPlaybackSink {
             configured_output_device = %self.output_device_name.as_deref().unwrap_or("<default>"),
             "opening Rodio OutputStream"
         );
+        self.mixer_props.export();
+        let host = output_host(self.output_host)?;
 
         match self.output_device_name.as_deref() {
-            Some(wanted) => match open_named_output_stream(wanted) {
+            Some(wanted) => match open_named_output_stream(&host, wanted, self.buffer_frames) {
                 Ok(stream) => Ok(stream),
                 Err(NamedDeviceStreamError::DeviceNotFound { wanted, available }) => {
                     tracing::warn!(
@@ -140,7 +201,7 @@ impl AudioPlaybackSink {
                         available_devices = %format_device_list(&available),
                         "configured output device not found; falling back to default output device"
                     );
-                    OutputStreamBuilder::open_default_stream().map_err(|e| {
+                    self.open_default_stream(&host).map_err(|e| {
                         PlaybackError::AudioOutputUnavailable {
                             details: format_stream_error_details(
                                 e,
@@ -161,7 +222,7 @@ impl AudioPlaybackSink {
                         available_devices = %format_device_list(&available),
                         "failed to open configured output device; falling back to default output device"
                     );
-                    OutputStreamBuilder::open_default_stream().map_err(|e| {
+                    self.open_default_stream(&host).map_err(|e| {
                         PlaybackError::AudioOutputUnavailable {
                             details: format_stream_error_details(
                                 e,
@@ -172,21 +233,35 @@ impl AudioPlaybackSink {
                     })
                 }
             },
-            None => OutputStreamBuilder::open_default_stream().map_err(|e| {
-                PlaybackError::AudioOutputUnavailable {
-                    details: format_stream_error_details(e, None, "open default output stream"),
-                }
-            }),
+            None => {
+                self.open_default_stream(&host)
+                    .map_err(|e| PlaybackError::AudioOutputUnavailable {
+                        details: format_stream_error_details(e, None, "open default output stream"),
+                    })
+            }
+        }
+    }
+
+    fn open_default_stream(&self, host: &rodio::cpal::Host) -> Result<OutputStream, StreamError> {
+        if self.output_host == OutputHost::Auto && self.buffer_frames.is_none() {
+            // Also tries the other devices when the default one fails.
+            return OutputStreamBuilder::open_default_stream();
         }
+        let device = host.default_output_device().ok_or(StreamError::NoDevice)?;
+        stream_builder(device, self.buffer_frames)?.open_stream_or_fallback()
     }
 
-    fn connect_sink(&self) -> Result<Sink, PlaybackError> {
-        self.output_stream.get_or_try_init_with(
-            || self.open_output_stream(),
-            |stream| {
-                let mixer = stream.mixer();
-                Sink::connect_new(&mixer)
+    fn connect_sink(&self) -> Result<Arc<Sink>, PlaybackError> {
+        self.output.get_or_try_init_with(
+            || {
+                let stream = self.open_output_stream()?;
+                let sink = Arc::new(Sink::connect_new(stream.mixer()));
+                Ok(Output {
+                    sink,
+                    _stream: stream,
+                })
             },
+            |output| Arc::clone(&output.sink),
             || PlaybackError::AudioOutputUnavailable {
                 details: "internal error: output stream cache invariant violated".to_owned(),
             },
@@ -196,6 +271,18 @@ impl AudioPlaybackSink {
 
 impl PlaybackSink for AudioPlaybackSink {
     fn play(&self, audio: TtsAudio) -> BoxFuture<'_, Result<(), PlaybackError>> {
+        self.play_with_gain(audio, 1.0)
+    }
+
+    /// Queues the clip behind those already playing and returns without
+    /// waiting for it to play; completion is reported through
+    /// [`PlaybackSink::subscribe_finished`]. `gain` is applied ahead of a soft
+    /// limiter, so loud clips are rounded off rather than clipped.
+    fn play_with_gain(
+        &self,
+        audio: TtsAudio,
+        gain: f32,
+    ) -> BoxFuture<'_, Result<(), PlaybackError>> {
         async move {
             if self.disabled.load(Ordering::Relaxed) {
                 return Ok(());
@@ -207,7 +294,10 @@ impl PlaybackSink for AudioPlaybackSink {
                 || audio.channels == 0
                 || audio.pcm_i16.is_empty()
                 || (usize::from(audio.channels) != 0
-                    && audio.pcm_i16.len() % usize::from(audio.channels) != 0)
+                    && !audio
+                        .pcm_i16
+                        .len()
+                        .is_multiple_of(usize::from(audio.channels)))
             {
                 if self.blank_audio_warn.should_log() {
                     tracing::warn!(
@@ -227,6 +317,13 @@ impl PlaybackSink for AudioPlaybackSink {
                 return Ok(());
             }
 
+            // Trimmed and faded so consecutive clips join without gaps or clicks.
+            let pcm = smooth_clip(audio.pcm_i16, audio.sample_rate_hz, audio.channels);
+            if pcm.is_empty() {
+                tracing::debug!("skipping playback of a silent clip");
+                return Ok(());
+            }
+
             let sink = match self.connect_sink() {
                 Ok(s) => s,
                 Err(e) => {
@@ -240,15 +337,49 @@ impl PlaybackSink for AudioPlaybackSink {
                 }
             };
 
-            let source = PcmSource::new(audio.pcm_i16, audio.sample_rate_hz, audio.channels);
+            // The slot is held by the queued source and freed when it
+            // finishes or is dropped by `clear`.
+            let slot = Arc::clone(&self.queue_slots)
+                .acquire_owned()
+                .await
+                .map_err(|_| PlaybackError::AudioOutputUnavailable {
+                    details: "internal error: playback queue closed".to_owned(),
+                })?;
+            let queued_at = Instant::now();
+            let finished = self.finished.clone();
+            let source = OnFinished::new(
+                SoftLimiter::new(
+                    PcmSource::new(pcm, audio.sample_rate_hz, audio.channels),
+                    gain,
+                ),
+                move || {
+                    drop(slot);
+                    // No subscribers is fine; nobody is tracking completions.
+                    let _ = finished.send(PlaybackFinished {
+                        queued_at,
+                        finished_at: Instant::now(),
+                    });
+                },
+            );
 
             sink.append(source);
-            sink.sleep_until_end();
 
             Ok(())
         }
         .boxed()
     }
+
+    fn subscribe_finished(&self) -> Option<broadcast::Receiver<PlaybackFinished>> {
+        Some(self.finished.subscribe())
+    }
+
+    fn clear(&self) {
+        self.output.with_existing(|output| {
+            // `clear` leaves the sink paused; later clips should still play.
+            output.sink.clear();
+            output.sink.play();
+        });
+    }
 }
 
 #[derive(Debug)]
@@ -268,10 +399,53 @@ fn normalize_device_name(s: &str) -> String {
     s.trim().to_ascii_lowercase()
 }
 
-fn open_named_output_stream(wanted: &str) -> Result<OutputStream, NamedDeviceStreamError> {
+/// The cpal host for `host`, or why this build cannot use it.
+fn output_host(host: OutputHost) -> Result<rodio::cpal::Host, PlaybackError> {
+    match host {
+        OutputHost::Auto => Ok(rodio::cpal::default_host()),
+        #[cfg(windows)]
+        OutputHost::Wasapi => host_from_id(host, rodio::cpal::HostId::Wasapi),
+        #[cfg(all(windows, feature = "asio"))]
+        OutputHost::Asio => host_from_id(host, rodio::cpal::HostId::Asio),
+        #[cfg(not(windows))]
+        OutputHost::Wasapi => Err(PlaybackError::AudioOutputUnavailable {
+            details: "WASAPI output is only available on Windows".to_owned(),
+        }),
+        #[cfg(not(all(windows, feature = "asio")))]
+        OutputHost::Asio => Err(PlaybackError::AudioOutputUnavailable {
+            details: "ASIO output needs a Windows build with --features asio".to_owned(),
+        }),
+    }
+}
+
+#[cfg(windows)]
+fn host_from_id(
+    host: OutputHost,
+    id: rodio::cpal::HostId,
+) -> Result<rodio::cpal::Host, PlaybackError> {
+    rodio::cpal::host_from_id(id).map_err(|e| PlaybackError::AudioOutputUnavailable {
+        details: format!("{host} output unavailable: {e}"),
+    })
+}
+
+fn stream_builder(
+    device: rodio::cpal::Device,
+    buffer_frames: Option<u32>,
+) -> Result<OutputStreamBuilder, StreamError> {
+    let builder = OutputStreamBuilder::from_device(device)?;
+    Ok(match buffer_frames {
+        Some(frames) => builder.with_buffer_size(rodio::cpal::BufferSize::Fixed(frames)),
+        None => builder,
+    })
+}
+
+fn open_named_output_stream(
+    host: &rodio::cpal::Host,
+    wanted: &str,
+    buffer_frames: Option<u32>,
+) -> Result<OutputStream, NamedDeviceStreamError> {
     let wanted_norm = normalize_device_name(wanted);
 
-    let host = rodio::cpal::default_host();
     let devices = host.output_devices().ok();
     let mut available: Vec<String> = Vec::new();
     let mut selected = None;
@@ -293,7 +467,7 @@ fn open_named_output_stream(wanted: &str) -> Result<OutputStream, NamedDeviceStr
         });
     };
 
-    match OutputStreamBuilder::from_device(device).and_then(|b| b.open_stream_or_fallback()) {
+    match stream_builder(device, buffer_frames).and_then(|b| b.open_stream_or_fallback()) {
         Ok(stream) => Ok(stream),
         Err(error) => Err(NamedDeviceStreamError::OpenFailed {
             wanted: wanted.to_owned(),
@@ -331,7 +505,21 @@ fn format_stream_error_details(err: StreamError, wanted: Option<&str>, context:
 
 #[cfg(feature = "playback-device-enum")]
 pub fn enumerate_output_device_names() -> Result<Vec<String>, PlaybackError> {
+    Ok(list_output_devices()?.into_iter().map(|d| d.name).collect())
+}
+
+/// An audio output device on the default host
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OutputDevice {
+    pub name: String,
+    pub is_default: bool,
+}
+
+/// Lists the output devices of the default audio host. Names are what
+/// [`AudioPlaybackSink::with_output_device_name`] expects.
+pub fn list_output_devices() -> Result<Vec<OutputDevice>, PlaybackError> {
     let host = rodio::cpal::default_host();
+    let default_name = host.default_output_device().and_then(|d| d.name().ok());
     let devices = host
         .output_devices()
         .map_err(|e| PlaybackError::AudioOutputUnavailable {
@@ -341,11 +529,54 @@ pub fn enumerate_output_device_names() -> Result<Vec<String>, PlaybackError> {
     let mut out = Vec::new();
     for d in devices {
         let name = d.name().unwrap_or_else(|_| "<unnamed>".to_owned());
-        out.push(name);
+        let is_default = default_name.as_deref() == Some(name.as_str());
+        out.push(OutputDevice { name, is_default });
     }
     Ok(out)
 }
 
+struct PcmSource {
+    samples: std::vec::IntoIter<i16>,
+    sample_rate: u32,
+    channels: u16,
+}
+
+impl PcmSource {
+    fn new(samples: Vec<i16>, sample_rate: u32, channels: u16) -> Self {
+        Self {
+            samples: samples.into_iter(),
+            sample_rate,
+            channels,
+        }
+    }
+}
+
+impl Iterator for PcmSource {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.samples.next().map(|s| s as f32 / i16::MAX as f32)
+    }
+}
+
+impl Source for PcmSource {
+    fn current_span_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<std::time::Duration> {
+        None
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -412,45 +643,3 @@ mod tests {
         assert_eq!(calls.load(Ordering::Relaxed), 1);
     }
 }
-
-struct PcmSource {
-    samples: std::vec::IntoIter<i16>,
-    sample_rate: u32,
-    channels: u16,
-}
-
-impl PcmSource {
-    fn new(samples: Vec<i16>, sample_rate: u32, channels: u16) -> Self {
-        Self {
-            samples: samples.into_iter(),
-            sample_rate,
-            channels,
-        }
-    }
-}
-
-impl Iterator for PcmSource {
-    type Item = f32;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        self.samples.next().map(|s| s as f32 / i16::MAX as f32)
-    }
-}
-
-impl Source for PcmSource {
-    fn current_span_len(&self) -> Option<usize> {
-        None
-    }
-
-    fn channels(&self) -> u16 {
-        self.channels
-    }
-
-    fn sample_rate(&self) -> u32 {
-        self.sample_rate
-    }
-
-    fn total_duration(&self) -> Option<std::time::Duration> {
-        None
-    }
-}
@@ -0,0 +1,190 @@
+//! The dub in a Discord voice channel, for watching the stream together
+//!
+//! A bot account joins the configured channel when the sink starts and plays
+//! each clip there as its own track; Discord does the mixing with whoever is
+//! talking. The bot needs the Connect and Speak permissions on the channel.
+
+use crate::config::DiscordVoice;
+use crate::playback::{PlaybackError, PlaybackSink};
+use crate::tts::TtsAudio;
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use serenity::all::{ChannelId, Context, EventHandler, GatewayIntents, GuildId, Ready};
+use serenity::Client;
+use songbird::input::RawAdapter;
+use songbird::tracks::PlayMode;
+use songbird::{Call, Event, EventContext, SerenityInit, Songbird, TrackEvent};
+use std::io::Cursor;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+
+/// How long to wait for the gateway and the voice connection.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// [`PlaybackSink`] that speaks into a Discord voice channel. `play` resolves
+/// once Discord has been sent the whole clip.
+#[derive(Clone)]
+pub struct DiscordPlaybackSink {
+    call: Arc<tokio::sync::Mutex<Call>>,
+    _gateway: Arc<Gateway>,
+}
+
+/// Leaves the channel and stops the bot once the last clone is gone.
+struct Gateway {
+    songbird: Arc<Songbird>,
+    guild: GuildId,
+    task: JoinHandle<()>,
+}
+
+impl Drop for Gateway {
+    fn drop(&mut self) {
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            self.task.abort();
+            return;
+        };
+        let (songbird, guild) = (Arc::clone(&self.songbird), self.guild);
+        let task = self.task.abort_handle();
+        runtime.spawn(async move {
+            let _ = songbird.remove(guild).await;
+            task.abort();
+        });
+    }
+}
+
+impl DiscordPlaybackSink {
+    /// Logs the bot in and joins `voice`'s channel.
+    pub async fn connect(voice: &DiscordVoice) -> Result<Self, PlaybackError> {
+        let songbird = Songbird::serenity();
+        let (ready_tx, ready) = oneshot::channel();
+        let mut client = Client::builder(
+            voice.token.expose(),
+            GatewayIntents::GUILDS | GatewayIntents::GUILD_VOICE_STATES,
+        )
+        .event_handler(ReadySignal(Mutex::new(Some(ready_tx))))
+        .register_songbird_with(Arc::clone(&songbird))
+        .await
+        .map_err(|e| discord_error(format!("failed to create Discord client: {e}")))?;
+        let task = tokio::spawn(async move {
+            if let Err(e) = client.start().await {
+                tracing::error!(error = %e, "Discord gateway connection failed");
+            }
+        });
+        let guild = GuildId::new(voice.guild_id);
+        let gateway = Arc::new(Gateway {
+            songbird: Arc::clone(&songbird),
+            guild,
+            task,
+        });
+
+        match tokio::time::timeout(CONNECT_TIMEOUT, ready).await {
+            Ok(Ok(())) => {}
+            Ok(Err(_)) => {
+                return Err(discord_error(
+                    "Discord gateway closed before it was ready; check the bot token".to_owned(),
+                ))
+            }
+            Err(_) => return Err(discord_error("timed out logging in to Discord".to_owned())),
+        }
+        let call = tokio::time::timeout(
+            CONNECT_TIMEOUT,
+            songbird.join(guild, ChannelId::new(voice.channel_id)),
+        )
+        .await
+        .map_err(|_| discord_error("timed out joining the voice channel".to_owned()))?
+        .map_err(|e| discord_error(format!("failed to join the voice channel: {e}")))?;
+        tracing::info!(
+            guild_id = voice.guild_id,
+            channel_id = voice.channel_id,
+            "joined Discord voice channel"
+        );
+
+        Ok(Self {
+            call,
+            _gateway: gateway,
+        })
+    }
+}
+
+impl PlaybackSink for DiscordPlaybackSink {
+    fn play(&self, audio: TtsAudio) -> BoxFuture<'_, Result<(), PlaybackError>> {
+        async move {
+            if audio.pcm_i16.is_empty() || audio.sample_rate_hz == 0 || audio.channels == 0 {
+                return Ok(());
+            }
+            // Songbird takes raw little-endian f32 and resamples it to 48 kHz.
+            let bytes: Vec<u8> = audio
+                .pcm_i16
+                .iter()
+                .flat_map(|&s| (f32::from(s) / 32_768.0).to_le_bytes())
+                .collect();
+            let input = RawAdapter::new(
+                Cursor::new(bytes),
+                audio.sample_rate_hz,
+                u32::from(audio.channels),
+            );
+
+            let (ended_tx, ended) = oneshot::channel();
+            let track = self.call.lock().await.play_input(input.into());
+            let signal = TrackEnded(Arc::new(Mutex::new(Some(ended_tx))));
+            for event in [TrackEvent::End, TrackEvent::Error] {
+                track
+                    .add_event(Event::Track(event), signal.clone())
+                    .map_err(|e| discord_error(format!("failed to track playback: {e}")))?;
+            }
+            match ended.await {
+                Ok(PlayMode::Errored(e)) => {
+                    Err(discord_error(format!("voice playback failed: {e}")))
+                }
+                Ok(_) => Ok(()),
+                Err(_) => Err(discord_error("voice connection dropped".to_owned())),
+            }
+        }
+        .boxed()
+    }
+}
+
+fn discord_error(details: String) -> PlaybackError {
+    PlaybackError::AudioOutputUnavailable { details }
+}
+
+/// Fires once the gateway session is ready to join voice channels.
+struct ReadySignal(Mutex<Option<oneshot::Sender<()>>>);
+
+#[serenity::async_trait]
+impl EventHandler for ReadySignal {
+    async fn ready(&self, _ctx: Context, ready: Ready) {
+        tracing::info!(bot = %ready.user.name, "logged in to Discord");
+        let sender = match self.0.lock() {
+            Ok(mut g) => g.take(),
+            Err(poisoned) => poisoned.into_inner().take(),
+        };
+        if let Some(sender) = sender {
+            let _ = sender.send(());
+        }
+    }
+}
+
+/// Reports how a track ended to the `play` call waiting on it.
+#[derive(Clone)]
+struct TrackEnded(Arc<Mutex<Option<oneshot::Sender<PlayMode>>>>);
+
+#[serenity::async_trait]
+impl songbird::EventHandler for TrackEnded {
+    async fn act(&self, ctx: &EventContext<'_>) -> Option<Event> {
+        if let Some((state, _)) = match ctx {
+            EventContext::Track(tracks) => tracks.first(),
+            _ => None,
+        } {
+            let sender = match self.0.lock() {
+                Ok(mut g) => g.take(),
+                Err(poisoned) => poisoned.into_inner().take(),
+            };
+            if let Some(sender) = sender {
+                let _ = sender.send(state.playing.clone());
+            }
+        }
+        Some(Event::Cancel)
+    }
+}
@@ -0,0 +1,100 @@
+//! Gain and soft peak limiting for speech on its way to the output device
+//!
+//! The gain is applied in floating point ahead of the limiter, so a clip
+//! turned up, or one that was synthesized hot, has its loudest peaks rounded
+//! off smoothly instead of clipped at full scale.
+
+use rodio::source::Source;
+use std::time::Duration;
+
+/// Peaks above this level, in full scale, are compressed.
+const THRESHOLD: f32 = 0.8;
+/// How quickly the gain reduction recovers after a peak.
+const RELEASE: Duration = Duration::from_millis(80);
+
+/// Wraps a source with a linear gain followed by a soft limiter.
+pub(crate) struct SoftLimiter<S> {
+    inner: S,
+    gain: f32,
+    /// Peak follower: jumps to each new peak, then decays at `release` per
+    /// sample.
+    envelope: f32,
+    release: f32,
+}
+
+impl<S: Source> SoftLimiter<S> {
+    pub(crate) fn new(inner: S, gain: f32) -> Self {
+        let samples_per_sec = inner.sample_rate() as f32 * f32::from(inner.channels().max(1));
+        let release = (-1.0 / (RELEASE.as_secs_f32() * samples_per_sec.max(1.0))).exp();
+        Self {
+            inner,
+            gain,
+            envelope: 0.0,
+            release,
+        }
+    }
+}
+
+/// Level a peak of `level` is brought down to: unchanged up to the
+/// threshold, then approaching full scale without reaching it.
+fn limit(level: f32) -> f32 {
+    if level <= THRESHOLD {
+        return level;
+    }
+    let headroom = 1.0 - THRESHOLD;
+    THRESHOLD + headroom * ((level - THRESHOLD) / headroom).tanh()
+}
+
+impl<S: Iterator<Item = f32>> Iterator for SoftLimiter<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let sample = self.inner.next()? * self.gain;
+        self.envelope = sample.abs().max(self.envelope * self.release);
+        if self.envelope <= THRESHOLD {
+            return Some(sample);
+        }
+        Some(sample * limit(self.envelope) / self.envelope)
+    }
+}
+
+impl<S: Source> Source for SoftLimiter<S> {
+    fn current_span_len(&self) -> Option<usize> {
+        self.inner.current_span_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.inner.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.inner.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.inner.total_duration()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rodio::buffer::SamplesBuffer;
+
+    fn limited(samples: Vec<f32>, gain: f32) -> Vec<f32> {
+        SoftLimiter::new(SamplesBuffer::new(1, 16_000, samples), gain).collect()
+    }
+
+    #[test]
+    fn quiet_audio_is_only_scaled() {
+        assert_eq!(limited(vec![0.1, -0.2, 0.3], 2.0), [0.2, -0.4, 0.6]);
+    }
+
+    #[test]
+    fn loud_peaks_stay_below_full_scale() {
+        let out = limited(vec![0.9, -1.0, 0.5, 1.0, -0.7], 2.0);
+        assert!(out.iter().all(|s| s.abs() < 1.0), "{out:?}");
+        // Signs survive and the loudest input is still the loudest output.
+        assert!(out[1] < 0.0 && out[3] > out[2]);
+    }
+}
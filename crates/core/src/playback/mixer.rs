@@ -0,0 +1,133 @@
+//! How the playback stream shows up in a Linux desktop mixer
+//!
+//! Playback goes through ALSA, whose PulseAudio and PipeWire plugins take
+//! stream properties and a target sink from the environment when a stream is
+//! opened. Setting them just before opening names the stream, gives it a
+//! media role and routes it, so pavucontrol, GNOME Settings or qpwgraph can
+//! control the translator separately from the stream player.
+
+use std::sync::OnceLock;
+
+/// Variables [`MixerProps::export`] writes. Any of them already set when it
+/// first runs came from the user and are left alone.
+const VARS: [&str; 4] = ["PULSE_PROP", "PULSE_SINK", "PIPEWIRE_ALSA", "PIPEWIRE_NODE"];
+
+/// Properties of the playback stream for PulseAudio and PipeWire
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MixerProps {
+    /// `application.name`, the name mixers group the stream under.
+    pub app_name: String,
+    /// `media.name`, e.g. the channel being translated.
+    pub media_name: Option<String>,
+    /// `media.role`, e.g. `a11y`, `video` or `music`.
+    pub media_role: Option<String>,
+    /// Sink (PulseAudio) or node (PipeWire) to play to instead of the default.
+    pub target: Option<String>,
+}
+
+impl Default for MixerProps {
+    fn default() -> Self {
+        Self {
+            app_name: "Twitch Translator".to_owned(),
+            media_name: None,
+            media_role: None,
+            target: None,
+        }
+    }
+}
+
+impl MixerProps {
+    fn properties(&self) -> Vec<(&'static str, &str)> {
+        let mut props = vec![("application.name", self.app_name.as_str())];
+        if let Some(name) = &self.media_name {
+            props.push(("media.name", name));
+        }
+        if let Some(role) = &self.media_role {
+            props.push(("media.role", role));
+        }
+        props
+    }
+
+    /// `PULSE_PROP` value: space-separated `key="value"` pairs.
+    fn pulse_prop(&self) -> String {
+        self.properties()
+            .into_iter()
+            .map(|(key, value)| format!("{key}={}", quote(value)))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// `PIPEWIRE_ALSA` value: an SPA JSON object.
+    fn pipewire_alsa(&self) -> String {
+        let props: Vec<_> = self
+            .properties()
+            .into_iter()
+            .map(|(key, value)| format!("{key}={}", quote(value)))
+            .collect();
+        format!("{{ {} }}", props.join(" "))
+    }
+
+    /// Sets the plugin variables for the next stream opened by this process.
+    /// Does nothing outside Linux.
+    pub(crate) fn export(&self) {
+        if !cfg!(target_os = "linux") {
+            return;
+        }
+        static USER_SET: OnceLock<Vec<&'static str>> = OnceLock::new();
+        let user_set = USER_SET.get_or_init(|| {
+            VARS.into_iter()
+                .filter(|var| std::env::var_os(var).is_some())
+                .collect()
+        });
+        let target = self.target.clone().unwrap_or_default();
+        let values = [
+            self.pulse_prop(),
+            target.clone(),
+            self.pipewire_alsa(),
+            target,
+        ];
+        for (var, value) in VARS.into_iter().zip(values) {
+            if user_set.contains(&var) {
+                continue;
+            }
+            if value.is_empty() {
+                std::env::remove_var(var);
+            } else {
+                std::env::set_var(var, value);
+            }
+        }
+    }
+}
+
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn props() -> MixerProps {
+        MixerProps {
+            media_name: Some("say \"hi\"".to_owned()),
+            media_role: Some("a11y".to_owned()),
+            ..MixerProps::default()
+        }
+    }
+
+    #[test]
+    fn pulse_prop_quotes_values() {
+        assert_eq!(
+            props().pulse_prop(),
+            r#"application.name="Twitch Translator" media.name="say \"hi\"" media.role="a11y""#
+        );
+    }
+
+    #[test]
+    fn pipewire_alsa_is_an_object() {
+        assert_eq!(
+            MixerProps::default().pipewire_alsa(),
+            r#"{ application.name="Twitch Translator" }"#
+        );
+    }
+}
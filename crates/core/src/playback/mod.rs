@@ -1,11 +1,38 @@
 mod audio;
+#[cfg(feature = "discord")]
+mod discord;
 mod dummy;
+mod limiter;
+mod mixer;
+#[cfg(feature = "network-audio")]
+mod network;
+#[cfg(feature = "obs")]
+mod obs;
+pub(crate) mod pacing;
+mod queue;
+mod record;
+pub(crate) mod resample;
+mod smooth;
+pub(crate) mod stretch;
+#[cfg(feature = "webrtc")]
+mod webrtc;
 
 use crate::tts::TtsAudio;
 use futures::future::BoxFuture;
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::broadcast;
 
-pub use audio::AudioPlaybackSink;
+pub use audio::{list_output_devices, AudioPlaybackSink, OutputDevice};
+#[cfg(feature = "discord")]
+pub use discord::DiscordPlaybackSink;
 pub use dummy::DummyPlaybackSink;
+pub use mixer::MixerProps;
+#[cfg(feature = "network-audio")]
+pub use network::NetworkPlaybackSink;
+#[cfg(feature = "obs")]
+pub use obs::ObsPlaybackSink;
+pub use record::RecordingSink;
 
 #[derive(thiserror::Error, Debug)]
 pub enum PlaybackError {
@@ -14,8 +41,108 @@ pub enum PlaybackError {
 
     #[error("audio output unavailable: {details}")]
     AudioOutputUnavailable { details: String },
+
+    #[error("network audio output failed: {details}")]
+    NetworkOutput { details: String },
+
+    #[error("OBS audio output failed: {details}")]
+    ObsOutput { details: String },
+
+    #[error("recording the dub failed: {details}")]
+    Recording { details: String },
+}
+
+/// A clip a queueing sink finished playing
+#[derive(Clone, Copy, Debug)]
+pub struct PlaybackFinished {
+    /// When `play` handed the clip to the sink.
+    pub queued_at: Instant,
+    /// When its last sample went to the output.
+    pub finished_at: Instant,
 }
 
 pub trait PlaybackSink: Send + Sync {
     fn play(&self, audio: TtsAudio) -> BoxFuture<'_, Result<(), PlaybackError>>;
+
+    /// For sinks whose `play` returns once a clip is queued rather than once
+    /// it has played: reports each queued clip as it finishes. `None` for
+    /// sinks that play a clip before returning.
+    fn subscribe_finished(&self) -> Option<broadcast::Receiver<PlaybackFinished>> {
+        None
+    }
+
+    /// Stops the clip playing and drops those queued behind it.
+    fn clear(&self) {}
+
+    /// Plays `audio` scaled by a linear `gain`. The default saturates samples
+    /// that would clip; sinks that can limit peaks smoothly override it.
+    fn play_with_gain(
+        &self,
+        mut audio: TtsAudio,
+        gain: f32,
+    ) -> BoxFuture<'_, Result<(), PlaybackError>> {
+        apply_gain(&mut audio.pcm_i16, gain);
+        self.play(audio)
+    }
+}
+
+/// Type-erased [`PlaybackSink`] for picking the output at runtime.
+#[derive(Clone)]
+pub struct DynPlaybackSink {
+    inner: Arc<dyn PlaybackSink>,
+}
+
+impl DynPlaybackSink {
+    pub fn new(inner: Arc<dyn PlaybackSink>) -> Self {
+        Self { inner }
+    }
+}
+
+impl PlaybackSink for DynPlaybackSink {
+    fn play(&self, audio: TtsAudio) -> BoxFuture<'_, Result<(), PlaybackError>> {
+        self.inner.play(audio)
+    }
+
+    fn play_with_gain(
+        &self,
+        audio: TtsAudio,
+        gain: f32,
+    ) -> BoxFuture<'_, Result<(), PlaybackError>> {
+        self.inner.play_with_gain(audio, gain)
+    }
+
+    fn subscribe_finished(&self) -> Option<broadcast::Receiver<PlaybackFinished>> {
+        self.inner.subscribe_finished()
+    }
+
+    fn clear(&self) {
+        self.inner.clear();
+    }
+}
+
+/// Scales PCM samples by a linear `gain`, saturating instead of wrapping.
+pub fn apply_gain(pcm: &mut [i16], gain: f32) {
+    if (gain - 1.0).abs() < f32::EPSILON {
+        return;
+    }
+    for sample in pcm.iter_mut() {
+        let scaled = (f32::from(*sample) * gain).round();
+        *sample = scaled.clamp(f32::from(i16::MIN), f32::from(i16::MAX)) as i16;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn apply_gain_scales_and_saturates() {
+        let mut pcm = [1000, -1000, 30_000, i16::MIN];
+        apply_gain(&mut pcm, 0.5);
+        assert_eq!(pcm, [500, -500, 15_000, -16_384]);
+
+        let mut pcm = [20_000, -20_000];
+        apply_gain(&mut pcm, 2.0);
+        assert_eq!(pcm, [i16::MAX, i16::MIN]);
+    }
 }
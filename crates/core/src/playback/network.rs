@@ -0,0 +1,486 @@
+//! The dub as a live Opus stream, for listening on a phone or second PC
+//!
+//! Clips are resampled to 48 kHz mono and encoded in 20 ms frames on a steady
+//! clock, with silence between clips, so listeners hear one continuous
+//! stream. The frames go out as Ogg/Opus to HTTP clients or an Icecast
+//! mount, as RTP over UDP, or to browsers over WebRTC (see `webrtc`).
+
+use crate::config::NetworkAudio;
+use crate::playback::resample::{to_48k_mono, SAMPLE_RATE_HZ};
+#[cfg(feature = "webrtc")]
+use crate::playback::webrtc::{WebRtcServer, WebRtcTrack};
+use crate::playback::{PlaybackError, PlaybackSink};
+use crate::status::PipelineStatus;
+use crate::tts::TtsAudio;
+use bytes::Bytes;
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use ogg::writing::{PacketWriteEndInfo, PacketWriter};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream, UdpSocket};
+use tokio::sync::{broadcast, mpsc, oneshot};
+use tokio::task::JoinHandle;
+
+/// Samples per 20 ms frame at 48 kHz.
+const FRAME_SAMPLES: usize = 960;
+pub(super) const FRAME_DURATION: Duration = Duration::from_millis(20);
+/// Frames per Ogg page; pages are what listeners receive, so this bounds the
+/// added latency to 100 ms.
+const FRAMES_PER_PAGE: u64 = 5;
+/// Samples the decoder discards at the start (the encoder's lookahead).
+const PRE_SKIP: u16 = 312;
+const MAX_PACKET_BYTES: usize = 4000;
+const MAX_REQUEST_HEAD_BYTES: usize = 8 * 1024;
+/// Dynamic RTP payload type the stream is sent with.
+const RTP_PAYLOAD_TYPE: u8 = 111;
+const ICECAST_RETRY: Duration = Duration::from_secs(5);
+
+struct Clip {
+    pcm: Vec<i16>,
+    played: oneshot::Sender<()>,
+}
+
+/// Aborts the sink's background tasks once the last clone is gone.
+struct Tasks(Vec<JoinHandle<()>>);
+
+impl Drop for Tasks {
+    fn drop(&mut self) {
+        for task in &self.0 {
+            task.abort();
+        }
+    }
+}
+
+/// [`PlaybackSink`] that streams the dub to another device instead of
+/// playing it locally. `play` resolves once the clip has been encoded and
+/// sent, at real-time pace.
+#[derive(Clone)]
+pub struct NetworkPlaybackSink {
+    clips: mpsc::Sender<Clip>,
+    _tasks: Arc<Tasks>,
+}
+
+impl NetworkPlaybackSink {
+    /// Starts encoding and, for [`NetworkAudio::Http`] and
+    /// [`NetworkAudio::WebRtc`], listening. WebRTC listeners also get each
+    /// translation published on `status` as a caption.
+    pub async fn start(
+        target: &NetworkAudio,
+        status: &PipelineStatus,
+    ) -> Result<Self, PlaybackError> {
+        let encoder = opus::Encoder::new(
+            SAMPLE_RATE_HZ,
+            opus::Channels::Mono,
+            opus::Application::Voip,
+        )
+        .map_err(|e| network_error(format!("failed to create Opus encoder: {e}")))?;
+        let (clips, clips_rx) = mpsc::channel(1);
+
+        let mut tasks = Vec::new();
+        let output = match target {
+            NetworkAudio::Rtp(addr) => {
+                let socket = UdpSocket::bind(if addr.starts_with('[') {
+                    "[::]:0"
+                } else {
+                    "0.0.0.0:0"
+                })
+                .await
+                .map_err(|e| network_error(format!("failed to open UDP socket: {e}")))?;
+                socket
+                    .connect(addr.as_str())
+                    .await
+                    .map_err(|e| network_error(format!("failed to resolve {addr}: {e}")))?;
+                tracing::info!(%addr, "streaming dub over RTP");
+                Output::Rtp(RtpStream::new(socket))
+            }
+            NetworkAudio::Http(addr) => {
+                let listener = TcpListener::bind(*addr)
+                    .await
+                    .map_err(|e| network_error(format!("failed to listen on {addr}: {e}")))?;
+                tracing::info!(%addr, "serving dub as Ogg/Opus over HTTP");
+                let ogg = OggStream::new();
+                tasks.push(tokio::spawn(serve_http(listener, ogg.feed())));
+                Output::Ogg(ogg)
+            }
+            NetworkAudio::Icecast {
+                host,
+                port,
+                mount,
+                user,
+                password,
+            } => {
+                let ogg = OggStream::new();
+                let source = IcecastSource {
+                    addr: format!("{host}:{port}"),
+                    mount: mount.clone(),
+                    authorization: basic_auth(user, password.expose()),
+                };
+                tasks.push(tokio::spawn(source.run(ogg.feed())));
+                Output::Ogg(ogg)
+            }
+            #[cfg(feature = "webrtc")]
+            NetworkAudio::WebRtc(addr) => {
+                let track = WebRtcTrack::new();
+                let server = WebRtcServer::new(track.clone(), status.clone())?;
+                let listener = TcpListener::bind(*addr)
+                    .await
+                    .map_err(|e| network_error(format!("failed to listen on {addr}: {e}")))?;
+                tracing::info!(%addr, "serving dub over WebRTC");
+                tasks.push(tokio::spawn(server.serve(listener)));
+                Output::WebRtc(track)
+            }
+            #[cfg(not(feature = "webrtc"))]
+            NetworkAudio::WebRtc(_) => {
+                let _ = status;
+                return Err(network_error(
+                    "webrtc:// outputs need a build with --features webrtc".to_owned(),
+                ));
+            }
+        };
+        tasks.push(tokio::spawn(encode(encoder, clips_rx, output)));
+
+        Ok(Self {
+            clips,
+            _tasks: Arc::new(Tasks(tasks)),
+        })
+    }
+}
+
+impl PlaybackSink for NetworkPlaybackSink {
+    fn play(&self, audio: TtsAudio) -> BoxFuture<'_, Result<(), PlaybackError>> {
+        async move {
+            let pcm = to_48k_mono(&audio.pcm_i16, audio.sample_rate_hz, audio.channels);
+            if pcm.is_empty() {
+                return Ok(());
+            }
+            let (played, done) = oneshot::channel();
+            self.clips
+                .send(Clip { pcm, played })
+                .await
+                .map_err(|_| network_error("network audio encoder stopped".to_owned()))?;
+            done.await
+                .map_err(|_| network_error("network audio encoder stopped".to_owned()))
+        }
+        .boxed()
+    }
+}
+
+fn network_error(details: String) -> PlaybackError {
+    PlaybackError::NetworkOutput { details }
+}
+
+/// Encodes queued clips, or silence between them, one frame per tick.
+async fn encode(mut encoder: opus::Encoder, mut clips: mpsc::Receiver<Clip>, mut output: Output) {
+    let mut ticks = tokio::time::interval(FRAME_DURATION);
+    let mut current: Option<(Clip, usize)> = None;
+    let mut frame = [0i16; FRAME_SAMPLES];
+    let mut packet = [0u8; MAX_PACKET_BYTES];
+    loop {
+        ticks.tick().await;
+        let mut filled = 0;
+        while filled < FRAME_SAMPLES {
+            if current.is_none() {
+                match clips.try_recv() {
+                    Ok(clip) => current = Some((clip, 0)),
+                    Err(mpsc::error::TryRecvError::Empty) => break,
+                    Err(mpsc::error::TryRecvError::Disconnected) => return,
+                }
+            }
+            let Some((clip, pos)) = &mut current else {
+                break;
+            };
+            let n = (FRAME_SAMPLES - filled).min(clip.pcm.len() - *pos);
+            frame[filled..filled + n].copy_from_slice(&clip.pcm[*pos..*pos + n]);
+            filled += n;
+            *pos += n;
+            if *pos == clip.pcm.len() {
+                if let Some((clip, _)) = current.take() {
+                    let _ = clip.played.send(());
+                }
+            }
+        }
+        frame[filled..].fill(0);
+
+        match encoder.encode(&frame, &mut packet) {
+            Ok(len) => output.push(&packet[..len]).await,
+            Err(e) => tracing::warn!(error = %e, "failed to encode Opus frame"),
+        }
+    }
+}
+
+enum Output {
+    Ogg(OggStream),
+    Rtp(RtpStream),
+    #[cfg(feature = "webrtc")]
+    WebRtc(WebRtcTrack),
+}
+
+impl Output {
+    async fn push(&mut self, packet: &[u8]) {
+        match self {
+            Self::Ogg(ogg) => ogg.push(packet),
+            Self::Rtp(rtp) => rtp.push(packet).await,
+            #[cfg(feature = "webrtc")]
+            Self::WebRtc(track) => track.push(packet).await,
+        }
+    }
+}
+
+/// What a new Ogg listener is sent: the header pages, then live pages.
+#[derive(Clone)]
+struct OggFeed {
+    headers: Bytes,
+    pages: broadcast::Sender<Bytes>,
+}
+
+/// One logical Ogg/Opus stream, cut into pages for every listener.
+struct OggStream {
+    writer: PacketWriter<'static, Vec<u8>>,
+    serial: u32,
+    frames: u64,
+    feed: OggFeed,
+}
+
+impl OggStream {
+    fn new() -> Self {
+        let serial = rand::random();
+        let mut writer = PacketWriter::new(Vec::new());
+        // Writing into a Vec cannot fail.
+        let _ = writer.write_packet(opus_head(), serial, PacketWriteEndInfo::EndPage, 0);
+        let _ = writer.write_packet(opus_tags(), serial, PacketWriteEndInfo::EndPage, 0);
+        let headers = Bytes::from(std::mem::take(writer.inner_mut()));
+        Self {
+            writer,
+            serial,
+            frames: 0,
+            feed: OggFeed {
+                headers,
+                pages: broadcast::channel(64).0,
+            },
+        }
+    }
+
+    fn feed(&self) -> OggFeed {
+        self.feed.clone()
+    }
+
+    fn push(&mut self, packet: &[u8]) {
+        self.frames += 1;
+        let end = if self.frames % FRAMES_PER_PAGE == 0 {
+            PacketWriteEndInfo::EndPage
+        } else {
+            PacketWriteEndInfo::NormalPacket
+        };
+        let granule = self.frames * FRAME_SAMPLES as u64;
+        let _ = self
+            .writer
+            .write_packet(packet.to_vec(), self.serial, end, granule);
+        let page = std::mem::take(self.writer.inner_mut());
+        if !page.is_empty() {
+            // No listeners is fine; the stream carries on regardless.
+            let _ = self.feed.pages.send(Bytes::from(page));
+        }
+    }
+}
+
+/// `OpusHead` identification header (RFC 7845, section 5.1), mono 48 kHz.
+fn opus_head() -> Vec<u8> {
+    let mut head = b"OpusHead".to_vec();
+    head.push(1); // version
+    head.push(1); // channels
+    head.extend_from_slice(&PRE_SKIP.to_le_bytes());
+    head.extend_from_slice(&SAMPLE_RATE_HZ.to_le_bytes());
+    head.extend_from_slice(&0i16.to_le_bytes()); // output gain
+    head.push(0); // channel mapping family
+    head
+}
+
+/// `OpusTags` comment header (RFC 7845, section 5.2) with no comments.
+fn opus_tags() -> Vec<u8> {
+    let vendor = concat!("twitch-translator ", env!("CARGO_PKG_VERSION"));
+    let mut tags = b"OpusTags".to_vec();
+    tags.extend_from_slice(&(vendor.len() as u32).to_le_bytes());
+    tags.extend_from_slice(vendor.as_bytes());
+    tags.extend_from_slice(&0u32.to_le_bytes());
+    tags
+}
+
+/// Sends the stream to `stream` until it closes or the stream ends.
+async fn send_feed(stream: &mut TcpStream, feed: &OggFeed) -> std::io::Result<()> {
+    // Subscribe before the headers go out so no page falls in between.
+    let mut pages = feed.pages.subscribe();
+    stream.write_all(&feed.headers).await?;
+    loop {
+        match pages.recv().await {
+            Ok(page) => stream.write_all(&page).await?,
+            // A slow listener skips ahead; the decoder resynchronises.
+            Err(broadcast::error::RecvError::Lagged(_)) => continue,
+            Err(broadcast::error::RecvError::Closed) => return Ok(()),
+        }
+    }
+}
+
+/// Serves the stream to every HTTP client, whatever path it asks for.
+async fn serve_http(listener: TcpListener, feed: OggFeed) {
+    loop {
+        let (mut stream, peer) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                tracing::warn!(error = %e, "network audio listener failed");
+                return;
+            }
+        };
+        let feed = feed.clone();
+        tokio::spawn(async move {
+            tracing::info!(%peer, "network audio listener connected");
+            let result = async {
+                read_head(&mut stream).await?;
+                stream
+                    .write_all(
+                        b"HTTP/1.1 200 OK\r\nContent-Type: audio/ogg\r\n\
+                          Cache-Control: no-cache, no-store\r\nConnection: close\r\n\r\n",
+                    )
+                    .await?;
+                send_feed(&mut stream, &feed).await
+            }
+            .await;
+            tracing::info!(%peer, result = ?result.err(), "network audio listener left");
+        });
+    }
+}
+
+/// Reads an HTTP request or response head up to the blank line.
+pub(super) async fn read_head(stream: &mut TcpStream) -> std::io::Result<String> {
+    let mut head = Vec::new();
+    let mut byte = [0u8; 1];
+    while !head.ends_with(b"\r\n\r\n") {
+        if head.len() >= MAX_REQUEST_HEAD_BYTES || stream.read(&mut byte).await? == 0 {
+            return Err(std::io::ErrorKind::InvalidData.into());
+        }
+        head.push(byte[0]);
+    }
+    Ok(String::from_utf8_lossy(&head).into_owned())
+}
+
+/// Pushes the stream to an Icecast mount, reconnecting when it drops.
+struct IcecastSource {
+    addr: String,
+    mount: String,
+    authorization: String,
+}
+
+impl IcecastSource {
+    async fn run(self, feed: OggFeed) {
+        loop {
+            match self.stream(&feed).await {
+                Ok(()) => return,
+                Err(e) => tracing::warn!(
+                    addr = %self.addr,
+                    mount = %self.mount,
+                    error = %e,
+                    retry_in_secs = ICECAST_RETRY.as_secs(),
+                    "Icecast source connection failed"
+                ),
+            }
+            tokio::time::sleep(ICECAST_RETRY).await;
+        }
+    }
+
+    async fn stream(&self, feed: &OggFeed) -> std::io::Result<()> {
+        let mut stream = TcpStream::connect(self.addr.as_str()).await?;
+        let request = format!(
+            "PUT {} HTTP/1.1\r\nHost: {}\r\nAuthorization: Basic {}\r\n\
+             Content-Type: audio/ogg\r\nIce-Name: Twitch Translator\r\nIce-Public: 0\r\n\
+             Expect: 100-continue\r\n\r\n",
+            self.mount, self.addr, self.authorization
+        );
+        stream.write_all(request.as_bytes()).await?;
+        let head = read_head(&mut stream).await?;
+        let status = head.split_whitespace().nth(1).unwrap_or_default();
+        if status != "100" && status != "200" {
+            let line = head.lines().next().unwrap_or_default().to_owned();
+            return Err(std::io::Error::other(format!("server refused: {line}")));
+        }
+        tracing::info!(addr = %self.addr, mount = %self.mount, "streaming dub to Icecast");
+        send_feed(&mut stream, feed).await
+    }
+}
+
+fn basic_auth(user: &str, password: &str) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let input = format!("{user}:{password}");
+    let mut out = String::new();
+    for chunk in input.as_bytes().chunks(3) {
+        let b = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = (u32::from(b[0]) << 16) | (u32::from(b[1]) << 8) | u32::from(b[2]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(char::from(ALPHABET[(n >> (18 - 6 * i)) as usize & 63]));
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// Opus in RTP (RFC 7587) to a single receiver.
+struct RtpStream {
+    socket: UdpSocket,
+    ssrc: u32,
+    sequence: u16,
+    timestamp: u32,
+}
+
+impl RtpStream {
+    fn new(socket: UdpSocket) -> Self {
+        Self {
+            socket,
+            ssrc: rand::random(),
+            sequence: rand::random(),
+            timestamp: rand::random(),
+        }
+    }
+
+    async fn push(&mut self, packet: &[u8]) {
+        let mut datagram = Vec::with_capacity(12 + packet.len());
+        datagram.push(0x80); // version 2
+        datagram.push(RTP_PAYLOAD_TYPE);
+        datagram.extend_from_slice(&self.sequence.to_be_bytes());
+        datagram.extend_from_slice(&self.timestamp.to_be_bytes());
+        datagram.extend_from_slice(&self.ssrc.to_be_bytes());
+        datagram.extend_from_slice(packet);
+        self.sequence = self.sequence.wrapping_add(1);
+        self.timestamp = self.timestamp.wrapping_add(FRAME_SAMPLES as u32);
+        if let Err(e) = self.socket.send(&datagram).await {
+            // Nobody listening yet; keep sending in case a receiver appears.
+            tracing::debug!(error = %e, "failed to send RTP packet");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn basic_auth_is_base64() {
+        assert_eq!(basic_auth("source", "hackme"), "c291cmNlOmhhY2ttZQ==");
+        assert_eq!(basic_auth("a", "b"), "YTpi");
+    }
+
+    #[test]
+    fn headers_follow_rfc_7845() {
+        let head = opus_head();
+        assert_eq!(head.len(), 19);
+        assert_eq!(&head[..8], b"OpusHead");
+        assert_eq!(u32::from_le_bytes(head[12..16].try_into().unwrap()), 48_000);
+        assert_eq!(&opus_tags()[..8], b"OpusTags");
+    }
+}
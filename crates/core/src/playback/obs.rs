@@ -0,0 +1,220 @@
+//! The dub in named shared memory, for mixing into an OBS broadcast
+//!
+//! Clips are resampled to 48 kHz mono and written on a steady 20 ms clock,
+//! with silence between clips, into a ring buffer other processes can map by
+//! name (`shm_open` on Unix, a named file mapping on Windows). An OBS plugin
+//! reads it as an audio source, so the dub lands in the stream mix without a
+//! virtual audio cable. The region holds, little-endian:
+//!
+//! | offset | size | field                                                |
+//! |--------|------|------------------------------------------------------|
+//! | 0      | 8    | magic `TTDUB001`                                     |
+//! | 8      | 4    | sample rate in Hz (48000)                            |
+//! | 12     | 4    | channels (1)                                         |
+//! | 16     | 4    | ring capacity in samples                             |
+//! | 24     | 8    | samples written so far, stored after the samples     |
+//! | 32     | 2 × capacity | signed 16-bit samples; sample `n` is at `n % capacity` |
+//!
+//! A reader remembers how many samples it has consumed, reads up to the
+//! written count (skipping ahead if it fell more than a ring behind) and
+//! waits for more.
+
+use crate::playback::resample::{to_48k_mono, SAMPLE_RATE_HZ};
+use crate::playback::{PlaybackError, PlaybackSink};
+use crate::tts::TtsAudio;
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use shared_memory::{Shmem, ShmemConf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
+
+const MAGIC: &[u8; 8] = b"TTDUB001";
+const HEADER_BYTES: usize = 32;
+const WRITTEN_OFFSET: usize = 24;
+/// Two seconds of audio, so a reader can stall briefly without losing any.
+const RING_SAMPLES: usize = 2 * SAMPLE_RATE_HZ as usize;
+/// Samples per 20 ms tick at 48 kHz.
+const TICK_SAMPLES: usize = 960;
+const TICK: Duration = Duration::from_millis(20);
+
+struct Clip {
+    pcm: Vec<i16>,
+    played: oneshot::Sender<()>,
+}
+
+/// [`PlaybackSink`] that writes the dub into a shared-memory ring for OBS
+/// instead of playing it locally. `play` resolves once the clip has been
+/// written, at real-time pace.
+#[derive(Clone)]
+pub struct ObsPlaybackSink {
+    clips: mpsc::Sender<Clip>,
+    _writer: Arc<Writer>,
+}
+
+/// Stops writing and releases the region once the last clone is gone.
+struct Writer(JoinHandle<()>);
+
+impl Drop for Writer {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+impl ObsPlaybackSink {
+    /// Creates the region `name` and starts writing silence into it.
+    pub fn create(name: &str) -> Result<Self, PlaybackError> {
+        let shmem = ShmemConf::new()
+            .size(HEADER_BYTES + RING_SAMPLES * 2)
+            .os_id(name)
+            .create()
+            .map_err(|e| obs_error(format!("failed to create shared memory '{name}': {e}")))?;
+        let ring = Ring::new(shmem);
+        let (clips, clips_rx) = mpsc::channel(1);
+        let writer = tokio::spawn(write(ring, clips_rx));
+        tracing::info!(name, "writing the dub to shared memory for OBS");
+        Ok(Self {
+            clips,
+            _writer: Arc::new(Writer(writer)),
+        })
+    }
+}
+
+impl PlaybackSink for ObsPlaybackSink {
+    fn play(&self, audio: TtsAudio) -> BoxFuture<'_, Result<(), PlaybackError>> {
+        async move {
+            let pcm = to_48k_mono(&audio.pcm_i16, audio.sample_rate_hz, audio.channels);
+            if pcm.is_empty() {
+                return Ok(());
+            }
+            let (played, done) = oneshot::channel();
+            self.clips
+                .send(Clip { pcm, played })
+                .await
+                .map_err(|_| obs_error("OBS audio writer stopped".to_owned()))?;
+            done.await
+                .map_err(|_| obs_error("OBS audio writer stopped".to_owned()))
+        }
+        .boxed()
+    }
+}
+
+fn obs_error(details: String) -> PlaybackError {
+    PlaybackError::ObsOutput { details }
+}
+
+/// The mapped region, owned by the writer task.
+struct Ring {
+    shmem: Shmem,
+    written: u64,
+}
+
+// The mapping is only touched from the task that owns it.
+unsafe impl Send for Ring {}
+
+impl Ring {
+    fn new(shmem: Shmem) -> Self {
+        let ring = Self { shmem, written: 0 };
+        // SAFETY: the region is at least HEADER_BYTES long and nobody else
+        // writes to it.
+        let header = unsafe { std::slice::from_raw_parts_mut(ring.shmem.as_ptr(), HEADER_BYTES) };
+        header.fill(0);
+        header[..8].copy_from_slice(MAGIC);
+        header[8..12].copy_from_slice(&SAMPLE_RATE_HZ.to_le_bytes());
+        header[12..16].copy_from_slice(&1u32.to_le_bytes());
+        header[16..20].copy_from_slice(&(RING_SAMPLES as u32).to_le_bytes());
+        ring.publish();
+        ring
+    }
+
+    fn push(&mut self, samples: &[i16]) {
+        let base = self.shmem.as_ptr();
+        for &sample in samples {
+            let at = HEADER_BYTES + (self.written as usize % RING_SAMPLES) * 2;
+            // SAFETY: `at + 2` is within the region, sized for RING_SAMPLES.
+            unsafe {
+                std::ptr::copy_nonoverlapping(sample.to_le_bytes().as_ptr(), base.add(at), 2);
+            }
+            self.written += 1;
+        }
+        self.publish();
+    }
+
+    /// Stores the written count after the samples, so readers never see a
+    /// count ahead of the data.
+    fn publish(&self) {
+        // SAFETY: the mapping is page aligned, so the 8-byte field at offset
+        // 24 is aligned for an AtomicU64.
+        let written = unsafe { &*(self.shmem.as_ptr().add(WRITTEN_OFFSET) as *const AtomicU64) };
+        written.store(self.written.to_le(), Ordering::Release);
+    }
+}
+
+/// Writes queued clips, or silence between them, one tick at a time.
+async fn write(mut ring: Ring, mut clips: mpsc::Receiver<Clip>) {
+    let mut ticks = tokio::time::interval(TICK);
+    let mut current: Option<(Clip, usize)> = None;
+    let mut tick = [0i16; TICK_SAMPLES];
+    loop {
+        ticks.tick().await;
+        let mut filled = 0;
+        while filled < TICK_SAMPLES {
+            if current.is_none() {
+                match clips.try_recv() {
+                    Ok(clip) => current = Some((clip, 0)),
+                    Err(mpsc::error::TryRecvError::Empty) => break,
+                    Err(mpsc::error::TryRecvError::Disconnected) => return,
+                }
+            }
+            let Some((clip, pos)) = &mut current else {
+                break;
+            };
+            let n = (TICK_SAMPLES - filled).min(clip.pcm.len() - *pos);
+            tick[filled..filled + n].copy_from_slice(&clip.pcm[*pos..*pos + n]);
+            filled += n;
+            *pos += n;
+            if *pos == clip.pcm.len() {
+                if let Some((clip, _)) = current.take() {
+                    let _ = clip.played.send(());
+                }
+            }
+        }
+        tick[filled..].fill(0);
+        ring.push(&tick);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ring_wraps_and_publishes_the_written_count() {
+        let name = format!("tt-test-{}", std::process::id());
+        let shmem = ShmemConf::new()
+            .size(HEADER_BYTES + RING_SAMPLES * 2)
+            .os_id(&name)
+            .create()
+            .unwrap();
+        let mut ring = Ring::new(shmem);
+        let base = ring.shmem.as_ptr();
+        // SAFETY: reads back the region this test owns, between writes.
+        let read = |at: usize, len: usize| unsafe {
+            std::slice::from_raw_parts(base.add(at), len).to_vec()
+        };
+        assert_eq!(read(0, 8), MAGIC);
+        assert_eq!(read(8, 4), 48_000u32.to_le_bytes());
+
+        ring.push(&vec![1; RING_SAMPLES - 1]);
+        ring.push(&[7, 8]);
+        assert_eq!(read(24, 8), (RING_SAMPLES as u64 + 1).to_le_bytes());
+        let sample = |i: usize| {
+            let bytes = read(HEADER_BYTES + i * 2, 2);
+            i16::from_le_bytes([bytes[0], bytes[1]])
+        };
+        assert_eq!(sample(RING_SAMPLES - 1), 7);
+        assert_eq!(sample(0), 8);
+    }
+}
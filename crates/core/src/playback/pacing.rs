@@ -0,0 +1,195 @@
+//! Pacing the dub to the speaker
+//!
+//! Speech comes out of TTS in bursts: a slow API answer holds back a few
+//! utterances, then releases them at once, and the dub races through them
+//! back to back before falling silent. A [`Pacer`] evens this out with a
+//! token bucket of speech, refilled at one second per second up to
+//! [`BURST`] and spent by each clip played; once clips come faster than the
+//! bucket allows, they are sped up to catch up. Each clip is also held to
+//! the streamer's speaking rate: one that takes longer than the original
+//! did is sped up a little, and one that finishes early waits a moment
+//! before it starts, so the dub keeps the rhythm of the speech it
+//! translates.
+
+use crate::asr::TranscriptSegment;
+use crate::playback::smooth::smooth_clip;
+use crate::playback::stretch::time_stretch;
+use crate::tts::TtsAudio;
+use std::time::{Duration, Instant};
+
+/// Speech that may queue up ahead of the output before clips are sped up.
+const BURST: Duration = Duration::from_secs(3);
+/// Backlog beyond [`BURST`] at which clips are sped up all the way.
+const CATCH_UP: Duration = Duration::from_secs(4);
+/// The most a clip is sped up; beyond it speech sounds rushed.
+const MAX_TEMPO: f32 = 1.25;
+/// The longest pause before a clip that came out faster than the original.
+const MAX_GAP: Duration = Duration::from_millis(400);
+/// Fewer words than this give no reliable rate.
+const MIN_WORDS: usize = 3;
+/// Nor does less speech than this.
+const MIN_SPEECH: Duration = Duration::from_millis(500);
+/// Speed-ups smaller than this are not worth stretching the clip for.
+const MIN_STRETCH: f32 = 1.02;
+
+/// Words per second in `transcript`, the speaking rate of
+/// [`crate::emotion::ProsodyFeatures`]: over its timed words when the
+/// backend reports them, else over the chunk from where speech begins.
+pub(crate) fn speaking_rate(transcript: &TranscriptSegment) -> Option<f32> {
+    let words = transcript.text.split_whitespace().count();
+    let speech = match (transcript.words.first(), transcript.words.last()) {
+        (Some(first), Some(last)) => last.end.saturating_sub(first.start),
+        _ => transcript
+            .audio_duration
+            .saturating_sub(transcript.speech_start.unwrap_or_default()),
+    };
+    (words >= MIN_WORDS && speech >= MIN_SPEECH).then(|| words as f32 / speech.as_secs_f32())
+}
+
+/// How long `text` takes to say at `speaking_rate` words per second, if it
+/// has enough words to tell. Scripts written without spaces have none.
+pub(crate) fn spoken_length(text: &str, speaking_rate: f32) -> Option<Duration> {
+    let words = text.split_whitespace().count();
+    (words >= MIN_WORDS && speaking_rate > 0.0)
+        .then(|| Duration::from_secs_f32(words as f32 / speaking_rate))
+}
+
+/// A clip ready to play once `wait` has passed
+pub(crate) struct Paced {
+    pub audio: TtsAudio,
+    pub wait: Duration,
+}
+
+/// Paces the clips of one output
+#[derive(Default)]
+pub(crate) struct Pacer {
+    /// When the clips handed out so far are done playing.
+    busy_until: Option<Instant>,
+}
+
+impl Pacer {
+    /// Paces `audio`, which the speaker said in `spoken` if known, to play
+    /// from `now`. The silence TTS leaves around speech is trimmed first, so
+    /// it is not taken for slow speech.
+    pub(crate) fn pace(
+        &mut self,
+        audio: TtsAudio,
+        spoken: Option<Duration>,
+        now: Instant,
+    ) -> Paced {
+        let audio = TtsAudio {
+            pcm_i16: smooth_clip(audio.pcm_i16, audio.sample_rate_hz, audio.channels),
+            ..audio
+        };
+        if audio.pcm_i16.is_empty() {
+            return Paced {
+                audio,
+                wait: Duration::ZERO,
+            };
+        }
+        let length = clip_length(&audio);
+        let backlog = self
+            .busy_until
+            .map_or(Duration::ZERO, |until| until.saturating_duration_since(now));
+        let mut tempo = 1.0_f32;
+        let mut wait = Duration::ZERO;
+        match spoken.filter(|spoken| !spoken.is_zero()) {
+            Some(spoken) if length > spoken => {
+                tempo = length.as_secs_f32() / spoken.as_secs_f32();
+            }
+            // A pause only helps while nothing is playing; otherwise the
+            // clip already waits its turn.
+            Some(spoken) if backlog.is_zero() => wait = (spoken - length).min(MAX_GAP),
+            _ => {}
+        }
+        if let Some(over) = backlog.checked_sub(BURST) {
+            let behind = (over.as_secs_f32() / CATCH_UP.as_secs_f32()).min(1.0);
+            tempo = tempo.max(1.0 + (MAX_TEMPO - 1.0) * behind);
+        }
+        let tempo = tempo.min(MAX_TEMPO);
+        let audio = if tempo >= MIN_STRETCH {
+            TtsAudio {
+                pcm_i16: time_stretch(&audio.pcm_i16, audio.sample_rate_hz, audio.channels, tempo),
+                ..audio
+            }
+        } else {
+            audio
+        };
+        self.busy_until = Some(now + backlog + wait + clip_length(&audio));
+        Paced { audio, wait }
+    }
+
+    /// Forgets what was queued, e.g. after the sink was cleared.
+    pub(crate) fn reset(&mut self) {
+        self.busy_until = None;
+    }
+}
+
+fn clip_length(audio: &TtsAudio) -> Duration {
+    let frames = audio.pcm_i16.len() / usize::from(audio.channels.max(1));
+    Duration::from_secs_f64(frames as f64 / f64::from(audio.sample_rate_hz.max(1)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    fn tone(secs: f32) -> TtsAudio {
+        let rate = 16_000;
+        TtsAudio {
+            sample_rate_hz: rate,
+            channels: 1,
+            pcm_i16: (0..(rate as f32 * secs) as usize)
+                .map(|i| ((2.0 * PI * 220.0 * i as f32 / rate as f32).sin() * 8000.0) as i16)
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn measures_the_speaking_rate() {
+        let transcript = TranscriptSegment {
+            text: "we are finally at the boss".to_owned(),
+            audio_duration: Duration::from_secs(4),
+            confidence: None,
+            words: Vec::new(),
+            speech_start: Some(Duration::from_secs(1)),
+        };
+        assert_eq!(speaking_rate(&transcript), Some(2.0));
+        assert_eq!(
+            spoken_length("wir sind endlich beim Endboss", 2.5),
+            Some(Duration::from_secs(2))
+        );
+        assert_eq!(spoken_length("ついにラスボスだ", 2.5), None);
+    }
+
+    #[test]
+    fn speeds_up_long_clips_and_holds_back_short_ones() {
+        let now = Instant::now();
+        let mut pacer = Pacer::default();
+        let paced = pacer.pace(tone(2.4), Some(Duration::from_secs(2)), now);
+        assert_eq!(paced.wait, Duration::ZERO);
+        assert!((clip_length(&paced.audio).as_secs_f32() - 2.0).abs() < 0.01);
+
+        // Still playing the first clip: no pause, and no speed-up within the burst.
+        let paced = pacer.pace(tone(1.0), Some(Duration::from_secs(2)), now);
+        assert_eq!(paced.wait, Duration::ZERO);
+        assert_eq!(clip_length(&paced.audio), Duration::from_secs(1));
+
+        let later = now + Duration::from_secs(10);
+        let paced = pacer.pace(tone(1.0), Some(Duration::from_secs(2)), later);
+        assert_eq!(paced.wait, MAX_GAP);
+    }
+
+    #[test]
+    fn catches_up_after_a_burst() {
+        let now = Instant::now();
+        let mut pacer = Pacer::default();
+        let lengths: Vec<f32> = (0..6)
+            .map(|_| clip_length(&pacer.pace(tone(2.0), None, now).audio).as_secs_f32())
+            .collect();
+        assert_eq!(lengths[..2], [2.0, 2.0]);
+        assert!(lengths[2] < 2.0 && lengths[5] <= lengths[2]);
+        assert!(lengths[5] >= 2.0 / MAX_TEMPO - 0.01);
+    }
+}
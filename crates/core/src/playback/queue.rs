@@ -0,0 +1,89 @@
+//! Completion tracking for clips queued on a persistent rodio `Sink`
+
+use rodio::source::Source;
+use std::time::Duration;
+
+/// Wraps a source and runs a callback once, when its last sample has been
+/// read. A source dropped early, e.g. by `Sink::clear`, never runs it.
+pub(crate) struct OnFinished<S> {
+    inner: S,
+    on_finished: Option<Box<dyn FnOnce() + Send>>,
+}
+
+impl<S> OnFinished<S> {
+    pub(crate) fn new(inner: S, on_finished: impl FnOnce() + Send + 'static) -> Self {
+        Self {
+            inner,
+            on_finished: Some(Box::new(on_finished)),
+        }
+    }
+}
+
+impl<S: Iterator<Item = f32>> Iterator for OnFinished<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let sample = self.inner.next();
+        if sample.is_none() {
+            if let Some(on_finished) = self.on_finished.take() {
+                on_finished();
+            }
+        }
+        sample
+    }
+}
+
+impl<S: Source> Source for OnFinished<S> {
+    fn current_span_len(&self) -> Option<usize> {
+        self.inner.current_span_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.inner.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.inner.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.inner.total_duration()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rodio::buffer::SamplesBuffer;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[test]
+    fn callback_runs_once_after_the_last_sample() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&calls);
+        let mut source =
+            OnFinished::new(SamplesBuffer::new(1, 16_000, vec![0.1, 0.2]), move || {
+                counter.fetch_add(1, Ordering::Relaxed);
+            });
+
+        assert_eq!(source.next(), Some(0.1));
+        assert_eq!(source.next(), Some(0.2));
+        assert_eq!(calls.load(Ordering::Relaxed), 0);
+        assert_eq!(source.next(), None);
+        assert_eq!(source.next(), None);
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn dropped_source_never_runs_callback() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&calls);
+        let source = OnFinished::new(SamplesBuffer::new(1, 16_000, vec![0.1]), move || {
+            counter.fetch_add(1, Ordering::Relaxed);
+        });
+
+        drop(source);
+        assert_eq!(calls.load(Ordering::Relaxed), 0);
+    }
+}
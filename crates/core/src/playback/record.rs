@@ -0,0 +1,208 @@
+//! Recording the dub to a file as it plays
+//!
+//! [`RecordingSink`] hands each clip to the sink it wraps and also pipes it,
+//! resampled to 48 kHz mono, into an ffmpeg process that encodes the file as
+//! WAV, MP3, Opus or AAC. Each clip is placed at the time it was queued, with
+//! silence in between, so the recording lines up with the session; a clip
+//! queued before the one ahead of it has ended follows it instead.
+
+use crate::config::RecordFormat;
+use crate::ingest::push::ffmpeg_binary;
+use crate::playback::resample::{to_48k_mono, SAMPLE_RATE_HZ};
+use crate::playback::{PlaybackError, PlaybackFinished, PlaybackSink};
+use crate::tts::TtsAudio;
+use futures::future::BoxFuture;
+use std::path::Path;
+use std::process::Stdio;
+use std::time::Instant;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::ChildStdin;
+use tokio::sync::{broadcast, mpsc};
+
+/// Clips waiting for ffmpeg before further ones are left out of the
+/// recording rather than holding up playback.
+const QUEUE_CLIPS: usize = 32;
+
+struct Clip {
+    queued_at: Instant,
+    pcm: Vec<i16>,
+}
+
+/// [`PlaybackSink`] that plays through another sink and records what it is
+/// given into a file, at the volume it was synthesized at whatever the
+/// playback gain.
+pub struct RecordingSink<S> {
+    inner: S,
+    clips: mpsc::Sender<Clip>,
+}
+
+impl<S: PlaybackSink> RecordingSink<S> {
+    /// Starts recording into `path`, in the format its extension names, at
+    /// `bitrate_kbps` or the format's default, and plays through `inner`.
+    /// The file is complete once the sink is dropped and ffmpeg has flushed
+    /// it.
+    pub fn start(inner: S, path: &Path, bitrate_kbps: Option<u32>) -> Result<Self, PlaybackError> {
+        let format = RecordFormat::from_path(path).ok_or_else(|| {
+            recording_error(format!(
+                "{}: expected a .wav, .mp3, .opus, .ogg or .aac file",
+                path.display()
+            ))
+        })?;
+        let bitrate_kbps = format
+            .default_bitrate_kbps()
+            .map(|default| bitrate_kbps.unwrap_or(default));
+        let mut child = tokio::process::Command::new(ffmpeg_binary())
+            .args(["-hide_banner", "-nostdin", "-loglevel", "warning", "-y"])
+            .args(["-f", "s16le", "-ac", "1", "-ar"])
+            .arg(SAMPLE_RATE_HZ.to_string())
+            .args(["-i", "pipe:0"])
+            .args(encoder_args(format, bitrate_kbps))
+            .arg(path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| recording_error(format!("failed to start ffmpeg: {e}")))?;
+        let stdin = child
+            .stdin
+            .take()
+            .ok_or_else(|| recording_error("ffmpeg stdin unavailable".to_owned()))?;
+        if let Some(stderr) = child.stderr.take() {
+            tokio::spawn(async move {
+                let mut lines = BufReader::new(stderr).lines();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    tracing::warn!(target: "ffmpeg", "dub recording: {}", line);
+                }
+            });
+        }
+
+        let (clips, clips_rx) = mpsc::channel(QUEUE_CLIPS);
+        tokio::spawn(async move {
+            if let Err(e) = write(stdin, clips_rx).await {
+                tracing::warn!(error = %e, "stopped recording the dub");
+            }
+            // With its input closed, ffmpeg finishes the file and exits.
+            match child.wait().await {
+                Ok(status) if !status.success() => {
+                    tracing::warn!(%status, "dub recording ffmpeg failed");
+                }
+                Ok(_) => {}
+                Err(e) => tracing::warn!(error = %e, "dub recording ffmpeg failed"),
+            }
+        });
+        tracing::info!(
+            path = %path.display(),
+            %format,
+            bitrate_kbps,
+            "recording the dub"
+        );
+        Ok(Self { inner, clips })
+    }
+
+    fn record(&self, audio: &TtsAudio) {
+        let pcm = to_48k_mono(&audio.pcm_i16, audio.sample_rate_hz, audio.channels);
+        if pcm.is_empty() {
+            return;
+        }
+        let clip = Clip {
+            queued_at: Instant::now(),
+            pcm,
+        };
+        // Once closed, the writer has already logged why it stopped.
+        if let Err(mpsc::error::TrySendError::Full(_)) = self.clips.try_send(clip) {
+            tracing::warn!("dub recording is falling behind; leaving a clip out");
+        }
+    }
+}
+
+impl<S: PlaybackSink> PlaybackSink for RecordingSink<S> {
+    fn play(&self, audio: TtsAudio) -> BoxFuture<'_, Result<(), PlaybackError>> {
+        self.record(&audio);
+        self.inner.play(audio)
+    }
+
+    fn play_with_gain(
+        &self,
+        audio: TtsAudio,
+        gain: f32,
+    ) -> BoxFuture<'_, Result<(), PlaybackError>> {
+        self.record(&audio);
+        self.inner.play_with_gain(audio, gain)
+    }
+
+    fn subscribe_finished(&self) -> Option<broadcast::Receiver<PlaybackFinished>> {
+        self.inner.subscribe_finished()
+    }
+
+    fn clear(&self) {
+        self.inner.clear();
+    }
+}
+
+fn recording_error(details: String) -> PlaybackError {
+    PlaybackError::Recording { details }
+}
+
+/// ffmpeg output arguments that encode to `format`, at `bitrate_kbps` for
+/// compressed formats.
+fn encoder_args(format: RecordFormat, bitrate_kbps: Option<u32>) -> Vec<String> {
+    let (codec, muxer) = match format {
+        RecordFormat::Wav => ("pcm_s16le", "wav"),
+        RecordFormat::Mp3 => ("libmp3lame", "mp3"),
+        RecordFormat::Opus => ("libopus", "ogg"),
+        RecordFormat::Aac => ("aac", "adts"),
+    };
+    let mut args = vec!["-c:a".to_owned(), codec.to_owned()];
+    if let Some(kbps) = bitrate_kbps {
+        args.push("-b:a".to_owned());
+        args.push(format!("{kbps}k"));
+    }
+    args.push("-f".to_owned());
+    args.push(muxer.to_owned());
+    args
+}
+
+/// Pipes each clip into ffmpeg at its offset from the start of the
+/// recording, with silence up to it, until the sink is dropped.
+async fn write(mut stdin: ChildStdin, mut clips: mpsc::Receiver<Clip>) -> std::io::Result<()> {
+    const SILENCE: [u8; 4096] = [0; 4096];
+    let started = Instant::now();
+    // Samples written so far.
+    let mut written: u64 = 0;
+    while let Some(clip) = clips.recv().await {
+        let offset = clip.queued_at.saturating_duration_since(started);
+        let at = (offset.as_secs_f64() * f64::from(SAMPLE_RATE_HZ)) as u64;
+        let mut missing = at.saturating_sub(written) * 2;
+        while missing > 0 {
+            let n = missing.min(SILENCE.len() as u64) as usize;
+            stdin.write_all(&SILENCE[..n]).await?;
+            missing -= n as u64;
+        }
+        let bytes: Vec<u8> = clip.pcm.iter().flat_map(|s| s.to_le_bytes()).collect();
+        stdin.write_all(&bytes).await?;
+        written = written.max(at) + clip.pcm.len() as u64;
+    }
+    stdin.shutdown().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_in_the_format_asked_for() {
+        assert_eq!(
+            encoder_args(RecordFormat::Opus, Some(32)),
+            ["-c:a", "libopus", "-b:a", "32k", "-f", "ogg"]
+        );
+        assert_eq!(
+            encoder_args(RecordFormat::Wav, None),
+            ["-c:a", "pcm_s16le", "-f", "wav"]
+        );
+        assert_eq!(
+            RecordFormat::from_path(Path::new("session.MP3")),
+            Some(RecordFormat::Mp3)
+        );
+        assert_eq!(RecordFormat::from_path(Path::new("session.flac")), None);
+    }
+}
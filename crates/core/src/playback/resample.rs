@@ -0,0 +1,45 @@
+//! Shared by the outputs that carry the dub as 48 kHz mono, and by the dubbed
+//! track of [`crate::vod`]
+
+pub(crate) const SAMPLE_RATE_HZ: u32 = 48_000;
+
+/// Downmixes interleaved `pcm` to mono and resamples it to 48 kHz.
+pub(crate) fn to_48k_mono(pcm: &[i16], sample_rate_hz: u32, channels: u16) -> Vec<i16> {
+    let channels = usize::from(channels.max(1));
+    if sample_rate_hz == 0 {
+        return Vec::new();
+    }
+    let mono: Vec<f32> = pcm
+        .chunks_exact(channels)
+        .map(|frame| frame.iter().map(|&s| f32::from(s)).sum::<f32>() / channels as f32)
+        .collect();
+    if sample_rate_hz == SAMPLE_RATE_HZ {
+        return mono.into_iter().map(|s| s as i16).collect();
+    }
+    // Linear interpolation: plenty for speech headed for a voice codec.
+    let step = f64::from(sample_rate_hz) / f64::from(SAMPLE_RATE_HZ);
+    let out_len = (mono.len() as f64 / step) as usize;
+    (0..out_len)
+        .map(|i| {
+            let at = i as f64 * step;
+            let j = at as usize;
+            let frac = (at - j as f64) as f32;
+            let a = mono[j];
+            let b = mono.get(j + 1).copied().unwrap_or(a);
+            (a + (b - a) * frac) as i16
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn audio_is_downmixed_and_resampled() {
+        assert_eq!(to_48k_mono(&[100, 300, -50, 50], 48_000, 2), [200, 0]);
+        // 24 kHz doubles, filling in halfway values.
+        assert_eq!(to_48k_mono(&[0, 100], 24_000, 1), [0, 50, 100, 100]);
+        assert!(to_48k_mono(&[1, 2], 0, 1).is_empty());
+    }
+}
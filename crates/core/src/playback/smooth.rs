@@ -0,0 +1,86 @@
+//! Edge cleanup for TTS clips queued back to back
+//!
+//! Synthesized clips tend to start and end with a stretch of near silence,
+//! which leaves audible gaps in continuous narration, and to start or stop
+//! on a non-zero sample, which clicks at the join. Clips are trimmed to their
+//! speech plus a little padding, then faded in and out over a few
+//! milliseconds so neighbouring clips meet at zero.
+
+use std::time::Duration;
+
+/// Samples at or below this magnitude count as silence (about -40 dBFS).
+const SILENCE_LEVEL: u16 = 300;
+/// Silence kept around the speech, so soft onsets and releases survive.
+const PADDING: Duration = Duration::from_millis(40);
+/// Length of the fade at each end of a clip.
+const FADE: Duration = Duration::from_millis(5);
+
+/// Trims silence from both ends of interleaved `pcm` and fades the ends.
+/// A clip that is silent throughout comes back empty.
+pub(crate) fn smooth_clip(mut pcm: Vec<i16>, sample_rate_hz: u32, channels: u16) -> Vec<i16> {
+    let channels = usize::from(channels.max(1));
+    let frames_in = |d: Duration| (f64::from(sample_rate_hz) * d.as_secs_f64()) as usize;
+    let is_loud = |frame: &[i16]| frame.iter().any(|s| s.unsigned_abs() > SILENCE_LEVEL);
+
+    let Some(first) = pcm.chunks_exact(channels).position(is_loud) else {
+        return Vec::new();
+    };
+    let last = pcm
+        .chunks_exact(channels)
+        .rposition(is_loud)
+        .unwrap_or(first);
+    let padding = frames_in(PADDING);
+    let start = first.saturating_sub(padding);
+    let end = (last + 1 + padding).min(pcm.len() / channels);
+    pcm.truncate(end * channels);
+    pcm.drain(..start * channels);
+
+    let frames = pcm.len() / channels;
+    let fade = frames_in(FADE).min(frames / 2);
+    for i in 0..fade {
+        let gain = i as f32 / fade as f32;
+        for at in [i, frames - 1 - i] {
+            for sample in &mut pcm[at * channels..(at + 1) * channels] {
+                *sample = (f32::from(*sample) * gain).round() as i16;
+            }
+        }
+    }
+    pcm
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn silence_is_trimmed_down_to_the_padding() {
+        let mut pcm = vec![0; 100];
+        pcm.extend([10_000; 20]);
+        pcm.extend([0; 100]);
+
+        // 1 kHz: 40 frames of padding either side of the 20 loud ones.
+        let out = smooth_clip(pcm, 1_000, 1);
+        assert_eq!(out.len(), 100);
+        assert!(out[40..60].iter().all(|&s| s == 10_000));
+    }
+
+    #[test]
+    fn ends_are_faded_to_zero() {
+        let out = smooth_clip(vec![10_000; 100], 1_000, 1);
+        assert_eq!(out.len(), 100);
+        assert_eq!((out[0], out[2], out[99]), (0, 4_000, 0));
+        assert_eq!(out[50], 10_000);
+    }
+
+    #[test]
+    fn fade_applies_to_whole_frames() {
+        let out = smooth_clip([10_000i16, -10_000].repeat(50), 1_000, 2);
+        assert_eq!(&out[..2], &[0, 0]);
+        assert_eq!(&out[4..6], &[4_000, -4_000]);
+    }
+
+    #[test]
+    fn silent_clip_comes_back_empty() {
+        assert!(smooth_clip(vec![100; 50], 1_000, 1).is_empty());
+    }
+}
@@ -0,0 +1,182 @@
+//! Changing the tempo or pitch of a clip without changing the other, for
+//! pacing the dub ([`super::pacing`]) and matching its voice to the
+//! speaker's
+
+use crate::config::SpeakerGender;
+use crate::emotion::{estimate_pitch, gender_of_pitch, typical_pitch};
+use crate::tts::TtsAudio;
+use std::f32::consts::PI;
+
+/// The furthest a voice is shifted, about five semitones either way; beyond
+/// it the voice sounds processed.
+const MAX_PITCH_SHIFT: f32 = 1.33;
+
+/// Plays interleaved `pcm` `tempo` times as fast without changing its
+/// pitch. Windows taken `tempo` times further apart than they are laid down
+/// are overlap-added, each shifted to where it best continues the one before
+/// (WSOLA).
+pub(crate) fn time_stretch(
+    pcm: &[i16],
+    sample_rate_hz: u32,
+    channels: u16,
+    tempo: f32,
+) -> Vec<i16> {
+    let channels = usize::from(channels.max(1));
+    let frames = pcm.len() / channels;
+    let frames_in = |ms: f32| (sample_rate_hz as f32 * ms / 1000.0) as usize;
+    let window = frames_in(30.0).max(2) & !1;
+    let hop = window / 2;
+    let tolerance = frames_in(10.0);
+    if (tempo - 1.0).abs() < 1e-3 || tempo <= 0.0 || frames < 2 * window + tolerance {
+        return pcm.to_vec();
+    }
+    // Periodic Hann windows at half overlap add up to one.
+    let weights: Vec<f32> = (0..window)
+        .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f32 / window as f32).cos())
+        .collect();
+    let mono = |frame: usize| -> f32 {
+        pcm[frame * channels..(frame + 1) * channels]
+            .iter()
+            .map(|&s| f32::from(s))
+            .sum()
+    };
+
+    let out_frames = (frames as f32 / tempo) as usize;
+    let mut out = vec![0.0_f32; (out_frames + window) * channels];
+    let mut previous = 0;
+    for k in 0.. {
+        let nominal = (k as f32 * hop as f32 * tempo) as usize;
+        if nominal + window + tolerance > frames {
+            break;
+        }
+        let start = if k == 0 {
+            0
+        } else {
+            // The stretch that would have followed the previous window.
+            let natural = previous + hop;
+            let similarity = |at: usize| -> f32 {
+                (0..hop)
+                    .step_by(4)
+                    .map(|i| mono(at + i) * mono(natural + i))
+                    .sum()
+            };
+            (nominal.saturating_sub(tolerance)..=nominal + tolerance)
+                .map(|at| (at, similarity(at)))
+                .max_by(|a, b| a.1.total_cmp(&b.1))
+                .map_or(nominal, |(at, _)| at)
+        };
+        let at = k * hop;
+        for (i, weight) in weights.iter().enumerate() {
+            for c in 0..channels {
+                out[(at + i) * channels + c] += weight * f32::from(pcm[(start + i) * channels + c]);
+            }
+        }
+        previous = start;
+    }
+    out.truncate(out_frames * channels);
+    out.into_iter().map(to_i16).collect()
+}
+
+/// Raises the pitch of interleaved `pcm` by `ratio` (lowers it below one)
+/// without changing its length: the clip is resampled, which moves pitch and
+/// tempo together, then stretched back to length.
+pub(crate) fn shift_pitch(pcm: &[i16], sample_rate_hz: u32, channels: u16, ratio: f32) -> Vec<i16> {
+    let channels = usize::from(channels.max(1));
+    let frames = pcm.len() / channels;
+    if (ratio - 1.0).abs() < 1e-3 || ratio <= 0.0 || frames < 2 {
+        return pcm.to_vec();
+    }
+    // Linear interpolation, as in `super::resample`.
+    let out_frames = (frames as f32 / ratio) as usize;
+    let mut resampled = Vec::with_capacity(out_frames * channels);
+    for i in 0..out_frames {
+        let at = i as f32 * ratio;
+        let j = (at as usize).min(frames - 1);
+        let next = (j + 1).min(frames - 1);
+        let frac = at - j as f32;
+        for c in 0..channels {
+            let a = f32::from(pcm[j * channels + c]);
+            let b = f32::from(pcm[next * channels + c]);
+            resampled.push(to_i16(a + (b - a) * frac));
+        }
+    }
+    time_stretch(&resampled, sample_rate_hz, channels as u16, 1.0 / ratio)
+}
+
+/// `audio` shifted into the pitch range of `gender` if it sounds outside
+/// it, so a voice of the other range still roughly matches the speaker.
+pub(crate) fn pitch_into_range(audio: TtsAudio, gender: SpeakerGender) -> TtsAudio {
+    let channels = usize::from(audio.channels.max(1));
+    let mono: Vec<f32> = audio
+        .pcm_i16
+        .chunks_exact(channels)
+        .map(|frame| frame.iter().map(|&s| f32::from(s)).sum::<f32>() / channels as f32 / 32768.0)
+        .collect();
+    let Some(pitch) = estimate_pitch(&mono, audio.sample_rate_hz) else {
+        return audio;
+    };
+    if gender_of_pitch(pitch) == Some(gender) {
+        return audio;
+    }
+    let ratio = (typical_pitch(gender) / pitch).clamp(1.0 / MAX_PITCH_SHIFT, MAX_PITCH_SHIFT);
+    TtsAudio {
+        pcm_i16: shift_pitch(&audio.pcm_i16, audio.sample_rate_hz, audio.channels, ratio),
+        ..audio
+    }
+}
+
+fn to_i16(sample: f32) -> i16 {
+    sample
+        .round()
+        .clamp(f32::from(i16::MIN), f32::from(i16::MAX)) as i16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tone(pitch_hz: f32, secs: f32) -> Vec<i16> {
+        let rate = 16_000.0;
+        (0..(rate * secs) as usize)
+            .map(|i| ((2.0 * PI * pitch_hz * i as f32 / rate).sin() * 8000.0) as i16)
+            .collect()
+    }
+
+    fn pitch_of(pcm: &[i16]) -> f32 {
+        let samples: Vec<f32> = pcm.iter().map(|&s| f32::from(s) / 32768.0).collect();
+        estimate_pitch(&samples, 16_000).unwrap()
+    }
+
+    #[test]
+    fn stretches_without_changing_pitch() {
+        for tempo in [0.8, 1.25] {
+            let stretched = time_stretch(&tone(200.0, 1.0), 16_000, 1, tempo);
+            assert_eq!(stretched.len(), (16_000.0 / tempo) as usize);
+            assert!((pitch_of(&stretched) - 200.0).abs() < 6.0);
+        }
+    }
+
+    #[test]
+    fn shifts_pitch_without_changing_length() {
+        let shifted = shift_pitch(&tone(200.0, 1.0), 16_000, 1, 1.2);
+        assert!(shifted.len().abs_diff(16_000) <= 2);
+        assert!((pitch_of(&shifted) - 240.0).abs() < 8.0);
+        assert_eq!(shift_pitch(&[1, 2, 3], 16_000, 1, 1.0), [1, 2, 3]);
+    }
+
+    #[test]
+    fn pitches_voices_into_the_speakers_range() {
+        let audio = |pcm_i16| TtsAudio {
+            sample_rate_hz: 16_000,
+            channels: 1,
+            pcm_i16,
+        };
+        // Shifted as far as it goes, 120 Hz * 1.33.
+        let raised = pitch_into_range(audio(tone(120.0, 1.0)), SpeakerGender::Female);
+        assert!((pitch_of(&raised.pcm_i16) - 160.0).abs() < 6.0);
+        let lowered = pitch_into_range(audio(tone(160.0, 1.0)), SpeakerGender::Male);
+        assert!((pitch_of(&lowered.pcm_i16) - 120.0).abs() < 4.0);
+        let kept = pitch_into_range(audio(tone(120.0, 1.0)), SpeakerGender::Male);
+        assert_eq!(kept.pcm_i16, tone(120.0, 1.0));
+    }
+}
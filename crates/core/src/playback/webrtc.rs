@@ -0,0 +1,284 @@
+//! WebRTC listeners for the network stream, for watching together from any
+//! browser
+//!
+//! The server hosts a small page; opening it and pressing Listen sends a
+//! WebRTC offer to `POST /offer`, and the answer carries the Opus track every
+//! listener shares plus a `captions` data channel with each translation as
+//! JSON. Candidates are gathered up front rather than trickled, so signalling
+//! is one plain HTTP round trip.
+
+use crate::playback::network::{read_head, FRAME_DURATION};
+use crate::playback::resample::SAMPLE_RATE_HZ;
+use crate::playback::PlaybackError;
+use crate::status::{PipelineEvent, PipelineStatus};
+use bytes::Bytes;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::oneshot;
+use webrtc::api::interceptor_registry::register_default_interceptors;
+use webrtc::api::media_engine::{MediaEngine, MIME_TYPE_OPUS};
+use webrtc::api::{APIBuilder, API};
+use webrtc::data_channel::RTCDataChannel;
+use webrtc::ice_transport::ice_server::RTCIceServer;
+use webrtc::interceptor::registry::Registry;
+use webrtc::media::Sample;
+use webrtc::peer_connection::configuration::RTCConfiguration;
+use webrtc::peer_connection::peer_connection_state::RTCPeerConnectionState;
+use webrtc::peer_connection::sdp::session_description::RTCSessionDescription;
+use webrtc::rtp_transceiver::rtp_codec::RTCRtpCodecCapability;
+use webrtc::track::track_local::track_local_static_sample::TrackLocalStaticSample;
+use webrtc::track::track_local::TrackLocal;
+
+const PAGE: &str = include_str!("webrtc.html");
+/// Lets listeners behind NAT find their public address; the page uses the same.
+const STUN_SERVER: &str = "stun:stun.l.google.com:19302";
+/// SDP offers are a few kilobytes; anything much bigger is not one.
+const MAX_OFFER_BYTES: usize = 64 * 1024;
+
+/// The Opus track shared by every WebRTC listener.
+#[derive(Clone)]
+pub(super) struct WebRtcTrack(Arc<TrackLocalStaticSample>);
+
+impl WebRtcTrack {
+    pub(super) fn new() -> Self {
+        Self(Arc::new(TrackLocalStaticSample::new(
+            RTCRtpCodecCapability {
+                mime_type: MIME_TYPE_OPUS.to_owned(),
+                clock_rate: SAMPLE_RATE_HZ,
+                channels: 2,
+                ..Default::default()
+            },
+            "dub".to_owned(),
+            "twitch-translator".to_owned(),
+        )))
+    }
+
+    /// Sends one encoded frame to every connected listener.
+    pub(super) async fn push(&self, packet: &[u8]) {
+        let sample = Sample {
+            data: Bytes::copy_from_slice(packet),
+            duration: FRAME_DURATION,
+            ..Default::default()
+        };
+        if let Err(e) = self.0.write_sample(&sample).await {
+            tracing::debug!(error = %e, "failed to send WebRTC audio frame");
+        }
+    }
+}
+
+/// Answers offers for `track` from browsers connecting to `listener`.
+pub(super) struct WebRtcServer {
+    api: Arc<API>,
+    track: WebRtcTrack,
+    status: PipelineStatus,
+}
+
+impl WebRtcServer {
+    pub(super) fn new(track: WebRtcTrack, status: PipelineStatus) -> Result<Self, PlaybackError> {
+        let mut media = MediaEngine::default();
+        media
+            .register_default_codecs()
+            .map_err(|e| webrtc_error(format!("failed to register codecs: {e}")))?;
+        let registry = register_default_interceptors(Registry::new(), &mut media)
+            .map_err(|e| webrtc_error(format!("failed to register interceptors: {e}")))?;
+        let api = APIBuilder::new()
+            .with_media_engine(media)
+            .with_interceptor_registry(registry)
+            .build();
+        Ok(Self {
+            api: Arc::new(api),
+            track,
+            status,
+        })
+    }
+
+    pub(super) async fn serve(self, listener: TcpListener) {
+        let server = Arc::new(self);
+        loop {
+            let (mut stream, peer) = match listener.accept().await {
+                Ok(accepted) => accepted,
+                Err(e) => {
+                    tracing::warn!(error = %e, "WebRTC listener failed");
+                    return;
+                }
+            };
+            let server = Arc::clone(&server);
+            tokio::spawn(async move {
+                if let Err(e) = server.handle(&mut stream).await {
+                    tracing::debug!(%peer, error = %e, "WebRTC signalling request failed");
+                }
+            });
+        }
+    }
+
+    /// Serves the page for `GET` and an answer for `POST /offer`.
+    async fn handle(&self, stream: &mut TcpStream) -> std::io::Result<()> {
+        let head = read_head(stream).await?;
+        let mut request_line = head.lines().next().unwrap_or_default().split_whitespace();
+        let (method, path) = (request_line.next(), request_line.next());
+        match (method, path.map(|p| p.trim_end_matches('/'))) {
+            (Some("POST"), Some(p)) if p.ends_with("/offer") => {
+                let length = content_length(&head).filter(|&n| n <= MAX_OFFER_BYTES);
+                let Some(length) = length else {
+                    return respond(stream, "413 Payload Too Large", "text/plain", "bad offer")
+                        .await;
+                };
+                let mut offer = vec![0u8; length];
+                stream.read_exact(&mut offer).await?;
+                match self
+                    .answer(String::from_utf8_lossy(&offer).into_owned())
+                    .await
+                {
+                    Ok(sdp) => respond(stream, "200 OK", "application/sdp", &sdp).await,
+                    Err(e) => {
+                        tracing::warn!(error = %e, "failed to answer WebRTC offer");
+                        respond(
+                            stream,
+                            "500 Internal Server Error",
+                            "text/plain",
+                            &e.to_string(),
+                        )
+                        .await
+                    }
+                }
+            }
+            (Some("GET"), _) => respond(stream, "200 OK", "text/html; charset=utf-8", PAGE).await,
+            _ => respond(stream, "405 Method Not Allowed", "text/plain", "").await,
+        }
+    }
+
+    /// Sets up a peer connection for `offer` and returns its SDP answer.
+    async fn answer(&self, offer: String) -> Result<String, webrtc::Error> {
+        let config = RTCConfiguration {
+            ice_servers: vec![RTCIceServer {
+                urls: vec![STUN_SERVER.to_owned()],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let pc = Arc::new(self.api.new_peer_connection(config).await?);
+        let sender = pc
+            .add_track(Arc::clone(&self.track.0) as Arc<dyn TrackLocal + Send + Sync>)
+            .await?;
+        // RTCP has to be read for the interceptors to work; the reports
+        // themselves aren't needed.
+        tokio::spawn(async move {
+            let mut buf = vec![0u8; 1500];
+            while sender.read(&mut buf).await.is_ok() {}
+        });
+
+        let status = self.status.clone();
+        pc.on_data_channel(Box::new(move |channel| {
+            send_captions(channel, status.clone());
+            Box::pin(async {})
+        }));
+        // Hold the connection until it drops, then close it to free its ports.
+        let (ended_tx, ended) = oneshot::channel();
+        let ended_tx = Mutex::new(Some(ended_tx));
+        pc.on_peer_connection_state_change(Box::new(move |state| {
+            tracing::info!(%state, "WebRTC listener connection changed");
+            if matches!(
+                state,
+                RTCPeerConnectionState::Failed
+                    | RTCPeerConnectionState::Disconnected
+                    | RTCPeerConnectionState::Closed
+            ) {
+                let sender = match ended_tx.lock() {
+                    Ok(mut g) => g.take(),
+                    Err(poisoned) => poisoned.into_inner().take(),
+                };
+                if let Some(sender) = sender {
+                    let _ = sender.send(());
+                }
+            }
+            Box::pin(async {})
+        }));
+
+        pc.set_remote_description(RTCSessionDescription::offer(offer)?)
+            .await?;
+        let answer = pc.create_answer(None).await?;
+        let mut gathered = pc.gathering_complete_promise().await;
+        pc.set_local_description(answer).await?;
+        let _ = gathered.recv().await;
+        let Some(local) = pc.local_description().await else {
+            let _ = pc.close().await;
+            return Err(webrtc::Error::ErrConnectionClosed);
+        };
+        tokio::spawn(async move {
+            let _ = ended.await;
+            let _ = pc.close().await;
+        });
+        Ok(local.sdp)
+    }
+}
+
+/// Forwards each translation, non-speech sound and watched word to `channel`
+/// as JSON once it opens, for the page to caption and highlight.
+fn send_captions(channel: Arc<RTCDataChannel>, status: PipelineStatus) {
+    let sender = Arc::clone(&channel);
+    channel.on_open(Box::new(move || {
+        Box::pin(async move {
+            let mut events = status.subscribe();
+            loop {
+                let caption = match events.recv().await {
+                    Ok(
+                        event @ (PipelineEvent::Translation { .. }
+                        | PipelineEvent::NonSpeech { .. }
+                        | PipelineEvent::KeywordMatched { .. }),
+                    ) => event,
+                    Ok(_) | Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => return,
+                };
+                let Ok(json) = serde_json::to_string(&caption) else {
+                    continue;
+                };
+                if sender.send_text(json).await.is_err() {
+                    // The listener left.
+                    return;
+                }
+            }
+        })
+    }));
+}
+
+fn content_length(head: &str) -> Option<usize> {
+    head.lines().find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        name.trim()
+            .eq_ignore_ascii_case("content-length")
+            .then(|| value.trim().parse().ok())?
+    })
+}
+
+async fn respond(
+    stream: &mut TcpStream,
+    status: &str,
+    content_type: &str,
+    body: &str,
+) -> std::io::Result<()> {
+    let head = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\n\
+         Cache-Control: no-store\r\nConnection: close\r\n\r\n",
+        body.len()
+    );
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(body.as_bytes()).await
+}
+
+fn webrtc_error(details: String) -> PlaybackError {
+    PlaybackError::NetworkOutput { details }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn content_length_is_read_case_insensitively() {
+        let head = "POST /offer HTTP/1.1\r\nHost: x\r\ncontent-LENGTH: 42\r\n\r\n";
+        assert_eq!(content_length(head), Some(42));
+        assert_eq!(content_length("GET / HTTP/1.1\r\n\r\n"), None);
+    }
+}
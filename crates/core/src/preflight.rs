@@ -0,0 +1,414 @@
+//! Preflight validation of external dependencies
+//!
+//! Every check runs before the pipeline starts and the results are collected
+//! into a [`PreflightReport`], so a misconfigured machine reports all of its
+//! problems at once instead of failing mid-stream on the first one it hits.
+
+use crate::config::{
+    ApiKey, AppConfig, AsrProvider, HttpConfig, HttpService, OutputConfig, PiperConfig,
+};
+use crate::playback::list_output_devices;
+use crate::translate::DeepLTranslator;
+use crate::tts::ElevenLabsTtsClient;
+use crate::util::ReqwestTransport;
+use serde::Serialize;
+use std::fmt;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+
+/// First four bytes of a ggml Whisper model ("ggml" read as little-endian u32).
+const GGML_MAGIC: [u8; 4] = *b"lmgg";
+
+#[derive(Clone, Copy, Debug, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    Ok,
+    /// Degraded but usable, e.g. an optional backend is missing.
+    Warn,
+    /// The pipeline cannot run correctly until this is fixed.
+    Fail,
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct CheckResult {
+    pub name: &'static str,
+    pub status: CheckStatus,
+    pub detail: String,
+}
+
+impl CheckResult {
+    pub fn ok<S: Into<String>>(name: &'static str, detail: S) -> Self {
+        Self::new(name, CheckStatus::Ok, detail)
+    }
+
+    pub fn warn<S: Into<String>>(name: &'static str, detail: S) -> Self {
+        Self::new(name, CheckStatus::Warn, detail)
+    }
+
+    pub fn fail<S: Into<String>>(name: &'static str, detail: S) -> Self {
+        Self::new(name, CheckStatus::Fail, detail)
+    }
+
+    fn new<S: Into<String>>(name: &'static str, status: CheckStatus, detail: S) -> Self {
+        Self {
+            name,
+            status,
+            detail: detail.into(),
+        }
+    }
+}
+
+impl fmt::Display for CheckResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let tag = match self.status {
+            CheckStatus::Ok => "ok",
+            CheckStatus::Warn => "warn",
+            CheckStatus::Fail => "FAIL",
+        };
+        write!(f, "[{tag:<4}] {:<12} {}", self.name, self.detail)
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, PartialEq, Eq)]
+pub struct PreflightReport {
+    pub checks: Vec<CheckResult>,
+}
+
+impl PreflightReport {
+    pub fn push(&mut self, check: CheckResult) {
+        self.checks.push(check);
+    }
+
+    pub fn with_status(&self, status: CheckStatus) -> impl Iterator<Item = &CheckResult> {
+        self.checks.iter().filter(move |c| c.status == status)
+    }
+
+    pub fn has_failures(&self) -> bool {
+        self.with_status(CheckStatus::Fail).next().is_some()
+    }
+}
+
+impl fmt::Display for PreflightReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, check) in self.checks.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{check}")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PreflightOptions {
+    /// Fully load the Whisper model instead of only checking its header. Slow
+    /// for large models, so only `doctor` does this.
+    pub load_whisper_model: bool,
+}
+
+/// Runs all checks concurrently and returns them in a stable order.
+pub async fn run_preflight(cfg: &AppConfig, options: PreflightOptions) -> PreflightReport {
+    // Piper is the only TTS without an ElevenLabs key, otherwise just the fallback.
+    let piper_required = cfg.api_keys.elevenlabs.is_none();
+    let speaks = cfg.speaks();
+
+    let (ffmpeg, whisper, piper, deepl, elevenlabs, audio) = tokio::join!(
+        check_ffmpeg(),
+        async {
+            if cfg.asr.provider != AsrProvider::Whisper {
+                return CheckResult::ok("whisper", format!("not needed with {}", cfg.asr.provider));
+            }
+            if let Some(endpoint) = &cfg.asr.endpoint {
+                return CheckResult::ok("whisper", format!("runs on {endpoint}"));
+            }
+            check_whisper_model(&cfg.asr.model_path, options.load_whisper_model).await
+        },
+        if_speaking(speaks, "piper", check_piper(&cfg.piper, piper_required)),
+        check_deepl(cfg.api_keys.deepl.as_ref(), &cfg.http),
+        if_speaking(
+            speaks,
+            "elevenlabs",
+            check_elevenlabs(cfg.api_keys.elevenlabs.as_ref(), &cfg.http)
+        ),
+        if_speaking(speaks, "audio output", check_audio_output(&cfg.output)),
+    );
+
+    PreflightReport {
+        checks: vec![ffmpeg, whisper, piper, deepl, elevenlabs, audio],
+    }
+}
+
+/// Runs a speech or playback `check`, which a session without speech skips.
+async fn if_speaking(
+    speaks: bool,
+    name: &'static str,
+    check: impl Future<Output = CheckResult>,
+) -> CheckResult {
+    if !speaks {
+        return CheckResult::ok(name, "not needed without speech");
+    }
+    check.await
+}
+
+pub async fn check_ffmpeg() -> CheckResult {
+    #[cfg(feature = "ffmpeg-sidecar")]
+    {
+        match tokio::task::spawn_blocking(ffmpeg_sidecar::version::ffmpeg_version).await {
+            Ok(Ok(version)) => CheckResult::ok("ffmpeg", format!("version {version}")),
+            _ => CheckResult::warn(
+                "ffmpeg",
+                "not found on PATH; a copy will be downloaded on first use",
+            ),
+        }
+    }
+    #[cfg(not(feature = "ffmpeg-sidecar"))]
+    {
+        CheckResult::fail(
+            "ffmpeg",
+            "built without ffmpeg support; audio cannot be decoded",
+        )
+    }
+}
+
+pub async fn check_whisper_model(model_path: &str, load: bool) -> CheckResult {
+    const NAME: &str = "whisper";
+    let path = Path::new(model_path);
+
+    let header = match std::fs::File::open(path).and_then(|mut f| {
+        let mut magic = [0u8; 4];
+        std::io::Read::read_exact(&mut f, &mut magic).map(|_| magic)
+    }) {
+        Ok(magic) => magic,
+        Err(e) => {
+            return CheckResult::fail(
+                NAME,
+                format!("cannot read model {model_path}: {e} (try `models download`)"),
+            )
+        }
+    };
+    if header != GGML_MAGIC {
+        return CheckResult::fail(NAME, format!("{model_path} is not a ggml Whisper model"));
+    }
+
+    if !cfg!(feature = "whisper-rs") {
+        return CheckResult::fail(NAME, "built without Whisper ASR support");
+    }
+    if !load {
+        return CheckResult::ok(NAME, model_path);
+    }
+
+    load_whisper_model(model_path).await
+}
+
+#[cfg(feature = "whisper-rs")]
+async fn load_whisper_model(model_path: &str) -> CheckResult {
+    let owned = model_path.to_owned();
+    let loaded =
+        tokio::task::spawn_blocking(move || crate::asr::WhisperAsrBackend::new(&owned).map(drop))
+            .await;
+    match loaded {
+        Ok(Ok(())) => CheckResult::ok("whisper", format!("{model_path} loads")),
+        Ok(Err(e)) => CheckResult::fail("whisper", e.to_string()),
+        Err(e) => CheckResult::fail("whisper", format!("model load panicked: {e}")),
+    }
+}
+
+#[cfg(not(feature = "whisper-rs"))]
+async fn load_whisper_model(_model_path: &str) -> CheckResult {
+    CheckResult::fail("whisper", "built without Whisper ASR support")
+}
+
+pub async fn check_piper(piper: &PiperConfig, required: bool) -> CheckResult {
+    const NAME: &str = "piper";
+    let problem = match (
+        find_executable(&piper.binary_path),
+        Path::new(&piper.model_path).is_file(),
+    ) {
+        (Some(binary), true) => {
+            return CheckResult::ok(
+                NAME,
+                format!("{} with {}", binary.display(), piper.model_path),
+            )
+        }
+        (None, _) => format!("binary '{}' not found", piper.binary_path),
+        (Some(_), false) => format!("voice model {} not found", piper.model_path),
+    };
+
+    if required {
+        CheckResult::fail(
+            NAME,
+            format!("{problem}; it is the only TTS without an ElevenLabs key"),
+        )
+    } else {
+        CheckResult::warn(
+            NAME,
+            format!("{problem}; no TTS fallback if ElevenLabs fails"),
+        )
+    }
+}
+
+pub async fn check_deepl(key: Option<&ApiKey>, http: &HttpConfig) -> CheckResult {
+    const NAME: &str = "deepl";
+    let Some(key) = key else {
+        return CheckResult::warn(
+            NAME,
+            "no API key (--deepl-api-key or DEEPL_API_KEY); transcribing only",
+        );
+    };
+    let transport = match ReqwestTransport::from_config(http, HttpService::DeepL) {
+        Ok(t) => t.shared(),
+        Err(e) => return CheckResult::fail(NAME, e.to_string()),
+    };
+    match DeepLTranslator::new(key.expose().to_owned())
+        .with_transport(transport)
+        .usage()
+        .await
+    {
+        Ok(usage)
+            if usage.character_limit > 0 && usage.character_count >= usage.character_limit =>
+        {
+            CheckResult::fail(
+                NAME,
+                format!(
+                    "character quota exhausted ({}/{})",
+                    usage.character_count, usage.character_limit
+                ),
+            )
+        }
+        Ok(usage) => CheckResult::ok(
+            NAME,
+            format!(
+                "key valid, {}/{} characters used",
+                usage.character_count, usage.character_limit
+            ),
+        ),
+        Err(e) => CheckResult::fail(NAME, format!("key check failed: {e}")),
+    }
+}
+
+pub async fn check_elevenlabs(key: Option<&ApiKey>, http: &HttpConfig) -> CheckResult {
+    const NAME: &str = "elevenlabs";
+    let Some(key) = key else {
+        return CheckResult::warn(NAME, "no API key; local Piper TTS will be used");
+    };
+    let transport = match ReqwestTransport::from_config(http, HttpService::ElevenLabs) {
+        Ok(t) => t.shared(),
+        Err(e) => return CheckResult::fail(NAME, e.to_string()),
+    };
+    match ElevenLabsTtsClient::new(key.expose().to_owned())
+        .with_transport(transport)
+        .list_voices()
+        .await
+    {
+        Ok(voices) => CheckResult::ok(
+            NAME,
+            format!("key valid, {} voices available", voices.len()),
+        ),
+        Err(e) => CheckResult::fail(NAME, format!("key check failed: {e}")),
+    }
+}
+
+/// Checks for a local output device, unless `output` sends the dub elsewhere.
+pub async fn check_audio_output(output: &OutputConfig) -> CheckResult {
+    const NAME: &str = "audio output";
+    if output.is_remote() {
+        return CheckResult::ok(NAME, "not played locally");
+    }
+    match tokio::task::spawn_blocking(list_output_devices).await {
+        Ok(Ok(devices)) => match devices.iter().find(|d| d.is_default) {
+            Some(default) => CheckResult::ok(NAME, format!("default device: {}", default.name)),
+            None if devices.is_empty() => CheckResult::fail(NAME, "no output devices found"),
+            None => CheckResult::warn(
+                NAME,
+                "no default output device; the first one will be tried",
+            ),
+        },
+        Ok(Err(e)) => CheckResult::fail(NAME, e.to_string()),
+        Err(e) => CheckResult::fail(NAME, format!("device enumeration panicked: {e}")),
+    }
+}
+
+/// Resolves `program` the way a spawn would: as a path when it contains a
+/// separator, otherwise by searching `PATH`.
+fn find_executable(program: &str) -> Option<PathBuf> {
+    let candidate = Path::new(program);
+    if candidate.components().count() > 1 {
+        return candidate.is_file().then(|| candidate.to_path_buf());
+    }
+
+    let paths = std::env::var_os("PATH")?;
+    std::env::split_paths(&paths).find_map(|dir| {
+        let full = dir.join(program);
+        if full.is_file() {
+            return Some(full);
+        }
+        if cfg!(windows) {
+            let exe = full.with_extension("exe");
+            if exe.is_file() {
+                return Some(exe);
+            }
+        }
+        None
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_file(name: &str, contents: &[u8]) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("preflight-{}-{name}", std::process::id()));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn report_detects_failures() {
+        let mut report = PreflightReport::default();
+        report.push(CheckResult::ok("a", "fine"));
+        report.push(CheckResult::warn("b", "meh"));
+        assert!(!report.has_failures());
+
+        report.push(CheckResult::fail("c", "broken"));
+        assert!(report.has_failures());
+        assert_eq!(report.with_status(CheckStatus::Fail).count(), 1);
+        assert_eq!(report.to_string().lines().count(), 3);
+        assert!(report.to_string().ends_with("[FAIL] c            broken"));
+    }
+
+    #[tokio::test]
+    async fn whisper_check_rejects_missing_and_non_ggml_files() {
+        let missing = check_whisper_model("/nonexistent/ggml-base.bin", false).await;
+        assert_eq!(missing.status, CheckStatus::Fail);
+
+        let bogus = temp_file("bogus.bin", b"<html>not a model</html>");
+        let result = check_whisper_model(bogus.to_str().unwrap(), false).await;
+        std::fs::remove_file(&bogus).unwrap();
+        assert_eq!(result.status, CheckStatus::Fail);
+        assert!(result.detail.contains("not a ggml"));
+    }
+
+    #[tokio::test]
+    async fn piper_severity_depends_on_whether_it_is_required() {
+        let cfg = PiperConfig {
+            binary_path: "/nonexistent/piper".to_owned(),
+            model_path: "/nonexistent/voice.onnx".to_owned(),
+            speaker_id: None,
+        };
+        assert_eq!(check_piper(&cfg, true).await.status, CheckStatus::Fail);
+        assert_eq!(check_piper(&cfg, false).await.status, CheckStatus::Warn);
+        let skipped = if_speaking(false, "piper", check_piper(&cfg, true)).await;
+        assert_eq!(skipped.status, CheckStatus::Ok);
+    }
+
+    #[tokio::test]
+    async fn missing_keys_warn_without_network() {
+        let http = HttpConfig::default();
+        assert_eq!(check_deepl(None, &http).await.status, CheckStatus::Warn);
+        assert_eq!(
+            check_elevenlabs(None, &http).await.status,
+            CheckStatus::Warn
+        );
+    }
+}
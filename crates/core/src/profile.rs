@@ -0,0 +1,111 @@
+//! Channel profiles
+//!
+//! A [`ChannelProfile`] keeps what is set up once per streamer, such as the
+//! ElevenLabs voice cloned from their speech and how their names are
+//! pronounced, in one small JSON file per channel. Sessions on the channel pick it up without further flags.
+
+use crate::config::PronunciationConfig;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Directory profiles are kept in unless another is given.
+pub const DEFAULT_PROFILE_DIR: &str = "profiles";
+
+#[derive(thiserror::Error, Debug)]
+pub enum ProfileError {
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("invalid channel profile: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+/// What is kept for one channel
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct ChannelProfile {
+    /// ElevenLabs voice the channel is dubbed with.
+    pub voice_id: Option<String>,
+    /// Name the voice was created under.
+    pub voice_name: Option<String>,
+    /// How the channel's names are said, on top of what the session is
+    /// given.
+    pub pronunciation: PronunciationConfig,
+    /// When the profile was last saved, in milliseconds since the Unix epoch.
+    pub saved_at_ms: u64,
+}
+
+impl ChannelProfile {
+    /// Reads the profile of `channel` from `dir`; an empty one if it has
+    /// none yet.
+    pub fn load(dir: impl AsRef<Path>, channel: &str) -> Result<Self, ProfileError> {
+        match std::fs::read(profile_path(dir.as_ref(), channel)) {
+            Ok(bytes) => Ok(serde_json::from_slice(&bytes)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Writes the profile of `channel` into `dir`, creating the directory if
+    /// needed, and returns the file written.
+    pub fn save(&mut self, dir: impl AsRef<Path>, channel: &str) -> Result<PathBuf, ProfileError> {
+        let dir = dir.as_ref();
+        std::fs::create_dir_all(dir)?;
+        self.saved_at_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+        let path = profile_path(dir, channel);
+        // Renamed over the old file, so a crash mid-write keeps it intact.
+        let partial = path.with_extension("json.part");
+        std::fs::write(&partial, serde_json::to_vec_pretty(self)?)?;
+        std::fs::rename(&partial, &path)?;
+        Ok(path)
+    }
+}
+
+/// The profile file of `channel`, keeping only characters safe on every
+/// platform; Twitch logins are case-insensitive.
+fn profile_path(dir: &Path, channel: &str) -> PathBuf {
+    let name: String = channel
+        .trim()
+        .to_lowercase()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '-' | '_') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .take(120)
+        .collect();
+    dir.join(format!("{name}.json"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn profile_survives_reloading() {
+        let dir = std::env::temp_dir().join(format!("channel-profile-{}", std::process::id()));
+        assert_eq!(
+            ChannelProfile::load(&dir, "Streamer").unwrap(),
+            ChannelProfile::default()
+        );
+
+        let mut profile = ChannelProfile {
+            voice_id: Some("abc123".to_owned()),
+            voice_name: Some("streamer (clone)".to_owned()),
+            ..ChannelProfile::default()
+        };
+        let path = profile.save(&dir, "Streamer").unwrap();
+        let loaded = ChannelProfile::load(&dir, "streamer").unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(path.file_name().unwrap(), "streamer.json");
+        assert_eq!(loaded, profile);
+        assert!(loaded.saved_at_ms > 0);
+    }
+}
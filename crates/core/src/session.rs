@@ -0,0 +1,139 @@
+//! Session state persisted across restarts
+//!
+//! A [`SessionStore`] keeps one small JSON file per input in a state
+//! directory, recording how far ingest got. An ingestor given the store picks
+//! up after the last segment it sent, so restarting on the same VOD, or after
+//! a crash on a live channel whose playlist still lists the missed segments,
+//! resumes there instead of at the live edge.
+
+use crate::config::InputSource;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::Mutex;
+
+#[derive(thiserror::Error, Debug)]
+pub enum SessionError {
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("invalid session state: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+/// What is saved for one input
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct SessionState {
+    /// `EXT-X-MEDIA-SEQUENCE` of the first segment not yet sent downstream.
+    pub next_media_sequence: Option<u64>,
+    /// When the state was last written, in milliseconds since the Unix epoch.
+    pub saved_at_ms: u64,
+}
+
+/// Session state file for one input
+pub struct SessionStore {
+    path: PathBuf,
+    state: Mutex<SessionState>,
+}
+
+impl SessionStore {
+    /// Opens the state for `input` in `dir`, creating the directory if
+    /// needed. An unreadable or corrupt file is ignored with a warning, so a
+    /// bad state never stops a session from starting.
+    pub async fn open(dir: impl AsRef<Path>, input: &InputSource) -> Result<Self, SessionError> {
+        let dir = dir.as_ref();
+        tokio::fs::create_dir_all(dir).await?;
+        let path = dir.join(format!("{}.json", file_stem(input)));
+        let state = match tokio::fs::read(&path).await {
+            Ok(bytes) => serde_json::from_slice(&bytes).unwrap_or_else(|e| {
+                tracing::warn!(path = %path.display(), error = %e, "ignoring corrupt session state");
+                SessionState::default()
+            }),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => SessionState::default(),
+            Err(e) => return Err(e.into()),
+        };
+        if let Some(next) = state.next_media_sequence {
+            tracing::info!(path = %path.display(), next, "resuming session");
+        }
+        Ok(Self {
+            path,
+            state: Mutex::new(state),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub async fn state(&self) -> SessionState {
+        self.state.lock().await.clone()
+    }
+
+    /// Records that every segment before `next` was handled and saves.
+    pub async fn set_next_media_sequence(&self, next: u64) -> Result<(), SessionError> {
+        let mut state = self.state.lock().await;
+        state.next_media_sequence = Some(next);
+        state.saved_at_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+        self.save(&state).await
+    }
+
+    /// Writes to a temporary file and renames it over the old one, so a
+    /// crash mid-write leaves the previous state intact.
+    async fn save(&self, state: &SessionState) -> Result<(), SessionError> {
+        let partial = self.path.with_extension("json.part");
+        tokio::fs::write(&partial, serde_json::to_vec_pretty(state)?).await?;
+        tokio::fs::rename(&partial, &self.path).await?;
+        Ok(())
+    }
+}
+
+/// File name for `input`, keeping only characters safe on every platform.
+fn file_stem(input: &InputSource) -> String {
+    let (kind, name) = match input {
+        InputSource::Channel(channel) => ("channel", channel.to_lowercase()),
+        InputSource::Url(url) => ("url", url.clone()),
+        InputSource::Replay(dir) => ("replay", dir.clone()),
+        InputSource::Listen(url) => ("listen", url.clone()),
+        InputSource::Microphone(device) => ("microphone", device.clone()),
+        InputSource::Peer(url) => ("peer", url.clone()),
+    };
+    let name: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .take(120)
+        .collect();
+    format!("{kind}-{name}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn state_survives_reopening() {
+        let dir = std::env::temp_dir().join(format!("session-state-{}", std::process::id()));
+        let input = InputSource::Url("https://cdn.example/vod/index.m3u8?t=1".to_owned());
+
+        let store = SessionStore::open(&dir, &input).await.unwrap();
+        assert_eq!(store.state().await.next_media_sequence, None);
+        store.set_next_media_sequence(42).await.unwrap();
+        let file_name = store.path().file_name().unwrap().to_owned();
+
+        let reopened = SessionStore::open(&dir, &input).await.unwrap();
+        let state = reopened.state().await;
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(file_name, "url-https___cdn.example_vod_index.m3u8_t_1.json");
+        assert_eq!(state.next_media_sequence, Some(42));
+        assert!(state.saved_at_ms > 0);
+    }
+}
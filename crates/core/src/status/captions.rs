@@ -0,0 +1,160 @@
+use crate::gloss::Gloss;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::time::{Duration, SystemTime};
+
+/// How long captions are kept unless configured otherwise.
+pub const DEFAULT_CAPTION_HISTORY: Duration = Duration::from_secs(5 * 60);
+
+/// One transcribed utterance and, once available, its translation.
+#[derive(Clone, Debug, Serialize, PartialEq)]
+pub struct Caption {
+    pub at: SystemTime,
+    pub source_text: String,
+    pub text: Option<String>,
+    pub target_lang: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub glosses: Vec<Gloss>,
+    /// The watched word said in it, for overlays to highlight.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keyword: Option<String>,
+}
+
+/// Captions from the last `window`, oldest first.
+pub(crate) struct CaptionHistory {
+    window: Duration,
+    captions: VecDeque<Caption>,
+}
+
+impl CaptionHistory {
+    pub(crate) fn new(window: Duration) -> Self {
+        Self {
+            window,
+            captions: VecDeque::new(),
+        }
+    }
+
+    pub(crate) fn set_window(&mut self, window: Duration) {
+        self.window = window;
+        self.prune(SystemTime::now());
+    }
+
+    pub(crate) fn push_transcript(&mut self, text: &str, at: SystemTime) {
+        self.captions.push_back(Caption {
+            at,
+            source_text: text.to_owned(),
+            text: None,
+            target_lang: None,
+            glosses: Vec::new(),
+            keyword: None,
+        });
+        self.prune(at);
+    }
+
+    /// Attaches a translation to the latest untranslated caption with the same
+    /// source text, or adds it on its own if there is none.
+    pub(crate) fn push_translation(
+        &mut self,
+        source_text: &str,
+        text: &str,
+        target_lang: &str,
+        at: SystemTime,
+    ) {
+        let pending = self
+            .captions
+            .iter_mut()
+            .rev()
+            .find(|c| c.text.is_none() && c.source_text == source_text);
+        match pending {
+            Some(caption) => {
+                caption.text = Some(text.to_owned());
+                caption.target_lang = Some(target_lang.to_owned());
+            }
+            None => self.captions.push_back(Caption {
+                at,
+                source_text: source_text.to_owned(),
+                text: Some(text.to_owned()),
+                target_lang: Some(target_lang.to_owned()),
+                glosses: Vec::new(),
+                keyword: None,
+            }),
+        }
+        self.prune(at);
+    }
+
+    /// Attaches `glosses` to the latest caption of `source_text` that has none.
+    pub(crate) fn attach_glosses(&mut self, source_text: &str, glosses: &[Gloss]) {
+        let caption = self
+            .captions
+            .iter_mut()
+            .rev()
+            .find(|c| c.glosses.is_empty() && c.source_text == source_text);
+        if let Some(caption) = caption {
+            caption.glosses = glosses.to_vec();
+        }
+    }
+
+    /// Marks the latest caption of `source_text` as holding `keyword`, unless
+    /// it already holds one; false if it does.
+    pub(crate) fn highlight(&mut self, source_text: &str, keyword: &str) -> bool {
+        let caption = self
+            .captions
+            .iter_mut()
+            .rev()
+            .find(|c| c.source_text == source_text);
+        match caption {
+            Some(caption) if caption.keyword.is_some() => false,
+            Some(caption) => {
+                caption.keyword = Some(keyword.to_owned());
+                true
+            }
+            None => true,
+        }
+    }
+
+    /// Captions transcribed after `since`, or all kept ones.
+    pub(crate) fn since(&self, since: Option<SystemTime>) -> Vec<Caption> {
+        self.captions
+            .iter()
+            .filter(|c| since.is_none_or(|since| c.at > since))
+            .cloned()
+            .collect()
+    }
+
+    fn prune(&mut self, now: SystemTime) {
+        let Some(oldest) = now.checked_sub(self.window) else {
+            return;
+        };
+        while self.captions.front().is_some_and(|c| c.at < oldest) {
+            self.captions.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pairs_translations_and_drops_old_captions() {
+        let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000);
+        let mut history = CaptionHistory::new(Duration::from_secs(60));
+        history.push_transcript("hello", start);
+        history.push_transcript("bye", start + Duration::from_secs(30));
+        history.push_translation("hello", "olá", "pt-BR", start + Duration::from_secs(31));
+
+        let captions = history.since(None);
+        assert_eq!(captions.len(), 2);
+        assert_eq!(captions[0].text.as_deref(), Some("olá"));
+        assert_eq!(captions[1].text, None);
+        assert_eq!(history.since(Some(start)).len(), 1);
+
+        history.push_transcript("later", start + Duration::from_secs(75));
+        let kept: Vec<_> = history
+            .since(None)
+            .into_iter()
+            .map(|c| c.source_text)
+            .collect();
+        assert_eq!(kept, ["bye", "later"]);
+    }
+}
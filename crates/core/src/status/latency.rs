@@ -0,0 +1,149 @@
+//! Latency distribution of a stage over the whole session
+//!
+//! Samples go into log-linear buckets, eight per power of two of
+//! microseconds, so percentiles are within about 6% while memory stays fixed
+//! however long the session runs.
+
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+const SUB_BUCKETS: u64 = 8;
+/// Values below 8 µs get a bucket each; above, eight per power of two up to
+/// `u32::MAX` µs (71 minutes), where samples are clamped.
+const BUCKETS: usize = 8 + 29 * SUB_BUCKETS as usize;
+
+pub(super) struct LatencyHistogram {
+    buckets: Box<[AtomicU64]>,
+    count: AtomicU64,
+    sum_us: AtomicU64,
+    max_us: AtomicU64,
+}
+
+impl Default for LatencyHistogram {
+    fn default() -> Self {
+        Self {
+            buckets: (0..BUCKETS).map(|_| AtomicU64::new(0)).collect(),
+            count: AtomicU64::new(0),
+            sum_us: AtomicU64::new(0),
+            max_us: AtomicU64::new(0),
+        }
+    }
+}
+
+impl LatencyHistogram {
+    pub(super) fn record(&self, micros: u64) {
+        let micros = micros.min(u64::from(u32::MAX));
+        self.buckets[bucket(micros)].fetch_add(1, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum_us.fetch_add(micros, Ordering::Relaxed);
+        self.max_us.fetch_max(micros, Ordering::Relaxed);
+    }
+
+    /// `None` until a sample was recorded.
+    pub(super) fn summary(&self) -> Option<LatencySummary> {
+        let counts: Vec<u64> = self
+            .buckets
+            .iter()
+            .map(|b| b.load(Ordering::Relaxed))
+            .collect();
+        let samples: u64 = counts.iter().sum();
+        if samples == 0 {
+            return None;
+        }
+        let max_us = self.max_us.load(Ordering::Relaxed);
+        let percentile = |q: f64| {
+            let rank = ((q * samples as f64).ceil() as u64).max(1);
+            let mut seen = 0;
+            let index = counts
+                .iter()
+                .position(|&n| {
+                    seen += n;
+                    seen >= rank
+                })
+                .unwrap_or(BUCKETS - 1);
+            to_ms(midpoint(index).min(max_us))
+        };
+        let count = self.count.load(Ordering::Relaxed).max(1);
+        Some(LatencySummary {
+            samples,
+            mean_ms: to_ms(self.sum_us.load(Ordering::Relaxed) / count),
+            p50_ms: percentile(0.5),
+            p90_ms: percentile(0.9),
+            p99_ms: percentile(0.99),
+            max_ms: to_ms(max_us),
+        })
+    }
+}
+
+fn bucket(micros: u64) -> usize {
+    if micros < SUB_BUCKETS {
+        return micros as usize;
+    }
+    let exponent = u64::from(63 - micros.leading_zeros());
+    let mantissa = (micros >> (exponent - 3)) & (SUB_BUCKETS - 1);
+    ((exponent - 2) * SUB_BUCKETS + mantissa) as usize
+}
+
+/// Middle of the range of values that fall in bucket `index`.
+fn midpoint(index: usize) -> u64 {
+    let index = index as u64;
+    if index < SUB_BUCKETS {
+        return index;
+    }
+    let exponent = index / SUB_BUCKETS + 2;
+    let width = 1 << (exponent - 3);
+    let lower = (SUB_BUCKETS + index % SUB_BUCKETS) * width;
+    lower + width / 2
+}
+
+fn to_ms(micros: u64) -> f64 {
+    micros as f64 / 1000.0
+}
+
+/// How long a stage took per item over the session
+#[derive(Clone, Debug, Serialize, PartialEq)]
+pub struct LatencySummary {
+    pub samples: u64,
+    pub mean_ms: f64,
+    pub p50_ms: f64,
+    pub p90_ms: f64,
+    pub p99_ms: f64,
+    pub max_ms: f64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buckets_cover_the_range_in_order() {
+        assert_eq!(bucket(0), 0);
+        assert_eq!(bucket(7), 7);
+        assert_eq!(bucket(8), 8);
+        assert_eq!(bucket(15), 15);
+        assert_eq!(bucket(16), 16);
+        assert_eq!(bucket(17), 16);
+        assert_eq!(bucket(u64::from(u32::MAX)), BUCKETS - 1);
+        for micros in [9, 100, 1_000, 123_456, 10_000_000] {
+            let mid = midpoint(bucket(micros));
+            assert_eq!(bucket(mid), bucket(micros));
+            assert!(mid.abs_diff(micros) as f64 <= micros as f64 * 0.07);
+        }
+    }
+
+    #[test]
+    fn summarizes_percentiles() {
+        let histogram = LatencyHistogram::default();
+        assert!(histogram.summary().is_none());
+        for ms in 1..=100 {
+            histogram.record(ms * 1000);
+        }
+        let summary = histogram.summary().unwrap();
+        assert_eq!(summary.samples, 100);
+        assert_eq!(summary.mean_ms, 50.5);
+        assert_eq!(summary.max_ms, 100.0);
+        assert!((summary.p50_ms - 50.0).abs() <= 3.0, "{summary:?}");
+        assert!((summary.p90_ms - 90.0).abs() <= 6.0, "{summary:?}");
+        assert!(summary.p99_ms <= summary.max_ms);
+    }
+}
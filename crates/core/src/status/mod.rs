@@ -0,0 +1,805 @@
+//! Shared runtime status for headless monitoring
+//!
+//! [`PipelineStatus`] is a cheap, cloneable handle that pipeline stages update as
+//! items flow through them. A [`StatusSnapshot`] can be taken at any time and
+//! serialized to JSON, which is what the status HTTP server exposes. Discrete
+//! happenings (new transcripts, translations) are additionally published as
+//! [`PipelineEvent`]s for live frontends such as the TUI, and the last few
+//! minutes of captions are kept so late-joining clients can backfill. The
+//! speech being played can be tapped too, for embedders that want the audio.
+//! When the session ends, a [`SessionReport`] sums it up.
+
+mod captions;
+mod latency;
+mod report;
+#[cfg(feature = "native")]
+mod server;
+
+use crate::cost::{CostMeter, CostSnapshot};
+use crate::tts::TtsAudio;
+use crate::watch::WatchList;
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use tokio::sync::broadcast;
+
+pub use captions::{Caption, DEFAULT_CAPTION_HISTORY};
+pub use latency::LatencySummary;
+pub use report::{SessionReport, StageReport};
+#[cfg(feature = "native")]
+pub use server::{StatusServer, StatusServerError};
+
+use captions::CaptionHistory;
+use latency::LatencyHistogram;
+
+/// A stage of the translation pipeline
+#[derive(Clone, Copy, Debug, Serialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Stage {
+    Ingest,
+    Decode,
+    Asr,
+    Translate,
+    Tts,
+    Playback,
+}
+
+impl Stage {
+    pub const ALL: [Stage; 6] = [
+        Stage::Ingest,
+        Stage::Decode,
+        Stage::Asr,
+        Stage::Translate,
+        Stage::Tts,
+        Stage::Playback,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Stage::Ingest => "ingest",
+            Stage::Decode => "decode",
+            Stage::Asr => "asr",
+            Stage::Translate => "translate",
+            Stage::Tts => "tts",
+            Stage::Playback => "playback",
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+const EVENT_CHANNEL_CAPACITY: usize = 256;
+/// Clips are large; a tap that falls this far behind skips ahead.
+const AUDIO_CHANNEL_CAPACITY: usize = 16;
+
+/// Something noteworthy that happened in the pipeline
+#[derive(Clone, Debug, Serialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PipelineEvent {
+    /// What was said, with how sure the recognizer was (0 to 1) if it says,
+    /// and how long it took over the audio.
+    Transcript {
+        text: String,
+        confidence: Option<f32>,
+        latency_ms: u64,
+    },
+    Translation {
+        source_text: String,
+        text: String,
+        target_lang: String,
+    },
+    /// The ingestor stepped down to a lower bandwidth variant because segment
+    /// downloads were falling behind.
+    VariantSwitched {
+        from_bandwidth: Option<u64>,
+        to_bandwidth: u64,
+    },
+    /// The channel's title or category changed (or was first seen).
+    StreamMetadata(StreamMetadata),
+    /// Word-level glosses of a translated utterance, for language learners.
+    Gloss {
+        source_text: String,
+        target_lang: String,
+        glosses: Vec<crate::gloss::Gloss>,
+    },
+    /// Rolling summary of what was said in the last `window_secs`.
+    Summary { text: String, window_secs: u64 },
+    /// ASR has been slower than real time over the last few chunks, so the
+    /// delay is growing.
+    AsrOverloaded { rtf: f64 },
+    /// A stage dropped an item that was already older than the latency
+    /// budget, favouring recency over completeness.
+    Skipped {
+        stage: Stage,
+        sequence: u64,
+        age_ms: u64,
+    },
+    /// A stage gave up on an item that took longer than its deadline,
+    /// `deadline_ms`, and skipped it or answered it another way.
+    DeadlineExceeded { stage: Stage, deadline_ms: u64 },
+    /// The Whisper model was swapped, e.g. for a smaller one after an overload.
+    AsrModelSwitched { from: String, to: String },
+    /// A stage panicked or failed and was started again; `restarts` counts
+    /// the restarts since it was last healthy.
+    StageRestarted {
+        stage: Stage,
+        restarts: u32,
+        reason: String,
+    },
+    /// An utterance was voiced by `backend` while two TTS backends are A/B
+    /// tested, so listeners can tell which one they heard.
+    TtsVariant { backend: String, text: String },
+    /// The recognizer heard music, laughter, applause or silence rather than
+    /// speech; captioned as e.g. `[music]` and neither translated nor voiced.
+    NonSpeech { sound: crate::text::NonSpeech },
+    /// A watched word was said in `source_text`, or turned up in its
+    /// translation `text` when `translated`; sent once per utterance.
+    KeywordMatched {
+        keyword: String,
+        source_text: String,
+        text: String,
+        translated: bool,
+    },
+}
+
+/// What the channel is streaming, as reported by Twitch.
+#[derive(Clone, Debug, Default, Serialize, PartialEq, Eq)]
+pub struct StreamMetadata {
+    pub title: String,
+    /// Category, e.g. the game being played.
+    pub game: String,
+    pub viewer_count: u64,
+}
+
+#[derive(Default)]
+struct StageCounters {
+    queue_depth: AtomicUsize,
+    processed: AtomicU64,
+    errors: AtomicU64,
+    skipped: AtomicU64,
+    last_latency_us: AtomicU64,
+    latency: LatencyHistogram,
+}
+
+struct StatusInner {
+    started_at: SystemTime,
+    stream_online: AtomicBool,
+    tts_fallback_active: AtomicBool,
+    tts_fallback_activations: AtomicU64,
+    audio_processed_us: AtomicU64,
+    utterances: AtomicU64,
+    segments_recovered: AtomicU64,
+    segments_dropped: AtomicU64,
+    segments_late: AtomicU64,
+    jitter_buffer_us: AtomicU64,
+    buffered_bytes: AtomicU64,
+    /// Zero when uncapped.
+    buffer_cap_bytes: AtomicU64,
+    stages: [StageCounters; 6],
+    last_transcript: Mutex<Option<TimedText>>,
+    last_translation: Mutex<Option<TimedText>>,
+    last_summary: Mutex<Option<TimedText>>,
+    asr_rtf: Mutex<Option<f64>>,
+    stream_metadata: Mutex<Option<StreamMetadata>>,
+    cost: Mutex<Option<CostMeter>>,
+    captions: Mutex<CaptionHistory>,
+    watch: Mutex<WatchList>,
+    events: broadcast::Sender<PipelineEvent>,
+    audio: broadcast::Sender<TtsAudio>,
+}
+
+impl Default for StatusInner {
+    fn default() -> Self {
+        let (events, _) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
+        let (audio, _) = broadcast::channel(AUDIO_CHANNEL_CAPACITY);
+        Self {
+            started_at: SystemTime::now(),
+            stream_online: AtomicBool::new(false),
+            tts_fallback_active: AtomicBool::new(false),
+            tts_fallback_activations: AtomicU64::new(0),
+            audio_processed_us: AtomicU64::new(0),
+            utterances: AtomicU64::new(0),
+            segments_recovered: AtomicU64::new(0),
+            segments_dropped: AtomicU64::new(0),
+            segments_late: AtomicU64::new(0),
+            jitter_buffer_us: AtomicU64::new(0),
+            buffered_bytes: AtomicU64::new(0),
+            buffer_cap_bytes: AtomicU64::new(0),
+            stages: Default::default(),
+            last_transcript: Mutex::new(None),
+            last_translation: Mutex::new(None),
+            last_summary: Mutex::new(None),
+            asr_rtf: Mutex::new(None),
+            stream_metadata: Mutex::new(None),
+            cost: Mutex::new(None),
+            captions: Mutex::new(CaptionHistory::new(DEFAULT_CAPTION_HISTORY)),
+            watch: Mutex::new(WatchList::default()),
+            events,
+            audio,
+        }
+    }
+}
+
+/// Shared handle to the live pipeline status. Clones share the same state.
+#[derive(Clone, Default)]
+pub struct PipelineStatus {
+    inner: Arc<StatusInner>,
+}
+
+impl PipelineStatus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Subscribes to future events. Slow subscribers miss events rather than
+    /// applying backpressure to the pipeline.
+    pub fn subscribe(&self) -> broadcast::Receiver<PipelineEvent> {
+        self.inner.events.subscribe()
+    }
+
+    /// Subscribes to the speech clips played from now on, before volume is
+    /// applied. Like events, slow subscribers miss clips.
+    pub fn subscribe_audio(&self) -> broadcast::Receiver<TtsAudio> {
+        self.inner.audio.subscribe()
+    }
+
+    /// Sends a clip about to be played to audio subscribers, if there are any.
+    pub fn publish_audio(&self, audio: &TtsAudio) {
+        if self.inner.audio.receiver_count() > 0 {
+            let _ = self.inner.audio.send(audio.clone());
+        }
+    }
+
+    /// Sends `event` to subscribers; transcripts and translations are also
+    /// added to the caption history, and transcripts counted as utterances.
+    /// A watched word in either is followed by a
+    /// [`PipelineEvent::KeywordMatched`].
+    pub fn publish(&self, event: PipelineEvent) {
+        let matched = match &event {
+            PipelineEvent::Transcript { text, .. } => {
+                self.inner.utterances.fetch_add(1, Ordering::Relaxed);
+                lock(&self.inner.captions).push_transcript(text, SystemTime::now());
+                self.watch(text, text, false)
+            }
+            PipelineEvent::Translation {
+                source_text,
+                text,
+                target_lang,
+            } => {
+                lock(&self.inner.captions).push_translation(
+                    source_text,
+                    text,
+                    target_lang,
+                    SystemTime::now(),
+                );
+                self.watch(source_text, text, true)
+            }
+            PipelineEvent::Gloss {
+                source_text,
+                glosses,
+                ..
+            } => {
+                lock(&self.inner.captions).attach_glosses(source_text, glosses);
+                None
+            }
+            _ => None,
+        };
+        // An error only means nobody is listening right now.
+        let _ = self.inner.events.send(event);
+        if let Some(matched) = matched {
+            let _ = self.inner.events.send(matched);
+        }
+    }
+
+    /// Looks for watched words in transcripts and translations from now on.
+    pub fn set_watch_list(&self, watch: WatchList) {
+        *lock(&self.inner.watch) = watch;
+    }
+
+    /// The event for a watched word in `text`, the utterance `source_text`
+    /// or its translation, unless its caption was already highlighted.
+    fn watch(&self, source_text: &str, text: &str, translated: bool) -> Option<PipelineEvent> {
+        let keyword = lock(&self.inner.watch).find(text)?.to_owned();
+        let first = lock(&self.inner.captions).highlight(source_text, &keyword);
+        first.then(|| PipelineEvent::KeywordMatched {
+            keyword,
+            source_text: source_text.to_owned(),
+            text: text.to_owned(),
+            translated,
+        })
+    }
+
+    pub fn set_stream_online(&self, online: bool) {
+        self.inner.stream_online.store(online, Ordering::Relaxed);
+    }
+
+    pub fn is_stream_online(&self) -> bool {
+        self.inner.stream_online.load(Ordering::Relaxed)
+    }
+
+    pub fn set_tts_fallback_active(&self, active: bool) {
+        let was_active = self
+            .inner
+            .tts_fallback_active
+            .swap(active, Ordering::Relaxed);
+        if active && !was_active {
+            self.inner
+                .tts_fallback_activations
+                .fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Counts a media segment that was fetched after one or more retries.
+    pub fn record_segment_recovered(&self) {
+        self.inner
+            .segments_recovered
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Counts a media segment given up on, leaving a gap in the audio.
+    pub fn record_segment_dropped(&self) {
+        self.inner.segments_dropped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Counts a fetched segment dropped because it could no longer be
+    /// played within the latency budget, see [`crate::ingest::JitterBuffer`].
+    pub fn record_segment_late(&self) {
+        self.inner.segments_late.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Sets how much fetched audio is waiting for the decoder.
+    pub fn set_jitter_buffered(&self, audio: Duration) {
+        self.inner
+            .jitter_buffer_us
+            .store(audio.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    pub fn set_buffered_bytes(&self, bytes: usize) {
+        self.inner
+            .buffered_bytes
+            .store(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn set_buffer_cap(&self, cap: Option<usize>) {
+        self.inner
+            .buffer_cap_bytes
+            .store(cap.unwrap_or(0) as u64, Ordering::Relaxed);
+    }
+
+    /// Records the number of items waiting in the input queue of `stage`.
+    pub fn set_queue_depth(&self, stage: Stage, depth: usize) {
+        self.inner.stages[stage.index()]
+            .queue_depth
+            .store(depth, Ordering::Relaxed);
+    }
+
+    pub fn record_processed(&self, stage: Stage) {
+        self.inner.stages[stage.index()]
+            .processed
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records how long `stage` spent on its most recent item.
+    pub fn record_latency(&self, stage: Stage, elapsed: Duration) {
+        let micros = u64::try_from(elapsed.as_micros()).unwrap_or(u64::MAX);
+        let counters = &self.inner.stages[stage.index()];
+        counters.last_latency_us.store(micros, Ordering::Relaxed);
+        counters.latency.record(micros);
+    }
+
+    /// Counts `audio` as transcribed, for the session report.
+    pub fn record_audio_processed(&self, audio: Duration) {
+        let micros = u64::try_from(audio.as_micros()).unwrap_or(u64::MAX);
+        self.inner
+            .audio_processed_us
+            .fetch_add(micros, Ordering::Relaxed);
+    }
+
+    pub fn record_error(&self, stage: Stage) {
+        self.inner.stages[stage.index()]
+            .errors
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_skipped(&self, stage: Stage) {
+        self.inner.stages[stage.index()]
+            .skipped
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn set_last_transcript<S: Into<String>>(&self, text: S) {
+        store_text(&self.inner.last_transcript, text.into());
+    }
+
+    pub fn set_last_translation<S: Into<String>>(&self, text: S) {
+        store_text(&self.inner.last_translation, text.into());
+    }
+
+    pub fn set_last_summary<S: Into<String>>(&self, text: S) {
+        store_text(&self.inner.last_summary, text.into());
+    }
+
+    /// Reports the session's spend from `meter` in snapshots.
+    pub fn set_cost_meter(&self, meter: CostMeter) {
+        *lock(&self.inner.cost) = Some(meter);
+    }
+
+    pub fn cost_meter(&self) -> Option<CostMeter> {
+        lock(&self.inner.cost).clone()
+    }
+
+    /// ASR real-time factor over recent chunks (inference time / audio time).
+    pub fn set_asr_rtf(&self, rtf: f64) {
+        *lock(&self.inner.asr_rtf) = Some(rtf);
+    }
+
+    /// Stores the latest channel metadata, publishing an event when the
+    /// title or category changed.
+    pub fn set_stream_metadata(&self, metadata: StreamMetadata) {
+        let changed = {
+            let mut slot = lock(&self.inner.stream_metadata);
+            let changed = slot
+                .as_ref()
+                .is_none_or(|old| old.title != metadata.title || old.game != metadata.game);
+            *slot = Some(metadata.clone());
+            changed
+        };
+        if changed {
+            self.publish(PipelineEvent::StreamMetadata(metadata));
+        }
+    }
+
+    /// How long captions are kept for [`Self::captions`].
+    pub fn set_caption_history(&self, window: Duration) {
+        lock(&self.inner.captions).set_window(window);
+    }
+
+    /// Recent captions transcribed after `since` (all kept ones if `None`),
+    /// oldest first.
+    pub fn captions(&self, since: Option<SystemTime>) -> Vec<Caption> {
+        lock(&self.inner.captions).since(since)
+    }
+
+    pub fn stream_metadata(&self) -> Option<StreamMetadata> {
+        lock(&self.inner.stream_metadata).clone()
+    }
+
+    pub fn snapshot(&self) -> StatusSnapshot {
+        let stages = Stage::ALL
+            .iter()
+            .map(|stage| {
+                let c = &self.inner.stages[stage.index()];
+                StageSnapshot {
+                    stage: *stage,
+                    queue_depth: c.queue_depth.load(Ordering::Relaxed),
+                    processed: c.processed.load(Ordering::Relaxed),
+                    errors: c.errors.load(Ordering::Relaxed),
+                    skipped: c.skipped.load(Ordering::Relaxed),
+                    last_latency_ms: c.last_latency_us.load(Ordering::Relaxed) as f64 / 1000.0,
+                }
+            })
+            .collect();
+
+        StatusSnapshot {
+            stream_online: self.is_stream_online(),
+            tts_fallback_active: self.inner.tts_fallback_active.load(Ordering::Relaxed),
+            segments_recovered: self.inner.segments_recovered.load(Ordering::Relaxed),
+            segments_dropped: self.inner.segments_dropped.load(Ordering::Relaxed),
+            segments_late: self.inner.segments_late.load(Ordering::Relaxed),
+            jitter_buffer_ms: self.inner.jitter_buffer_us.load(Ordering::Relaxed) / 1000,
+            buffered_bytes: self.inner.buffered_bytes.load(Ordering::Relaxed),
+            buffer_cap_bytes: match self.inner.buffer_cap_bytes.load(Ordering::Relaxed) {
+                0 => None,
+                cap => Some(cap),
+            },
+            stages,
+            last_transcript: load_text(&self.inner.last_transcript),
+            last_translation: load_text(&self.inner.last_translation),
+            last_summary: load_text(&self.inner.last_summary),
+            asr_rtf: *lock(&self.inner.asr_rtf),
+            stream: self.stream_metadata(),
+            cost: lock(&self.inner.cost).as_ref().map(CostMeter::snapshot),
+        }
+    }
+
+    /// Totals since this status was created, with latency percentiles per stage.
+    pub fn report(&self) -> SessionReport {
+        let stages = Stage::ALL
+            .iter()
+            .map(|stage| {
+                let c = &self.inner.stages[stage.index()];
+                StageReport {
+                    stage: *stage,
+                    processed: c.processed.load(Ordering::Relaxed),
+                    errors: c.errors.load(Ordering::Relaxed),
+                    skipped: c.skipped.load(Ordering::Relaxed),
+                    latency: c.latency.summary(),
+                }
+            })
+            .collect();
+
+        SessionReport {
+            duration_secs: self
+                .inner
+                .started_at
+                .elapsed()
+                .unwrap_or_default()
+                .as_secs_f64(),
+            audio_secs: Duration::from_micros(
+                self.inner.audio_processed_us.load(Ordering::Relaxed),
+            )
+            .as_secs_f64(),
+            utterances: self.inner.utterances.load(Ordering::Relaxed),
+            stages,
+            segments_recovered: self.inner.segments_recovered.load(Ordering::Relaxed),
+            segments_dropped: self.inner.segments_dropped.load(Ordering::Relaxed),
+            segments_late: self.inner.segments_late.load(Ordering::Relaxed),
+            tts_fallback_activations: self.inner.tts_fallback_activations.load(Ordering::Relaxed),
+            cost: lock(&self.inner.cost).as_ref().map(CostMeter::snapshot),
+        }
+    }
+}
+
+pub(crate) fn lock<T>(m: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    match m.lock() {
+        Ok(g) => g,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+fn store_text(slot: &Mutex<Option<TimedText>>, text: String) {
+    *lock(slot) = Some(TimedText {
+        text,
+        at: SystemTime::now(),
+    });
+}
+
+fn load_text(slot: &Mutex<Option<TimedText>>) -> Option<TimedText> {
+    lock(slot).clone()
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq)]
+pub struct TimedText {
+    pub text: String,
+    pub at: SystemTime,
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq)]
+pub struct StageSnapshot {
+    pub stage: Stage,
+    pub queue_depth: usize,
+    pub processed: u64,
+    pub errors: u64,
+    /// Items dropped for being too old (see [`PipelineEvent::Skipped`]),
+    /// for missing their deadline (see [`PipelineEvent::DeadlineExceeded`])
+    /// or because the buffer cap was reached.
+    pub skipped: u64,
+    pub last_latency_ms: f64,
+}
+
+/// Point-in-time view of [`PipelineStatus`], serialized as the status endpoint body
+#[derive(Clone, Debug, Serialize, PartialEq)]
+pub struct StatusSnapshot {
+    pub stream_online: bool,
+    pub tts_fallback_active: bool,
+    pub segments_recovered: u64,
+    pub segments_dropped: u64,
+    /// Segments dropped for being fetched too late to play within the
+    /// latency budget.
+    pub segments_late: u64,
+    /// Fetched audio waiting for the decoder.
+    pub jitter_buffer_ms: u64,
+    /// Audio and segment data waiting between stages, see [`crate::memory`].
+    pub buffered_bytes: u64,
+    pub buffer_cap_bytes: Option<u64>,
+    pub stages: Vec<StageSnapshot>,
+    pub last_transcript: Option<TimedText>,
+    pub last_translation: Option<TimedText>,
+    pub last_summary: Option<TimedText>,
+    pub asr_rtf: Option<f64>,
+    pub stream: Option<StreamMetadata>,
+    /// Estimated spend on cloud backends, when it is tracked.
+    pub cost: Option<CostSnapshot>,
+}
+
+impl StatusSnapshot {
+    pub fn stage(&self, stage: Stage) -> Option<&StageSnapshot> {
+        self.stages.iter().find(|s| s.stage == stage)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snapshot_reflects_updates() {
+        let status = PipelineStatus::new();
+        let clone = status.clone();
+
+        clone.set_stream_online(true);
+        clone.set_queue_depth(Stage::Asr, 3);
+        clone.record_processed(Stage::Asr);
+        clone.record_error(Stage::Translate);
+        clone.record_error(Stage::Translate);
+        clone.set_last_transcript("hello");
+
+        let snap = status.snapshot();
+        assert!(snap.stream_online);
+        assert!(!snap.tts_fallback_active);
+        assert_eq!(snap.stage(Stage::Asr).unwrap().queue_depth, 3);
+        assert_eq!(snap.stage(Stage::Asr).unwrap().processed, 1);
+        assert_eq!(snap.stage(Stage::Translate).unwrap().errors, 2);
+        assert_eq!(snap.last_transcript.unwrap().text, "hello");
+        assert!(snap.last_translation.is_none());
+    }
+
+    #[test]
+    fn latency_is_reported_in_milliseconds() {
+        let status = PipelineStatus::new();
+        status.record_latency(Stage::Tts, Duration::from_micros(1500));
+        let snap = status.snapshot();
+        assert_eq!(snap.stage(Stage::Tts).unwrap().last_latency_ms, 1.5);
+    }
+
+    #[test]
+    fn report_sums_up_the_session() {
+        let status = PipelineStatus::new();
+        status.record_audio_processed(Duration::from_millis(1500));
+        status.record_audio_processed(Duration::from_millis(500));
+        status.publish(PipelineEvent::Transcript {
+            text: "hello".to_owned(),
+            confidence: None,
+            latency_ms: 100,
+        });
+        for ms in [100, 200, 300] {
+            status.record_latency(Stage::Asr, Duration::from_millis(ms));
+        }
+        status.set_tts_fallback_active(true);
+        status.set_tts_fallback_active(true);
+        status.set_tts_fallback_active(false);
+        status.set_tts_fallback_active(true);
+
+        let report = status.report();
+        assert_eq!(report.audio_secs, 2.0);
+        assert_eq!(report.utterances, 1);
+        assert_eq!(report.tts_fallback_activations, 2);
+        let asr = report
+            .stages
+            .iter()
+            .find(|s| s.stage == Stage::Asr)
+            .unwrap();
+        let latency = asr.latency.as_ref().unwrap();
+        assert_eq!(latency.samples, 3);
+        assert_eq!(latency.mean_ms, 200.0);
+        assert_eq!(latency.max_ms, 300.0);
+        assert!(report.stages[0].latency.is_none());
+        assert!(report
+            .to_string()
+            .contains("0:00:02 of audio, 1 utterances"));
+    }
+
+    #[tokio::test]
+    async fn published_events_reach_subscribers() {
+        let status = PipelineStatus::new();
+        status.publish(PipelineEvent::Transcript {
+            text: "dropped".to_owned(),
+            confidence: None,
+            latency_ms: 0,
+        });
+
+        let mut rx = status.subscribe();
+        status.publish(PipelineEvent::Transcript {
+            text: "hello".to_owned(),
+            confidence: Some(0.9),
+            latency_ms: 0,
+        });
+        assert_eq!(
+            rx.recv().await.unwrap(),
+            PipelineEvent::Transcript {
+                text: "hello".to_owned(),
+                confidence: Some(0.9),
+                latency_ms: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn watched_words_are_reported_once_per_utterance() {
+        let status = PipelineStatus::new();
+        status.set_watch_list(WatchList::new(&["giveaway".parse().unwrap()]));
+        let mut rx = status.subscribe();
+        for (source_text, text) in [
+            ("giveaway al final", "giveaway at the end"),
+            ("sorteo mañana", "giveaway tomorrow"),
+        ] {
+            status.publish(PipelineEvent::Transcript {
+                text: source_text.to_owned(),
+                confidence: None,
+                latency_ms: 0,
+            });
+            status.publish(PipelineEvent::Translation {
+                source_text: source_text.to_owned(),
+                text: text.to_owned(),
+                target_lang: "EN".to_owned(),
+            });
+        }
+
+        let matched: Vec<_> = std::iter::from_fn(|| rx.try_recv().ok())
+            .filter_map(|event| match event {
+                PipelineEvent::KeywordMatched {
+                    text, translated, ..
+                } => Some((text, translated)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            matched,
+            [
+                ("giveaway al final".to_owned(), false),
+                ("giveaway tomorrow".to_owned(), true)
+            ]
+        );
+        let captions = status.captions(None);
+        assert!(captions
+            .iter()
+            .all(|c| c.keyword.as_deref() == Some("giveaway")));
+    }
+
+    #[tokio::test]
+    async fn played_audio_reaches_taps() {
+        let status = PipelineStatus::new();
+        let clip = TtsAudio {
+            sample_rate_hz: 16_000,
+            channels: 1,
+            pcm_i16: vec![1, 2, 3],
+        };
+        status.publish_audio(&clip);
+
+        let mut rx = status.subscribe_audio();
+        status.publish_audio(&clip);
+        assert_eq!(rx.recv().await.unwrap(), clip);
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn metadata_event_only_on_title_or_game_change() {
+        let status = PipelineStatus::new();
+        let mut rx = status.subscribe();
+        let metadata = StreamMetadata {
+            title: "speedruns".to_owned(),
+            game: "Celeste".to_owned(),
+            viewer_count: 10,
+        };
+        status.set_stream_metadata(metadata.clone());
+        status.set_stream_metadata(StreamMetadata {
+            viewer_count: 12,
+            ..metadata.clone()
+        });
+        status.set_stream_metadata(StreamMetadata {
+            game: "Just Chatting".to_owned(),
+            ..metadata.clone()
+        });
+
+        assert_eq!(
+            rx.try_recv().unwrap(),
+            PipelineEvent::StreamMetadata(metadata)
+        );
+        assert!(matches!(
+            rx.try_recv().unwrap(),
+            PipelineEvent::StreamMetadata(m) if m.game == "Just Chatting"
+        ));
+        assert!(rx.try_recv().is_err());
+        assert_eq!(status.snapshot().stream.unwrap().viewer_count, 10);
+    }
+
+    #[test]
+    fn snapshot_serializes_stage_names_in_snake_case() {
+        let json = serde_json::to_value(PipelineStatus::new().snapshot()).unwrap();
+        assert_eq!(json["stages"][0]["stage"], "ingest");
+        assert_eq!(json["stages"].as_array().unwrap().len(), Stage::ALL.len());
+    }
+}
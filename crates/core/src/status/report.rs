@@ -0,0 +1,99 @@
+use super::{LatencySummary, Stage};
+use crate::cost::CostSnapshot;
+use serde::Serialize;
+use std::fmt;
+
+/// Totals of a session for tuning it, from [`super::PipelineStatus::report`];
+/// printed when the session ends.
+#[derive(Clone, Debug, Serialize, PartialEq)]
+pub struct SessionReport {
+    /// Wall-clock time since the session started.
+    pub duration_secs: f64,
+    /// Audio transcribed.
+    pub audio_secs: f64,
+    /// Non-empty transcripts.
+    pub utterances: u64,
+    pub stages: Vec<StageReport>,
+    pub segments_recovered: u64,
+    pub segments_dropped: u64,
+    /// Segments dropped for missing their playout time.
+    pub segments_late: u64,
+    /// How often TTS switched to the local fallback voice.
+    pub tts_fallback_activations: u64,
+    pub cost: Option<CostSnapshot>,
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq)]
+pub struct StageReport {
+    pub stage: Stage,
+    pub processed: u64,
+    pub errors: u64,
+    pub skipped: u64,
+    /// `None` for stages that timed nothing.
+    pub latency: Option<LatencySummary>,
+}
+
+impl fmt::Display for SessionReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "session: {} running, {} of audio, {} utterances",
+            clock(self.duration_secs),
+            clock(self.audio_secs),
+            self.utterances
+        )?;
+        writeln!(
+            f,
+            "{:<10} {:>7} {:>6} {:>7} {:>8} {:>8} {:>8} {:>8} {:>8}",
+            "stage", "items", "errors", "skipped", "mean ms", "p50", "p90", "p99", "max"
+        )?;
+        for stage in &self.stages {
+            write!(
+                f,
+                "{:<10} {:>7} {:>6} {:>7}",
+                stage.stage.as_str(),
+                stage.processed,
+                stage.errors,
+                stage.skipped
+            )?;
+            match &stage.latency {
+                Some(l) => writeln!(
+                    f,
+                    " {:>8.0} {:>8.0} {:>8.0} {:>8.0} {:>8.0}",
+                    l.mean_ms, l.p50_ms, l.p90_ms, l.p99_ms, l.max_ms
+                )?,
+                None => writeln!(f, " {:>8}", "-")?,
+            }
+        }
+        writeln!(
+            f,
+            "segments: {} recovered by retry, {} dropped, {} late; TTS fallback activated {} times",
+            self.segments_recovered,
+            self.segments_dropped,
+            self.segments_late,
+            self.tts_fallback_activations
+        )?;
+        if let Some(cost) = &self.cost {
+            write!(
+                f,
+                "usage: {} DeepL chars, {} ElevenLabs chars, {} + {} LLM tokens, ${:.2} estimated",
+                cost.deepl_chars,
+                cost.elevenlabs_chars,
+                cost.llm_input_tokens,
+                cost.llm_output_tokens,
+                cost.spent_usd
+            )?;
+            if let Some(budget) = cost.budget_usd {
+                write!(f, " of ${budget:.2}")?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// `h:mm:ss`
+fn clock(secs: f64) -> String {
+    let secs = secs as u64;
+    format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
@@ -0,0 +1,377 @@
+use crate::config::TargetLang;
+use crate::control::ControlHandle;
+use crate::status::PipelineStatus;
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use std::time::{Duration, UNIX_EPOCH};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+const MAX_REQUEST_HEAD_BYTES: usize = 8 * 1024;
+/// Control requests carry a few bytes of JSON.
+const MAX_REQUEST_BODY_BYTES: usize = 4 * 1024;
+
+#[derive(thiserror::Error, Debug)]
+pub enum StatusServerError {
+    #[error("failed to bind status server on {addr}: {source}")]
+    Bind {
+        addr: SocketAddr,
+        source: std::io::Error,
+    },
+
+    #[error("status server io error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// Minimal HTTP/1.1 server exposing [`PipelineStatus`] as JSON.
+///
+/// `GET /status` (and `GET /health`, which returns the same body) serves the
+/// snapshot; `GET /captions[?since=<unix ms>]` (or `/transcripts`) serves the
+/// recent caption history. With [`StatusServer::with_control`], `GET /control`
+/// reports the runtime controls and `POST /language` (`{"lang": "de"}`),
+/// `POST /pause` and `POST /resume` change them. Every connection is closed
+/// after a single response. This deliberately avoids pulling a full web
+/// framework in for a handful of endpoints.
+pub struct StatusServer {
+    listener: TcpListener,
+    status: PipelineStatus,
+    control: Option<ControlHandle>,
+}
+
+impl StatusServer {
+    pub async fn bind(addr: SocketAddr, status: PipelineStatus) -> Result<Self, StatusServerError> {
+        let listener = TcpListener::bind(addr)
+            .await
+            .map_err(|source| StatusServerError::Bind { addr, source })?;
+        Ok(Self {
+            listener,
+            status,
+            control: None,
+        })
+    }
+
+    /// Also serves the control endpoints, acting on `control`.
+    pub fn with_control(mut self, control: ControlHandle) -> Self {
+        self.control = Some(control);
+        self
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr, StatusServerError> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Accepts connections until the task is dropped or the listener fails.
+    pub async fn serve(self) -> Result<(), StatusServerError> {
+        tracing::info!(addr = %self.local_addr()?, "status server listening");
+        loop {
+            let (stream, peer) = self.listener.accept().await?;
+            let status = self.status.clone();
+            let control = self.control.clone();
+            tokio::spawn(async move {
+                if let Err(e) = handle_connection(stream, status, control).await {
+                    tracing::debug!(%peer, error = %e, "status connection failed");
+                }
+            });
+        }
+    }
+}
+
+/// Body of `POST /language`.
+#[derive(Deserialize)]
+struct LanguageRequest {
+    lang: String,
+}
+
+/// What `GET /control` reports.
+#[derive(Serialize)]
+struct ControlState {
+    target_lang: String,
+    paused: bool,
+    muted: bool,
+    volume: f32,
+}
+
+async fn handle_connection(
+    mut stream: TcpStream,
+    status: PipelineStatus,
+    control: Option<ControlHandle>,
+) -> Result<(), std::io::Error> {
+    let (head, body) = read_request(&mut stream).await?;
+    let (code, body) = match (parse_request_line(&head), &control) {
+        (Some(("GET", "/status" | "/health" | "/")), _) => json_body(&status.snapshot()),
+        (Some(("GET", "/captions" | "/transcripts")), _) => {
+            let since = query_param(&head, "since")
+                .and_then(|ms| ms.parse::<u64>().ok())
+                .map(|ms| UNIX_EPOCH + Duration::from_millis(ms));
+            json_body(&status.captions(since))
+        }
+        (Some(("GET", "/control")), Some(control)) => json_body(&ControlState {
+            target_lang: control.target_lang().as_str().to_owned(),
+            paused: control.is_paused(),
+            muted: control.is_muted(),
+            volume: control.volume(),
+        }),
+        (Some(("POST", "/language")), Some(control)) => {
+            let lang = serde_json::from_slice::<LanguageRequest>(&body)
+                .map_err(|e| e.to_string())
+                .and_then(|req| TargetLang::new(req.lang).map_err(|e| e.to_string()));
+            match lang {
+                Ok(lang) => {
+                    control.set_target_lang(lang);
+                    ok_body()
+                }
+                Err(e) => (
+                    "400 Bad Request",
+                    serde_json::json!({ "error": e }).to_string(),
+                ),
+            }
+        }
+        (Some(("POST", "/pause")), Some(control)) => {
+            control.set_paused(true);
+            ok_body()
+        }
+        (Some(("POST", "/resume")), Some(control)) => {
+            control.set_paused(false);
+            ok_body()
+        }
+        (Some(("GET", _)), _) => (
+            "404 Not Found",
+            serde_json::json!({ "error": "not found" }).to_string(),
+        ),
+        (Some(_), _) => (
+            "405 Method Not Allowed",
+            serde_json::json!({ "error": "method not allowed" }).to_string(),
+        ),
+        (None, _) => (
+            "400 Bad Request",
+            serde_json::json!({ "error": "bad request" }).to_string(),
+        ),
+    };
+
+    let response = format!(
+        "HTTP/1.1 {code}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+fn json_body<T: Serialize>(value: &T) -> (&'static str, String) {
+    match serde_json::to_string(value) {
+        Ok(json) => ("200 OK", json),
+        Err(e) => (
+            "500 Internal Server Error",
+            serde_json::json!({ "error": e.to_string() }).to_string(),
+        ),
+    }
+}
+
+fn ok_body() -> (&'static str, String) {
+    ("200 OK", serde_json::json!({ "ok": true }).to_string())
+}
+
+/// Reads the request head and, up to [`MAX_REQUEST_BODY_BYTES`], its body.
+async fn read_request(stream: &mut TcpStream) -> Result<(String, Vec<u8>), std::io::Error> {
+    let mut buf = Vec::with_capacity(512);
+    let mut chunk = [0u8; 512];
+    let head_end = loop {
+        if let Some(i) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break i + 4;
+        }
+        if buf.len() >= MAX_REQUEST_HEAD_BYTES {
+            break buf.len();
+        }
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            break buf.len();
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    };
+    let mut body = buf.split_off(head_end);
+    let head = String::from_utf8_lossy(&buf).into_owned();
+    let length = content_length(&head).min(MAX_REQUEST_BODY_BYTES);
+    while body.len() < length {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            break;
+        }
+        body.extend_from_slice(&chunk[..n]);
+    }
+    body.truncate(length);
+    Ok((head, body))
+}
+
+fn content_length(head: &str) -> usize {
+    head.lines()
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, value)| value.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+fn parse_request_line(head: &str) -> Option<(&str, &str)> {
+    let line = head.lines().next()?;
+    let mut parts = line.split_whitespace();
+    let method = parts.next()?;
+    let target = parts.next()?;
+    let path = target.split('?').next().unwrap_or(target);
+    Some((method, path))
+}
+
+/// The value of `name` in the request line's query string, if present.
+fn query_param<'a>(head: &'a str, name: &str) -> Option<&'a str> {
+    let target = head.lines().next()?.split_whitespace().nth(1)?;
+    let (_, query) = target.split_once('?')?;
+    query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::status::Stage;
+
+    #[test]
+    fn parse_request_line_strips_query() {
+        assert_eq!(
+            parse_request_line("GET /status?pretty=1 HTTP/1.1\r\nHost: x\r\n\r\n"),
+            Some(("GET", "/status"))
+        );
+        assert_eq!(parse_request_line(""), None);
+    }
+
+    #[tokio::test]
+    async fn serves_status_json() {
+        let status = PipelineStatus::new();
+        status.set_stream_online(true);
+        status.set_queue_depth(Stage::Tts, 2);
+
+        let server = StatusServer::bind("127.0.0.1:0".parse().unwrap(), status)
+            .await
+            .unwrap();
+        let addr = server.local_addr().unwrap();
+        let handle = tokio::spawn(server.serve());
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(b"GET /status HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        handle.abort();
+
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        let body = response.split("\r\n\r\n").nth(1).unwrap();
+        let json: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(json["stream_online"], true);
+        assert_eq!(json["stages"][4]["stage"], "tts");
+        assert_eq!(json["stages"][4]["queue_depth"], 2);
+    }
+
+    #[tokio::test]
+    async fn serves_captions_since_a_timestamp() {
+        let status = PipelineStatus::new();
+        status.publish(crate::status::PipelineEvent::Transcript {
+            text: "hello".to_owned(),
+            confidence: None,
+            latency_ms: 0,
+        });
+        status.publish(crate::status::PipelineEvent::Translation {
+            source_text: "hello".to_owned(),
+            text: "olá".to_owned(),
+            target_lang: "pt-BR".to_owned(),
+        });
+
+        let server = StatusServer::bind("127.0.0.1:0".parse().unwrap(), status)
+            .await
+            .unwrap();
+        let addr = server.local_addr().unwrap();
+        let handle = tokio::spawn(server.serve());
+        let get = |target: &'static str| async move {
+            let mut stream = TcpStream::connect(addr).await.unwrap();
+            let request = format!("GET {target} HTTP/1.1\r\nHost: localhost\r\n\r\n");
+            stream.write_all(request.as_bytes()).await.unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).await.unwrap();
+            let body = response.split("\r\n\r\n").nth(1).unwrap().to_owned();
+            serde_json::from_str::<serde_json::Value>(&body).unwrap()
+        };
+
+        let all = get("/captions").await;
+        let future = get("/captions?since=32503680000000").await;
+        handle.abort();
+
+        assert_eq!(all[0]["source_text"], "hello");
+        assert_eq!(all[0]["text"], "olá");
+        assert_eq!(all[0]["target_lang"], "pt-BR");
+        assert_eq!(future.as_array().unwrap().len(), 0);
+    }
+
+    #[tokio::test]
+    async fn control_endpoints_change_the_session() {
+        let control = ControlHandle::new(TargetLang::new("en").unwrap());
+        let server = StatusServer::bind("127.0.0.1:0".parse().unwrap(), PipelineStatus::new())
+            .await
+            .unwrap()
+            .with_control(control.clone());
+        let addr = server.local_addr().unwrap();
+        let handle = tokio::spawn(server.serve());
+        let request = |method: &'static str, path: &'static str, body: &'static str| async move {
+            let mut stream = TcpStream::connect(addr).await.unwrap();
+            let request = format!(
+                "{method} {path} HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\r\n{body}",
+                body.len()
+            );
+            stream.write_all(request.as_bytes()).await.unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).await.unwrap();
+            response
+        };
+
+        let changed = request("POST", "/language", r#"{"lang":"de"}"#).await;
+        let empty = request("POST", "/language", r#"{"lang":" "}"#).await;
+        let paused = request("POST", "/pause", "").await;
+        let state = request("GET", "/control", "").await;
+        handle.abort();
+
+        assert!(changed.starts_with("HTTP/1.1 200 OK"));
+        assert_eq!(control.target_lang().as_str(), "de");
+        assert!(empty.starts_with("HTTP/1.1 400"));
+        assert!(paused.starts_with("HTTP/1.1 200 OK"));
+        assert!(control.is_paused());
+        let body = state.split("\r\n\r\n").nth(1).unwrap();
+        let json: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(json["target_lang"], "de");
+        assert_eq!(json["paused"], true);
+    }
+
+    #[tokio::test]
+    async fn control_endpoints_need_a_control_handle() {
+        let server = StatusServer::bind("127.0.0.1:0".parse().unwrap(), PipelineStatus::new())
+            .await
+            .unwrap();
+        let addr = server.local_addr().unwrap();
+        let handle = tokio::spawn(server.serve());
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(b"POST /pause HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        handle.abort();
+
+        assert!(response.starts_with("HTTP/1.1 405"));
+    }
+
+    #[test]
+    fn reads_query_parameters() {
+        let head = "GET /captions?x=1&since=42 HTTP/1.1\r\n\r\n";
+        assert_eq!(query_param(head, "since"), Some("42"));
+        assert_eq!(query_param(head, "until"), None);
+    }
+}
@@ -0,0 +1,352 @@
+//! SRT subtitle output
+//!
+//! [`SrtWriter`] turns the pipeline's translation events into numbered SRT
+//! cues, timed from when the writer was created, so a session can be saved as
+//! a subtitle track or fed to an overlay that tails the file. [`CaptionFile`]
+//! instead keeps only the caption on screen now, for an OBS Text source.
+//! Sessions that only transcribe use the transcripts instead. Either can add
+//! a romanized line under captions in non-Latin scripts.
+
+use crate::status::PipelineEvent;
+#[cfg(feature = "native")]
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::sync::broadcast::{self, error::RecvError};
+
+const MIN_CUE: Duration = Duration::from_millis(1500);
+const MAX_CUE: Duration = Duration::from_secs(7);
+/// Rough reading speed used to size cues.
+const PER_CHAR: Duration = Duration::from_millis(60);
+
+pub struct SrtWriter<W> {
+    out: W,
+    started: Instant,
+    next_index: usize,
+    transcripts: bool,
+    romanize: bool,
+}
+
+#[cfg(feature = "native")]
+impl SrtWriter<tokio::fs::File> {
+    /// Creates (or truncates) the subtitle file at `path`.
+    pub async fn create(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        Ok(Self::new(tokio::fs::File::create(path).await?))
+    }
+}
+
+impl<W: AsyncWrite + Unpin> SrtWriter<W> {
+    pub fn new(out: W) -> Self {
+        Self {
+            out,
+            started: Instant::now(),
+            next_index: 1,
+            transcripts: false,
+            romanize: false,
+        }
+    }
+
+    /// Writes cues for transcripts instead of translations, for sessions
+    /// that only transcribe.
+    pub fn with_transcripts(mut self) -> Self {
+        self.transcripts = true;
+        self
+    }
+
+    /// Follows each cue in a non-Latin script with its romanized form.
+    pub fn with_romanization(mut self) -> Self {
+        self.romanize = true;
+        self
+    }
+
+    /// Appends a cue for `text` starting now.
+    pub async fn write_cue(&mut self, text: &str) -> std::io::Result<()> {
+        let start = self.started.elapsed();
+        self.write_cue_at(start, text).await
+    }
+
+    async fn write_cue_at(&mut self, start: Duration, text: &str) -> std::io::Result<()> {
+        let end = start + cue_duration(text.trim());
+        self.write_timed_cue(start, end, text).await
+    }
+
+    /// Appends a cue for `text` from `start` to `end`, offsets into the
+    /// recording, for audio processed faster than real time.
+    pub async fn write_timed_cue(
+        &mut self,
+        start: Duration,
+        end: Duration,
+        text: &str,
+    ) -> std::io::Result<()> {
+        let text = text.trim();
+        if text.is_empty() {
+            return Ok(());
+        }
+        let cue = format!(
+            "{}\n{} --> {}\n{}\n\n",
+            self.next_index,
+            format_timestamp(start),
+            format_timestamp(end),
+            text
+        );
+        self.out.write_all(cue.as_bytes()).await?;
+        // Flushed per cue so tools tailing the file see it immediately.
+        self.out.flush().await?;
+        self.next_index += 1;
+        Ok(())
+    }
+
+    /// Writes a cue for every translation (or transcript) until the pipeline's
+    /// event channel closes.
+    pub async fn run(
+        mut self,
+        mut events: broadcast::Receiver<PipelineEvent>,
+    ) -> std::io::Result<()> {
+        loop {
+            match events.recv().await {
+                Ok(event) => {
+                    if let Some(text) = caption_text(event, self.transcripts, self.romanize) {
+                        self.write_cue(&text).await?;
+                    }
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    tracing::warn!(skipped, "subtitle writer fell behind; cues dropped");
+                }
+                Err(RecvError::Closed) => return Ok(()),
+            }
+        }
+    }
+}
+
+/// The caption on screen now as a plain text file, for an OBS Text source set
+/// to read from a file. Each translation replaces the contents and is cleared
+/// again once it has been up for its reading time.
+#[cfg(feature = "native")]
+pub struct CaptionFile {
+    path: PathBuf,
+    transcripts: bool,
+    romanize: bool,
+}
+
+#[cfg(feature = "native")]
+impl CaptionFile {
+    /// Creates (or empties) the caption file at `path`.
+    pub async fn create(path: impl Into<PathBuf>) -> std::io::Result<Self> {
+        let file = Self {
+            path: path.into(),
+            transcripts: false,
+            romanize: false,
+        };
+        file.show("").await?;
+        Ok(file)
+    }
+
+    /// Shows transcripts instead of translations, for sessions that only
+    /// transcribe.
+    pub fn with_transcripts(mut self) -> Self {
+        self.transcripts = true;
+        self
+    }
+
+    /// Follows each caption in a non-Latin script with its romanized form.
+    pub fn with_romanization(mut self) -> Self {
+        self.romanize = true;
+        self
+    }
+
+    /// Shows each translation (or transcript) until the pipeline's event
+    /// channel closes, then leaves the file empty.
+    pub async fn run(self, mut events: broadcast::Receiver<PipelineEvent>) -> std::io::Result<()> {
+        let mut clear_at = None;
+        loop {
+            let event = match clear_at {
+                Some(at) => tokio::select! {
+                    event = events.recv() => event,
+                    () = tokio::time::sleep_until(at) => {
+                        self.show("").await?;
+                        clear_at = None;
+                        continue;
+                    }
+                },
+                None => events.recv().await,
+            };
+            let text = match event {
+                Ok(event) => caption_text(event, self.transcripts, self.romanize),
+                // Only the newest caption matters, and it is still to come.
+                Err(RecvError::Lagged(_)) => None,
+                Err(RecvError::Closed) => return self.show("").await,
+            };
+            if let Some(text) = text.as_deref().map(str::trim).filter(|t| !t.is_empty()) {
+                self.show(text).await?;
+                clear_at = Some(tokio::time::Instant::now() + cue_duration(text));
+            }
+        }
+    }
+
+    /// Replaces the contents in one step, so OBS never reads half a caption.
+    async fn show(&self, text: &str) -> std::io::Result<()> {
+        let mut partial = self.path.clone().into_os_string();
+        partial.push(".tmp");
+        tokio::fs::write(&partial, text).await?;
+        tokio::fs::rename(&partial, &self.path).await
+    }
+}
+
+/// The caption `event` carries: a translation, or with `transcripts` a
+/// transcript, with `romanize` followed by a romanized line if it has one.
+/// Sounds such as music are captioned in either case.
+fn caption_text(event: PipelineEvent, transcripts: bool, romanize: bool) -> Option<String> {
+    let text = match event {
+        PipelineEvent::NonSpeech { sound } => return Some(sound.label()),
+        PipelineEvent::Translation { text, .. } if !transcripts => text,
+        PipelineEvent::Transcript { text, .. } if transcripts => text,
+        _ => return None,
+    };
+    let roman = if romanize {
+        crate::text::romanize(&text)
+    } else {
+        None
+    };
+    match roman {
+        Some(roman) => Some(format!("{}\n{}", text.trim(), roman.trim())),
+        None => Some(text),
+    }
+}
+
+fn cue_duration(text: &str) -> Duration {
+    (PER_CHAR * text.chars().count() as u32).clamp(MIN_CUE, MAX_CUE)
+}
+
+/// `HH:MM:SS,mmm`
+fn format_timestamp(at: Duration) -> String {
+    let ms = at.as_millis();
+    format!(
+        "{:02}:{:02}:{:02},{:03}",
+        ms / 3_600_000,
+        ms / 60_000 % 60,
+        ms / 1000 % 60,
+        ms % 1000
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_srt_timestamps() {
+        assert_eq!(format_timestamp(Duration::ZERO), "00:00:00,000");
+        assert_eq!(
+            format_timestamp(Duration::from_millis(3_723_045)),
+            "01:02:03,045"
+        );
+    }
+
+    #[tokio::test]
+    async fn transcript_writer_ignores_translations() {
+        let (events, rx) = broadcast::channel(4);
+        events
+            .send(PipelineEvent::Transcript {
+                text: "hello chat".to_owned(),
+                confidence: None,
+                latency_ms: 0,
+            })
+            .unwrap();
+        events
+            .send(PipelineEvent::Translation {
+                source_text: "hello chat".to_owned(),
+                text: "olá chat".to_owned(),
+                target_lang: "PT-BR".to_owned(),
+            })
+            .unwrap();
+        drop(events);
+
+        let mut out = Vec::new();
+        SrtWriter::new(&mut out)
+            .with_transcripts()
+            .run(rx)
+            .await
+            .unwrap();
+        let srt = String::from_utf8(out).unwrap();
+        assert!(srt.ends_with("\nhello chat\n\n"));
+        assert_eq!(srt.matches("-->").count(), 1);
+    }
+
+    #[test]
+    fn romanized_captions_add_a_line() {
+        let translation = |text: &str| PipelineEvent::Translation {
+            source_text: String::new(),
+            text: text.to_owned(),
+            target_lang: "RU".to_owned(),
+        };
+        assert_eq!(
+            caption_text(translation("Привет, чат"), false, true).as_deref(),
+            Some("Привет, чат\nPrivet, chat")
+        );
+        assert_eq!(
+            caption_text(translation("hello chat"), false, true).as_deref(),
+            Some("hello chat")
+        );
+        assert_eq!(
+            caption_text(translation("Привет"), false, false).as_deref(),
+            Some("Привет")
+        );
+    }
+
+    #[tokio::test]
+    async fn numbers_cues_and_skips_empty_text() {
+        let mut writer = SrtWriter::new(Vec::new());
+        writer
+            .write_cue_at(Duration::from_secs(1), "olá")
+            .await
+            .unwrap();
+        writer
+            .write_cue_at(Duration::from_secs(2), "  ")
+            .await
+            .unwrap();
+        writer
+            .write_cue_at(Duration::from_secs(3), "tudo bem")
+            .await
+            .unwrap();
+
+        let srt = String::from_utf8(writer.out).unwrap();
+        assert_eq!(
+            srt,
+            "1\n00:00:01,000 --> 00:00:02,500\nolá\n\n2\n00:00:03,000 --> 00:00:04,500\ntudo bem\n\n"
+        );
+    }
+
+    /// Polls `path` until it holds `want`, for up to five seconds.
+    #[cfg(feature = "native")]
+    async fn wait_for_contents(path: &Path, want: &str) {
+        for _ in 0..100 {
+            if std::fs::read_to_string(path).is_ok_and(|c| c == want) {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        panic!("{} never held {want:?}", path.display());
+    }
+
+    #[cfg(feature = "native")]
+    #[tokio::test]
+    async fn caption_file_shows_the_latest_translation_then_clears() {
+        let path = std::env::temp_dir().join(format!("captions-{}.txt", std::process::id()));
+        let (events, rx) = broadcast::channel(4);
+        let task = tokio::spawn(CaptionFile::create(path.clone()).await.unwrap().run(rx));
+        let translation = |text: &str| PipelineEvent::Translation {
+            source_text: String::new(),
+            text: text.to_owned(),
+            target_lang: "PT-BR".to_owned(),
+        };
+
+        events.send(translation("olá")).unwrap();
+        wait_for_contents(&path, "olá").await;
+        // Cleared after its reading time, MIN_CUE for a short caption.
+        wait_for_contents(&path, "").await;
+
+        drop(events);
+        task.await.unwrap().unwrap();
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,337 @@
+//! Rolling stream summaries
+//!
+//! Every few minutes, [`run`] hands the recently translated captions to a
+//! [`Summarizer`] and publishes the result as a [`PipelineEvent::Summary`]
+//! (and in the status snapshot), so viewers who join mid-stream can catch up.
+//! [`ChatSummarizer`] talks to any OpenAI-compatible chat completions API,
+//! including local servers such as Ollama or llama.cpp.
+
+use crate::config::{LlmConfig, TargetLang};
+use crate::cost::{BudgetExceeded, CostMeter};
+use crate::status::{PipelineEvent, PipelineStatus};
+use crate::util::{HttpError, HttpRequest, ReqwestTransport, SharedTransport};
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime};
+
+#[derive(thiserror::Error, Debug)]
+pub enum SummaryError {
+    #[error("network error: {0}")]
+    Network(#[from] HttpError),
+    #[error("API error: {0}")]
+    Api(String),
+    #[error("invalid response: {0}")]
+    InvalidResponse(String),
+    #[error(transparent)]
+    BudgetExceeded(#[from] BudgetExceeded),
+}
+
+pub trait Summarizer: Send + Sync {
+    /// Summarizes `text` in `lang`.
+    fn summarize(
+        &self,
+        text: String,
+        lang: TargetLang,
+    ) -> BoxFuture<'_, Result<String, SummaryError>>;
+}
+
+/// Summarizer backed by an OpenAI-compatible `/chat/completions` endpoint.
+#[derive(Clone)]
+pub struct ChatSummarizer {
+    transport: SharedTransport,
+    base_url: String,
+    model: String,
+    api_key: Option<String>,
+    cost: Option<CostMeter>,
+}
+
+impl ChatSummarizer {
+    /// `base_url` is the API root, e.g. `https://api.openai.com/v1` or
+    /// `http://localhost:11434/v1`.
+    pub fn new(base_url: impl Into<String>, model: impl Into<String>) -> Self {
+        Self {
+            transport: ReqwestTransport::default().shared(),
+            base_url: base_url.into().trim_end_matches('/').to_owned(),
+            model: model.into(),
+            api_key: None,
+            cost: None,
+        }
+    }
+
+    /// The API, model and key of `config`.
+    pub fn from_config(config: &LlmConfig) -> Self {
+        let summarizer = Self::new(&config.base_url, &config.model);
+        match &config.api_key {
+            Some(key) => summarizer.with_api_key(key.expose().to_owned()),
+            None => summarizer,
+        }
+    }
+
+    /// Sent as a bearer token; local servers usually need none.
+    pub fn with_api_key(mut self, api_key: String) -> Self {
+        self.api_key = Some(api_key);
+        self
+    }
+
+    /// Sends requests through `transport` instead of the network, e.g. a
+    /// [`crate::util::MockTransport`] in tests.
+    pub fn with_transport(mut self, transport: SharedTransport) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    /// Counts the tokens used against `cost`, and stops calling the model
+    /// once its budget is spent.
+    pub fn with_cost_meter(mut self, cost: CostMeter) -> Self {
+        self.cost = Some(cost);
+        self
+    }
+
+    /// The model's reply to `user` under the `system` prompt.
+    pub(crate) async fn complete(
+        &self,
+        system: String,
+        user: String,
+    ) -> Result<String, SummaryError> {
+        self.converse(system, &[], user).await
+    }
+
+    /// Like [`Self::complete`], after an earlier exchange of `history`, each
+    /// a user message and the model's reply to it.
+    pub(crate) async fn converse(
+        &self,
+        system: String,
+        history: &[(String, String)],
+        user: String,
+    ) -> Result<String, SummaryError> {
+        if let Some(cost) = &self.cost {
+            cost.check_budget()?;
+        }
+        let message = |role: &str, content: String| ChatMessage {
+            role: role.to_owned(),
+            content,
+        };
+        let mut messages = vec![message("system", system)];
+        for (asked, replied) in history {
+            messages.push(message("user", asked.clone()));
+            messages.push(message("assistant", replied.clone()));
+        }
+        messages.push(message("user", user));
+        let body = ChatRequest {
+            model: &self.model,
+            messages,
+        };
+        let mut request =
+            HttpRequest::post(format!("{}/chat/completions", self.base_url)).with_json(&body)?;
+        if let Some(key) = &self.api_key {
+            request = request.with_header("Authorization", format!("Bearer {key}"));
+        }
+
+        let response = self.transport.send(request).await?;
+        if !response.is_success() {
+            return Err(SummaryError::Api(format!(
+                "HTTP {}: {}",
+                response.status,
+                response.text()
+            )));
+        }
+        let parsed: ChatResponse = response
+            .json()
+            .map_err(|e| SummaryError::InvalidResponse(e.to_string()))?;
+        let reply = parsed
+            .choices
+            .into_iter()
+            .next()
+            .map(|c| c.message.content.trim().to_owned())
+            .filter(|s| !s.is_empty())
+            .ok_or_else(|| SummaryError::InvalidResponse("empty reply".to_owned()))?;
+        if let Some(cost) = &self.cost {
+            let prompt = body
+                .messages
+                .iter()
+                .map(|m| m.content.as_str())
+                .collect::<Vec<_>>()
+                .join("\n");
+            let usage = parsed.usage.map(|u| (u.prompt_tokens, u.completion_tokens));
+            cost.record_llm(&prompt, &reply, usage);
+        }
+        Ok(reply)
+    }
+}
+
+#[derive(Serialize)]
+struct ChatRequest<'a> {
+    model: &'a str,
+    messages: Vec<ChatMessage>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ChatMessage {
+    role: String,
+    content: String,
+}
+
+#[derive(Deserialize)]
+struct ChatResponse {
+    choices: Vec<ChatChoice>,
+    /// Billed tokens, reported by most OpenAI-compatible servers.
+    usage: Option<ChatUsage>,
+}
+
+#[derive(Deserialize)]
+struct ChatUsage {
+    prompt_tokens: u64,
+    completion_tokens: u64,
+}
+
+#[derive(Deserialize)]
+struct ChatChoice {
+    message: ChatMessage,
+}
+
+impl Summarizer for ChatSummarizer {
+    fn summarize(
+        &self,
+        text: String,
+        lang: TargetLang,
+    ) -> BoxFuture<'_, Result<String, SummaryError>> {
+        async move {
+            let prompt = format!(
+                "You summarize a live stream for viewers who just joined. The user message is \
+                 what the streamer said recently. Reply in the language with code {} using two \
+                 or three short sentences, without preamble.",
+                lang.as_str()
+            );
+            self.complete(prompt, text).await
+        }
+        .boxed()
+    }
+}
+
+/// Every `every`, summarizes the captions of the last `window` and
+/// publishes the summary; runs until aborted. Captions are read from the
+/// status caption history, which must reach back at least `window`.
+pub async fn run<S: Summarizer>(
+    summarizer: S,
+    status: PipelineStatus,
+    every: Duration,
+    window: Duration,
+) {
+    let mut ticks = tokio::time::interval(every);
+    // The first tick fires immediately, before anything has been said.
+    ticks.tick().await;
+    loop {
+        ticks.tick().await;
+        let since = SystemTime::now().checked_sub(window);
+        let captions = status.captions(since);
+        let Some(lang) = captions
+            .iter()
+            .rev()
+            .find_map(|c| c.target_lang.clone())
+            .and_then(|l| TargetLang::new(l).ok())
+        else {
+            continue;
+        };
+        let text: Vec<&str> = captions
+            .iter()
+            .map(|c| c.text.as_deref().unwrap_or(&c.source_text))
+            .collect();
+
+        match summarizer.summarize(text.join("\n"), lang).await {
+            Ok(summary) => {
+                status.set_last_summary(summary.clone());
+                status.publish(PipelineEvent::Summary {
+                    text: summary,
+                    window_secs: window.as_secs(),
+                });
+            }
+            Err(e) => tracing::warn!(error = %e, "summary failed"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::{HttpMethod, MockTransport};
+
+    #[tokio::test]
+    async fn chat_summarizer_sends_prompt_and_reads_reply() {
+        let mock = MockTransport::new().on(
+            HttpMethod::Post,
+            "localhost:11434/v1/chat/completions",
+            200,
+            r#"{"choices":[{"message":{"role":"assistant","content":" Eles venceram o chefe. "}}]}"#,
+        );
+        let summarizer = ChatSummarizer::new("http://localhost:11434/v1/", "llama3")
+            .with_api_key("secret".to_owned())
+            .with_transport(mock.clone().shared());
+
+        let summary = summarizer
+            .summarize(
+                "venceram o chefe".to_owned(),
+                TargetLang::new("pt-BR").unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(summary, "Eles venceram o chefe.");
+
+        let request = &mock.requests()[0];
+        assert_eq!(request.header("authorization"), Some("Bearer secret"));
+        let body: serde_json::Value =
+            serde_json::from_slice(request.body.as_ref().unwrap()).unwrap();
+        assert_eq!(body["model"], "llama3");
+        assert!(body["messages"][0]["content"]
+            .as_str()
+            .unwrap()
+            .contains("pt-BR"));
+        assert_eq!(body["messages"][1]["content"], "venceram o chefe");
+    }
+
+    #[tokio::test]
+    async fn run_publishes_summaries_of_recent_captions() {
+        let mock = MockTransport::new().on(
+            HttpMethod::Post,
+            "/chat/completions",
+            200,
+            r#"{"choices":[{"message":{"role":"assistant","content":"Resumo."}}]}"#,
+        );
+        let summarizer =
+            ChatSummarizer::new("http://llm/v1", "m").with_transport(mock.clone().shared());
+        let status = PipelineStatus::new();
+        status.publish(PipelineEvent::Transcript {
+            text: "hello".into(),
+            confidence: None,
+            latency_ms: 0,
+        });
+        status.publish(PipelineEvent::Translation {
+            source_text: "hello".into(),
+            text: "olá".into(),
+            target_lang: "pt-BR".into(),
+        });
+        status.publish(PipelineEvent::Transcript {
+            text: "bye".into(),
+            confidence: None,
+            latency_ms: 0,
+        });
+        let mut events = status.subscribe();
+
+        let task = tokio::spawn(run(
+            summarizer,
+            status.clone(),
+            Duration::from_millis(20),
+            Duration::from_secs(60),
+        ));
+        let event = events.recv().await.unwrap();
+        task.abort();
+
+        assert!(
+            matches!(event, PipelineEvent::Summary { text, window_secs: 60 } if text == "Resumo.")
+        );
+        assert_eq!(status.snapshot().last_summary.unwrap().text, "Resumo.");
+        let body: serde_json::Value =
+            serde_json::from_slice(mock.requests()[0].body.as_ref().unwrap()).unwrap();
+        assert_eq!(body["messages"][1]["content"], "olá\nbye");
+    }
+}
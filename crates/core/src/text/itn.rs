@@ -0,0 +1,761 @@
+use super::TextTransform;
+use crate::config::TargetLang;
+
+/// What a number word contributes, which decides the words that may follow it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Kind {
+    /// 0 to 9.
+    Unit,
+    /// A two-digit word that takes no unit after it, e.g. `twelve` or
+    /// Spanish `veinticinco`.
+    Teen,
+    /// `twenty` to `ninety`.
+    Tens,
+    /// A whole hundreds word, e.g. Spanish `doscientos`.
+    Hundreds,
+    /// English `hundred`, multiplying what came before.
+    Hundred,
+    /// `thousand`, `million` and up.
+    Scale,
+}
+
+use Kind::*;
+
+/// How a source language speaks numbers, amounts and dates.
+struct Numerals {
+    words: &'static [(&'static str, Kind, u64)],
+    /// Word joining the parts of a number, allowed after these kinds.
+    connector: &'static str,
+    connects_after: &'static [Kind],
+    /// Stands for one before `hundred` or a scale word ("a hundred").
+    article: Option<&'static str>,
+    /// The thousands word alone means one thousand (Spanish `mil`).
+    bare_thousand: bool,
+    decimal_word: &'static str,
+    decimal_separator: char,
+    /// Written between groups of three digits from 10 000 up.
+    group_separator: char,
+    percent: &'static [&'static [&'static str]],
+    percent_sign: &'static str,
+    /// Currency words written as a symbol before the amount.
+    currencies: &'static [(&'static str, &'static str)],
+    /// Hundredths of those currencies, e.g. `cents`.
+    minor_units: &'static [&'static str],
+    /// Years spoken in pairs, "nineteen ninety nine".
+    paired_years: bool,
+    /// Words a paired year follows, besides a month name, e.g. `since`.
+    /// Elsewhere "twelve thirty" is a time and "fifteen twenty" a count.
+    year_after: &'static [&'static str],
+    /// Words before and after which an hour and minutes are a time of day.
+    time_after: &'static [&'static str],
+    clock_words: &'static [&'static str],
+    /// Month names, for dates spoken as a month and an ordinal.
+    months: &'static [&'static str],
+    /// Months that are also common words, only taken as months when
+    /// capitalized: "you may first want to" is no date.
+    word_months: &'static [&'static str],
+    ordinals: &'static [(&'static str, u64)],
+}
+
+const EN: Numerals = Numerals {
+    words: &[
+        ("zero", Unit, 0),
+        ("one", Unit, 1),
+        ("two", Unit, 2),
+        ("three", Unit, 3),
+        ("four", Unit, 4),
+        ("five", Unit, 5),
+        ("six", Unit, 6),
+        ("seven", Unit, 7),
+        ("eight", Unit, 8),
+        ("nine", Unit, 9),
+        ("ten", Teen, 10),
+        ("eleven", Teen, 11),
+        ("twelve", Teen, 12),
+        ("thirteen", Teen, 13),
+        ("fourteen", Teen, 14),
+        ("fifteen", Teen, 15),
+        ("sixteen", Teen, 16),
+        ("seventeen", Teen, 17),
+        ("eighteen", Teen, 18),
+        ("nineteen", Teen, 19),
+        ("twenty", Tens, 20),
+        ("thirty", Tens, 30),
+        ("forty", Tens, 40),
+        ("fifty", Tens, 50),
+        ("sixty", Tens, 60),
+        ("seventy", Tens, 70),
+        ("eighty", Tens, 80),
+        ("ninety", Tens, 90),
+        ("hundred", Hundred, 100),
+        ("thousand", Scale, 1_000),
+        ("million", Scale, 1_000_000),
+        ("billion", Scale, 1_000_000_000),
+    ],
+    connector: "and",
+    connects_after: &[Hundred, Scale],
+    article: Some("a"),
+    bare_thousand: false,
+    decimal_word: "point",
+    decimal_separator: '.',
+    group_separator: ',',
+    percent: &[&["percent"], &["per", "cent"]],
+    percent_sign: "%",
+    currencies: &[
+        ("dollar", "$"),
+        ("dollars", "$"),
+        ("buck", "$"),
+        ("bucks", "$"),
+        ("euro", "€"),
+        ("euros", "€"),
+        ("pound", "£"),
+        ("pounds", "£"),
+    ],
+    minor_units: &["cent", "cents"],
+    paired_years: true,
+    year_after: &["in", "since", "of", "year", "circa"],
+    time_after: &["at"],
+    clock_words: &["am", "pm", "a.m", "p.m"],
+    months: &[
+        "january",
+        "february",
+        "march",
+        "april",
+        "may",
+        "june",
+        "july",
+        "august",
+        "september",
+        "october",
+        "november",
+        "december",
+    ],
+    word_months: &["may"],
+    ordinals: &[
+        ("first", 1),
+        ("second", 2),
+        ("third", 3),
+        ("fourth", 4),
+        ("fifth", 5),
+        ("sixth", 6),
+        ("seventh", 7),
+        ("eighth", 8),
+        ("ninth", 9),
+        ("tenth", 10),
+        ("eleventh", 11),
+        ("twelfth", 12),
+        ("thirteenth", 13),
+        ("fourteenth", 14),
+        ("fifteenth", 15),
+        ("sixteenth", 16),
+        ("seventeenth", 17),
+        ("eighteenth", 18),
+        ("nineteenth", 19),
+        ("twentieth", 20),
+        ("thirtieth", 30),
+    ],
+};
+
+const ES: Numerals = Numerals {
+    words: &[
+        ("cero", Unit, 0),
+        ("un", Unit, 1),
+        ("uno", Unit, 1),
+        ("una", Unit, 1),
+        ("dos", Unit, 2),
+        ("tres", Unit, 3),
+        ("cuatro", Unit, 4),
+        ("cinco", Unit, 5),
+        ("seis", Unit, 6),
+        ("siete", Unit, 7),
+        ("ocho", Unit, 8),
+        ("nueve", Unit, 9),
+        ("diez", Teen, 10),
+        ("once", Teen, 11),
+        ("doce", Teen, 12),
+        ("trece", Teen, 13),
+        ("catorce", Teen, 14),
+        ("quince", Teen, 15),
+        ("dieciséis", Teen, 16),
+        ("diecisiete", Teen, 17),
+        ("dieciocho", Teen, 18),
+        ("diecinueve", Teen, 19),
+        ("veinte", Tens, 20),
+        ("veintiún", Teen, 21),
+        ("veintiuno", Teen, 21),
+        ("veintiuna", Teen, 21),
+        ("veintidós", Teen, 22),
+        ("veintitrés", Teen, 23),
+        ("veinticuatro", Teen, 24),
+        ("veinticinco", Teen, 25),
+        ("veintiséis", Teen, 26),
+        ("veintisiete", Teen, 27),
+        ("veintiocho", Teen, 28),
+        ("veintinueve", Teen, 29),
+        ("treinta", Tens, 30),
+        ("cuarenta", Tens, 40),
+        ("cincuenta", Tens, 50),
+        ("sesenta", Tens, 60),
+        ("setenta", Tens, 70),
+        ("ochenta", Tens, 80),
+        ("noventa", Tens, 90),
+        ("cien", Hundreds, 100),
+        ("ciento", Hundreds, 100),
+        ("doscientos", Hundreds, 200),
+        ("doscientas", Hundreds, 200),
+        ("trescientos", Hundreds, 300),
+        ("trescientas", Hundreds, 300),
+        ("cuatrocientos", Hundreds, 400),
+        ("cuatrocientas", Hundreds, 400),
+        ("quinientos", Hundreds, 500),
+        ("quinientas", Hundreds, 500),
+        ("seiscientos", Hundreds, 600),
+        ("seiscientas", Hundreds, 600),
+        ("setecientos", Hundreds, 700),
+        ("setecientas", Hundreds, 700),
+        ("ochocientos", Hundreds, 800),
+        ("ochocientas", Hundreds, 800),
+        ("novecientos", Hundreds, 900),
+        ("novecientas", Hundreds, 900),
+        ("mil", Scale, 1_000),
+        ("millón", Scale, 1_000_000),
+        ("millones", Scale, 1_000_000),
+    ],
+    connector: "y",
+    connects_after: &[Tens],
+    article: None,
+    bare_thousand: true,
+    decimal_word: "coma",
+    decimal_separator: ',',
+    group_separator: '.',
+    percent: &[&["por", "ciento"]],
+    percent_sign: " %",
+    currencies: &[],
+    minor_units: &[],
+    paired_years: false,
+    year_after: &[],
+    time_after: &[],
+    clock_words: &[],
+    months: &[],
+    word_months: &[],
+    ordinals: &[],
+};
+
+const PT: Numerals = Numerals {
+    words: &[
+        ("zero", Unit, 0),
+        ("um", Unit, 1),
+        ("uma", Unit, 1),
+        ("dois", Unit, 2),
+        ("duas", Unit, 2),
+        ("três", Unit, 3),
+        ("quatro", Unit, 4),
+        ("cinco", Unit, 5),
+        ("seis", Unit, 6),
+        ("sete", Unit, 7),
+        ("oito", Unit, 8),
+        ("nove", Unit, 9),
+        ("dez", Teen, 10),
+        ("onze", Teen, 11),
+        ("doze", Teen, 12),
+        ("treze", Teen, 13),
+        ("catorze", Teen, 14),
+        ("quatorze", Teen, 14),
+        ("quinze", Teen, 15),
+        ("dezesseis", Teen, 16),
+        ("dezasseis", Teen, 16),
+        ("dezessete", Teen, 17),
+        ("dezassete", Teen, 17),
+        ("dezoito", Teen, 18),
+        ("dezenove", Teen, 19),
+        ("dezanove", Teen, 19),
+        ("vinte", Tens, 20),
+        ("trinta", Tens, 30),
+        ("quarenta", Tens, 40),
+        ("cinquenta", Tens, 50),
+        ("sessenta", Tens, 60),
+        ("setenta", Tens, 70),
+        ("oitenta", Tens, 80),
+        ("noventa", Tens, 90),
+        ("cem", Hundreds, 100),
+        ("cento", Hundreds, 100),
+        ("duzentos", Hundreds, 200),
+        ("duzentas", Hundreds, 200),
+        ("trezentos", Hundreds, 300),
+        ("trezentas", Hundreds, 300),
+        ("quatrocentos", Hundreds, 400),
+        ("quatrocentas", Hundreds, 400),
+        ("quinhentos", Hundreds, 500),
+        ("quinhentas", Hundreds, 500),
+        ("seiscentos", Hundreds, 600),
+        ("seiscentas", Hundreds, 600),
+        ("setecentos", Hundreds, 700),
+        ("setecentas", Hundreds, 700),
+        ("oitocentos", Hundreds, 800),
+        ("oitocentas", Hundreds, 800),
+        ("novecentos", Hundreds, 900),
+        ("novecentas", Hundreds, 900),
+        ("mil", Scale, 1_000),
+        ("milhão", Scale, 1_000_000),
+        ("milhões", Scale, 1_000_000),
+    ],
+    connector: "e",
+    connects_after: &[Tens, Hundreds, Scale],
+    article: None,
+    bare_thousand: true,
+    decimal_word: "vírgula",
+    decimal_separator: ',',
+    group_separator: '.',
+    percent: &[&["por", "cento"]],
+    percent_sign: "%",
+    currencies: &[],
+    minor_units: &[],
+    paired_years: false,
+    year_after: &[],
+    time_after: &[],
+    clock_words: &[],
+    months: &[],
+    word_months: &[],
+    ordinals: &[],
+};
+
+fn numerals(language: &str) -> Option<&'static Numerals> {
+    let primary = language
+        .split(['-', '_'])
+        .next()
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    Some(match primary.as_str() {
+        "en" => &EN,
+        "es" => &ES,
+        "pt" => &PT,
+        _ => return None,
+    })
+}
+
+/// A word with the punctuation around it split off.
+struct Token<'a> {
+    raw: &'a str,
+    lead: &'a str,
+    /// Lower-cased, for matching.
+    word: String,
+    trail: &'a str,
+}
+
+fn tokenize<'a>(text: &'a str, numerals: &Numerals) -> Vec<Token<'a>> {
+    let mut tokens = Vec::new();
+    for raw in text.split_whitespace() {
+        let start = raw.find(|c: char| c.is_alphanumeric()).unwrap_or(raw.len());
+        let end = raw.rfind(|c: char| c.is_alphanumeric()).map_or(start, |i| {
+            i + raw[i..].chars().next().map_or(0, char::len_utf8)
+        });
+        let (lead, core, trail) = (&raw[..start], &raw[start..end], &raw[end..]);
+        // "twenty-five" is two words.
+        if let Some((tens, unit)) = core.split_once('-') {
+            let tens_word = tens.to_lowercase();
+            let unit_word = unit.to_lowercase();
+            if matches!(numerals.word(&tens_word), Some((Tens, _)))
+                && matches!(numerals.word(&unit_word), Some((Unit, n)) if n > 0)
+            {
+                tokens.push(Token {
+                    raw: tens,
+                    lead,
+                    word: tens_word,
+                    trail: "",
+                });
+                tokens.push(Token {
+                    raw: unit,
+                    lead: "",
+                    word: unit_word,
+                    trail,
+                });
+                continue;
+            }
+        }
+        tokens.push(Token {
+            raw,
+            lead,
+            word: core.to_lowercase(),
+            trail,
+        });
+    }
+    tokens
+}
+
+/// Whether `tokens[k]` follows on from the token before it, with no
+/// punctuation in between.
+fn continues(tokens: &[Token], k: usize) -> bool {
+    k > 0 && k < tokens.len() && tokens[k].lead.is_empty() && tokens[k - 1].trail.is_empty()
+}
+
+impl Numerals {
+    fn word(&self, word: &str) -> Option<(Kind, u64)> {
+        self.words
+            .iter()
+            .find(|(w, _, _)| *w == word)
+            .map(|&(_, kind, value)| (kind, value))
+    }
+
+    fn is_number_word(&self, tokens: &[Token], k: usize) -> bool {
+        continues(tokens, k) && self.word(&tokens[k].word).is_some()
+    }
+
+    /// The number spoken from `tokens[start]` on, and the index after it.
+    fn number(&self, tokens: &[Token], start: usize) -> Option<(u64, usize)> {
+        let (mut total, mut current) = (0, 0);
+        let mut last: Option<Kind> = None;
+        let mut last_scale = u64::MAX;
+        let (mut k, mut end) = (start, start);
+        while k < tokens.len() && (k == start || continues(tokens, k)) {
+            let word = tokens[k].word.as_str();
+            if k == start && self.article == Some(word) {
+                let next = self
+                    .is_number_word(tokens, k + 1)
+                    .then(|| self.word(&tokens[k + 1].word))
+                    .flatten();
+                if !matches!(next, Some((Hundred | Scale, _))) {
+                    break;
+                }
+                current = 1;
+                last = Some(Unit);
+                k += 1;
+                continue;
+            }
+            if word == self.connector
+                && last.is_some_and(|kind| self.connects_after.contains(&kind))
+                && self.is_number_word(tokens, k + 1)
+            {
+                k += 1;
+                continue;
+            }
+            let Some((kind, value)) = self.word(word) else {
+                break;
+            };
+            let fits = match kind {
+                Unit => matches!(last, None | Some(Tens | Hundreds | Hundred | Scale)),
+                Teen | Tens => matches!(last, None | Some(Hundreds | Hundred | Scale)),
+                Hundreds => matches!(last, None | Some(Scale)),
+                Hundred => matches!(last, Some(Unit | Teen)) && (1..100).contains(&current),
+                Scale => {
+                    value < last_scale
+                        && (current > 0 || (self.bare_thousand && value == 1_000 && last.is_none()))
+                }
+            };
+            if !fits {
+                break;
+            }
+            match kind {
+                Hundred => current *= value,
+                Scale => {
+                    total += current.max(1) * value;
+                    current = 0;
+                    last_scale = value;
+                }
+                _ => current += value,
+            }
+            last = Some(kind);
+            k += 1;
+            end = k;
+        }
+        (end > start).then_some((total + current, end))
+    }
+
+    /// A number from 10 to 99 in one or two words.
+    fn two_digits(&self, tokens: &[Token], k: usize) -> Option<(u64, usize)> {
+        match self.word(&tokens.get(k)?.word)? {
+            (Teen, value) => Some((value, k + 1)),
+            (Tens, value) => match self
+                .is_number_word(tokens, k + 1)
+                .then(|| self.word(&tokens[k + 1].word))
+                .flatten()
+            {
+                Some((Unit, unit)) if unit > 0 => Some((value + unit, k + 2)),
+                _ => Some((value, k + 1)),
+            },
+            _ => None,
+        }
+    }
+
+    /// "in nineteen ninety nine" as 1999.
+    fn year(&self, tokens: &[Token], start: usize) -> Option<(String, usize)> {
+        if !self.paired_years || !continues(tokens, start) {
+            return None;
+        }
+        let before = tokens[start - 1].word.as_str();
+        if !self.year_after.contains(&before) && !self.months.contains(&before) {
+            return None;
+        }
+        let (century, middle) = self.two_digits(tokens, start)?;
+        if !(11..=20).contains(&century) || !continues(tokens, middle) {
+            return None;
+        }
+        let (year, end) = self.two_digits(tokens, middle)?;
+        if self.is_number_word(tokens, end) {
+            return None;
+        }
+        Some(((century * 100 + year).to_string(), end))
+    }
+
+    /// "at twelve thirty" or "eleven fifteen pm" as 12:30 and 11:15.
+    fn time(&self, tokens: &[Token], start: usize) -> Option<(String, usize)> {
+        let (Unit | Teen, hour) = self.word(&tokens[start].word)? else {
+            return None;
+        };
+        if !(1..=12).contains(&hour) || !continues(tokens, start + 1) {
+            return None;
+        }
+        let (minutes, end) = self.two_digits(tokens, start + 1)?;
+        if !(10..=59).contains(&minutes) || self.is_number_word(tokens, end) {
+            return None;
+        }
+        let at =
+            continues(tokens, start) && self.time_after.contains(&tokens[start - 1].word.as_str());
+        let clock = continues(tokens, end) && self.clock_words.contains(&tokens[end].word.as_str());
+        (at || clock).then(|| (format!("{hour}:{minutes:02}"), end))
+    }
+
+    fn ordinal(&self, tokens: &[Token], k: usize) -> Option<(u64, usize)> {
+        if !continues(tokens, k) {
+            return None;
+        }
+        let find = |word: &str| {
+            self.ordinals
+                .iter()
+                .find(|(w, _)| *w == word)
+                .map(|&(_, n)| n)
+        };
+        if let Some(n) = find(&tokens[k].word) {
+            return Some((n, k + 1));
+        }
+        let (Tens, tens) = self.word(&tokens[k].word)? else {
+            return None;
+        };
+        let unit = continues(tokens, k + 1)
+            .then(|| find(&tokens[k + 1].word))
+            .flatten()
+            .filter(|&n| n < 10)?;
+        Some((tens + unit, k + 2))
+    }
+
+    /// "march twenty first" as "March 21".
+    fn date(&self, tokens: &[Token], start: usize) -> Option<(String, usize)> {
+        let month = self.months.iter().find(|m| **m == tokens[start].word)?;
+        if self.word_months.contains(month) && !tokens[start].raw.starts_with(char::is_uppercase) {
+            return None;
+        }
+        let (day, end) = self.ordinal(tokens, start + 1)?;
+        if !(1..=31).contains(&day) {
+            return None;
+        }
+        let mut chars = month.chars();
+        let first = chars.next()?.to_uppercase();
+        Some((format!("{first}{} {day}", chars.as_str()), end))
+    }
+
+    /// Index after the words of `phrase` at `tokens[k]`, if they are there.
+    fn phrase(&self, tokens: &[Token], k: usize, phrase: &[&str]) -> Option<usize> {
+        phrase
+            .iter()
+            .enumerate()
+            .all(|(i, word)| continues(tokens, k + i) && tokens[k + i].word == *word)
+            .then_some(k + phrase.len())
+    }
+
+    fn digits(&self, value: u64) -> String {
+        let plain = value.to_string();
+        if value < 10_000 {
+            return plain;
+        }
+        let mut grouped = String::with_capacity(plain.len() + plain.len() / 3);
+        for (i, digit) in plain.chars().enumerate() {
+            if i > 0 && (plain.len() - i).is_multiple_of(3) {
+                grouped.push(self.group_separator);
+            }
+            grouped.push(digit);
+        }
+        grouped
+    }
+
+    /// A number with any decimals, percent sign or currency after it.
+    fn amount(&self, tokens: &[Token], start: usize) -> Option<(String, usize)> {
+        let (value, mut end) = self.number(tokens, start)?;
+        let mut written = self.digits(value);
+        let mut decimal = false;
+        if continues(tokens, end) && tokens[end].word == self.decimal_word {
+            let mut fraction = String::new();
+            let mut k = end + 1;
+            while let Some((Unit, digit)) = self
+                .is_number_word(tokens, k)
+                .then(|| self.word(&tokens[k].word))
+                .flatten()
+            {
+                fraction.push_str(&digit.to_string());
+                k += 1;
+            }
+            if !fraction.is_empty() {
+                written = format!("{written}{}{fraction}", self.decimal_separator);
+                decimal = true;
+                end = k;
+            }
+        }
+        if let Some(after) = self
+            .percent
+            .iter()
+            .find_map(|phrase| self.phrase(tokens, end, phrase))
+        {
+            return Some((format!("{written}{}", self.percent_sign), after));
+        }
+        if let Some(&(_, symbol)) = continues(tokens, end)
+            .then(|| self.currencies.iter().find(|(w, _)| *w == tokens[end].word))
+            .flatten()
+        {
+            let mut after = end + 1;
+            if !decimal
+                && continues(tokens, after)
+                && tokens[after].word == self.connector
+                && continues(tokens, after + 1)
+            {
+                if let Some((cents, k)) = self.number(tokens, after + 1) {
+                    if cents < 100
+                        && continues(tokens, k)
+                        && self.minor_units.contains(&tokens[k].word.as_str())
+                    {
+                        written = format!("{written}{}{cents:02}", self.decimal_separator);
+                        after = k + 1;
+                    }
+                }
+            }
+            return Some((format!("{symbol}{written}"), after));
+        }
+        // Numbers under ten read better as words, and "one" is often a pronoun.
+        (end - start > 1 || value >= 10 || decimal).then_some((written, end))
+    }
+}
+
+/// Inverse text normalization: writes numbers, amounts, percentages and
+/// dates spoken in a transcript as digits and symbols, for cleaner captions
+/// and translations. "twenty five dollars" becomes `$25`.
+///
+/// Rules exist for English, Spanish and Portuguese; other languages pass
+/// through unchanged. Numbers under ten standing alone stay words. English
+/// also writes years spoken in pairs ("nineteen ninety nine") and dates
+/// spoken as a month and an ordinal ("march twenty first", `March 21`).
+pub struct InverseNormalize {
+    numerals: Option<&'static Numerals>,
+}
+
+impl InverseNormalize {
+    /// `language` is the language of the transcripts, e.g. `en`.
+    pub fn new(language: &str) -> Self {
+        Self {
+            numerals: numerals(language),
+        }
+    }
+
+    /// Whether there are rules for `language`.
+    pub fn supports(language: &str) -> bool {
+        numerals(language).is_some()
+    }
+}
+
+impl TextTransform for InverseNormalize {
+    fn name(&self) -> &'static str {
+        "inverse_normalize"
+    }
+
+    fn apply(&self, text: &str, _lang: &TargetLang) -> String {
+        let Some(numerals) = self.numerals else {
+            return text.to_owned();
+        };
+        let tokens = tokenize(text, numerals);
+        let mut out = Vec::with_capacity(tokens.len());
+        let mut i = 0;
+        while i < tokens.len() {
+            let written = numerals
+                .date(&tokens, i)
+                .or_else(|| numerals.time(&tokens, i))
+                .or_else(|| numerals.year(&tokens, i))
+                .or_else(|| numerals.amount(&tokens, i));
+            match written {
+                Some((written, end)) => {
+                    out.push(format!(
+                        "{}{written}{}",
+                        tokens[i].lead,
+                        tokens[end - 1].trail
+                    ));
+                    i = end;
+                }
+                None => {
+                    out.push(tokens[i].raw.to_owned());
+                    i += 1;
+                }
+            }
+        }
+        out.join(" ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn itn(language: &str, text: &str) -> String {
+        InverseNormalize::new(language).apply(text, &TargetLang::new("DE").unwrap())
+    }
+
+    #[test]
+    fn writes_english_numbers_amounts_and_dates() {
+        assert_eq!(itn("en", "that's twenty five dollars"), "that's $25");
+        assert_eq!(
+            itn("en", "it costs four dollars and fifty cents."),
+            "it costs $4.50."
+        );
+        assert_eq!(itn("en", "about two point five percent"), "about 2.5%");
+        assert_eq!(
+            itn("en", "we hit one hundred and twenty thousand followers"),
+            "we hit 120,000 followers"
+        );
+        assert_eq!(itn("en", "a hundred subs"), "100 subs");
+        assert_eq!(itn("en", "Twenty-five, thirty"), "25, 30");
+        assert_eq!(
+            itn("en", "the patch is on march twenty first"),
+            "the patch is on March 21"
+        );
+        assert_eq!(itn("en", "back in nineteen ninety nine"), "back in 1999");
+        assert_eq!(itn("en", "since May first"), "since May 1");
+    }
+
+    #[test]
+    fn years_need_year_context() {
+        assert_eq!(itn("en", "see you at twelve thirty"), "see you at 12:30");
+        assert_eq!(
+            itn("en", "we had fifteen twenty people"),
+            "we had 15 20 people"
+        );
+        assert_eq!(itn("en", "in eleven fifteen pm"), "in 11:15 pm");
+        assert_eq!(itn("en", "since twenty twenty"), "since 2020");
+        assert_eq!(
+            itn("en", "you may first want to check"),
+            "you may first want to check"
+        );
+    }
+
+    #[test]
+    fn leaves_small_numbers_and_other_words() {
+        assert_eq!(itn("en", "one two three go"), "one two three go");
+        assert_eq!(itn("en", "five people and a dog"), "five people and a dog");
+        assert_eq!(itn("en", "(twelve) apples"), "(12) apples");
+        assert_eq!(itn("de", "fünfundzwanzig euro"), "fünfundzwanzig euro");
+    }
+
+    #[test]
+    fn writes_spanish_and_portuguese_numbers() {
+        assert_eq!(itn("es", "el treinta y cinco por ciento"), "el 35 %");
+        assert_eq!(itn("es-MX", "son veinticinco dólares"), "son 25 dólares");
+        assert_eq!(itn("es", "cien mil"), "100.000");
+        assert_eq!(itn("es", "dos mil veinticuatro"), "2024");
+        assert_eq!(itn("pt", "mil e quinhentos reais"), "1500 reais");
+        assert_eq!(itn("pt-BR", "vinte e cinco por cento"), "25%");
+        assert_eq!(itn("pt", "três vírgula cinco"), "3,5");
+    }
+}
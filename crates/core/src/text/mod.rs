@@ -0,0 +1,313 @@
+//! Text middleware
+//!
+//! A [`TextChain`] runs a list of [`TextTransform`]s over a piece of text. The
+//! pipeline applies one chain to transcripts before they are translated,
+//! another to translations before they are shown and spoken, and a last one to
+//! the text handed to TTS only, so recurring recognition or translation
+//! mistakes can be fixed from configuration ([`TextRule`]) instead of in code.
+//!
+//! [`TwitchTokens`] recognises emotes, `@mentions`, `!commands` and
+//! do-not-translate terms, which translators and TTS voices otherwise mangle.
+//! [`romanize`] spells captions in non-Latin scripts with Latin letters for
+//! the text outputs that ask for it. [`RestorePunctuation`] cases and
+//! punctuates transcripts from recognizers that leave that out, and
+//! [`InverseNormalize`] writes the numbers in them as digits.
+//! [`split_non_speech`] takes the sounds the recognizer annotates, such as
+//! `[Music]`, out of a transcript.
+
+mod itn;
+mod nonspeech;
+mod numbers;
+mod punctuation;
+mod romanize;
+mod tokens;
+
+use crate::config::{TargetLang, TextConfig};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::Arc;
+
+pub use itn::InverseNormalize;
+pub use nonspeech::{lyrics, split_non_speech, MusicDetector, NonSpeech};
+pub use numbers::LocalizeNumbers;
+pub use punctuation::RestorePunctuation;
+pub use romanize::romanize;
+pub use tokens::{Protected, SpeakTokens, TokenKind, TokenSpeech, TwitchTokens};
+
+#[derive(thiserror::Error, Debug)]
+pub enum TextError {
+    #[error("invalid pattern {pattern:?}: {source}")]
+    InvalidPattern {
+        pattern: String,
+        #[source]
+        source: regex::Error,
+    },
+}
+
+/// One step of a [`TextChain`].
+pub trait TextTransform: Send + Sync {
+    /// Short name used in logs.
+    fn name(&self) -> &'static str;
+
+    /// `lang` is the language the session currently translates into.
+    fn apply(&self, text: &str, lang: &TargetLang) -> String;
+}
+
+/// Ordered list of transforms; clones share the same transforms.
+#[derive(Clone, Default)]
+pub struct TextChain {
+    transforms: Vec<Arc<dyn TextTransform>>,
+}
+
+impl TextChain {
+    /// Builds the chain described by `rules`, in order; `tokens` is what
+    /// [`TextRule::TwitchTokens`] recognises.
+    pub fn from_rules(rules: &[TextRule], tokens: &TwitchTokens) -> Result<Self, TextError> {
+        rules.iter().try_fold(Self::default(), |chain, rule| {
+            Ok(match rule {
+                TextRule::Replace {
+                    pattern,
+                    replacement,
+                } => chain.with(RegexReplace::new(pattern, replacement.clone())?),
+                TextRule::StripEmoji => chain.with(StripEmoji),
+                TextRule::NormalizeCaps => chain.with(NormalizeCaps),
+                TextRule::LocalizeNumbers {
+                    fahrenheit_to_celsius,
+                } => chain.with(LocalizeNumbers::new(*fahrenheit_to_celsius)),
+                TextRule::TwitchTokens { speak } => chain.with(SpeakTokens {
+                    tokens: tokens.clone(),
+                    speech: *speak,
+                }),
+            })
+        })
+    }
+
+    /// Appends `transform` to the end of the chain.
+    pub fn with(mut self, transform: impl TextTransform + 'static) -> Self {
+        self.transforms.push(Arc::new(transform));
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.transforms.is_empty()
+    }
+
+    pub fn apply(&self, text: String, lang: &TargetLang) -> String {
+        self.transforms
+            .iter()
+            .fold(text, |text, transform| transform.apply(&text, lang))
+    }
+}
+
+/// The chains a session runs, built together from one [`TextConfig`].
+#[derive(Clone, Debug, Default)]
+pub struct TextChains {
+    pub pre_translate: TextChain,
+    pub post_translate: TextChain,
+    pub pre_tts: TextChain,
+}
+
+impl TextChains {
+    pub fn from_config(text: &TextConfig) -> Result<Self, TextError> {
+        let tokens = text.twitch_tokens();
+        Ok(Self {
+            pre_translate: TextChain::from_rules(&text.pre_translate, &tokens)?,
+            post_translate: TextChain::from_rules(&text.post_translate, &tokens)?,
+            pre_tts: TextChain::from_rules(&text.pre_tts, &tokens)?,
+        })
+    }
+}
+
+impl fmt::Debug for TextChain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.transforms.iter().map(|t| t.name()))
+            .finish()
+    }
+}
+
+/// Serializable description of a built-in transform.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TextRule {
+    /// Replaces every match of a regular expression; `replacement` may refer
+    /// to capture groups as `$1` or `$name`.
+    Replace {
+        pattern: String,
+        replacement: String,
+    },
+    StripEmoji,
+    NormalizeCaps,
+    /// See [`LocalizeNumbers`].
+    LocalizeNumbers {
+        #[serde(default)]
+        fahrenheit_to_celsius: bool,
+    },
+    /// Skips or spells emotes, mentions and commands; meant for `pre_tts`.
+    TwitchTokens {
+        #[serde(default)]
+        speak: TokenSpeech,
+    },
+}
+
+/// Regular expression replacement.
+pub struct RegexReplace {
+    pattern: regex::Regex,
+    replacement: String,
+}
+
+impl RegexReplace {
+    pub fn new(pattern: &str, replacement: String) -> Result<Self, TextError> {
+        let compiled = regex::Regex::new(pattern).map_err(|source| TextError::InvalidPattern {
+            pattern: pattern.to_owned(),
+            source,
+        })?;
+        Ok(Self {
+            pattern: compiled,
+            replacement,
+        })
+    }
+}
+
+impl TextTransform for RegexReplace {
+    fn name(&self) -> &'static str {
+        "replace"
+    }
+
+    fn apply(&self, text: &str, _lang: &TargetLang) -> String {
+        self.pattern
+            .replace_all(text, self.replacement.as_str())
+            .into_owned()
+    }
+}
+
+/// Removes emoji (and the joiners and selectors that combine them), which
+/// TTS voices otherwise read out or stumble over.
+pub struct StripEmoji;
+
+impl StripEmoji {
+    fn is_emoji(c: char) -> bool {
+        matches!(
+            u32::from(c),
+            0x1F000..=0x1FAFF | 0x2600..=0x27BF | 0x2B00..=0x2BFF | 0xFE00..=0xFE0F | 0x200D
+        )
+    }
+}
+
+impl TextTransform for StripEmoji {
+    fn name(&self) -> &'static str {
+        "strip_emoji"
+    }
+
+    fn apply(&self, text: &str, _lang: &TargetLang) -> String {
+        if !text.chars().any(Self::is_emoji) {
+            return text.to_owned();
+        }
+        let stripped: String = text.chars().filter(|c| !Self::is_emoji(*c)).collect();
+        stripped.split_whitespace().collect::<Vec<_>>().join(" ")
+    }
+}
+
+/// Rewrites text that is mostly upper case (shouting, or a caption track in
+/// all caps) into sentence case, which translates far better.
+pub struct NormalizeCaps;
+
+impl NormalizeCaps {
+    /// Fewer letters than this are left alone, so "OK" or "GG" survive.
+    const MIN_LETTERS: usize = 4;
+}
+
+impl TextTransform for NormalizeCaps {
+    fn name(&self) -> &'static str {
+        "normalize_caps"
+    }
+
+    fn apply(&self, text: &str, _lang: &TargetLang) -> String {
+        let letters = text.chars().filter(|c| c.is_alphabetic()).count();
+        let upper = text.chars().filter(|c| c.is_uppercase()).count();
+        if letters < Self::MIN_LETTERS || upper * 10 < letters * 8 {
+            return text.to_owned();
+        }
+
+        let mut out = String::with_capacity(text.len());
+        let mut sentence_start = true;
+        for c in text.chars() {
+            if c.is_alphabetic() {
+                if sentence_start {
+                    out.extend(c.to_uppercase());
+                } else {
+                    out.extend(c.to_lowercase());
+                }
+                sentence_start = false;
+            } else {
+                out.push(c);
+                if matches!(c, '.' | '!' | '?') {
+                    sentence_start = true;
+                }
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pt() -> TargetLang {
+        TargetLang::new("pt-BR").unwrap()
+    }
+
+    #[test]
+    fn rules_apply_in_order() {
+        let rules: Vec<TextRule> = serde_json::from_str(
+            r#"[
+                {"type": "normalize_caps"},
+                {"type": "replace", "pattern": "(?i)\\bpog\\b", "replacement": "amazing"},
+                {"type": "strip_emoji"}
+            ]"#,
+        )
+        .unwrap();
+        let chain = TextChain::from_rules(&rules, &TwitchTokens::new()).unwrap();
+        assert_eq!(
+            format!("{chain:?}"),
+            r#"["normalize_caps", "replace", "strip_emoji"]"#
+        );
+        assert_eq!(
+            chain.apply("THAT WAS POG 🎉🔥 WOW!".to_owned(), &pt()),
+            "That was amazing wow!"
+        );
+    }
+
+    #[test]
+    fn replace_supports_capture_groups() {
+        let chain =
+            TextChain::default().with(RegexReplace::new(r"(\d+) ?k\b", "$1 000".into()).unwrap());
+        assert_eq!(
+            chain.apply("we hit 10k subs".to_owned(), &pt()),
+            "we hit 10 000 subs"
+        );
+    }
+
+    #[test]
+    fn invalid_pattern_is_reported() {
+        let rules = [TextRule::Replace {
+            pattern: "(".to_owned(),
+            replacement: String::new(),
+        }];
+        assert!(matches!(
+            TextChain::from_rules(&rules, &TwitchTokens::new()),
+            Err(TextError::InvalidPattern { .. })
+        ));
+    }
+
+    #[test]
+    fn normalize_caps_leaves_mixed_case_and_short_text() {
+        assert_eq!(NormalizeCaps.apply("GG everyone", &pt()), "GG everyone");
+        assert_eq!(NormalizeCaps.apply("OK", &pt()), "OK");
+        assert_eq!(
+            NormalizeCaps.apply("NO WAY. REALLY?", &pt()),
+            "No way. Really?"
+        );
+    }
+}
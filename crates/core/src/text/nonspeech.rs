@@ -0,0 +1,294 @@
+//! Sounds the recognizer annotates instead of transcribing
+//!
+//! Whisper writes what it hears besides speech as annotations such as
+//! `[Music]`, `(laughs)`, `[APPLAUSE]`, `♪ ... ♪` or `[BLANK_AUDIO]`.
+//! [`split_non_speech`] takes them out of a transcript so they are not
+//! translated or spoken, and names the sound for captions. [`MusicDetector`]
+//! tells when a song has been playing for a while, and [`lyrics`] keeps the
+//! words sung in it.
+
+use serde::{Deserialize, Serialize};
+
+/// A sound that is not speech, shown in captions as e.g. `[music]`
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum NonSpeech {
+    Music,
+    Laughter,
+    Applause,
+    Silence,
+}
+
+impl NonSpeech {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Music => "music",
+            Self::Laughter => "laughter",
+            Self::Applause => "applause",
+            Self::Silence => "silence",
+        }
+    }
+
+    /// The caption for the sound, e.g. `[music]`.
+    pub fn label(self) -> String {
+        format!("[{}]", self.as_str())
+    }
+
+    /// The sound an annotation names, by whole words in the languages
+    /// Whisper annotates in most, so a name like `(Marisa)` is not `risa`.
+    fn classify(annotation: &str) -> Option<Self> {
+        const KEYWORDS: &[(NonSpeech, &[&str])] = &[
+            (
+                NonSpeech::Music,
+                &[
+                    "music", "musical", "singing", "sings", "song", "songs", "música", "musique",
+                    "musik",
+                ],
+            ),
+            (
+                NonSpeech::Laughter,
+                &[
+                    "laugh",
+                    "laughs",
+                    "laughing",
+                    "laughter",
+                    "chuckle",
+                    "chuckles",
+                    "chuckling",
+                    "giggle",
+                    "giggles",
+                    "giggling",
+                    "risa",
+                    "risas",
+                    "rire",
+                    "rires",
+                    "lachen",
+                    "lacht",
+                    "gelächter",
+                ],
+            ),
+            (
+                NonSpeech::Applause,
+                &[
+                    "applause",
+                    "applauding",
+                    "applauds",
+                    "clapping",
+                    "claps",
+                    "aplauso",
+                    "aplausos",
+                    "applaudissements",
+                    "cheering",
+                    "beifall",
+                    "applaus",
+                ],
+            ),
+            (
+                NonSpeech::Silence,
+                &["blank_audio", "silence", "silencio", "no speech"],
+            ),
+        ];
+        if annotation.contains('♪') {
+            return Some(Self::Music);
+        }
+        let annotation = annotation.to_lowercase();
+        let words: Vec<&str> = annotation
+            .split(|c: char| !c.is_alphanumeric() && c != '_')
+            .filter(|w| !w.is_empty())
+            .collect();
+        let names = |keyword: &&str| {
+            let keyword: Vec<&str> = keyword.split(' ').collect();
+            words.windows(keyword.len()).any(|run| run == keyword)
+        };
+        KEYWORDS
+            .iter()
+            .find(|(_, keywords)| keywords.iter().any(names))
+            .map(|(sound, _)| *sound)
+    }
+}
+
+/// Splits `text` into the speech in it, with annotations removed, and the
+/// first sound they name. Annotations are spans in `[]` and lyrics between
+/// `♪` marks; spans in `()` or `**` only count when they name a sound, such
+/// as `(laughs)`, as others are usually spoken asides or emphasis.
+pub fn split_non_speech(text: &str) -> (String, Option<NonSpeech>) {
+    split(text, false)
+}
+
+/// The words in `text`, lyrics between `♪` marks included, with other
+/// annotations removed.
+pub fn lyrics(text: &str) -> String {
+    split(text, true).0
+}
+
+fn split(text: &str, keep_lyrics: bool) -> (String, Option<NonSpeech>) {
+    let mut speech = String::with_capacity(text.len());
+    let mut sound = None;
+    let mut rest = text;
+    while let Some(start) = rest.find(['[', '(', '*', '♪']) {
+        let open = rest[start..].chars().next().unwrap_or_default();
+        let close = match open {
+            '[' => ']',
+            '(' => ')',
+            c => c,
+        };
+        let inner_start = start + open.len_utf8();
+        let (inner, after) = match rest[inner_start..].find(close) {
+            Some(end) => (
+                &rest[inner_start..inner_start + end],
+                inner_start + end + close.len_utf8(),
+            ),
+            // An unclosed song runs to the end, as when a chunk cuts it off.
+            None if open == '♪' => (&rest[inner_start..], rest.len()),
+            // A lone bracket or asterisk is just part of what was said.
+            None => {
+                speech.push_str(&rest[..inner_start]);
+                rest = &rest[inner_start..];
+                continue;
+            }
+        };
+        let named = if open == '♪' {
+            Some(NonSpeech::Music)
+        } else {
+            NonSpeech::classify(inner)
+        };
+        if named.is_none() && matches!(open, '(' | '*') {
+            speech.push_str(&rest[..after]);
+            rest = &rest[after..];
+            continue;
+        }
+        speech.push_str(&rest[..start]);
+        speech.push(' ');
+        if open == '♪' && keep_lyrics {
+            speech.push_str(inner);
+            speech.push(' ');
+        }
+        sound = sound.or(named);
+        rest = &rest[after..];
+    }
+    speech.push_str(rest);
+    let speech = speech.split_whitespace().collect::<Vec<_>>().join(" ");
+    (speech, sound)
+}
+
+/// Chunks in a row with music before it counts as sustained, and without
+/// before it counts as over.
+const MUSIC_CHUNKS: usize = 2;
+
+/// Follows whether music with vocals is playing, from the annotations in
+/// consecutive transcripts. A song is only taken as started after
+/// [`MUSIC_CHUNKS`] chunks with music, so a jingle or a hummed line does not
+/// count, and as over after as many without, as Whisper does not mark every
+/// sung line.
+#[derive(Debug, Default)]
+pub struct MusicDetector {
+    playing: bool,
+    streak: usize,
+}
+
+impl MusicDetector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Takes the next transcript as the recognizer wrote it and returns
+    /// whether music is playing.
+    pub fn observe(&mut self, text: &str) -> bool {
+        let music = text.contains('♪') || split(text, false).1 == Some(NonSpeech::Music);
+        if music == self.playing {
+            self.streak = 0;
+        } else {
+            self.streak += 1;
+            if self.streak >= MUSIC_CHUNKS {
+                self.playing = music;
+                self.streak = 0;
+            }
+        }
+        self.playing
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_annotated_sounds() {
+        assert_eq!(
+            split_non_speech(" [Music]"),
+            (String::new(), Some(NonSpeech::Music))
+        );
+        assert_eq!(
+            split_non_speech("♪ never gonna give you up ♪"),
+            (String::new(), Some(NonSpeech::Music))
+        );
+        assert_eq!(
+            split_non_speech("(laughs) that was close"),
+            ("that was close".to_owned(), Some(NonSpeech::Laughter))
+        );
+        assert_eq!(
+            split_non_speech("thank you [APPLAUSE] so much"),
+            ("thank you so much".to_owned(), Some(NonSpeech::Applause))
+        );
+        assert_eq!(
+            split_non_speech("[BLANK_AUDIO]"),
+            (String::new(), Some(NonSpeech::Silence))
+        );
+        assert_eq!(
+            split_non_speech("(Laughing) no way"),
+            ("no way".to_owned(), Some(NonSpeech::Laughter))
+        );
+        assert_eq!(
+            split_non_speech("[No speech detected]"),
+            (String::new(), Some(NonSpeech::Silence))
+        );
+        assert_eq!(NonSpeech::Laughter.label(), "[laughter]");
+    }
+
+    #[test]
+    fn keeps_plain_speech() {
+        assert_eq!(
+            split_non_speech("let's go chat"),
+            ("let's go chat".to_owned(), None)
+        );
+        assert_eq!(
+            split_non_speech("it was (honestly) *so* good"),
+            ("it was (honestly) *so* good".to_owned(), None)
+        );
+        assert_eq!(split_non_speech("[sighs] okay"), ("okay".to_owned(), None));
+        assert_eq!(
+            split_non_speech("shout out to (Marisa) for the sub"),
+            ("shout out to (Marisa) for the sub".to_owned(), None)
+        );
+        assert_eq!(
+            split_non_speech("it's 5 * 3 (or was it [four"),
+            ("it's 5 * 3 (or was it [four".to_owned(), None)
+        );
+    }
+
+    #[test]
+    fn keeps_lyrics_when_asked() {
+        assert_eq!(
+            lyrics("[Music] ♪ never gonna give you up ♪ (laughs)"),
+            "never gonna give you up"
+        );
+        assert_eq!(lyrics("♪ never gonna"), "never gonna");
+    }
+
+    #[test]
+    fn waits_for_sustained_music() {
+        let mut music = MusicDetector::new();
+        assert!(!music.observe("♪ la la ♪"));
+        assert!(!music.observe("hello chat"));
+        assert!(!music.observe("♪ never gonna give you up ♪"));
+        assert!(music.observe("[Music]"));
+        // A line Whisper did not mark is still part of the song.
+        assert!(music.observe("never gonna let you down"));
+        assert!(!music.observe("okay that was the intro"));
+        assert!(!music.is_playing());
+    }
+}
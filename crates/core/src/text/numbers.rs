@@ -0,0 +1,283 @@
+use super::TextTransform;
+use crate::config::TargetLang;
+use regex::{Captures, Regex};
+
+/// Digits with optional `.`/`,` separators, e.g. `5`, `5.50` or `1,000`.
+const AMOUNT: &str = r"\d(?:[\d.,]*\d)?";
+
+/// Spoken-form conventions of a target language.
+struct Locale {
+    comma_decimal: bool,
+    /// Singular and plural currency names.
+    dollar: [&'static str; 2],
+    euro: [&'static str; 2],
+    pound: [&'static str; 2],
+}
+
+const EN: Locale = Locale {
+    comma_decimal: false,
+    dollar: ["dollar", "dollars"],
+    euro: ["euro", "euros"],
+    pound: ["pound", "pounds"],
+};
+const PT: Locale = Locale {
+    comma_decimal: true,
+    dollar: ["dólar", "dólares"],
+    euro: ["euro", "euros"],
+    pound: ["libra", "libras"],
+};
+const ES: Locale = Locale {
+    comma_decimal: true,
+    dollar: ["dólar", "dólares"],
+    euro: ["euro", "euros"],
+    pound: ["libra", "libras"],
+};
+const FR: Locale = Locale {
+    comma_decimal: true,
+    dollar: ["dollar", "dollars"],
+    euro: ["euro", "euros"],
+    pound: ["livre", "livres"],
+};
+const DE: Locale = Locale {
+    comma_decimal: true,
+    dollar: ["Dollar", "Dollar"],
+    euro: ["Euro", "Euro"],
+    pound: ["Pfund", "Pfund"],
+};
+const IT: Locale = Locale {
+    comma_decimal: true,
+    dollar: ["dollaro", "dollari"],
+    euro: ["euro", "euro"],
+    pound: ["sterlina", "sterline"],
+};
+const NL: Locale = Locale {
+    comma_decimal: true,
+    dollar: ["dollar", "dollar"],
+    euro: ["euro", "euro"],
+    pound: ["pond", "pond"],
+};
+
+fn locale(lang: &TargetLang) -> Option<&'static Locale> {
+    let primary = lang.as_str().split('-').next()?.to_ascii_lowercase();
+    Some(match primary.as_str() {
+        "en" => &EN,
+        "pt" => &PT,
+        "es" => &ES,
+        "fr" => &FR,
+        "de" => &DE,
+        "it" => &IT,
+        "nl" => &NL,
+        _ => return None,
+    })
+}
+
+/// Rewrites currency amounts, plain numbers and temperatures the way the
+/// target language speaks them, since DeepL tends to leave `$5`, `3.5` or
+/// `75°F` as-is and TTS voices then read them awkwardly.
+///
+/// `$5.50` becomes `5,50 dólares` and `10,000.5` becomes `10000,5` for
+/// Portuguese. Numbers and currency are only rewritten for languages with a
+/// known spoken form (en, pt, es, fr, de, it, nl). Fahrenheit is converted to
+/// Celsius only when enabled.
+pub struct LocalizeNumbers {
+    fahrenheit_to_celsius: bool,
+    currency: Regex,
+    number: Regex,
+    fahrenheit: Regex,
+}
+
+impl LocalizeNumbers {
+    pub fn new(fahrenheit_to_celsius: bool) -> Self {
+        let currency = format!(
+            r"(?:(?P<pre>US\$|[$€£])\s?(?P<pre_amount>{AMOUNT})|(?P<post_amount>{AMOUNT})\s?(?P<post>[$€£]|\b(?:USD|EUR|GBP)\b))"
+        );
+        Self {
+            fahrenheit_to_celsius,
+            currency: Regex::new(&currency).expect("valid currency pattern"),
+            number: Regex::new(&format!(r"\b{AMOUNT}\b")).expect("valid number pattern"),
+            fahrenheit: Regex::new(&format!(r"(?P<value>-?{AMOUNT})\s?°\s?F\b"))
+                .expect("valid temperature pattern"),
+        }
+    }
+
+    fn currency(&self, text: &str, locale: &Locale) -> String {
+        self.currency
+            .replace_all(text, |caps: &Captures| {
+                let (symbol, amount) = match (caps.name("pre"), caps.name("post")) {
+                    (Some(symbol), _) => (symbol.as_str(), &caps["pre_amount"]),
+                    (None, Some(symbol)) => (symbol.as_str(), &caps["post_amount"]),
+                    (None, None) => return caps[0].to_owned(),
+                };
+                let names = match symbol {
+                    "$" | "US$" | "USD" => locale.dollar,
+                    "€" | "EUR" => locale.euro,
+                    _ => locale.pound,
+                };
+                let (whole, fraction) = split_amount(amount);
+                let name = if whole == "1" && fraction.is_none() {
+                    names[0]
+                } else {
+                    names[1]
+                };
+                match fraction {
+                    Some(fraction) => {
+                        let separator = if locale.comma_decimal { ',' } else { '.' };
+                        format!("{whole}{separator}{fraction} {name}")
+                    }
+                    None => format!("{whole} {name}"),
+                }
+            })
+            .into_owned()
+    }
+
+    fn numbers(&self, text: &str, locale: &Locale) -> String {
+        self.number
+            .replace_all(text, |caps: &Captures| {
+                // The tail of something like `1.2.3`
+                let start = caps.get(0).map_or(0, |m| m.start());
+                if text[..start].ends_with(['.', ',']) {
+                    return caps[0].to_owned();
+                }
+                match english_number(&caps[0]) {
+                    Some((whole, Some(fraction))) => {
+                        let separator = if locale.comma_decimal { ',' } else { '.' };
+                        format!("{whole}{separator}{fraction}")
+                    }
+                    Some((whole, None)) => whole,
+                    None => caps[0].to_owned(),
+                }
+            })
+            .into_owned()
+    }
+
+    fn temperature(&self, text: &str) -> String {
+        self.fahrenheit
+            .replace_all(text, |caps: &Captures| {
+                let value = caps["value"].replace(',', ".");
+                match value.parse::<f64>() {
+                    Ok(f) => format!("{} °C", ((f - 32.0) * 5.0 / 9.0).round() as i64),
+                    Err(_) => caps[0].to_owned(),
+                }
+            })
+            .into_owned()
+    }
+}
+
+impl TextTransform for LocalizeNumbers {
+    fn name(&self) -> &'static str {
+        "localize_numbers"
+    }
+
+    fn apply(&self, text: &str, lang: &TargetLang) -> String {
+        let mut text = match locale(lang) {
+            Some(locale) => self.numbers(&self.currency(text, locale), locale),
+            None => text.to_owned(),
+        };
+        if self.fahrenheit_to_celsius {
+            text = self.temperature(&text);
+        }
+        text
+    }
+}
+
+/// Splits `1,000.50`, `1.000,50` or `12` into whole digits and cents. A
+/// final separator followed by one or two digits marks the fraction; any
+/// other separator groups thousands and is dropped.
+fn split_amount(amount: &str) -> (String, Option<String>) {
+    let (whole, fraction) = match amount.rfind(['.', ',']) {
+        Some(i) if (1..=2).contains(&(amount.len() - i - 1)) => {
+            (&amount[..i], Some(amount[i + 1..].to_owned()))
+        }
+        _ => (amount, None),
+    };
+    let whole = whole.chars().filter(char::is_ascii_digit).collect();
+    (whole, fraction)
+}
+
+/// `number` read as written in English, e.g. `10,000.5` or `3.25`, as whole
+/// digits and fraction. Anything else, such as `1.500`, `5,50` or `1.2.3`,
+/// may already follow the target language or not be an amount at all, and
+/// gives `None`.
+fn english_number(number: &str) -> Option<(String, Option<&str>)> {
+    let (whole, fraction) = match number.split_once('.') {
+        Some((whole, fraction)) => (whole, Some(fraction)),
+        None => (number, None),
+    };
+    if fraction.is_some_and(|f| f.contains(['.', ','])) {
+        return None;
+    }
+    let groups: Vec<&str> = whole.split(',').collect();
+    if groups.len() > 1 {
+        if groups[0].len() > 3 || groups[1..].iter().any(|g| g.len() != 3) {
+            return None;
+        }
+    } else if fraction.is_none_or(|f| f.len() == 3) {
+        return None;
+    }
+    Some((groups.concat(), fraction))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn localize(text: &str, lang: &str, fahrenheit_to_celsius: bool) -> String {
+        LocalizeNumbers::new(fahrenheit_to_celsius).apply(text, &TargetLang::new(lang).unwrap())
+    }
+
+    #[test]
+    fn currency_is_spoken_in_the_target_language() {
+        assert_eq!(
+            localize("Custa $5 ou US$1.", "pt-BR", false),
+            "Custa 5 dólares ou 1 dólar."
+        );
+        assert_eq!(
+            localize("Nur 5,50 € heute", "DE", false),
+            "Nur 5,50 Euro heute"
+        );
+        assert_eq!(
+            localize("it was $1,000.25", "en-US", false),
+            "it was 1000.25 dollars"
+        );
+        assert_eq!(
+            localize("£3 y 20 USD", "es", false),
+            "3 libras y 20 dólares"
+        );
+    }
+
+    #[test]
+    fn plain_numbers_use_the_target_separators() {
+        assert_eq!(
+            localize("São 3.5 km e 10,000 pessoas", "pt-BR", false),
+            "São 3,5 km e 10000 pessoas"
+        );
+        assert_eq!(
+            localize("it was 1,234,567.89 views", "en", false),
+            "it was 1234567.89 views"
+        );
+        // Already localized, or not an amount
+        assert_eq!(
+            localize("Es waren 1.500 oder 2,5 Leute, v1.2.3", "de", false),
+            "Es waren 1.500 oder 2,5 Leute, v1.2.3"
+        );
+    }
+
+    #[test]
+    fn unknown_languages_keep_currency() {
+        assert_eq!(localize("$5", "ja", false), "$5");
+        assert_eq!(localize("3.5", "ja", false), "3.5");
+    }
+
+    #[test]
+    fn fahrenheit_converts_only_when_enabled() {
+        assert_eq!(
+            localize("Faz 75°F lá fora", "pt-BR", false),
+            "Faz 75°F lá fora"
+        );
+        assert_eq!(
+            localize("Faz 75°F lá fora", "pt-BR", true),
+            "Faz 24 °C lá fora"
+        );
+        assert_eq!(localize("-4 °F", "ja", true), "-20 °C");
+    }
+}
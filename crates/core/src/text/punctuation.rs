@@ -0,0 +1,187 @@
+use super::TextTransform;
+use crate::config::TargetLang;
+
+/// How a source language writes what a bare transcript leaves out.
+struct Rules {
+    /// First words that make an utterance a question.
+    question_words: &'static [&'static str],
+    /// Words always capitalized, in lower case.
+    capitalized: &'static [&'static str],
+    /// Questions also open with `¿`.
+    inverted_question: bool,
+}
+
+const EN: Rules = Rules {
+    question_words: &[
+        "what", "why", "how", "who", "whom", "whose", "where", "when", "which", "is", "are", "am",
+        "was", "were", "do", "does", "did", "can", "could", "would", "will", "should", "shall",
+        "have", "has", "may", "isn't", "aren't", "don't", "doesn't", "didn't", "can't", "won't",
+    ],
+    capitalized: &["i", "i'm", "i've", "i'll", "i'd"],
+    inverted_question: false,
+};
+const DE: Rules = Rules {
+    question_words: &[
+        "was", "warum", "wieso", "weshalb", "wie", "wer", "wen", "wem", "wo", "woher", "wohin",
+        "wann", "welche", "welcher", "welches", "ist", "sind", "hast", "hat", "habt", "kannst",
+        "bist", "gibt's",
+    ],
+    capitalized: &[],
+    inverted_question: false,
+};
+const ES: Rules = Rules {
+    question_words: &[
+        "qué", "cómo", "quién", "quiénes", "dónde", "cuándo", "cuál", "cuáles", "cuánto",
+        "cuántos", "cuánta", "cuántas",
+    ],
+    capitalized: &[],
+    inverted_question: true,
+};
+const FR: Rules = Rules {
+    question_words: &[
+        "pourquoi", "comment", "qui", "où", "quand", "quel", "quelle", "quels", "quelles",
+        "combien", "est-ce",
+    ],
+    capitalized: &[],
+    inverted_question: false,
+};
+const PT: Rules = Rules {
+    question_words: &[
+        "qual", "quais", "quem", "onde", "quando", "como", "quanto", "quantos", "quanta",
+        "quantas", "cadê",
+    ],
+    capitalized: &[],
+    inverted_question: false,
+};
+const IT: Rules = Rules {
+    question_words: &[
+        "perché", "come", "chi", "dove", "quando", "quale", "quali", "quanto", "quanti", "cosa",
+    ],
+    capitalized: &[],
+    inverted_question: false,
+};
+/// Languages without rules still get sentence case and a full stop.
+const OTHER: Rules = Rules {
+    question_words: &[],
+    capitalized: &[],
+    inverted_question: false,
+};
+
+fn rules(language: &str) -> &'static Rules {
+    let primary = language
+        .split(['-', '_'])
+        .next()
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    match primary.as_str() {
+        "en" => &EN,
+        "de" => &DE,
+        "es" => &ES,
+        "fr" => &FR,
+        "pt" => &PT,
+        "it" => &IT,
+        _ => &OTHER,
+    }
+}
+
+/// Restores casing and end punctuation of transcripts from recognizers that
+/// emit them all lower case and unpunctuated, for the translator and for
+/// captions.
+///
+/// The first letter is capitalized, and the utterance ends with a question
+/// mark when it opens with a question word of the source language, otherwise
+/// a full stop. English `i` becomes `I`. Text with any capital letter is left
+/// alone, as the recognizer already cased it. Punctuation inside an
+/// utterance cannot be recovered by rules, so a run-on stays one sentence.
+pub struct RestorePunctuation {
+    rules: &'static Rules,
+}
+
+impl RestorePunctuation {
+    /// `language` is the language of the transcripts, e.g. `en`; unknown
+    /// codes or `auto` get only the rules common to all languages.
+    pub fn new(language: &str) -> Self {
+        Self {
+            rules: rules(language),
+        }
+    }
+
+    fn is_question(&self, first_word: &str) -> bool {
+        let word = first_word.trim_matches(|c: char| !c.is_alphanumeric() && c != '\'');
+        self.rules.question_words.contains(&word)
+    }
+}
+
+impl TextTransform for RestorePunctuation {
+    fn name(&self) -> &'static str {
+        "restore_punctuation"
+    }
+
+    fn apply(&self, text: &str, _lang: &TargetLang) -> String {
+        if text.chars().any(char::is_uppercase) {
+            return text.to_owned();
+        }
+        let words: Vec<&str> = text.split_whitespace().collect();
+        let Some(first) = words.first() else {
+            return text.to_owned();
+        };
+        let question = self.is_question(first);
+
+        let mut out = String::with_capacity(text.len() + 2);
+        if question && self.rules.inverted_question && !first.starts_with('¿') {
+            out.push('¿');
+        }
+        let mut sentence_start = true;
+        for (i, word) in words.iter().enumerate() {
+            if i > 0 {
+                out.push(' ');
+            }
+            if self.rules.capitalized.contains(word) || sentence_start {
+                let mut chars = word.chars();
+                if let Some(c) = chars.next() {
+                    out.extend(c.to_uppercase());
+                    out.push_str(chars.as_str());
+                }
+            } else {
+                out.push_str(word);
+            }
+            sentence_start = word.ends_with(['.', '!', '?']);
+        }
+        if out.ends_with(char::is_alphanumeric) {
+            out.push(if question { '?' } else { '.' });
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn restore(language: &str, text: &str) -> String {
+        RestorePunctuation::new(language).apply(text, &TargetLang::new("DE").unwrap())
+    }
+
+    #[test]
+    fn cases_and_punctuates_bare_transcripts() {
+        assert_eq!(
+            restore("en", "so i think we go left here"),
+            "So I think we go left here."
+        );
+        assert_eq!(
+            restore("en", "what are you doing chat"),
+            "What are you doing chat?"
+        );
+        assert_eq!(restore("en", "wait. i'm back"), "Wait. I'm back.");
+        assert_eq!(restore("es", "qué pasó aquí"), "¿Qué pasó aquí?");
+        assert_eq!(restore("de-AT", "wo ist er"), "Wo ist er?");
+        assert_eq!(restore("auto", "ça va"), "Ça va.");
+        assert_eq!(restore("en", "nice!"), "Nice!");
+    }
+
+    #[test]
+    fn leaves_cased_or_empty_text_alone() {
+        assert_eq!(restore("en", "Already done"), "Already done");
+        assert_eq!(restore("en", "  "), "  ");
+    }
+}
@@ -0,0 +1,234 @@
+//! Latin spelling of non-Latin captions
+//!
+//! Covers the scripts that can be romanized letter by letter: Cyrillic
+//! (Russian, Ukrainian and Belarusian letters, a simple BGN-style table),
+//! Greek, Korean Hangul (Revised Romanization, without sound changes across
+//! syllables) and Japanese kana (Hepburn). Kanji and Chinese characters need
+//! a dictionary to read and are left as they are.
+
+/// Hepburn for each hiragana from U+3041 to U+3096; katakana use the same
+/// table 0x60 code points lower.
+const KANA: [&str; 86] = [
+    "a", "a", "i", "i", "u", "u", "e", "e", "o", "o", // ぁ..お
+    "ka", "ga", "ki", "gi", "ku", "gu", "ke", "ge", "ko", "go", // か..ご
+    "sa", "za", "shi", "ji", "su", "zu", "se", "ze", "so", "zo", // さ..ぞ
+    "ta", "da", "chi", "ji", "", "tsu", "zu", "te", "de", "to", "do", // た..ど
+    "na", "ni", "nu", "ne", "no", // な..の
+    "ha", "ba", "pa", "hi", "bi", "pi", "fu", "bu", "pu", "he", "be", "pe", "ho", "bo",
+    "po", // は..ぽ
+    "ma", "mi", "mu", "me", "mo", // ま..も
+    "ya", "ya", "yu", "yu", "yo", "yo", // ゃ..よ
+    "ra", "ri", "ru", "re", "ro", // ら..ろ
+    "wa", "wa", "i", "e", "o", "n", "vu", "ka", "ke", // ゎ..ゖ
+];
+
+const HANGUL_INITIALS: [&str; 19] = [
+    "g", "kk", "n", "d", "tt", "r", "m", "b", "pp", "s", "ss", "", "j", "jj", "ch", "k", "t", "p",
+    "h",
+];
+const HANGUL_VOWELS: [&str; 21] = [
+    "a", "ae", "ya", "yae", "eo", "e", "yeo", "ye", "o", "wa", "wae", "oe", "yo", "u", "wo", "we",
+    "wi", "yu", "eu", "ui", "i",
+];
+const HANGUL_FINALS: [&str; 28] = [
+    "", "k", "k", "k", "n", "n", "n", "t", "l", "k", "m", "l", "l", "l", "p", "l", "m", "p", "p",
+    "t", "t", "ng", "t", "t", "k", "t", "p", "t",
+];
+
+/// `text` spelled in Latin letters, or `None` when it holds nothing to
+/// romanize.
+pub fn romanize(text: &str) -> Option<String> {
+    let mut out = String::with_capacity(text.len());
+    let mut changed = false;
+    // The previous character was kana, which small kana attach to.
+    let mut after_kana = false;
+    // A small tsu doubles the next consonant.
+    let mut geminate = false;
+
+    for c in text.chars() {
+        if let Some(hiragana) = to_hiragana(c) {
+            changed = true;
+            let roman = KANA[(hiragana as u32 - 0x3041) as usize];
+            match hiragana {
+                'っ' => geminate = true,
+                'ゃ' | 'ゅ' | 'ょ' if after_kana && out.ends_with('i') => {
+                    out.pop();
+                    // しゃ is "sha", not "shya".
+                    if out.ends_with("sh") || out.ends_with("ch") || out.ends_with('j') {
+                        out.push_str(&roman[1..]);
+                    } else {
+                        out.push_str(roman);
+                    }
+                }
+                'ぁ' | 'ぃ' | 'ぅ' | 'ぇ' | 'ぉ' if after_kana && out.ends_with(is_vowel) => {
+                    // ファ is "fa" and ティ "ti".
+                    out.pop();
+                    out.push_str(roman);
+                }
+                _ => {
+                    if std::mem::take(&mut geminate) {
+                        match roman.chars().next() {
+                            Some('c') => out.push('t'),
+                            Some(first) if !is_vowel(first) && first != 'n' => out.push(first),
+                            _ => {}
+                        }
+                    }
+                    out.push_str(roman);
+                }
+            }
+            after_kana = true;
+            continue;
+        }
+        after_kana = false;
+        geminate = false;
+
+        match c {
+            // The long vowel mark repeats the vowel before it.
+            'ー' => {
+                if let Some(vowel) = out.chars().last().filter(|&v| is_vowel(v)) {
+                    out.push(vowel);
+                }
+                after_kana = true;
+            }
+            '。' => out.push('.'),
+            '、' => out.push(','),
+            '！' => out.push('!'),
+            '？' => out.push('?'),
+            '「' | '」' | '『' | '』' => out.push('"'),
+            '\u{3000}' => out.push(' '),
+            '\u{ac00}'..='\u{d7a3}' => {
+                changed = true;
+                let index = c as usize - 0xac00;
+                out.push_str(HANGUL_INITIALS[index / 588]);
+                out.push_str(HANGUL_VOWELS[index % 588 / 28]);
+                out.push_str(HANGUL_FINALS[index % 28]);
+            }
+            _ => {
+                let lower = c.to_lowercase().next().unwrap_or(c);
+                match letter(lower) {
+                    Some(roman) => {
+                        changed = true;
+                        if c != lower {
+                            let mut chars = roman.chars();
+                            out.extend(chars.next().map(|f| f.to_ascii_uppercase()));
+                            out.push_str(chars.as_str());
+                        } else {
+                            out.push_str(roman);
+                        }
+                    }
+                    None => out.push(c),
+                }
+            }
+        }
+    }
+    changed.then_some(out)
+}
+
+fn to_hiragana(c: char) -> Option<char> {
+    match c {
+        '\u{3041}'..='\u{3096}' => Some(c),
+        '\u{30a1}'..='\u{30f6}' => char::from_u32(c as u32 - 0x60),
+        _ => None,
+    }
+}
+
+fn is_vowel(c: char) -> bool {
+    matches!(c, 'a' | 'e' | 'i' | 'o' | 'u')
+}
+
+/// A lowercase Cyrillic or Greek letter in Latin letters.
+fn letter(c: char) -> Option<&'static str> {
+    Some(match c {
+        'а' => "a",
+        'б' => "b",
+        'в' => "v",
+        'г' | 'ґ' => "g",
+        'д' => "d",
+        'е' => "e",
+        'ё' => "yo",
+        'є' => "ye",
+        'ж' => "zh",
+        'з' => "z",
+        'и' | 'і' => "i",
+        'ї' => "yi",
+        'й' => "y",
+        'к' => "k",
+        'л' => "l",
+        'м' => "m",
+        'н' => "n",
+        'о' => "o",
+        'п' => "p",
+        'р' => "r",
+        'с' => "s",
+        'т' => "t",
+        'у' | 'ў' => "u",
+        'ф' => "f",
+        'х' => "kh",
+        'ц' => "ts",
+        'ч' => "ch",
+        'ш' => "sh",
+        'щ' => "shch",
+        'ъ' | 'ь' => "",
+        'ы' => "y",
+        'э' => "e",
+        'ю' => "yu",
+        'я' => "ya",
+        'α' | 'ά' => "a",
+        'β' => "v",
+        'γ' => "g",
+        'δ' => "d",
+        'ε' | 'έ' => "e",
+        'ζ' => "z",
+        'η' | 'ή' | 'ι' | 'ί' | 'ϊ' | 'ΐ' => "i",
+        'θ' => "th",
+        'κ' => "k",
+        'λ' => "l",
+        'μ' => "m",
+        'ν' => "n",
+        'ξ' => "x",
+        'ο' | 'ό' | 'ω' | 'ώ' => "o",
+        'π' => "p",
+        'ρ' => "r",
+        'σ' | 'ς' => "s",
+        'τ' => "t",
+        'υ' | 'ύ' | 'ϋ' | 'ΰ' => "y",
+        'φ' => "f",
+        'χ' => "ch",
+        'ψ' => "ps",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cyrillic_and_greek_keep_capitals() {
+        assert_eq!(romanize("Привет, мир!").as_deref(), Some("Privet, mir!"));
+        assert_eq!(romanize("Щука и Ёж").as_deref(), Some("Shchuka i Yozh"));
+        assert_eq!(romanize("Καλημέρα").as_deref(), Some("Kalimera"));
+    }
+
+    #[test]
+    fn hangul_is_spelled_per_syllable() {
+        assert_eq!(romanize("안녕하세요").as_deref(), Some("annyeonghaseyo"));
+    }
+
+    #[test]
+    fn kana_combine_into_hepburn() {
+        assert_eq!(romanize("ありがとう。").as_deref(), Some("arigatou."));
+        assert_eq!(romanize("しゃしん").as_deref(), Some("shashin"));
+        assert_eq!(romanize("ちょっと").as_deref(), Some("chotto"));
+        assert_eq!(romanize("マッチ").as_deref(), Some("matchi"));
+        assert_eq!(romanize("コーヒー").as_deref(), Some("koohii"));
+        assert_eq!(romanize("ファン").as_deref(), Some("fan"));
+    }
+
+    #[test]
+    fn latin_text_and_kanji_are_left_alone() {
+        assert_eq!(romanize("hello chat"), None);
+        assert_eq!(romanize("日本"), None);
+        assert_eq!(romanize("日本のゲーム").as_deref(), Some("日本nogeemu"));
+    }
+}
@@ -0,0 +1,422 @@
+use super::TextTransform;
+use crate::config::TargetLang;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::{Arc, LazyLock};
+
+/// Widely used global, BTTV and 7TV emotes. Names that are also ordinary
+/// English words (`Clap`, `Aware`, `EZ`) are left out so they still get
+/// translated; add them per channel with [`TwitchTokens::with_emotes`].
+const COMMON_EMOTES: &[&str] = &[
+    "4Head",
+    "BabyRage",
+    "BibleThump",
+    "COPIUM",
+    "catJAM",
+    "CoolStoryBob",
+    "DansGame",
+    "FailFish",
+    "FeelsBadMan",
+    "FeelsGoodMan",
+    "FeelsStrongMan",
+    "HeyGuys",
+    "ICANT",
+    "Jebaited",
+    "Kappa",
+    "KappaPride",
+    "Keepo",
+    "KEKW",
+    "Kreygasm",
+    "LUL",
+    "LULW",
+    "modCheck",
+    "monkaHmm",
+    "monkaS",
+    "monkaW",
+    "NotLikeThis",
+    "OMEGALUL",
+    "peepoHappy",
+    "peepoSad",
+    "Pepega",
+    "PepeHands",
+    "PepeLaugh",
+    "Pog",
+    "PogChamp",
+    "POGGERS",
+    "PogU",
+    "ResidentSleeper",
+    "Sadge",
+    "SeemsGood",
+    "SMOrc",
+    "TriHard",
+    "VoHiYo",
+    "widepeepoHappy",
+    "WutFace",
+    "xdd",
+];
+
+/// Placeholder markup sent to the translator in place of a protected token.
+static PLACEHOLDER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"<x id="(\d+)"\s*/>"#).expect("valid placeholder pattern"));
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TokenKind {
+    /// A do-not-translate term; always spoken as written.
+    Term,
+    Emote,
+    /// `@username`
+    Mention,
+    /// `!command`
+    Command,
+}
+
+/// How [`SpeakTokens`] voices a Twitch token.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenSpeech {
+    /// Leave it out of the speech.
+    #[default]
+    Skip,
+    /// Say it in a readable form: `KEKW` as letters, `@some_user` as
+    /// "some user", `PogChamp` as "Pog Champ".
+    Spell,
+}
+
+/// Finds emotes, `@mentions`, `!commands` and do-not-translate terms in
+/// chat-style text.
+#[derive(Clone, Debug)]
+pub struct TwitchTokens {
+    /// Whether emotes, mentions and commands are recognised at all.
+    chat: bool,
+    emotes: Arc<HashSet<String>>,
+    word: Regex,
+    terms: Vec<String>,
+    term_pattern: Option<Regex>,
+}
+
+impl Default for TwitchTokens {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TwitchTokens {
+    /// Recognises [`COMMON_EMOTES`] plus mentions and commands.
+    pub fn new() -> Self {
+        Self {
+            chat: true,
+            emotes: Arc::new(COMMON_EMOTES.iter().map(|e| (*e).to_owned()).collect()),
+            word: Regex::new(r"[@!]?\w+").expect("valid word pattern"),
+            terms: Vec::new(),
+            term_pattern: None,
+        }
+    }
+
+    /// Recognises nothing until terms are added with [`Self::with_terms`].
+    pub fn terms_only() -> Self {
+        Self {
+            chat: false,
+            ..Self::new()
+        }
+    }
+
+    /// Also protects `terms` (streamer names, game titles, brands), matched
+    /// as whole words regardless of case. The text keeps the spelling it
+    /// had, so a term's case in the list does not matter.
+    pub fn with_terms(mut self, terms: impl IntoIterator<Item = String>) -> Self {
+        self.terms.extend(
+            terms
+                .into_iter()
+                .map(|t| t.trim().to_owned())
+                .filter(|t| !t.is_empty()),
+        );
+        if self.terms.is_empty() {
+            return self;
+        }
+        // Longest first, so "Elden Ring DLC" wins over "Elden Ring".
+        self.terms.sort_by_key(|t| std::cmp::Reverse(t.len()));
+        self.terms.dedup();
+        let alternatives: Vec<String> = self.terms.iter().map(|t| regex::escape(t)).collect();
+        let pattern = format!(r"(?i)\b(?:{})\b", alternatives.join("|"));
+        self.term_pattern = Some(Regex::new(&pattern).expect("escaped terms form a valid pattern"));
+        self
+    }
+
+    /// Also treats `emotes` (matched case-sensitively) as tokens, e.g. a
+    /// channel's own subscriber emotes.
+    pub fn with_emotes(mut self, emotes: impl IntoIterator<Item = String>) -> Self {
+        Arc::make_mut(&mut self.emotes).extend(emotes);
+        self
+    }
+
+    /// Byte ranges and kinds of the tokens in `text`, in order.
+    pub fn find(&self, text: &str) -> Vec<(std::ops::Range<usize>, TokenKind)> {
+        let mut found: Vec<_> = self
+            .term_pattern
+            .iter()
+            .flat_map(|terms| terms.find_iter(text))
+            .map(|m| (m.range(), TokenKind::Term))
+            .collect();
+        if self.chat {
+            let chat = self.chat_tokens(text);
+            let overlaps = |range: &std::ops::Range<usize>| {
+                found
+                    .iter()
+                    .any(|(term, _)| range.start < term.end && term.start < range.end)
+            };
+            let chat: Vec<_> = chat.into_iter().filter(|(r, _)| !overlaps(r)).collect();
+            found.extend(chat);
+            found.sort_by_key(|(range, _)| range.start);
+        }
+        found
+    }
+
+    fn chat_tokens(&self, text: &str) -> Vec<(std::ops::Range<usize>, TokenKind)> {
+        self.word
+            .find_iter(text)
+            .filter_map(|m| {
+                let word = m.as_str();
+                let after_space = text[..m.start()]
+                    .chars()
+                    .next_back()
+                    .is_none_or(char::is_whitespace);
+                let kind = if word.starts_with('@') {
+                    after_space.then_some(TokenKind::Mention)?
+                } else if word.starts_with('!') {
+                    after_space.then_some(TokenKind::Command)?
+                } else if self.emotes.contains(word) {
+                    TokenKind::Emote
+                } else {
+                    return None;
+                };
+                Some((m.range(), kind))
+            })
+            .collect()
+    }
+
+    /// Swaps every token for an XML placeholder so a translator run with XML
+    /// tag handling passes it through untouched; the rest is XML-escaped.
+    /// Text without tokens is returned as-is.
+    pub fn protect(&self, text: &str) -> Protected {
+        let spans = self.find(text);
+        if spans.is_empty() {
+            return Protected {
+                text: text.to_owned(),
+                tokens: Vec::new(),
+            };
+        }
+
+        let mut out = String::with_capacity(text.len() + spans.len() * 12);
+        let mut tokens = Vec::with_capacity(spans.len());
+        let mut last = 0;
+        for (range, _) in spans {
+            escape_xml(&text[last..range.start], &mut out);
+            out.push_str(&format!(r#"<x id="{}"/>"#, tokens.len()));
+            tokens.push(text[range.clone()].to_owned());
+            last = range.end;
+        }
+        escape_xml(&text[last..], &mut out);
+        Protected { text: out, tokens }
+    }
+
+    /// Rewrites the tokens in `text` for speech.
+    pub fn speak(&self, text: &str, speech: TokenSpeech) -> String {
+        let mut out = String::with_capacity(text.len());
+        let mut last = 0;
+        for (range, kind) in self.find(text) {
+            out.push_str(&text[last..range.start]);
+            match (kind, speech) {
+                (TokenKind::Term, _) => out.push_str(&text[range.clone()]),
+                (_, TokenSpeech::Spell) => out.push_str(&spell(&text[range.clone()], kind)),
+                (_, TokenSpeech::Skip) => {}
+            }
+            last = range.end;
+        }
+        out.push_str(&text[last..]);
+        match speech {
+            TokenSpeech::Skip => out.split_whitespace().collect::<Vec<_>>().join(" "),
+            TokenSpeech::Spell => out,
+        }
+    }
+}
+
+/// Translator input produced by [`TwitchTokens::protect`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Protected {
+    pub text: String,
+    tokens: Vec<String>,
+}
+
+impl Protected {
+    /// Whether `text` is XML markup that needs tag handling.
+    pub fn has_tokens(&self) -> bool {
+        !self.tokens.is_empty()
+    }
+
+    /// Puts the original tokens back into a translation of [`Self::text`].
+    pub fn restore(&self, translated: &str) -> String {
+        if !self.has_tokens() {
+            return translated.to_owned();
+        }
+        let mut out = String::with_capacity(translated.len());
+        let mut last = 0;
+        for caps in PLACEHOLDER.captures_iter(translated) {
+            let whole = caps.get(0).expect("group 0 always matches");
+            out.push_str(&unescape_xml(&translated[last..whole.start()]));
+            let token = caps[1]
+                .parse::<usize>()
+                .ok()
+                .and_then(|i| self.tokens.get(i));
+            if let Some(token) = token {
+                out.push_str(token);
+            }
+            last = whole.end();
+        }
+        out.push_str(&unescape_xml(&translated[last..]));
+        out
+    }
+}
+
+/// [`TextTransform`] that voices Twitch tokens per [`TokenSpeech`].
+pub struct SpeakTokens {
+    pub tokens: TwitchTokens,
+    pub speech: TokenSpeech,
+}
+
+impl TextTransform for SpeakTokens {
+    fn name(&self) -> &'static str {
+        "twitch_tokens"
+    }
+
+    fn apply(&self, text: &str, _lang: &TargetLang) -> String {
+        self.tokens.speak(text, self.speech)
+    }
+}
+
+fn spell(token: &str, kind: TokenKind) -> String {
+    let name = token.trim_start_matches(['@', '!']);
+    let letters = name.chars().filter(|c| c.is_alphabetic()).count();
+    let upper = name.chars().filter(|c| c.is_uppercase()).count();
+    if kind == TokenKind::Emote && letters > 1 && upper == letters {
+        let spaced: Vec<String> = name.chars().map(String::from).collect();
+        return spaced.join(" ");
+    }
+
+    let mut out = String::with_capacity(name.len() + 4);
+    let mut prev: Option<char> = None;
+    for c in name.chars() {
+        if c == '_' {
+            out.push(' ');
+        } else {
+            let boundary = prev.is_some_and(|p| {
+                (p.is_lowercase() && c.is_uppercase()) || (p.is_alphabetic() && c.is_numeric())
+            });
+            if boundary {
+                out.push(' ');
+            }
+            out.push(c);
+        }
+        prev = Some(c);
+    }
+    out.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn escape_xml(text: &str, out: &mut String) {
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            _ => out.push(c),
+        }
+    }
+}
+
+fn unescape_xml(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_emotes_mentions_and_commands() {
+        let tokens = TwitchTokens::new().with_emotes(["xqcL".to_owned()]);
+        let text = "KEKW @some_user type !discord, mail a@b.com xqcL kappa";
+        let found: Vec<(&str, TokenKind)> = tokens
+            .find(text)
+            .into_iter()
+            .map(|(range, kind)| (&text[range], kind))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ("KEKW", TokenKind::Emote),
+                ("@some_user", TokenKind::Mention),
+                ("!discord", TokenKind::Command),
+                ("xqcL", TokenKind::Emote),
+            ]
+        );
+    }
+
+    #[test]
+    fn protect_round_trips_through_placeholders() {
+        let protected = TwitchTokens::new().protect("thanks @bob_42 for R&D KEKW");
+        assert!(protected.has_tokens());
+        assert_eq!(
+            protected.text,
+            r#"thanks <x id="0"/> for R&amp;D <x id="1"/>"#
+        );
+        let translated = r#"<x id="1"/> obrigado <x id="0" /> pelo P&amp;D"#;
+        assert_eq!(
+            protected.restore(translated),
+            "KEKW obrigado @bob_42 pelo P&D"
+        );
+
+        let plain = TwitchTokens::new().protect("a < b");
+        assert!(!plain.has_tokens());
+        assert_eq!(plain.restore("a < b"), "a < b");
+    }
+
+    #[test]
+    fn terms_are_protected_in_their_original_spelling() {
+        let tokens = TwitchTokens::terms_only()
+            .with_terms(["elden ring".to_owned(), "Elden Ring DLC".to_owned()])
+            .with_terms(["xQc".to_owned(), "  ".to_owned()]);
+        let protected = tokens.protect("XQC loved the Elden Ring DLC, KEKW");
+        assert_eq!(protected.text, r#"<x id="0"/> loved the <x id="1"/>, KEKW"#);
+        assert_eq!(
+            protected.restore(r#"<x id="0"/> adorou o <x id="1"/>, KEKW"#),
+            "XQC adorou o Elden Ring DLC, KEKW"
+        );
+
+        let both = TwitchTokens::new().with_terms(["Pog Champions".to_owned()]);
+        let found: Vec<TokenKind> = both
+            .find("Pog Champions PogChamp")
+            .into_iter()
+            .map(|(_, kind)| kind)
+            .collect();
+        assert_eq!(found, [TokenKind::Term, TokenKind::Emote]);
+        assert_eq!(
+            both.speak("Pog Champions PogChamp", TokenSpeech::Skip),
+            "Pog Champions"
+        );
+    }
+
+    #[test]
+    fn speech_skips_or_spells_tokens() {
+        let tokens = TwitchTokens::new();
+        let text = "KEKW that was great @some_user PogChamp";
+        assert_eq!(tokens.speak(text, TokenSpeech::Skip), "that was great");
+        assert_eq!(
+            tokens.speak(text, TokenSpeech::Spell),
+            "K E K W that was great some user Pog Champ"
+        );
+    }
+}
@@ -0,0 +1,226 @@
+use crate::config::{Formality, SpeakerGender, TargetLang, TranslationStyle};
+use crate::control::ControlHandle;
+use crate::summary::{ChatSummarizer, SummaryError};
+use crate::translate::{ContextWindow, TranslateError, Translation, Translator};
+use futures::future::BoxFuture;
+use futures::FutureExt;
+
+/// Translates with the chat model used for summaries.
+impl Translator for ChatSummarizer {
+    fn translate(
+        &self,
+        text: String,
+        target: TargetLang,
+    ) -> BoxFuture<'_, Result<Translation, TranslateError>> {
+        translate_with(self, text, target, TranslationStyle::default(), None).boxed()
+    }
+}
+
+/// Translates with a chat model in a [`TranslationStyle`], read from the
+/// session's [`ControlHandle`] for each utterance so a change of style takes
+/// effect at the next one.
+#[derive(Clone)]
+pub struct LlmTranslator {
+    llm: ChatSummarizer,
+    control: Option<ControlHandle>,
+    context_window: Option<ContextWindow>,
+}
+
+impl LlmTranslator {
+    pub fn new(llm: ChatSummarizer) -> Self {
+        Self {
+            llm,
+            control: None,
+            context_window: None,
+        }
+    }
+
+    /// Replays the sentences translated just before as earlier turns of the
+    /// conversation, so the model keeps pronouns and terms consistent.
+    pub fn with_context_window(mut self, window: ContextWindow) -> Self {
+        self.context_window = Some(window);
+        self
+    }
+
+    /// Follows the style set on `control`; without one the default style is
+    /// used.
+    pub fn with_control(mut self, control: ControlHandle) -> Self {
+        self.control = Some(control);
+        self
+    }
+}
+
+impl Translator for LlmTranslator {
+    fn translate(
+        &self,
+        text: String,
+        target: TargetLang,
+    ) -> BoxFuture<'_, Result<Translation, TranslateError>> {
+        let style = self
+            .control
+            .as_ref()
+            .map(ControlHandle::translation_style)
+            .unwrap_or_default();
+        translate_with(&self.llm, text, target, style, self.context_window.as_ref()).boxed()
+    }
+}
+
+async fn translate_with(
+    llm: &ChatSummarizer,
+    text: String,
+    target: TargetLang,
+    style: TranslationStyle,
+    context_window: Option<&ContextWindow>,
+) -> Result<Translation, TranslateError> {
+    let history: Vec<(String, String)> = context_window
+        .into_iter()
+        .flat_map(|window| window.pairs(&target))
+        .map(|pair| (pair.source, pair.translation))
+        .collect();
+    let source = text.clone();
+    let text = llm
+        .converse(prompt(&target, &style), &history, text)
+        .await
+        .map_err(|e| match e {
+            SummaryError::Network(e) => TranslateError::Network(e),
+            SummaryError::Api(message) => TranslateError::Api(message),
+            SummaryError::InvalidResponse(message) => TranslateError::InvalidResponse(message),
+            SummaryError::BudgetExceeded(e) => TranslateError::BudgetExceeded(e),
+        })?;
+    if let Some(window) = context_window {
+        window.push(source, text.clone(), target);
+    }
+    Ok(Translation {
+        text,
+        detected_source_lang: None,
+    })
+}
+
+fn prompt(target: &TargetLang, style: &TranslationStyle) -> String {
+    let mut prompt = format!(
+        "Translate the user message, a line spoken on a live stream, into the language with \
+         code {}.",
+        target.as_str()
+    );
+    match style.formality {
+        Formality::More => prompt.push_str(" Address the audience formally."),
+        Formality::Less => prompt.push_str(" Address the audience informally, as a friend."),
+        Formality::Default => {}
+    }
+    match style.speaker_gender {
+        Some(SpeakerGender::Female) => prompt.push_str(
+            " The speaker is a woman; inflect words that refer to the speaker accordingly.",
+        ),
+        Some(SpeakerGender::Male) => prompt.push_str(
+            " The speaker is a man; inflect words that refer to the speaker accordingly.",
+        ),
+        None => {}
+    }
+    if let Some(persona) = style.persona.as_deref().map(str::trim) {
+        if !persona.is_empty() {
+            prompt.push_str(&format!(
+                " Phrase it the way {persona} would, without adding or leaving out content."
+            ));
+        }
+    }
+    if style.keep_slang {
+        prompt.push_str(
+            " Keep names and emotes as they are, and render slang with slang of the target \
+             language rather than neutral words.",
+        );
+    } else {
+        prompt.push_str(" Keep names, emotes and slang that has no equivalent as they are.");
+    }
+    prompt.push_str(" Reply with the translation only.");
+    prompt
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::{HttpMethod, MockTransport};
+
+    #[tokio::test]
+    async fn chat_summarizer_translates_into_the_target_language() {
+        let mock = MockTransport::new().on(
+            HttpMethod::Post,
+            "/chat/completions",
+            200,
+            r#"{"choices":[{"message":{"role":"assistant","content":"olá chat\n"}}]}"#,
+        );
+        let llm = ChatSummarizer::new("http://llm/v1", "m").with_transport(mock.clone().shared());
+
+        let translation = llm
+            .translate("hello chat".to_owned(), TargetLang::new("PT-BR").unwrap())
+            .await
+            .unwrap();
+        assert_eq!(translation.text, "olá chat");
+
+        let body: serde_json::Value =
+            serde_json::from_slice(mock.requests()[0].body.as_ref().unwrap()).unwrap();
+        assert!(body["messages"][0]["content"]
+            .as_str()
+            .unwrap()
+            .contains("code PT-BR"));
+        assert_eq!(body["messages"][1]["content"], "hello chat");
+    }
+
+    #[tokio::test]
+    async fn llm_translator_follows_the_style_of_the_control_handle() {
+        let mock = MockTransport::new().on(
+            HttpMethod::Post,
+            "/chat/completions",
+            200,
+            r#"{"choices":[{"message":{"role":"assistant","content":"hallo"}}]}"#,
+        );
+        let llm = ChatSummarizer::new("http://llm/v1", "m").with_transport(mock.clone().shared());
+        let control = ControlHandle::new(TargetLang::new("de").unwrap());
+        let window = ContextWindow::new(3);
+        let translator = LlmTranslator::new(llm)
+            .with_control(control.clone())
+            .with_context_window(window);
+        let de = TargetLang::new("de").unwrap();
+
+        translator
+            .translate("hi".to_owned(), de.clone())
+            .await
+            .unwrap();
+        control.set_translation_style(TranslationStyle {
+            formality: Formality::Less,
+            keep_slang: true,
+            speaker_gender: Some(SpeakerGender::Female),
+            persona: Some("a sports commentator".to_owned()),
+        });
+        translator.translate("hi".to_owned(), de).await.unwrap();
+
+        let prompts: Vec<String> = mock
+            .requests()
+            .iter()
+            .map(|r| {
+                let body: serde_json::Value =
+                    serde_json::from_slice(r.body.as_ref().unwrap()).unwrap();
+                body["messages"][0]["content"].as_str().unwrap().to_owned()
+            })
+            .collect();
+        let second: serde_json::Value =
+            serde_json::from_slice(mock.requests()[1].body.as_ref().unwrap()).unwrap();
+        let roles: Vec<&str> = second["messages"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|m| m["role"].as_str().unwrap())
+            .collect();
+        assert_eq!(roles, ["system", "user", "assistant", "user"]);
+        assert_eq!(second["messages"][2]["content"], "hallo");
+        assert!(!prompts[0].contains("informally"));
+        assert!(!prompts[0].contains("commentator"));
+        for expected in [
+            "informally",
+            "a woman",
+            "the way a sports commentator would",
+            "slang of the target",
+        ] {
+            assert!(prompts[1].contains(expected), "{expected}: {}", prompts[1]);
+        }
+    }
+}
@@ -0,0 +1,97 @@
+use crate::config::TargetLang;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Sentences passed to the translator as context by default.
+pub const DEFAULT_CONTEXT_SENTENCES: usize = 3;
+
+/// The last sentences a session translated, for translators to pass along
+/// with the next one so pronouns and topic words stay consistent across
+/// utterances. Clones share the window.
+#[derive(Clone, Debug)]
+pub struct ContextWindow {
+    pairs: Arc<Mutex<VecDeque<ContextPair>>>,
+    capacity: usize,
+}
+
+/// A sentence and its translation
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ContextPair {
+    pub source: String,
+    pub translation: String,
+    pub target: TargetLang,
+}
+
+impl ContextWindow {
+    /// Keeps the last `capacity` sentences; 0 keeps none.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            pairs: Arc::new(Mutex::new(VecDeque::with_capacity(capacity))),
+            capacity,
+        }
+    }
+
+    /// Remembers that `source` was translated into `target` as
+    /// `translation`, forgetting the oldest sentence once full.
+    pub fn push(&self, source: String, translation: String, target: TargetLang) {
+        if self.capacity == 0 || source.trim().is_empty() {
+            return;
+        }
+        let mut pairs = self.lock();
+        if pairs.len() == self.capacity {
+            pairs.pop_front();
+        }
+        pairs.push_back(ContextPair {
+            source,
+            translation,
+            target,
+        });
+    }
+
+    /// Oldest first, the sentences translated into `target`. After a switch
+    /// of target language the window refills in the new one, rather than
+    /// showing the translator text in a language it is no longer asked for.
+    pub fn pairs(&self, target: &TargetLang) -> Vec<ContextPair> {
+        self.lock()
+            .iter()
+            .filter(|pair| pair.target.deepl_code() == target.deepl_code())
+            .cloned()
+            .collect()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, VecDeque<ContextPair>> {
+        match self.pairs.lock() {
+            Ok(g) => g,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_the_last_sentences_in_the_current_language() {
+        let window = ContextWindow::new(2);
+        let de = TargetLang::new("de").unwrap();
+        let fr = TargetLang::new("fr").unwrap();
+        window.push("one".into(), "eins".into(), de.clone());
+        window.push("two".into(), "zwei".into(), de.clone());
+        window.push(
+            "three".into(),
+            "drei".into(),
+            TargetLang::new("DE").unwrap(),
+        );
+        let sources: Vec<_> = window.pairs(&de).into_iter().map(|p| p.source).collect();
+        assert_eq!(sources, ["two", "three"]);
+
+        window.push("four".into(), "quatre".into(), fr.clone());
+        assert_eq!(window.pairs(&fr).len(), 1);
+        assert_eq!(window.pairs(&de).len(), 1);
+
+        let off = ContextWindow::new(0);
+        off.push("one".into(), "eins".into(), de.clone());
+        assert!(off.pairs(&de).is_empty());
+    }
+}
@@ -1,24 +1,141 @@
-use crate::config::TargetLang;
-use crate::translate::{TranslateError, Translation, Translator};
-use crate::util::{is_http_retryable, retry_with_backoff, RetryConfig};
+use crate::config::{Formality, TargetLang};
+use crate::cost::{Billable, CostMeter};
+use crate::error::Classify;
+use crate::status::PipelineStatus;
+use crate::text::TwitchTokens;
+use crate::translate::{ContextWindow, TranslateError, Translation, Translator};
+#[cfg(feature = "native")]
+use crate::util::ReqwestTransport;
+use crate::util::{retry_with_backoff, HttpRequest, RetryConfig, SharedTransport};
 use futures::future::BoxFuture;
 use futures::FutureExt;
-use reqwest::Client;
 use serde::{Deserialize, Serialize};
 
 #[derive(Clone)]
 pub struct DeepLTranslator {
-    client: Client,
+    transport: SharedTransport,
     api_key: String,
+    stream_context: Option<PipelineStatus>,
+    protected_tokens: Option<TwitchTokens>,
+    cost: Option<CostMeter>,
+    formality: Formality,
+    context_window: Option<ContextWindow>,
 }
 
 impl DeepLTranslator {
+    #[cfg(feature = "native")]
     pub fn new(api_key: String) -> Self {
+        Self::from_transport(api_key, ReqwestTransport::default().shared())
+    }
+
+    /// A translator that sends its requests through `transport`, e.g. one
+    /// wrapping the browser's `fetch` where reqwest is not built.
+    pub fn from_transport(api_key: String, transport: SharedTransport) -> Self {
         Self {
-            client: Client::new(),
+            transport,
             api_key,
+            stream_context: None,
+            protected_tokens: None,
+            cost: None,
+            formality: Formality::Default,
+            context_window: None,
+        }
+    }
+
+    /// Passes the stream title and category from `status` to DeepL as
+    /// context, which helps with game-specific terms.
+    pub fn with_stream_context(mut self, status: PipelineStatus) -> Self {
+        self.stream_context = Some(status);
+        self
+    }
+
+    /// Keeps the emotes, mentions, commands and terms `tokens` finds out of
+    /// translation by sending them as XML placeholders.
+    pub fn with_protected_tokens(mut self, tokens: TwitchTokens) -> Self {
+        self.protected_tokens = Some(tokens);
+        self
+    }
+
+    /// Counts the characters translated against `cost`, and stops
+    /// translating once its budget is spent.
+    pub fn with_cost_meter(mut self, cost: CostMeter) -> Self {
+        self.cost = Some(cost);
+        self
+    }
+
+    /// Translates more or less formally into the languages that tell the
+    /// two apart; others, e.g. after a switch to English mid-stream, are
+    /// translated as usual.
+    pub fn with_formality(mut self, formality: Formality) -> Self {
+        self.formality = formality;
+        self
+    }
+
+    /// Passes the sentences translated just before to DeepL as context, so
+    /// e.g. a pronoun is translated for who was just mentioned. Context is
+    /// not billed.
+    pub fn with_context_window(mut self, window: ContextWindow) -> Self {
+        self.context_window = Some(window);
+        self
+    }
+
+    fn context(&self, target: &TargetLang) -> Option<String> {
+        let mut parts = Vec::new();
+        if let Some(metadata) = self.stream_context.as_ref().and_then(|s| s.stream_metadata()) {
+            parts.push(format!(
+                "Live stream titled \"{}\" in the category \"{}\".",
+                metadata.title, metadata.game
+            ));
+        }
+        let previous: Vec<String> = self
+            .context_window
+            .iter()
+            .flat_map(|window| window.pairs(target))
+            .map(|pair| pair.source)
+            .collect();
+        if !previous.is_empty() {
+            parts.push(format!("Said just before: {}", previous.join(" ")));
+        }
+        (!parts.is_empty()).then(|| parts.join(" "))
+    }
+
+    /// Sends requests through `transport` instead of the network, e.g. a
+    /// [`crate::util::MockTransport`] in tests.
+    pub fn with_transport(mut self, transport: SharedTransport) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    /// Free-tier keys (suffixed `:fx`) are served from a separate host.
+    fn api_base(&self) -> &'static str {
+        if self.api_key.ends_with(":fx") {
+            "https://api-free.deepl.com/v2"
+        } else {
+            "https://api.deepl.com/v2"
         }
     }
+
+    /// Fetches the account's character usage; doubles as an API key check.
+    pub async fn usage(&self) -> Result<DeepLUsage, TranslateError> {
+        let request = HttpRequest::get(format!("{}/usage", self.api_base()))
+            .with_header("Authorization", format!("DeepL-Auth-Key {}", self.api_key));
+        let response = self.transport.send(request).await?;
+
+        if !response.is_success() {
+            return Err(TranslateError::HttpStatus(response.status, response.text()));
+        }
+
+        response
+            .json()
+            .map_err(|e| TranslateError::InvalidResponse(format!("Failed to parse JSON: {}", e)))
+    }
+}
+
+/// Character usage for the current billing period, as reported by `/v2/usage`
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct DeepLUsage {
+    pub character_count: u64,
+    pub character_limit: u64,
 }
 
 #[derive(Serialize, Clone)]
@@ -27,6 +144,12 @@ struct DeepLRequest {
     target_lang: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     source_lang: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    context: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tag_handling: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    formality: Option<&'static str>,
 }
 
 #[derive(Deserialize)]
@@ -48,67 +171,65 @@ impl Translator for DeepLTranslator {
     ) -> BoxFuture<'_, Result<Translation, TranslateError>> {
         let this = self.clone();
         async move {
-            // Prepare the request
-            // For most language codes, we use uppercase, but some have special cases
-            let target_lang = match target.as_str().to_lowercase().as_str() {
-                "pt-br" => "pt-BR".to_string(),
-                "pt-pt" => "pt-PT".to_string(),
-                "en-gb" => "en-GB".to_string(),
-                "en-us" => "en-US".to_string(),
-                _ => target.as_str().to_uppercase(),
+            if let Some(cost) = &this.cost {
+                cost.check_budget()?;
+            }
+
+            // DeepL rejects `formality` for languages without it
+            let formality = match this.formality {
+                Formality::More if target.supports_formality() => Some("more"),
+                Formality::Less if target.supports_formality() => Some("less"),
+                _ => None,
             };
             
+            let source = text.clone();
+            let protected = this.protected_tokens.as_ref().map(|tokens| tokens.protect(&text));
+            let (text, tag_handling) = match &protected {
+                Some(protected) if protected.has_tokens() => (protected.text.clone(), Some("xml")),
+                _ => (text, None),
+            };
+            let billed_chars = text.chars().count() as u64;
+
             let request = DeepLRequest {
                 text: vec![text],
-                target_lang,
+                target_lang: target.deepl_code(),
                 source_lang: None, // Let DeepL detect the source language
+                context: this.context(&target),
+                tag_handling,
+                formality,
             };
 
             // Build the URL
-            let url = if this.api_key.ends_with(":fx") {
-                "https://api-free.deepl.com/v2/translate"
-            } else {
-                "https://api.deepl.com/v2/translate"
-            };
+            let url = format!("{}/translate", this.api_base());
 
             // Configure retry with exponential backoff
             let retry_config = RetryConfig::default();
             
             // Perform the translation with retry logic
             retry_with_backoff(&retry_config, || {
-                let client = this.client.clone();
+                let transport = this.transport.clone();
                 let api_key = this.api_key.clone();
                 let request_body = request.clone();
-                let url_str = url.to_string();
+                let url_str = url.clone();
                 
                 async move {
                     // Send the request
-                    let response = client
-                        .post(&url_str)
-                        .header("Authorization", format!("DeepL-Auth-Key {}", api_key))
-                        .json(&request_body)
-                        .send()
+                    let request = HttpRequest::post(url_str)
+                        .with_header("Authorization", format!("DeepL-Auth-Key {}", api_key))
+                        .with_json(&request_body)?;
+                    let response = transport
+                        .send(request)
                         .await
                         .map_err(TranslateError::Network)?;
 
                     // Check if the request was successful
-                    if !response.status().is_success() {
-                        let status = response.status();
-                        let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
-                        
-                        // Check if this error is retryable
-                        if is_http_retryable(status.as_u16()) {
-                            return Err(TranslateError::Api(format!("HTTP {}: {}", status, error_text)));
-                        } else {
-                            // Non-retryable error, return immediately
-                            return Err(TranslateError::Api(format!("HTTP {}: {}", status, error_text)));
-                        }
+                    if !response.is_success() {
+                        return Err(TranslateError::HttpStatus(response.status, response.text()));
                     }
 
                     // Parse the response
                     let deepl_response: DeepLResponse = response
                         .json()
-                        .await
                         .map_err(|e| TranslateError::InvalidResponse(format!("Failed to parse JSON: {}", e)))?;
 
                     // Extract the translation
@@ -125,10 +246,154 @@ impl Translator for DeepLTranslator {
                     })
                 }
             }, |error| {
-                // Only retry on API errors with retryable HTTP status codes
-                matches!(error, TranslateError::Api(_))
+                // Only retry transient failures, not a rejected key or bad request
+                error.is_retryable()
             }).await
+            .map(|mut translation| {
+                if let Some(cost) = &this.cost {
+                    cost.record(Billable::DeepLChars, billed_chars);
+                }
+                if let Some(protected) = &protected {
+                    translation.text = protected.restore(&translation.text);
+                }
+                if let Some(window) = &this.context_window {
+                    window.push(source, translation.text.clone(), target);
+                }
+                translation
+            })
         }
         .boxed()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::{HttpMethod, MockTransport};
+
+    #[cfg(feature = "native")]
+    #[tokio::test]
+    async fn translates_through_transport_after_retry() {
+        use std::sync::Arc;
+
+        let mock = MockTransport::new()
+            .on(HttpMethod::Post, "api-free.deepl.com/v2/translate", 503, "busy")
+            .on(
+                HttpMethod::Post,
+                "api-free.deepl.com/v2/translate",
+                200,
+                r#"{"translations":[{"detected_source_language":"EN","text":"olá"}]}"#,
+            );
+        let translator =
+            DeepLTranslator::from_transport("key:fx".to_owned(), Arc::new(mock.clone()))
+                .with_formality(Formality::Less);
+
+        let translation = translator
+            .translate("hello".to_owned(), TargetLang::new("pt-br").unwrap())
+            .await
+            .unwrap();
+        assert_eq!(translation.text, "olá");
+        assert_eq!(translation.detected_source_lang.as_deref(), Some("EN"));
+
+        let requests = mock.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[1].header("authorization"), Some("DeepL-Auth-Key key:fx"));
+        let body: serde_json::Value = serde_json::from_slice(requests[1].body.as_ref().unwrap()).unwrap();
+        assert_eq!(body["target_lang"], "PT-BR");
+        assert_eq!(body["formality"], "less");
+    }
+
+    #[tokio::test]
+    async fn sends_stream_metadata_as_context() {
+        let mock = MockTransport::new().on(
+            HttpMethod::Post,
+            "/v2/translate",
+            200,
+            r#"{"translations":[{"detected_source_language":"EN","text":"olá"}]}"#,
+        );
+        let status = PipelineStatus::new();
+        let translator = DeepLTranslator::from_transport("key".to_owned(), mock.clone().shared())
+            .with_stream_context(status.clone());
+
+        let target = TargetLang::new("pt-br").unwrap();
+        translator.translate("hi".to_owned(), target.clone()).await.unwrap();
+        status.set_stream_metadata(crate::status::StreamMetadata {
+            title: "any% runs".to_owned(),
+            game: "Celeste".to_owned(),
+            viewer_count: 1,
+        });
+        translator.translate("hi".to_owned(), target).await.unwrap();
+
+        let bodies: Vec<serde_json::Value> = mock
+            .requests()
+            .iter()
+            .map(|r| serde_json::from_slice(r.body.as_ref().unwrap()).unwrap())
+            .collect();
+        assert!(bodies[0].get("context").is_none());
+        assert!(bodies[0].get("formality").is_none());
+        assert_eq!(
+            bodies[1]["context"],
+            "Live stream titled \"any% runs\" in the category \"Celeste\"."
+        );
+    }
+
+    #[tokio::test]
+    async fn passes_previous_sentences_as_context() {
+        let mock = MockTransport::new().on(
+            HttpMethod::Post,
+            "/v2/translate",
+            200,
+            r#"{"translations":[{"detected_source_language":"EN","text":"Er ist da."}]}"#,
+        );
+        let window = ContextWindow::new(3);
+        let translator = DeepLTranslator::from_transport("key".to_owned(), mock.clone().shared())
+            .with_context_window(window.clone());
+
+        let target = TargetLang::new("de").unwrap();
+        translator.translate("Bob joined.".to_owned(), target.clone()).await.unwrap();
+        translator.translate("He is here.".to_owned(), target.clone()).await.unwrap();
+
+        let bodies: Vec<serde_json::Value> = mock
+            .requests()
+            .iter()
+            .map(|r| serde_json::from_slice(r.body.as_ref().unwrap()).unwrap())
+            .collect();
+        assert!(bodies[0].get("context").is_none());
+        assert_eq!(bodies[1]["context"], "Said just before: Bob joined.");
+        assert_eq!(bodies[1]["text"][0], "He is here.");
+        assert_eq!(window.pairs(&target).len(), 2);
+    }
+
+    #[tokio::test]
+    async fn protected_tokens_survive_translation() {
+        let mock = MockTransport::new().on(
+            HttpMethod::Post,
+            "/v2/translate",
+            200,
+            r#"{"translations":[{"detected_source_language":"EN","text":"valeu <x id=\"0\"/>, isso foi <x id=\"1\"/>"}]}"#,
+        );
+        let translator = DeepLTranslator::from_transport("key".to_owned(), mock.clone().shared())
+            .with_protected_tokens(TwitchTokens::new());
+
+        let translation = translator
+            .translate("thanks @bob, that was KEKW".to_owned(), TargetLang::new("pt-br").unwrap())
+            .await
+            .unwrap();
+        assert_eq!(translation.text, "valeu @bob, isso foi KEKW");
+
+        let body: serde_json::Value =
+            serde_json::from_slice(mock.requests()[0].body.as_ref().unwrap()).unwrap();
+        assert_eq!(body["tag_handling"], "xml");
+        assert_eq!(body["text"][0], r#"thanks <x id="0"/>, that was <x id="1"/>"#);
+    }
+
+    #[tokio::test]
+    async fn usage_reports_rejected_key() {
+        let mock = MockTransport::new().on(HttpMethod::Get, "/v2/usage", 403, "Forbidden");
+        let err = DeepLTranslator::from_transport("bad".to_owned(), mock.shared())
+            .usage()
+            .await
+            .unwrap_err();
+        assert!(matches!(err, TranslateError::HttpStatus(403, _)));
+    }
+}
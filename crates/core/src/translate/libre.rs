@@ -0,0 +1,147 @@
+use crate::config::TargetLang;
+use crate::error::Classify;
+use crate::translate::{TranslateError, Translation, Translator};
+#[cfg(feature = "native")]
+use crate::util::ReqwestTransport;
+use crate::util::{retry_with_backoff, HttpRequest, RetryConfig, SharedTransport};
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use serde::{Deserialize, Serialize};
+
+/// Translator backed by a LibreTranslate server, e.g. a self-hosted one.
+#[derive(Clone)]
+pub struct LibreTranslator {
+    transport: SharedTransport,
+    base_url: String,
+    api_key: Option<String>,
+}
+
+impl LibreTranslator {
+    /// `base_url` is the server root, e.g. `http://localhost:5000`.
+    #[cfg(feature = "native")]
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self::from_transport(base_url, ReqwestTransport::default().shared())
+    }
+
+    pub fn from_transport(base_url: impl Into<String>, transport: SharedTransport) -> Self {
+        Self {
+            transport,
+            base_url: base_url.into().trim_end_matches('/').to_owned(),
+            api_key: None,
+        }
+    }
+
+    /// Needed by servers started with `--api-keys`, such as libretranslate.com.
+    pub fn with_api_key(mut self, api_key: String) -> Self {
+        self.api_key = Some(api_key);
+        self
+    }
+
+    /// Sends requests through `transport` instead of the network, e.g. a
+    /// [`crate::util::MockTransport`] in tests.
+    pub fn with_transport(mut self, transport: SharedTransport) -> Self {
+        self.transport = transport;
+        self
+    }
+}
+
+#[derive(Serialize)]
+struct LibreRequest<'a> {
+    q: &'a str,
+    source: &'static str,
+    target: &'a str,
+    format: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    api_key: Option<&'a str>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LibreResponse {
+    translated_text: String,
+    detected_language: Option<LibreDetectedLanguage>,
+}
+
+#[derive(Deserialize)]
+struct LibreDetectedLanguage {
+    language: String,
+}
+
+impl Translator for LibreTranslator {
+    fn translate(
+        &self,
+        text: String,
+        target: TargetLang,
+    ) -> BoxFuture<'_, Result<Translation, TranslateError>> {
+        async move {
+            // LibreTranslate names languages without a region: `pt`, not `PT-BR`.
+            let target = target.primary();
+            let url = format!("{}/translate", self.base_url);
+            let body = LibreRequest {
+                q: &text,
+                source: "auto",
+                target: &target,
+                format: "text",
+                api_key: self.api_key.as_deref(),
+            };
+            retry_with_backoff(
+                &RetryConfig::default(),
+                || {
+                    let request = HttpRequest::post(url.clone()).with_json(&body);
+                    let transport = self.transport.clone();
+                    async move {
+                        let response = transport.send(request?).await?;
+                        if !response.is_success() {
+                            return Err(TranslateError::HttpStatus(
+                                response.status,
+                                response.text(),
+                            ));
+                        }
+                        let parsed: LibreResponse = response.json().map_err(|e| {
+                            TranslateError::InvalidResponse(format!("Failed to parse JSON: {}", e))
+                        })?;
+                        Ok(Translation {
+                            text: parsed.translated_text,
+                            detected_source_lang: parsed.detected_language.map(|d| d.language),
+                        })
+                    }
+                },
+                |error| error.is_retryable(),
+            )
+            .await
+        }
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::{HttpMethod, MockTransport};
+
+    #[tokio::test]
+    async fn translates_with_the_primary_language_code() {
+        let mock = MockTransport::new().on(
+            HttpMethod::Post,
+            "libre.local/translate",
+            200,
+            r#"{"translatedText":"olá","detectedLanguage":{"confidence":90,"language":"en"}}"#,
+        );
+        let translator =
+            LibreTranslator::from_transport("http://libre.local/", mock.clone().shared())
+                .with_api_key("key".to_owned());
+
+        let translation = translator
+            .translate("hello".to_owned(), TargetLang::new("PT-BR").unwrap())
+            .await
+            .unwrap();
+        assert_eq!(translation.text, "olá");
+        assert_eq!(translation.detected_source_lang.as_deref(), Some("en"));
+
+        let body: serde_json::Value =
+            serde_json::from_slice(mock.requests()[0].body.as_ref().unwrap()).unwrap();
+        assert_eq!(body["target"], "pt");
+        assert_eq!(body["source"], "auto");
+        assert_eq!(body["api_key"], "key");
+    }
+}
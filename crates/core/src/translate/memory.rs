@@ -0,0 +1,313 @@
+//! Translations remembered across sessions
+//!
+//! [`TranslationMemory`] keeps the sentences translated for each channel in a
+//! SQLite file, by target language, and [`MemoryTranslator`] answers a
+//! sentence heard again from it instead of asking the translation service.
+//! Streamers repeat themselves (greetings, thanks for subs, catchphrases), so
+//! a memory kept over many sessions makes translation faster and cheaper.
+
+use super::{TranslateError, Translation, Translator};
+use crate::config::TargetLang;
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use rusqlite::{params, Connection, OptionalExtension};
+use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Longer sentences rarely come up again, so they are not remembered.
+const MAX_SOURCE_CHARS: usize = 300;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS translations (
+        channel TEXT NOT NULL,
+        target TEXT NOT NULL,
+        variant TEXT NOT NULL,
+        source TEXT NOT NULL,
+        translation TEXT NOT NULL,
+        hits INTEGER NOT NULL DEFAULT 0,
+        updated_at INTEGER NOT NULL,
+        PRIMARY KEY (channel, target, variant, source)
+    );
+";
+
+#[derive(thiserror::Error, Debug)]
+pub enum MemoryError {
+    #[error("translation memory: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+
+    #[error("translation memory task failed: {0}")]
+    Task(String),
+}
+
+/// What a remembered translation may be reused for: the same channel,
+/// target language and variant
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MemoryScope {
+    pub channel: String,
+    pub target: TargetLang,
+    /// Tells apart translations made differently, e.g. at another formality.
+    pub variant: String,
+}
+
+/// Past translations in a SQLite file. Calls block on the file, so async
+/// code runs them on a blocking thread; clones share the connection.
+#[derive(Clone)]
+pub struct TranslationMemory {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl TranslationMemory {
+    /// Opens the memory at `path`, creating the file if needed. Several
+    /// processes may share it.
+    pub fn open(path: &Path) -> Result<Self, MemoryError> {
+        let conn = Connection::open(path)?;
+        conn.busy_timeout(Duration::from_secs(5))?;
+        conn.pragma_update_and_check(None, "journal_mode", "WAL", |_| Ok(()))?;
+        Self::init(conn)
+    }
+
+    /// A memory kept only as long as the process runs.
+    pub fn in_memory() -> Result<Self, MemoryError> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    fn init(conn: Connection) -> Result<Self, MemoryError> {
+        conn.execute_batch(SCHEMA)?;
+        Ok(Self {
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    /// The translation remembered for `source` in `scope`, counting the hit.
+    /// Sentences match regardless of case and spacing.
+    pub fn lookup(&self, scope: &MemoryScope, source: &str) -> Result<Option<String>, MemoryError> {
+        let source = normalize(source);
+        let target = scope.target.deepl_code();
+        let conn = self.lock();
+        let found = conn
+            .query_row(
+                "SELECT translation FROM translations
+                 WHERE channel = ?1 AND target = ?2 AND variant = ?3 AND source = ?4",
+                params![scope.channel, target, scope.variant, source],
+                |row| row.get::<_, String>(0),
+            )
+            .optional()?;
+        if found.is_some() {
+            conn.execute(
+                "UPDATE translations SET hits = hits + 1
+                 WHERE channel = ?1 AND target = ?2 AND variant = ?3 AND source = ?4",
+                params![scope.channel, target, scope.variant, source],
+            )?;
+        }
+        Ok(found)
+    }
+
+    /// Remembers `translation` for `source` in `scope`, replacing an older
+    /// one. Sentences too long to come up again are skipped.
+    pub fn remember(
+        &self,
+        scope: &MemoryScope,
+        source: &str,
+        translation: &str,
+    ) -> Result<(), MemoryError> {
+        let source = normalize(source);
+        if source.is_empty() || source.chars().count() > MAX_SOURCE_CHARS {
+            return Ok(());
+        }
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs() as i64;
+        self.lock().execute(
+            "INSERT INTO translations (channel, target, variant, source, translation, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+             ON CONFLICT (channel, target, variant, source)
+             DO UPDATE SET translation = excluded.translation, updated_at = excluded.updated_at",
+            params![
+                scope.channel,
+                scope.target.deepl_code(),
+                scope.variant,
+                source,
+                translation,
+                now
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Sentences remembered for every channel and language.
+    pub fn count(&self) -> Result<u64, MemoryError> {
+        let count = self
+            .lock()
+            .query_row("SELECT COUNT(*) FROM translations", [], |row| {
+                row.get::<_, i64>(0)
+            })?;
+        Ok(count.max(0) as u64)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Connection> {
+        match self.conn.lock() {
+            Ok(g) => g,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+}
+
+/// Lower case with single spaces, as ASR casing and spacing vary between
+/// sayings of the same sentence.
+fn normalize(source: &str) -> String {
+    source
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// [`Translator`] that answers from a [`TranslationMemory`] and asks the
+/// translator it wraps only for sentences it has not seen, remembering
+/// them. A memory that fails is logged and bypassed.
+pub struct MemoryTranslator<T> {
+    inner: T,
+    memory: TranslationMemory,
+    channel: String,
+    variant: String,
+}
+
+impl<T> MemoryTranslator<T> {
+    /// Remembers translations for `channel` in `memory`.
+    pub fn new(inner: T, memory: TranslationMemory, channel: impl Into<String>) -> Self {
+        Self {
+            inner,
+            memory,
+            channel: channel.into().to_lowercase(),
+            variant: String::new(),
+        }
+    }
+
+    /// Keeps these translations apart from ones made differently, e.g. at
+    /// another formality.
+    pub fn with_variant(mut self, variant: impl Into<String>) -> Self {
+        self.variant = variant.into();
+        self
+    }
+
+    async fn blocking<R, F>(&self, f: F) -> Result<R, MemoryError>
+    where
+        R: Send + 'static,
+        F: FnOnce(&TranslationMemory) -> Result<R, MemoryError> + Send + 'static,
+    {
+        let memory = self.memory.clone();
+        tokio::task::spawn_blocking(move || f(&memory))
+            .await
+            .map_err(|e| MemoryError::Task(e.to_string()))?
+    }
+}
+
+impl<T: Translator> Translator for MemoryTranslator<T> {
+    fn translate(
+        &self,
+        text: String,
+        target: TargetLang,
+    ) -> BoxFuture<'_, Result<Translation, TranslateError>> {
+        async move {
+            let scope = MemoryScope {
+                channel: self.channel.clone(),
+                target: target.clone(),
+                variant: self.variant.clone(),
+            };
+            let (lookup_scope, source) = (scope.clone(), text.clone());
+            match self
+                .blocking(move |memory| memory.lookup(&lookup_scope, &source))
+                .await
+            {
+                Ok(Some(text)) => {
+                    tracing::debug!(%text, "translation memory hit");
+                    return Ok(Translation {
+                        text,
+                        detected_source_lang: None,
+                    });
+                }
+                Ok(None) => {}
+                Err(e) => tracing::warn!(error = %e, "translation memory lookup failed"),
+            }
+
+            let translation = self.inner.translate(text.clone(), target).await?;
+            let remembered = translation.text.clone();
+            if let Err(e) = self
+                .blocking(move |memory| memory.remember(&scope, &text, &remembered))
+                .await
+            {
+                tracing::warn!(error = %e, "failed to remember a translation");
+            }
+            Ok(translation)
+        }
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Upper-cases the text and counts the calls.
+    #[derive(Default)]
+    struct Counting(AtomicUsize);
+
+    impl Translator for Counting {
+        fn translate(
+            &self,
+            text: String,
+            _target: TargetLang,
+        ) -> BoxFuture<'_, Result<Translation, TranslateError>> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            async move {
+                Ok(Translation {
+                    text: text.to_uppercase(),
+                    detected_source_lang: None,
+                })
+            }
+            .boxed()
+        }
+    }
+
+    #[tokio::test]
+    async fn answers_sentences_heard_before_from_memory() {
+        let memory = TranslationMemory::in_memory().unwrap();
+        let de = TargetLang::new("de").unwrap();
+        let translator = MemoryTranslator::new(Counting::default(), memory.clone(), "Streamer");
+
+        let first = translator
+            .translate("thanks for the sub".into(), de.clone())
+            .await;
+        let again = translator
+            .translate("Thanks  for the sub ".into(), de.clone())
+            .await;
+        assert_eq!(first.unwrap().text, "THANKS FOR THE SUB");
+        assert_eq!(again.unwrap().text, "THANKS FOR THE SUB");
+        assert_eq!(translator.inner.0.load(Ordering::SeqCst), 1);
+
+        // Other languages, channels and formalities are translated afresh.
+        let fr = TargetLang::new("fr").unwrap();
+        translator
+            .translate("thanks for the sub".into(), fr)
+            .await
+            .unwrap();
+        let other = MemoryTranslator::new(Counting::default(), memory.clone(), "other");
+        other
+            .translate("thanks for the sub".into(), de.clone())
+            .await
+            .unwrap();
+        let formal = MemoryTranslator::new(Counting::default(), memory.clone(), "streamer")
+            .with_variant("more");
+        formal
+            .translate("thanks for the sub".into(), de)
+            .await
+            .unwrap();
+        assert_eq!(translator.inner.0.load(Ordering::SeqCst), 2);
+        assert_eq!(other.inner.0.load(Ordering::SeqCst), 1);
+        assert_eq!(formal.inner.0.load(Ordering::SeqCst), 1);
+        assert_eq!(memory.count().unwrap(), 4);
+    }
+}
@@ -1,12 +1,25 @@
+#[cfg(feature = "native")]
+mod chat;
+mod context;
 mod deepl;
 mod dummy;
+mod libre;
+#[cfg(feature = "sqlite")]
+mod memory;
 
 use crate::config::TargetLang;
 use futures::future::BoxFuture;
 use serde::{Deserialize, Serialize};
+use std::sync::Arc;
 
-pub use deepl::DeepLTranslator;
+#[cfg(feature = "native")]
+pub use chat::LlmTranslator;
+pub use context::{ContextPair, ContextWindow, DEFAULT_CONTEXT_SENTENCES};
+pub use deepl::{DeepLTranslator, DeepLUsage};
 pub use dummy::DummyTranslator;
+pub use libre::LibreTranslator;
+#[cfg(feature = "sqlite")]
+pub use memory::{MemoryError, MemoryScope, MemoryTranslator, TranslationMemory};
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
 pub struct Translation {
@@ -20,13 +33,19 @@ pub enum TranslateError {
     NotImplemented,
     
     #[error("network error: {0}")]
-    Network(#[from] reqwest::Error),
+    Network(#[from] crate::util::HttpError),
+    
+    #[error("http error {0}: {1}")]
+    HttpStatus(u16, String),
     
     #[error("invalid response: {0}")]
     InvalidResponse(String),
     
     #[error("API error: {0}")]
     Api(String),
+
+    #[error(transparent)]
+    BudgetExceeded(#[from] crate::cost::BudgetExceeded),
 }
 
 pub trait Translator: Send + Sync {
@@ -36,3 +55,25 @@ pub trait Translator: Send + Sync {
         target: TargetLang,
     ) -> BoxFuture<'_, Result<Translation, TranslateError>>;
 }
+
+/// Type-erased [`Translator`] for picking the backend at runtime.
+#[derive(Clone)]
+pub struct DynTranslator {
+    inner: Arc<dyn Translator>,
+}
+
+impl DynTranslator {
+    pub fn new(inner: Arc<dyn Translator>) -> Self {
+        Self { inner }
+    }
+}
+
+impl Translator for DynTranslator {
+    fn translate(
+        &self,
+        text: String,
+        target: TargetLang,
+    ) -> BoxFuture<'_, Result<Translation, TranslateError>> {
+        self.inner.translate(text, target)
+    }
+}
@@ -0,0 +1,198 @@
+use crate::status::{PipelineEvent, PipelineStatus};
+use crate::tts::{TtsAudio, TtsClient, TtsError, TtsRequest};
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+const LOG_TARGET: &str = "tts::ab_test";
+
+/// Splits utterances between two TTS backends, for comparing them by ear over
+/// a real session. Each utterance that is voiced is published as a
+/// [`PipelineEvent::TtsVariant`] naming the backend that voiced it.
+///
+/// The split is interleaved rather than random, so at 50% the backends
+/// alternate and any stretch of the session hears both.
+#[derive(Clone)]
+pub struct AbTestTtsClient<A, B> {
+    a: A,
+    b: B,
+    names: [String; 2],
+    percent_a: u8,
+    utterances: Arc<AtomicU64>,
+    status: Option<PipelineStatus>,
+}
+
+impl<A, B> AbTestTtsClient<A, B> {
+    /// Voices `percent_a` percent of utterances with `a` and the rest with
+    /// `b`; values above 100 are treated as 100.
+    pub fn new(a: A, b: B, percent_a: u8) -> Self {
+        Self {
+            a,
+            b,
+            names: ["a".to_owned(), "b".to_owned()],
+            percent_a: percent_a.min(100),
+            utterances: Arc::new(AtomicU64::new(0)),
+            status: None,
+        }
+    }
+
+    /// Names the backends in events and logs, e.g. `elevenlabs` and `piper`.
+    pub fn with_names(mut self, a: impl Into<String>, b: impl Into<String>) -> Self {
+        self.names = [a.into(), b.into()];
+        self
+    }
+
+    /// Publishes which backend voiced each utterance on `status`.
+    pub fn with_status(mut self, status: PipelineStatus) -> Self {
+        self.status = Some(status);
+        self
+    }
+
+    /// Whether utterance `n` (from 0) goes to `a`: `a` takes one each time
+    /// `n * percent_a / 100` steps up, spreading its share evenly.
+    fn routes_to_a(&self, n: u64) -> bool {
+        let percent = u64::from(self.percent_a);
+        (n + 1) * percent / 100 > n * percent / 100
+    }
+}
+
+impl<A, B> TtsClient for AbTestTtsClient<A, B>
+where
+    A: TtsClient,
+    B: TtsClient,
+{
+    fn synthesize(&self, request: TtsRequest) -> BoxFuture<'_, Result<TtsAudio, TtsError>> {
+        async move {
+            let n = self.utterances.fetch_add(1, Ordering::Relaxed);
+            let to_a = self.routes_to_a(n);
+            let chosen = usize::from(!to_a);
+            let first = if to_a {
+                self.a.synthesize(request.clone()).await
+            } else {
+                self.b.synthesize(request.clone()).await
+            };
+            // The other backend stands in, so a failure costs the comparison
+            // one sample rather than the viewer an utterance.
+            let (audio, voiced_by) = match first {
+                Ok(audio) => (audio, chosen),
+                Err(e @ TtsError::BudgetExceeded(_)) => return Err(e),
+                Err(e) => {
+                    tracing::warn!(
+                        target: LOG_TARGET,
+                        "{} failed, voicing this utterance with {}: {e}",
+                        self.names[chosen],
+                        self.names[1 - chosen]
+                    );
+                    let audio = if to_a {
+                        self.b.synthesize(request.clone()).await?
+                    } else {
+                        self.a.synthesize(request.clone()).await?
+                    };
+                    (audio, 1 - chosen)
+                }
+            };
+            tracing::debug!(target: LOG_TARGET, backend = %self.names[voiced_by], "voiced utterance");
+            if let Some(status) = &self.status {
+                status.publish(PipelineEvent::TtsVariant {
+                    backend: self.names[voiced_by].clone(),
+                    text: request.text,
+                });
+            }
+            Ok(audio)
+        }
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone)]
+    struct RateClient(u32);
+
+    impl TtsClient for RateClient {
+        fn synthesize(&self, _request: TtsRequest) -> BoxFuture<'_, Result<TtsAudio, TtsError>> {
+            let sample_rate_hz = self.0;
+            async move {
+                Ok(TtsAudio {
+                    sample_rate_hz,
+                    channels: 1,
+                    pcm_i16: vec![0; 4],
+                })
+            }
+            .boxed()
+        }
+    }
+
+    #[derive(Clone)]
+    struct FailingClient;
+
+    impl TtsClient for FailingClient {
+        fn synthesize(&self, _request: TtsRequest) -> BoxFuture<'_, Result<TtsAudio, TtsError>> {
+            async { Err(TtsError::backend("test", "down")) }.boxed()
+        }
+    }
+
+    fn request(text: &str) -> TtsRequest {
+        TtsRequest {
+            text: text.into(),
+            voice: None,
+            prosody: None,
+            speaker: None,
+        }
+    }
+
+    async fn rates<A: TtsClient, B: TtsClient>(
+        client: &AbTestTtsClient<A, B>,
+        n: usize,
+    ) -> Vec<u32> {
+        let mut rates = Vec::new();
+        for _ in 0..n {
+            rates.push(
+                client
+                    .synthesize(request("hi"))
+                    .await
+                    .unwrap()
+                    .sample_rate_hz,
+            );
+        }
+        rates
+    }
+
+    #[tokio::test]
+    async fn splits_utterances_by_percentage() {
+        let half = AbTestTtsClient::new(RateClient(1), RateClient(2), 50);
+        assert_eq!(rates(&half, 4).await, [2, 1, 2, 1]);
+
+        let quarter = AbTestTtsClient::new(RateClient(1), RateClient(2), 25);
+        let voiced = rates(&quarter, 100).await;
+        assert_eq!(voiced.iter().filter(|&&r| r == 1).count(), 25);
+        assert_eq!(&voiced[..4], [2, 2, 2, 1]);
+
+        let all_b = AbTestTtsClient::new(RateClient(1), RateClient(2), 0);
+        assert_eq!(rates(&all_b, 3).await, [2, 2, 2]);
+        let all_a = AbTestTtsClient::new(RateClient(1), RateClient(2), 200);
+        assert_eq!(rates(&all_a, 3).await, [1, 1, 1]);
+    }
+
+    #[tokio::test]
+    async fn events_name_the_backend_that_voiced_the_utterance() {
+        let status = PipelineStatus::new();
+        let mut events = status.subscribe();
+        let client = AbTestTtsClient::new(FailingClient, RateClient(2), 100)
+            .with_names("elevenlabs", "piper")
+            .with_status(status.clone());
+
+        let audio = client.synthesize(request("hola")).await.unwrap();
+        assert_eq!(audio.sample_rate_hz, 2);
+        assert_eq!(
+            events.recv().await.unwrap(),
+            PipelineEvent::TtsVariant {
+                backend: "piper".into(),
+                text: "hola".into(),
+            }
+        );
+    }
+}
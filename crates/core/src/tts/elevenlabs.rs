@@ -1,43 +1,54 @@
+use crate::config::{DictionaryLocator, MAX_ELEVENLABS_DICTIONARIES};
+use crate::cost::{Billable, CostMeter};
+use crate::error::Classify;
 use crate::tts::{TtsAudio, TtsClient, TtsError, TtsRequest};
-use crate::util::{is_http_retryable, retry_with_backoff, RetryConfig};
+use crate::util::{
+    retry_with_backoff, HttpError, HttpRequest, ReqwestTransport, RetryConfig, SharedTransport,
+};
 use futures::future::BoxFuture;
 use futures::FutureExt;
-use reqwest::Client;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use std::io::Cursor;
+use symphonia::core::audio::Signal;
 use symphonia::core::codecs::{DecoderOptions, CODEC_TYPE_NULL};
 use symphonia::core::formats::FormatOptions;
 use symphonia::core::io::MediaSourceStream;
 use symphonia::core::meta::MetadataOptions;
 use symphonia::core::probe::Hint;
-use symphonia::core::audio::Signal;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
 pub enum ElevenLabsError {
     #[error("HTTP request failed: {0}")]
-    HttpRequest(#[from] reqwest::Error),
+    HttpRequest(#[from] HttpError),
     
     #[error("Audio decoding failed: {0}")]
     AudioDecoding(String),
     
     #[error("No audio data received")]
     NoAudioData,
+
+    #[error("HTTP error {0}: {1}")]
+    HttpStatus(u16, String),
 }
 
 #[derive(Clone)]
 pub struct ElevenLabsTtsClient {
-    client: Client,
+    transport: SharedTransport,
     api_key: String,
     base_url: String,
+    cost: Option<CostMeter>,
+    dictionaries: Vec<PronunciationDictionaryLocator>,
 }
 
 impl ElevenLabsTtsClient {
     pub fn new(api_key: String) -> Self {
         Self {
-            client: Client::new(),
+            transport: ReqwestTransport::default().shared(),
             api_key,
             base_url: "https://api.elevenlabs.io/v1".to_string(),
+            cost: None,
+            dictionaries: Vec::new(),
         }
     }
 
@@ -45,6 +56,125 @@ impl ElevenLabsTtsClient {
         self.base_url = base_url;
         self
     }
+
+    /// Sends requests through `transport` instead of the network, e.g. a
+    /// [`crate::util::MockTransport`] in tests.
+    pub fn with_transport(mut self, transport: SharedTransport) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    /// Counts the characters spoken against `cost`, and stops synthesizing
+    /// once its budget is spent.
+    pub fn with_cost_meter(mut self, cost: CostMeter) -> Self {
+        self.cost = Some(cost);
+        self
+    }
+
+    /// Pronounces words as `dictionaries` say, e.g. the names of a channel;
+    /// the API applies no more than the first three.
+    pub fn with_pronunciation_dictionaries(mut self, dictionaries: &[DictionaryLocator]) -> Self {
+        self.dictionaries = dictionaries
+            .iter()
+            .take(MAX_ELEVENLABS_DICTIONARIES)
+            .map(|d| PronunciationDictionaryLocator {
+                pronunciation_dictionary_id: d.dictionary_id.clone(),
+                version_id: d.version_id.clone(),
+            })
+            .collect();
+        self
+    }
+
+    /// Lists the voices available to this API key (premade and cloned).
+    pub async fn list_voices(&self) -> Result<Vec<ElevenLabsVoice>, ElevenLabsError> {
+        let request = HttpRequest::get(format!("{}/voices", self.base_url))
+            .with_header("xi-api-key", &self.api_key);
+        let response = self.transport.send(request).await?;
+
+        if !response.is_success() {
+            return Err(ElevenLabsError::HttpStatus(response.status, response.text()));
+        }
+
+        let body: VoicesResponse = response.json()?;
+        Ok(body.voices)
+    }
+
+    /// Clones a voice from `sample`, a recording of the speaker saved as
+    /// `file_name` (WAV, MP3 and most other audio files), and returns the
+    /// ID of the new voice. ElevenLabs expects the speaker's consent.
+    pub async fn add_voice(
+        &self,
+        name: &str,
+        description: &str,
+        file_name: &str,
+        sample: &[u8],
+    ) -> Result<String, ElevenLabsError> {
+        let fields = [
+            ("name", name),
+            ("description", description),
+            // Samples taken from a stream carry game audio and music.
+            ("remove_background_noise", "true"),
+        ];
+        let request = HttpRequest::post(format!("{}/voices/add", self.base_url))
+            .with_header("xi-api-key", &self.api_key)
+            .with_body(
+                format!("multipart/form-data; boundary={SAMPLE_BOUNDARY}"),
+                voice_form(&fields, file_name, sample),
+            );
+        let response = self.transport.send(request).await?;
+
+        if !response.is_success() {
+            return Err(ElevenLabsError::HttpStatus(response.status, response.text()));
+        }
+
+        let body: AddVoiceResponse = response.json()?;
+        Ok(body.voice_id)
+    }
+}
+
+const SAMPLE_BOUNDARY: &str = "twitch-translator-voice-sample";
+
+/// A `multipart/form-data` body of text `fields` and `sample` as `files`.
+fn voice_form(fields: &[(&str, &str)], file_name: &str, sample: &[u8]) -> Vec<u8> {
+    let mut body = Vec::with_capacity(sample.len() + 512);
+    for (name, value) in fields {
+        body.extend_from_slice(
+            format!(
+                "--{SAMPLE_BOUNDARY}\r\nContent-Disposition: form-data; name=\"{name}\"\r\n\r\n{value}\r\n"
+            )
+            .as_bytes(),
+        );
+    }
+    let file_name = file_name.replace(['"', '\r', '\n'], "_");
+    body.extend_from_slice(
+        format!(
+            "--{SAMPLE_BOUNDARY}\r\nContent-Disposition: form-data; name=\"files\"; \
+             filename=\"{file_name}\"\r\nContent-Type: application/octet-stream\r\n\r\n"
+        )
+        .as_bytes(),
+    );
+    body.extend_from_slice(sample);
+    body.extend_from_slice(format!("\r\n--{SAMPLE_BOUNDARY}--\r\n").as_bytes());
+    body
+}
+
+/// A voice as returned by the ElevenLabs `/voices` endpoint
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ElevenLabsVoice {
+    pub voice_id: String,
+    pub name: String,
+    #[serde(default)]
+    pub category: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct VoicesResponse {
+    voices: Vec<ElevenLabsVoice>,
+}
+
+#[derive(Deserialize)]
+struct AddVoiceResponse {
+    voice_id: String,
 }
 
 #[derive(Serialize, Clone)]
@@ -164,6 +294,10 @@ impl TtsClient for ElevenLabsTtsClient {
     fn synthesize(&self, request: TtsRequest) -> BoxFuture<'_, Result<TtsAudio, TtsError>> {
         let this = self.clone();
         async move {
+            if let Some(cost) = &this.cost {
+                cost.check_budget()?;
+            }
+
             // Determine the voice ID
             let voice_id = request
                 .voice
@@ -195,7 +329,8 @@ impl TtsClient for ElevenLabsTtsClient {
             let elevenlabs_request = ElevenLabsRequest {
                 text: request.text,
                 voice_settings,
-                pronunciation_dictionary_locators: None,
+                pronunciation_dictionary_locators: (!this.dictionaries.is_empty())
+                    .then(|| this.dictionaries.clone()),
             };
 
             // Configure retry with exponential backoff
@@ -203,76 +338,44 @@ impl TtsClient for ElevenLabsTtsClient {
             
             // Perform the TTS synthesis with retry logic
             let audio_data = retry_with_backoff(&retry_config, || {
-                let client = this.client.clone();
+                let transport = this.transport.clone();
                 let api_key = this.api_key.clone();
                 let request_body = elevenlabs_request.clone();
                 let url_str = url.clone();
                 
                 async move {
                     // Send the request
-                    let response = client
-                        .post(&url_str)
-                        .header("xi-api-key", &api_key)
-                        .header("Content-Type", "application/json")
-                        .header("Accept", "audio/mpeg")
-                        .json(&request_body)
-                        .send()
-                        .await
-                        .map_err(|e| TtsError::Other(format!("HTTP request failed: {}", e)))?;
-
-                    if !response.status().is_success() {
-                        let status = response.status();
-                        let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
-
-                        if status.as_u16() == 401
-                            || error_text.to_lowercase().contains("quota")
-                        {
-                            return Err(TtsError::QuotaExhausted);
-                        }
-
-                        if is_http_retryable(status.as_u16()) {
-                            return Err(TtsError::Other(format!(
-                                "HTTP error {}: {}",
-                                status, error_text
-                            )));
-                        }
-
-                        return Err(TtsError::Other(format!(
-                            "HTTP error {}: {}",
-                            status, error_text
-                        )));
+                    let request = HttpRequest::post(url_str)
+                        .with_header("xi-api-key", &api_key)
+                        .with_header("Content-Type", "application/json")
+                        .with_header("Accept", "audio/mpeg")
+                        .with_json(&request_body)?;
+                    let response = transport.send(request).await?;
+
+                    if !response.is_success() {
+                        return Err(TtsError::from_status(response.status, response.text()));
                     }
 
                     // Get the audio data
-                    let audio_data = response
-                        .bytes()
-                        .await
-                        .map_err(|e| TtsError::Other(format!("Failed to read audio data: {}", e)))?;
+                    let audio_data = response.body;
 
                     if audio_data.is_empty() {
-                        return Err(TtsError::Other("No audio data received from ElevenLabs".to_string()));
+                        return Err(TtsError::Decode("no audio data received from ElevenLabs".to_string()));
                     }
 
                     Ok(audio_data.to_vec())
                 }
-            }, |error| {
-                // Only retry on HTTP errors with retryable status codes
-                matches!(error, TtsError::Other(_))
-            }).await?;
-
-            // Decode the MP3 audio to PCM
-            match decode_mp3_to_pcm(audio_data) {
-                Ok(tts_audio) => Ok(tts_audio),
-                Err(e) => {
-                    tracing::warn!("Failed to decode MP3 audio, falling back to dummy audio: {}", e);
-                    // Fallback to dummy audio if decoding fails
-                    Ok(TtsAudio {
-                        sample_rate_hz: 22050,
-                        channels: 1,
-                        pcm_i16: vec![0; 22050],
-                    })
-                }
+            }, |error: &TtsError| error.is_retryable()).await?;
+            if let Some(cost) = &this.cost {
+                cost.record_chars(Billable::ElevenLabsChars, &elevenlabs_request.text);
             }
+
+            // Decode the MP3 audio to PCM; a clip that cannot be decoded is
+            // voiced by the fallback instead.
+            decode_mp3_to_pcm(audio_data).map_err(|e| match e {
+                ElevenLabsError::AudioDecoding(message) => TtsError::Decode(message),
+                other => TtsError::Decode(other.to_string()),
+            })
         }
         .boxed()
     }
@@ -289,4 +392,90 @@ fn map_energy_to_style(energy: f32) -> f32 {
     // Map energy to style (0.0 to 1.0)
     // Higher energy -> higher style (more emotional)
     energy.clamp(0.0, 1.0)
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::{HttpMethod, MockTransport};
+
+    #[tokio::test]
+    async fn lists_voices_through_transport() {
+        let mock = MockTransport::new().on(
+            HttpMethod::Get,
+            "/v1/voices",
+            200,
+            r#"{"voices":[{"voice_id":"abc","name":"Rachel","category":"premade"}]}"#,
+        );
+        let voices = ElevenLabsTtsClient::new("key".to_owned())
+            .with_transport(mock.clone().shared())
+            .list_voices()
+            .await
+            .unwrap();
+        assert_eq!(voices[0].voice_id, "abc");
+        assert_eq!(mock.requests()[0].header("xi-api-key"), Some("key"));
+    }
+
+    #[tokio::test]
+    async fn clones_a_voice_from_a_sample() {
+        let mock = MockTransport::new().on(
+            HttpMethod::Post,
+            "/v1/voices/add",
+            200,
+            r#"{"voice_id":"cloned","requires_verification":false}"#,
+        );
+        let voice_id = ElevenLabsTtsClient::new("key".to_owned())
+            .with_transport(mock.clone().shared())
+            .add_voice("streamer", "cloned for dubbing", "sample.wav", b"RIFF")
+            .await
+            .unwrap();
+        assert_eq!(voice_id, "cloned");
+        let request = &mock.requests()[0];
+        let body = String::from_utf8_lossy(request.body.as_ref().unwrap()).into_owned();
+        assert!(body.contains("name=\"name\"\r\n\r\nstreamer\r\n"));
+        assert!(body.contains("filename=\"sample.wav\""));
+        assert!(body.contains("RIFF"));
+    }
+
+    #[tokio::test]
+    async fn rejected_synthesis_is_told_apart_and_not_retried() {
+        let request = || TtsRequest {
+            text: "hi".to_owned(),
+            voice: None,
+            prosody: None,
+            speaker: None,
+        };
+        let mock = MockTransport::new().on(HttpMethod::Post, "/text-to-speech/", 401, "unauthorized");
+        let client = ElevenLabsTtsClient::new("key".to_owned()).with_transport(mock.clone().shared());
+        assert!(matches!(client.synthesize(request()).await, Err(TtsError::Unauthorized(_))));
+        assert_eq!(mock.requests().len(), 1);
+
+        let body = r#"{"detail":{"status":"quota_exceeded","message":"This request exceeds your quota"}}"#;
+        let mock = MockTransport::new().on(HttpMethod::Post, "/text-to-speech/", 401, body);
+        let client = ElevenLabsTtsClient::new("key".to_owned()).with_transport(mock.clone().shared());
+        assert!(matches!(client.synthesize(request()).await, Err(TtsError::QuotaExhausted)));
+        assert_eq!(mock.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn sends_pronunciation_dictionaries() {
+        let mock = MockTransport::new().on(HttpMethod::Post, "/text-to-speech/", 401, "unauthorized");
+        let dictionary: DictionaryLocator = "dict:v2".parse().unwrap();
+        let dictionaries: Vec<_> = std::iter::repeat_n(dictionary, 4).collect();
+        let client = ElevenLabsTtsClient::new("key".to_owned())
+            .with_transport(mock.clone().shared())
+            .with_pronunciation_dictionaries(&dictionaries);
+        let request = TtsRequest {
+            text: "hi".to_owned(),
+            voice: None,
+            prosody: None,
+            speaker: None,
+        };
+        let _ = client.synthesize(request).await;
+        let body: serde_json::Value =
+            serde_json::from_slice(mock.requests()[0].body.as_ref().unwrap()).unwrap();
+        let locators = body["pronunciation_dictionary_locators"].as_array().unwrap();
+        assert_eq!(locators.len(), MAX_ELEVENLABS_DICTIONARIES);
+        assert_eq!(locators[0]["pronunciation_dictionary_id"], "dict");
+        assert_eq!(locators[0]["version_id"], "v2");
+    }
+}
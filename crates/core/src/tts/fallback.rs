@@ -1,3 +1,5 @@
+use crate::control::ControlHandle;
+use crate::status::{PipelineEvent, PipelineStatus, Stage};
 use crate::tts::{TtsAudio, TtsClient, TtsError, TtsRequest};
 use futures::future::BoxFuture;
 use futures::FutureExt;
@@ -18,6 +20,9 @@ where
     primary: P,
     local: L,
     state: Arc<FallbackState>,
+    status: Option<PipelineStatus>,
+    control: Option<ControlHandle>,
+    primary_deadline: Option<Duration>,
 }
 
 struct FallbackState {
@@ -38,6 +43,37 @@ where
                 quota_exhausted: AtomicBool::new(false),
                 exhausted_at: Mutex::new(None),
             }),
+            status: None,
+            control: None,
+            primary_deadline: None,
+        }
+    }
+
+    /// Mirrors the fallback state into `status` so it shows up on the status endpoint.
+    pub fn with_status(mut self, status: PipelineStatus) -> Self {
+        status.set_tts_fallback_active(self.is_using_fallback());
+        self.status = Some(status);
+        self
+    }
+
+    /// Uses the local voice for every request while `control` prefers it.
+    pub fn with_control(mut self, control: ControlHandle) -> Self {
+        self.control = Some(control);
+        self
+    }
+
+    /// Speaks a request with the local voice when the primary has not
+    /// answered it within `deadline`. The local voice is the last resort
+    /// and gets no deadline of its own.
+    pub fn with_primary_deadline(mut self, deadline: Duration) -> Self {
+        self.primary_deadline = Some(deadline);
+        self
+    }
+
+    fn set_quota_exhausted(&self, exhausted: bool) {
+        self.state.quota_exhausted.store(exhausted, Ordering::Relaxed);
+        if let Some(status) = &self.status {
+            status.set_tts_fallback_active(exhausted);
         }
     }
 
@@ -46,7 +82,7 @@ where
     }
 
     pub fn reset_quota_flag(&self) {
-        self.state.quota_exhausted.store(false, Ordering::Relaxed);
+        self.set_quota_exhausted(false);
         if let Ok(mut exhausted_at) = self.state.exhausted_at.try_lock() {
             *exhausted_at = None;
         }
@@ -66,6 +102,9 @@ where
 {
     fn synthesize(&self, request: TtsRequest) -> BoxFuture<'_, Result<TtsAudio, TtsError>> {
         async move {
+            if self.control.as_ref().is_some_and(|c| c.prefers_local_tts()) {
+                return self.local.synthesize(request).await;
+            }
             if self.state.quota_exhausted.load(Ordering::Relaxed) {
                 let should_retry = {
                     let exhausted_at = self.state.exhausted_at.lock().await;
@@ -78,17 +117,17 @@ where
                     tracing::warn!(target: LOG_TARGET, "Retrying ElevenLabs after 5m cooldown...");
                     match self.primary.synthesize(request.clone()).await {
                         Ok(audio) => {
-                            self.state.quota_exhausted.store(false, Ordering::Relaxed);
+                            self.set_quota_exhausted(false);
                             *self.state.exhausted_at.lock().await = None;
                             tracing::info!(target: LOG_TARGET, "ElevenLabs recovered, switching back to cloud TTS");
                             return Ok(audio);
                         }
-                        Err(TtsError::QuotaExhausted) => {
+                        Err(e) if e.disables_backend() => {
                             *self.state.exhausted_at.lock().await = Some(Instant::now());
                             return self.local.synthesize(request).await;
                         }
                         Err(e) => {
-                            tracing::warn!(target: LOG_TARGET, "ElevenLabs error (not quota), falling back to Piper for this request: {e}");
+                            tracing::warn!(target: LOG_TARGET, "ElevenLabs error, falling back to Piper for this request: {e}");
                             return self.local.synthesize(request).await;
                         }
                     }
@@ -97,16 +136,35 @@ where
                 return self.local.synthesize(request).await;
             }
 
-            match self.primary.synthesize(request.clone()).await {
+            let primary = self.primary.synthesize(request.clone());
+            let result = match self.primary_deadline {
+                Some(deadline) => match tokio::time::timeout(deadline, primary).await {
+                    Ok(result) => result,
+                    Err(_) => {
+                        tracing::warn!(target: LOG_TARGET, "ElevenLabs took longer than {deadline:?}, falling back to Piper for this request");
+                        if let Some(status) = &self.status {
+                            status.publish(PipelineEvent::DeadlineExceeded {
+                                stage: Stage::Tts,
+                                deadline_ms: deadline.as_millis() as u64,
+                            });
+                        }
+                        return self.local.synthesize(request).await;
+                    }
+                },
+                None => primary.await,
+            };
+            match result {
                 Ok(audio) => Ok(audio),
-                Err(TtsError::QuotaExhausted) => {
-                    tracing::warn!(target: LOG_TARGET, "ElevenLabs quota exhausted, switching to local Piper TTS");
-                    self.state.quota_exhausted.store(true, Ordering::Relaxed);
+                // Out of budget stops the session rather than switching voices.
+                Err(e @ TtsError::BudgetExceeded(_)) => Err(e),
+                Err(e) if e.disables_backend() => {
+                    tracing::warn!(target: LOG_TARGET, "ElevenLabs unavailable ({e}), switching to local Piper TTS");
+                    self.set_quota_exhausted(true);
                     *self.state.exhausted_at.lock().await = Some(Instant::now());
                     self.local.synthesize(request).await
                 }
                 Err(e) => {
-                    tracing::warn!(target: LOG_TARGET, "ElevenLabs error (not quota), falling back to Piper for this request: {e}");
+                    tracing::warn!(target: LOG_TARGET, "ElevenLabs error, falling back to Piper for this request: {e}");
                     self.local.synthesize(request).await
                 }
             }
@@ -178,7 +236,7 @@ mod tests {
             &self,
             _request: TtsRequest,
         ) -> BoxFuture<'_, Result<TtsAudio, TtsError>> {
-            async { Err(TtsError::Other("network timeout".into())) }.boxed()
+            async { Err(TtsError::HttpStatus(503, "Service Unavailable".into())) }.boxed()
         }
     }
 
@@ -187,6 +245,7 @@ mod tests {
             text: "hello".into(),
             voice: None,
             prosody: None,
+            speaker: None,
         }
     }
 
@@ -245,6 +304,72 @@ mod tests {
         assert!(!client.is_using_fallback());
     }
 
+    #[tokio::test]
+    async fn fallback_state_is_mirrored_into_status() {
+        let status = PipelineStatus::new();
+        let client = FallbackTtsClient::new(QuotaClient, StubLocalClient).with_status(status.clone());
+        assert!(!status.snapshot().tts_fallback_active);
+
+        client.synthesize(make_request()).await.unwrap();
+        assert!(status.snapshot().tts_fallback_active);
+
+        client.reset_quota_flag();
+        assert!(!status.snapshot().tts_fallback_active);
+    }
+
+    #[tokio::test]
+    async fn control_can_switch_to_the_local_voice() {
+        let control = ControlHandle::new(Default::default());
+        let client = FallbackTtsClient::new(OkClient, StubLocalClient).with_control(control.clone());
+
+        control.set_prefer_local_tts(true);
+        let result = client.synthesize(make_request()).await.unwrap();
+        assert_eq!(result.sample_rate_hz, 22050);
+
+        control.set_prefer_local_tts(false);
+        let result = client.synthesize(make_request()).await.unwrap();
+        assert_eq!(result.sample_rate_hz, 44100);
+    }
+
+    #[derive(Clone)]
+    struct SlowClient;
+
+    impl TtsClient for SlowClient {
+        fn synthesize(
+            &self,
+            request: TtsRequest,
+        ) -> BoxFuture<'_, Result<TtsAudio, TtsError>> {
+            async move {
+                tokio::time::sleep(Duration::from_millis(200)).await;
+                OkClient.synthesize(request).await
+            }
+            .boxed()
+        }
+    }
+
+    #[tokio::test]
+    async fn slow_primary_answers_are_voiced_locally() {
+        let status = PipelineStatus::new();
+        let mut events = status.subscribe();
+        let client = FallbackTtsClient::new(SlowClient, StubLocalClient)
+            .with_primary_deadline(Duration::from_millis(20))
+            .with_status(status);
+        let result = client.synthesize(make_request()).await.unwrap();
+        assert_eq!(result.sample_rate_hz, 22050);
+        assert!(!client.is_using_fallback());
+        assert!(matches!(
+            events.try_recv().unwrap(),
+            PipelineEvent::DeadlineExceeded {
+                stage: Stage::Tts,
+                deadline_ms: 20
+            }
+        ));
+
+        let client = FallbackTtsClient::new(SlowClient, StubLocalClient);
+        let result = client.synthesize(make_request()).await.unwrap();
+        assert_eq!(result.sample_rate_hz, 44100);
+    }
+
     #[tokio::test]
     async fn no_retry_before_interval_elapsed() {
         let client = FallbackTtsClient::new(OkClient, StubLocalClient);
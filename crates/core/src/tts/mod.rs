@@ -1,16 +1,28 @@
+#[cfg(feature = "native")]
+mod ab_test;
 mod basic;
+#[cfg(feature = "native")]
 mod elevenlabs;
+#[cfg(feature = "native")]
 mod fallback;
+#[cfg(feature = "native")]
 mod piper;
+mod pronounce;
 
 use crate::emotion::ProsodyFeatures;
 use futures::future::BoxFuture;
 use serde::{Deserialize, Serialize};
 
+#[cfg(feature = "native")]
+pub use ab_test::AbTestTtsClient;
 pub use basic::BasicTtsClient;
-pub use elevenlabs::ElevenLabsTtsClient;
+#[cfg(feature = "native")]
+pub use elevenlabs::{ElevenLabsError, ElevenLabsTtsClient, ElevenLabsVoice};
+#[cfg(feature = "native")]
 pub use fallback::FallbackTtsClient;
-pub use piper::PiperTtsClient;
+#[cfg(feature = "native")]
+pub use piper::{list_piper_voices, PiperTtsClient, PiperVoice};
+pub use pronounce::Respellings;
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
 pub struct VoiceId(pub String);
@@ -20,6 +32,10 @@ pub struct TtsRequest {
     pub text: String,
     pub voice: Option<VoiceId>,
     pub prosody: Option<ProsodyFeatures>,
+    /// Diarized speaker of the speech being dubbed, for clients that give
+    /// each speaker a voice of their own.
+    #[serde(default)]
+    pub speaker: Option<String>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
@@ -29,16 +45,77 @@ pub struct TtsAudio {
     pub pcm_i16: Vec<i16>,
 }
 
+/// Why a client could not voice a request, told apart so that fallback and
+/// retries treat every backend alike (see [`crate::error::Classify`])
 #[derive(thiserror::Error, Debug)]
 pub enum TtsError {
     #[error("tts not implemented")]
     NotImplemented,
 
-    #[error("quota exhausted (upstream returned 401/quota)")]
+    /// The account has no characters or credits left.
+    #[error("quota exhausted")]
     QuotaExhausted,
 
-    #[error("{0}")]
-    Other(String),
+    /// The API key was rejected.
+    #[error("unauthorized: {0}")]
+    Unauthorized(String),
+
+    /// Too many requests at once or in a short time.
+    #[error("rate limited: {0}")]
+    RateLimited(String),
+
+    #[error("network error: {0}")]
+    Network(#[from] crate::util::HttpError),
+
+    #[error("http error {0}: {1}")]
+    HttpStatus(u16, String),
+
+    /// The backend answered with audio that could not be decoded, or none.
+    #[error("audio decoding failed: {0}")]
+    Decode(String),
+
+    /// A failure particular to one backend, such as the Piper process
+    /// exiting early.
+    #[error("{backend}: {message}")]
+    Backend {
+        backend: &'static str,
+        message: String,
+    },
+
+    #[error(transparent)]
+    BudgetExceeded(#[from] crate::cost::BudgetExceeded),
+}
+
+impl TtsError {
+    /// The error for a non-success HTTP `status` with response `body` from a
+    /// TTS API. Quota errors are recognised by the body, as services report
+    /// them under differing statuses (ElevenLabs under 401).
+    pub fn from_status(status: u16, body: String) -> Self {
+        if body.to_lowercase().contains("quota") {
+            Self::QuotaExhausted
+        } else {
+            match status {
+                401 | 403 => Self::Unauthorized(body),
+                429 => Self::RateLimited(body),
+                _ => Self::HttpStatus(status, body),
+            }
+        }
+    }
+
+    /// A failure of `backend` that fits no other variant.
+    pub fn backend(backend: &'static str, message: impl Into<String>) -> Self {
+        Self::Backend {
+            backend,
+            message: message.into(),
+        }
+    }
+
+    /// Whether the backend will refuse the following requests too, so a
+    /// fallback should stand in until it recovers rather than for this
+    /// request alone.
+    pub fn disables_backend(&self) -> bool {
+        matches!(self, Self::QuotaExhausted | Self::Unauthorized(_))
+    }
 }
 
 pub trait TtsClient: Send + Sync {
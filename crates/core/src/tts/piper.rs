@@ -1,7 +1,10 @@
-use crate::tts::{TtsAudio, TtsClient, TtsError, TtsRequest};
+use crate::config::PiperConfig;
+use crate::tts::{Respellings, TtsAudio, TtsClient, TtsError, TtsRequest};
 use futures::future::BoxFuture;
 use futures::FutureExt;
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 use tokio::io::AsyncWriteExt;
 use tokio::process::Command;
 
@@ -13,6 +16,14 @@ const WAV_HEADER_BYTES: usize = 44;
 pub struct PiperTtsClient {
     piper_binary: PathBuf,
     model_path: PathBuf,
+    /// Speaker of a multi-speaker model to speak with; the model's default
+    /// when `None`.
+    speaker_id: Option<u32>,
+    /// Speakers the model has.
+    speakers: u32,
+    /// Speaker ID given to each diarized speaker so far, shared by clones.
+    assigned: Arc<Mutex<HashMap<String, u32>>>,
+    respellings: Respellings,
 }
 
 impl PiperTtsClient {
@@ -21,58 +32,111 @@ impl PiperTtsClient {
         Self {
             piper_binary,
             model_path,
+            speaker_id: None,
+            speakers: 1,
+            assigned: Arc::default(),
+            respellings: Respellings::default(),
         }
     }
+
+    /// A client for the binary and model of `config`, speaking with its
+    /// speaker ID and, if the model has several speakers, giving each
+    /// diarized speaker one of them.
+    #[must_use]
+    pub fn from_config(config: &PiperConfig) -> Self {
+        let model_path = PathBuf::from(&config.model_path);
+        let speakers = model_speakers(&model_path);
+        let client = Self::new(config.binary_path.clone().into(), model_path);
+        client.with_speakers(config.speaker_id, speakers)
+    }
+
+    /// Speaks with `speaker_id` of a model with `speakers` speakers. Each
+    /// diarized speaker gets the next ID from there on, in the order they
+    /// first speak, wrapping around after the last.
+    #[must_use]
+    pub fn with_speakers(mut self, speaker_id: Option<u32>, speakers: u32) -> Self {
+        self.speaker_id = speaker_id;
+        self.speakers = speakers.max(1);
+        self
+    }
+
+    /// Says the words of `respellings` as they are respelled there.
+    #[must_use]
+    pub fn with_respellings(mut self, respellings: Respellings) -> Self {
+        self.respellings = respellings;
+        self
+    }
+
+    /// The speaker ID to speak the words of diarized `speaker` with.
+    fn speaker_for(&self, speaker: Option<&str>) -> Option<u32> {
+        let Some(speaker) = speaker.filter(|_| self.speakers > 1) else {
+            return self.speaker_id;
+        };
+        let mut assigned = match self.assigned.lock() {
+            Ok(g) => g,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        let next = assigned.len() as u32;
+        let first = self.speaker_id.unwrap_or(0);
+        let id = *assigned
+            .entry(speaker.to_owned())
+            .or_insert_with(|| first.wrapping_add(next) % self.speakers);
+        Some(id)
+    }
 }
 
 impl TtsClient for PiperTtsClient {
     fn synthesize(&self, request: TtsRequest) -> BoxFuture<'_, Result<TtsAudio, TtsError>> {
         let piper_binary = self.piper_binary.clone();
         let model_path = self.model_path.clone();
-        let text = request.text;
+        let speaker_id = self.speaker_for(request.speaker.as_deref());
+        let text = self.respellings.apply(&request.text).into_owned();
 
         async move {
-            let mut child = Command::new(&piper_binary)
-                .arg("--model")
-                .arg(&model_path)
-                .arg("--output_raw")
+            let mut command = Command::new(&piper_binary);
+            command.arg("--model").arg(&model_path).arg("--output_raw");
+            if let Some(id) = speaker_id {
+                command.arg("--speaker").arg(id.to_string());
+            }
+            let mut child = command
                 .stdin(std::process::Stdio::piped())
                 .stdout(std::process::Stdio::piped())
                 .stderr(std::process::Stdio::piped())
                 .spawn()
                 .map_err(|e| {
                     let path = piper_binary.display();
-                    TtsError::Other(format!("failed to spawn piper at {path}: {e}"))
+                    TtsError::backend("piper", format!("failed to spawn {path}: {e}"))
                 })?;
 
             {
                 let stdin = child
                     .stdin
                     .as_mut()
-                    .ok_or_else(|| TtsError::Other("failed to open piper stdin".into()))?;
+                    .ok_or_else(|| TtsError::backend("piper", "failed to open stdin"))?;
                 stdin
                     .write_all(text.as_bytes())
                     .await
-                    .map_err(|e| TtsError::Other(format!("piper stdin write failed: {e}")))?;
+                    .map_err(|e| TtsError::backend("piper", format!("stdin write failed: {e}")))?;
             }
             child.stdin.take();
 
             let output = child
                 .wait_with_output()
                 .await
-                .map_err(|e| TtsError::Other(format!("piper process failed: {e}")))?;
+                .map_err(|e| TtsError::backend("piper", format!("process failed: {e}")))?;
 
             if !output.status.success() {
                 let stderr = String::from_utf8_lossy(&output.stderr);
                 let status = output.status;
-                return Err(TtsError::Other(format!(
-                    "piper exited with {status}: {stderr}"
-                )));
+                return Err(TtsError::backend(
+                    "piper",
+                    format!("exited with {status}: {stderr}"),
+                ));
             }
 
             let raw_pcm = &output.stdout;
             if raw_pcm.is_empty() {
-                return Err(TtsError::Other("piper produced no audio output".into()));
+                return Err(TtsError::Decode("piper produced no audio output".into()));
             }
 
             let pcm_bytes = if raw_pcm.len() > WAV_HEADER_BYTES && &raw_pcm[..4] == b"RIFF" {
@@ -87,7 +151,7 @@ impl TtsClient for PiperTtsClient {
                 .collect();
 
             if pcm_i16.is_empty() {
-                return Err(TtsError::Other("piper produced empty PCM data".into()));
+                return Err(TtsError::Decode("piper produced empty PCM data".into()));
             }
 
             Ok(TtsAudio {
@@ -99,3 +163,99 @@ impl TtsClient for PiperTtsClient {
         .boxed()
     }
 }
+
+/// A Piper voice model found on disk
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PiperVoice {
+    pub name: String,
+    pub model_path: PathBuf,
+    /// Language code from the model's `.onnx.json` config, e.g. `en_US`.
+    pub language: Option<String>,
+    /// Speakers the model can speak as, by `--piper-speaker-id` 0 and up.
+    pub speakers: u32,
+}
+
+/// The `.onnx.json` config next to the model at `path`, if readable.
+fn model_config(path: &Path) -> Option<serde_json::Value> {
+    let mut config = path.to_path_buf().into_os_string();
+    config.push(".json");
+    let raw = std::fs::read(config).ok()?;
+    serde_json::from_slice(&raw).ok()
+}
+
+/// How many speakers the model at `path` has; one unless its config says
+/// otherwise.
+fn model_speakers(path: &Path) -> u32 {
+    model_config(path)
+        .and_then(|v| v["num_speakers"].as_u64())
+        .map_or(1, |n| u32::try_from(n).unwrap_or(u32::MAX).max(1))
+}
+
+/// Lists the `*.onnx` voice models in `dir`, sorted by name.
+pub fn list_piper_voices(dir: &Path) -> std::io::Result<Vec<PiperVoice>> {
+    let mut voices = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("onnx") {
+            continue;
+        }
+        let Some(name) = path.file_stem().and_then(|s| s.to_str()) else {
+            continue;
+        };
+
+        let language =
+            model_config(&path).and_then(|v| v["language"]["code"].as_str().map(str::to_owned));
+
+        voices.push(PiperVoice {
+            name: name.to_owned(),
+            model_path: path.clone(),
+            language,
+            speakers: model_speakers(&path),
+        });
+    }
+    voices.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(voices)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lists_onnx_models_with_language() {
+        let dir = std::env::temp_dir().join(format!("piper-voices-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("de_DE-thorsten-medium.onnx"), b"").unwrap();
+        std::fs::write(
+            dir.join("de_DE-thorsten-medium.onnx.json"),
+            br#"{"language":{"code":"de_DE"},"num_speakers":4}"#,
+        )
+        .unwrap();
+        std::fs::write(dir.join("custom.onnx"), b"").unwrap();
+        std::fs::write(dir.join("notes.txt"), b"").unwrap();
+
+        let voices = list_piper_voices(&dir).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(voices.len(), 2);
+        assert_eq!(voices[0].name, "custom");
+        assert_eq!(voices[0].language, None);
+        assert_eq!(voices[1].name, "de_DE-thorsten-medium");
+        assert_eq!(voices[1].language.as_deref(), Some("de_DE"));
+        assert_eq!((voices[0].speakers, voices[1].speakers), (1, 4));
+    }
+
+    #[test]
+    fn diarized_speakers_get_speakers_of_their_own() {
+        let client = PiperTtsClient::new("piper".into(), "voice.onnx".into());
+        assert_eq!(client.speaker_for(Some("A")), None);
+
+        let client = client.with_speakers(Some(2), 3);
+        assert_eq!(client.speaker_for(None), Some(2));
+        assert_eq!(client.speaker_for(Some("A")), Some(2));
+        assert_eq!(client.speaker_for(Some("B")), Some(0));
+        assert_eq!(client.clone().speaker_for(Some("A")), Some(2));
+        assert_eq!(client.speaker_for(Some("C")), Some(1));
+        assert_eq!(client.speaker_for(Some("D")), Some(2));
+    }
+}
@@ -0,0 +1,78 @@
+//! Respelling names for local TTS
+//!
+//! Piper reads a channel's names by the spelling rules of the target
+//! language, so "xQc" comes out as one garbled word. [`Respellings`]
+//! replaces each listed word with how it should sound, written the way the
+//! voice reads it, before the text reaches the model.
+
+use regex::{Captures, Regex};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+
+/// What is said in place of listed words, matched as whole words
+/// regardless of case
+#[derive(Clone, Debug, Default)]
+pub struct Respellings {
+    spoken: HashMap<String, String>,
+    pattern: Option<Regex>,
+}
+
+impl Respellings {
+    pub fn new(respellings: &BTreeMap<String, String>) -> Self {
+        let spoken: HashMap<String, String> = respellings
+            .iter()
+            .map(|(word, spoken)| (word.trim().to_lowercase(), spoken.trim().to_owned()))
+            .filter(|(word, spoken)| !word.is_empty() && !spoken.is_empty())
+            .collect();
+        if spoken.is_empty() {
+            return Self::default();
+        }
+        // Longest first, so "Elden Ring" wins over "Elden".
+        let mut words: Vec<&String> = spoken.keys().collect();
+        words.sort_by_key(|w| std::cmp::Reverse(w.len()));
+        let alternatives: Vec<String> = words.iter().map(|w| regex::escape(w)).collect();
+        let pattern = format!(r"(?i)\b(?:{})\b", alternatives.join("|"));
+        Self {
+            pattern: Some(Regex::new(&pattern).expect("escaped words form a valid pattern")),
+            spoken,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pattern.is_none()
+    }
+
+    /// `text` with every listed word respelled.
+    pub fn apply<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        let Some(pattern) = &self.pattern else {
+            return Cow::Borrowed(text);
+        };
+        pattern.replace_all(text, |caps: &Captures| {
+            let word = caps[0].to_lowercase();
+            self.spoken
+                .get(&word)
+                .cloned()
+                .unwrap_or_else(|| caps[0].to_owned())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn respells_whole_words_regardless_of_case() {
+        let respellings = Respellings::new(&BTreeMap::from([
+            ("xQc".to_owned(), "ex cue see".to_owned()),
+            ("Elden".to_owned(), "Ellden".to_owned()),
+            ("Elden Ring".to_owned(), "Ellden Ring".to_owned()),
+        ]));
+        assert_eq!(
+            respellings.apply("XQC spielt Elden Ring mit xqcFan"),
+            "ex cue see spielt Ellden Ring mit xqcFan"
+        );
+        assert!(matches!(respellings.apply("nichts"), Cow::Borrowed(_)));
+        assert!(Respellings::new(&BTreeMap::new()).is_empty());
+    }
+}
@@ -0,0 +1,386 @@
+//! HTTP transport abstraction
+//!
+//! The network clients (Twitch ingest, DeepL, ElevenLabs) send requests through
+//! an [`HttpTransport`] rather than calling reqwest directly. Production code
+//! uses [`ReqwestTransport`]; tests inject a [`MockTransport`] with canned
+//! responses, so nothing needs real credentials or a live channel.
+
+#[cfg(feature = "native")]
+use crate::config::{HttpConfig, HttpService};
+use bytes::Bytes;
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+#[derive(thiserror::Error, Debug)]
+pub enum HttpError {
+    #[cfg(feature = "native")]
+    #[error("http request failed: {0}")]
+    Transport(#[from] reqwest::Error),
+
+    #[error("invalid json: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[cfg(feature = "native")]
+    #[error("invalid proxy '{0}': {1}")]
+    InvalidProxy(String, reqwest::Error),
+
+    #[error("failed to load CA bundle {0}: {1}")]
+    CaBundle(String, String),
+
+    #[error("no mock response for {0}")]
+    Unmatched(String),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HttpMethod {
+    Get,
+    Post,
+}
+
+impl fmt::Display for HttpMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HttpMethod::Get => f.write_str("GET"),
+            HttpMethod::Post => f.write_str("POST"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HttpRequest {
+    pub method: HttpMethod,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Option<Bytes>,
+}
+
+impl HttpRequest {
+    pub fn get(url: impl Into<String>) -> Self {
+        Self::new(HttpMethod::Get, url)
+    }
+
+    pub fn post(url: impl Into<String>) -> Self {
+        Self::new(HttpMethod::Post, url)
+    }
+
+    fn new(method: HttpMethod, url: impl Into<String>) -> Self {
+        Self {
+            method,
+            url: url.into(),
+            headers: Vec::new(),
+            body: None,
+        }
+    }
+
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Serializes `body` as the JSON request body and sets `Content-Type`.
+    pub fn with_json<T: Serialize>(mut self, body: &T) -> Result<Self, HttpError> {
+        self.body = Some(serde_json::to_vec(body)?.into());
+        if self.header("content-type").is_none() {
+            self = self.with_header("Content-Type", "application/json");
+        }
+        Ok(self)
+    }
+
+    /// Sets a raw request body of `content_type`, e.g. a multipart form.
+    pub fn with_body(mut self, content_type: impl Into<String>, body: impl Into<Bytes>) -> Self {
+        self.body = Some(body.into());
+        self.with_header("Content-Type", content_type)
+    }
+
+    /// Case-insensitive header lookup.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub body: Bytes,
+}
+
+impl HttpResponse {
+    pub fn new(status: u16, body: impl Into<Bytes>) -> Self {
+        Self {
+            status,
+            body: body.into(),
+        }
+    }
+
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+
+    pub fn json<T: DeserializeOwned>(&self) -> Result<T, HttpError> {
+        Ok(serde_json::from_slice(&self.body)?)
+    }
+}
+
+pub trait HttpTransport: Send + Sync {
+    fn send(&self, request: HttpRequest) -> BoxFuture<'_, Result<HttpResponse, HttpError>>;
+}
+
+pub type SharedTransport = Arc<dyn HttpTransport>;
+
+/// How long an idle connection to a segment host is kept for reuse.
+#[cfg(feature = "native")]
+const SEGMENT_POOL_IDLE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(90);
+
+/// Interval of keepalive probes and HTTP/2 pings to segment hosts.
+#[cfg(feature = "native")]
+const SEGMENT_KEEPALIVE: std::time::Duration = std::time::Duration::from_secs(15);
+
+/// How long a ping may go unanswered before the connection is dropped.
+#[cfg(feature = "native")]
+const SEGMENT_KEEPALIVE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Sends requests over the network with reqwest.
+#[cfg(feature = "native")]
+#[derive(Clone, Debug, Default)]
+pub struct ReqwestTransport {
+    client: reqwest::Client,
+}
+
+#[cfg(feature = "native")]
+impl ReqwestTransport {
+    pub fn new(client: reqwest::Client) -> Self {
+        Self { client }
+    }
+
+    /// Builds a client with the proxy, CA bundle and `service` timeout from `config`.
+    pub fn from_config(config: &HttpConfig, service: HttpService) -> Result<Self, HttpError> {
+        let mut builder = reqwest::Client::builder().timeout(config.timeout(service));
+
+        if service == HttpService::Twitch {
+            // Segments come from the same few CDN hosts every couple of
+            // seconds; keep those connections open and pinged so each fetch
+            // skips the handshake and a dead one is noticed between fetches.
+            builder = builder
+                .pool_idle_timeout(SEGMENT_POOL_IDLE_TIMEOUT)
+                .tcp_keepalive(SEGMENT_KEEPALIVE)
+                .http2_keep_alive_interval(SEGMENT_KEEPALIVE)
+                .http2_keep_alive_timeout(SEGMENT_KEEPALIVE_TIMEOUT)
+                .http2_keep_alive_while_idle(true)
+                .http2_adaptive_window(true);
+        }
+
+        if let Some(proxy) = &config.proxy {
+            let proxy = reqwest::Proxy::all(proxy)
+                .map_err(|e| HttpError::InvalidProxy(proxy.clone(), e))?;
+            builder = builder.proxy(proxy);
+        }
+
+        if let Some(path) = &config.ca_bundle {
+            let ca_error = |e: String| HttpError::CaBundle(path.display().to_string(), e);
+            let pem = std::fs::read(path).map_err(|e| ca_error(e.to_string()))?;
+            let certs =
+                reqwest::Certificate::from_pem_bundle(&pem).map_err(|e| ca_error(e.to_string()))?;
+            if certs.is_empty() {
+                return Err(ca_error("no certificates found".to_owned()));
+            }
+            builder = builder.tls_certs_merge(certs);
+        }
+
+        Ok(Self::new(builder.build()?))
+    }
+
+    pub fn shared(self) -> SharedTransport {
+        Arc::new(self)
+    }
+}
+
+#[cfg(feature = "native")]
+impl HttpTransport for ReqwestTransport {
+    fn send(&self, request: HttpRequest) -> BoxFuture<'_, Result<HttpResponse, HttpError>> {
+        async move {
+            let mut builder = match request.method {
+                HttpMethod::Get => self.client.get(&request.url),
+                HttpMethod::Post => self.client.post(&request.url),
+            };
+            for (name, value) in &request.headers {
+                builder = builder.header(name, value);
+            }
+            if let Some(body) = request.body {
+                builder = builder.body(body);
+            }
+
+            let response = builder.send().await?;
+            let status = response.status().as_u16();
+            let body = response.bytes().await?;
+            Ok(HttpResponse { status, body })
+        }
+        .boxed()
+    }
+}
+
+struct MockRoute {
+    method: HttpMethod,
+    url_contains: String,
+    responses: VecDeque<HttpResponse>,
+}
+
+/// Serves canned responses and records every request it receives.
+///
+/// A request is answered by the first route whose method matches and whose
+/// pattern occurs in the URL. Responses registered for the same route are
+/// returned in order, the last one repeating, so a test can script e.g. a 503
+/// followed by a success. Unmatched requests fail with [`HttpError::Unmatched`].
+#[derive(Clone, Default)]
+pub struct MockTransport {
+    routes: Arc<Mutex<Vec<MockRoute>>>,
+    requests: Arc<Mutex<Vec<HttpRequest>>>,
+}
+
+impl MockTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn on(
+        self,
+        method: HttpMethod,
+        url_contains: &str,
+        status: u16,
+        body: impl Into<Bytes>,
+    ) -> Self {
+        let response = HttpResponse::new(status, body);
+        {
+            let mut routes = lock(&self.routes);
+            match routes
+                .iter_mut()
+                .find(|r| r.method == method && r.url_contains == url_contains)
+            {
+                Some(route) => route.responses.push_back(response),
+                None => routes.push(MockRoute {
+                    method,
+                    url_contains: url_contains.to_owned(),
+                    responses: VecDeque::from([response]),
+                }),
+            }
+        }
+        self
+    }
+
+    /// Requests received so far, in order.
+    pub fn requests(&self) -> Vec<HttpRequest> {
+        lock(&self.requests).clone()
+    }
+
+    pub fn shared(self) -> SharedTransport {
+        Arc::new(self)
+    }
+}
+
+impl HttpTransport for MockTransport {
+    fn send(&self, request: HttpRequest) -> BoxFuture<'_, Result<HttpResponse, HttpError>> {
+        let response = {
+            let mut routes = lock(&self.routes);
+            routes
+                .iter_mut()
+                .find(|r| r.method == request.method && request.url.contains(&r.url_contains))
+                .and_then(|r| {
+                    if r.responses.len() > 1 {
+                        r.responses.pop_front()
+                    } else {
+                        r.responses.front().cloned()
+                    }
+                })
+        };
+        let key = format!("{} {}", request.method, request.url);
+        lock(&self.requests).push(request);
+        async move { response.ok_or(HttpError::Unmatched(key)) }.boxed()
+    }
+}
+
+fn lock<T>(m: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    match m.lock() {
+        Ok(g) => g,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn mock_replays_scripted_responses_then_repeats_the_last() {
+        let mock = MockTransport::new()
+            .on(HttpMethod::Get, "/status", 503, "busy")
+            .on(HttpMethod::Get, "/status", 200, "ok");
+
+        let statuses = [
+            mock.send(HttpRequest::get("https://x/status"))
+                .await
+                .unwrap(),
+            mock.send(HttpRequest::get("https://x/status"))
+                .await
+                .unwrap(),
+            mock.send(HttpRequest::get("https://x/status"))
+                .await
+                .unwrap(),
+        ]
+        .map(|r| r.status);
+        assert_eq!(statuses, [503, 200, 200]);
+        assert_eq!(mock.requests().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn mock_rejects_unmatched_requests() {
+        let mock = MockTransport::new().on(HttpMethod::Get, "/a", 200, "");
+        let err = mock
+            .send(HttpRequest::post("https://x/a"))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, HttpError::Unmatched(_)));
+    }
+
+    #[cfg(feature = "native")]
+    #[test]
+    fn from_config_rejects_bad_proxy_and_missing_ca_bundle() {
+        let config = HttpConfig {
+            proxy: Some("not a url".to_owned()),
+            ..Default::default()
+        };
+        assert!(matches!(
+            ReqwestTransport::from_config(&config, HttpService::DeepL),
+            Err(HttpError::InvalidProxy(..))
+        ));
+
+        let config = HttpConfig {
+            proxy: Some("socks5://127.0.0.1:1080".to_owned()),
+            ca_bundle: Some("/nonexistent/ca.pem".into()),
+            ..Default::default()
+        };
+        assert!(matches!(
+            ReqwestTransport::from_config(&config, HttpService::Twitch),
+            Err(HttpError::CaBundle(..))
+        ));
+    }
+
+    #[test]
+    fn json_body_sets_content_type_once() {
+        let request = HttpRequest::post("https://x")
+            .with_json(&serde_json::json!({ "a": 1 }))
+            .unwrap();
+        assert_eq!(request.header("content-type"), Some("application/json"));
+        assert_eq!(request.body.as_deref(), Some(&b"{\"a\":1}"[..]));
+    }
+}
@@ -1,4 +1,11 @@
+pub mod http;
 pub mod ring_buffer;
 pub mod retry;
 
 pub use retry::{is_http_retryable, retry_with_backoff, RetryConfig};
+pub use http::{
+    HttpError, HttpMethod, HttpRequest, HttpResponse, HttpTransport, MockTransport,
+    SharedTransport,
+};
+#[cfg(feature = "native")]
+pub use http::ReqwestTransport;
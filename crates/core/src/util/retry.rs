@@ -4,7 +4,6 @@
 //! particularly useful for network requests to external APIs.
 
 use std::time::Duration;
-use tokio::time::sleep;
 use tracing::{debug, warn};
 
 /// Configuration for retry behavior
@@ -73,13 +72,19 @@ where
             Err(e) => {
                 last_error = Some(e);
                 
-                if attempt < config.max_attempts && is_retryable(last_error.as_ref().unwrap()) {
+                // Without `native` there is no timer to back off with, so the
+                // first failure is returned rather than retried at once.
+                if attempt < config.max_attempts
+                    && cfg!(feature = "native")
+                    && is_retryable(last_error.as_ref().unwrap())
+                {
                     let delay = config.delay_for_attempt(attempt);
                     warn!(
                         "Operation failed on attempt {}/{}, retrying after {:?}",
                         attempt, config.max_attempts, delay
                     );
-                    sleep(delay).await;
+                    #[cfg(feature = "native")]
+                    tokio::time::sleep(delay).await;
                 } else {
                     break;
                 }
@@ -0,0 +1,614 @@
+//! Batch VOD processing
+//!
+//! [`VodJob`] runs a finished recording through the same decode → ASR →
+//! translate → TTS stages as the live pipeline, but without real-time pacing:
+//! segments are fetched and decoded ahead while the previous one is
+//! transcribed, and cues are timed by their position in the recording rather
+//! than by the clock. It writes a subtitle file in the spoken language, one in
+//! the target language when translating, and optionally a dubbed WAV track as
+//! long as the recording with each line spoken where it was said, ready to
+//! mux back onto the video, and a list of chapters.
+
+use crate::asr::{AsrBackend, AsrError};
+use crate::chapters::{youtube_timestamps, Chapterizer};
+use crate::config::TargetLang;
+use crate::decode::{AudioDecoder, DecodeError};
+use crate::error::{Classify, ErrorClass};
+use crate::ingest::{IngestError, Ingestor};
+use crate::playback::resample::{to_48k_mono, SAMPLE_RATE_HZ};
+use crate::subtitles::SrtWriter;
+use crate::translate::{TranslateError, Translator};
+use crate::tts::{TtsClient, TtsError, TtsRequest, VoiceId};
+use futures::StreamExt;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncSeekExt, AsyncWriteExt, BufWriter};
+
+/// Segments fetched and decoded ahead of the one being transcribed.
+const DECODE_AHEAD: usize = 4;
+/// How often progress is logged, in segments.
+const PROGRESS_EVERY: usize = 30;
+
+#[derive(thiserror::Error, Debug)]
+pub enum VodError {
+    #[error("failed to write output: {0}")]
+    Output(#[from] std::io::Error),
+
+    #[error(transparent)]
+    Ingest(#[from] IngestError),
+
+    #[error(transparent)]
+    Decode(#[from] DecodeError),
+
+    #[error(transparent)]
+    Asr(#[from] AsrError),
+
+    #[error(transparent)]
+    Translate(#[from] TranslateError),
+
+    #[error(transparent)]
+    Tts(#[from] TtsError),
+}
+
+pub struct VodJob<I, D, A, Tr, Ts, C> {
+    pub ingest: I,
+    pub decode: D,
+    pub asr: A,
+    /// Only the source subtitles are written when `None`.
+    pub translate: Option<Tr>,
+    /// Speaks the translations into a dubbed track; skipped when `None` or
+    /// when not translating.
+    pub tts: Option<Ts>,
+    pub voice: Option<VoiceId>,
+    /// Where each line is placed in the dubbed track.
+    pub dub_align: DubAlign,
+    pub target_lang: TargetLang,
+    /// Splits the transcript into chapters once it is complete; skipped
+    /// when `None`.
+    pub chapters: Option<C>,
+}
+
+/// Where a [`VodJob`] places each spoken line in the dubbed track
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DubAlign {
+    /// Where speech begins in the original, by the recognizer's timestamps,
+    /// or where its segment begins with a recognizer that reports none.
+    #[default]
+    Speech,
+    /// Where the segment it was heard in begins.
+    Segment,
+    /// Right after the line before, without silence in between. The track
+    /// is then shorter than the recording and not aligned with it.
+    Packed,
+}
+
+impl FromStr for DubAlign {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "speech" => Ok(Self::Speech),
+            "segment" => Ok(Self::Segment),
+            "packed" => Ok(Self::Packed),
+            other => Err(format!(
+                "unknown dub alignment '{other}' (expected speech, segment or packed)"
+            )),
+        }
+    }
+}
+
+/// What a [`VodJob`] produced.
+#[derive(Clone, Debug, Default, Serialize, PartialEq)]
+pub struct VodReport {
+    pub segments: usize,
+    /// Length of the recording processed.
+    pub audio_duration: Duration,
+    pub elapsed: Duration,
+    /// Lines transcribed.
+    pub cues: usize,
+    /// Segments or lines left out after an error.
+    pub skipped: usize,
+    pub chapters: usize,
+    pub files: Vec<PathBuf>,
+}
+
+impl VodReport {
+    /// Processing time divided by the recording's length.
+    pub fn real_time_factor(&self) -> Option<f64> {
+        (!self.audio_duration.is_zero())
+            .then(|| self.elapsed.as_secs_f64() / self.audio_duration.as_secs_f64())
+    }
+}
+
+impl<I, D, A, Tr, Ts, C> VodJob<I, D, A, Tr, Ts, C>
+where
+    I: Ingestor,
+    D: AudioDecoder,
+    A: AsrBackend,
+    Tr: Translator,
+    Ts: TtsClient,
+    C: Chapterizer,
+{
+    /// Processes the whole recording into `out_dir`: `source.srt`, plus
+    /// `<lang>.srt` and, with TTS, `dub-<lang>.wav` when translating, and
+    /// `chapters.txt` with a chapterizer. Errors a retry could fix skip the
+    /// segment or line; others end the job, apart from failing to find
+    /// chapters, which only leaves them out.
+    pub async fn run(&self, out_dir: &Path) -> Result<VodReport, VodError> {
+        let started = Instant::now();
+        tokio::fs::create_dir_all(out_dir).await?;
+        let lang = self.target_lang.as_str().to_ascii_lowercase();
+        let mut report = VodReport::default();
+
+        let source_path = out_dir.join("source.srt");
+        let mut source = SrtWriter::create(&source_path).await?;
+        report.files.push(source_path);
+        let mut target = match &self.translate {
+            Some(_) => {
+                let path = out_dir.join(format!("{lang}.srt"));
+                let writer = SrtWriter::create(&path).await?;
+                report.files.push(path);
+                Some(writer)
+            }
+            None => None,
+        };
+        let mut dub = match (&self.translate, &self.tts) {
+            (Some(_), Some(_)) => {
+                let path = out_dir.join(format!("dub-{lang}.wav"));
+                let track = DubTrack::create(&path).await?;
+                report.files.push(path);
+                Some(track)
+            }
+            _ => None,
+        };
+
+        let mut decoded = self
+            .ingest
+            .stream(DECODE_AHEAD)
+            .map(|item| async move {
+                let item = item?;
+                let (gap, expected) = (item.gap_before, item.approx_duration);
+                Ok::<_, IngestError>((gap, expected, self.decode.decode_segment(item).await))
+            })
+            .buffered(DECODE_AHEAD);
+
+        let mut lines = Vec::new();
+        let mut position = Duration::ZERO;
+        while let Some(next) = decoded.next().await {
+            let (gap, expected, pcm) = next?;
+            report.segments += 1;
+            if report.segments % PROGRESS_EVERY == 0 {
+                tracing::info!(
+                    position = %format_position(position),
+                    elapsed_secs = started.elapsed().as_secs(),
+                    "VOD progress"
+                );
+            }
+            position += gap;
+            let start = position;
+            let pcm = match skip_retryable(pcm, &mut report, "decode")? {
+                Some(pcm) => pcm,
+                None => {
+                    position += expected;
+                    continue;
+                }
+            };
+            position += pcm.duration_estimate;
+            let end = position;
+
+            let transcribed = self.asr.transcribe(pcm).await;
+            let segment = match skip_retryable(transcribed, &mut report, "ASR")? {
+                Some(segment) => segment,
+                None => continue,
+            };
+            let transcript = segment.text.trim();
+            if transcript.is_empty() {
+                continue;
+            }
+            source.write_timed_cue(start, end, transcript).await?;
+            report.cues += 1;
+            if self.chapters.is_some() {
+                lines.push((start, transcript.to_owned()));
+            }
+
+            let (Some(translate), Some(target)) = (&self.translate, target.as_mut()) else {
+                continue;
+            };
+            let translated = translate
+                .translate(transcript.to_owned(), self.target_lang.clone())
+                .await;
+            let translation = match skip_retryable(translated, &mut report, "translation")? {
+                Some(t) => t.text,
+                None => continue,
+            };
+            target.write_timed_cue(start, end, &translation).await?;
+
+            let (Some(tts), Some(dub)) = (&self.tts, dub.as_mut()) else {
+                continue;
+            };
+            let request = TtsRequest {
+                text: translation,
+                voice: self.voice.clone(),
+                prosody: None,
+                speaker: None,
+            };
+            let spoken = tts.synthesize(request).await;
+            if let Some(audio) = skip_retryable(spoken, &mut report, "TTS")? {
+                let mono = to_48k_mono(&audio.pcm_i16, audio.sample_rate_hz, audio.channels);
+                let at = match self.dub_align {
+                    DubAlign::Speech => {
+                        start + segment.speech_start.unwrap_or_default().min(end - start)
+                    }
+                    DubAlign::Segment => start,
+                    // Placed at the start, a clip follows the one before.
+                    DubAlign::Packed => Duration::ZERO,
+                };
+                dub.place(at, &mono).await?;
+            }
+        }
+
+        if let Some(dub) = dub {
+            let end = match self.dub_align {
+                DubAlign::Packed => Duration::ZERO,
+                _ => position,
+            };
+            dub.finish(end).await?;
+        }
+        if let Some(chapterizer) = &self.chapters {
+            // Titled in the subtitles' language.
+            let lang = self.translate.as_ref().map(|_| self.target_lang.clone());
+            match chapterizer.chapters(lines, lang).await {
+                Ok(chapters) => {
+                    let path = out_dir.join("chapters.txt");
+                    let list = youtube_timestamps(&chapters);
+                    tokio::fs::write(&path, &list).await?;
+                    report.chapters = list.lines().count();
+                    report.files.push(path);
+                }
+                Err(e) => tracing::warn!(error = %e, "failed to find chapters"),
+            }
+        }
+        report.audio_duration = position;
+        report.elapsed = started.elapsed();
+        Ok(report)
+    }
+}
+
+/// The value of `result`, `None` after an error worth skipping past, or the
+/// error itself when it would fail every later segment too.
+fn skip_retryable<T, E>(
+    result: Result<T, E>,
+    report: &mut VodReport,
+    stage: &str,
+) -> Result<Option<T>, VodError>
+where
+    E: Classify + std::fmt::Display + Into<VodError>,
+{
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(e) if e.class() == ErrorClass::Fatal => Err(e.into()),
+        Err(e) => {
+            tracing::warn!(stage, error = %e, "skipping a VOD segment");
+            report.skipped += 1;
+            Ok(None)
+        }
+    }
+}
+
+/// `H:MM:SS` into the recording.
+fn format_position(at: Duration) -> String {
+    let secs = at.as_secs();
+    format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+/// A 48 kHz mono 16-bit WAV file written front to back: silence up to each
+/// clip's place, the clip, and the sizes filled in at the end. A clip that
+/// would overlap the one before starts right after it instead.
+struct DubTrack {
+    out: BufWriter<tokio::fs::File>,
+    /// Samples written so far.
+    written: u64,
+}
+
+impl DubTrack {
+    async fn create(path: &Path) -> std::io::Result<Self> {
+        let mut out = BufWriter::new(tokio::fs::File::create(path).await?);
+        out.write_all(&wav_header(0)).await?;
+        Ok(Self { out, written: 0 })
+    }
+
+    async fn place(&mut self, at: Duration, clip: &[i16]) -> std::io::Result<()> {
+        self.pad_to(at).await?;
+        for sample in clip {
+            self.out.write_all(&sample.to_le_bytes()).await?;
+        }
+        self.written += clip.len() as u64;
+        Ok(())
+    }
+
+    /// Pads the track to `end`, the length of the recording, and completes
+    /// the header. Nothing is added if the track is already that long.
+    async fn finish(mut self, end: Duration) -> std::io::Result<()> {
+        self.pad_to(end).await?;
+        self.out.flush().await?;
+        let mut file = self.out.into_inner();
+        file.seek(std::io::SeekFrom::Start(0)).await?;
+        let data_len = u32::try_from(self.written * 2).unwrap_or(u32::MAX);
+        file.write_all(&wav_header(data_len)).await?;
+        file.flush().await
+    }
+
+    async fn pad_to(&mut self, at: Duration) -> std::io::Result<()> {
+        const SILENCE: [u8; 4096] = [0; 4096];
+        let target = (at.as_secs_f64() * f64::from(SAMPLE_RATE_HZ)) as u64;
+        let mut missing = target.saturating_sub(self.written) * 2;
+        while missing > 0 {
+            let n = missing.min(SILENCE.len() as u64) as usize;
+            self.out.write_all(&SILENCE[..n]).await?;
+            missing -= n as u64;
+        }
+        self.written = self.written.max(target);
+        Ok(())
+    }
+}
+
+fn wav_header(data_len: u32) -> [u8; 44] {
+    let mut header = [0u8; 44];
+    header[0..4].copy_from_slice(b"RIFF");
+    header[4..8].copy_from_slice(&data_len.saturating_add(36).to_le_bytes());
+    header[8..16].copy_from_slice(b"WAVEfmt ");
+    header[16..20].copy_from_slice(&16u32.to_le_bytes());
+    header[20..22].copy_from_slice(&1u16.to_le_bytes());
+    header[22..24].copy_from_slice(&1u16.to_le_bytes());
+    header[24..28].copy_from_slice(&SAMPLE_RATE_HZ.to_le_bytes());
+    header[28..32].copy_from_slice(&(SAMPLE_RATE_HZ * 2).to_le_bytes());
+    header[32..34].copy_from_slice(&2u16.to_le_bytes());
+    header[34..36].copy_from_slice(&16u16.to_le_bytes());
+    header[36..40].copy_from_slice(b"data");
+    header[40..44].copy_from_slice(&data_len.to_le_bytes());
+    header
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asr::TranscriptSegment;
+    use crate::chapters::Chapter;
+    use crate::decode::{PcmChunk, PcmFormat};
+    use crate::ingest::IngestItem;
+    use crate::summary::SummaryError;
+    use crate::translate::Translation;
+    use crate::tts::TtsAudio;
+    use bytes::Bytes;
+    use futures::future::BoxFuture;
+    use futures::FutureExt;
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::time::SystemTime;
+    use tokio::sync::mpsc::Sender;
+
+    /// Three 2 s segments; the second says nothing and the third follows a
+    /// 2 s gap.
+    #[derive(Clone)]
+    struct Segments;
+
+    impl Ingestor for Segments {
+        fn start(
+            &self,
+            tx: Sender<IngestItem>,
+        ) -> Pin<Box<dyn Future<Output = Result<(), IngestError>> + Send + 'static>> {
+            Box::pin(async move {
+                for (sequence, text, gap) in [(0, "hello chat", 0), (1, "", 0), (2, "bye", 2)] {
+                    let item = IngestItem {
+                        sequence,
+                        fetched_at: SystemTime::now(),
+                        url: url::Url::parse("https://cdn.example/vod.m3u8").unwrap(),
+                        approx_duration: Duration::from_secs(2),
+                        bytes: Bytes::from_static(text.as_bytes()),
+                        discontinuity: gap > 0,
+                        gap_before: Duration::from_secs(gap),
+                    };
+                    let _ = tx.send(item).await;
+                }
+                Ok(())
+            })
+        }
+    }
+
+    /// Decodes an item into 2 s of audio carrying its text as the first
+    /// sample's index into `TEXTS`.
+    struct Decode;
+
+    const TEXTS: [&str; 3] = ["", "hello chat", "bye"];
+
+    impl AudioDecoder for Decode {
+        fn decode_segment(&self, item: IngestItem) -> BoxFuture<'_, Result<PcmChunk, DecodeError>> {
+            let text = std::str::from_utf8(&item.bytes).unwrap();
+            let index = TEXTS.iter().position(|t| *t == text).unwrap();
+            let mut samples = vec![0.0; 32_000];
+            samples[0] = index as f32;
+            async move {
+                Ok(PcmChunk {
+                    sequence: item.sequence,
+                    started_at: item.fetched_at,
+                    fetched_at: item.fetched_at,
+                    format: PcmFormat::whisper_f32_mono_16khz(),
+                    samples,
+                    duration_estimate: Duration::from_secs(2),
+                    discontinuity: item.discontinuity,
+                })
+            }
+            .boxed()
+        }
+    }
+
+    /// Transcribes the text a chunk carries, said half a second in.
+    struct Asr;
+
+    impl AsrBackend for Asr {
+        fn transcribe(
+            &self,
+            audio: PcmChunk,
+        ) -> BoxFuture<'_, Result<TranscriptSegment, AsrError>> {
+            async move {
+                Ok(TranscriptSegment {
+                    text: TEXTS[audio.samples[0] as usize].to_owned(),
+                    audio_duration: audio.duration_estimate,
+                    confidence: None,
+                    words: Vec::new(),
+                    speech_start: Some(Duration::from_millis(500)),
+                })
+            }
+            .boxed()
+        }
+    }
+
+    struct Upper;
+
+    impl Translator for Upper {
+        fn translate(
+            &self,
+            text: String,
+            _target: TargetLang,
+        ) -> BoxFuture<'_, Result<Translation, TranslateError>> {
+            async move {
+                Ok(Translation {
+                    text: text.to_uppercase(),
+                    detected_source_lang: None,
+                })
+            }
+            .boxed()
+        }
+    }
+
+    /// Half a second of a constant tone at 24 kHz.
+    struct Tone;
+
+    impl TtsClient for Tone {
+        fn synthesize(&self, _request: TtsRequest) -> BoxFuture<'_, Result<TtsAudio, TtsError>> {
+            async move {
+                Ok(TtsAudio {
+                    sample_rate_hz: 24_000,
+                    channels: 1,
+                    pcm_i16: vec![1000; 12_000],
+                })
+            }
+            .boxed()
+        }
+    }
+
+    /// A chapter at every line, titled with the line and language.
+    struct EveryLine;
+
+    impl Chapterizer for EveryLine {
+        fn chapters(
+            &self,
+            transcript: Vec<(Duration, String)>,
+            lang: Option<TargetLang>,
+        ) -> BoxFuture<'_, Result<Vec<Chapter>, SummaryError>> {
+            let lang = lang.map(|l| l.0).unwrap_or_default();
+            let chapters = transcript
+                .into_iter()
+                .map(|(start, text)| Chapter {
+                    start,
+                    title: format!("{text} ({lang})"),
+                })
+                .collect();
+            async move { Ok(chapters) }.boxed()
+        }
+    }
+
+    #[tokio::test]
+    async fn writes_subtitles_and_a_dub_timed_by_position() {
+        let dir = std::env::temp_dir().join(format!("vod-{}", std::process::id()));
+        let job = VodJob {
+            ingest: Segments,
+            decode: Decode,
+            asr: Asr,
+            translate: Some(Upper),
+            tts: Some(Tone),
+            voice: None,
+            dub_align: DubAlign::Segment,
+            target_lang: TargetLang::new("PT-BR").unwrap(),
+            chapters: Some(EveryLine),
+        };
+
+        let report = job.run(&dir).await.unwrap();
+        let source = std::fs::read_to_string(dir.join("source.srt")).unwrap();
+        let target = std::fs::read_to_string(dir.join("pt-br.srt")).unwrap();
+        let dub = std::fs::read(dir.join("dub-pt-br.wav")).unwrap();
+        let chapters = std::fs::read_to_string(dir.join("chapters.txt")).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(report.segments, 3);
+        assert_eq!(report.cues, 2);
+        assert_eq!(report.audio_duration, Duration::from_secs(8));
+        assert_eq!(
+            source,
+            "1\n00:00:00,000 --> 00:00:02,000\nhello chat\n\n\
+             2\n00:00:06,000 --> 00:00:08,000\nbye\n\n"
+        );
+        assert!(target.contains("00:00:06,000 --> 00:00:08,000\nBYE\n"));
+        // The second line is too close to the first to be its own chapter.
+        assert_eq!(chapters, "0:00 hello chat (PT-BR)\n");
+        assert_eq!(report.chapters, 1);
+
+        // 8 s of 48 kHz audio, with the second line starting at 6 s.
+        let samples = wav_samples(&dub);
+        assert_eq!(&dub[..4], b"RIFF");
+        assert_eq!(
+            u32::from_le_bytes(dub[40..44].try_into().unwrap()),
+            8 * 96_000
+        );
+        assert_eq!(samples.len(), 8 * 48_000);
+        assert_eq!(samples[0], 1000);
+        assert_eq!(samples[24_000], 0);
+        assert_eq!(samples[6 * 48_000 - 1], 0);
+        assert_eq!(samples[6 * 48_000], 1000);
+    }
+
+    #[tokio::test]
+    async fn aligns_the_dub_with_speech_or_packs_it() {
+        let dub = |dub_align| async move {
+            let dir =
+                std::env::temp_dir().join(format!("vod-{}-{dub_align:?}", std::process::id()));
+            let job = VodJob {
+                ingest: Segments,
+                decode: Decode,
+                asr: Asr,
+                translate: Some(Upper),
+                tts: Some(Tone),
+                voice: None,
+                dub_align,
+                target_lang: TargetLang::new("de").unwrap(),
+                chapters: None::<EveryLine>,
+            };
+            job.run(&dir).await.unwrap();
+            let dub = std::fs::read(dir.join("dub-de.wav")).unwrap();
+            std::fs::remove_dir_all(&dir).unwrap();
+            wav_samples(&dub)
+        };
+
+        // Each line starts half a second into its segment, where it was said.
+        let aligned = dub(DubAlign::Speech).await;
+        assert_eq!(aligned.len(), 8 * 48_000);
+        assert_eq!(aligned[24_000 - 1], 0);
+        assert_eq!(aligned[24_000], 1000);
+        assert_eq!(aligned[48_000], 0);
+        assert_eq!(aligned[6 * 48_000 + 24_000 - 1], 0);
+        assert_eq!(aligned[6 * 48_000 + 24_000], 1000);
+
+        // Two half-second lines back to back.
+        let packed = dub(DubAlign::Packed).await;
+        assert_eq!(packed, vec![1000; 48_000]);
+    }
+
+    fn wav_samples(wav: &[u8]) -> Vec<i16> {
+        wav[44..]
+            .chunks_exact(2)
+            .map(|b| i16::from_le_bytes([b[0], b[1]]))
+            .collect()
+    }
+}
@@ -0,0 +1,152 @@
+//! Watched words
+//!
+//! Moderators wait for their name, viewers for a game or a giveaway. A
+//! [`WatchList`] spots such [`WatchWord`]s in what is said or in its
+//! translation; [`crate::status::PipelineStatus`] then highlights the caption
+//! and publishes a [`crate::status::PipelineEvent::KeywordMatched`] for
+//! frontends to ring, notify or show.
+
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// A word or phrase to watch for, or a `/regex/`; both match regardless of
+/// case.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum WatchWord {
+    /// Matches as a whole word or phrase.
+    Word(String),
+    /// Matches anywhere the pattern does.
+    Pattern(String),
+}
+
+impl FromStr for WatchWord {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Some(pattern) = s
+            .strip_prefix('/')
+            .and_then(|rest| rest.strip_suffix('/'))
+            .filter(|pattern| !pattern.is_empty())
+        {
+            compile(pattern).map_err(|e| format!("invalid watch pattern /{pattern}/: {e}"))?;
+            return Ok(Self::Pattern(pattern.to_owned()));
+        }
+        if s.is_empty() {
+            return Err("watch word must not be empty".to_owned());
+        }
+        Ok(Self::Word(s.to_owned()))
+    }
+}
+
+impl fmt::Display for WatchWord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Word(word) => f.write_str(word),
+            Self::Pattern(pattern) => write!(f, "/{pattern}/"),
+        }
+    }
+}
+
+impl TryFrom<String> for WatchWord {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<WatchWord> for String {
+    fn from(word: WatchWord) -> Self {
+        word.to_string()
+    }
+}
+
+fn compile(pattern: &str) -> Result<Regex, regex::Error> {
+    RegexBuilder::new(pattern).case_insensitive(true).build()
+}
+
+enum Matcher {
+    /// Lower-cased.
+    Word(String),
+    Pattern(Regex),
+}
+
+/// [`WatchWord`]s ready to be looked for; empty by default
+#[derive(Default)]
+pub struct WatchList {
+    words: Vec<(String, Matcher)>,
+}
+
+impl WatchList {
+    pub fn new(words: &[WatchWord]) -> Self {
+        let words = words
+            .iter()
+            .filter_map(|word| {
+                let matcher = match word {
+                    WatchWord::Word(w) => Matcher::Word(w.to_lowercase()),
+                    // Parsing already checked the pattern compiles.
+                    WatchWord::Pattern(p) => Matcher::Pattern(compile(p).ok()?),
+                };
+                Some((word.to_string(), matcher))
+            })
+            .collect();
+        Self { words }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    /// The first watch word found in `text`, as it was given.
+    pub fn find(&self, text: &str) -> Option<&str> {
+        if self.words.is_empty() {
+            return None;
+        }
+        let lower = text.to_lowercase();
+        self.words
+            .iter()
+            .find(|(_, matcher)| match matcher {
+                Matcher::Word(word) => contains_word(&lower, word),
+                Matcher::Pattern(regex) => regex.is_match(text),
+            })
+            .map(|(word, _)| word.as_str())
+    }
+}
+
+/// Whether `text` holds `word` with no letter or digit right before or
+/// after it.
+fn contains_word(text: &str, word: &str) -> bool {
+    text.match_indices(word).any(|(at, _)| {
+        let before = text[..at].chars().next_back();
+        let after = text[at + word.len()..].chars().next();
+        !before.is_some_and(char::is_alphanumeric) && !after.is_some_and(char::is_alphanumeric)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_words_and_patterns() {
+        let words: Vec<WatchWord> = ["Elden Ring", "@mod_anna", r"/give\s?away/"]
+            .iter()
+            .map(|w| w.parse().unwrap())
+            .collect();
+        assert_eq!(words[2], WatchWord::Pattern(r"give\s?away".to_owned()));
+        let list = WatchList::new(&words);
+
+        assert_eq!(list.find("we're back in elden ring!"), Some("Elden Ring"));
+        assert_eq!(list.find("thanks @MOD_ANNA"), Some("@mod_anna"));
+        assert_eq!(list.find("GIVEAWAYS at the end"), Some(r"/give\s?away/"));
+        assert_eq!(list.find("elden ringing in my ears"), None);
+        assert_eq!(WatchList::default().find("anything"), None);
+
+        assert!("/(/".parse::<WatchWord>().is_err());
+        assert!(" ".parse::<WatchWord>().is_err());
+    }
+}
@@ -0,0 +1,287 @@
+//! Webhook notifications
+//!
+//! A [`WebhookNotifier`] watches a session and posts an [`Alert`] to Discord,
+//! Slack or any JSON endpoint when something needs attention: the stream
+//! went live, speech fell back to the local voice, spend is nearing the
+//! budget, or a watched word was said or translated. Streamers and moderators learn of it
+//! without keeping the TUI or status page open.
+
+use crate::config::{Webhook, WebhookFormat};
+use crate::status::{PipelineEvent, PipelineStatus, StatusSnapshot};
+use crate::util::{HttpError, HttpRequest, ReqwestTransport, SharedTransport};
+use serde::Serialize;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+
+/// How often the session's state is checked for changes.
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// Share of the budget spent at which [`Alert::BudgetNearLimit`] is sent.
+const NEAR_BUDGET: f64 = 0.8;
+
+/// Something a webhook is notified of
+#[derive(Clone, Debug, Serialize, PartialEq)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Alert {
+    /// The stream started, or came back after going offline.
+    StreamLive {
+        title: Option<String>,
+        game: Option<String>,
+    },
+    /// The primary voice failed and the local fallback is speaking instead.
+    TtsDegraded,
+    /// Spend has passed [`NEAR_BUDGET`] of the budget, after which the
+    /// session stops.
+    BudgetNearLimit { spent_usd: f64, budget_usd: f64 },
+    /// A watched word was said, or turned up in the translation `text` when
+    /// `translated`.
+    KeywordSpoken {
+        keyword: String,
+        text: String,
+        translated: bool,
+    },
+}
+
+impl Alert {
+    /// One line describing the alert for people, about `channel`.
+    pub fn message(&self, channel: &str) -> String {
+        match self {
+            Self::StreamLive { title, game } => match (title, game) {
+                (Some(title), Some(game)) => format!("{channel} is live: {title} ({game})"),
+                (Some(title), None) => format!("{channel} is live: {title}"),
+                _ => format!("{channel} is live"),
+            },
+            Self::TtsDegraded => {
+                format!("{channel}: the voice failed; speaking with the local fallback voice")
+            }
+            Self::BudgetNearLimit {
+                spent_usd,
+                budget_usd,
+            } => format!(
+                "{channel}: ${spent_usd:.2} of the ${budget_usd:.2} budget spent; \
+                 the session stops when it runs out"
+            ),
+            Self::KeywordSpoken {
+                keyword,
+                text,
+                translated: false,
+            } => format!("{channel}: \"{keyword}\" was said: {text}"),
+            Self::KeywordSpoken { keyword, text, .. } => {
+                format!("{channel}: \"{keyword}\" was in the translation: {text}")
+            }
+        }
+    }
+}
+
+/// Posts [`Alert`]s about one channel's session to webhooks
+#[derive(Clone)]
+pub struct WebhookNotifier {
+    hooks: Vec<Webhook>,
+    channel: String,
+    transport: SharedTransport,
+}
+
+impl WebhookNotifier {
+    /// Notifies `hooks` about the session of `channel`.
+    pub fn new(hooks: Vec<Webhook>, channel: impl Into<String>) -> Self {
+        Self {
+            hooks,
+            channel: channel.into(),
+            transport: ReqwestTransport::default().shared(),
+        }
+    }
+
+    /// Sends requests through `transport` instead of the network, e.g. a
+    /// [`crate::util::MockTransport`] in tests.
+    pub fn with_transport(mut self, transport: SharedTransport) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    /// Posts `alert` to every webhook; failures are logged, as a missed
+    /// notification is no reason to stop the session.
+    pub async fn send(&self, alert: &Alert) {
+        for hook in &self.hooks {
+            if let Err(e) = self.post(hook, alert).await {
+                tracing::warn!(error = %e, format = ?hook.format, "webhook failed");
+            }
+        }
+    }
+
+    async fn post(&self, hook: &Webhook, alert: &Alert) -> Result<(), HttpError> {
+        let message = alert.message(&self.channel);
+        let body = match hook.format {
+            WebhookFormat::Discord => serde_json::json!({ "content": message }),
+            WebhookFormat::Slack => serde_json::json!({ "text": message }),
+            WebhookFormat::Json => serde_json::to_value(JsonPayload {
+                channel: &self.channel,
+                message,
+                alert,
+            })?,
+        };
+        let request = HttpRequest::post(hook.url.expose()).with_json(&body)?;
+        let response = self.transport.send(request).await?;
+        if !response.is_success() {
+            tracing::warn!(
+                status = response.status,
+                body = %response.text(),
+                "webhook refused the alert"
+            );
+        }
+        Ok(())
+    }
+
+    /// Sends alerts about the session `status` reports; runs until aborted.
+    pub async fn run(self, status: PipelineStatus) {
+        let mut events = status.subscribe();
+        let mut watch = Watch::default();
+        let mut ticks = tokio::time::interval(POLL_INTERVAL);
+        loop {
+            tokio::select! {
+                _ = ticks.tick() => {
+                    for alert in watch.observe(&status.snapshot()) {
+                        self.send(&alert).await;
+                    }
+                }
+                event = events.recv() => match event {
+                    Ok(PipelineEvent::KeywordMatched {
+                        keyword,
+                        text,
+                        translated,
+                        ..
+                    }) => {
+                        let alert = Alert::KeywordSpoken {
+                            keyword,
+                            text,
+                            translated,
+                        };
+                        self.send(&alert).await;
+                    }
+                    Ok(_) | Err(RecvError::Lagged(_)) => {}
+                    Err(RecvError::Closed) => return,
+                },
+            }
+        }
+    }
+}
+
+/// Body posted to [`WebhookFormat::Json`] endpoints
+#[derive(Serialize)]
+struct JsonPayload<'a> {
+    channel: &'a str,
+    message: String,
+    #[serde(flatten)]
+    alert: &'a Alert,
+}
+
+/// What the last snapshot showed, to alert on changes only
+#[derive(Default)]
+struct Watch {
+    online: bool,
+    degraded: bool,
+    near_budget: bool,
+}
+
+impl Watch {
+    fn observe(&mut self, snapshot: &StatusSnapshot) -> Vec<Alert> {
+        let mut alerts = Vec::new();
+        if snapshot.stream_online && !self.online {
+            let stream = snapshot.stream.as_ref();
+            alerts.push(Alert::StreamLive {
+                title: stream.map(|s| s.title.clone()).filter(|t| !t.is_empty()),
+                game: stream.map(|s| s.game.clone()).filter(|g| !g.is_empty()),
+            });
+        }
+        self.online = snapshot.stream_online;
+
+        if snapshot.tts_fallback_active && !self.degraded {
+            alerts.push(Alert::TtsDegraded);
+        }
+        self.degraded = snapshot.tts_fallback_active;
+
+        if let Some(cost) = &snapshot.cost {
+            if let Some(budget_usd) = cost.budget_usd {
+                let near = cost.spent_usd >= budget_usd * NEAR_BUDGET;
+                if near && !self.near_budget {
+                    alerts.push(Alert::BudgetNearLimit {
+                        spent_usd: cost.spent_usd,
+                        budget_usd,
+                    });
+                }
+                self.near_budget = near;
+            }
+        }
+        alerts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::{HttpMethod, MockTransport};
+
+    #[test]
+    fn alerts_on_changes_only() {
+        let status = PipelineStatus::new();
+        let mut watch = Watch::default();
+        assert!(watch.observe(&status.snapshot()).is_empty());
+
+        status.set_stream_online(true);
+        status.set_tts_fallback_active(true);
+        assert_eq!(
+            watch.observe(&status.snapshot()),
+            [
+                Alert::StreamLive {
+                    title: None,
+                    game: None
+                },
+                Alert::TtsDegraded
+            ]
+        );
+        assert!(watch.observe(&status.snapshot()).is_empty());
+
+        status.set_stream_online(false);
+        status.set_tts_fallback_active(false);
+        assert!(watch.observe(&status.snapshot()).is_empty());
+        status.set_stream_online(true);
+        assert_eq!(watch.observe(&status.snapshot()).len(), 1);
+    }
+
+    #[tokio::test]
+    async fn posts_in_the_format_of_each_webhook() {
+        let mock = MockTransport::new()
+            .on(HttpMethod::Post, "discord.com", 204, "")
+            .on(HttpMethod::Post, "localhost", 200, "");
+        let hooks = vec![
+            "https://discord.com/api/webhooks/1/token".parse().unwrap(),
+            "http://localhost:9000/hook".parse().unwrap(),
+        ];
+        let notifier =
+            WebhookNotifier::new(hooks, "streamer").with_transport(mock.clone().shared());
+
+        notifier
+            .send(&Alert::KeywordSpoken {
+                keyword: "giveaway".to_owned(),
+                text: "giveaway at the end".to_owned(),
+                translated: false,
+            })
+            .await;
+        let requests = mock.requests();
+        let body = |i: usize| -> serde_json::Value {
+            serde_json::from_slice(requests[i].body.as_ref().unwrap()).unwrap()
+        };
+        assert_eq!(
+            body(0)["content"],
+            "streamer: \"giveaway\" was said: giveaway at the end"
+        );
+        let json = body(1);
+        assert_eq!(json["event"], "keyword_spoken");
+        assert_eq!(json["channel"], "streamer");
+        assert_eq!(json["keyword"], "giveaway");
+        assert_eq!(json["text"], "giveaway at the end");
+        assert_eq!(json["translated"], false);
+        assert_eq!(
+            json["message"],
+            "streamer: \"giveaway\" was said: giveaway at the end"
+        );
+    }
+}
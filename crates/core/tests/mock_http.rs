@@ -0,0 +1,230 @@
+//! End-to-end pipeline run against mocked Twitch, DeepL and ElevenLabs
+//! endpoints. Decode and ASR are stubbed so no ffmpeg or Whisper model is needed.
+
+#![cfg(feature = "native")]
+
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use twitch_translator_core::asr::{AsrBackend, AsrError, TranscriptSegment};
+use twitch_translator_core::config::{
+    ApiKey, ApiKeys, InputSource, LatencyBudget, TargetLang, TwitchConfig,
+};
+use twitch_translator_core::control::ControlHandle;
+use twitch_translator_core::decode::{AudioDecoder, DecodeError, PcmChunk, PcmFormat};
+use twitch_translator_core::ingest::{IngestItem, TwitchHlsIngestor, TwitchIngestOptions};
+use twitch_translator_core::pipeline::{Pipeline, PipelineConfig};
+use twitch_translator_core::playback::{PlaybackError, PlaybackSink};
+use twitch_translator_core::status::{PipelineEvent, PipelineStatus};
+use twitch_translator_core::translate::DeepLTranslator;
+use twitch_translator_core::tts::{ElevenLabsTtsClient, TtsAudio};
+use twitch_translator_core::util::{HttpMethod, MockTransport};
+
+const MASTER: &str = "#EXTM3U\n\
+#EXT-X-STREAM-INF:BANDWIDTH=160000,CODECS=\"mp4a.40.2\"\n\
+audio_only.m3u8\n";
+
+const MEDIA: &str = "#EXTM3U\n\
+#EXT-X-VERSION:3\n\
+#EXT-X-TARGETDURATION:2\n\
+#EXT-X-MEDIA-SEQUENCE:0\n\
+#EXTINF:2.000,\n\
+seg0.ts\n";
+
+#[derive(Clone)]
+struct StubDecoder;
+
+impl AudioDecoder for StubDecoder {
+    fn decode_segment(&self, item: IngestItem) -> BoxFuture<'_, Result<PcmChunk, DecodeError>> {
+        async move {
+            Ok(PcmChunk {
+                sequence: item.sequence,
+                started_at: item.fetched_at,
+                fetched_at: item.fetched_at,
+                format: PcmFormat::whisper_f32_mono_16khz(),
+                samples: vec![0.0; 16_000],
+                duration_estimate: item.approx_duration,
+                discontinuity: item.discontinuity,
+            })
+        }
+        .boxed()
+    }
+}
+
+#[derive(Clone)]
+struct StubAsr;
+
+impl AsrBackend for StubAsr {
+    fn transcribe(&self, audio: PcmChunk) -> BoxFuture<'_, Result<TranscriptSegment, AsrError>> {
+        async move {
+            Ok(TranscriptSegment {
+                text: "hello chat".to_owned(),
+                audio_duration: audio.duration_estimate,
+                confidence: None,
+                words: Vec::new(),
+                speech_start: None,
+            })
+        }
+        .boxed()
+    }
+}
+
+/// A tenth of a second of silence as 16 kHz mono WAV, which the ElevenLabs
+/// client decodes like the MP3 the service sends.
+fn wav_clip() -> Vec<u8> {
+    let data_len = 3_200u32;
+    let mut wav = Vec::new();
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    wav.extend_from_slice(&1u16.to_le_bytes()); // mono
+    wav.extend_from_slice(&16_000u32.to_le_bytes());
+    wav.extend_from_slice(&32_000u32.to_le_bytes()); // bytes per second
+    wav.extend_from_slice(&2u16.to_le_bytes()); // bytes per frame
+    wav.extend_from_slice(&16u16.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_len.to_le_bytes());
+    wav.resize(wav.len() + data_len as usize, 0);
+    wav
+}
+
+#[derive(Clone, Default)]
+struct CaptureSink {
+    played: Arc<Mutex<Vec<TtsAudio>>>,
+}
+
+impl PlaybackSink for CaptureSink {
+    fn play(&self, audio: TtsAudio) -> BoxFuture<'_, Result<(), PlaybackError>> {
+        self.played.lock().unwrap().push(audio);
+        async { Ok(()) }.boxed()
+    }
+}
+
+fn config(target_lang: &TargetLang) -> PipelineConfig {
+    PipelineConfig {
+        latency: LatencyBudget::new(1500).unwrap(),
+        api_keys: ApiKeys {
+            deepl: Some(ApiKey::new("key").unwrap()),
+            elevenlabs: Some(ApiKey::new("key").unwrap()),
+            ..ApiKeys::default()
+        },
+        target_lang: target_lang.clone(),
+        post_asr: Default::default(),
+        pre_translate: Default::default(),
+        post_translate: Default::default(),
+        pre_tts: Default::default(),
+        asr_workers: 1,
+        skip_stale: false,
+        stage_deadlines: false,
+        dedup_window: None,
+        max_buffer_bytes: None,
+        non_speech_events: false,
+        music_policy: Default::default(),
+        pace_speech: false,
+        voice_match: None,
+    }
+}
+
+fn ingestor(mock: &MockTransport) -> TwitchHlsIngestor {
+    TwitchHlsIngestor::new(
+        TwitchConfig::default(),
+        InputSource::Url("https://cdn.example/live/index.m3u8".to_owned()),
+        TwitchIngestOptions::default(),
+    )
+    .unwrap()
+    .with_transport(mock.clone().shared())
+}
+
+#[tokio::test]
+async fn segment_flows_from_mock_twitch_to_playback() {
+    let mock = MockTransport::new()
+        .on(HttpMethod::Get, "/live/index.m3u8", 200, MASTER)
+        .on(HttpMethod::Get, "/live/audio_only.m3u8", 200, MEDIA)
+        .on(
+            HttpMethod::Get,
+            "/live/seg0.ts",
+            200,
+            &b"not really mpeg-ts"[..],
+        )
+        .on(
+            HttpMethod::Post,
+            "deepl.com/v2/translate",
+            200,
+            r#"{"translations":[{"detected_source_language":"EN","text":"olá chat"}]}"#,
+        )
+        .on(HttpMethod::Post, "/text-to-speech/", 200, wav_clip());
+
+    let ingest = ingestor(&mock);
+    let target_lang = TargetLang::new("pt-BR").unwrap();
+    let playback = CaptureSink::default();
+    let pipeline = Pipeline::new(
+        ingest,
+        StubDecoder,
+        StubAsr,
+        config(&target_lang),
+        PipelineStatus::new(),
+        ControlHandle::new(target_lang),
+    )
+    .with_translator(DeepLTranslator::new("key".to_owned()).with_transport(mock.clone().shared()))
+    .with_tts(ElevenLabsTtsClient::new("key".to_owned()).with_transport(mock.clone().shared()))
+    .with_playback(playback.clone());
+
+    // The HLS ingestor polls forever, so stop once the first clip is played.
+    let run = tokio::spawn(async move { pipeline.run().await });
+    tokio::time::timeout(Duration::from_secs(10), async {
+        while playback.played.lock().unwrap().is_empty() {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    })
+    .await
+    .expect("nothing reached playback");
+    run.abort();
+
+    let urls: Vec<String> = mock.requests().into_iter().map(|r| r.url).collect();
+    assert_eq!(urls[0], "https://cdn.example/live/index.m3u8");
+    assert!(urls.contains(&"https://cdn.example/live/seg0.ts".to_owned()));
+    let translate = mock
+        .requests()
+        .into_iter()
+        .find(|r| r.url.ends_with("/v2/translate"))
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&translate.body.unwrap()).unwrap();
+    assert_eq!(body["text"][0], "hello chat");
+}
+
+#[tokio::test]
+async fn transcription_only_pipeline_needs_no_other_stages() {
+    let mock = MockTransport::new()
+        .on(HttpMethod::Get, "/live/index.m3u8", 200, MASTER)
+        .on(HttpMethod::Get, "/live/audio_only.m3u8", 200, MEDIA)
+        .on(HttpMethod::Get, "/live/seg0.ts", 200, &b"not mpeg-ts"[..]);
+    let target_lang = TargetLang::new("pt-BR").unwrap();
+    let status = PipelineStatus::new();
+    let mut events = status.subscribe();
+    let pipeline = Pipeline::new(
+        ingestor(&mock),
+        StubDecoder,
+        StubAsr,
+        config(&target_lang),
+        status,
+        ControlHandle::new(target_lang),
+    );
+
+    let run = tokio::spawn(async move { pipeline.run().await });
+    let transcript = tokio::time::timeout(Duration::from_secs(10), async {
+        loop {
+            if let Ok(PipelineEvent::Transcript { text, .. }) = events.recv().await {
+                return text;
+            }
+        }
+    })
+    .await
+    .expect("no transcript published");
+    run.abort();
+
+    assert_eq!(transcript, "hello chat");
+    assert!(!mock.requests().iter().any(|r| r.url.contains("deepl")));
+}
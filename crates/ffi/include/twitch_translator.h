@@ -0,0 +1,76 @@
+/*
+ * twitch-translator C API
+ *
+ * Link against the twitch_translator cdylib or staticlib built from
+ * crates/ffi. Functions returning int give 0 on success and -1 on failure,
+ * with the reason in tt_last_error(). Callbacks run on the session's own
+ * threads: they must return quickly and must not call tt_session_start,
+ * tt_session_stop or tt_session_free.
+ */
+
+#ifndef TWITCH_TRANSLATOR_H
+#define TWITCH_TRANSLATOR_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct TtSession TtSession;
+
+/* One pipeline event, e.g. {"type":"translation","source_text":...,"text":...,"target_lang":...}. */
+typedef void (*TtEventFn)(void *user_data, const char *event_json);
+
+/* One speech clip of interleaved signed 16-bit samples, valid for the call only. */
+typedef void (*TtAudioFn)(void *user_data, const int16_t *pcm, size_t samples,
+                          uint32_t sample_rate_hz, uint16_t channels);
+
+/* The last error on this thread, or NULL; valid until the next failing call on this thread. */
+const char *tt_last_error(void);
+
+/*
+ * Creates a stopped session, or returns NULL. options_json is an object with
+ * "channel" or "url", and optionally "target_lang", "latency_ms",
+ * "deepl_api_key", "elevenlabs_api_key", "whisper_model", "piper_binary",
+ * "piper_model", "twitch_client_id", "twitch_oauth_token" and
+ * "output_device". Keys left out fall back to the CLI's environment variables.
+ * Without a DeepL key the session only transcribes: it sends transcript events
+ * and no audio.
+ */
+TtSession *tt_session_new(const char *options_json);
+
+/* Stops and frees the session; NULL is ignored. */
+void tt_session_free(TtSession *session);
+
+/* Calls callback with every pipeline event; NULL stops the calls. */
+int tt_session_set_event_callback(TtSession *session, TtEventFn callback, void *user_data);
+
+/*
+ * Hands each speech clip to callback instead of playing it, from the next
+ * start; NULL plays on the speakers again. Clips arrive as soon as they are
+ * synthesized, so the host queues and paces them.
+ */
+int tt_session_set_audio_callback(TtSession *session, TtAudioFn callback, void *user_data);
+
+/* Loads the models and starts translating, replacing a running pipeline. */
+int tt_session_start(TtSession *session);
+
+/* Stops translating; fails if nothing was running. */
+int tt_session_stop(TtSession *session);
+
+/* 1 while the pipeline runs, 0 once stopped or ended, -1 on error. */
+int tt_session_is_running(TtSession *session);
+
+/* Switches the target language from the next utterance, e.g. "DE". */
+int tt_session_set_language(TtSession *session, const char *lang);
+
+/* Pauses (non-zero) or resumes (zero) speech; transcripts and translations keep flowing. */
+int tt_session_set_paused(TtSession *session, int paused);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* TWITCH_TRANSLATOR_H */
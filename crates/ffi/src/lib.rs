@@ -0,0 +1,274 @@
+//! C ABI for embedding the translator in OBS plugins or C++/C# GUIs
+//!
+//! `include/twitch_translator.h` declares the API. Each `TtSession` owns its
+//! own async runtime, so hosts need no async support of their own. Functions
+//! returning `int` give 0 on success and -1 on failure, with the reason in
+//! `tt_last_error`. Callbacks run on the session's threads; they must return
+//! quickly and must not call `tt_session_start`, `tt_session_stop` or
+//! `tt_session_free`.
+
+#![deny(warnings)]
+
+mod options;
+mod session;
+
+use session::Session;
+use std::cell::RefCell;
+use std::ffi::{c_char, c_int, c_void, CStr, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr;
+use twitch_translator_core::config::{StdEnv, TargetLang};
+use twitch_translator_core::tts::TtsAudio;
+
+/// Opaque to C.
+pub struct TtSession(Session);
+
+pub type TtEventFn = unsafe extern "C" fn(user_data: *mut c_void, event_json: *const c_char);
+pub type TtAudioFn = unsafe extern "C" fn(
+    user_data: *mut c_void,
+    pcm: *const i16,
+    samples: usize,
+    sample_rate_hz: u32,
+    channels: u16,
+);
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: impl std::fmt::Display) {
+    let message = CString::new(message.to_string().replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|e| *e.borrow_mut() = Some(message));
+}
+
+/// Runs `f`, turning errors and panics into -1 and `tt_last_error`.
+fn guard(f: impl FnOnce() -> anyhow::Result<()>) -> c_int {
+    match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => 0,
+        Ok(Err(e)) => {
+            set_last_error(format!("{e:#}"));
+            -1
+        }
+        Err(_) => {
+            set_last_error("internal error (panic)");
+            -1
+        }
+    }
+}
+
+/// The host's opaque pointer, handed back to its callbacks.
+#[derive(Clone, Copy)]
+struct UserData(*mut c_void);
+
+// The host promises its callbacks can be called from any thread.
+unsafe impl Send for UserData {}
+unsafe impl Sync for UserData {}
+
+#[derive(Clone, Copy)]
+pub(crate) struct EventCallback {
+    func: TtEventFn,
+    user_data: UserData,
+}
+
+impl EventCallback {
+    pub(crate) fn call(&self, json: &str) {
+        let Ok(json) = CString::new(json) else {
+            return;
+        };
+        // SAFETY: the host registered `func` for `user_data`.
+        unsafe { (self.func)(self.user_data.0, json.as_ptr()) }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub(crate) struct AudioCallback {
+    func: TtAudioFn,
+    user_data: UserData,
+}
+
+impl AudioCallback {
+    pub(crate) fn call(&self, audio: &TtsAudio) {
+        // SAFETY: the host registered `func` for `user_data`; the samples
+        // outlive the call.
+        unsafe {
+            (self.func)(
+                self.user_data.0,
+                audio.pcm_i16.as_ptr(),
+                audio.pcm_i16.len(),
+                audio.sample_rate_hz,
+                audio.channels,
+            )
+        }
+    }
+}
+
+/// Reads a C string argument, failing on NULL or invalid UTF-8.
+///
+/// # Safety
+/// `s` must be NULL or point to a NUL-terminated string.
+unsafe fn arg<'a>(s: *const c_char, name: &str) -> anyhow::Result<&'a str> {
+    if s.is_null() {
+        anyhow::bail!("{name} must not be NULL");
+    }
+    CStr::from_ptr(s)
+        .to_str()
+        .map_err(|_| anyhow::anyhow!("{name} is not valid UTF-8"))
+}
+
+/// # Safety
+/// `session` must be NULL or a pointer from `tt_session_new` not yet freed.
+unsafe fn session<'a>(session: *mut TtSession) -> anyhow::Result<&'a mut Session> {
+    session
+        .as_mut()
+        .map(|s| &mut s.0)
+        .ok_or_else(|| anyhow::anyhow!("session must not be NULL"))
+}
+
+/// The last error on this thread, or NULL. Valid until the next call that
+/// fails on this thread.
+#[no_mangle]
+pub extern "C" fn tt_last_error() -> *const c_char {
+    LAST_ERROR.with(|e| e.borrow().as_ref().map_or(ptr::null(), |e| e.as_ptr()))
+}
+
+/// Creates a stopped session from a JSON options object; NULL on failure.
+///
+/// # Safety
+/// `options_json` must be NULL or a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn tt_session_new(options_json: *const c_char) -> *mut TtSession {
+    let mut created = None;
+    let result = guard(|| {
+        let options: options::SessionOptions =
+            serde_json::from_str(arg(options_json, "options_json")?)?;
+        let config = options.into_config(&StdEnv)?;
+        created = Some(Box::new(TtSession(Session::new(config)?)));
+        Ok(())
+    });
+    match created {
+        Some(session) if result == 0 => Box::into_raw(session),
+        _ => ptr::null_mut(),
+    }
+}
+
+/// Stops and frees `session`. NULL is ignored.
+///
+/// # Safety
+/// `session` must be NULL or a pointer from `tt_session_new` not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn tt_session_free(session: *mut TtSession) {
+    if !session.is_null() {
+        let _ = catch_unwind(AssertUnwindSafe(|| drop(Box::from_raw(session))));
+    }
+}
+
+/// Calls `callback` with every pipeline event as a JSON object; NULL stops
+/// the calls.
+///
+/// # Safety
+/// `session` must be a live session; `callback` must be safe to call from any
+/// thread with `user_data` until replaced or the session is freed.
+#[no_mangle]
+pub unsafe extern "C" fn tt_session_set_event_callback(
+    session: *mut TtSession,
+    callback: Option<TtEventFn>,
+    user_data: *mut c_void,
+) -> c_int {
+    guard(|| {
+        let callback = callback.map(|func| EventCallback {
+            func,
+            user_data: UserData(user_data),
+        });
+        self::session(session)?.set_event_callback(callback);
+        Ok(())
+    })
+}
+
+/// Hands each speech clip to `callback` instead of playing it, from the next
+/// start; NULL plays on the speakers again. Clips arrive as soon as they are
+/// synthesized, so the host queues and paces them.
+///
+/// # Safety
+/// As for `tt_session_set_event_callback`.
+#[no_mangle]
+pub unsafe extern "C" fn tt_session_set_audio_callback(
+    session: *mut TtSession,
+    callback: Option<TtAudioFn>,
+    user_data: *mut c_void,
+) -> c_int {
+    guard(|| {
+        let callback = callback.map(|func| AudioCallback {
+            func,
+            user_data: UserData(user_data),
+        });
+        self::session(session)?.set_audio_callback(callback);
+        Ok(())
+    })
+}
+
+/// Loads the models and starts translating, replacing a running pipeline.
+///
+/// # Safety
+/// `session` must be a live session.
+#[no_mangle]
+pub unsafe extern "C" fn tt_session_start(session: *mut TtSession) -> c_int {
+    guard(|| self::session(session)?.start())
+}
+
+/// Stops translating; fails if nothing was running.
+///
+/// # Safety
+/// `session` must be a live session.
+#[no_mangle]
+pub unsafe extern "C" fn tt_session_stop(session: *mut TtSession) -> c_int {
+    guard(|| {
+        if !self::session(session)?.stop() {
+            anyhow::bail!("no session is running");
+        }
+        Ok(())
+    })
+}
+
+/// 1 while the pipeline runs, 0 once stopped or ended, -1 on error.
+///
+/// # Safety
+/// `session` must be a live session.
+#[no_mangle]
+pub unsafe extern "C" fn tt_session_is_running(session: *mut TtSession) -> c_int {
+    let mut running = false;
+    match guard(|| {
+        running = self::session(session)?.is_running();
+        Ok(())
+    }) {
+        0 => c_int::from(running),
+        error => error,
+    }
+}
+
+/// Switches the target language from the next utterance.
+///
+/// # Safety
+/// `session` must be a live session; `lang` a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn tt_session_set_language(
+    session: *mut TtSession,
+    lang: *const c_char,
+) -> c_int {
+    guard(|| {
+        let lang = TargetLang::new(arg(lang, "lang")?)?;
+        self::session(session)?.set_language(lang);
+        Ok(())
+    })
+}
+
+/// Pauses (non-zero) or resumes (zero) speech; text keeps flowing.
+///
+/// # Safety
+/// `session` must be a live session.
+#[no_mangle]
+pub unsafe extern "C" fn tt_session_set_paused(session: *mut TtSession, paused: c_int) -> c_int {
+    guard(|| {
+        self::session(session)?.set_paused(paused != 0);
+        Ok(())
+    })
+}
@@ -0,0 +1,177 @@
+//! The JSON object `tt_session_new` takes
+//!
+//! Only the settings an embedder is likely to need; everything else keeps the
+//! CLI's defaults. Keys left out fall back to the same environment variables
+//! the CLI reads.
+
+use anyhow::Context;
+use serde::Deserialize;
+use std::time::SystemTime;
+use twitch_translator_core::config::{
+    resolve_api_key, resolve_optional_string, resolve_string_with_default, ApiKeys, AppConfig,
+    AsrConfig, Env, HttpConfig, InputSource, LatencyBudget, OutputConfig, PiperConfig, TargetLang,
+    TextConfig, TwitchConfig, DEFAULT_LATENCY_MS, DEFAULT_TARGET_LANG, ENV_DEEPL_API_KEY,
+    ENV_ELEVENLABS_API_KEY, ENV_PIPER_BINARY, ENV_PIPER_MODEL, ENV_TWITCH_CLIENT_ID,
+    ENV_TWITCH_OAUTH_TOKEN, ENV_WHISPER_MODEL,
+};
+use twitch_translator_core::translate::DEFAULT_CONTEXT_SENTENCES;
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SessionOptions {
+    pub channel: Option<String>,
+    pub url: Option<String>,
+    pub target_lang: String,
+    pub latency_ms: u64,
+    pub deepl_api_key: Option<String>,
+    pub elevenlabs_api_key: Option<String>,
+    /// Path to a ggml Whisper model; it must be multilingual (no `.en` in
+    /// its name) unless the stream is in English.
+    pub whisper_model: Option<String>,
+    pub piper_binary: Option<String>,
+    pub piper_model: Option<String>,
+    pub twitch_client_id: Option<String>,
+    pub twitch_oauth_token: Option<String>,
+    /// Speakers to play on when no audio callback is set.
+    pub output_device: Option<String>,
+}
+
+impl Default for SessionOptions {
+    fn default() -> Self {
+        Self {
+            channel: None,
+            url: None,
+            target_lang: DEFAULT_TARGET_LANG.to_owned(),
+            latency_ms: DEFAULT_LATENCY_MS,
+            deepl_api_key: None,
+            elevenlabs_api_key: None,
+            whisper_model: None,
+            piper_binary: None,
+            piper_model: None,
+            twitch_client_id: None,
+            twitch_oauth_token: None,
+            output_device: None,
+        }
+    }
+}
+
+impl SessionOptions {
+    pub fn into_config(self, env: &impl Env) -> anyhow::Result<AppConfig> {
+        let input = match (self.channel, self.url) {
+            (Some(c), None) if !c.trim().is_empty() => InputSource::Channel(c),
+            (None, Some(u)) if !u.trim().is_empty() => InputSource::Url(u),
+            _ => anyhow::bail!("exactly one of channel or url must be provided"),
+        };
+        let twitch_defaults = TwitchConfig::default();
+        let asr_defaults = AsrConfig::default();
+        let piper_defaults = PiperConfig::default();
+        let asr = AsrConfig {
+            model_path: resolve_string_with_default(
+                self.whisper_model,
+                ENV_WHISPER_MODEL,
+                env,
+                &asr_defaults.model_path,
+            ),
+            ..asr_defaults
+        };
+        asr.validate()?;
+        let piper = PiperConfig {
+            binary_path: resolve_string_with_default(
+                self.piper_binary,
+                ENV_PIPER_BINARY,
+                env,
+                &piper_defaults.binary_path,
+            ),
+            model_path: resolve_string_with_default(
+                self.piper_model,
+                ENV_PIPER_MODEL,
+                env,
+                &piper_defaults.model_path,
+            ),
+            speaker_id: None,
+        };
+        piper.validate()?;
+        Ok(AppConfig {
+            input,
+            target_lang: TargetLang::new(self.target_lang)?,
+            formality: Default::default(),
+            llm_translator: None,
+            context_sentences: DEFAULT_CONTEXT_SENTENCES,
+            translation_memory: None,
+            translation_style: Default::default(),
+            api_keys: ApiKeys {
+                deepl: resolve_api_key(self.deepl_api_key, ENV_DEEPL_API_KEY, env)?,
+                elevenlabs: resolve_api_key(self.elevenlabs_api_key, ENV_ELEVENLABS_API_KEY, env)?,
+                ..ApiKeys::default()
+            },
+            latency: LatencyBudget::new(self.latency_ms).context("invalid latency_ms")?,
+            stage_deadlines: false,
+            twitch: TwitchConfig {
+                client_id: resolve_string_with_default(
+                    self.twitch_client_id,
+                    ENV_TWITCH_CLIENT_ID,
+                    env,
+                    &twitch_defaults.client_id,
+                ),
+                oauth_token: resolve_optional_string(
+                    self.twitch_oauth_token,
+                    ENV_TWITCH_OAUTH_TOKEN,
+                    env,
+                ),
+                ..twitch_defaults
+            },
+            asr,
+            piper,
+            http: HttpConfig::default(),
+            output: OutputConfig {
+                device: self.output_device,
+                ..OutputConfig::default()
+            },
+            text: TextConfig::default(),
+            status_addr: None,
+            record_ingest: None,
+            state_dir: None,
+            max_buffer_mb: None,
+            live_config: None,
+            voice: None,
+            lang_voices: Default::default(),
+            pronunciation: Default::default(),
+            conversation: None,
+            cost: Default::default(),
+            start_time: SystemTime::now(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use twitch_translator_core::config::MapEnv;
+
+    #[test]
+    fn options_fill_in_defaults_and_environment() {
+        let options: SessionOptions =
+            serde_json::from_str(r#"{"channel": "some_channel", "target_lang": "DE"}"#).unwrap();
+        let env = MapEnv::default().with_var(ENV_DEEPL_API_KEY, "key");
+        let config = options.into_config(&env).unwrap();
+
+        assert_eq!(
+            config.input,
+            InputSource::Channel("some_channel".to_owned())
+        );
+        assert_eq!(config.target_lang.as_str(), "DE");
+        assert_eq!(config.latency.target_ms, DEFAULT_LATENCY_MS);
+        assert_eq!(config.api_keys.deepl.unwrap().expose(), "key");
+    }
+
+    #[test]
+    fn options_need_exactly_one_input() {
+        let both: SessionOptions =
+            serde_json::from_str(r#"{"channel": "a", "url": "https://b/c.m3u8"}"#).unwrap();
+        assert!(both.into_config(&MapEnv::default()).is_err());
+        assert!(SessionOptions::default()
+            .into_config(&MapEnv::default())
+            .is_err());
+        assert!(serde_json::from_str::<SessionOptions>(r#"{"chanel": "a"}"#).is_err());
+    }
+}
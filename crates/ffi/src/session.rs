@@ -0,0 +1,233 @@
+//! A translation session driven from C
+
+use crate::{AudioCallback, EventCallback};
+use anyhow::Context;
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use std::sync::Arc;
+use tokio::runtime::Runtime;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::task::JoinHandle;
+use twitch_translator_core::asr::WhisperAsrBackend;
+use twitch_translator_core::config::{AppConfig, HttpService, TargetLang};
+use twitch_translator_core::control::ControlHandle;
+use twitch_translator_core::decode::FfmpegAudioDecoder;
+use twitch_translator_core::ingest::{DynIngestor, TwitchHlsIngestor, TwitchIngestOptions};
+use twitch_translator_core::pipeline::{Pipeline, PipelineConfig};
+use twitch_translator_core::playback::{
+    AudioPlaybackSink, DynPlaybackSink, PlaybackError, PlaybackSink,
+};
+use twitch_translator_core::status::PipelineStatus;
+use twitch_translator_core::translate::{ContextWindow, DeepLTranslator, Translator};
+use twitch_translator_core::tts::{
+    ElevenLabsTtsClient, FallbackTtsClient, PiperTtsClient, TtsAudio, TtsClient,
+};
+use twitch_translator_core::util::{ReqwestTransport, SharedTransport};
+
+/// Owns the runtime the pipeline and callbacks run on.
+pub struct Session {
+    runtime: Runtime,
+    config: AppConfig,
+    status: PipelineStatus,
+    control: ControlHandle,
+    audio: Option<AudioCallback>,
+    events: Option<JoinHandle<()>>,
+    pipeline: Option<JoinHandle<()>>,
+}
+
+impl Session {
+    pub fn new(config: AppConfig) -> anyhow::Result<Self> {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .thread_name("twitch-translator")
+            .build()
+            .context("failed to start the async runtime")?;
+        let control = ControlHandle::new(config.target_lang.clone());
+        Ok(Self {
+            runtime,
+            config,
+            status: PipelineStatus::new(),
+            control,
+            audio: None,
+            events: None,
+            pipeline: None,
+        })
+    }
+
+    /// Calls `callback` with each pipeline event as JSON, replacing any
+    /// previous callback; `None` stops the calls.
+    pub fn set_event_callback(&mut self, callback: Option<EventCallback>) {
+        if let Some(task) = self.events.take() {
+            task.abort();
+        }
+        let Some(callback) = callback else {
+            return;
+        };
+        let mut events = self.status.subscribe();
+        self.events = Some(self.runtime.spawn(async move {
+            loop {
+                let event = match events.recv().await {
+                    Ok(event) => event,
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => return,
+                };
+                match serde_json::to_string(&event) {
+                    Ok(json) => callback.call(&json),
+                    Err(e) => tracing::warn!(error = %e, "failed to serialize event"),
+                }
+            }
+        }));
+    }
+
+    /// Hands speech to `callback` instead of the speakers from the next start.
+    pub fn set_audio_callback(&mut self, callback: Option<AudioCallback>) {
+        self.audio = callback;
+    }
+
+    /// Builds the pipeline and starts it in the background, replacing a
+    /// running one.
+    pub fn start(&mut self) -> anyhow::Result<()> {
+        self.stop();
+        let mut config = self.config.clone();
+        config.target_lang = self.control.target_lang();
+        let run = self.runtime.block_on(build(
+            config,
+            self.status.clone(),
+            self.control.clone(),
+            self.audio,
+        ))?;
+        let status = self.status.clone();
+        self.pipeline = Some(self.runtime.spawn(async move {
+            if let Err(e) = run.await {
+                tracing::error!(error = %e, "session ended with error");
+            }
+            status.set_stream_online(false);
+        }));
+        Ok(())
+    }
+
+    /// Stops the pipeline; returns whether one was running.
+    pub fn stop(&mut self) -> bool {
+        match self.pipeline.take() {
+            Some(task) => {
+                let running = !task.is_finished();
+                task.abort();
+                self.status.set_stream_online(false);
+                running
+            }
+            None => false,
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.pipeline.as_ref().is_some_and(|t| !t.is_finished())
+    }
+
+    pub fn set_language(&self, lang: TargetLang) {
+        self.control.set_target_lang(lang);
+    }
+
+    pub fn set_paused(&self, paused: bool) {
+        self.control.set_paused(paused);
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        self.stop();
+        if let Some(task) = self.events.take() {
+            task.abort();
+        }
+    }
+}
+
+/// Sets up every stage for `cfg` and returns the running pipeline.
+async fn build(
+    cfg: AppConfig,
+    status: PipelineStatus,
+    control: ControlHandle,
+    audio: Option<AudioCallback>,
+) -> anyhow::Result<BoxFuture<'static, anyhow::Result<()>>> {
+    let ingest = DynIngestor::new(Arc::new(
+        TwitchHlsIngestor::new(
+            cfg.twitch.clone(),
+            cfg.input.clone(),
+            TwitchIngestOptions::from_config(&cfg.twitch),
+        )?
+        .with_transport(transport(&cfg, HttpService::Twitch)?)
+        .with_status(status.clone()),
+    ));
+    let asr = WhisperAsrBackend::from_config(&cfg.asr)?.with_max_states(cfg.asr.workers);
+    let config = PipelineConfig::from_app(&cfg)?;
+    let pipeline = Pipeline::new(
+        ingest,
+        FfmpegAudioDecoder::default(),
+        asr,
+        config,
+        status.clone(),
+        control.clone(),
+    );
+    let Some(deepl_key) = cfg.api_keys.deepl.clone() else {
+        // Transcription only: neither the translator nor the speech stages run.
+        return Ok(run(pipeline));
+    };
+    let pipeline = pipeline.with_translator(
+        DeepLTranslator::new(deepl_key.expose().to_string())
+            .with_transport(transport(&cfg, HttpService::DeepL)?)
+            .with_formality(cfg.formality)
+            .with_context_window(ContextWindow::new(cfg.context_sentences)),
+    );
+    let playback = match audio {
+        Some(callback) => DynPlaybackSink::new(Arc::new(CallbackPlaybackSink(callback))),
+        None => {
+            let mut sink =
+                AudioPlaybackSink::new().context("failed to initialise audio playback")?;
+            if let Some(device) = &cfg.output.device {
+                sink = sink.with_output_device_name(device.clone());
+            }
+            DynPlaybackSink::new(Arc::new(sink))
+        }
+    };
+    let pipeline = pipeline.with_playback(playback);
+    let local = PiperTtsClient::from_config(&cfg.piper);
+
+    let Some(elevenlabs_key) = cfg.api_keys.elevenlabs.clone() else {
+        return Ok(run(pipeline.with_tts(local)));
+    };
+    let primary = ElevenLabsTtsClient::new(elevenlabs_key.expose().to_string())
+        .with_transport(transport(&cfg, HttpService::ElevenLabs)?);
+    let tts = FallbackTtsClient::new(primary, local)
+        .with_status(status.clone())
+        .with_control(control.clone());
+    Ok(run(pipeline.with_tts(tts)))
+}
+
+fn run<Tr, Ts, P>(
+    pipeline: Pipeline<DynIngestor, FfmpegAudioDecoder, WhisperAsrBackend, Tr, Ts, P>,
+) -> BoxFuture<'static, anyhow::Result<()>>
+where
+    Tr: Translator + Clone + 'static,
+    Ts: TtsClient + Clone + 'static,
+    P: PlaybackSink + Clone + 'static,
+{
+    async move {
+        pipeline.run().await?;
+        Ok(())
+    }
+    .boxed()
+}
+
+fn transport(cfg: &AppConfig, service: HttpService) -> anyhow::Result<SharedTransport> {
+    Ok(ReqwestTransport::from_config(&cfg.http, service)?.shared())
+}
+
+/// Hands each clip to the host, which queues and plays it; `play` returns
+/// as soon as the callback does.
+struct CallbackPlaybackSink(AudioCallback);
+
+impl PlaybackSink for CallbackPlaybackSink {
+    fn play(&self, audio: TtsAudio) -> BoxFuture<'_, Result<(), PlaybackError>> {
+        self.0.call(&audio);
+        futures::future::ready(Ok(())).boxed()
+    }
+}